@@ -0,0 +1,89 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use wasmer::*;
+
+// The memory is larger than the pages touched, like most guests.
+static WAT: &str = r#"(module
+    (memory 17)
+    (func (export "touch")
+       (i32.store (i32.const 70000) (i32.const 1))))"#;
+
+// Guests written in languages with a large runtime start with megabytes
+// of static data, of which an instance only touches a few pages.
+fn data_wat() -> String {
+    format!(
+        r#"(module
+    (memory 65)
+    (data (i32.const 0) "{}")
+    (func (export "touch")
+       (i32.store (i32.const 70000) (i32.const 1))))"#,
+        "a".repeat(4 << 20)
+    )
+}
+
+fn instantiate(engine: &Engine, module: &Module) {
+    let mut store = Store::new(engine.clone());
+    let instance = Instance::new(&mut store, module, &imports! {}).unwrap();
+    let touch: TypedFunction<(), ()> = instance
+        .exports
+        .get_typed_function(&store, "touch")
+        .unwrap();
+    touch.call(&mut store).unwrap();
+}
+
+#[cfg(unix)]
+fn pooled_engine(mut engine: Engine) -> Engine {
+    let base = BaseTunables::for_target(&Target::default());
+    let pool = vm::MemoryPool::new(
+        4,
+        base.static_memory_bound,
+        base.static_memory_offset_guard_size,
+    )
+    .unwrap();
+    engine.set_tunables(PoolingTunables::new(base, pool));
+    engine
+}
+
+fn run_instantiation(engine: Engine, compiler_name: &str, c: &mut Criterion) {
+    let module = Module::new(&engine, WAT).unwrap();
+    c.bench_function(&format!("instantiation {}", compiler_name), |b| {
+        b.iter(|| instantiate(&engine, &module))
+    });
+
+    #[cfg(unix)]
+    {
+        let engine = pooled_engine(engine);
+        let module = Module::new(&engine, WAT).unwrap();
+        c.bench_function(&format!("pooled instantiation {}", compiler_name), |b| {
+            b.iter(|| instantiate(&engine, &module))
+        });
+    }
+}
+
+fn run_data_instantiation(engine: Engine, compiler_name: &str, c: &mut Criterion) {
+    let module = Module::new(&engine, data_wat()).unwrap();
+    c.bench_function(
+        &format!("instantiation with data segments {}", compiler_name),
+        |b| b.iter(|| instantiate(&engine, &module)),
+    );
+}
+
+fn run_instantiation_benchmarks(c: &mut Criterion) {
+    #[cfg(feature = "cranelift")]
+    {
+        let engine: Engine = EngineBuilder::new(Cranelift::default()).engine().into();
+        run_instantiation(engine.clone(), "cranelift", c);
+        run_data_instantiation(engine, "cranelift", c);
+    }
+
+    #[cfg(feature = "singlepass")]
+    {
+        let engine: Engine = EngineBuilder::new(Singlepass::default()).engine().into();
+        run_instantiation(engine.clone(), "singlepass", c);
+        run_data_instantiation(engine, "singlepass", c);
+    }
+}
+
+criterion_group!(benches, run_instantiation_benchmarks);
+
+criterion_main!(benches);
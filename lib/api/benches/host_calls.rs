@@ -0,0 +1,69 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use wasmer::*;
+
+static WAT: &str = r#"(module
+    (import "env" "typed" (func $typed (param i32) (result i32)))
+    (import "env" "dynamic" (func $dynamic (param i32) (result i32)))
+    (func (export "nop") (param i32) (result i32) (local.get 0))
+    (func (export "sum") (param i32 i64 f32 f64) (result f64)
+        (f64.add
+            (f64.add (f64.convert_i32_s (local.get 0)) (f64.convert_i64_s (local.get 1)))
+            (f64.add (f64.promote_f32 (local.get 2)) (local.get 3))))
+    (func (export "call_typed") (param i32) (result i32)
+        (call $typed (local.get 0)))
+    (func (export "call_dynamic") (param i32) (result i32)
+        (call $dynamic (local.get 0))))"#;
+
+fn host_calls(c: &mut Criterion) {
+    let mut store = Store::default();
+    let module = Module::new(&store, WAT).unwrap();
+    let typed = Function::new_typed(&mut store, |n: i32| n + 1);
+    let dynamic = Function::new(
+        &mut store,
+        FunctionType::new(vec![Type::I32], vec![Type::I32]),
+        |args| Ok(vec![Value::I32(args[0].unwrap_i32() + 1)]),
+    );
+    let imports = imports! {
+        "env" => {
+            "typed" => typed,
+            "dynamic" => dynamic,
+        }
+    };
+    let instance = Instance::new(&mut store, &module, &imports).unwrap();
+
+    // Calls from the host into WebAssembly.
+    let nop: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "nop").unwrap();
+    c.bench_function("typed call into wasm", |b| {
+        b.iter(|| nop.call(&mut store, 1).unwrap())
+    });
+    let sum: TypedFunction<(i32, i64, f32, f64), f64> =
+        instance.exports.get_typed_function(&store, "sum").unwrap();
+    c.bench_function("typed call into wasm with four arguments", |b| {
+        b.iter(|| sum.call(&mut store, 1, 2, 3.0, 4.0).unwrap())
+    });
+    let nop = instance.exports.get_function("nop").unwrap().clone();
+    c.bench_function("untyped call into wasm", |b| {
+        b.iter(|| nop.call(&mut store, &[Value::I32(1)]).unwrap())
+    });
+
+    // Calls from WebAssembly into the host, measured on top of the call
+    // into WebAssembly above.
+    let call_typed: TypedFunction<i32, i32> = instance
+        .exports
+        .get_typed_function(&store, "call_typed")
+        .unwrap();
+    c.bench_function("wasm calling a typed host function", |b| {
+        b.iter(|| call_typed.call(&mut store, 1).unwrap())
+    });
+    let call_dynamic: TypedFunction<i32, i32> = instance
+        .exports
+        .get_typed_function(&store, "call_dynamic")
+        .unwrap();
+    c.bench_function("wasm calling a dynamic host function", |b| {
+        b.iter(|| call_dynamic.call(&mut store, 1).unwrap())
+    });
+}
+
+criterion_group!(benches, host_calls);
+criterion_main!(benches);
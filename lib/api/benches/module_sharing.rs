@@ -0,0 +1,74 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::thread;
+
+use wasmer::*;
+
+static WAT: &str = r#"(module
+    (memory 1)
+    (table 4 funcref)
+    (global $counter (mut i32) (i32.const 0))
+    (func $increment (export "increment") (result i32)
+        (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+        (global.get $counter))
+    (elem (i32.const 0) $increment)
+    (data (i32.const 0) "hello"))"#;
+
+const THREADS: usize = 4;
+const INSTANCES_PER_THREAD: usize = 16;
+
+/// Instantiates `module` on `THREADS` threads, sharing the compiled module.
+fn instantiate_shared(engine: &Engine, module: &Module) {
+    let workers = (0..THREADS)
+        .map(|_| {
+            let engine = engine.clone();
+            let module = module.clone();
+            thread::spawn(move || {
+                let mut store = Store::new(engine);
+                for _ in 0..INSTANCES_PER_THREAD {
+                    Instance::new(&mut store, &module, &imports! {}).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
+
+/// Instantiates the module on `THREADS` threads, deserializing it on
+/// each thread.
+fn instantiate_deserialized(engine: &Engine, serialized: &bytes::Bytes) {
+    let workers = (0..THREADS)
+        .map(|_| {
+            let engine = engine.clone();
+            let serialized = serialized.clone();
+            thread::spawn(move || {
+                let mut store = Store::new(engine);
+                let module = unsafe { Module::deserialize(&store, serialized).unwrap() };
+                for _ in 0..INSTANCES_PER_THREAD {
+                    Instance::new(&mut store, &module, &imports! {}).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
+
+fn module_sharing(c: &mut Criterion) {
+    let engine = Engine::default();
+    let module = Module::new(&engine, WAT).unwrap();
+    let serialized = module.serialize().unwrap();
+
+    c.bench_function("clone module", |b| b.iter(|| module.clone()));
+    c.bench_function("instantiate shared module on threads", |b| {
+        b.iter(|| instantiate_shared(&engine, &module))
+    });
+    c.bench_function("deserialize and instantiate module on threads", |b| {
+        b.iter(|| instantiate_deserialized(&engine, &serialized))
+    });
+}
+
+criterion_group!(benches, module_sharing);
+criterion_main!(benches);
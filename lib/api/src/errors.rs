@@ -31,6 +31,11 @@ pub enum LinkError {
     /// Insufficient resources available for linking.
     #[cfg_attr(feature = "std", error("Insufficient resources: {0}"))]
     Resource(String),
+
+    /// A definition would replace an existing one while shadowing isn't
+    /// allowed.
+    #[cfg_attr(feature = "std", error("{0:?}.{1:?} is already defined"))]
+    Shadowed(String, String),
 }
 
 /// An error while instantiating a module.
@@ -68,6 +73,80 @@ pub enum InstantiationError {
     DifferentArchOS,
 }
 
+/// An error returned by a host function, with a code and a message that
+/// the caller of the WebAssembly function can retrieve with
+/// [`RuntimeError::host_error`].
+///
+/// Host functions returning `Result<T, HostError>` can use `?` on any error
+/// type that implements `From<E> for HostError`, instead of building a
+/// [`RuntimeError`] from a string.
+///
+/// # Example
+/// ```
+/// # use wasmer::HostError;
+/// enum DbError {
+///     NotFound,
+///     Busy,
+/// }
+///
+/// impl From<DbError> for HostError {
+///     fn from(error: DbError) -> Self {
+///         match error {
+///             DbError::NotFound => HostError::new(1, "not found"),
+///             DbError::Busy => HostError::new(2, "busy"),
+///         }
+///     }
+/// }
+///
+/// fn lookup(key: i32) -> Result<i32, DbError> {
+///     Err(DbError::NotFound)
+/// }
+///
+/// // Can be passed to `Function::new_typed`.
+/// fn host_lookup(key: i32) -> Result<i32, HostError> {
+///     Ok(lookup(key)?)
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostError {
+    code: u32,
+    message: String,
+}
+
+impl HostError {
+    /// Creates a new `HostError` with the given `code` and `message`.
+    pub fn new<I: Into<String>>(code: u32, message: I) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// Returns the code of the error.
+    pub fn code(&self) -> u32 {
+        self.code
+    }
+
+    /// Returns the message of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for HostError {}
+
+impl From<HostError> for RuntimeError {
+    fn from(error: HostError) -> Self {
+        Self::user(Box::new(error))
+    }
+}
+
 /// A struct representing an aborted instruction execution, with a message
 /// indicating the cause.
 #[derive(Clone)]
@@ -171,6 +250,9 @@ impl RuntimeError {
 
     /// Returns a list of function frames in WebAssembly code that led to this
     /// trap happening.
+    ///
+    /// Each frame has the name of its function and its offset in the module,
+    /// and its line of source code if the module has DWARF sections.
     pub fn trace(&self) -> &[FrameInfo] {
         &self.inner.wasm_trace
     }
@@ -201,6 +283,12 @@ impl RuntimeError {
         self.inner.as_ref().source.downcast_ref::<T>()
     }
 
+    /// Returns the [`HostError`] returned by the host function that caused
+    /// this error, if any.
+    pub fn host_error(&self) -> Option<&HostError> {
+        self.downcast_ref::<HostError>()
+    }
+
     /// Returns true if the `RuntimeError` is the same as T
     pub fn is<T: std::error::Error + 'static>(&self) -> bool {
         self.inner.source.is::<T>()
@@ -242,6 +330,10 @@ impl fmt::Display for RuntimeError {
                 func_index,
                 frame.module_offset()
             )?;
+            if let Some(location) = frame.source_location() {
+                writeln!(f)?;
+                write!(f, "        at {}", location)?;
+            }
         }
         Ok(())
     }
@@ -32,6 +32,17 @@ impl ExternRef {
         self.0.downcast(store)
     }
 
+    /// Try to downcast to the given value, mutably.
+    ///
+    /// The value lives as long as the store, so host objects can be handed
+    /// to WebAssembly as opaque handles and modified when they come back.
+    pub fn downcast_mut<'a, T>(&self, store: &'a mut impl AsStoreMut) -> Option<&'a mut T>
+    where
+        T: Any + Send + Sync + 'static + Sized,
+    {
+        self.0.downcast_mut(store)
+    }
+
     pub(crate) fn vm_externref(&self) -> VMExternRef {
         self.0.vm_externref()
     }
@@ -13,6 +13,8 @@ use crate::Extern;
 use crate::MemoryAccessError;
 use crate::MemoryType;
 use std::mem::MaybeUninit;
+#[cfg(feature = "sys")]
+use std::time::Duration;
 use wasmer_types::{MemoryError, Pages};
 
 /// A WebAssembly `memory` instance.
@@ -172,6 +174,80 @@ impl Memory {
         self.0.duplicate_in_store(store, new_store).map(Self)
     }
 
+    /// Blocks the current thread until another thread notifies `offset`,
+    /// like `memory.atomic.wait32` does for WebAssembly threads.
+    ///
+    /// Returns `0` once notified, `1` without waiting if the 32-bit value at
+    /// `offset` isn't `expected`, and `2` if `timeout` elapses first.
+    ///
+    /// The memory must be shared. A host thread waits with its own `Store`,
+    /// holding a clone of the memory made with [`Memory::clone_in_store`],
+    /// since the store can't be used while the thread is blocked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{Memory, MemoryType, Store};
+    /// # use std::sync::atomic::Ordering;
+    /// # let mut store = Store::default();
+    /// let memory = Memory::new(&mut store, MemoryType::new(1, Some(1), true)).unwrap();
+    ///
+    /// let mut waiter_store = Store::default();
+    /// let waiter_memory = memory.clone_in_store(&store, &mut waiter_store).unwrap();
+    /// let waiter = std::thread::spawn(move || {
+    ///     waiter_memory
+    ///         .atomic_wait32(&mut waiter_store, 0, 0, None)
+    ///         .unwrap()
+    /// });
+    ///
+    /// // Publish a value, and wake the waiter up once it is waiting.
+    /// memory.view(&store).atomic_u32(0).unwrap().store(1, Ordering::SeqCst);
+    /// while memory.atomic_notify(&mut store, 0, 1).unwrap() == 0 {
+    ///     if waiter.is_finished() {
+    ///         break;
+    ///     }
+    /// }
+    /// assert!(waiter.join().unwrap() <= 1);
+    /// ```
+    #[cfg(feature = "sys")]
+    pub fn atomic_wait32(
+        &self,
+        store: &mut impl AsStoreMut,
+        offset: u64,
+        expected: u32,
+        timeout: Option<Duration>,
+    ) -> Result<u32, MemoryAccessError> {
+        self.0.atomic_wait32(store, offset, expected, timeout)
+    }
+
+    /// Blocks the current thread until another thread notifies `offset`,
+    /// like `memory.atomic.wait64` does for WebAssembly threads.
+    ///
+    /// See [`Memory::atomic_wait32`].
+    #[cfg(feature = "sys")]
+    pub fn atomic_wait64(
+        &self,
+        store: &mut impl AsStoreMut,
+        offset: u64,
+        expected: u64,
+        timeout: Option<Duration>,
+    ) -> Result<u32, MemoryAccessError> {
+        self.0.atomic_wait64(store, offset, expected, timeout)
+    }
+
+    /// Wakes up to `count` threads waiting on `offset`, whether they are
+    /// host threads in [`Memory::atomic_wait32`] or WebAssembly threads in
+    /// `memory.atomic.wait32`, and returns the number of threads woken up.
+    #[cfg(feature = "sys")]
+    pub fn atomic_notify(
+        &self,
+        store: &mut impl AsStoreMut,
+        offset: u64,
+        count: u32,
+    ) -> Result<u32, MemoryAccessError> {
+        self.0.atomic_notify(store, offset, count)
+    }
+
     /// To `VMExtern`.
     pub(crate) fn to_vm_extern(&self) -> VMExtern {
         self.0.to_vm_extern()
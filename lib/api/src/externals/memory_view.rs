@@ -2,6 +2,8 @@ use super::memory::{Memory, MemoryBuffer};
 use crate::store::AsStoreRef;
 use crate::MemoryAccessError;
 use std::mem::MaybeUninit;
+#[cfg(feature = "sys")]
+use std::sync::atomic::{AtomicU32, AtomicU64};
 use wasmer_types::Pages;
 
 #[cfg(feature = "js")]
@@ -39,6 +41,37 @@ impl<'a> MemoryView<'a> {
         self.0.data_size()
     }
 
+    /// Returns the 32-bit value at `offset` as an atomic.
+    ///
+    /// The host can then load, store and compare-and-swap values that are
+    /// shared with WebAssembly threads through a shared memory, with the
+    /// same guarantees as the `memory.atomic.*` instructions. `offset` must
+    /// be aligned to 4 bytes.
+    ///
+    /// ```
+    /// # use wasmer::{Memory, MemoryType, Store};
+    /// # use std::sync::atomic::Ordering;
+    /// # let mut store = Store::default();
+    /// let memory = Memory::new(&mut store, MemoryType::new(1, Some(1), true)).unwrap();
+    /// let view = memory.view(&store);
+    /// let head = view.atomic_u32(8).unwrap();
+    /// assert_eq!(head.compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst), Ok(0));
+    /// assert_eq!(head.fetch_add(1, Ordering::SeqCst), 1);
+    /// ```
+    #[cfg(feature = "sys")]
+    pub fn atomic_u32(&self, offset: u64) -> Result<&AtomicU32, MemoryAccessError> {
+        self.0.atomic_u32(offset)
+    }
+
+    /// Returns the 64-bit value at `offset` as an atomic. `offset` must be
+    /// aligned to 8 bytes.
+    ///
+    /// See [`MemoryView::atomic_u32`].
+    #[cfg(feature = "sys")]
+    pub fn atomic_u64(&self, offset: u64) -> Result<&AtomicU64, MemoryAccessError> {
+        self.0.atomic_u64(offset)
+    }
+
     /// Retrieve a slice of the memory contents.
     ///
     /// # Safety
@@ -160,16 +193,40 @@ impl<'a> MemoryView<'a> {
 
     /// Copies the memory to another new memory object
     pub fn copy_to_memory(&self, amount: u64, new_memory: &Self) -> Result<(), MemoryAccessError> {
-        let mut offset = 0;
+        self.copy_range_to_memory(0, new_memory, 0, amount)
+    }
+
+    /// Copies `len` bytes starting at `offset` to `dst_offset` in the
+    /// memory viewed by `dst`, which can be another memory of the same
+    /// instance.
+    ///
+    /// Nothing is copied if either range is out of bounds. The ranges
+    /// must not overlap when both views are of the same memory.
+    pub fn copy_range_to_memory(
+        &self,
+        offset: u64,
+        dst: &Self,
+        dst_offset: u64,
+        len: u64,
+    ) -> Result<(), MemoryAccessError> {
+        let end = offset.checked_add(len).ok_or(MemoryAccessError::Overflow)?;
+        let dst_end = dst_offset
+            .checked_add(len)
+            .ok_or(MemoryAccessError::Overflow)?;
+        if end > self.data_size() || dst_end > dst.data_size() {
+            return Err(MemoryAccessError::HeapOutOfBounds);
+        }
+
+        let mut copied = 0;
         let mut chunk = [0u8; 40960];
-        while offset < amount {
-            let remaining = amount - offset;
+        while copied < len {
+            let remaining = len - copied;
             let sublen = remaining.min(chunk.len() as u64) as usize;
-            self.read(offset, &mut chunk[..sublen])?;
+            self.read(offset + copied, &mut chunk[..sublen])?;
 
-            new_memory.write(offset, &chunk[..sublen])?;
+            dst.write(dst_offset + copied, &chunk[..sublen])?;
 
-            offset += sublen as u64;
+            copied += sublen as u64;
         }
         Ok(())
     }
@@ -83,6 +83,35 @@ impl Table {
         self.0.grow(store, delta, init)
     }
 
+    /// Sets the `len` elements of the `Table` starting at `index` to `val`,
+    /// like `table.fill` does.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the range is out of bounds of the table, in which
+    /// case no element is set.
+    pub fn fill(
+        &self,
+        store: &mut impl AsStoreMut,
+        index: u32,
+        val: Value,
+        len: u32,
+    ) -> Result<(), RuntimeError> {
+        match index.checked_add(len) {
+            Some(end) if end <= self.size(store) => {}
+            _ => return Err(RuntimeError::new("out of bounds table access")),
+        }
+        for i in index..index + len {
+            self.set(store, i, val.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Returns an iterator over the elements of the `Table`.
+    pub fn iter<'a>(&'a self, store: &'a mut impl AsStoreMut) -> impl Iterator<Item = Value> + 'a {
+        (0..self.size(store)).filter_map(move |index| self.get(store, index))
+    }
+
     /// Copies the `len` elements of `src_table` starting at `src_index`
     /// to the destination table `dst_table` at index `dst_index`.
     ///
@@ -122,6 +122,22 @@ impl<T: Send + 'static> FunctionEnvMut<'_, T> {
         self.func_env.as_mut(&mut self.store_mut)
     }
 
+    /// Returns the data attached to the store with [`Store::set_data`],
+    /// if it is a `D`.
+    ///
+    /// [`Store::set_data`]: crate::Store::set_data
+    pub fn store_data<D: 'static>(&self) -> Option<&D> {
+        self.store_mut.data()
+    }
+
+    /// Returns the data attached to the store with [`Store::set_data`]
+    /// mutably, if it is a `D`.
+    ///
+    /// [`Store::set_data`]: crate::Store::set_data
+    pub fn store_data_mut<D: 'static>(&mut self) -> Option<&mut D> {
+        self.store_mut.data_mut()
+    }
+
     /// Borrows a new immmutable reference
     pub fn as_ref(&self) -> FunctionEnv<T> {
         self.func_env.clone()
@@ -0,0 +1,208 @@
+//! The host registry module holds sets of host functions that are defined
+//! once and imported into many stores.
+use crate::externals::function::{HostFunction, WithEnv, WithoutEnv};
+use crate::store::{AsStoreMut, StoreMut};
+use crate::{
+    Function, FunctionEnv, FunctionEnvMut, FunctionType, Imports, RuntimeError, Value, WasmTypeList,
+};
+use std::fmt;
+use std::sync::Arc;
+
+/// Creates a host function in a store, given the environment shared by
+/// the functions of the registry in that store.
+type Factory = Arc<dyn Fn(&mut StoreMut, &FunctionEnv<()>) -> Function + Send + Sync>;
+
+/// A set of host functions, defined once and imported into any number of
+/// stores.
+///
+/// Host functions belong to a store, so building [`Imports`] for each new
+/// store usually means running the code that creates every closure again.
+/// A `HostRegistry` keeps the closures instead, and
+/// [`HostRegistry::imports`] only creates the functions of a store from
+/// them. Registries are cheap to clone and can be shared between threads.
+///
+/// Functions that need state of their own for each store can reach the
+/// data attached with [`Store::set_data`] through
+/// [`FunctionEnvMut::store_data_mut`].
+///
+/// # Usage
+/// ```
+/// use wasmer::{FunctionEnvMut, HostRegistry, Instance, Module, Store};
+/// # fn foo_test(module: Module) -> anyhow::Result<()> {
+///
+/// let mut registry = HostRegistry::new();
+/// registry.define_typed("env", "double", |n: i32| n * 2);
+/// registry.define_typed_with_env("env", "count", |mut env: FunctionEnvMut<()>| {
+///     *env.store_data_mut::<u32>().unwrap() += 1;
+/// });
+///
+/// for _ in 0..1000 {
+///     let mut store = Store::default();
+///     store.set_data(0u32);
+///     let imports = registry.imports(&mut store);
+///     let instance = Instance::new(&mut store, &module, &imports)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Store::set_data`]: crate::Store::set_data
+#[derive(Clone, Default)]
+pub struct HostRegistry {
+    functions: Vec<(String, String, Factory)>,
+}
+
+impl HostRegistry {
+    /// Create a new, empty `HostRegistry`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Defines a dynamic host function as the import `name` of module
+    /// `module`. See [`Function::new`].
+    pub fn define<FT, F>(&mut self, module: &str, name: &str, ty: FT, func: F) -> &mut Self
+    where
+        FT: Into<FunctionType>,
+        F: Fn(&[Value]) -> Result<Vec<Value>, RuntimeError> + 'static + Send + Sync,
+    {
+        let ty = ty.into();
+        let func = Arc::new(func);
+        self.insert(module, name, move |store, env| {
+            let func = func.clone();
+            Function::new_with_env(store, env, ty.clone(), move |_env, args| func(args))
+        })
+    }
+
+    /// Defines a typed host function as the import `name` of module
+    /// `module`. See [`Function::new_typed`].
+    ///
+    /// The function is cloned for each store, so its captured state is
+    /// best kept behind an [`Arc`].
+    pub fn define_typed<F, Args, Rets>(&mut self, module: &str, name: &str, func: F) -> &mut Self
+    where
+        F: HostFunction<(), Args, Rets, WithoutEnv> + Clone + 'static + Send + Sync,
+        Args: WasmTypeList,
+        Rets: WasmTypeList,
+    {
+        self.insert(module, name, move |store, _env| {
+            Function::new_typed(store, func.clone())
+        })
+    }
+
+    /// Defines a typed host function receiving a [`FunctionEnvMut`] as the
+    /// import `name` of module `module`. See
+    /// [`Function::new_typed_with_env`].
+    ///
+    /// The functions of the registry share one environment per store,
+    /// which holds no data. The data of the store is reachable through
+    /// [`FunctionEnvMut::store_data_mut`].
+    pub fn define_typed_with_env<F, Args, Rets>(
+        &mut self,
+        module: &str,
+        name: &str,
+        func: F,
+    ) -> &mut Self
+    where
+        F: HostFunction<(), Args, Rets, WithEnv> + Clone + 'static + Send + Sync,
+        Args: WasmTypeList,
+        Rets: WasmTypeList,
+    {
+        self.insert(module, name, move |store, env| {
+            Function::new_typed_with_env(store, env, func.clone())
+        })
+    }
+
+    fn insert(
+        &mut self,
+        module: &str,
+        name: &str,
+        factory: impl Fn(&mut StoreMut, &FunctionEnv<()>) -> Function + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.functions
+            .retain(|(m, n, _)| !(m == module && n == name));
+        self.functions
+            .push((module.to_string(), name.to_string(), Arc::new(factory)));
+        self
+    }
+
+    /// Creates the functions of this registry in `store`, and returns them
+    /// as [`Imports`].
+    ///
+    /// The imports can be used for any number of instances in `store`.
+    pub fn imports(&self, store: &mut impl AsStoreMut) -> Imports {
+        let mut store = store.as_store_mut();
+        let env = FunctionEnv::new(&mut store, ());
+        let mut imports = Imports::new();
+        for (module, name, factory) in &self.functions {
+            imports.define(module, name, factory(&mut store, &env));
+        }
+        imports
+    }
+
+    /// Returns the number of functions in this registry.
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    /// Returns whether this registry has no functions.
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+}
+
+impl fmt::Debug for HostRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(
+                self.functions
+                    .iter()
+                    .map(|(module, name, _)| format!("{}.{}", module, name)),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::store::Store;
+    use crate::value::Value;
+    use crate::{Extern, FunctionEnvMut, FunctionType, HostRegistry, Type};
+
+    #[test]
+    fn imports_per_store() {
+        let mut registry = HostRegistry::new();
+        registry
+            .define_typed("env", "double", |n: i32| n * 2)
+            .define_typed_with_env("env", "count", |mut env: FunctionEnvMut<()>| {
+                let count = env.store_data_mut::<u32>().unwrap();
+                *count += 1;
+                *count
+            })
+            .define(
+                "env",
+                "negate",
+                FunctionType::new(vec![Type::I32], vec![Type::I32]),
+                |args| Ok(vec![Value::I32(-args[0].unwrap_i32())]),
+            );
+        // Shadowed definitions are replaced.
+        registry.define_typed("env", "double", |n: i32| n + n);
+        assert_eq!(registry.len(), 3);
+
+        for _ in 0..2 {
+            let mut store = Store::default();
+            store.set_data(0u32);
+            let imports = registry.imports(&mut store);
+            let call = |store: &mut Store, name: &str, args: &[Value]| match imports
+                .get_export("env", name)
+            {
+                Some(Extern::Function(f)) => f.call(store, args).unwrap()[0].clone(),
+                _ => panic!("expected a function"),
+            };
+            assert_eq!(call(&mut store, "double", &[Value::I32(4)]), Value::I32(8));
+            assert_eq!(call(&mut store, "negate", &[Value::I32(4)]), Value::I32(-4));
+            // Each store has its own data.
+            assert_eq!(call(&mut store, "count", &[]), Value::I32(1));
+            assert_eq!(call(&mut store, "count", &[]), Value::I32(2));
+        }
+    }
+}
@@ -5,6 +5,10 @@ use std::fmt;
 
 use crate::imports::Imports;
 use crate::store::AsStoreMut;
+#[cfg(feature = "sys")]
+use crate::store::AsStoreRef;
+#[cfg(feature = "sys")]
+use crate::{DeserializeError, RuntimeError, SerializeError};
 
 #[cfg(feature = "js")]
 use crate::js::instance as instance_imp;
@@ -74,6 +78,58 @@ impl Instance {
         })
     }
 
+    #[cfg(feature = "sys")]
+    /// Creates a new `Instance` like [`Instance::new`], without running the
+    /// start function of the module.
+    ///
+    /// The tables and memories are initialized, so the exports can be
+    /// inspected or patched, for example to preinitialize a memory, before
+    /// any guest code runs. The start function then runs with
+    /// [`Instance::run_start`].
+    ///
+    /// ```
+    /// # use wasmer::{imports, Instance, Module, Store, Value};
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut store = Store::default();
+    /// let module = Module::new(&store, r#"
+    ///     (module
+    ///         (global $g (export "g") (mut i32) (i32.const 0))
+    ///         (func $start (global.set $g (i32.add (global.get $g) (i32.const 1))))
+    ///         (start $start))
+    /// "#)?;
+    /// let instance = Instance::new_without_start(&mut store, &module, &imports! {})?;
+    /// let g = instance.exports.get_global("g")?;
+    /// g.set(&mut store, Value::I32(41))?;
+    /// instance.run_start(&mut store)?;
+    /// assert_eq!(g.get(&mut store), Value::I32(42));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[allow(clippy::result_large_err)]
+    pub fn new_without_start(
+        store: &mut impl AsStoreMut,
+        module: &Module,
+        imports: &Imports,
+    ) -> Result<Self, InstantiationError> {
+        let (_inner, exports) =
+            instance_imp::Instance::new_with_start(store, module, imports, false)?;
+        Ok(Self {
+            _inner,
+            module: module.clone(),
+            exports,
+        })
+    }
+
+    #[cfg(feature = "sys")]
+    /// Runs the start function of an instance created with
+    /// [`Instance::new_without_start`].
+    ///
+    /// Does nothing if the module has no start function, or if it already
+    /// ran.
+    pub fn run_start(&self, store: &mut impl AsStoreMut) -> Result<(), RuntimeError> {
+        self._inner.run_start(store)
+    }
+
     /// Creates a new `Instance` from a WebAssembly [`Module`] and a
     /// vector of imports.
     ///
@@ -102,6 +158,37 @@ impl Instance {
     pub fn module(&self) -> &Module {
         &self.module
     }
+
+    #[cfg(feature = "sys")]
+    /// Captures the contents of the memories, globals and tables defined
+    /// by this instance.
+    ///
+    /// The snapshot can be serialized, and restored with
+    /// [`Instance::restore`] into a new instance of the same module, for
+    /// example to skip the initialization of a language runtime.
+    /// Imported memories, globals and tables aren't captured.
+    ///
+    /// Fails if a table or a global holds an external reference or a
+    /// function of another instance.
+    pub fn snapshot(
+        &self,
+        store: &impl AsStoreRef,
+    ) -> Result<crate::InstanceSnapshot, SerializeError> {
+        self._inner.snapshot(store)
+    }
+
+    #[cfg(feature = "sys")]
+    /// Restores a snapshot taken with [`Instance::snapshot`] from an
+    /// instance of the same module.
+    ///
+    /// Memories and tables are grown to their size in the snapshot.
+    pub fn restore(
+        &self,
+        store: &mut impl AsStoreMut,
+        snapshot: &crate::InstanceSnapshot,
+    ) -> Result<(), DeserializeError> {
+        self._inner.restore(store, snapshot)
+    }
 }
 
 impl fmt::Debug for Instance {
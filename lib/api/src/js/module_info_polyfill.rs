@@ -374,24 +374,8 @@ pub fn parse_import_section<'data>(
             TypeRef::Tag(_) => {
                 unimplemented!("exception handling not implemented yet")
             }
-            TypeRef::Memory(WPMemoryType {
-                shared,
-                memory64,
-                initial,
-                maximum,
-            }) => {
-                if memory64 {
-                    unimplemented!("64bit memory not implemented yet");
-                }
-                module_info.declare_memory_import(
-                    MemoryType {
-                        minimum: Pages(initial as u32),
-                        maximum: maximum.map(|p| Pages(p as u32)),
-                        shared,
-                    },
-                    module_name,
-                    field_name,
-                )?;
+            TypeRef::Memory(ty) => {
+                module_info.declare_memory_import(memory_type(ty)?, module_name, field_name)?;
             }
             TypeRef::Global(ref ty) => {
                 module_info.declare_global_import(
@@ -454,6 +438,29 @@ pub fn parse_table_section(
     Ok(())
 }
 
+/// Converts a memory type of `wasmparser`, refusing sizes that `Pages`
+/// can't represent.
+fn memory_type(ty: WPMemoryType) -> WasmResult<MemoryType> {
+    let WPMemoryType {
+        shared,
+        memory64,
+        initial,
+        maximum,
+    } = ty;
+    let pages = |count: u64| {
+        u32::try_from(count)
+            .map(Pages)
+            .map_err(|_| format!("memories of {} pages are not supported", count))
+    };
+    let minimum = pages(initial)?;
+    let maximum = maximum.map(pages).transpose()?;
+    if memory64 {
+        Ok(MemoryType::new64(minimum, maximum, shared))
+    } else {
+        Ok(MemoryType::new(minimum, maximum, shared))
+    }
+}
+
 /// Parses the Memory section of the wasm module.
 pub fn parse_memory_section(
     memories: MemorySectionReader,
@@ -462,20 +469,8 @@ pub fn parse_memory_section(
     module_info.reserve_memories(memories.get_count())?;
 
     for entry in memories {
-        let WPMemoryType {
-            shared,
-            memory64,
-            initial,
-            maximum,
-        } = entry.map_err(transform_err)?;
-        if memory64 {
-            unimplemented!("64bit memory not implemented yet");
-        }
-        module_info.declare_memory(MemoryType {
-            minimum: Pages(initial as u32),
-            maximum: maximum.map(|p| Pages(p as u32)),
-            shared,
-        })?;
+        let ty = entry.map_err(transform_err)?;
+        module_info.declare_memory(memory_type(ty)?)?;
     }
 
     Ok(())
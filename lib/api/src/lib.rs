@@ -433,9 +433,11 @@ mod exports;
 mod extern_ref;
 mod externals;
 mod function_env;
+mod host_registry;
 mod imports;
 mod instance;
 mod into_bytes;
+mod linker;
 mod mem_access;
 mod module;
 mod native_type;
@@ -466,20 +468,25 @@ pub use jsc::*;
 pub use crate::externals::{Extern, Function, Global, HostFunction, Memory, MemoryView, Table};
 pub use access::WasmSliceAccess;
 pub use engine::{AsEngineRef, Engine, EngineRef};
-pub use errors::{InstantiationError, LinkError, RuntimeError};
+pub use errors::{HostError, InstantiationError, LinkError, RuntimeError};
 pub use exports::{ExportError, Exportable, Exports, ExportsIterator};
 pub use extern_ref::ExternRef;
 pub use function_env::{FunctionEnv, FunctionEnvMut};
+pub use host_registry::HostRegistry;
 pub use imports::Imports;
 pub use instance::Instance;
 pub use into_bytes::IntoBytes;
+pub use linker::Linker;
 pub use mem_access::{MemoryAccessError, WasmRef, WasmSlice, WasmSliceIter};
 pub use module::{IoCompileError, Module};
 pub use native_type::{FromToNativeWasmType, NativeWasmTypeInto, WasmTypeList};
 pub use ptr::{Memory32, Memory64, MemorySize, WasmPtr, WasmPtr64};
 pub use store::{AsStoreMut, AsStoreRef, OnCalledHandler, Store, StoreId, StoreMut, StoreRef};
 #[cfg(feature = "sys")]
-pub use store::{TrapHandlerFn, Tunables};
+pub use store::{
+    CallHook, CallHookHandler, CoredumpSink, InterruptHandle, ResourceLimiter, TrapHandlerFn,
+    Tunables,
+};
 #[cfg(any(feature = "sys", feature = "jsc"))]
 pub use target_lexicon::{Architecture, CallingConvention, OperatingSystem, Triple, HOST};
 pub use typed_function::TypedFunction;
@@ -490,11 +497,12 @@ pub use value::Value;
 pub use wasmer_derive::ValueType;
 // TODO: OnCalledAction is needed for asyncify. It will be refactored with https://github.com/wasmerio/wasmer/issues/3451
 pub use wasmer_types::{
-    is_wasm, Bytes, CompileError, CpuFeature, DeserializeError, ExportIndex, ExportType,
-    ExternType, FrameInfo, FunctionType, GlobalInit, GlobalType, ImportType, LocalFunctionIndex,
-    MemoryError, MemoryType, MiddlewareError, Mutability, OnCalledAction, Pages,
-    ParseCpuFeatureError, SerializeError, TableType, Target, Type, ValueType, WasmError,
-    WasmResult, WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
+    is_wasm, ArtifactVariants, Bytes, CompileError, CpuFeature, DeserializeError, ExportIndex,
+    ExportType, ExternType, FrameInfo, FunctionType, GlobalInit, GlobalType, ImportType,
+    LocalFunctionIndex, MemoryError, MemoryIndex, MemoryType, MiddlewareError, Mutability,
+    OnCalledAction, Pages, ParseCpuFeatureError, Producers, ProducersField, SerializeError,
+    SourceLocation, StartFunction, TableType, Target, TargetFeature, TargetFeaturePrefix, Type,
+    ValueType, WasmError, WasmResult, WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
 };
 #[cfg(feature = "wat")]
 pub use wat::parse_bytes as wat2wasm;
@@ -0,0 +1,158 @@
+//! The linker module resolves the imports of modules by name, from host
+//! definitions and from the exports of other instances.
+use crate::store::AsStoreMut;
+use crate::{Extern, Imports, Instance, InstantiationError, LinkError, Module};
+
+/// Resolves the imports of modules by module and field name.
+///
+/// Unlike [`Imports`], a `Linker` refuses to replace a definition unless
+/// shadowing is allowed, and can link modules together: the exports of
+/// an instance registered under a name satisfy the imports of later
+/// modules from that name.
+///
+/// # Usage
+/// ```no_run
+/// use wasmer::{Function, Linker, Module, Store};
+/// # fn foo_test(mut store: &mut Store, a: Module, b: Module) -> anyhow::Result<()> {
+///
+/// let mut linker = Linker::new();
+/// linker.define("env", "double", Function::new_typed(&mut store, |n: i32| n * 2))?;
+/// // Instantiates `a`, and registers its exports under the name "a".
+/// linker.module(&mut store, "a", &a)?;
+/// // Imports of `b` from "a" are resolved with the exports of `a`.
+/// let instance = linker.instantiate(&mut store, &b)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct Linker {
+    imports: Imports,
+    allow_shadowing: bool,
+}
+
+impl Linker {
+    /// Create a new `Linker`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets whether a definition may replace an existing definition with
+    /// the same module and name. Shadowing is disallowed by default.
+    pub fn allow_shadowing(&mut self, allow: bool) -> &mut Self {
+        self.allow_shadowing = allow;
+        self
+    }
+
+    /// Defines `item` as the import `name` of module `module`.
+    ///
+    /// Fails with [`LinkError::Shadowed`] if the import is already
+    /// defined and shadowing isn't allowed.
+    #[allow(clippy::result_large_err)]
+    pub fn define(
+        &mut self,
+        module: &str,
+        name: &str,
+        item: impl Into<Extern>,
+    ) -> Result<&mut Self, LinkError> {
+        if !self.allow_shadowing && self.imports.exists(module, name) {
+            return Err(LinkError::Shadowed(module.to_string(), name.to_string()));
+        }
+        self.imports.define(module, name, item);
+        Ok(self)
+    }
+
+    /// Defines every export of `instance` as an import of module
+    /// `module`.
+    ///
+    /// Nothing is defined if one of the exports would be shadowed while
+    /// shadowing isn't allowed.
+    #[allow(clippy::result_large_err)]
+    pub fn define_instance(
+        &mut self,
+        module: &str,
+        instance: &Instance,
+    ) -> Result<&mut Self, LinkError> {
+        if !self.allow_shadowing {
+            if let Some((name, _)) = instance
+                .exports
+                .iter()
+                .find(|(name, _)| self.imports.exists(module, name))
+            {
+                return Err(LinkError::Shadowed(module.to_string(), name.clone()));
+            }
+        }
+        self.imports.register_namespace(
+            module,
+            instance
+                .exports
+                .iter()
+                .map(|(name, item)| (name.clone(), item.clone())),
+        );
+        Ok(self)
+    }
+
+    /// Instantiates `module` with its imports resolved by this linker,
+    /// and defines its exports as imports of module `name`.
+    #[allow(clippy::result_large_err)]
+    pub fn module(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        name: &str,
+        module: &Module,
+    ) -> Result<Instance, InstantiationError> {
+        let instance = self.instantiate(store, module)?;
+        self.define_instance(name, &instance)
+            .map_err(InstantiationError::Link)?;
+        Ok(instance)
+    }
+
+    /// Instantiates `module` with its imports resolved by this linker.
+    ///
+    /// Fails with [`LinkError::Import`] if an import isn't defined.
+    #[allow(clippy::result_large_err)]
+    pub fn instantiate(
+        &self,
+        store: &mut impl AsStoreMut,
+        module: &Module,
+    ) -> Result<Instance, InstantiationError> {
+        Instance::new(store, module, &self.imports)
+    }
+
+    /// Gets the definition of the import `name` of module `module`.
+    pub fn get(&self, module: &str, name: &str) -> Option<Extern> {
+        self.imports.get_export(module, name)
+    }
+
+    /// Returns the definitions of this linker as an [`Imports`].
+    pub fn imports(&self) -> &Imports {
+        &self.imports
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::store::Store;
+    use crate::value::Value;
+    use crate::{Extern, Global, LinkError, Linker};
+
+    #[test]
+    fn shadowing() {
+        let mut store = Store::default();
+        let g1 = Global::new(&mut store, Value::I32(1));
+        let g2 = Global::new(&mut store, Value::I32(2));
+
+        let mut linker = Linker::new();
+        linker.define("env", "g", g1).unwrap();
+        assert!(matches!(
+            linker.define("env", "g", g2.clone()),
+            Err(LinkError::Shadowed(module, name)) if module == "env" && name == "g"
+        ));
+
+        linker.allow_shadowing(true);
+        linker.define("env", "g", g2).unwrap();
+        match linker.get("env", "g") {
+            Some(Extern::Global(g)) => assert_eq!(g.get(&mut store).unwrap_i32(), 2),
+            _ => panic!("expected a global"),
+        }
+    }
+}
@@ -13,7 +13,7 @@ use thiserror::Error;
 use wasmer_types::ValueType;
 
 /// Error for invalid [`Memory`][super::Memory] access.
-#[derive(Clone, Copy, Debug, Error)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
 #[non_exhaustive]
 pub enum MemoryAccessError {
     /// Memory access is outside heap bounds.
@@ -25,6 +25,18 @@ pub enum MemoryAccessError {
     /// String is not valid UTF-8.
     #[error("string is not valid utf-8")]
     NonUtf8String,
+    /// Address is not aligned for the type being accessed.
+    #[error("address is not aligned")]
+    Misaligned,
+    /// No null terminator was found within the maximum length of a string.
+    #[error("string is not null-terminated")]
+    Unterminated,
+    /// Waiting on a memory that isn't shared.
+    #[error("memory is not shared")]
+    NotShared,
+    /// Too many threads are waiting on the memory.
+    #[error("too many waiters")]
+    TooManyWaiters,
 }
 
 impl From<MemoryAccessError> for RuntimeError {
@@ -216,6 +228,18 @@ impl<'a, T: ValueType> WasmSlice<'a, T> {
         }
     }
 
+    /// Get a `WasmRef` to an element in the slice.
+    ///
+    /// Returns a `MemoryAccessError` instead of panicking if `idx` is out of
+    /// the bounds of the slice.
+    #[inline]
+    pub fn try_index(self, idx: u64) -> Result<WasmRef<'a, T>, MemoryAccessError> {
+        if idx >= self.len {
+            return Err(MemoryAccessError::HeapOutOfBounds);
+        }
+        Ok(self.index(idx))
+    }
+
     /// Get a `WasmSlice` for a subslice of this slice.
     #[inline]
     pub fn subslice(self, range: Range<u64>) -> WasmSlice<'a, T> {
@@ -231,6 +255,18 @@ impl<'a, T: ValueType> WasmSlice<'a, T> {
         }
     }
 
+    /// Get a `WasmSlice` for a subslice of this slice.
+    ///
+    /// Returns a `MemoryAccessError` instead of panicking if `range` is out
+    /// of the bounds of the slice.
+    #[inline]
+    pub fn try_subslice(self, range: Range<u64>) -> Result<WasmSlice<'a, T>, MemoryAccessError> {
+        if range.start > range.end || range.end > self.len {
+            return Err(MemoryAccessError::HeapOutOfBounds);
+        }
+        Ok(self.subslice(range))
+    }
+
     /// Get an iterator over the elements in this slice.
     #[inline]
     pub fn iter(self) -> WasmSliceIter<'a, T> {
@@ -6,14 +6,19 @@ use std::path::Path;
 
 use crate::engine::AsEngineRef;
 use thiserror::Error;
+#[cfg(feature = "sys")]
+use wasmer_types::SerializeOptions;
 #[cfg(feature = "wat")]
 use wasmer_types::WasmError;
 use wasmer_types::{
-    CompileError, DeserializeError, ExportsIterator, ImportsIterator, ModuleInfo, SerializeError,
+    CompileError, DeserializeError, ExportsIterator, ImportsIterator, ModuleInfo, Producers,
+    SerializeError, StartFunction, TargetFeature,
 };
 use wasmer_types::{ExportType, ImportType};
 
 use crate::into_bytes::IntoBytes;
+#[cfg(feature = "compiler")]
+use crate::ValidationReport;
 
 #[cfg(feature = "js")]
 use crate::js::module as module_imp;
@@ -41,6 +46,22 @@ pub enum IoCompileError {
 ///
 /// Cloning a module is cheap: it does a shallow copy of the compiled
 /// contents rather than a deep copy.
+///
+/// ## Sharing a module between threads
+///
+/// With the `sys` backend, a module is `Send` and `Sync`. A server can
+/// compile (or deserialize) a module once, and clone it into worker
+/// threads that each instantiate it in their own [`Store`]. The stores
+/// must be created from clones of the [`Engine`](crate::Engine) the module
+/// was compiled with. Instantiating the same module concurrently is safe,
+/// and doesn't copy or relocate its code: all the instances run the
+/// same compiled functions, while their memories, tables and globals
+/// belong to their store.
+///
+/// Because the compiled contents are shared, [`Module::set_name`] fails
+/// once a module has been cloned.
+///
+/// [`Store`]: crate::Store
 #[derive(Clone, PartialEq, Eq)]
 pub struct Module(pub(crate) module_imp::Module);
 
@@ -176,6 +197,34 @@ impl Module {
         module_imp::Module::validate(engine, binary)
     }
 
+    /// Validates a WebAssembly module like [`Module::validate`], without
+    /// compiling it, and reports precisely why it is invalid.
+    ///
+    /// The report locates the error (its byte offset and, for errors in
+    /// function bodies, the function and the offending instruction), and
+    /// lists the features enabled in the engine as well as the features
+    /// the module requires. An `Err` is only returned when the engine
+    /// can't validate modules, like headless engines.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let report = Module::validate_detailed(&store, b"\0asm\x01\0\0\0")?;
+    /// assert!(report.is_valid());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "compiler")]
+    pub fn validate_detailed(
+        engine: &impl AsEngineRef,
+        binary: &[u8],
+    ) -> Result<ValidationReport, CompileError> {
+        module_imp::Module::validate_detailed(engine, binary)
+    }
+
     /// Serializes a module into a binary representation that the `Engine`
     /// can later process via [`Module::deserialize`].
     ///
@@ -184,6 +233,10 @@ impl Module {
     /// This function will return a custom binary format that will be different than
     /// the `wasm` binary format, but faster to load in Native hosts.
     ///
+    /// The output is reproducible: compiling the same module with the same
+    /// compiler, target and features always serializes to the same bytes, so
+    /// the result can be cached and audited by its content hash.
+    ///
     /// # Usage
     ///
     /// ```ignore
@@ -199,6 +252,29 @@ impl Module {
         self.0.serialize()
     }
 
+    /// Serializes a module like [`Module::serialize`], leaving out the
+    /// data that `options` asks to strip.
+    ///
+    /// Stripped artifacts are smaller and run the same way, but the
+    /// frames of their traps have less detail: no function names, source
+    /// locations or instruction offsets, depending on the options.
+    ///
+    /// # Usage
+    ///
+    /// ```ignore
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut store = Store::default();
+    /// # let module = Module::from_file(&store, "path/to/foo.wasm")?;
+    /// let serialized = module.serialize_with(&SerializeOptions::strip_all())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "sys")]
+    pub fn serialize_with(&self, options: &SerializeOptions) -> Result<Bytes, SerializeError> {
+        self.0.serialize_with(options)
+    }
+
     /// Serializes a module into a file that the `Engine`
     /// can later process via [`Module::deserialize_from_file`].
     ///
@@ -445,6 +521,91 @@ impl Module {
         self.0.custom_sections(name)
     }
 
+    /// Returns an iterator over all the custom sections of the module, as
+    /// `(name, contents)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut store = Store::default();
+    /// let wat = r#"(module (@custom "hello" "world"))"#;
+    /// let module = Module::new(&store, wat)?;
+    /// let sections = module.all_custom_sections().collect::<Vec<_>>();
+    /// assert_eq!(sections, vec![("hello", &b"world"[..])]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn all_custom_sections(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.0.info().all_custom_sections()
+    }
+
+    /// Returns the contents of the `producers` section of the module,
+    /// which lists the languages and tools that produced it.
+    ///
+    /// Returns `None` if the module has no such section, or if it is
+    /// malformed.
+    pub fn producers(&self) -> Option<Producers> {
+        Producers::parse(&self.custom_sections("producers").next()?)
+    }
+
+    /// Returns the contents of the `target_features` section of the
+    /// module, which lists the features it was compiled with.
+    ///
+    /// Returns `None` if the module has no such section, or if it is
+    /// malformed.
+    pub fn target_features(&self) -> Option<Vec<TargetFeature>> {
+        TargetFeature::parse_section(&self.custom_sections("target_features").next()?)
+    }
+
+    /// Returns the start function of the module, if it has one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut store = Store::default();
+    /// let wat = r#"(module (func $init) (start $init))"#;
+    /// let module = Module::new(&store, wat)?;
+    /// let start = module.start_function().unwrap();
+    /// assert_eq!(start.name.as_deref(), Some("init"));
+    /// assert_eq!(start.ty, FunctionType::new(vec![], vec![]));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn start_function(&self) -> Option<StartFunction> {
+        self.0.info().start_function_info()
+    }
+
+    /// Adds a custom section to the module, replacing the section with the
+    /// same name if there is one. The section is kept when the module is
+    /// serialized.
+    ///
+    /// It will return `true` if the section was added successfully, and
+    /// return `false` otherwise (in case the module is cloned or already
+    /// instantiated).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut store = Store::default();
+    /// let mut module = Module::new(&store, "(module)")?;
+    /// assert!(module.add_custom_section("build-id", &b"1234"[..]));
+    /// let serialized = module.serialize()?;
+    /// let module = unsafe { Module::deserialize(&store, serialized)? };
+    /// assert_eq!(&*module.custom_sections("build-id").next().unwrap(), b"1234");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "sys")]
+    pub fn add_custom_section(&mut self, name: &str, data: impl Into<Box<[u8]>>) -> bool {
+        self.0.add_custom_section(name, data.into())
+    }
+
     /// The ABI of the [`ModuleInfo`] is very unstable, we refactor it very often.
     /// This function is public because in some cases it can be useful to get some
     /// extra information from the module.
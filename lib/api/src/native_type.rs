@@ -731,6 +731,74 @@ macro_rules! impl_wasmtypelist {
     };
 }
 
+// Implement `WasmTypeList` on the 128-bit integers, which are lowered to
+// two `i64`: the low half, then the high half. They can be returned by
+// host functions and by typed functions, which then return two `i64` in
+// WebAssembly.
+macro_rules! impl_wasmtypelist_wide_int {
+    ( $( $type:ty ),* ) => {
+        $(
+            // `as u128` is a no-op for `u128` itself.
+            #[allow(trivial_numeric_casts)]
+            impl WasmTypeList for $type {
+                type CStruct = S2<i64, i64>;
+                type Array = [RawValue; 2];
+
+                fn size() -> u32 {
+                    2
+                }
+
+                unsafe fn from_array(_: &mut impl AsStoreMut, array: Self::Array) -> Self {
+                    let [low, high] = array;
+                    (((high.i64 as u64 as u128) << 64) | low.i64 as u64 as u128) as $type
+                }
+
+                unsafe fn from_slice(
+                    store: &mut impl AsStoreMut,
+                    slice: &[RawValue],
+                ) -> Result<Self, TryFromSliceError> {
+                    Ok(Self::from_array(store, slice.try_into()?))
+                }
+
+                unsafe fn into_array(self, _: &mut impl AsStoreMut) -> Self::Array {
+                    let S2(low, high) = split_wide_int(self as u128);
+                    [RawValue { i64: low }, RawValue { i64: high }]
+                }
+
+                fn empty_array() -> Self::Array {
+                    [RawValue { i64: 0 }; 2]
+                }
+
+                unsafe fn from_c_struct(store: &mut impl AsStoreMut, c_struct: Self::CStruct) -> Self {
+                    let S2(low, high) = c_struct;
+                    Self::from_array(store, [RawValue { i64: low }, RawValue { i64: high }])
+                }
+
+                unsafe fn into_c_struct(self, _: &mut impl AsStoreMut) -> Self::CStruct {
+                    split_wide_int(self as u128)
+                }
+
+                unsafe fn write_c_struct_to_ptr(c_struct: Self::CStruct, ptr: *mut RawValue) {
+                    let S2(low, high) = c_struct;
+                    *ptr.cast() = low;
+                    *ptr.add(1).cast() = high;
+                }
+
+                fn wasm_types() -> &'static [Type] {
+                    &[Type::I64, Type::I64]
+                }
+            }
+        )*
+    };
+}
+
+/// Splits a 128-bit integer into its low and high halves.
+fn split_wide_int(value: u128) -> S2<i64, i64> {
+    S2(value as u64 as i64, (value >> 64) as u64 as i64)
+}
+
+impl_wasmtypelist_wide_int!(i128, u128);
+
 // Black-magic to count the number of identifiers at compile-time.
 macro_rules! count_idents {
     ( $($idents:ident),* ) => {
@@ -201,6 +201,53 @@ impl<T: ValueType, M: MemorySize> WasmPtr<T, M> {
     ) -> Result<WasmRefAccess<'a, T>, MemoryAccessError> {
         self.deref(view).access()
     }
+
+    /// Creates a `WasmRef` from this `WasmPtr`, after checking that the value
+    /// is within the bounds of the memory and that the pointer is aligned
+    /// for `T`.
+    ///
+    /// [`WasmPtr::deref`] accepts any address and defers the bounds check to
+    /// each access. Checking up front lets host functions reject a bad
+    /// pointer from the guest once, with a typed error, and guarantees that
+    /// [`WasmRef::access`] hands out a properly aligned reference.
+    #[inline]
+    pub fn deref_checked<'a>(
+        &self,
+        view: &'a MemoryView,
+    ) -> Result<WasmRef<'a, T>, MemoryAccessError> {
+        self.check_range(view, 1)?;
+        Ok(self.deref(view))
+    }
+
+    /// Creates a `WasmSlice` of `len` values starting at this `WasmPtr`,
+    /// after checking that the whole slice is within the bounds of the memory
+    /// and that the pointer is aligned for `T`.
+    ///
+    /// See [`WasmPtr::deref_checked`].
+    #[inline]
+    pub fn slice_checked<'a>(
+        &self,
+        view: &'a MemoryView,
+        len: M::Offset,
+    ) -> Result<WasmSlice<'a, T>, MemoryAccessError> {
+        self.check_range(view, len.into())?;
+        self.slice(view, len)
+    }
+
+    fn check_range(&self, view: &MemoryView, len: u64) -> Result<(), MemoryAccessError> {
+        let offset = self.offset.into();
+        if offset % mem::align_of::<T>() as u64 != 0 {
+            return Err(MemoryAccessError::Misaligned);
+        }
+        let end = len
+            .checked_mul(mem::size_of::<T>() as u64)
+            .and_then(|len| offset.checked_add(len))
+            .ok_or(MemoryAccessError::Overflow)?;
+        if end > view.data_size() {
+            return Err(MemoryAccessError::HeapOutOfBounds);
+        }
+        Ok(())
+    }
 }
 
 impl<M: MemorySize> WasmPtr<u8, M> {
@@ -230,6 +277,55 @@ impl<M: MemorySize> WasmPtr<u8, M> {
         let vec = self.read_until(view, |&byte| byte == 0)?;
         Ok(String::from_utf8(vec)?)
     }
+
+    /// Creates a `WasmSlice` over the null-terminated string at this
+    /// `WasmPtr`, excluding the terminator.
+    ///
+    /// At most `max_len` bytes are searched for the terminator, so a guest
+    /// can't make the host scan the whole memory. Returns
+    /// `MemoryAccessError::Unterminated` if there is none within `max_len`
+    /// bytes, and `MemoryAccessError::HeapOutOfBounds` if the memory ends
+    /// first.
+    pub fn slice_with_nul<'a>(
+        &self,
+        view: &'a MemoryView,
+        max_len: M::Offset,
+    ) -> Result<WasmSlice<'a, u8>, MemoryAccessError> {
+        let offset = self.offset.into();
+        let max_len = max_len.into();
+        let mut chunk = [0u8; 256];
+        let mut len = 0;
+        while len < max_len {
+            let start = offset.checked_add(len).ok_or(MemoryAccessError::Overflow)?;
+            let available = view.data_size().saturating_sub(start);
+            let chunk_len = (max_len - len).min(chunk.len() as u64).min(available) as usize;
+            if chunk_len == 0 {
+                return Err(MemoryAccessError::HeapOutOfBounds);
+            }
+            let chunk = &mut chunk[..chunk_len];
+            view.read(start, chunk)?;
+            if let Some(nul) = chunk.iter().position(|&byte| byte == 0) {
+                return WasmSlice::new(view, offset, len + nul as u64);
+            }
+            len += chunk_len as u64;
+        }
+        Err(MemoryAccessError::Unterminated)
+    }
+
+    /// Reads a null-terminated UTF-8 string of at most `max_len` bytes from
+    /// the `WasmPtr`. See [`WasmPtr::slice_with_nul`].
+    ///
+    /// This method is safe to call even if the memory is being concurrently
+    /// modified.
+    #[inline]
+    pub fn read_utf8_string_with_nul_max(
+        &self,
+        view: &MemoryView,
+        max_len: M::Offset,
+    ) -> Result<String, MemoryAccessError> {
+        let vec = self.slice_with_nul(view, max_len)?.read_to_vec()?;
+        Ok(String::from_utf8(vec)?)
+    }
 }
 
 unsafe impl<T: ValueType, M: MemorySize> FromToNativeWasmType for WasmPtr<T, M>
@@ -1,16 +1,21 @@
 use crate::engine::{AsEngineRef, Engine, EngineRef};
+#[cfg(feature = "sys")]
+use crate::RuntimeError;
 use derivative::Derivative;
 use std::{
+    any::Any,
     fmt,
     ops::{Deref, DerefMut},
 };
 #[cfg(feature = "sys")]
 pub use wasmer_compiler::Tunables;
+#[cfg(feature = "sys")]
+use wasmer_types::FrameInfo;
 pub use wasmer_types::{OnCalledAction, StoreId};
 #[cfg(feature = "sys")]
 use wasmer_vm::init_traps;
 #[cfg(feature = "sys")]
-pub use wasmer_vm::TrapHandlerFn;
+pub use wasmer_vm::{InterruptHandle, ResourceLimiter, TrapHandlerFn};
 
 #[cfg(feature = "sys")]
 pub use wasmer_vm::{StoreHandle, StoreObjects};
@@ -27,6 +32,41 @@ pub type OnCalledHandler = Box<
     dyn FnOnce(StoreMut<'_>) -> Result<OnCalledAction, Box<dyn std::error::Error + Send + Sync>>,
 >;
 
+/// A transition between the host and WebAssembly, reported to the call
+/// hook of a store.
+#[cfg(feature = "sys")]
+#[derive(Debug, Clone)]
+pub enum CallHook {
+    /// The host is calling a function through [`Function::call`] or
+    /// [`TypedFunction::call`].
+    ///
+    /// Holds the module name, index and name of the function, unless it
+    /// isn't defined by a module.
+    ///
+    /// [`Function::call`]: crate::Function::call
+    /// [`TypedFunction::call`]: crate::TypedFunction::call
+    CallingWasm(Option<FrameInfo>),
+    /// A call made with [`CallHook::CallingWasm`] returned or trapped.
+    ReturningFromWasm,
+    /// WebAssembly is calling a host function.
+    CallingHost,
+    /// A host function called with [`CallHook::CallingHost`] is returning
+    /// to WebAssembly.
+    ReturningFromHost,
+}
+
+/// Call hook of a store.
+///
+/// An error returned by the hook becomes a trap of the call.
+#[cfg(feature = "sys")]
+pub type CallHookHandler =
+    Box<dyn FnMut(StoreMut<'_>, CallHook) -> Result<(), RuntimeError> + Send + Sync>;
+
+/// Receives the coredumps written when WebAssembly traps, see
+/// [`Store::set_coredump_sink`].
+#[cfg(feature = "sys")]
+pub type CoredumpSink = Box<dyn FnMut(&[u8]) + Send + Sync>;
+
 /// We require the context to have a fixed memory address for its lifetime since
 /// various bits of the VM have raw pointers that point back to it. Hence we
 /// wrap the actual context in a box.
@@ -41,6 +81,16 @@ pub(crate) struct StoreInner {
     pub(crate) trap_handler: Option<Box<TrapHandlerFn<'static>>>,
     #[derivative(Debug = "ignore")]
     pub(crate) on_called: Option<OnCalledHandler>,
+    #[derivative(Debug = "ignore")]
+    pub(crate) data: Option<Box<dyn Any + Send + Sync>>,
+    #[cfg(feature = "sys")]
+    #[derivative(Debug = "ignore")]
+    pub(crate) call_hook: Option<CallHookHandler>,
+    #[cfg(feature = "sys")]
+    #[derivative(Debug = "ignore")]
+    pub(crate) coredump_sink: Option<CoredumpSink>,
+    #[cfg(feature = "sys")]
+    pub(crate) stack_size: Option<usize>,
 }
 
 /// The store represents all global state that can be manipulated by
@@ -71,6 +121,13 @@ impl Store {
                 #[cfg(feature = "sys")]
                 trap_handler: None,
                 on_called: None,
+                data: None,
+                #[cfg(feature = "sys")]
+                call_hook: None,
+                #[cfg(feature = "sys")]
+                coredump_sink: None,
+                #[cfg(feature = "sys")]
+                stack_size: None,
             }),
         }
     }
@@ -90,6 +147,99 @@ impl Store {
         self.inner.trap_handler = handler;
     }
 
+    #[cfg(feature = "sys")]
+    /// Set the hook invoked on every transition between the host and
+    /// WebAssembly, for example to implement reentrancy guards, per-call
+    /// deadlines or audit trails.
+    ///
+    /// The hook isn't invoked for the calls it makes itself.
+    pub fn call_hook(
+        &mut self,
+        hook: impl FnMut(StoreMut<'_>, CallHook) -> Result<(), RuntimeError> + Send + Sync + 'static,
+    ) {
+        self.inner.call_hook = Some(Box::new(hook));
+    }
+
+    #[cfg(feature = "sys")]
+    /// Remove the call hook of this store.
+    pub fn remove_call_hook(&mut self) {
+        self.inner.call_hook = None;
+    }
+
+    #[cfg(feature = "sys")]
+    /// Set the sink receiving a coredump of this store whenever a call
+    /// into WebAssembly traps.
+    ///
+    /// Coredumps are written in the [wasm-coredump] format, with the
+    /// memories and globals of the store and the frames of the trap, so
+    /// that crashes can be debugged after the fact. Errors returned by
+    /// host functions don't produce a coredump.
+    ///
+    /// [wasm-coredump]: https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md
+    pub fn set_coredump_sink(&mut self, sink: Option<CoredumpSink>) {
+        self.inner.coredump_sink = sink;
+    }
+
+    #[cfg(feature = "sys")]
+    /// Set the size in bytes of the stack that WebAssembly runs on when
+    /// called from this store, or `None` for the process-wide default of
+    /// [`wasmer_vm::set_stack_size`].
+    ///
+    /// Calls that run out of stack trap with `TrapCode::StackOverflow`.
+    /// Hosts of deeply recursive guests can raise the limit, and hosts of
+    /// many small guests lower it. The size is clamped between 8 KiB and
+    /// 100 MiB.
+    pub fn set_stack_size(&mut self, size: Option<usize>) {
+        self.inner.stack_size = size;
+    }
+
+    #[cfg(feature = "sys")]
+    /// Set the limiter deciding whether the memories and tables of this
+    /// store may grow.
+    pub fn set_resource_limiter(&mut self, limiter: Option<Box<dyn ResourceLimiter>>) {
+        self.inner.objects.set_resource_limiter(limiter);
+    }
+
+    #[cfg(feature = "sys")]
+    /// Returns the handle interrupting the WebAssembly code running in this
+    /// store.
+    ///
+    /// Only modules compiled with the `Interrupt` middleware of
+    /// `wasmer-middlewares` check for interruptions. The handle can be
+    /// sent to other threads, and triggered from a signal handler.
+    pub fn interrupt_handle(&mut self) -> InterruptHandle {
+        self.inner.objects.interrupt_handle()
+    }
+
+    #[cfg(feature = "sys")]
+    /// Set the handle interrupting the WebAssembly code running in this
+    /// store, for example one created with [`InterruptHandle::from_raw`] in
+    /// memory shared with a supervisor process.
+    ///
+    /// Instances keep the handle that was set when they were created.
+    pub fn set_interrupt_handle(&mut self, handle: InterruptHandle) {
+        self.inner.objects.set_interrupt_handle(handle);
+    }
+
+    #[cfg(feature = "sys")]
+    /// Returns the fuel left for the WebAssembly code running in this
+    /// store.
+    ///
+    /// Only modules compiled with the `Fuel` middleware of
+    /// `wasmer-middlewares` consume fuel, and trap with
+    /// `TrapCode::OutOfFuel` when it runs out. A store starts without
+    /// fuel.
+    pub fn fuel_remaining(&self) -> u64 {
+        self.inner.objects.fuel_remaining()
+    }
+
+    #[cfg(feature = "sys")]
+    /// Set the fuel left for the WebAssembly code running in this store,
+    /// for example to refuel it after an out-of-fuel trap.
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.inner.objects.set_fuel(fuel);
+    }
+
     #[cfg(feature = "sys")]
     #[deprecated(
         since = "3.2.0",
@@ -131,6 +281,38 @@ impl Store {
     pub fn id(&self) -> StoreId {
         self.inner.objects.id()
     }
+
+    /// Attaches `data` to this store, replacing the previous data.
+    ///
+    /// The data can then be reached from host functions through
+    /// [`FunctionEnvMut::store_data_mut`], without sharing it with an
+    /// `Arc<Mutex<_>>`.
+    ///
+    /// [`FunctionEnvMut::store_data_mut`]: crate::FunctionEnvMut::store_data_mut
+    pub fn set_data<T: Send + Sync + 'static>(&mut self, data: T) {
+        self.inner.data = Some(Box::new(data));
+    }
+
+    /// Returns the data attached to this store, if it is a `T`.
+    pub fn data<T: 'static>(&self) -> Option<&T> {
+        self.inner.data.as_ref()?.downcast_ref()
+    }
+
+    /// Returns the data attached to this store mutably, if it is a `T`.
+    pub fn data_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.inner.data.as_mut()?.downcast_mut()
+    }
+
+    /// Detaches the data of this store, if it is a `T`, and returns it.
+    pub fn take_data<T: 'static>(&mut self) -> Option<T> {
+        match self.inner.data.take()?.downcast() {
+            Ok(data) => Some(*data),
+            Err(data) => {
+                self.inner.data = Some(data);
+                None
+            }
+        }
+    }
 }
 
 impl PartialEq for Store {
@@ -221,6 +403,11 @@ impl<'a> StoreRef<'a> {
         a.inner.objects.id() == b.inner.objects.id()
     }
 
+    /// Returns the data attached to the store, if it is a `T`.
+    pub fn data<T: 'static>(&self) -> Option<&'a T> {
+        self.inner.data.as_ref()?.downcast_ref()
+    }
+
     /// The signal handler
     #[cfg(feature = "sys")]
     #[inline]
@@ -230,6 +417,13 @@ impl<'a> StoreRef<'a> {
             .as_ref()
             .map(|handler| handler.as_ref() as *const _)
     }
+
+    /// The size of the stack WebAssembly runs on, if set for this store.
+    #[cfg(feature = "sys")]
+    #[inline]
+    pub(crate) fn stack_size(&self) -> Option<usize> {
+        self.inner.stack_size
+    }
 }
 
 /// A temporary handle to a [`Store`].
@@ -259,6 +453,32 @@ impl<'a> StoreMut<'a> {
         a.inner.objects.id() == b.inner.objects.id()
     }
 
+    /// Returns the data attached to the store, if it is a `T`.
+    pub fn data<T: 'static>(&self) -> Option<&T> {
+        self.inner.data.as_ref()?.downcast_ref()
+    }
+
+    /// Returns the data attached to the store mutably, if it is a `T`.
+    pub fn data_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.inner.data.as_mut()?.downcast_mut()
+    }
+
+    #[cfg(feature = "sys")]
+    /// Returns the fuel left for the WebAssembly code running in this
+    /// store.
+    pub fn fuel_remaining(&self) -> u64 {
+        self.inner.objects.fuel_remaining()
+    }
+
+    #[cfg(feature = "sys")]
+    /// Set the fuel left for the WebAssembly code running in this store.
+    ///
+    /// Host functions can use it to charge for their own work, or to
+    /// refuel the store.
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.inner.objects.set_fuel(fuel);
+    }
+
     #[allow(unused)]
     pub(crate) fn engine_and_objects_mut(&mut self) -> (&Engine, &mut StoreObjects) {
         (&self.inner.engine, &mut self.inner.objects)
@@ -272,6 +492,44 @@ impl<'a> StoreMut<'a> {
         Self { inner: &mut *raw }
     }
 
+    /// Whether the store has a call hook.
+    #[cfg(feature = "sys")]
+    #[inline]
+    pub(crate) fn has_call_hook(&self) -> bool {
+        self.inner.call_hook.is_some()
+    }
+
+    /// Invokes the call hook of the store, if it has one.
+    #[cfg(feature = "sys")]
+    pub(crate) fn invoke_call_hook(&mut self, transition: CallHook) -> Result<(), RuntimeError> {
+        // The hook is taken out while it runs, so that it isn't invoked
+        // for its own calls.
+        match self.inner.call_hook.take() {
+            Some(mut hook) => {
+                let result = hook(self.as_store_mut(), transition);
+                if self.inner.call_hook.is_none() {
+                    self.inner.call_hook = Some(hook);
+                }
+                result
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Writes a coredump of the store to its coredump sink, if it has one.
+    #[cfg(feature = "sys")]
+    pub(crate) fn write_coredump(&mut self, error: &RuntimeError) {
+        if let Some(sink) = self.inner.coredump_sink.as_mut() {
+            let frames = error.trace();
+            let name = frames.first().map_or("", |frame| frame.module_name());
+            sink(&wasmer_vm::write_coredump(
+                &self.inner.objects,
+                name,
+                frames,
+            ));
+        }
+    }
+
     // TODO: OnCalledAction is needed for asyncify. It will be refactored with https://github.com/wasmerio/wasmer/issues/3451
     /// Sets the unwind callback which will be invoked when the call finishes
     pub fn on_called<F>(&mut self, callback: F)
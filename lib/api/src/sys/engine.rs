@@ -1,5 +1,5 @@
 pub use wasmer_compiler::{
-    Artifact, BaseTunables, CompilerConfig, Engine, EngineBuilder, Tunables,
+    Artifact, BaseTunables, CompilerConfig, Engine, EngineBuilder, ProfilingStrategy, Tunables,
 };
 #[cfg(feature = "compiler")]
 use wasmer_types::Features;
@@ -83,6 +83,15 @@ pub trait NativeEngineExt {
 
     /// Get a reference to attached Tunable of this engine
     fn tunables(&self) -> &dyn Tunables;
+
+    /// Report the functions compiled or deserialized from now on to a
+    /// native profiler, such as `perf`
+    fn set_profiling_strategy(&mut self, profiling_strategy: ProfilingStrategy);
+
+    /// Translate the DWARF sections of the modules compiled from now on,
+    /// so that native debuggers can step through their source
+    #[cfg(feature = "compiler")]
+    fn set_debug_info(&mut self, enable: bool);
 }
 
 impl NativeEngineExt for crate::engine::Engine {
@@ -106,4 +115,13 @@ impl NativeEngineExt for crate::engine::Engine {
     fn tunables(&self) -> &dyn Tunables {
         self.0.tunables()
     }
+
+    fn set_profiling_strategy(&mut self, profiling_strategy: ProfilingStrategy) {
+        self.0.set_profiling_strategy(profiling_strategy)
+    }
+
+    #[cfg(feature = "compiler")]
+    fn set_debug_info(&mut self, enable: bool) {
+        self.0.set_debug_info(enable)
+    }
 }
@@ -30,6 +30,16 @@ impl ExternRef {
             .downcast_ref::<T>()
     }
 
+    pub fn downcast_mut<'a, T>(&self, store: &'a mut impl AsStoreMut) -> Option<&'a mut T>
+    where
+        T: Any + Send + Sync + 'static + Sized,
+    {
+        self.handle
+            .get_mut(store.objects_mut())
+            .as_mut()
+            .downcast_mut::<T>()
+    }
+
     pub(crate) fn vm_externref(&self) -> VMExternRef {
         VMExternRef(self.handle.internal_handle())
     }
@@ -1,15 +1,16 @@
 use crate::externals::function::{HostFunction, WithEnv, WithoutEnv};
 use crate::native_type::{FromToNativeWasmType, IntoResult, NativeWasmTypeInto, WasmTypeList};
-use crate::store::{AsStoreMut, AsStoreRef, StoreInner, StoreMut};
+use crate::store::{AsStoreMut, AsStoreRef, CallHook, StoreInner, StoreMut};
 use crate::vm::{VMExternFunction, VMFunctionCallback};
 use crate::{FunctionEnv, FunctionEnvMut, FunctionType, RuntimeError, Value};
 use std::panic::{self, AssertUnwindSafe};
 use std::{cell::UnsafeCell, cmp::max, ffi::c_void};
+use wasmer_compiler::FRAME_INFO;
 use wasmer_types::{NativeWasmType, RawValue};
 use wasmer_vm::{
     on_host_stack, raise_user_trap, resume_panic, wasmer_call_trampoline, MaybeInstanceOwned,
-    StoreHandle, VMCallerCheckedAnyfunc, VMContext, VMDynamicFunctionContext, VMExtern, VMFuncRef,
-    VMFunction, VMFunctionContext, VMFunctionKind, VMTrampoline,
+    StoreHandle, Trap, VMCallerCheckedAnyfunc, VMContext, VMDynamicFunctionContext, VMExtern,
+    VMFuncRef, VMFunction, VMFunctionBody, VMFunctionContext, VMFunctionKind, VMTrampoline,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -53,7 +54,10 @@ impl Function {
                     store_mut,
                     func_env: func_env.clone(),
                 };
-                let returns = func(env, &args)?;
+                store.invoke_call_hook(CallHook::CallingHost)?;
+                let returns = func(env, &args);
+                store.invoke_call_hook(CallHook::ReturningFromHost)?;
+                let returns = returns?;
 
                 // We need to dynamically check that the returns
                 // match the expected types, as well as expected length.
@@ -208,6 +212,7 @@ impl Function {
     fn call_wasm(
         &self,
         store: &mut impl AsStoreMut,
+        signature: &FunctionType,
         trampoline: VMTrampoline,
         params: &[Value],
         results: &mut [Value],
@@ -219,20 +224,18 @@ impl Function {
                 .collect::<Vec<String>>()
                 .join(", ")
         };
-        // TODO: Avoid cloning the signature here, it's expensive.
-        let signature = self.ty(store);
         if signature.params().len() != params.len() {
             return Err(RuntimeError::new(format!(
                 "Parameters of type [{}] did not match signature {}",
                 format_types_for_error_message(params),
-                &signature
+                signature
             )));
         }
         if signature.results().len() != results.len() {
             return Err(RuntimeError::new(format!(
                 "Results of type [{}] did not match signature {}",
                 format_types_for_error_message(results),
-                &signature,
+                signature,
             )));
         }
 
@@ -245,7 +248,7 @@ impl Function {
                 let param_types = format_types_for_error_message(params);
                 return Err(RuntimeError::new(format!(
                     "Parameters of type [{}] did not match signature {}",
-                    param_types, &signature,
+                    param_types, signature,
                 )));
             }
             if !arg.is_from_store(store) {
@@ -255,17 +258,25 @@ impl Function {
         }
 
         // Invoke the call
-        self.call_wasm_raw(store, trampoline, values_vec, results)?;
+        self.call_wasm_raw(store, signature, trampoline, values_vec, results)?;
         Ok(())
     }
 
     fn call_wasm_raw(
         &self,
         store: &mut impl AsStoreMut,
+        signature: &FunctionType,
         trampoline: VMTrampoline,
         mut params: Vec<RawValue>,
         results: &mut [Value],
     ) -> Result<(), RuntimeError> {
+        let function = self
+            .handle
+            .get(store.as_store_ref().objects())
+            .anyfunc
+            .as_ptr();
+        enter_wasm(store, unsafe { function.as_ref().func_ptr })?;
+
         // Call the trampoline.
         let result = {
             let mut r;
@@ -275,6 +286,7 @@ impl Function {
                 r = unsafe {
                     wasmer_call_trampoline(
                         store.as_store_ref().signal_handler(),
+                        store.as_store_ref().stack_size(),
                         vm_function.anyfunc.as_ptr().as_ref().vmctx,
                         trampoline,
                         vm_function.anyfunc.as_ptr().as_ref().func_ptr,
@@ -300,12 +312,15 @@ impl Function {
             }
             r
         };
-        if let Err(error) = result {
-            return Err(error.into());
+        let hooked = store
+            .as_store_mut()
+            .invoke_call_hook(CallHook::ReturningFromWasm);
+        if let Err(trap) = result {
+            return Err(wasm_trapped(store, trap));
         }
+        hooked?;
 
         // Load the return values out of `values_vec`.
-        let signature = self.ty(store);
         for (index, &value_type) in signature.results().iter().enumerate() {
             unsafe {
                 results[index] = Value::from_raw(store, value_type, params[index]);
@@ -332,8 +347,10 @@ impl Function {
                 .as_ref()
                 .call_trampoline
         };
-        let mut results = vec![Value::null(); self.result_arity(store)];
-        self.call_wasm(store, trampoline, params, &mut results)?;
+        // The signature is cloned once per call, and borrowed from then on.
+        let signature = self.ty(store);
+        let mut results = vec![Value::null(); signature.results().len()];
+        self.call_wasm(store, &signature, trampoline, params, &mut results)?;
         Ok(results.into_boxed_slice())
     }
 
@@ -352,8 +369,9 @@ impl Function {
                 .as_ref()
                 .call_trampoline
         };
-        let mut results = vec![Value::null(); self.result_arity(store)];
-        self.call_wasm_raw(store, trampoline, params, &mut results)?;
+        let signature = self.ty(store);
+        let mut results = vec![Value::null(); signature.results().len()];
+        self.call_wasm_raw(store, &signature, trampoline, params, &mut results)?;
         Ok(results.into_boxed_slice())
     }
 
@@ -403,6 +421,49 @@ impl Function {
     }
 }
 
+/// Invokes the call hook of the store for a call into WebAssembly.
+pub(crate) fn enter_wasm(
+    store: &mut impl AsStoreMut,
+    func_ptr: *const VMFunctionBody,
+) -> Result<(), RuntimeError> {
+    let mut store = store.as_store_mut();
+    if !store.has_call_hook() {
+        return Ok(());
+    }
+    let function = FRAME_INFO
+        .read()
+        .unwrap()
+        .lookup_frame_info(func_ptr as usize);
+    store.invoke_call_hook(CallHook::CallingWasm(function))
+}
+
+/// Converts a trap out of WebAssembly into a [`RuntimeError`], writing a
+/// coredump of the store unless the trap is an error of the host.
+pub(crate) fn wasm_trapped(store: &mut impl AsStoreMut, trap: Trap) -> RuntimeError {
+    let user = matches!(trap, Trap::User(_));
+    let error = RuntimeError::from(trap);
+    if !user {
+        store.as_store_mut().write_coredump(&error);
+    }
+    error
+}
+
+/// Invokes the call hook of the store on the host stack, from a host
+/// function called by WebAssembly, and traps if the hook fails.
+unsafe fn call_hook_or_trap(store: &mut StoreMut, transition: CallHook) {
+    if !store.has_call_hook() {
+        return;
+    }
+    let result = on_host_stack(|| {
+        panic::catch_unwind(AssertUnwindSafe(|| store.invoke_call_hook(transition)))
+    });
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(trap)) => raise_user_trap(Box::new(trap)),
+        Err(panic) => resume_panic(panic),
+    }
+}
+
 /// Host state for a dynamic function.
 pub(crate) struct DynamicFunction<F> {
     func: F,
@@ -489,6 +550,7 @@ macro_rules! impl_host_function {
                     {
                         // println!("func wrapper");
                         let mut store = StoreMut::from_raw(env.raw_store as *mut _);
+                        call_hook_or_trap(&mut store, CallHook::CallingHost);
                         let result = on_host_stack(|| {
                             // println!("func wrapper1");
                             panic::catch_unwind(AssertUnwindSafe(|| {
@@ -505,6 +567,7 @@ macro_rules! impl_host_function {
                                 (env.func)(f_env, $($x),* ).into_result()
                             }))
                         });
+                        call_hook_or_trap(&mut store, CallHook::ReturningFromHost);
 
                         match result {
                             Ok(Ok(result)) => return result.into_c_struct(&mut store),
@@ -573,6 +636,7 @@ macro_rules! impl_host_function {
                     {
                         // println!("func wrapper");
                         let mut store = StoreMut::from_raw(env.raw_store as *mut _);
+                        call_hook_or_trap(&mut store, CallHook::CallingHost);
                         let result = on_host_stack(|| {
                             // println!("func wrapper1");
                             panic::catch_unwind(AssertUnwindSafe(|| {
@@ -582,6 +646,7 @@ macro_rules! impl_host_function {
                                 (env.func)($($x),* ).into_result()
                             }))
                         });
+                        call_hook_or_trap(&mut store, CallHook::ReturningFromHost);
 
                         match result {
                             Ok(Ok(result)) => return result.into_c_struct(&mut store),
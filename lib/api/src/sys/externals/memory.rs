@@ -8,10 +8,12 @@ use std::marker::PhantomData;
 use std::mem;
 use std::mem::MaybeUninit;
 use std::slice;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 #[cfg(feature = "tracing")]
 use tracing::warn;
 use wasmer_types::Pages;
-use wasmer_vm::{LinearMemory, MemoryError, StoreHandle, VMExtern, VMMemory};
+use wasmer_vm::{LinearMemory, MemoryError, NotifyLocation, StoreHandle, VMExtern, VMMemory};
 
 #[derive(Debug, Clone)]
 pub struct Memory {
@@ -53,7 +55,13 @@ impl Memory {
     where
         IntoPages: Into<Pages>,
     {
-        self.handle.get_mut(store.objects_mut()).grow(delta.into())
+        let objects = store.objects_mut();
+        assert_eq!(
+            self.handle.store_id(),
+            objects.id(),
+            "object used with the wrong context"
+        );
+        objects.grow_memory(self.handle.internal_handle(), delta.into())
     }
 
     pub fn copy_to_store(
@@ -112,6 +120,62 @@ impl Memory {
             .map(|new_memory| Self::new_from_existing(new_store, new_memory.into()))
     }
 
+    pub fn atomic_wait32(
+        &self,
+        store: &mut impl AsStoreMut,
+        offset: u64,
+        expected: u32,
+        timeout: Option<Duration>,
+    ) -> Result<u32, MemoryAccessError> {
+        let value = self.view(store).atomic_u32(offset)?.load(Ordering::SeqCst);
+        self.wait(store, offset, value == expected, timeout)
+    }
+
+    pub fn atomic_wait64(
+        &self,
+        store: &mut impl AsStoreMut,
+        offset: u64,
+        expected: u64,
+        timeout: Option<Duration>,
+    ) -> Result<u32, MemoryAccessError> {
+        let value = self.view(store).atomic_u64(offset)?.load(Ordering::SeqCst);
+        self.wait(store, offset, value == expected, timeout)
+    }
+
+    fn wait(
+        &self,
+        store: &mut impl AsStoreMut,
+        offset: u64,
+        equal: bool,
+        timeout: Option<Duration>,
+    ) -> Result<u32, MemoryAccessError> {
+        if !self.ty(store).shared {
+            return Err(MemoryAccessError::NotShared);
+        }
+        if !equal {
+            return Ok(1);
+        }
+        let address = offset.try_into().map_err(|_| MemoryAccessError::Overflow)?;
+        self.handle
+            .get_mut(store.objects_mut())
+            .do_wait(NotifyLocation { address }, timeout)
+            .map_err(|_| MemoryAccessError::TooManyWaiters)
+    }
+
+    pub fn atomic_notify(
+        &self,
+        store: &mut impl AsStoreMut,
+        offset: u64,
+        count: u32,
+    ) -> Result<u32, MemoryAccessError> {
+        self.view(store).atomic_u32(offset)?;
+        let address = offset.try_into().map_err(|_| MemoryAccessError::Overflow)?;
+        Ok(self
+            .handle
+            .get_mut(store.objects_mut())
+            .do_notify(NotifyLocation { address }, count))
+    }
+
     /// To `VMExtern`.
     pub(crate) fn to_vm_extern(&self) -> VMExtern {
         VMExtern::Memory(self.handle.internal_handle())
@@ -2,8 +2,9 @@ use crate::store::AsStoreRef;
 use crate::MemoryAccessError;
 use std::convert::TryInto;
 use std::marker::PhantomData;
-use std::mem::MaybeUninit;
+use std::mem::{self, MaybeUninit};
 use std::slice;
+use std::sync::atomic::{AtomicU32, AtomicU64};
 use wasmer_types::Pages;
 use wasmer_vm::LinearMemory;
 
@@ -52,6 +53,32 @@ impl<'a> MemoryView<'a> {
         self.buffer.len.try_into().unwrap()
     }
 
+    /// Returns the 32-bit value at `offset` as an atomic.
+    pub fn atomic_u32(&self, offset: u64) -> Result<&AtomicU32, MemoryAccessError> {
+        let ptr = self.atomic_ptr::<u32>(offset)?;
+        Ok(unsafe { &*(ptr as *const AtomicU32) })
+    }
+
+    /// Returns the 64-bit value at `offset` as an atomic.
+    pub fn atomic_u64(&self, offset: u64) -> Result<&AtomicU64, MemoryAccessError> {
+        let ptr = self.atomic_ptr::<u64>(offset)?;
+        Ok(unsafe { &*(ptr as *const AtomicU64) })
+    }
+
+    fn atomic_ptr<T>(&self, offset: u64) -> Result<*mut u8, MemoryAccessError> {
+        // Atomic accesses must be naturally aligned, as in WebAssembly.
+        if offset % mem::size_of::<T>() as u64 != 0 {
+            return Err(MemoryAccessError::Misaligned);
+        }
+        let end = offset
+            .checked_add(mem::size_of::<T>() as u64)
+            .ok_or(MemoryAccessError::Overflow)?;
+        if end > self.data_size() {
+            return Err(MemoryAccessError::HeapOutOfBounds);
+        }
+        Ok(unsafe { self.buffer.base.add(offset as usize) })
+    }
+
     /// Retrieve a slice of the memory contents.
     ///
     /// # Safety
@@ -1,11 +1,12 @@
-use crate::errors::InstantiationError;
+use crate::errors::{InstantiationError, RuntimeError};
 use crate::exports::Exports;
 use crate::module::Module;
 use wasmer_vm::{StoreHandle, VMInstance};
 
 use crate::imports::Imports;
-use crate::store::AsStoreMut;
+use crate::store::{AsStoreMut, AsStoreRef};
 use crate::Extern;
+use wasmer_types::{DeserializeError, InstanceSnapshot, SerializeError};
 
 #[derive(Clone, PartialEq, Eq)]
 pub struct Instance {
@@ -42,11 +43,21 @@ impl Instance {
         store: &mut impl AsStoreMut,
         module: &Module,
         imports: &Imports,
+    ) -> Result<(Self, Exports), InstantiationError> {
+        Self::new_with_start(store, module, imports, true)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn new_with_start(
+        store: &mut impl AsStoreMut,
+        module: &Module,
+        imports: &Imports,
+        run_start: bool,
     ) -> Result<(Self, Exports), InstantiationError> {
         let externs = imports
             .imports_for_module(module)
             .map_err(InstantiationError::Link)?;
-        let mut handle = module.0.instantiate(store, &externs)?;
+        let mut handle = module.0.instantiate(store, &externs, run_start)?;
         let exports = Self::get_exports(store, module, &mut handle);
 
         let instance = Self {
@@ -63,7 +74,7 @@ impl Instance {
         externs: &[Extern],
     ) -> Result<(Self, Exports), InstantiationError> {
         let externs = externs.to_vec();
-        let mut handle = module.0.instantiate(store, &externs)?;
+        let mut handle = module.0.instantiate(store, &externs, true)?;
         let exports = Self::get_exports(store, module, &mut handle);
         let instance = Self {
             _handle: StoreHandle::new(store.objects_mut(), handle),
@@ -72,6 +83,28 @@ impl Instance {
         Ok((instance, exports))
     }
 
+    pub(crate) fn run_start(&self, store: &mut impl AsStoreMut) -> Result<(), RuntimeError> {
+        let signal_handler = store.as_store_ref().signal_handler();
+        let stack_size = store.as_store_ref().stack_size();
+        let handle = self._handle.get_mut(store.objects_mut());
+        unsafe { handle.invoke_start(signal_handler, stack_size) }.map_err(RuntimeError::from)
+    }
+
+    pub(crate) fn snapshot(
+        &self,
+        store: &impl AsStoreRef,
+    ) -> Result<InstanceSnapshot, SerializeError> {
+        self._handle.get(store.as_store_ref().objects()).snapshot()
+    }
+
+    pub(crate) fn restore(
+        &self,
+        store: &mut impl AsStoreMut,
+        snapshot: &InstanceSnapshot,
+    ) -> Result<(), DeserializeError> {
+        self._handle.get_mut(store.objects_mut()).restore(snapshot)
+    }
+
     fn get_exports(
         store: &mut impl AsStoreMut,
         module: &Module,
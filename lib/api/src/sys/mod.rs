@@ -5,22 +5,33 @@ pub(crate) mod externals;
 pub(crate) mod instance;
 pub(crate) mod mem_access;
 pub(crate) mod module;
+#[cfg(feature = "compiler")]
+mod recompiling;
 mod tunables;
 pub(crate) mod typed_function;
 
 pub use crate::sys::engine::NativeEngineExt;
+#[cfg(feature = "compiler")]
+pub use crate::sys::recompiling::{RecompilingModule, Tier};
 pub use crate::sys::tunables::BaseTunables;
 #[cfg(feature = "compiler")]
 pub use wasmer_compiler::{
-    wasmparser, CompilerConfig, FunctionMiddleware, MiddlewareReaderState, ModuleMiddleware,
+    wasmparser, CompilerConfig, FeatureUse, FunctionMiddleware, MiddlewareReaderState,
+    ModuleMiddleware, ValidationError, ValidationReport,
+};
+pub use wasmer_compiler::{
+    Artifact, BoundsCheckStrategy, BoundsCheckTunables, EngineBuilder, Features, MemoryObserver,
+    ObservingTunables, ProfilingStrategy, Tunables,
 };
-pub use wasmer_compiler::{Artifact, EngineBuilder, Features, Tunables};
+#[cfg(unix)]
+pub use wasmer_compiler::PoolingTunables;
 #[cfg(feature = "cranelift")]
 pub use wasmer_compiler_cranelift::{Cranelift, CraneliftOptLevel};
 #[cfg(feature = "llvm")]
 pub use wasmer_compiler_llvm::{LLVMOptLevel, LLVM};
 #[cfg(feature = "singlepass")]
 pub use wasmer_compiler_singlepass::Singlepass;
+pub use wasmer_types::{GlobalSnapshot, InstanceSnapshot, SerializeOptions};
 
 pub(crate) mod vm {
     //! The `vm` module re-exports wasmer-vm types.
@@ -4,8 +4,11 @@ use std::path::Path;
 use std::sync::Arc;
 use wasmer_compiler::Artifact;
 use wasmer_compiler::ArtifactCreate;
+#[cfg(feature = "compiler")]
+use wasmer_compiler::ValidationReport;
 use wasmer_types::{
     CompileError, DeserializeError, ExportsIterator, ImportsIterator, ModuleInfo, SerializeError,
+    SerializeOptions,
 };
 use wasmer_types::{ExportType, ImportType};
 
@@ -31,6 +34,20 @@ pub struct Module {
     artifact: Arc<Artifact>,
 }
 
+#[cfg(test)]
+mod send_test {
+    use super::*;
+
+    fn is_send_and_sync<T: Send + Sync>() -> bool {
+        true
+    }
+
+    #[test]
+    fn module_is_send_and_sync() {
+        assert!(is_send_and_sync::<Module>());
+    }
+}
+
 impl Module {
     pub(crate) fn from_binary(
         engine: &impl AsEngineRef,
@@ -52,6 +69,14 @@ impl Module {
         engine.as_engine_ref().engine().0.validate(binary)
     }
 
+    #[cfg(feature = "compiler")]
+    pub(crate) fn validate_detailed(
+        engine: &impl AsEngineRef,
+        binary: &[u8],
+    ) -> Result<ValidationReport, CompileError> {
+        engine.as_engine_ref().engine().0.validate_detailed(binary)
+    }
+
     #[cfg(feature = "compiler")]
     fn compile(engine: &impl AsEngineRef, binary: &[u8]) -> Result<Self, CompileError> {
         let artifact = engine.as_engine_ref().engine().0.compile(binary)?;
@@ -69,6 +94,15 @@ impl Module {
         self.artifact.serialize().map(|bytes| bytes.into())
     }
 
+    pub(crate) fn serialize_with(
+        &self,
+        options: &SerializeOptions,
+    ) -> Result<Bytes, SerializeError> {
+        self.artifact
+            .serialize_with(options)
+            .map(|bytes| bytes.into())
+    }
+
     pub unsafe fn deserialize(
         engine: &impl AsEngineRef,
         bytes: impl IntoBytes,
@@ -124,6 +158,7 @@ impl Module {
         &self,
         store: &mut impl AsStoreMut,
         imports: &[crate::Extern],
+        run_start: bool,
     ) -> Result<VMInstance, InstantiationError> {
         if !self.artifact.allocated() {
             // Return an error mentioning that the artifact is compiled for a different
@@ -153,10 +188,15 @@ impl Module {
             // of this steps traps, we still need to keep the instance alive
             // as some of the Instance elements may have placed in other
             // instance tables.
-            self.artifact.finish_instantiation(
-                store.as_store_ref().signal_handler(),
-                &mut instance_handle,
-            )?;
+            if run_start {
+                self.artifact.finish_instantiation(
+                    store.as_store_ref().signal_handler(),
+                    store.as_store_ref().stack_size(),
+                    &mut instance_handle,
+                )?;
+            } else {
+                self.artifact.initialize_instance(&mut instance_handle)?;
+            }
 
             Ok(instance_handle)
         }
@@ -172,6 +212,12 @@ impl Module {
         })
     }
 
+    pub(crate) fn add_custom_section(&mut self, name: &str, data: Box<[u8]>) -> bool {
+        Arc::get_mut(&mut self.artifact).map_or(false, |artifact| {
+            artifact.add_module_info_custom_section(name.to_string(), data)
+        })
+    }
+
     pub(crate) fn imports(&self) -> ImportsIterator<impl Iterator<Item = ImportType> + '_> {
         self.info().imports()
     }
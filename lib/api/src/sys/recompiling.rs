@@ -0,0 +1,208 @@
+//! Modules recompiled in the background by an optimizing engine.
+//!
+//! A [`RecompilingModule`] is compiled twice: first with a baseline engine
+//! that compiles quickly (typically singlepass), so that it can be
+//! instantiated right away, and then on a background thread with an
+//! optimizing engine (Cranelift or LLVM). Once the optimized module is
+//! ready, [`RecompilingModule::current`] hands it out instead of the
+//! baseline one.
+//!
+//! This is tiering at instantiation time only. Running instances are not
+//! moved to the optimized code: calls between the functions of a module
+//! are resolved when it is compiled, so there are no call targets to patch
+//! later on. Instances created from the baseline module keep running the
+//! baseline code until they are dropped, only the instances created
+//! afterwards run the optimized code.
+
+use crate::engine::Engine;
+use crate::module::Module;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use wasmer_types::CompileError;
+
+/// Which of the two modules of a [`RecompilingModule`] is handed out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    /// The module compiled by the baseline engine.
+    Baseline,
+    /// The module compiled by the optimizing engine.
+    Optimized,
+}
+
+#[derive(Debug)]
+enum Optimized {
+    Compiling,
+    Ready(Engine, Module),
+    Failed(Arc<CompileError>),
+}
+
+#[derive(Debug)]
+struct Shared {
+    optimized: Mutex<Optimized>,
+    ready: Condvar,
+}
+
+/// A module that is available right away from a baseline engine while an
+/// optimizing engine compiles it again in the background.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::{imports, wat2wasm, Engine, Instance, RecompilingModule, Store};
+/// # fn main() -> anyhow::Result<()> {
+/// # let wasm_bytes = wat2wasm(b"(module)")?;
+/// // Typically singlepass for the baseline and Cranelift or LLVM for the
+/// // optimized module.
+/// let module = RecompilingModule::new(Engine::default(), Engine::default(), &wasm_bytes)?;
+///
+/// // Uses whichever module is ready at the time of the call.
+/// let (engine, current) = module.current();
+/// let mut store = Store::new(engine);
+/// let instance = Instance::new(&mut store, &current, &imports! {})?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecompilingModule {
+    baseline: (Engine, Module),
+    shared: Arc<Shared>,
+}
+
+impl RecompilingModule {
+    /// Compiles `bytes` with the `baseline` engine and starts compiling
+    /// them with the `optimized` engine on a background thread.
+    pub fn new(
+        baseline: Engine,
+        optimized: Engine,
+        bytes: impl AsRef<[u8]>,
+    ) -> Result<Self, CompileError> {
+        let module = Module::new(&baseline, bytes.as_ref())?;
+        let shared = Arc::new(Shared {
+            optimized: Mutex::new(Optimized::Compiling),
+            ready: Condvar::new(),
+        });
+
+        let bytes = bytes.as_ref().to_vec();
+        let background = shared.clone();
+        thread::Builder::new()
+            .name("wasmer-recompile".to_string())
+            .spawn(move || {
+                let result = match Module::new(&optimized, &bytes) {
+                    Ok(module) => Optimized::Ready(optimized, module),
+                    Err(err) => Optimized::Failed(Arc::new(err)),
+                };
+                *background.optimized.lock().unwrap() = result;
+                background.ready.notify_all();
+            })
+            .map_err(|err| {
+                CompileError::Resource(format!("failed to spawn the recompilation thread: {}", err))
+            })?;
+
+        Ok(Self {
+            baseline: (baseline, module),
+            shared,
+        })
+    }
+
+    /// The module that [`RecompilingModule::current`] hands out.
+    pub fn tier(&self) -> Tier {
+        match *self.shared.optimized.lock().unwrap() {
+            Optimized::Ready(..) => Tier::Optimized,
+            _ => Tier::Baseline,
+        }
+    }
+
+    /// The engine and module of the optimized module if it is ready, and
+    /// of the baseline module otherwise. The module must be instantiated
+    /// in a store that uses the returned engine.
+    pub fn current(&self) -> (Engine, Module) {
+        match &*self.shared.optimized.lock().unwrap() {
+            Optimized::Ready(engine, module) => (engine.clone(), module.clone()),
+            _ => self.baseline.clone(),
+        }
+    }
+
+    /// The engine and module of the baseline module.
+    pub fn baseline(&self) -> (Engine, Module) {
+        self.baseline.clone()
+    }
+
+    /// Blocks until the optimizing engine is done and returns its engine
+    /// and module, or the error that it failed with.
+    pub fn wait_optimized(&self) -> Result<(Engine, Module), Arc<CompileError>> {
+        let mut optimized = self.shared.optimized.lock().unwrap();
+        loop {
+            match &*optimized {
+                Optimized::Compiling => optimized = self.shared.ready.wait(optimized).unwrap(),
+                Optimized::Ready(engine, module) => return Ok((engine.clone(), module.clone())),
+                Optimized::Failed(err) => return Err(err.clone()),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "cranelift", feature = "wat"))]
+mod tests {
+    use super::*;
+    use crate::{imports, wat2wasm, EngineBuilder, Features, Instance, Store, TypedFunction};
+    use wasmer_compiler_cranelift::Cranelift;
+
+    fn wasm() -> Vec<u8> {
+        wat2wasm(
+            br#"
+            (module
+              (memory 1)
+              (func (export "fill") (result i32)
+                (memory.fill (i32.const 0) (i32.const 7) (i32.const 16))
+                (i32.load8_u (i32.const 15))))
+            "#,
+        )
+        .unwrap()
+        .into_owned()
+    }
+
+    fn call_fill(engine: Engine, module: &Module) -> i32 {
+        let mut store = Store::new(engine);
+        let instance = Instance::new(&mut store, module, &imports! {}).unwrap();
+        let fill: TypedFunction<(), i32> =
+            instance.exports.get_typed_function(&store, "fill").unwrap();
+        fill.call(&mut store).unwrap()
+    }
+
+    #[test]
+    fn optimized_module_is_handed_out_once_ready() {
+        let baseline: Engine = EngineBuilder::new(Cranelift::default()).engine().into();
+        let optimized: Engine = EngineBuilder::new(Cranelift::default()).engine().into();
+        let module = RecompilingModule::new(baseline, optimized, wasm()).unwrap();
+
+        // The baseline module can be used while the other one compiles
+        let (engine, baseline) = module.baseline();
+        assert_eq!(call_fill(engine, &baseline), 7);
+
+        let (engine, optimized) = module.wait_optimized().unwrap();
+        assert_eq!(call_fill(engine, &optimized), 7);
+
+        assert_eq!(module.tier(), Tier::Optimized);
+        let (engine, current) = module.current();
+        assert_eq!(call_fill(engine, &current), 7);
+    }
+
+    #[test]
+    fn optimizing_errors_are_kept_as_they_are() {
+        let baseline: Engine = EngineBuilder::new(Cranelift::default()).engine().into();
+        let mut features = Features::default();
+        features.bulk_memory(false);
+        let optimized: Engine = EngineBuilder::new(Cranelift::default())
+            .set_features(Some(features))
+            .engine()
+            .into();
+        let module = RecompilingModule::new(baseline, optimized, wasm()).unwrap();
+
+        let err = module.wait_optimized().unwrap_err();
+        assert!(matches!(*err, CompileError::Validate(_)), "{:?}", err);
+        assert!(Arc::ptr_eq(&err, &module.wait_optimized().unwrap_err()));
+        assert_eq!(module.tier(), Tier::Baseline);
+        let (engine, current) = module.current();
+        assert_eq!(call_fill(engine, &current), 7);
+    }
+}
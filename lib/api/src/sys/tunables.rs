@@ -10,8 +10,8 @@ mod tests {
     use crate::TableType;
     use std::cell::UnsafeCell;
     use std::ptr::NonNull;
-    use wasmer_compiler::Tunables;
-    use wasmer_types::{MemoryType, Pages, WASM_PAGE_SIZE};
+    use wasmer_compiler::{BoundsCheckStrategy, Tunables};
+    use wasmer_types::{MemoryIndex, MemoryType, Pages, WASM_PAGE_SIZE};
     use wasmer_vm::{
         LinearMemory, MemoryError, MemoryStyle, TableStyle, VMMemory, VMMemoryDefinition, VMTable,
         VMTableDefinition,
@@ -54,6 +54,61 @@ mod tests {
             }
             s => panic!("Unexpected memory style: {:?}", s),
         }
+
+        // 64-bit memory, even with a small maximum
+        let requested = MemoryType::new64(3, Some(16), false);
+        let style = tunables.memory_style(&requested);
+        match style {
+            MemoryStyle::Dynamic { offset_guard_size } => assert_eq!(offset_guard_size, 256),
+            s => panic!("Unexpected memory style: {:?}", s),
+        }
+    }
+
+    #[test]
+    fn memory_style_bounds_checks() {
+        let tunables = BaseTunables {
+            static_memory_bound: Pages(2048),
+            static_memory_offset_guard_size: 128,
+            dynamic_memory_offset_guard_size: 256,
+        }
+        .with_bounds_checks(BoundsCheckStrategy::Explicit)
+        .with_memory_bounds_checks(
+            MemoryIndex::from_u32(1),
+            BoundsCheckStrategy::ExplicitNoReservation,
+        );
+
+        // A small maximum is checked explicitly too.
+        let requested = MemoryType::new(3, Some(16), false);
+        assert_eq!(
+            tunables.memory_style(&requested),
+            MemoryStyle::Dynamic {
+                offset_guard_size: 256
+            }
+        );
+        assert_eq!(
+            tunables.module_memory_style(MemoryIndex::from_u32(0), &requested),
+            MemoryStyle::Dynamic {
+                offset_guard_size: 256
+            }
+        );
+
+        // The overridden memory reserves no guard.
+        assert_eq!(
+            tunables.module_memory_style(MemoryIndex::from_u32(1), &requested),
+            MemoryStyle::Dynamic {
+                offset_guard_size: 0
+            }
+        );
+
+        // Guard pages are used again once asked for.
+        let tunables = tunables.with_bounds_checks(BoundsCheckStrategy::GuardPages);
+        assert_eq!(
+            tunables.module_memory_style(MemoryIndex::from_u32(0), &requested),
+            MemoryStyle::Static {
+                bound: Pages(2048),
+                offset_guard_size: 128
+            }
+        );
     }
 
     #[derive(Debug)]
@@ -84,11 +139,7 @@ mod tests {
 
     impl LinearMemory for VMTinyMemory {
         fn ty(&self) -> MemoryType {
-            MemoryType {
-                minimum: Pages::from(18u32),
-                maximum: Some(Pages::from(18u32)),
-                shared: false,
-            }
+            MemoryType::new(18u32, Some(18u32), false)
         }
         fn size(&self) -> Pages {
             Pages::from(18u32)
@@ -266,4 +317,53 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn pooled_memories_are_reused() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::{
+            imports, wat2wasm, Engine, EngineBuilder, Instance, Module, Store, TypedFunction,
+        };
+        use wasmer_compiler::PoolingTunables;
+        use wasmer_compiler_cranelift::Cranelift;
+        use wasmer_types::Target;
+        use wasmer_vm::MemoryPool;
+
+        let wasm_bytes = wat2wasm(
+            br#"(module
+            (memory 1)
+            (func (export "swap") (param i32) (result i32)
+              (i32.load (i32.const 8))
+              (i32.store (i32.const 8) (local.get 0))))"#,
+        )?;
+        let base = BaseTunables::for_target(&Target::default());
+        let pool = MemoryPool::new(
+            2,
+            base.static_memory_bound,
+            base.static_memory_offset_guard_size,
+        )?;
+        let mut engine: Engine = EngineBuilder::new(Cranelift::default()).engine().into();
+        engine.set_tunables(PoolingTunables::new(base, pool.clone()));
+        let module = Module::new(&engine, wasm_bytes)?;
+
+        // A store per request: the memory of the previous one comes back
+        // zeroed.
+        for request in 1..10 {
+            let mut store = Store::new(engine.clone());
+            let instance = Instance::new(&mut store, &module, &imports! {})?;
+            assert_eq!(pool.available(), 1);
+            let swap: TypedFunction<i32, i32> =
+                instance.exports.get_typed_function(&store, "swap")?;
+            assert_eq!(swap.call(&mut store, request)?, 0);
+            assert_eq!(swap.call(&mut store, 0)?, request);
+            assert_eq!(swap.call(&mut store, request)?, 0);
+        }
+        assert_eq!(pool.available(), 2);
+
+        let mut store = Store::new(engine);
+        Instance::new(&mut store, &module, &imports! {})?;
+        Instance::new(&mut store, &module, &imports! {})?;
+        assert!(Instance::new(&mut store, &module, &imports! {}).is_err());
+        Ok(())
+    }
 }
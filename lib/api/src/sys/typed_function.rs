@@ -1,8 +1,10 @@
 use crate::{FromToNativeWasmType, RuntimeError, TypedFunction, WasmTypeList};
-use wasmer_types::RawValue;
+use wasmer_types::{NativeWasmType, RawValue, Type};
+use wasmer_vm::{VMCallerCheckedAnyfunc, VMFunctionContext};
 
 use crate::native_type::NativeWasmTypeInto;
-use crate::store::{AsStoreMut, AsStoreRef};
+use crate::store::{AsStoreMut, AsStoreRef, CallHook};
+use crate::sys::externals::function::{enter_wasm, wasm_trapped};
 
 macro_rules! impl_native_traits {
     (  $( $x:ident ),* ) => {
@@ -30,6 +32,17 @@ macro_rules! impl_native_traits {
                         "cross-`Store` values are not supported",
                     ));
                 }
+                // Signatures without several results or 128-bit values are
+                // called natively, the way compiled code calls its imports,
+                // instead of through the trampoline of the function and an
+                // array of values.
+                if Rets::size() <= 1
+                    && !<( $( $x ),* ) as WasmTypeList>::wasm_types().contains(&Type::V128)
+                    && !Rets::wasm_types().contains(&Type::V128)
+                {
+                    $( let $x = $x.to_native().into_abi(store); )*
+                    return self.call_native(store, anyfunc, $( $x ),*);
+                }
                 // TODO: when `const fn` related features mature more, we can declare a single array
                 // of the correct size here.
                 let mut params_list = [ $( $x.to_native().into_raw(store) ),* ];
@@ -47,11 +60,13 @@ macro_rules! impl_native_traits {
                     rets_list.as_mut()
                 };
 
+                enter_wasm(store, anyfunc.func_ptr)?;
                 let mut r;
                 loop {
                     r = unsafe {
                         wasmer_vm::wasmer_call_trampoline(
                             store.as_store_ref().signal_handler(),
+                            store.as_store_ref().stack_size(),
                             anyfunc.vmctx,
                             anyfunc.call_trampoline,
                             anyfunc.func_ptr,
@@ -69,7 +84,11 @@ macro_rules! impl_native_traits {
                     }
                     break;
                 }
-                r?;
+                let hooked = store.as_store_mut().invoke_call_hook(CallHook::ReturningFromWasm);
+                if let Err(trap) = r {
+                    return Err(wasm_trapped(store, trap));
+                }
+                hooked?;
 
                 let num_rets = rets_list.len();
                 if !using_rets_array && num_rets > 0 {
@@ -97,6 +116,51 @@ macro_rules! impl_native_traits {
                 // Ok(Rets::from_c_struct(results))
             }
 
+            /// Calls the body of the function through a trampoline
+            /// specialized for its signature.
+            #[allow(clippy::too_many_arguments)]
+            fn call_native(
+                &self,
+                store: &mut impl AsStoreMut,
+                anyfunc: VMCallerCheckedAnyfunc,
+                $( $x: <$x::Native as NativeWasmType>::Abi, )*
+            ) -> Result<Rets, RuntimeError> {
+                enter_wasm(store, anyfunc.func_ptr)?;
+                let mut r;
+                loop {
+                    r = unsafe {
+                        wasmer_vm::catch_traps(
+                            store.as_store_ref().signal_handler(),
+                            store.as_store_ref().stack_size(),
+                            || {
+                                let body: unsafe extern "C" fn(
+                                    VMFunctionContext,
+                                    $( <$x::Native as NativeWasmType>::Abi, )*
+                                ) -> Rets::CStruct = std::mem::transmute(anyfunc.func_ptr);
+                                body(anyfunc.vmctx, $( $x ),*)
+                            },
+                        )
+                    };
+                    let store_mut = store.as_store_mut();
+                    if let Some(callback) = store_mut.inner.on_called.take() {
+                        match callback(store_mut) {
+                            Ok(wasmer_types::OnCalledAction::InvokeAgain) => { continue; }
+                            Ok(wasmer_types::OnCalledAction::Finish) => { break; }
+                            Ok(wasmer_types::OnCalledAction::Trap(trap)) => { return Err(RuntimeError::user(trap)) },
+                            Err(trap) => { return Err(RuntimeError::user(trap)) },
+                        }
+                    }
+                    break;
+                }
+                let hooked = store.as_store_mut().invoke_call_hook(CallHook::ReturningFromWasm);
+                let results = match r {
+                    Ok(results) => results,
+                    Err(trap) => return Err(wasm_trapped(store, trap)),
+                };
+                hooked?;
+                Ok(unsafe { Rets::from_c_struct(store, results) })
+            }
+
             #[doc(hidden)]
             #[allow(missing_docs)]
             #[allow(unused_mut)]
@@ -126,11 +190,13 @@ macro_rules! impl_native_traits {
                     rets_list.as_mut()
                 };
 
+                enter_wasm(store, anyfunc.func_ptr)?;
                 let mut r;
                 loop {
                     r = unsafe {
                         wasmer_vm::wasmer_call_trampoline(
                             store.as_store_ref().signal_handler(),
+                            store.as_store_ref().stack_size(),
                             anyfunc.vmctx,
                             anyfunc.call_trampoline,
                             anyfunc.func_ptr,
@@ -149,7 +215,11 @@ macro_rules! impl_native_traits {
                     }
                     break;
                 }
-                r?;
+                let hooked = store.as_store_mut().invoke_call_hook(CallHook::ReturningFromWasm);
+                if let Err(trap) = r {
+                    return Err(wasm_trapped(store, trap));
+                }
+                hooked?;
 
                 let num_rets = rets_list.len();
                 if !using_rets_array && num_rets > 0 {
@@ -36,5 +36,9 @@ pub use wasmer_vm::{
     VMTableDefinition,
 };
 
+// Memories for the pooling tunables
+#[cfg(all(feature = "sys", unix))]
+pub use wasmer_vm::{MemoryPool, PooledMemory};
+
 // Deprecated exports
 pub use wasmer_types::{MemoryError, MemoryStyle, TableStyle};
@@ -157,18 +157,101 @@ fn table_grow() -> Result<(), String> {
 
 #[universal_test]
 fn table_copy() -> Result<(), String> {
-    // TODO: table copy test not yet implemented
+    // Tables are not yet fully supported in Wasm
+    #[cfg(feature = "sys")]
+    {
+        let mut store = Store::default();
+        let table_type = TableType::new(Type::FuncRef, 4, None);
+        let f = Function::new_typed(&mut store, |num: i32| num + 1);
+        let src = Table::new(&mut store, table_type, Value::FuncRef(None))
+            .map_err(|e| format!("{e:?}"))?;
+        let dst = Table::new(&mut store, table_type, Value::FuncRef(None))
+            .map_err(|e| format!("{e:?}"))?;
+        src.set(&mut store, 1, Value::FuncRef(Some(f)))
+            .map_err(|e| format!("{e:?}"))?;
+
+        Table::copy(&mut store, &dst, 2, &src, 1, 2).map_err(|e| format!("{e:?}"))?;
+        let copied = dst
+            .iter(&mut store)
+            .map(|e| matches!(e, Value::FuncRef(None)))
+            .collect::<Vec<_>>();
+        assert_eq!(copied, [true, true, false, true]);
+        assert!(Table::copy(&mut store, &dst, 3, &src, 0, 2).is_err());
+    }
+
     Ok(())
 }
 
 #[universal_test]
-fn memory_new() -> Result<(), String> {
+fn table_fill() -> Result<(), String> {
+    // Tables are not yet fully supported in Wasm
+    #[cfg(feature = "sys")]
+    {
+        let mut store = Store::default();
+        let table_type = TableType::new(Type::FuncRef, 5, None);
+        let f = Function::new_typed(&mut store, || {});
+        let table = Table::new(&mut store, table_type, Value::FuncRef(None))
+            .map_err(|e| format!("{e:?}"))?;
+
+        table
+            .fill(&mut store, 1, Value::FuncRef(Some(f.clone())), 3)
+            .map_err(|e| format!("{e:?}"))?;
+        let filled = table
+            .iter(&mut store)
+            .map(|e| matches!(e, Value::FuncRef(None)))
+            .collect::<Vec<_>>();
+        assert_eq!(filled, [true, false, false, false, true]);
+
+        // Out of bounds fills leave the table untouched.
+        assert!(table
+            .fill(&mut store, 4, Value::FuncRef(Some(f)), 2)
+            .is_err());
+        assert!(matches!(
+            table.get(&mut store, 4),
+            Some(Value::FuncRef(None))
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn table_extern_refs() -> Result<(), String> {
+    struct Handle {
+        uses: u32,
+    }
+
     let mut store = Store::default();
-    let memory_type = MemoryType {
-        shared: false,
-        minimum: Pages(0),
-        maximum: Some(Pages(10)),
+    let table_type = TableType::new(Type::ExternRef, 2, None);
+    let table =
+        Table::new(&mut store, table_type, Value::ExternRef(None)).map_err(|e| format!("{e:?}"))?;
+    let handle = ExternRef::new(&mut store, Handle { uses: 0 });
+    table
+        .set(&mut store, 1, Value::ExternRef(Some(handle)))
+        .map_err(|e| format!("{e:?}"))?;
+
+    for _ in 0..2 {
+        let handle = match table.get(&mut store, 1) {
+            Some(Value::ExternRef(Some(handle))) => handle,
+            _ => return Err("expected an externref".to_string()),
+        };
+        handle.downcast_mut::<Handle>(&mut store).unwrap().uses += 1;
+        assert!(handle.downcast_mut::<u32>(&mut store).is_none());
+    }
+    let handle = match table.get(&mut store, 1) {
+        Some(Value::ExternRef(Some(handle))) => handle,
+        _ => return Err("expected an externref".to_string()),
     };
+    assert_eq!(handle.downcast::<Handle>(&store).unwrap().uses, 2);
+
+    Ok(())
+}
+
+#[universal_test]
+fn memory_new() -> Result<(), String> {
+    let mut store = Store::default();
+    let memory_type = MemoryType::new(0, Some(10), false);
     let memory = Memory::new(&mut store, memory_type).map_err(|e| format!("{e:?}"))?;
     assert_eq!(memory.view(&mut store).size(), Pages(0));
     assert_eq!(memory.ty(&mut store), memory_type);
@@ -400,3 +483,113 @@ fn function_new_dynamic_env() -> Result<(), String> {
 
     Ok(())
 }
+
+#[universal_test]
+fn memory_checked_access() -> Result<(), String> {
+    let mut store = Store::default();
+    let memory = Memory::new(&mut store, MemoryType::new(Pages(1), None, false))
+        .map_err(|e| format!("{e:?}"))?;
+    let view = memory.view(&store);
+    let size = view.data_size() as u32;
+    view.write(16, b"hello\0").map_err(|e| format!("{e:?}"))?;
+
+    let ptr: WasmPtr<u32> = WasmPtr::new(16);
+    assert!(ptr.deref_checked(&view).is_ok());
+    assert!(matches!(
+        WasmPtr::<u32>::new(17).deref_checked(&view),
+        Err(MemoryAccessError::Misaligned)
+    ));
+    assert!(matches!(
+        WasmPtr::<u32>::new(size).deref_checked(&view),
+        Err(MemoryAccessError::HeapOutOfBounds)
+    ));
+    assert!(ptr.slice_checked(&view, (size - 16) / 4).is_ok());
+    assert!(matches!(
+        ptr.slice_checked(&view, (size - 16) / 4 + 1),
+        Err(MemoryAccessError::HeapOutOfBounds)
+    ));
+
+    let slice = ptr.slice_checked(&view, 2).map_err(|e| format!("{e:?}"))?;
+    assert!(slice.try_index(1).is_ok());
+    assert!(matches!(
+        slice.try_index(2),
+        Err(MemoryAccessError::HeapOutOfBounds)
+    ));
+    assert!(matches!(
+        slice.try_subslice(1..3),
+        Err(MemoryAccessError::HeapOutOfBounds)
+    ));
+
+    let string: WasmPtr<u8> = WasmPtr::new(16);
+    let hello = string
+        .read_utf8_string_with_nul_max(&view, 64)
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(hello, "hello");
+    assert!(matches!(
+        string.slice_with_nul(&view, 5),
+        Err(MemoryAccessError::Unterminated)
+    ));
+    view.write_u8(u64::from(size) - 1, b'!')
+        .map_err(|e| format!("{e:?}"))?;
+    assert!(matches!(
+        WasmPtr::<u8>::new(size - 1).slice_with_nul(&view, 64),
+        Err(MemoryAccessError::HeapOutOfBounds)
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn memory_atomics() -> Result<(), String> {
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    let mut store = Store::default();
+    let memory =
+        Memory::new(&mut store, MemoryType::new(1, Some(1), true)).map_err(|e| format!("{e:?}"))?;
+    let view = memory.view(&store);
+    let counter = view.atomic_u64(8).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(counter.fetch_add(5, Ordering::SeqCst), 0);
+    assert_eq!(
+        counter.compare_exchange(5, 7, Ordering::SeqCst, Ordering::SeqCst),
+        Ok(5)
+    );
+    assert_eq!(view.read_u8(8).map_err(|e| format!("{e:?}"))?, 7);
+    assert!(matches!(
+        view.atomic_u32(2),
+        Err(MemoryAccessError::Misaligned)
+    ));
+    assert!(matches!(
+        view.atomic_u32(view.data_size()),
+        Err(MemoryAccessError::HeapOutOfBounds)
+    ));
+
+    // The value differs, or nobody notifies in time.
+    assert_eq!(memory.atomic_wait64(&mut store, 8, 0, None), Ok(1));
+    assert_eq!(
+        memory.atomic_wait32(&mut store, 0, 0, Some(Duration::from_millis(1))),
+        Ok(2)
+    );
+
+    let mut waiter_store = Store::default();
+    let waiter_memory = memory
+        .clone_in_store(&store, &mut waiter_store)
+        .ok_or("shared memories can be cloned")?;
+    let waiter = std::thread::spawn(move || {
+        waiter_memory.atomic_wait32(&mut waiter_store, 0, 0, Some(Duration::from_secs(10)))
+    });
+    while memory.atomic_notify(&mut store, 0, 1) == Ok(0) {
+        std::thread::yield_now();
+    }
+    assert_eq!(waiter.join().unwrap(), Ok(0));
+
+    let unshared =
+        Memory::new(&mut store, MemoryType::new(1, None, false)).map_err(|e| format!("{e:?}"))?;
+    assert!(matches!(
+        unshared.atomic_wait32(&mut store, 0, 0, None),
+        Err(MemoryAccessError::NotShared)
+    ));
+
+    Ok(())
+}
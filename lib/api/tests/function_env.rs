@@ -41,3 +41,32 @@ fn data_and_store_mut() -> Result<(), String> {
 
     Ok(())
 }
+
+#[universal_test]
+fn store_data() -> Result<(), String> {
+    #[derive(Debug, PartialEq)]
+    struct Counter(u32);
+
+    let mut store = Store::default();
+    assert!(store.data::<Counter>().is_none());
+    store.set_data(Counter(1));
+    assert!(store.data::<String>().is_none());
+
+    let env = FunctionEnv::new(&mut store, ());
+    let increment = Function::new_typed_with_env(&mut store, &env, |mut env: FunctionEnvMut<()>| {
+        env.store_data_mut::<Counter>().unwrap().0 += 1;
+    });
+    increment
+        .call(&mut store, &[])
+        .map_err(|e| format!("{e:?}"))?;
+    increment
+        .call(&mut store, &[])
+        .map_err(|e| format!("{e:?}"))?;
+
+    assert_eq!(store.data::<Counter>(), Some(&Counter(3)));
+    assert_eq!(store.as_store_ref().data::<Counter>(), Some(&Counter(3)));
+    assert_eq!(store.take_data::<String>(), None);
+    assert_eq!(store.take_data::<Counter>(), Some(Counter(3)));
+    assert!(store.data::<Counter>().is_none());
+    Ok(())
+}
@@ -108,3 +108,61 @@ fn back_and_forth_with_imports() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "sys")]
+#[test]
+fn call_hook_sees_transitions() -> Result<()> {
+    use std::sync::{Arc, Mutex};
+
+    let mut store = Store::default();
+    let wat = r#"(module
+        (func $log (import "host" "log") (param i32))
+        (func $run (export "run") (param i32)
+            (call $log (local.get 0)))
+    )"#;
+    let module = Module::new(&store, wat)?;
+    let imports = imports! {
+        "host" => {
+            "log" => Function::new_typed(&mut store, |_: i32| {}),
+        },
+    };
+    let instance = Instance::new(&mut store, &module, &imports)?;
+    let run: TypedFunction<i32, ()> = instance.exports.get_typed_function(&store, "run")?;
+
+    let transitions = Arc::new(Mutex::new(Vec::new()));
+    let recorded = transitions.clone();
+    store.call_hook(move |_, transition| {
+        recorded.lock().unwrap().push(match transition {
+            CallHook::CallingWasm(Some(frame)) => {
+                format!("wasm {} {:?}", frame.func_index(), frame.function_name())
+            }
+            CallHook::CallingWasm(None) => "wasm".to_string(),
+            CallHook::ReturningFromWasm => "return from wasm".to_string(),
+            CallHook::CallingHost => "host".to_string(),
+            CallHook::ReturningFromHost => "return from host".to_string(),
+        });
+        Ok(())
+    });
+    run.call(&mut store, 1)?;
+    assert_eq!(
+        *transitions.lock().unwrap(),
+        vec![
+            "wasm 1 Some(\"run\")",
+            "host",
+            "return from host",
+            "return from wasm",
+        ]
+    );
+
+    // An error of the hook traps the call.
+    store.call_hook(|_, transition| match transition {
+        CallHook::CallingHost => Err(RuntimeError::new("host calls are forbidden")),
+        _ => Ok(()),
+    });
+    let error = run.call(&mut store, 1).unwrap_err();
+    assert_eq!(error.message(), "host calls are forbidden");
+
+    store.remove_call_hook();
+    run.call(&mut store, 1)?;
+    Ok(())
+}
@@ -78,3 +78,311 @@ fn unit_native_function_env() -> Result<(), String> {
 
     Ok(())
 }
+
+#[cfg(feature = "sys")]
+#[test]
+fn snapshot_and_restore() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        "
+(module
+  (memory (export \"memory\") 1)
+  (global $counter (mut i32) (i32.const 0))
+  (table (export \"table\") 1 funcref)
+  (func $counter (result i32) global.get $counter)
+  (elem declare func $counter)
+  (func (export \"warm_up\")
+    (i32.store (i32.const 16) (i32.const 42))
+    (global.set $counter (i32.const 7))
+    (drop (memory.grow (i32.const 1)))
+    (drop (table.grow (ref.func $counter) (i32.const 1))))
+  (func (export \"counter\") (result i32) global.get $counter)
+  (func (export \"call\") (param i32) (result i32)
+    (call_indirect (result i32) (local.get 0))))
+",
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    let warm = Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let warm_up: TypedFunction<(), ()> = warm
+        .exports
+        .get_typed_function(&store, "warm_up")
+        .map_err(|e| format!("{e:?}"))?;
+    warm_up.call(&mut store).map_err(|e| format!("{e:?}"))?;
+    let snapshot = warm
+        .snapshot(&store)
+        .map_err(|e| format!("{e:?}"))?
+        .serialize()
+        .map_err(|e| format!("{e:?}"))?;
+
+    let fresh = Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let snapshot = InstanceSnapshot::deserialize(&snapshot).map_err(|e| format!("{e:?}"))?;
+    fresh
+        .restore(&mut store, &snapshot)
+        .map_err(|e| format!("{e:?}"))?;
+
+    let memory = fresh
+        .exports
+        .get_memory("memory")
+        .map_err(|e| format!("{e:?}"))?;
+    let view = memory.view(&store);
+    assert_eq!(view.size(), Pages(2));
+    let mut bytes = [0u8; 4];
+    view.read(16, &mut bytes).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(u32::from_le_bytes(bytes), 42);
+
+    let counter: TypedFunction<(), i32> = fresh
+        .exports
+        .get_typed_function(&store, "counter")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(counter.call(&mut store).map_err(|e| format!("{e:?}"))?, 7);
+
+    // The table element refers to the function of the fresh instance.
+    let call: TypedFunction<i32, i32> = fresh
+        .exports
+        .get_typed_function(&store, "call")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(call.call(&mut store, 1).map_err(|e| format!("{e:?}"))?, 7);
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn snapshot_and_restore_reference_globals() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        "
+(module
+  (global $counter (export \"counter\") (mut i32) (i32.const 0))
+  (global $callback (export \"callback\") (mut funcref) (ref.null func))
+  (global (export \"host\") (mut externref) (ref.null extern))
+  (func $counter (result i32) global.get $counter)
+  (elem declare func $counter)
+  (func (export \"warm_up\")
+    (global.set $counter (i32.const 7))
+    (global.set $callback (ref.func $counter))))
+",
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    let warm = Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let warm_up: TypedFunction<(), ()> = warm
+        .exports
+        .get_typed_function(&store, "warm_up")
+        .map_err(|e| format!("{e:?}"))?;
+    warm_up.call(&mut store).map_err(|e| format!("{e:?}"))?;
+    let snapshot = warm.snapshot(&store).map_err(|e| format!("{e:?}"))?;
+
+    let fresh = Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    fresh
+        .restore(&mut store, &snapshot)
+        .map_err(|e| format!("{e:?}"))?;
+
+    // The callback refers to the function of the fresh instance, which
+    // reads the counter of the fresh instance.
+    fresh
+        .exports
+        .get_global("counter")
+        .map_err(|e| format!("{e:?}"))?
+        .set(&mut store, Value::I32(9))
+        .map_err(|e| format!("{e:?}"))?;
+    let callback = match fresh
+        .exports
+        .get_global("callback")
+        .map_err(|e| format!("{e:?}"))?
+        .get(&mut store)
+    {
+        Value::FuncRef(Some(callback)) => callback,
+        other => return Err(format!("unexpected callback {other:?}")),
+    };
+    let result = callback
+        .call(&mut store, &[])
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(result.to_vec(), vec![Value::I32(9)]);
+
+    // External references can't be captured.
+    let extern_ref = ExternRef::new(&mut store, 42u32);
+    fresh
+        .exports
+        .get_global("host")
+        .map_err(|e| format!("{e:?}"))?
+        .set(&mut store, Value::ExternRef(Some(extern_ref)))
+        .map_err(|e| format!("{e:?}"))?;
+    assert!(fresh.snapshot(&store).is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn deferred_start_function() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        "
+(module
+  (memory (export \"memory\") 1)
+  (data (i32.const 0) \"\\01\")
+  (global $runs (export \"runs\") (mut i32) (i32.const 0))
+  (func $start
+    (global.set $runs (i32.add (global.get $runs) (i32.const 1)))
+    (i32.store8 (i32.const 0) (i32.add (i32.load8_u (i32.const 0)) (i32.const 1))))
+  (start $start))
+",
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    let instance = Instance::new_without_start(&mut store, &module, &imports! {})
+        .map_err(|e| format!("{e:?}"))?;
+    let runs = instance
+        .exports
+        .get_global("runs")
+        .map_err(|e| format!("{e:?}"))?
+        .clone();
+    let memory = instance
+        .exports
+        .get_memory("memory")
+        .map_err(|e| format!("{e:?}"))?
+        .clone();
+
+    // The data segments are applied, but the start function hasn't run.
+    assert_eq!(runs.get(&mut store), Value::I32(0));
+    let mut byte = [0u8];
+    memory
+        .view(&store)
+        .read(0, &mut byte)
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(byte, [1]);
+
+    // Patch the memory before any guest code runs.
+    memory
+        .view(&store)
+        .write(0, &[41])
+        .map_err(|e| format!("{e:?}"))?;
+    instance
+        .run_start(&mut store)
+        .map_err(|e| format!("{e:?}"))?;
+    instance
+        .run_start(&mut store)
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(runs.get(&mut store), Value::I32(1));
+    memory
+        .view(&store)
+        .read(0, &mut byte)
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(byte, [42]);
+
+    // `Instance::new` still runs the start function.
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let runs = instance
+        .exports
+        .get_global("runs")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(runs.get(&mut store), Value::I32(1));
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn data_segments_are_private_to_instances() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        "
+(module
+  (memory (export \"memory\") 2)
+  (data (i32.const 16) \"hello\")
+  (data (i32.const 70000) \"world\")
+  (func (export \"write\")
+    (i32.store8 (i32.const 16) (i32.const 106))
+    (drop (memory.grow (i32.const 1)))))
+",
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    let first = Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let second = Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let write: TypedFunction<(), ()> = first
+        .exports
+        .get_typed_function(&store, "write")
+        .map_err(|e| format!("{e:?}"))?;
+    write.call(&mut store).map_err(|e| format!("{e:?}"))?;
+
+    let read = |instance: &Instance, offset: u64| -> Result<[u8; 5], String> {
+        let memory = instance
+            .exports
+            .get_memory("memory")
+            .map_err(|e| format!("{e:?}"))?;
+        let mut bytes = [0u8; 5];
+        memory
+            .view(&store)
+            .read(offset, &mut bytes)
+            .map_err(|e| format!("{e:?}"))?;
+        Ok(bytes)
+    };
+    assert_eq!(&read(&first, 16)?, b"jello");
+    assert_eq!(&read(&first, 70000)?, b"world");
+    assert_eq!(&read(&second, 16)?, b"hello");
+    assert_eq!(&read(&second, 70000)?, b"world");
+
+    Ok(())
+}
+
+#[universal_test]
+fn linker_links_modules() -> Result<(), String> {
+    let mut store = Store::default();
+    let a = Module::new(
+        &store,
+        "
+(module
+  (import \"env\" \"double\" (func $double (param i32) (result i32)))
+  (func (export \"quadruple\") (param i32) (result i32)
+    (call $double (call $double (local.get 0)))))
+",
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let b = Module::new(
+        &store,
+        "
+(module
+  (import \"a\" \"quadruple\" (func $quadruple (param i32) (result i32)))
+  (func (export \"run\") (param i32) (result i32)
+    (i32.add (call $quadruple (local.get 0)) (i32.const 1))))
+",
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    let mut linker = Linker::new();
+    linker
+        .define(
+            "env",
+            "double",
+            Function::new_typed(&mut store, |n: i32| n * 2),
+        )
+        .map_err(|e| format!("{e:?}"))?;
+
+    // `b` can't be instantiated before `a` is registered.
+    assert!(linker.instantiate(&mut store, &b).is_err());
+
+    linker
+        .module(&mut store, "a", &a)
+        .map_err(|e| format!("{e:?}"))?;
+    let instance = linker
+        .instantiate(&mut store, &b)
+        .map_err(|e| format!("{e:?}"))?;
+    let run: TypedFunction<i32, i32> = instance
+        .exports
+        .get_typed_function(&store, "run")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(run.call(&mut store, 5).map_err(|e| format!("{e:?}"))?, 21);
+
+    // Registering `a` again would shadow its exports.
+    assert!(linker.module(&mut store, "a", &a).is_err());
+
+    Ok(())
+}
@@ -290,3 +290,122 @@ fn module_custom_sections() -> Result<(), String> {
     );
     Ok(())
 }
+
+#[cfg(feature = "compiler")]
+#[test]
+fn module_validate_detailed() -> Result<(), String> {
+    let store = Store::default();
+
+    let wasm = wat2wasm(br#"(module (func (export "id") (param v128) (result v128) local.get 0))"#)
+        .map_err(|e| format!("{e:?}"))?;
+    let report = Module::validate_detailed(&store, &wasm).map_err(|e| format!("{e:?}"))?;
+    assert!(report.is_valid());
+    let required = report.required_features.as_ref().unwrap();
+    assert!(required.simd);
+    assert!(!required.threads);
+    assert!(!required.memory64);
+    let features: Vec<_> = report.feature_uses.iter().map(|u| u.feature).collect();
+    assert_eq!(features, ["simd"]);
+    assert!(report.unsupported_features().is_empty());
+
+    // The body of the second function returns an `i64` instead of an `i32`.
+    let wasm = wat2wasm(br#"(module (func) (func (result i32) i64.const 1))"#)
+        .map_err(|e| format!("{e:?}"))?;
+    let report = Module::validate_detailed(&store, &wasm).map_err(|e| format!("{e:?}"))?;
+    assert!(!report.is_valid());
+    assert_eq!(report.required_features, None);
+    assert!(report.feature_uses.is_empty());
+    let error = report.error.unwrap();
+    assert!(error.message.contains("type mismatch"));
+    assert_eq!(error.function.map(|function| function.as_u32()), Some(1));
+    assert!(error.operator.is_some());
+    assert!(Module::validate(&store, &wasm).is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn module_instantiate_on_many_threads() -> Result<(), String> {
+    let engine = Engine::default();
+    let wat = r#"(module
+        (memory 1)
+        (global $counter (mut i32) (i32.const 0))
+        (func (export "increment") (result i32)
+            (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+            (global.get $counter)))"#;
+    let module = Module::new(&engine, wat).map_err(|e| format!("{e:?}"))?;
+
+    let workers = (0..4)
+        .map(|_| {
+            let engine = engine.clone();
+            let module = module.clone();
+            std::thread::spawn(move || {
+                let mut store = Store::new(engine);
+                let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+                let increment: TypedFunction<(), i32> = instance
+                    .exports
+                    .get_typed_function(&store, "increment")
+                    .unwrap();
+                // Each instance has its own globals.
+                (0..10).map(|_| increment.call(&mut store).unwrap()).last()
+            })
+        })
+        .collect::<Vec<_>>();
+    for worker in workers {
+        assert_eq!(worker.join().unwrap(), Some(10));
+    }
+
+    // The compiled contents are shared between the clones.
+    let mut clone = module.clone();
+    assert!(!clone.set_name("shared"));
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn module_metadata() -> Result<(), String> {
+    let store = Store::default();
+    let wat = r#"(module
+        (@custom "producers" "\01\08language\01\04Rust\061.65.0")
+        (@custom "target_features" "\01+\07simd128")
+        (func $init)
+        (start $init)
+    )"#;
+    let mut module = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+
+    let names = module
+        .all_custom_sections()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"producers"));
+    assert!(names.contains(&"target_features"));
+    assert_eq!(
+        module.producers().unwrap().field("language"),
+        Some(&[("Rust".to_string(), "1.65.0".to_string())][..])
+    );
+    assert_eq!(
+        module.target_features(),
+        Some(vec![TargetFeature {
+            prefix: TargetFeaturePrefix::Used,
+            name: "simd128".to_string(),
+        }])
+    );
+    let start = module.start_function().unwrap();
+    assert_eq!(start.index.as_u32(), 0);
+    assert_eq!(start.name.as_deref(), Some("init"));
+
+    // Added sections are kept in serialized artifacts.
+    assert!(module.add_custom_section("build-id", &b"1234"[..]));
+    let serialized = module.serialize().map_err(|e| format!("{e:?}"))?;
+    let module =
+        unsafe { Module::deserialize(&store, serialized) }.map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        module.custom_sections("build-id").collect::<Vec<_>>(),
+        vec![b"1234".to_vec().into_boxed_slice()]
+    );
+    let mut clone = module.clone();
+    assert!(!clone.add_custom_section("build-id", &b"5678"[..]));
+    Ok(())
+}
@@ -4,6 +4,7 @@ use std::{
     fmt::{self, Display, Formatter},
     str::FromStr,
 };
+use wasmer::Target;
 
 /// A hash used as a key when loading and storing modules in a
 /// [`crate::Cache`].
@@ -24,6 +25,21 @@ impl Hash {
         let hash = blake3::hash(bytes);
         Self::new(hash.into())
     }
+
+    /// Creates a new hash from a slice of bytes and the target they are
+    /// compiled for.
+    ///
+    /// Artifacts compiled for a target can only be loaded on hosts
+    /// with all of its CPU features, so the key of a module compiled
+    /// with different ISA extensions must differ, for instance when a
+    /// cache directory is shared between machines.
+    pub fn generate_for_target(bytes: &[u8], target: &Target) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(bytes);
+        hasher.update(target.triple().to_string().as_bytes());
+        hasher.update(&target.cpu_features().as_u64().to_le_bytes());
+        Self::new(hasher.finalize().into())
+    }
 }
 
 impl Display for Hash {
@@ -66,6 +82,33 @@ impl FromStr for Hash {
 mod tests {
     use super::*;
 
+    #[test]
+    fn hash_depends_on_target_cpu_features() {
+        use std::str::FromStr;
+        use wasmer::{CpuFeature, Triple};
+
+        let triple = Triple::from_str("x86_64-unknown-linux-gnu").unwrap();
+        let baseline = Target::new(triple.clone(), CpuFeature::SSE2.into());
+        let avx2 = Target::new(
+            triple,
+            CpuFeature::SSE2 | CpuFeature::AVX | CpuFeature::AVX2,
+        );
+
+        let bytes = b"\0asm\x01\0\0\0";
+        assert_eq!(
+            Hash::generate_for_target(bytes, &baseline),
+            Hash::generate_for_target(bytes, &baseline)
+        );
+        assert_ne!(
+            Hash::generate_for_target(bytes, &baseline),
+            Hash::generate_for_target(bytes, &avx2)
+        );
+        assert_ne!(
+            Hash::generate_for_target(bytes, &baseline),
+            Hash::generate(bytes)
+        );
+    }
+
     #[test]
     fn hash_is_displayed_as_hex() {
         let original = [
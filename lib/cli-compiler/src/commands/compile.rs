@@ -7,8 +7,7 @@ use std::path::{Path, PathBuf};
 use wasmer_compiler::{ArtifactBuild, ArtifactCreate, ModuleEnvironment};
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::{
-    Architecture, CompileError, CpuFeature, MemoryIndex, MemoryStyle, TableIndex, TableStyle,
-    Target, Triple,
+    CompileError, CpuFeature, MemoryIndex, MemoryStyle, TableIndex, TableStyle, Target, Triple,
 };
 
 #[derive(Debug, Parser)]
@@ -50,11 +49,7 @@ impl Compile {
                     .clone()
                     .into_iter()
                     .fold(CpuFeature::set(), |a, b| a | b);
-                // Cranelift requires SSE2, so we have this "hack" for now to facilitate
-                // usage
-                if target_triple.architecture == Architecture::X86_64 {
-                    features |= CpuFeature::SSE2;
-                }
+                features |= CpuFeature::baseline(target_triple);
                 Target::new(target_triple.clone(), features)
             })
             .unwrap_or_default();
@@ -91,8 +86,8 @@ impl Compile {
         let module = translation.module;
         let memory_styles: PrimaryMap<MemoryIndex, MemoryStyle> = module
             .memories
-            .values()
-            .map(|memory_type| tunables.memory_style(memory_type))
+            .iter()
+            .map(|(index, memory_type)| tunables.module_memory_style(index, memory_type))
             .collect();
         let table_styles: PrimaryMap<TableIndex, TableStyle> = module
             .tables
@@ -64,8 +64,12 @@ impl SubsetTunables {
         // tunables make it static.
         //
         // If the module doesn't declare an explicit maximum treat it as 4GiB.
+        //
+        // 64-bit memories are always dynamic: a static memory relies on the
+        // guard pages to catch out-of-bounds accesses, and no reservation
+        // can cover the range of a 64-bit index.
         let maximum = memory.maximum.unwrap_or_else(Pages::max_value);
-        if maximum <= self.static_memory_bound {
+        if !memory.is_memory64() && maximum <= self.static_memory_bound {
             MemoryStyle::Static {
                 // Bound can be larger than the maximum for performance reasons
                 bound: self.static_memory_bound,
@@ -70,6 +70,19 @@ fn gen_helper_functions(atom_name: &str, module_name: &str) -> String {
     ")
 }
 
+/// A module compiled into a static object file.
+pub struct StaticModule<'a> {
+    /// The name of the module in the generated C identifiers, such as
+    /// `wasmer_object_module_new_{atom_name}`.
+    pub atom_name: &'a str,
+    /// The module info of the module.
+    pub module_info: &'a ModuleInfo,
+    /// The names of the symbols of the module in the object file.
+    pub symbol_registry: &'a dyn SymbolRegistry,
+    /// The length of the metadata of the module.
+    pub metadata_length: usize,
+}
+
 /// Generate the header file that goes with the generated object file.
 pub fn generate_header_file(
     atom_name: &str,
@@ -77,6 +90,17 @@ pub fn generate_header_file(
     symbol_registry: &dyn SymbolRegistry,
     metadata_length: usize,
 ) -> String {
+    generate_header_file_for_modules(&[StaticModule {
+        atom_name,
+        module_info,
+        symbol_registry,
+        metadata_length,
+    }])
+}
+
+/// Generate the header file that goes with an object file holding several
+/// modules, exposing each of them.
+pub fn generate_header_file_for_modules(modules: &[StaticModule]) -> String {
     let mut c_statements = vec![
         CStatement::LiteralConstant {
             value: "#include \"wasmer.h\"\n#include <stdlib.h>\n#include <string.h>\n\n"
@@ -85,6 +109,33 @@ pub fn generate_header_file(
         CStatement::LiteralConstant {
             value: "#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n".to_string(),
         },
+        CStatement::TypeDef {
+            source_type: CType::Function {
+                arguments: vec![CType::void_ptr(), CType::void_ptr(), CType::void_ptr()],
+                return_value: None,
+            },
+            new_name: "dyn_func_trampoline_t".to_string(),
+        },
+    ];
+    for module in modules {
+        c_statements.extend(module_statements(module));
+    }
+    c_statements.push(CStatement::LiteralConstant {
+        value: "\n#ifdef __cplusplus\n}\n#endif\n\n".to_string(),
+    });
+
+    generate_c(&c_statements)
+}
+
+/// The declarations of a module and its helper functions.
+fn module_statements(module: &StaticModule) -> Vec<CStatement> {
+    let StaticModule {
+        atom_name,
+        module_info,
+        symbol_registry,
+        metadata_length,
+    } = *module;
+    let mut c_statements = vec![
         CStatement::Declaration {
             name: format!("module_bytes_len_{atom_name}"),
             is_extern: false,
@@ -254,14 +305,6 @@ pub fn generate_header_file(
     });
     c_statements.extend(dyn_func_declarations);
 
-    c_statements.push(CStatement::TypeDef {
-        source_type: CType::Function {
-            arguments: vec![CType::void_ptr(), CType::void_ptr(), CType::void_ptr()],
-            return_value: None,
-        },
-        new_name: "dyn_func_trampoline_t".to_string(),
-    });
-
     // dynamic function trampoline pointer array
     {
         let dynamic_function_trampoline_statements = module_info
@@ -293,9 +336,5 @@ pub fn generate_header_file(
         value: gen_helper_functions(atom_name, &symbol_registry.symbol_to_name(Symbol::Metadata)),
     });
 
-    c_statements.push(CStatement::LiteralConstant {
-        value: "\n#ifdef __cplusplus\n}\n#endif\n\n".to_string(),
-    });
-
-    generate_c(&c_statements)
+    c_statements
 }
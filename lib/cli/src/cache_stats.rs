@@ -0,0 +1,90 @@
+//! Hit and miss counters for the on-disk caches.
+//!
+//! The counters are kept in a small JSON file at the root of each cache
+//! directory so that `wasmer cache stats` can report hit rates across
+//! invocations. Recording is best-effort and never fails the caller.
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+/// Name of the file holding the counters, relative to the cache directory
+pub const STATS_FILE: &str = ".stats.json";
+
+/// How often lookups in a cache were answered from it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Lookups that found a usable entry
+    pub hits: u64,
+    /// Lookups that had to fall back to compiling or downloading
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Reads the counters of the cache in `dir`, all zero if none were
+    /// recorded yet
+    pub fn load(dir: &Path) -> CacheStats {
+        std::fs::read(dir.join(STATS_FILE))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// The fraction of lookups that were hits, if there were any lookups
+    pub fn hit_rate(&self) -> Option<f64> {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            None
+        } else {
+            Some(self.hits as f64 / total as f64)
+        }
+    }
+
+    /// Counts a lookup in the cache in `dir`
+    pub fn record(dir: &Path, hit: bool) {
+        let mut stats = CacheStats::load(dir);
+        if hit {
+            stats.hits += 1;
+        } else {
+            stats.misses += 1;
+        }
+
+        if let Err(e) = stats.save(dir) {
+            tracing::debug!(
+                dir = %dir.display(),
+                error = &e as &dyn std::error::Error,
+                "Unable to record cache statistics",
+            );
+        }
+    }
+
+    fn save(&self, dir: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        // Write to a temporary file first so concurrent readers never see a
+        // half-written file
+        let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+        temp.write_all(&serde_json::to_vec(self)?)?;
+        temp.persist(dir.join(STATS_FILE)).map_err(|e| e.error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_and_misses_are_persisted() {
+        let temp = tempfile::tempdir().unwrap();
+        assert_eq!(CacheStats::load(temp.path()).hit_rate(), None);
+
+        CacheStats::record(temp.path(), true);
+        CacheStats::record(temp.path(), true);
+        CacheStats::record(temp.path(), true);
+        CacheStats::record(temp.path(), false);
+
+        let stats = CacheStats::load(temp.path());
+        assert_eq!(stats, CacheStats { hits: 3, misses: 1 });
+        assert_eq!(stats.hit_rate(), Some(0.75));
+    }
+}
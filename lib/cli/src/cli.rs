@@ -2,16 +2,16 @@
 
 #[cfg(target_os = "linux")]
 use crate::commands::Binfmt;
-#[cfg(feature = "compiler")]
-use crate::commands::Compile;
 #[cfg(any(feature = "static-artifact-create", feature = "wasmer-artifact-create"))]
 use crate::commands::CreateExe;
 #[cfg(feature = "wast")]
 use crate::commands::Wast;
 use crate::commands::{
-    Add, Cache, Config, Init, Inspect, List, Login, Publish, Run, RunUnstable, SelfUpdate,
-    Validate, Whoami,
+    Add, Cache, Config, Debugger, Init, Inspect, List, Login, Package, Publish, Remove, Repl, Run,
+    RunUnstable, SelfUpdate, Serve, Upgrade, Validate, Whoami,
 };
+#[cfg(feature = "compiler")]
+use crate::commands::{Bench, Compile, Profile};
 #[cfg(feature = "static-artifact-create")]
 use crate::commands::{CreateObj, GenCHeader};
 use crate::error::PrettyError;
@@ -47,10 +47,14 @@ enum WasmerCLIOptions {
     /// Login into a wapm.io-like registry
     Login(Login),
 
-    /// Login into a wapm.io-like registry
+    /// Publish a package to a wapm.io-like registry
     #[clap(name = "publish")]
     Publish(Publish),
 
+    /// Build and publish packages
+    #[clap(subcommand)]
+    Package(Package),
+
     /// Wasmer cache
     #[clap(subcommand)]
     Cache(Cache),
@@ -144,6 +148,24 @@ enum WasmerCLIOptions {
     /// Inspect a WebAssembly file
     Inspect(Inspect),
 
+    /// Print the backtrace, globals and memory of a coredump written when a
+    /// WebAssembly program trapped, and optionally explore it interactively
+    Debug(Debugger),
+
+    /// Run a WebAssembly file under a sampling profiler, and write a
+    /// flamegraph of the time spent in its functions and syscalls
+    #[cfg(feature = "compiler")]
+    Profile(Profile),
+
+    /// Measure how long a WebAssembly file takes to compile, instantiate
+    /// and run with each compiler
+    #[cfg(feature = "compiler")]
+    Bench(Bench),
+
+    /// Explore a WebAssembly file interactively: call its functions, and
+    /// read its globals and memories
+    Repl(Repl),
+
     /// Initializes a new wasmer.toml file
     #[clap(name = "init")]
     Init(Init),
@@ -159,9 +181,20 @@ enum WasmerCLIOptions {
     /// Shows the current logged in user for the current active registry
     Whoami(Whoami),
 
-    /// Add a WAPM package's bindings to your application.
+    /// Add a dependency to a package's wasmer.toml, or a WAPM package's
+    /// bindings to your application.
     Add(Add),
 
+    /// Remove dependencies from a package's wasmer.toml
+    Remove(Remove),
+
+    /// Upgrade the dependencies of a package's wasmer.toml
+    Upgrade(Upgrade),
+
+    /// Serve one or more WCGI packages over HTTP, with health checks and
+    /// metrics, reloading local packages when they change
+    Serve(Serve),
+
     /// (unstable) Run a WebAssembly file or WEBC container.
     RunUnstable(RunUnstable),
 }
@@ -181,10 +214,17 @@ impl WasmerCLIOptions {
             Self::CreateObj(create_obj) => create_obj.execute(),
             Self::Config(config) => config.execute(),
             Self::Inspect(inspect) => inspect.execute(),
+            Self::Debug(debug) => debug.execute(),
+            #[cfg(feature = "compiler")]
+            Self::Profile(profile) => profile.execute(),
+            #[cfg(feature = "compiler")]
+            Self::Bench(bench) => bench.execute(),
+            Self::Repl(repl) => repl.execute(),
             Self::Init(init) => init.execute(),
             Self::List(list) => list.execute(),
             Self::Login(login) => login.execute(),
             Self::Publish(publish) => publish.execute(),
+            Self::Package(package) => package.execute(),
             #[cfg(feature = "static-artifact-create")]
             Self::GenCHeader(gen_heder) => gen_heder.execute(),
             #[cfg(feature = "wast")]
@@ -193,6 +233,9 @@ impl WasmerCLIOptions {
             Self::Binfmt(binfmt) => binfmt.execute(),
             Self::Whoami(whoami) => whoami.execute(),
             Self::Add(install) => install.execute(),
+            Self::Remove(remove) => remove.execute(),
+            Self::Upgrade(upgrade) => upgrade.execute(),
+            Self::Serve(serve) => serve.execute(),
             Self::RunUnstable(run2) => run2.execute(),
         }
     }
@@ -245,13 +288,16 @@ fn wasmer_main_inner() -> Result<(), anyhow::Error> {
         WasmerCLIOptions::Run(Run::from_binfmt_args())
     } else {
         match command.unwrap_or(&"".to_string()).as_ref() {
-            "add" | "cache" | "compile" | "config" | "create-obj" | "create-exe" | "help"
-            | "gen-c-header" | "inspect" | "init" | "run" | "run-unstable" | "self-update"
-            | "validate" | "wast" | "binfmt" | "list" | "login" | "publish" => {
+            "add" | "bench" | "cache" | "compile" | "config" | "create-obj" | "create-exe"
+            | "debug" | "help" | "gen-c-header" | "inspect" | "init" | "profile" | "repl"
+            | "run" | "run-unstable" | "self-update" | "serve" | "validate" | "wast" | "binfmt"
+            | "list" | "login" | "publish" | "package" | "remove" | "upgrade" => {
                 WasmerCLIOptions::parse()
             }
-            _ => {
-                WasmerCLIOptions::try_parse_from(args.iter()).unwrap_or_else(|e| {
+            _ => match Run::from_shebang_args(&args) {
+                // Eg. `wasmer ./script.wat --flag`, started by `#!/usr/bin/env wasmer`
+                Some(run) => WasmerCLIOptions::Run(run),
+                None => WasmerCLIOptions::try_parse_from(args.iter()).unwrap_or_else(|e| {
                     match e.kind() {
                         // This fixes a issue that:
                         // 1. Shows the version twice when doing `wasmer -V`
@@ -259,8 +305,8 @@ fn wasmer_main_inner() -> Result<(), anyhow::Error> {
                         ErrorKind::DisplayVersion | ErrorKind::DisplayHelp => e.exit(),
                         _ => WasmerCLIOptions::Run(Run::parse()),
                     }
-                })
-            }
+                }),
+            },
         }
     };
 
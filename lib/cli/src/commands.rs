@@ -1,5 +1,7 @@
 //! The commands available in the Wasmer binary.
 mod add;
+#[cfg(feature = "compiler")]
+mod bench;
 #[cfg(target_os = "linux")]
 mod binfmt;
 mod cache;
@@ -10,32 +12,46 @@ mod config;
 mod create_exe;
 #[cfg(feature = "static-artifact-create")]
 mod create_obj;
+mod debug;
+mod dependencies;
 #[cfg(feature = "static-artifact-create")]
 mod gen_c_header;
 mod init;
 mod inspect;
 mod list;
 mod login;
+mod package;
+#[cfg(feature = "compiler")]
+mod profile;
 mod publish;
+mod remove;
+mod repl;
 mod run;
 mod run_unstable;
 mod self_update;
+mod serve;
+mod upgrade;
 mod validate;
 #[cfg(feature = "wast")]
 mod wast;
 mod whoami;
 
+#[cfg(feature = "compiler")]
+pub use bench::*;
 #[cfg(target_os = "linux")]
 pub use binfmt::*;
 #[cfg(feature = "compiler")]
 pub use compile::*;
 #[cfg(any(feature = "static-artifact-create", feature = "wasmer-artifact-create"))]
 pub use create_exe::*;
+#[cfg(feature = "compiler")]
+pub use profile::*;
 #[cfg(feature = "wast")]
 pub use wast::*;
 pub use {
-    add::*, cache::*, config::*, init::*, inspect::*, list::*, login::*, publish::*, run::*,
-    run_unstable::RunUnstable, self_update::*, validate::*, whoami::*,
+    add::*, cache::*, config::*, debug::*, init::*, inspect::*, list::*, login::*, package::*,
+    publish::*, remove::*, run::*, run_unstable::RunUnstable, self_update::*, serve::*, upgrade::*,
+    validate::*, whoami::*,
 };
 #[cfg(feature = "static-artifact-create")]
 pub use {create_obj::*, gen_c_header::*};
@@ -1,15 +1,26 @@
+use std::path::PathBuf;
 use std::process::{Command, Stdio};
 
 use anyhow::{Context, Error};
 use clap::Parser;
-use wasmer_registry::{Bindings, ProgrammingLanguage, WasmerConfig};
+use wasmer_registry::{lockfile::Lockfile, Bindings, ProgrammingLanguage};
 
-/// Add a WAPM package's bindings to your application.
+use super::dependencies::{self, ManifestDependencies};
+
+/// Add a dependency to a package's `wasmer.toml`, or a WAPM package's
+/// bindings to your application.
+///
+/// Without `--npm`, `--pip` or `--yarn`, the packages are added to the
+/// `[dependencies]` of the `wasmer.toml`, pinned in its `wasmer.lock` and
+/// downloaded to the package cache.
 #[derive(Debug, Parser)]
 pub struct Add {
-    /// The registry to fetch bindings from.
+    /// The registry to fetch packages or bindings from.
     #[clap(long, env = "WAPM_REGISTRY")]
     registry: Option<String>,
+    /// The profile whose registry packages or bindings are fetched from.
+    #[clap(long, env = "WASMER_PROFILE")]
+    profile: Option<String>,
     /// Add the JavaScript bindings using "npm install".
     #[clap(long, groups = &["bindings", "js"])]
     npm: bool,
@@ -22,6 +33,9 @@ pub struct Add {
     /// Add the Python bindings using "pip install".
     #[clap(long, groups = &["bindings", "py"])]
     pip: bool,
+    /// Directory containing the `wasmer.toml` (defaults to the current dir)
+    #[clap(long, conflicts_with = "bindings")]
+    package_path: Option<PathBuf>,
     /// The packages to add (e.g. "wasmer/wasmer-pack@0.5.0" or "python/python")
     packages: Vec<wasmer_registry::Package>,
 }
@@ -30,14 +44,19 @@ impl Add {
     /// Execute [`Add`].
     pub fn execute(&self) -> Result<(), Error> {
         anyhow::ensure!(!self.packages.is_empty(), "No packages specified");
+        crate::utils::use_registry_profile(self.profile.as_deref())?;
 
-        let registry = self
-            .registry()
+        let registry = dependencies::registry(self.registry.as_deref())
             .context("Unable to determine which registry to use")?;
 
-        let bindings = self.lookup_bindings(&registry)?;
+        let target = match self.target()? {
+            Some(target) => target,
+            None => return self.add_dependencies(&registry),
+        };
+
+        let bindings = self.lookup_bindings(&registry, target)?;
 
-        let mut cmd = self.target()?.command(&bindings)?;
+        let mut cmd = target.command(&bindings)?;
         cmd.stdin(Stdio::null())
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit());
@@ -56,11 +75,36 @@ impl Add {
         Ok(())
     }
 
-    fn lookup_bindings(&self, registry: &str) -> Result<Vec<Bindings>, Error> {
+    /// Add the packages to the `[dependencies]` of the `wasmer.toml`.
+    fn add_dependencies(&self, registry: &str) -> Result<(), Error> {
+        let package_dir = dependencies::package_dir(self.package_path.as_deref())?;
+        let mut manifest = ManifestDependencies::open(&package_dir)?;
+        let mut lockfile = Lockfile::load(manifest.dir())?;
+
+        for pkg in &self.packages {
+            let name = pkg.package();
+            let requirement = pkg.version.as_deref().unwrap_or("*");
+            let locked = dependencies::lock(registry, &name, requirement)?;
+
+            // Like "cargo add", a bare package name depends on the latest
+            // version and anything compatible with it
+            let requirement = pkg.version.as_deref().unwrap_or(&locked.version);
+            manifest.insert(&name, requirement)?;
+            println!(
+                "Added {name} = \"{requirement}\" (locked to {})",
+                locked.version
+            );
+            lockfile.insert(locked);
+        }
+
+        manifest.save_with_lockfile(&mut lockfile)
+    }
+
+    fn lookup_bindings(&self, registry: &str, target: Target) -> Result<Vec<Bindings>, Error> {
         println!("Querying WAPM for package bindings");
 
         let mut bindings_to_add = Vec::new();
-        let language = self.target()?.language();
+        let language = target.language();
 
         for pkg in &self.packages {
             let bindings = lookup_bindings_for_package(registry, pkg, &language)
@@ -71,28 +115,13 @@ impl Add {
         Ok(bindings_to_add)
     }
 
-    fn registry(&self) -> Result<String, Error> {
-        match &self.registry {
-            Some(r) => Ok(r.clone()),
-            None => {
-                let wasmer_dir =
-                    WasmerConfig::get_wasmer_dir().map_err(|e| anyhow::anyhow!("{e}"))?;
-                let cfg = WasmerConfig::from_file(&wasmer_dir)
-                    .map_err(Error::msg)
-                    .context("Unable to load WAPM's config file")?;
-                Ok(cfg.registry.get_current_registry())
-            }
-        }
-    }
-
-    fn target(&self) -> Result<Target, Error> {
+    /// The package manager to add bindings with, if any.
+    fn target(&self) -> Result<Option<Target>, Error> {
         match (self.pip, self.npm, self.yarn) {
-            (false, false, false) => Err(anyhow::anyhow!(
-                "at least one of --npm, --pip or --yarn has to be specified"
-            )),
-            (true, false, false) => Ok(Target::Pip),
-            (false, true, false) => Ok(Target::Npm { dev: self.dev }),
-            (false, false, true) => Ok(Target::Yarn { dev: self.dev }),
+            (false, false, false) => Ok(None),
+            (true, false, false) => Ok(Some(Target::Pip)),
+            (false, true, false) => Ok(Some(Target::Npm { dev: self.dev })),
+            (false, false, true) => Ok(Some(Target::Yarn { dev: self.dev })),
             _ => Err(anyhow::anyhow!(
                 "only one of --npm, --pip or --yarn has to be specified"
             )),
@@ -0,0 +1,427 @@
+use crate::commands::run::Wasi;
+use crate::store::{CompilerType, StoreOptions};
+use crate::utils::read_module_bytes;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use wasmer::*;
+use wasmer_types::Type as ValueType;
+
+/// The options for the `wasmer bench` subcommand
+#[derive(Debug, Parser)]
+pub struct Bench {
+    /// WebAssembly file or package to benchmark
+    #[clap(name = "FILE")]
+    path: PathBuf,
+
+    /// The atom to run, when FILE is a package with several atoms
+    #[clap(long)]
+    atom: Option<String>,
+
+    /// Call this exported function instead of running the whole program.
+    /// ARGS are then the function's arguments.
+    #[clap(long, short = 'i')]
+    invoke: Option<String>,
+
+    /// The compilers to compare (default: every compiler in this binary)
+    #[clap(long = "compiler", value_parser = parse_compiler)]
+    compilers: Vec<CompilerType>,
+
+    /// How many measured runs to do with each compiler
+    #[clap(long, short = 'n', default_value_t = 10)]
+    iterations: usize,
+
+    /// How many runs to do with each compiler before measuring
+    #[clap(long, default_value_t = 1)]
+    warmup: usize,
+
+    /// Let the program write to stdout (it is discarded by default)
+    #[clap(long)]
+    show_output: bool,
+
+    /// Print the results as JSON
+    #[clap(long)]
+    json: bool,
+
+    #[clap(flatten)]
+    wasi: Wasi,
+
+    /// Application arguments
+    #[clap(value_name = "ARGS")]
+    args: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct BenchReport {
+    file: String,
+    /// The function that was called, or `_start` for the whole program
+    entrypoint: String,
+    args: Vec<String>,
+    iterations: usize,
+    warmup: usize,
+    compilers: Vec<CompilerReport>,
+}
+
+#[derive(Debug, Serialize)]
+struct CompilerReport {
+    compiler: String,
+    compile_ms: Option<f64>,
+    instantiate: Option<Stats>,
+    execute: Option<Stats>,
+    /// The exit code of the last run of a WASI program
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Summary of a set of timings, in milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Stats {
+    min_ms: f64,
+    max_ms: f64,
+    mean_ms: f64,
+    median_ms: f64,
+    stddev_ms: f64,
+}
+
+impl Stats {
+    fn new(samples: &[Duration]) -> Option<Stats> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut ms: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+        ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let n = ms.len() as f64;
+        let mean = ms.iter().sum::<f64>() / n;
+        let median = if ms.len() % 2 == 0 {
+            (ms[ms.len() / 2 - 1] + ms[ms.len() / 2]) / 2.0
+        } else {
+            ms[ms.len() / 2]
+        };
+        let variance = ms.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+
+        Some(Stats {
+            min_ms: ms[0],
+            max_ms: ms[ms.len() - 1],
+            mean_ms: mean,
+            median_ms: median,
+            stddev_ms: variance.sqrt(),
+        })
+    }
+}
+
+/// The timings of a single run.
+struct Timings {
+    instantiate: Duration,
+    execute: Duration,
+    exit_code: Option<i32>,
+}
+
+impl Bench {
+    /// Runs logic for the `bench` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .with_context(|| format!("failed to benchmark `{}`", self.path.display()))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        if self.iterations == 0 {
+            bail!("--iterations must be at least 1");
+        }
+        let bytes = read_module_bytes(&self.path, self.atom.as_deref())?;
+
+        let compilers = if self.compilers.is_empty() {
+            CompilerType::enabled()
+        } else {
+            self.compilers.clone()
+        };
+
+        let mut reports = Vec::new();
+        for compiler in compilers {
+            if !self.json {
+                eprintln!("Benchmarking with {}...", compiler.to_string());
+            }
+            reports.push(self.bench_compiler(compiler, &bytes));
+        }
+
+        let report = BenchReport {
+            file: self.path.display().to_string(),
+            entrypoint: self.invoke.clone().unwrap_or_else(|| "_start".to_string()),
+            args: self.args.clone(),
+            iterations: self.iterations,
+            warmup: self.warmup,
+            compilers: reports,
+        };
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print!("{}", render_report(&report));
+        }
+
+        if report.compilers.iter().all(|c| c.error.is_some()) {
+            bail!("the benchmark failed with every compiler");
+        }
+        Ok(())
+    }
+
+    /// Compile the module with one compiler, then instantiate and run it
+    /// from scratch for every iteration.
+    fn bench_compiler(&self, compiler: CompilerType, bytes: &[u8]) -> CompilerReport {
+        let mut report = CompilerReport {
+            compiler: compiler.to_string(),
+            compile_ms: None,
+            instantiate: None,
+            execute: None,
+            exit_code: None,
+            error: None,
+        };
+
+        let result = (|| -> Result<()> {
+            let store = StoreOptions::default().get_store_for_compiler(compiler)?;
+            let start = Instant::now();
+            let module = Module::new(&store, bytes)?;
+            report.compile_ms = Some(start.elapsed().as_secs_f64() * 1000.0);
+
+            let mut instantiate = Vec::new();
+            let mut execute = Vec::new();
+            for i in 0..self.warmup + self.iterations {
+                let mut store = Store::new(store.engine().clone());
+                let run = self
+                    .run_once(&mut store, &module)
+                    .with_context(|| format!("run {} failed", i + 1))?;
+                report.exit_code = run.exit_code;
+                if i >= self.warmup {
+                    instantiate.push(run.instantiate);
+                    execute.push(run.execute);
+                }
+            }
+
+            report.instantiate = Stats::new(&instantiate);
+            report.execute = Stats::new(&execute);
+            Ok(())
+        })();
+
+        if let Err(e) = result {
+            report.error = Some(format!("{e:#}"));
+        }
+        report
+    }
+
+    fn run_once(&self, store: &mut Store, module: &Module) -> Result<Timings> {
+        let start = Instant::now();
+        let (instance, wasi_env) = if Wasi::has_wasi_imports(module) {
+            let program_name = self
+                .path
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let args = if self.invoke.is_some() {
+                Vec::new()
+            } else {
+                self.args.clone()
+            };
+            let mut builder = self.wasi.prepare(store, module, program_name, args)?;
+            if !self.show_output {
+                builder = builder.stdout(Box::new(virtual_fs::NullFile::default()));
+            }
+            let (instance, env) = builder
+                .instantiate(module.clone(), store)
+                .context("failed to instantiate WASI module")?;
+            (instance, Some(env))
+        } else {
+            (Instance::new(store, module, &imports! {})?, None)
+        };
+        let instantiate = start.elapsed();
+
+        let (func, params) = match &self.invoke {
+            Some(name) => {
+                let func = instance
+                    .exports
+                    .get_function(name)
+                    .with_context(|| format!("the module has no `{name}` function"))?;
+                let params = parse_params(&func.ty(store), &self.args)?;
+                (func.clone(), params)
+            }
+            None => {
+                let func = instance
+                    .exports
+                    .get_function("_start")
+                    .context("the module has no `_start` function, pick one with --invoke")?;
+                (func.clone(), Vec::new())
+            }
+        };
+
+        let start = Instant::now();
+        let result = func.call(store, &params);
+        let execute = start.elapsed();
+
+        let exit_code = match wasi_env {
+            Some(env) => {
+                let exit_code = self.wasi.handle_result(result);
+                env.cleanup(store, None);
+                Some(exit_code?)
+            }
+            None => {
+                result?;
+                None
+            }
+        };
+
+        Ok(Timings {
+            instantiate,
+            execute,
+            exit_code,
+        })
+    }
+}
+
+fn parse_compiler(s: &str) -> Result<CompilerType> {
+    CompilerType::enabled()
+        .into_iter()
+        .find(|compiler| compiler.to_string() == s)
+        .ok_or_else(|| {
+            let enabled: Vec<_> = CompilerType::enabled()
+                .iter()
+                .map(|c| c.to_string())
+                .collect();
+            anyhow!(
+                "`{s}` isn't one of the compilers in this binary ({})",
+                enabled.join(", ")
+            )
+        })
+}
+
+fn parse_params(ty: &FunctionType, args: &[String]) -> Result<Vec<Value>> {
+    if ty.params().len() != args.len() {
+        bail!(
+            "the function expects {} arguments, but received {}",
+            ty.params().len(),
+            args.len()
+        );
+    }
+
+    args.iter()
+        .zip(ty.params())
+        .map(|(arg, ty)| {
+            Ok(match ty {
+                ValueType::I32 => Value::I32(arg.parse()?),
+                ValueType::I64 => Value::I64(arg.parse()?),
+                ValueType::F32 => Value::F32(arg.parse()?),
+                ValueType::F64 => Value::F64(arg.parse()?),
+                _ => bail!("Don't know how to convert `{arg}` into {ty:?}"),
+            })
+        })
+        .collect()
+}
+
+fn render_report(report: &BenchReport) -> String {
+    let fastest = report
+        .compilers
+        .iter()
+        .filter_map(|c| c.execute.as_ref())
+        .map(|stats| stats.mean_ms)
+        .fold(f64::INFINITY, f64::min);
+
+    let mut out = format!(
+        "\n{} `{}` ({} runs after {} warm-up runs)\n\n",
+        report.file,
+        std::iter::once(report.entrypoint.as_str())
+            .chain(report.args.iter().map(|a| a.as_str()))
+            .collect::<Vec<_>>()
+            .join(" "),
+        report.iterations,
+        report.warmup,
+    );
+    out.push_str(&format!(
+        "{:<12} {:>12} {:>22} {:>22} {:>8}\n",
+        "compiler", "compile", "instantiate", "execute", "relative"
+    ));
+
+    for c in &report.compilers {
+        if let Some(error) = &c.error {
+            out.push_str(&format!("{:<12} error: {error}\n", c.compiler));
+            continue;
+        }
+        let relative = match &c.execute {
+            Some(stats) if fastest > 0.0 => format!("{:.2}x", stats.mean_ms / fastest),
+            _ => "-".to_string(),
+        };
+        out.push_str(&format!(
+            "{:<12} {:>12} {:>22} {:>22} {:>8}\n",
+            c.compiler,
+            c.compile_ms.map(format_ms).unwrap_or_default(),
+            c.instantiate.as_ref().map(format_stats).unwrap_or_default(),
+            c.execute.as_ref().map(format_stats).unwrap_or_default(),
+            relative,
+        ));
+    }
+
+    out
+}
+
+fn format_stats(stats: &Stats) -> String {
+    format!(
+        "{} ± {}",
+        format_ms(stats.mean_ms),
+        format_ms(stats.stddev_ms)
+    )
+}
+
+fn format_ms(ms: f64) -> String {
+    if ms >= 1000.0 {
+        format!("{:.2}s", ms / 1000.0)
+    } else if ms >= 1.0 {
+        format!("{ms:.2}ms")
+    } else {
+        format!("{:.1}µs", ms * 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_of_samples() {
+        let samples: Vec<_> = [4, 1, 3, 2]
+            .iter()
+            .map(|&ms| Duration::from_millis(ms))
+            .collect();
+
+        let stats = Stats::new(&samples).unwrap();
+
+        assert_eq!(stats.min_ms, 1.0);
+        assert_eq!(stats.max_ms, 4.0);
+        assert_eq!(stats.mean_ms, 2.5);
+        assert_eq!(stats.median_ms, 2.5);
+        assert!((stats.stddev_ms - 1.25_f64.sqrt()).abs() < 1e-9);
+        assert_eq!(Stats::new(&[]), None);
+    }
+
+    #[test]
+    fn durations_are_readable() {
+        assert_eq!(format_ms(0.0125), "12.5µs");
+        assert_eq!(format_ms(12.345), "12.35ms");
+        assert_eq!(format_ms(1500.0), "1.50s");
+    }
+
+    #[test]
+    fn arguments_match_the_signature() {
+        let ty = FunctionType::new(vec![ValueType::I32, ValueType::F64], vec![]);
+
+        let params = parse_params(&ty, &["42".to_string(), "0.5".to_string()]).unwrap();
+        assert!(matches!(
+            params.as_slice(),
+            [Value::I32(42), Value::F64(f)] if *f == 0.5
+        ));
+
+        assert!(parse_params(&ty, &["42".to_string()]).is_err());
+        assert!(parse_params(&ty, &["x".to_string(), "0.5".to_string()]).is_err());
+    }
+}
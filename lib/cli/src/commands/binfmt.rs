@@ -8,7 +8,7 @@ use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 use Action::*;
 
-#[derive(Debug, Parser, Clone, Copy)]
+#[derive(Debug, Parser, Clone)]
 enum Action {
     /// Register wasmer as binfmt interpreter
     Register,
@@ -16,6 +16,42 @@ enum Action {
     Unregister,
     /// Soft unregister, and register
     Reregister,
+    /// Show whether wasmer is registered, and with which interpreter
+    Status,
+    /// Print a binfmt.d configuration (e.g. for
+    /// `/etc/binfmt.d/wasmer.conf`), to register wasmer at start-up with
+    /// systemd-binfmt
+    Print {
+        /// The interpreter to register: a copy or hard link of wasmer
+        /// named `wasmer-binfmt-interpreter` (defaults to one next to
+        /// the wasmer executable)
+        #[clap(long)]
+        interpreter: Option<PathBuf>,
+    },
+}
+
+/// The name wasmer must be started with to act as binfmt interpreter
+const INTERPRETER_NAME: &str = "wasmer-binfmt-interpreter";
+
+/// The binfmt_misc entries wasmer registers
+const REGISTRATIONS: [&str; 2] = ["wasm32", "wasm32-wat"];
+
+/// The binfmt_misc registration strings for an interpreter
+fn specs(interpreter: &Path) -> [Vec<u8>; 2] {
+    [
+        [
+            b":wasm32:M::\\x00asm\\x01\\x00\\x00::".as_ref(),
+            interpreter.as_os_str().as_bytes(),
+            b":PFC",
+        ]
+        .concat(),
+        [
+            b":wasm32-wat:E::wat::".as_ref(),
+            interpreter.as_os_str().as_bytes(),
+            b":PFC",
+        ]
+        .concat(),
+    ]
 }
 
 /// Unregister and/or register wasmer as binfmt interpreter
@@ -55,9 +91,15 @@ fn seccheck(path: &Path) -> Result<()> {
 impl Binfmt {
     /// execute [Binfmt]
     pub fn execute(&self) -> Result<()> {
+        if let Print { interpreter } = &self.action {
+            return print_config(interpreter.as_deref());
+        }
         if !self.binfmt_misc.exists() {
             panic!("{} does not exist", self.binfmt_misc.to_string_lossy());
         }
+        if let Status = self.action {
+            return self.status();
+        }
         let temp_dir;
         let specs = match self.action {
             Register | Reregister => {
@@ -66,7 +108,7 @@ impl Binfmt {
                 let bin_path_orig: PathBuf = env::current_exe()
                     .and_then(|p| p.canonicalize())
                     .context("Cannot get path to wasmer executable")?;
-                let bin_path = temp_dir.path().join("wasmer-binfmt-interpreter");
+                let bin_path = temp_dir.path().join(INTERPRETER_NAME);
                 fs::copy(&bin_path_orig, &bin_path).context("Copy wasmer binary to temp folder")?;
                 let bin_path = fs::canonicalize(&bin_path).with_context(|| {
                     format!(
@@ -74,29 +116,15 @@ impl Binfmt {
                         bin_path.to_string_lossy()
                     )
                 })?;
-                Some([
-                    [
-                        b":wasm32:M::\\x00asm\\x01\\x00\\x00::".as_ref(),
-                        bin_path.as_os_str().as_bytes(),
-                        b":PFC",
-                    ]
-                    .concat(),
-                    [
-                        b":wasm32-wat:E::wat::".as_ref(),
-                        bin_path.as_os_str().as_bytes(),
-                        b":PFC",
-                    ]
-                    .concat(),
-                ])
+                Some(specs(&bin_path))
             }
             _ => None,
         };
-        let wasm_registration = self.binfmt_misc.join("wasm32");
-        let wat_registration = self.binfmt_misc.join("wasm32-wat");
         match self.action {
             Reregister | Unregister => {
-                let unregister = [wasm_registration, wat_registration]
+                let unregister = REGISTRATIONS
                     .iter()
+                    .map(|name| self.binfmt_misc.join(name))
                     .map(|registration| {
                         if registration.exists() {
                             let mut registration = fs::OpenOptions::new()
@@ -118,7 +146,7 @@ impl Binfmt {
                     .collect::<Vec<_>>()
                     .into_iter()
                     .collect::<Result<Vec<_>>>()?;
-                if let (Unregister, false) = (self.action, unregister.into_iter().any(|b| b)) {
+                if let (Unregister, false) = (&self.action, unregister.into_iter().any(|b| b)) {
                     bail!("Nothing unregistered");
                 }
             }
@@ -148,4 +176,58 @@ impl Binfmt {
         }
         Ok(())
     }
+
+    fn status(&self) -> Result<()> {
+        for name in REGISTRATIONS {
+            let registration = self.binfmt_misc.join(name);
+            match fs::read_to_string(&registration) {
+                Ok(details) => {
+                    println!("{name}: registered");
+                    for line in details.lines() {
+                        println!("  {line}");
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    println!("{name}: not registered")
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Couldn't read {}", registration.to_string_lossy())
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn print_config(interpreter: Option<&Path>) -> Result<()> {
+    let interpreter = match interpreter {
+        Some(interpreter) => interpreter.to_path_buf(),
+        None => env::current_exe()
+            .and_then(|p| p.canonicalize())
+            .context("Cannot get path to wasmer executable")?
+            .with_file_name(INTERPRETER_NAME),
+    };
+    anyhow::ensure!(
+        interpreter.is_absolute(),
+        "The interpreter must be an absolute path"
+    );
+    anyhow::ensure!(
+        interpreter.file_name() == Some(INTERPRETER_NAME.as_ref()),
+        "The interpreter must be named {INTERPRETER_NAME}"
+    );
+    if !interpreter.exists() {
+        eprintln!(
+            "Warning: {} does not exist, create it with `ln \"$(command -v wasmer)\" {}`",
+            interpreter.to_string_lossy(),
+            interpreter.to_string_lossy()
+        );
+    }
+
+    println!("# Run WebAssembly files with wasmer, generated by `wasmer binfmt print`");
+    for spec in specs(&interpreter) {
+        println!("{}", String::from_utf8_lossy(&spec));
+    }
+    Ok(())
 }
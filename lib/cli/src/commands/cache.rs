@@ -1,7 +1,13 @@
+use crate::cache_stats::CacheStats;
 use crate::common::get_cache_dir;
 use anyhow::{Context, Result};
+use bytesize::ByteSize;
 use clap::Parser;
+use sha2::{Digest, Sha256};
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use wasmer_registry::WasmerConfig;
 
 #[derive(Debug, Parser)]
 /// The options for the `wasmer cache` subcommand
@@ -13,6 +19,44 @@ pub enum Cache {
     /// Display the location of the cache
     #[clap(name = "dir")]
     Dir,
+
+    /// Show the size of every cache and how often lookups hit it
+    #[clap(name = "stats")]
+    Stats,
+
+    /// Remove cache entries that are too old or don't fit in a size budget
+    #[clap(name = "prune")]
+    Prune(Prune),
+
+    /// Check cache entries against their content hashes and remove corrupt ones
+    #[clap(name = "verify")]
+    Verify(Verify),
+}
+
+/// The options for the `wasmer cache prune` subcommand
+#[derive(Debug, Parser)]
+pub struct Prune {
+    /// Remove the least recently used entries until all caches together
+    /// take up at most this much space (e.g. `500MB` or `2GiB`)
+    #[clap(long, value_name = "SIZE")]
+    pub max_size: Option<ByteSize>,
+
+    /// Remove the entries that haven't been used for this long
+    /// (e.g. `30d`, `12h` or `90m`)
+    #[clap(long, value_name = "AGE", value_parser = parse_age)]
+    pub max_age: Option<Duration>,
+
+    /// Only show what would be removed
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+/// The options for the `wasmer cache verify` subcommand
+#[derive(Debug, Parser)]
+pub struct Verify {
+    /// Only report corrupt entries, without removing them
+    #[clap(long)]
+    pub dry_run: bool,
 }
 
 impl Cache {
@@ -25,6 +69,13 @@ impl Cache {
             Cache::Dir => {
                 self.dir()?;
             }
+            Cache::Stats => stats(&locations()),
+            Cache::Prune(prune) => prune
+                .execute(&locations())
+                .context("failed to prune the wasmer cache.")?,
+            Cache::Verify(verify) => verify
+                .execute(&locations())
+                .context("failed to verify the wasmer cache.")?,
         }
         Ok(())
     }
@@ -42,3 +93,506 @@ impl Cache {
         Ok(())
     }
 }
+
+/// What a cache holds, which decides what an entry is and how it is verified
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    /// Serialized modules, one file per entry
+    Artifacts,
+    /// LZW-compressed serialized modules, one file per entry
+    CompressedArtifacts,
+    /// `.webc` files named after their checksum
+    Packages,
+    /// OCI layers named after their sha256 digest
+    OciBlobs,
+    /// Downloaded files and directories, which have no content hash
+    Downloads,
+}
+
+#[derive(Debug, Clone)]
+struct Location {
+    name: &'static str,
+    kind: Kind,
+    dir: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    path: PathBuf,
+    size: u64,
+    last_used: SystemTime,
+}
+
+/// All the caches that wasmer keeps, both for compiled artifacts and for
+/// packages
+fn locations() -> Vec<Location> {
+    let mut locations = vec![Location {
+        name: "artifacts",
+        kind: Kind::Artifacts,
+        dir: get_cache_dir(),
+    }];
+
+    if let Ok(home) = WasmerConfig::get_wasmer_dir() {
+        let cache = home.join("cache");
+        locations.extend([
+            Location {
+                name: "compiled",
+                kind: Kind::CompressedArtifacts,
+                dir: home.join("compiled"),
+            },
+            Location {
+                name: "modules",
+                kind: Kind::Artifacts,
+                dir: cache.clone(),
+            },
+            Location {
+                name: "packages",
+                kind: Kind::Packages,
+                dir: wasmer_registry::get_webc_dir(&home),
+            },
+            Location {
+                name: "oci",
+                kind: Kind::OciBlobs,
+                dir: cache.join("oci").join("blobs"),
+            },
+            Location {
+                name: "downloads",
+                kind: Kind::Downloads,
+                dir: wasmer_registry::get_checkouts_dir(&home),
+            },
+        ]);
+    }
+
+    locations
+}
+
+impl Location {
+    /// The entries of this cache, leaving out the caches nested inside it
+    fn entries(&self, all: &[Location]) -> Vec<Entry> {
+        let nested: Vec<&Path> = all
+            .iter()
+            .filter(|other| other.dir != self.dir && other.dir.starts_with(&self.dir))
+            .map(|other| other.dir.as_path())
+            .collect();
+        // Packages and downloads may be unpacked into directories, which
+        // are removed as a whole
+        let max_depth = match self.kind {
+            Kind::Packages | Kind::Downloads => 1,
+            _ => usize::MAX,
+        };
+
+        walkdir::WalkDir::new(&self.dir)
+            .min_depth(1)
+            .max_depth(max_depth)
+            .into_iter()
+            .filter_entry(|e| {
+                !is_hidden(e.path()) && !nested.iter().any(|dir| e.path().starts_with(dir))
+            })
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file() || max_depth == 1)
+            .map(|e| entry(e.path()))
+            .collect()
+    }
+
+    /// Checks an entry against its content hash, returning why it is
+    /// corrupt, if it is
+    fn verify(&self, entry: &Entry) -> Verdict {
+        match self.kind {
+            Kind::Artifacts => match fs::read(&entry.path) {
+                Ok(bytes) => verify_artifact(bytes),
+                Err(e) => Verdict::Corrupt(e.to_string()),
+            },
+            Kind::CompressedArtifacts => {
+                let compressed = match fs::read(&entry.path) {
+                    Ok(bytes) => bytes,
+                    Err(e) => return Verdict::Corrupt(e.to_string()),
+                };
+                let mut bytes = Vec::new();
+                let mut decoder = weezl::decode::Decoder::new(weezl::BitOrder::Msb, 8);
+                match decoder.into_vec(&mut bytes).decode_all(&compressed).status {
+                    Ok(_) => verify_artifact(bytes),
+                    Err(e) => Verdict::Corrupt(format!("unable to decompress: {e}")),
+                }
+            }
+            Kind::Packages => verify_webc(&entry.path),
+            Kind::OciBlobs => verify_oci_blob(&entry.path),
+            Kind::Downloads => Verdict::Unchecked,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Verdict {
+    Ok,
+    Corrupt(String),
+    Unchecked,
+}
+
+#[cfg(feature = "sys")]
+fn verify_artifact(bytes: Vec<u8>) -> Verdict {
+    let engine = wasmer::Engine::headless();
+    match wasmer::Module::deserialize_checked(&engine, bytes) {
+        Ok(_) => Verdict::Ok,
+        Err(e) => Verdict::Corrupt(e.to_string()),
+    }
+}
+
+#[cfg(not(feature = "sys"))]
+fn verify_artifact(_bytes: Vec<u8>) -> Verdict {
+    Verdict::Unchecked
+}
+
+fn verify_webc(path: &Path) -> Verdict {
+    if path.is_dir() {
+        return Verdict::Unchecked;
+    }
+    let webc = match webc::v1::WebCMmap::parse(path.to_path_buf(), &Default::default()) {
+        Ok(webc) => webc,
+        Err(e) => return Verdict::Corrupt(e.to_string()),
+    };
+    // Packages installed from the registry are named after their checksum
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_hexdigit()) {
+        let checksum = webc
+            .checksum
+            .as_ref()
+            .map(|c| wasmer_registry::get_checksum_hash(&c.data))
+            .unwrap_or_default();
+        if checksum != name {
+            return Verdict::Corrupt(format!("expected checksum {name}, found {checksum}"));
+        }
+    }
+    Verdict::Ok
+}
+
+fn verify_oci_blob(path: &Path) -> Verdict {
+    let expected = match path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .and_then(|s| s.strip_prefix("sha256-"))
+    {
+        Some(hex) => hex.to_ascii_lowercase(),
+        None => return Verdict::Unchecked,
+    };
+    match fs::read(path) {
+        Ok(bytes) => {
+            let actual = hex::encode(Sha256::digest(&bytes));
+            if actual == expected {
+                Verdict::Ok
+            } else {
+                Verdict::Corrupt(format!(
+                    "expected digest sha256:{expected}, found sha256:{actual}"
+                ))
+            }
+        }
+        Err(e) => Verdict::Corrupt(e.to_string()),
+    }
+}
+
+fn stats(locations: &[Location]) {
+    println!(
+        "{:<10} {:>8} {:>10} {:>9}  LOCATION",
+        "CACHE", "ENTRIES", "SIZE", "HIT RATE"
+    );
+
+    let mut total_entries = 0;
+    let mut total_size = 0;
+    for location in locations {
+        let entries = location.entries(locations);
+        let size: u64 = entries.iter().map(|e| e.size).sum();
+        let hit_rate = CacheStats::load(&location.dir)
+            .hit_rate()
+            .map(|rate| format!("{:.0}%", rate * 100.0))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<10} {:>8} {:>10} {:>9}  {}",
+            location.name,
+            entries.len(),
+            ByteSize(size).to_string(),
+            hit_rate,
+            location.dir.display()
+        );
+        total_entries += entries.len();
+        total_size += size;
+    }
+
+    println!(
+        "{:<10} {:>8} {:>10}",
+        "total",
+        total_entries,
+        ByteSize(total_size).to_string()
+    );
+}
+
+impl Prune {
+    fn execute(&self, locations: &[Location]) -> Result<()> {
+        if self.max_size.is_none() && self.max_age.is_none() {
+            anyhow::bail!("Nothing to prune, specify --max-size and/or --max-age");
+        }
+
+        let entries: Vec<Entry> = locations
+            .iter()
+            .flat_map(|location| location.entries(locations))
+            .collect();
+        let doomed = select_for_pruning(
+            &entries,
+            SystemTime::now(),
+            self.max_age,
+            self.max_size.map(|size| size.as_u64()),
+        );
+
+        let mut freed = 0;
+        for entry in doomed.iter() {
+            if self.dry_run {
+                println!("Would remove {}", entry.path.display());
+            } else {
+                remove(&entry.path)?;
+            }
+            freed += entry.size;
+        }
+
+        let verb = if self.dry_run { "Would free" } else { "Freed" };
+        eprintln!(
+            "{verb} {} by removing {} of {} entries",
+            ByteSize(freed),
+            doomed.len(),
+            entries.len()
+        );
+        Ok(())
+    }
+}
+
+/// Picks the entries to remove: everything unused for longer than
+/// `max_age`, then the least recently used entries until the rest fits in
+/// `max_size` bytes
+fn select_for_pruning(
+    entries: &[Entry],
+    now: SystemTime,
+    max_age: Option<Duration>,
+    max_size: Option<u64>,
+) -> Vec<Entry> {
+    let mut remaining: Vec<&Entry> = entries.iter().collect();
+    remaining.sort_by_key(|e| e.last_used);
+    let mut doomed = Vec::new();
+
+    if let Some(max_age) = max_age {
+        remaining.retain(|e| {
+            let age = now.duration_since(e.last_used).unwrap_or_default();
+            if age > max_age {
+                doomed.push((*e).clone());
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    if let Some(max_size) = max_size {
+        let mut size: u64 = remaining.iter().map(|e| e.size).sum();
+        for e in remaining {
+            if size <= max_size {
+                break;
+            }
+            size -= e.size;
+            doomed.push(e.clone());
+        }
+    }
+
+    doomed
+}
+
+impl Verify {
+    fn execute(&self, locations: &[Location]) -> Result<()> {
+        for location in locations {
+            let entries = location.entries(locations);
+            if entries.is_empty() {
+                continue;
+            }
+
+            let mut corrupt = 0;
+            let mut unchecked = 0;
+            for entry in entries.iter() {
+                match location.verify(entry) {
+                    Verdict::Ok => {}
+                    Verdict::Unchecked => unchecked += 1,
+                    Verdict::Corrupt(reason) => {
+                        corrupt += 1;
+                        if self.dry_run {
+                            println!("corrupt: {} ({reason})", entry.path.display());
+                        } else {
+                            remove(&entry.path)?;
+                            println!("removed: {} ({reason})", entry.path.display());
+                        }
+                    }
+                }
+            }
+
+            eprintln!(
+                "{}: {} checked, {corrupt} corrupt, {unchecked} can't be verified",
+                location.name,
+                entries.len() - unchecked,
+            );
+        }
+        Ok(())
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.starts_with('.'))
+        .unwrap_or(false)
+}
+
+/// The size and last use of a file, or of all the files in a directory
+fn entry(path: &Path) -> Entry {
+    let mut size = 0;
+    let mut last_used = SystemTime::UNIX_EPOCH;
+    for e in walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let metadata = match e.metadata() {
+            Ok(m) if m.is_file() => m,
+            _ => continue,
+        };
+        size += metadata.len();
+        for time in [metadata.modified(), metadata.accessed()]
+            .into_iter()
+            .flatten()
+        {
+            last_used = last_used.max(time);
+        }
+    }
+
+    Entry {
+        path: path.to_path_buf(),
+        size,
+        last_used,
+    }
+}
+
+fn remove(path: &Path) -> Result<()> {
+    let result = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    result.with_context(|| format!("Unable to remove \"{}\"", path.display()))
+}
+
+fn parse_age(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow::anyhow!("Missing unit in \"{s}\" (expected s, m, h, d or w)"))?;
+    let (amount, unit) = s.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .with_context(|| format!("Invalid duration \"{s}\""))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => anyhow::bail!("Unknown unit \"{unit}\" in \"{s}\" (expected s, m, h, d or w)"),
+    };
+    Ok(Duration::from_secs(amount * seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn entry_at(name: &str, size: u64, days_ago: u64, now: SystemTime) -> Entry {
+        Entry {
+            path: PathBuf::from(name),
+            size,
+            last_used: now - DAY * days_ago as u32,
+        }
+    }
+
+    fn names(entries: &[Entry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|e| e.path.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parse_ages() {
+        assert_eq!(parse_age("90m").unwrap(), Duration::from_secs(90 * 60));
+        assert_eq!(parse_age("30d").unwrap(), DAY * 30);
+        assert_eq!(parse_age("2w").unwrap(), DAY * 14);
+        assert!(parse_age("30").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("3y").is_err());
+    }
+
+    #[test]
+    fn prune_old_then_least_recently_used() {
+        let now = SystemTime::now();
+        let entries = [
+            entry_at("fresh", 10, 0, now),
+            entry_at("ancient", 1, 100, now),
+            entry_at("stale", 10, 5, now),
+            entry_at("recent", 10, 1, now),
+        ];
+
+        let doomed = select_for_pruning(&entries, now, Some(DAY * 30), None);
+        assert_eq!(names(&doomed), ["ancient"]);
+
+        let doomed = select_for_pruning(&entries, now, None, Some(20));
+        assert_eq!(names(&doomed), ["ancient", "stale", "recent"]);
+
+        let doomed = select_for_pruning(&entries, now, Some(DAY * 30), Some(25));
+        assert_eq!(names(&doomed), ["ancient", "stale"]);
+
+        assert!(select_for_pruning(&entries, now, None, Some(100)).is_empty());
+    }
+
+    #[test]
+    fn corrupt_entries_are_detected() {
+        let temp = tempfile::tempdir().unwrap();
+
+        let contents = b"not really a webc";
+        let digest = hex::encode(Sha256::digest(contents));
+        let good = temp.path().join(format!("sha256-{digest}.webc"));
+        fs::write(&good, contents).unwrap();
+        let bad = temp.path().join(format!("sha256-{}.webc", "0".repeat(64)));
+        fs::write(&bad, contents).unwrap();
+        fs::write(temp.path().join(".stats.json"), "{}").unwrap();
+
+        let oci = Location {
+            name: "oci",
+            kind: Kind::OciBlobs,
+            dir: temp.path().to_path_buf(),
+        };
+        let entries = oci.entries(&[]);
+        assert_eq!(entries.len(), 2);
+        for entry in entries.iter() {
+            let verdict = oci.verify(entry);
+            if entry.path == good {
+                assert_eq!(verdict, Verdict::Ok);
+            } else {
+                assert!(matches!(verdict, Verdict::Corrupt(_)));
+            }
+        }
+
+        let artifacts = Location {
+            name: "artifacts",
+            kind: Kind::Artifacts,
+            dir: temp.path().to_path_buf(),
+        };
+        #[cfg(feature = "sys")]
+        assert!(matches!(artifacts.verify(&entries[0]), Verdict::Corrupt(_)));
+        // The OCI blobs are nested in the artifacts cache but aren't part of it
+        assert!(artifacts.entries(&[artifacts.clone(), oci]).is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 use crate::store::StoreOptions;
 use crate::warning;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::Parser;
 use std::path::PathBuf;
 use wasmer::*;
@@ -25,6 +25,11 @@ pub struct Compile {
 
     #[clap(short = 'm')]
     cpu_features: Vec<CpuFeature>,
+
+    /// Compile the module a second time with a fresh engine and check that
+    /// both artifacts are byte-identical
+    #[clap(long = "verify")]
+    verify: bool,
 }
 
 impl Compile {
@@ -44,11 +49,7 @@ impl Compile {
                     .clone()
                     .into_iter()
                     .fold(CpuFeature::set(), |a, b| a | b);
-                // Cranelift requires SSE2, so we have this "hack" for now to facilitate
-                // usage
-                if target_triple.architecture == Architecture::X86_64 {
-                    features |= CpuFeature::SSE2;
-                }
+                features |= CpuFeature::baseline(target_triple);
                 Target::new(target_triple.clone(), features)
             })
             .unwrap_or_default();
@@ -74,7 +75,19 @@ impl Compile {
         println!("Target: {}", target.triple());
 
         let module = Module::from_file(&store, &self.path)?;
-        module.serialize_to_file(&self.output)?;
+        let serialized = module.serialize()?;
+        if self.verify {
+            let (store, _) = self.store.get_store_for_target(target)?;
+            let recompiled = Module::from_file(&store, &self.path)?.serialize()?;
+            if let Some(offset) = first_difference(&serialized, &recompiled) {
+                bail!(
+                    "the compilation is not reproducible: the artifacts differ at byte {}",
+                    offset
+                );
+            }
+            eprintln!("✔ Compilation is reproducible.");
+        }
+        std::fs::write(&self.output, &serialized)?;
         eprintln!(
             "✔ File compiled successfully to `{}`.",
             self.output.display(),
@@ -83,3 +96,11 @@ impl Compile {
         Ok(())
     }
 }
+
+/// Returns the offset of the first byte where `a` and `b` differ, if any.
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    match a.iter().zip(b).position(|(a, b)| a != b) {
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        offset => offset,
+    }
+}
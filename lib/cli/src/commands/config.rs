@@ -73,6 +73,12 @@ pub enum RetrievableConfigField {
     /// Print the token for the currently active registry or nothing if not logged in
     #[clap(name = "registry.token")]
     RegistryToken,
+    /// Print the name of the currently active profile or "none"
+    #[clap(name = "profile")]
+    Profile,
+    /// Print the saved profiles and the registry of each profile
+    #[clap(name = "profiles")]
+    Profiles,
     /// Print whether telemetry is currently enabled
     #[clap(name = "telemetry.enabled")]
     TelemetryEnabled,
@@ -93,6 +99,9 @@ pub enum StorableConfigField {
     /// Set the token for the currently active registry or nothing if not logged in
     #[clap(name = "registry.token")]
     RegistryToken(SetRegistryToken),
+    /// Set the currently active profile ("none" to use `registry.url`)
+    #[clap(name = "profile")]
+    Profile(SetProfile),
     /// Set whether telemetry is currently enabled
     #[clap(name = "telemetry.enabled")]
     TelemetryEnabled(SetTelemetryEnabled),
@@ -120,6 +129,14 @@ pub struct SetRegistryToken {
     pub token: String,
 }
 
+/// Set the current active profile
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Parser)]
+pub struct SetProfile {
+    /// Name of the profile, or "none"
+    #[clap(name = "NAME")]
+    pub name: String,
+}
+
 /// Set if update notifications are enabled
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Parser)]
 pub struct SetUpdateNotificationsEnabled {
@@ -261,6 +278,22 @@ impl GetOrSet {
                         println!("{s}");
                     }
                 }
+                RetrievableConfigField::Profile => match config.registry.current_profile() {
+                    Some((name, _)) => println!("{name}"),
+                    None => println!("none"),
+                },
+                RetrievableConfigField::Profiles => {
+                    let current = config.registry.current_profile().map(|(name, _)| name);
+                    for (name, profile) in &config.registry.profiles {
+                        let marker = if current == Some(name.as_str()) {
+                            "*"
+                        } else {
+                            " "
+                        };
+                        let keychain = if profile.keychain { " (keychain)" } else { "" };
+                        println!("{marker} {name}\t{}{keychain}", profile.registry);
+                    }
+                }
                 RetrievableConfigField::TelemetryEnabled => {
                     println!("{:?}", config.telemetry_enabled);
                 }
@@ -296,6 +329,13 @@ impl GetOrSet {
                             wasmer_registry::config::UpdateRegistry::LeaveAsIs,
                         );
                     }
+                    StorableConfigField::Profile(p) => {
+                        let name = Some(p.name.as_str()).filter(|name| *name != "none");
+                        config
+                            .registry
+                            .set_current_profile(name)
+                            .map_err(anyhow::Error::msg)?;
+                    }
                     StorableConfigField::TelemetryEnabled(t) => {
                         config.telemetry_enabled = t.enabled.0;
                     }
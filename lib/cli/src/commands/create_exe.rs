@@ -81,6 +81,12 @@ pub struct CreateExe {
     #[clap(long, short = 'l')]
     libraries: Vec<String>,
 
+    /// The command to run when the executable is started without
+    /// `--command` and under a name that isn't one of the package's
+    /// commands (defaults to the package's entrypoint)
+    #[clap(long)]
+    default_command: Option<String>,
+
     #[clap(flatten)]
     cross_compile: CrossCompile,
 
@@ -154,6 +160,26 @@ pub struct Entrypoint {
     pub atoms: Vec<CommandEntrypoint>,
     /// Volume objects (if any) to link into the final binary
     pub volumes: Vec<Volume>,
+    /// The commands of the package, if the input is a package. Otherwise,
+    /// each atom is its own command
+    #[serde(default)]
+    pub commands: Vec<PackageCommand>,
+    /// The command run when none is selected with `--command` or with the
+    /// name the binary was started as
+    #[serde(default)]
+    pub default_command: Option<String>,
+}
+
+/// A command of a package, which runs one of the atoms
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageCommand {
+    /// Command name
+    pub name: String,
+    /// Atom name
+    pub atom: String,
+    /// Arguments passed to the atom before the ones given on the command line
+    #[serde(default)]
+    pub main_args: Vec<String>,
 }
 
 /// Command entrypoint for multiple commands
@@ -257,6 +283,10 @@ impl CreateExe {
 
         get_module_infos(&store, &tempdir, &atoms)?;
         let mut entrypoint = get_entrypoint(&tempdir)?;
+        if let Some(default_command) = &self.default_command {
+            set_default_command(&tempdir, &mut entrypoint, default_command)?;
+        }
+        print_commands(&entrypoint);
         create_header_files_in_dir(&tempdir, &mut entrypoint, &atoms, &self.precompiled_atom)?;
         link_exe_from_dir(
             &tempdir,
@@ -285,6 +315,102 @@ impl CreateExe {
     }
 }
 
+fn set_default_command(
+    directory: &Path,
+    entrypoint: &mut Entrypoint,
+    default_command: &str,
+) -> Result<(), anyhow::Error> {
+    let names = command_names(entrypoint);
+    if !names.iter().any(|name| name == default_command) {
+        anyhow::bail!(
+            "there is no command named \"{default_command}\", the commands are: {}",
+            names.join(", ")
+        );
+    }
+    entrypoint.default_command = Some(default_command.to_string());
+    write_entrypoint(directory, entrypoint)
+}
+
+/// The names of the commands the executable can run
+fn command_names(entrypoint: &Entrypoint) -> Vec<String> {
+    if entrypoint.commands.is_empty() {
+        entrypoint.atoms.iter().map(|a| a.command.clone()).collect()
+    } else {
+        entrypoint.commands.iter().map(|c| c.name.clone()).collect()
+    }
+}
+
+fn print_commands(entrypoint: &Entrypoint) {
+    if entrypoint.commands.len() < 2 {
+        return;
+    }
+    println!("Commands (selected with --command or by renaming the executable):");
+    for command in &entrypoint.commands {
+        let default = if entrypoint.default_command.as_ref() == Some(&command.name) {
+            " (default)"
+        } else {
+            ""
+        };
+        if command.name == command.atom && command.main_args.is_empty() {
+            println!("  {}{default}", command.name);
+        } else {
+            let runs = std::iter::once(command.atom.as_str())
+                .chain(command.main_args.iter().map(|arg| arg.as_str()))
+                .collect::<Vec<_>>()
+                .join(" ");
+            println!("  {}{default}: runs `{runs}`", command.name);
+        }
+    }
+}
+
+/// The WASI commands of a package that run one of the given atoms, and the
+/// command to run by default.
+fn package_commands(pirita: &WebCMmap, atoms: &[String]) -> (Vec<PackageCommand>, Option<String>) {
+    use webc::metadata::annotations::{Wasi, WASI_RUNNER_URI};
+
+    let manifest = &pirita.manifest;
+    let mut commands = Vec::new();
+    for (name, command) in &manifest.commands {
+        if !command.runner.starts_with(WASI_RUNNER_URI) {
+            eprintln!(
+                "Warning: skipping command \"{name}\", only WASI commands are supported (runner: {})",
+                command.runner
+            );
+            continue;
+        }
+        let wasi = match command.annotation::<Wasi>("wasi") {
+            Ok(wasi) => wasi.unwrap_or_else(|| Wasi::new(name)),
+            Err(e) => {
+                eprintln!("Warning: skipping command \"{name}\", invalid WASI annotations: {e}");
+                continue;
+            }
+        };
+        if !atoms.contains(&wasi.atom) {
+            eprintln!(
+                "Warning: skipping command \"{name}\", its atom \"{}\" isn't in the package (atoms of dependencies need to be bundled into the package)",
+                wasi.atom
+            );
+            continue;
+        }
+        commands.push(PackageCommand {
+            name: name.clone(),
+            atom: wasi.atom,
+            main_args: wasi.main_args.unwrap_or_default(),
+        });
+    }
+
+    let default_command = manifest
+        .entrypoint
+        .clone()
+        .filter(|entrypoint| commands.iter().any(|c| &c.name == entrypoint))
+        .or_else(|| match commands.as_slice() {
+            [only] => Some(only.name.clone()),
+            _ => None,
+        });
+
+    (commands, default_command)
+}
+
 fn write_entrypoint(directory: &Path, entrypoint: &Entrypoint) -> Result<(), anyhow::Error> {
     std::fs::write(
         directory.join("entrypoint.json"),
@@ -403,6 +529,12 @@ pub(super) fn compile_pirita_into_directory(
         anyhow::anyhow!("cannot create /atoms dir in {}: {e}", target_dir.display())
     })?;
 
+    let atom_names = all_atoms
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    let (commands, default_command) = package_commands(pirita, &atom_names);
+
     let mut atoms_from_file = Vec::new();
     let mut target_paths = Vec::new();
 
@@ -472,6 +604,8 @@ pub(super) fn compile_pirita_into_directory(
             name: volume_name.to_string(),
             obj_file: volume_path,
         }],
+        commands,
+        default_command,
     };
 
     write_entrypoint(&target_dir, &entrypoint)?;
@@ -904,6 +1038,8 @@ pub(super) fn prepare_directory_from_single_wasm_file(
     let entrypoint = Entrypoint {
         atoms,
         volumes: Vec::new(),
+        commands: Vec::new(),
+        default_command: None,
     };
 
     write_entrypoint(&target_dir, &entrypoint)?;
@@ -1343,20 +1479,39 @@ fn generate_wasmer_main_c(
         .map(|a| &a.command)
         .collect::<Vec<_>>();
 
-    let c_code_to_add = String::new();
-    let mut c_code_to_instantiate = String::new();
-    let mut deallocate_module = String::new();
-    let mut extra_headers = Vec::new();
+    // Without package commands, each atom is its own command
+    let commands = if entrypoint.commands.is_empty() {
+        entrypoint
+            .atoms
+            .iter()
+            .map(|a| PackageCommand {
+                name: a.command.clone(),
+                atom: a.command.clone(),
+                main_args: Vec::new(),
+            })
+            .collect()
+    } else {
+        entrypoint.commands.clone()
+    };
 
-    for a in atom_names.iter() {
-        let prefix = prefixes
-            .get_prefix_for_atom(&utils::normalize_atom_name(a))
+    let get_prefix = |atom: &str| {
+        prefixes
+            .get_prefix_for_atom(&utils::normalize_atom_name(atom))
             .ok_or_else(|| {
                 anyhow::anyhow!(
-                    "cannot find prefix for atom {a} when generating wasmer_main.c ({:#?})",
+                    "cannot find prefix for atom {atom} when generating wasmer_main.c ({:#?})",
                     prefixes
                 )
-            })?;
+            })
+    };
+
+    let mut c_code_to_add = String::new();
+    let mut c_code_to_instantiate = String::new();
+    let mut deallocate_module = String::new();
+    let mut extra_headers = Vec::new();
+
+    for a in atom_names.iter() {
+        let prefix = get_prefix(a)?;
         let atom_name = prefix.clone();
 
         extra_headers.push(format!("#include \"static_defs_{atom_name}.h\""));
@@ -1373,6 +1528,22 @@ fn generate_wasmer_main_c(
         write!(deallocate_module, "wasm_module_delete(atom_{atom_name});")?;
     }
 
+    // The arguments each command passes before the ones from the command line
+    for (i, command) in commands.iter().enumerate() {
+        if !command.main_args.is_empty() {
+            writeln!(
+                c_code_to_add,
+                "static const char* command_main_args_{i}[] = {{{}}};",
+                command
+                    .main_args
+                    .iter()
+                    .map(|arg| c_string_literal(arg))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+    }
+
     let volumes_str = entrypoint
         .volumes
         .iter()
@@ -1401,36 +1572,49 @@ fn generate_wasmer_main_c(
         .replace("// DECLARE_VOLUMES", &volumes_str)
         .replace(
             "// SET_NUMBER_OF_COMMANDS",
-            &format!("number_of_commands = {};", atom_names.len()),
+            &format!("number_of_commands = {};", commands.len()),
         )
         .replace("// EXTRA_HEADERS", &extra_headers.join("\r\n"))
         .replace("wasm_module_delete(module);", &deallocate_module);
 
-    if atom_names.len() == 1 {
-        let prefix = prefixes
-            .get_prefix_for_atom(&utils::normalize_atom_name(atom_names[0]))
-            .ok_or_else(|| {
-                anyhow::anyhow!(
-                    "cannot find prefix for atom {} when generating wasmer_main.c ({:#?})",
-                    &atom_names[0],
-                    prefixes
-                )
-            })?;
-        write!(c_code_to_instantiate, "module = atom_{prefix};")?;
+    let select_command = |i: usize, command: &PackageCommand| -> Result<String, anyhow::Error> {
+        let mut code = format!("module = atom_{};", get_prefix(&command.atom)?);
+        if !command.main_args.is_empty() {
+            write!(
+                code,
+                " main_args = command_main_args_{i}; number_of_main_args = {};",
+                command.main_args.len()
+            )?;
+        }
+        Ok(code)
+    };
+
+    if commands.len() == 1 {
+        write!(
+            c_code_to_instantiate,
+            "{}",
+            select_command(0, &commands[0])?
+        )?;
     } else {
-        for a in atom_names.iter() {
-            let prefix = prefixes
-                .get_prefix_for_atom(&utils::normalize_atom_name(a))
-                .ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "cannot find prefix for atom {a} when generating wasmer_main.c ({:#?})",
-                        prefixes
-                    )
-                })?;
+        for (i, command) in commands.iter().enumerate() {
+            writeln!(
+                c_code_to_instantiate,
+                "if (is_command(selected_command, {})) {{ selected_atom = {}; {} }}",
+                c_string_literal(&command.name),
+                c_string_literal(&command.atom),
+                select_command(i, command)?,
+            )?;
+        }
+        if let Some((i, command)) = commands
+            .iter()
+            .enumerate()
+            .find(|(_, c)| Some(&c.name) == entrypoint.default_command.as_ref())
+        {
             writeln!(
                 c_code_to_instantiate,
-                "if (strcmp(selected_atom, \"{a}\") == 0) {{ module = atom_{}; }}",
-                prefix
+                "if (!module && !command_was_invoked) {{ selected_atom = {}; {} }}",
+                c_string_literal(&command.atom),
+                select_command(i, command)?,
             )?;
         }
     }
@@ -1439,16 +1623,20 @@ fn generate_wasmer_main_c(
         c_code_to_instantiate,
         "
     if (!module) {{
-        fprintf(stderr, \"No --command given, available commands are:\\n\");
+        if (command_was_invoked) {{
+            fprintf(stderr, \"Unknown command \\\"%s\\\", available commands are:\\n\", selected_command);
+        }} else {{
+            fprintf(stderr, \"No --command given, available commands are:\\n\");
+        }}
         fprintf(stderr, \"\\n\");
         {commands}
         fprintf(stderr, \"\\n\");
         return -1;
     }}
     ",
-        commands = atom_names
+        commands = commands
             .iter()
-            .map(|a| format!("fprintf(stderr, \"    {a}\\n\");"))
+            .map(|c| format!("fprintf(stderr, \"    %s\\n\", {});", c_string_literal(&c.name)))
             .collect::<Vec<_>>()
             .join("\n")
     )?;
@@ -1456,6 +1644,69 @@ fn generate_wasmer_main_c(
     Ok(return_str.replace("// INSTANTIATE_MODULES", &c_code_to_instantiate))
 }
 
+#[test]
+fn test_generate_main_c_with_commands() {
+    let atoms = vec![("python".to_string(), b"\0asm\x01\0\0\0".to_vec())];
+    let prefixes = PrefixMapCompilation::from_input(&atoms, &["py".to_string()], false).unwrap();
+    let entrypoint = Entrypoint {
+        atoms: vec![CommandEntrypoint {
+            command: "python".to_string(),
+            atom: "python".to_string(),
+            path: PathBuf::from("atoms/python.o"),
+            header: None,
+            module_info: None,
+        }],
+        volumes: Vec::new(),
+        commands: vec![
+            PackageCommand {
+                name: "python".to_string(),
+                atom: "python".to_string(),
+                main_args: Vec::new(),
+            },
+            PackageCommand {
+                name: "pip".to_string(),
+                atom: "python".to_string(),
+                main_args: vec!["-m".to_string(), "pip".to_string()],
+            },
+        ],
+        default_command: Some("python".to_string()),
+    };
+
+    let main_c = generate_wasmer_main_c(&entrypoint, &prefixes).unwrap();
+
+    assert!(main_c.contains("number_of_commands = 2;"));
+    assert!(main_c.contains(r#"static const char* command_main_args_1[] = {"-m", "pip"};"#));
+    assert!(main_c.contains(
+        r#"if (is_command(selected_command, "pip")) { selected_atom = "python"; module = atom_py; main_args = command_main_args_1; number_of_main_args = 2; }"#
+    ));
+    assert!(main_c.contains(
+        r#"if (!module && !command_was_invoked) { selected_atom = "python"; module = atom_py; }"#
+    ));
+}
+
+#[test]
+fn test_c_string_literal() {
+    assert_eq!(c_string_literal("pip"), r#""pip""#);
+    assert_eq!(c_string_literal(r#"say "hi"\n"#), r#""say \"hi\"\\n""#);
+    assert_eq!(c_string_literal("a\nb"), r#""a\012b""#);
+}
+
+/// Quote a string as a C string literal
+fn c_string_literal(s: &str) -> String {
+    let mut literal = String::from("\"");
+    for b in s.bytes() {
+        match b {
+            b'"' => literal.push_str("\\\""),
+            b'\\' => literal.push_str("\\\\"),
+            b' '..=b'~' => literal.push(b as char),
+            // Octal escapes can't swallow the characters that follow
+            _ => literal.push_str(&format!("\\{b:03o}")),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
 #[allow(dead_code)]
 pub(super) mod utils {
 
@@ -1473,11 +1724,7 @@ pub(super) mod utils {
         cpu_features: &[CpuFeature],
     ) -> Target {
         let mut features = cpu_features.iter().fold(CpuFeature::set(), |a, b| a | *b);
-        // Cranelift requires SSE2, so we have this "hack" for now to facilitate
-        // usage
-        if target_triple.architecture == Architecture::X86_64 {
-            features |= CpuFeature::SSE2;
-        }
+        features |= CpuFeature::baseline(target_triple);
         Target::new(target_triple.clone(), features)
     }
 
@@ -1,26 +1,46 @@
 #![allow(dead_code)]
 //! Create a standalone native executable for a given Wasm file.
 
+use crate::c_gen::staticlib_header::{generate_header_file_for_modules, StaticModule};
+use crate::commands::create_exe::utils::normalize_atom_name;
+use crate::commands::PrefixMapCompilation;
 use crate::store::CompilerOptions;
 use anyhow::{Context, Result};
 use clap::Parser;
 use std::env;
-
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 use wasmer::*;
+use wasmer_types::compilation::symbols::ModuleMetadataSymbolRegistry;
 
 #[derive(Debug, Parser)]
 /// The options for the `wasmer create-exe` subcommand
 pub struct CreateObj {
-    /// Input file
-    #[clap(name = "FILE")]
-    path: PathBuf,
+    /// Input files
+    ///
+    /// When several files are given, all of their modules are compiled
+    /// into the same object file.
+    #[clap(name = "FILE", required = true)]
+    paths: Vec<PathBuf>,
 
     /// Output file or directory if the input is a pirita file
     #[clap(name = "OUTPUT_PATH", short = 'o')]
     output: PathBuf,
 
+    /// Compile every atom of the pirita files into the object file,
+    /// instead of a single one
+    #[clap(long, conflicts_with = "ATOM")]
+    all_atoms: bool,
+
+    /// Header file exposing the modules of the object file, when several
+    /// modules are compiled into it
+    ///
+    /// Default value = the output file with the `.h` extension
+    #[clap(long, name = "HEADER")]
+    header: Option<PathBuf>,
+
     /// Optional directorey used for debugging: if present, will
     /// output the files to a debug instead of a temp directory
     #[clap(long, name = "DEBUG PATH")]
@@ -28,6 +48,9 @@ pub struct CreateObj {
 
     /// Prefix for the function names in the input file in the compiled object file.
     ///
+    /// When several modules are compiled, the prefix of each of them is
+    /// `{PREFIX}_{module name}`.
+    ///
     /// Default value = sha256 of the input file
     #[clap(long, name = "PREFIX")]
     prefix: Option<String>,
@@ -61,7 +84,10 @@ pub struct CreateObj {
 impl CreateObj {
     /// Runs logic for the `create-obj` subcommand
     pub fn execute(&self) -> Result<()> {
-        let path = crate::common::normalize_path(&format!("{}", self.path.display()));
+        if self.paths.len() > 1 || self.all_atoms {
+            return self.execute_modules();
+        }
+        let path = crate::common::normalize_path(&format!("{}", self.paths[0].display()));
         let target_triple = self.target_triple.clone().unwrap_or_else(Triple::host);
         let starting_cd = env::current_dir()?;
         let input_path = starting_cd.join(&path);
@@ -170,4 +196,146 @@ impl CreateObj {
 
         Ok(())
     }
+
+    /// Compiles the modules of every input file into a single object file,
+    /// and writes the header file exposing each of them.
+    fn execute_modules(&self) -> Result<()> {
+        let target_triple = self.target_triple.clone().unwrap_or_else(Triple::host);
+        let target = crate::commands::create_exe::utils::target_triple_to_target(
+            &target_triple,
+            &self.cpu_features,
+        );
+        let (engine, compiler_type) = self.compiler.get_engine_for_target(target.clone())?;
+        println!("Compiler: {}", compiler_type.to_string());
+        println!("Target: {}", target.triple());
+
+        let mut modules = Vec::new();
+        for path in &self.paths {
+            for (name, bytes) in self.read_modules(path)? {
+                let atom_name = normalize_atom_name(&name);
+                let prefix = match self.prefix.as_ref() {
+                    Some(prefix) => format!("{prefix}_{atom_name}"),
+                    None => PrefixMapCompilation::hash_for_bytes(&bytes),
+                };
+                if modules
+                    .iter()
+                    .any(|(other, other_prefix, _)| *other == atom_name || *other_prefix == prefix)
+                {
+                    anyhow::bail!(
+                        "cannot compile module {name:?} of {}: another module has the same name or contents",
+                        path.display()
+                    );
+                }
+                modules.push((atom_name, prefix, bytes));
+            }
+        }
+
+        let engine_inner = engine.inner();
+        let compiler = engine_inner.compiler()?;
+        let mut object = wasmer_object::get_object_for_target(target.triple())?;
+        let mut compiled = Vec::new();
+        for (atom_name, prefix, bytes) in &modules {
+            let (module_info, metadata_length, _) = Artifact::generate_object_into(
+                &mut object,
+                compiler,
+                bytes,
+                Some(prefix),
+                &target,
+                engine.tunables(),
+                engine_inner.features(),
+            )
+            .with_context(|| anyhow::anyhow!("could not compile module {atom_name:?}"))?;
+            compiled.push((
+                atom_name,
+                ModuleMetadataSymbolRegistry {
+                    prefix: prefix.clone(),
+                },
+                module_info,
+                metadata_length,
+            ));
+        }
+
+        if let Some(parent) = self.output.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut writer = BufWriter::new(File::create(&self.output)?);
+        object
+            .write_stream(&mut writer)
+            .map_err(|err| anyhow::anyhow!(err.to_string()))?;
+        writer.flush()?;
+
+        let header = generate_header_file_for_modules(
+            &compiled
+                .iter()
+                .map(
+                    |(atom_name, symbol_registry, module_info, metadata_length)| StaticModule {
+                        atom_name,
+                        module_info,
+                        symbol_registry,
+                        metadata_length: *metadata_length,
+                    },
+                )
+                .collect::<Vec<_>>(),
+        );
+        let header_path = self
+            .header
+            .clone()
+            .unwrap_or_else(|| self.output.with_extension("h"));
+        std::fs::write(&header_path, header)
+            .map_err(|e| anyhow::anyhow!("could not write {}: {e}", header_path.display()))?;
+
+        let output_file = self.output.canonicalize().unwrap().display().to_string();
+        let output_file = output_file
+            .strip_prefix(r"\\?\")
+            .unwrap_or(&output_file)
+            .to_string();
+        let names = compiled
+            .iter()
+            .map(|(atom_name, ..)| atom_name.as_str())
+            .collect::<Vec<_>>();
+        eprintln!(
+            "✔ Object with modules {} compiled successfully to `{output_file}`",
+            names.join(", ")
+        );
+
+        Ok(())
+    }
+
+    /// Returns the modules of a wasm or pirita file, with their names.
+    fn read_modules(&self, path: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+        let path = env::current_dir()?.join(crate::common::normalize_path(&format!(
+            "{}",
+            path.display()
+        )));
+        if let Ok(pirita) =
+            webc::v1::WebCMmap::parse(path.clone(), &webc::v1::ParseOptions::default())
+        {
+            return match self.atom.as_ref() {
+                Some(atom) => {
+                    let bytes = pirita
+                        .get_atom(&pirita.get_package_name(), atom)
+                        .with_context(|| {
+                            anyhow::anyhow!(
+                                "could not find atom {atom} in package {}",
+                                pirita.get_package_name()
+                            )
+                        })?;
+                    Ok(vec![(atom.clone(), bytes.to_vec())])
+                }
+                None => Ok(pirita
+                    .get_all_atoms()
+                    .into_iter()
+                    .map(|(name, bytes)| (name, bytes.to_vec()))
+                    .collect()),
+            };
+        }
+        let bytes = std::fs::read(&path)
+            .map_err(|e| anyhow::anyhow!("could not read {}: {e}", path.display()))?;
+        let name = path
+            .file_stem()
+            .and_then(|f| f.to_str())
+            .unwrap_or("main")
+            .to_string();
+        Ok(vec![(name, bytes)])
+    }
 }
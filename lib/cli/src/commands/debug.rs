@@ -0,0 +1,738 @@
+use crate::utils::read_module_bytes;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use super::repl::{hex_dump, parse_int, split_words};
+
+/// The options for the `wasmer debug` subcommand
+#[derive(Debug, Parser)]
+pub struct Debugger {
+    /// The WebAssembly file or package which trapped
+    #[clap(name = "FILE")]
+    path: PathBuf,
+
+    /// The coredump written when it trapped (e.g. by
+    /// `wasmer run --coredump-on-trap`)
+    #[clap(name = "COREDUMP")]
+    coredump: PathBuf,
+
+    /// The atom which trapped, when FILE is a package with several atoms
+    #[clap(long)]
+    atom: Option<String>,
+
+    /// How many bytes of memory to print from the shadow stack pointer
+    #[clap(long, default_value = "64")]
+    context: u64,
+
+    /// Explore the coredump interactively after printing the backtrace
+    #[clap(short, long)]
+    interactive: bool,
+}
+
+const HELP: &str = "\
+Commands:
+  bt                               Print the backtrace
+  frame <n>                        Print the locals and the operand stack of a frame
+  globals                          Print the globals
+  memories                         Print the size of each memory
+  read <offset> <len> [memory]     Print the bytes of a memory
+  help                             Print this message
+  quit                             Leave the debugger";
+
+impl Debugger {
+    /// Runs logic for the `debug` subcommand
+    pub fn execute(&self) -> Result<()> {
+        let inspector = self.load().with_context(|| {
+            format!(
+                "failed to load the coredump `{}` of `{}`",
+                self.coredump.display(),
+                self.path.display()
+            )
+        })?;
+
+        print!("{}", inspector.summary(self.context));
+
+        if self.interactive {
+            let stdin = std::io::stdin();
+            let mut lines = stdin.lock().lines();
+            loop {
+                print!("(debug) ");
+                std::io::stdout().flush()?;
+
+                let line = match lines.next() {
+                    Some(line) => line?,
+                    None => break,
+                };
+                let line = line.trim();
+                if line == "quit" || line == "exit" {
+                    break;
+                }
+
+                match inspector.eval(line) {
+                    Ok(output) if output.is_empty() => {}
+                    Ok(output) => println!("{output}"),
+                    Err(e) => eprintln!("error: {e:#}"),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Inspector> {
+        let module = read_module_bytes(&self.path, self.atom.as_deref())?;
+        let dump = std::fs::read(&self.coredump)?;
+        Inspector::new(&module, &dump)
+    }
+}
+
+/// A coredump, and the names of the module it was taken from.
+struct Inspector {
+    dump: Coredump,
+    names: Names,
+}
+
+impl Inspector {
+    fn new(module: &[u8], dump: &[u8]) -> Result<Self> {
+        let dump = Coredump::parse(dump).context("invalid coredump")?;
+        // Names are a nicety: a module without (valid) names can still be
+        // debugged.
+        let names = Names::parse(module).unwrap_or_default();
+        Ok(Inspector { dump, names })
+    }
+
+    /// Everything printed before the interactive session: the backtrace,
+    /// the globals, the memories and the memory at the shadow stack pointer.
+    fn summary(&self, context: u64) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "Coredump of `{}`", self.dump.executable);
+        let _ = writeln!(out, "{}", self.backtrace());
+        let _ = writeln!(out, "Globals:\n{}", self.globals());
+        let _ = writeln!(out, "Memories:\n{}", self.memories());
+
+        if let Some((name, address)) = self.stack_pointer() {
+            if context > 0 && !self.dump.memories.is_empty() {
+                let _ = writeln!(out, "Memory at {name} ({address:#x}):");
+                let _ = writeln!(
+                    out,
+                    "{}",
+                    hex_dump(address, &self.dump.read(0, address, context))
+                );
+            }
+        }
+
+        out
+    }
+
+    /// Evaluates one line of input, returning what should be printed.
+    fn eval(&self, line: &str) -> Result<String> {
+        let words = split_words(line)?;
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (command.as_str(), args),
+            None => return Ok(String::new()),
+        };
+
+        match (command, args) {
+            ("help", _) => Ok(HELP.to_string()),
+            ("bt" | "backtrace", []) => Ok(self.backtrace()),
+            ("frame", [n]) => self.frame(parse_int(n)?),
+            ("globals", []) => Ok(self.globals()),
+            ("memories", []) => Ok(self.memories()),
+            ("read", [offset, len]) => self.read("0", offset, len),
+            ("read", [offset, len, memory]) => self.read(memory, offset, len),
+            _ => bail!("unknown command `{line}`, type `help` for a list of commands"),
+        }
+    }
+
+    fn backtrace(&self) -> String {
+        let mut out = String::new();
+        for thread in &self.dump.threads {
+            let _ = writeln!(out, "Thread `{}`:", thread.name);
+            for (n, frame) in thread.frames.iter().enumerate() {
+                let _ = writeln!(out, "  #{n:<3} {}", self.frame_name(frame));
+            }
+        }
+        out.trim_end().to_string()
+    }
+
+    fn frame_name(&self, frame: &Frame) -> String {
+        let module = self
+            .dump
+            .instances
+            .get(frame.instance as usize)
+            .and_then(|instance| self.dump.modules.get(instance.module as usize))
+            .filter(|name| !name.is_empty())
+            .map(String::as_str)
+            .or(self.names.module.as_deref())
+            .unwrap_or("<module>");
+        let function = match self.names.functions.get(&frame.function) {
+            Some(name) => name.clone(),
+            None => format!("<func{}>", frame.function),
+        };
+        format!(
+            "{module}!{function} (func {}) + {:#x}",
+            frame.function, frame.code_offset
+        )
+    }
+
+    fn frame(&self, n: usize) -> Result<String> {
+        let frame = self
+            .dump
+            .threads
+            .first()
+            .and_then(|thread| thread.frames.get(n))
+            .ok_or_else(|| anyhow!("there is no frame #{n}"))?;
+
+        let mut out = format!("#{n} {}\n", self.frame_name(frame));
+        out.push_str("Locals:\n");
+        out.push_str(&values(&frame.locals));
+        out.push_str("\nOperand stack:\n");
+        out.push_str(&values(&frame.stack));
+        Ok(out)
+    }
+
+    fn globals(&self) -> String {
+        let names = self.global_names();
+        self.dump
+            .globals
+            .iter()
+            .enumerate()
+            .map(|(index, global)| {
+                let mutability = if global.mutable { "mut " } else { "" };
+                let name = match names.get(&(index as u32)) {
+                    Some(name) => format!(" ({name})"),
+                    None => String::new(),
+                };
+                format!("  {index}: {mutability}{}{name}", global.value)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn memories(&self) -> String {
+        self.dump
+            .memories
+            .iter()
+            .enumerate()
+            .map(|(index, memory)| {
+                format!(
+                    "  {index}: {} pages ({} bytes)",
+                    memory.pages,
+                    memory.pages * WASM_PAGE_SIZE
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn read(&self, memory: &str, offset: &str, len: &str) -> Result<String> {
+        let memory: usize = parse_int(memory)?;
+        let offset: u64 = parse_int(offset)?;
+        let len: u64 = parse_int(len)?;
+        let pages = self
+            .dump
+            .memories
+            .get(memory)
+            .ok_or_else(|| anyhow!("there is no memory {memory}"))?
+            .pages;
+        if offset.saturating_add(len) > pages * WASM_PAGE_SIZE {
+            bail!("out of bounds read of memory {memory}");
+        }
+        Ok(hex_dump(offset, &self.dump.read(memory, offset, len)))
+    }
+
+    /// The names of the globals of the store, from the names of the globals
+    /// of the module in the instance which owns them.
+    fn global_names(&self) -> HashMap<u32, &str> {
+        let mut names = HashMap::new();
+        for instance in &self.dump.instances {
+            for (local, global) in instance.globals.iter().enumerate() {
+                if let Some(name) = self.names.globals.get(&(local as u32)) {
+                    names.insert(*global, name.as_str());
+                }
+            }
+        }
+        names
+    }
+
+    /// The shadow stack pointer of the first memory, which is where
+    /// compilers like clang and rustc spill the locals of each function.
+    ///
+    /// It's the global called `__stack_pointer` or, in a module without
+    /// names, the first mutable `i32` global.
+    fn stack_pointer(&self) -> Option<(&str, u64)> {
+        let names = self.global_names();
+        let index = match names.iter().find(|(_, name)| **name == "__stack_pointer") {
+            Some((index, _)) => *index as usize,
+            None => self
+                .dump
+                .globals
+                .iter()
+                .position(|g| g.mutable && matches!(g.value, Value::I32(_)))?,
+        };
+        match self.dump.globals.get(index)?.value {
+            Value::I32(address) => Some(("__stack_pointer", address as u32 as u64)),
+            _ => None,
+        }
+    }
+}
+
+fn values(values: &[Option<Value>]) -> String {
+    if values.is_empty() {
+        return "  (not recorded)".to_string();
+    }
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| match value {
+            Some(value) => format!("  {index}: {value}"),
+            None => format!("  {index}: <optimized out>"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const WASM_PAGE_SIZE: u64 = 0x10000;
+
+/// The contents of a coredump, in the [wasm-coredump] format.
+///
+/// [wasm-coredump]: https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md
+#[derive(Debug, Default)]
+struct Coredump {
+    executable: String,
+    modules: Vec<String>,
+    instances: Vec<CoreInstance>,
+    threads: Vec<Thread>,
+    memories: Vec<CoreMemory>,
+    globals: Vec<CoreGlobal>,
+}
+
+#[derive(Debug)]
+struct CoreInstance {
+    module: u32,
+    globals: Vec<u32>,
+}
+
+#[derive(Debug)]
+struct Thread {
+    name: String,
+    frames: Vec<Frame>,
+}
+
+#[derive(Debug)]
+struct Frame {
+    instance: u32,
+    function: u32,
+    code_offset: u32,
+    locals: Vec<Option<Value>>,
+    stack: Vec<Option<Value>>,
+}
+
+#[derive(Debug)]
+struct CoreMemory {
+    pages: u64,
+    /// The initialized part of the memory; the rest is zeroed.
+    data: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct CoreGlobal {
+    mutable: bool,
+    value: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    V128(u128),
+    Null(&'static str),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::I32(v) => write!(f, "{v}: i32"),
+            Value::I64(v) => write!(f, "{v}: i64"),
+            Value::F32(v) => write!(f, "{v}: f32"),
+            Value::F64(v) => write!(f, "{v}: f64"),
+            Value::V128(v) => write!(f, "{v:#034x}: v128"),
+            Value::Null(ty) => write!(f, "null: {ty}"),
+        }
+    }
+}
+
+impl Coredump {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::module(bytes)?;
+        let mut dump = Coredump::default();
+
+        while !reader.is_empty() {
+            let (id, mut section) = reader.section()?;
+            match id {
+                0 => match section.name()?.as_str() {
+                    "core" => {
+                        section.tag(0)?;
+                        dump.executable = section.name()?;
+                    }
+                    "coremodules" => {
+                        dump.modules = section.vec(|r| {
+                            r.tag(0)?;
+                            r.name()
+                        })?;
+                    }
+                    "coreinstances" => {
+                        dump.instances = section.vec(|r| {
+                            r.tag(0)?;
+                            let module = r.u32()?;
+                            let _memories = r.vec(Reader::u32)?;
+                            let globals = r.vec(Reader::u32)?;
+                            Ok(CoreInstance { module, globals })
+                        })?;
+                    }
+                    "corestack" => {
+                        section.tag(0)?;
+                        let name = section.name()?;
+                        let frames = section.vec(|r| {
+                            r.tag(0)?;
+                            Ok(Frame {
+                                instance: r.u32()?,
+                                function: r.u32()?,
+                                code_offset: r.u32()?,
+                                locals: r.vec(Reader::frame_value)?,
+                                stack: r.vec(Reader::frame_value)?,
+                            })
+                        })?;
+                        dump.threads.push(Thread { name, frames });
+                    }
+                    _ => {}
+                },
+                // The memory section
+                5 => {
+                    dump.memories = section.vec(|r| {
+                        let flags = r.u8()?;
+                        let pages = r.u32()? as u64;
+                        if flags & 1 != 0 {
+                            let _maximum = r.u32()?;
+                        }
+                        Ok(CoreMemory {
+                            pages,
+                            data: Vec::new(),
+                        })
+                    })?;
+                }
+                // The global section
+                6 => {
+                    dump.globals = section.vec(|r| {
+                        let _ty = r.u8()?;
+                        let mutable = r.u8()? == 1;
+                        let value = r.const_expr()?;
+                        Ok(CoreGlobal { mutable, value })
+                    })?;
+                }
+                // The data section
+                11 => {
+                    let segments = section.vec(|r| {
+                        let memory = match r.u32()? {
+                            0 => 0,
+                            2 => r.u32()?,
+                            flags => bail!("unsupported data segment kind {flags}"),
+                        };
+                        let offset = match r.const_expr()? {
+                            Value::I32(offset) => offset as u32 as u64,
+                            Value::I64(offset) => offset as u64,
+                            other => bail!("invalid data segment offset {other}"),
+                        };
+                        let len = r.u32()? as usize;
+                        Ok((memory, offset, r.bytes(len)?.to_vec()))
+                    })?;
+                    for (memory, offset, bytes) in segments {
+                        let memory = dump
+                            .memories
+                            .get_mut(memory as usize)
+                            .ok_or_else(|| anyhow!("data for unknown memory {memory}"))?;
+                        let end = offset as usize + bytes.len();
+                        if memory.data.len() < end {
+                            memory.data.resize(end, 0);
+                        }
+                        memory.data[offset as usize..end].copy_from_slice(&bytes);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if dump.threads.is_empty() {
+            bail!("the file has no `corestack` section, it isn't a coredump");
+        }
+        Ok(dump)
+    }
+
+    /// Reads `len` bytes of a memory, past its initialized data if needed.
+    fn read(&self, memory: usize, offset: u64, len: u64) -> Vec<u8> {
+        let data = self
+            .memories
+            .get(memory)
+            .map_or(&[][..], |m| m.data.as_slice());
+        (offset..offset.saturating_add(len))
+            .map(|i| data.get(i as usize).copied().unwrap_or(0))
+            .collect()
+    }
+}
+
+/// The names in the `name` custom section of a module.
+#[derive(Debug, Default)]
+struct Names {
+    module: Option<String>,
+    functions: HashMap<u32, String>,
+    globals: HashMap<u32, String>,
+}
+
+impl Names {
+    fn parse(module: &[u8]) -> Result<Self> {
+        let mut reader = Reader::module(module)?;
+        let mut names = Names::default();
+
+        while !reader.is_empty() {
+            let (id, mut section) = reader.section()?;
+            if id != 0 || section.name()? != "name" {
+                continue;
+            }
+            while !section.is_empty() {
+                let (id, mut subsection) = section.section()?;
+                let map = |r: &mut Reader<'_>| -> Result<HashMap<u32, String>> {
+                    Ok(r.vec(|r| Ok((r.u32()?, r.name()?)))?.into_iter().collect())
+                };
+                match id {
+                    0 => names.module = Some(subsection.name()?),
+                    1 => names.functions = map(&mut subsection)?,
+                    7 => names.globals = map(&mut subsection)?,
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(names)
+    }
+}
+
+/// Reads the binary encoding of WebAssembly.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// A reader over the sections of a module.
+    fn module(bytes: &'a [u8]) -> Result<Self> {
+        match bytes {
+            [0, b'a', b's', b'm', 1, 0, 0, 0, sections @ ..] => Ok(Reader { bytes: sections }),
+            _ => bail!("not a WebAssembly module"),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            bail!("unexpected end of the file");
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn tag(&mut self, expected: u8) -> Result<()> {
+        match self.u8()? {
+            tag if tag == expected => Ok(()),
+            tag => bail!("unexpected tag {tag:#x}, expected {expected:#x}"),
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let mut value = 0u64;
+        for shift in (0..35).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return u32::try_from(value).map_err(|_| anyhow!("integer too large"));
+            }
+        }
+        bail!("integer too large")
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                value |= ((byte & 0x7f) as i64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return Ok(value);
+            }
+            if shift > 70 {
+                bail!("integer too large");
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+
+    fn vec<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T>) -> Result<Vec<T>> {
+        let len = self.u32()?;
+        (0..len).map(|_| item(self)).collect()
+    }
+
+    /// The id and the contents of the next section (or subsection).
+    fn section(&mut self) -> Result<(u8, Reader<'a>)> {
+        let id = self.u8()?;
+        let len = self.u32()? as usize;
+        Ok((
+            id,
+            Reader {
+                bytes: self.bytes(len)?,
+            },
+        ))
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn const_expr(&mut self) -> Result<Value> {
+        let value = match self.u8()? {
+            0x41 => Value::I32(self.i64()? as i32),
+            0x42 => Value::I64(self.i64()?),
+            0x43 => Value::F32(f32::from_le_bytes(self.array()?)),
+            0x44 => Value::F64(f64::from_le_bytes(self.array()?)),
+            0xfd => {
+                self.tag(0x0c)?;
+                Value::V128(u128::from_le_bytes(self.array()?))
+            }
+            0xd0 => Value::Null(ref_type(self.u8()?)),
+            op => bail!("unsupported instruction {op:#x} in a constant expression"),
+        };
+        self.tag(0x0b)?;
+        Ok(value)
+    }
+
+    /// A local or a value of the operand stack, which may have been
+    /// optimized out.
+    fn frame_value(&mut self) -> Result<Option<Value>> {
+        let value = match self.u8()? {
+            0x01 => return Ok(None),
+            0x7f => Value::I32(self.i64()? as i32),
+            0x7e => Value::I64(self.i64()?),
+            0x7d => Value::F32(f32::from_le_bytes(self.array()?)),
+            0x7c => Value::F64(f64::from_le_bytes(self.array()?)),
+            0x7b => Value::V128(u128::from_le_bytes(self.array()?)),
+            ty => bail!("unsupported value type {ty:#x}"),
+        };
+        Ok(Some(value))
+    }
+}
+
+fn ref_type(ty: u8) -> &'static str {
+    match ty {
+        0x70 => "funcref",
+        0x6f => "externref",
+        _ => "ref",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use wasmer::*;
+
+    const WAT: &str = r#"(module $app
+        (memory 1)
+        (global $__stack_pointer (mut i32) (i32.const 1024))
+        (global $answer i64 (i64.const 42))
+        (data (i32.const 1024) "spilled")
+        (func $crash unreachable)
+        (func $main (export "main") call $crash))"#;
+
+    fn coredump() -> Inspector {
+        let mut store = Store::default();
+        let dump = Arc::new(Mutex::new(Vec::new()));
+        let sink = dump.clone();
+        store.set_coredump_sink(Some(Box::new(move |bytes| {
+            *sink.lock().unwrap() = bytes.to_vec();
+        })));
+
+        let module = Module::new(&store, WAT).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        let main = instance.exports.get_function("main").unwrap();
+        assert!(main.call(&mut store, &[]).is_err());
+
+        let wasm = wat2wasm(WAT.as_bytes()).unwrap();
+        let dump = dump.lock().unwrap();
+        Inspector::new(&wasm, &dump).unwrap()
+    }
+
+    #[test]
+    fn backtrace_globals_and_memory() {
+        let inspector = coredump();
+
+        let backtrace = inspector.eval("bt").unwrap();
+        let crash = backtrace.find("app!crash (func 0)").unwrap();
+        let main = backtrace.find("app!main (func 1)").unwrap();
+        assert!(crash < main, "{backtrace}");
+
+        let globals = inspector.eval("globals").unwrap();
+        assert!(globals.contains("0: mut 1024: i32 (__stack_pointer)"));
+        assert!(globals.contains("1: 42: i64 (answer)"));
+
+        assert!(inspector
+            .eval("frame 0")
+            .unwrap()
+            .contains("(not recorded)"));
+        assert!(inspector.eval("frame 5").is_err());
+
+        assert!(inspector.eval("read 1024 7").unwrap().ends_with("spilled"));
+        assert!(inspector.eval("read 65535 2").is_err());
+
+        let summary = inspector.summary(16);
+        assert!(
+            summary.contains("Memory at __stack_pointer (0x400):"),
+            "{summary}"
+        );
+        assert!(
+            summary.contains("00000400: 73 70 69 6c 6c 65 64 00"),
+            "{summary}"
+        );
+    }
+
+    #[test]
+    fn not_a_coredump() {
+        let wasm = wat2wasm(WAT.as_bytes()).unwrap();
+        assert!(Inspector::new(&wasm, &wasm).is_err());
+        assert!(Inspector::new(&wasm, b"garbage").is_err());
+    }
+}
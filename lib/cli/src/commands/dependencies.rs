@@ -0,0 +1,203 @@
+//! Helpers shared by `wasmer add`, `wasmer remove` and `wasmer upgrade` to
+//! edit the `[dependencies]` of a `wasmer.toml` and keep its `wasmer.lock`
+//! in sync.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Error};
+use wasmer_registry::{
+    lockfile::{LockedPackage, Lockfile},
+    LocalPackage, WasmerConfig,
+};
+
+/// A `wasmer.toml` being edited.
+///
+/// The manifest is edited as a TOML document rather than through
+/// [`wasmer_toml::Manifest`], so that comments and formatting survive.
+#[derive(Debug)]
+pub(crate) struct ManifestDependencies {
+    path: PathBuf,
+    document: toml_edit::Document,
+}
+
+impl ManifestDependencies {
+    /// Opens the `wasmer.toml` (or `wapm.toml`) in `package_dir`.
+    pub(crate) fn open(package_dir: &Path) -> Result<Self, Error> {
+        let path = LocalPackage::get_wasmer_toml_path(package_dir)?;
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read \"{}\"", path.display()))?;
+        let document = contents
+            .parse::<toml_edit::Document>()
+            .with_context(|| format!("Unable to parse \"{}\"", path.display()))?;
+        Ok(ManifestDependencies { path, document })
+    }
+
+    /// The directory containing the manifest (and the lockfile).
+    pub(crate) fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or_else(|| Path::new("."))
+    }
+
+    /// The `(name, version requirement)` of every dependency.
+    pub(crate) fn iter(&self) -> Vec<(String, String)> {
+        let table = match self
+            .document
+            .get("dependencies")
+            .and_then(|item| item.as_table_like())
+        {
+            Some(table) => table,
+            None => return Vec::new(),
+        };
+        table
+            .iter()
+            .filter_map(|(name, item)| Some((name.to_string(), item.as_str()?.to_string())))
+            .collect()
+    }
+
+    /// The version requirement of a dependency.
+    pub(crate) fn get(&self, name: &str) -> Option<String> {
+        self.iter()
+            .into_iter()
+            .find_map(|(n, requirement)| if n == name { Some(requirement) } else { None })
+    }
+
+    /// Adds a dependency, or changes its version requirement.
+    pub(crate) fn insert(&mut self, name: &str, requirement: &str) -> Result<(), Error> {
+        if self.document.get("dependencies").is_none() {
+            self.document["dependencies"] = toml_edit::table();
+        }
+        self.document["dependencies"]
+            .as_table_like_mut()
+            .context("\"dependencies\" isn't a table")?
+            .insert(name, toml_edit::value(requirement));
+        Ok(())
+    }
+
+    /// Removes a dependency, returning whether there was one.
+    pub(crate) fn remove(&mut self, name: &str) -> bool {
+        self.document
+            .get_mut("dependencies")
+            .and_then(|item| item.as_table_like_mut())
+            .and_then(|table| table.remove(name))
+            .is_some()
+    }
+
+    /// Writes the manifest back, after checking that it is still valid.
+    pub(crate) fn save(&self) -> Result<(), Error> {
+        let contents = self.document.to_string();
+        wasmer_toml::Manifest::parse(&contents)
+            .with_context(|| format!("The edited \"{}\" is invalid", self.path.display()))?;
+        std::fs::write(&self.path, contents)
+            .with_context(|| format!("Unable to write to \"{}\"", self.path.display()))
+    }
+
+    /// Writes the manifest and its lockfile, dropping the locked packages
+    /// that aren't dependencies any more.
+    pub(crate) fn save_with_lockfile(&self, lockfile: &mut Lockfile) -> Result<(), Error> {
+        let dependencies = self.iter();
+        lockfile.retain_dependencies(dependencies.iter().map(|(name, _)| name.as_str()));
+        self.save()?;
+        lockfile.save(self.dir())
+    }
+}
+
+/// The directory containing the `wasmer.toml` to edit.
+pub(crate) fn package_dir(package_path: Option<&Path>) -> Result<PathBuf, Error> {
+    let current_dir = std::env::current_dir()?;
+    Ok(match package_path {
+        Some(path) => current_dir.join(path),
+        None => current_dir,
+    })
+}
+
+/// The registry to resolve dependencies with.
+pub(crate) fn registry(registry: Option<&str>) -> Result<String, Error> {
+    match registry {
+        Some(r) => Ok(r.to_string()),
+        None => {
+            let wasmer_dir = WasmerConfig::get_wasmer_dir().map_err(|e| anyhow::anyhow!("{e}"))?;
+            let cfg = WasmerConfig::from_file(&wasmer_dir)
+                .map_err(Error::msg)
+                .context("Unable to load WAPM's config file")?;
+            Ok(cfg.registry.get_current_registry())
+        }
+    }
+}
+
+/// Resolves a dependency to an exact version and caches it.
+pub(crate) fn lock(registry: &str, name: &str, requirement: &str) -> Result<LockedPackage, Error> {
+    let wasmer_dir = WasmerConfig::get_wasmer_dir().map_err(|e| anyhow::anyhow!("{e}"))?;
+    wasmer_registry::lockfile::lock_dependency(&wasmer_dir, registry, name, requirement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"[package]
+name = "wasmer/hello"
+version = "0.1.0"
+description = "hello"
+
+# Keep python pinned, 0.2 breaks the build
+[dependencies]
+"python/python" = "0.1.0"
+"#;
+
+    fn open(contents: &str) -> (tempfile::TempDir, ManifestDependencies) {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(temp.path().join("wasmer.toml"), contents).unwrap();
+        let manifest = ManifestDependencies::open(temp.path()).unwrap();
+        (temp, manifest)
+    }
+
+    #[test]
+    fn dependencies_are_edited_in_place() {
+        let (temp, mut manifest) = open(MANIFEST);
+        assert_eq!(manifest.get("python/python").as_deref(), Some("0.1.0"));
+
+        manifest.insert("sharrattj/coreutils", "1.0.16").unwrap();
+        assert!(manifest.remove("python/python"));
+        assert!(!manifest.remove("python/python"));
+        manifest.save().unwrap();
+
+        let contents = std::fs::read_to_string(temp.path().join("wasmer.toml")).unwrap();
+        assert!(contents.contains("# Keep python pinned"), "{contents}");
+        assert!(
+            contents.contains("\"sharrattj/coreutils\" = \"1.0.16\""),
+            "{contents}"
+        );
+        assert!(!contents.contains("python/python"), "{contents}");
+    }
+
+    #[test]
+    fn dependencies_table_is_created() {
+        let (_temp, mut manifest) = open(MANIFEST.split("\n#").next().unwrap());
+        assert!(manifest.iter().is_empty());
+
+        manifest.insert("python/python", "0.1.0").unwrap();
+        assert_eq!(
+            manifest.iter(),
+            [("python/python".to_string(), "0.1.0".to_string())]
+        );
+        manifest.save().unwrap();
+    }
+
+    #[test]
+    fn removed_dependencies_are_unlocked() {
+        let (temp, mut manifest) = open(MANIFEST);
+        let mut lockfile = Lockfile::default();
+        lockfile.insert(LockedPackage {
+            name: "python/python".to_string(),
+            version: "0.1.0".to_string(),
+            registry: "https://registry.wapm.io/graphql".to_string(),
+            download_url: "https://example.com/python-0.1.0.tar.gz".to_string(),
+            webc_url: None,
+            checksum: None,
+        });
+
+        manifest.remove("python/python");
+        manifest.save_with_lockfile(&mut lockfile).unwrap();
+
+        assert!(Lockfile::load(temp.path()).unwrap().packages.is_empty());
+    }
+}
@@ -7,6 +7,10 @@ use std::path::Path;
 use std::path::PathBuf;
 use wasmer_registry::WasmerConfig;
 
+mod templates;
+
+use self::templates::{DirectoryTemplate, TemplateSource, Variables};
+
 static NOTE: &str =
     "# See more keys and definitions at https://docs.wasmer.io/ecosystem/wapm/manifest";
 
@@ -42,9 +46,14 @@ pub struct Init {
     /// If the `manifest-path` is a Cargo.toml, use that file to initialize the wasmer.toml
     #[clap(long)]
     pub manifest_path: Option<PathBuf>,
-    /// Add default dependencies for common packages
+    /// Add default dependencies for common packages, or starter code for a language
     #[clap(long, value_enum)]
     pub template: Option<Template>,
+    /// Generate starter files from a template directory. `{{name}}`, `{{package}}`,
+    /// `{{version}}` and `{{description}}` are substituted in file names and contents,
+    /// and a wasmer.toml in the directory replaces the generated one.
+    #[clap(long, conflicts_with = "template")]
+    pub template_dir: Option<PathBuf>,
     /// Include file paths into the target container filesystem
     #[clap(long)]
     pub include: Vec<String>,
@@ -61,6 +70,13 @@ pub enum Template {
     Python,
     /// Add dependency on JS
     Js,
+    /// Rust starter code, built with `cargo build --target wasm32-wasi`
+    Rust,
+    /// C starter code, built with the WASI SDK
+    C,
+    /// AssemblyScript starter code, built with `asc` and the WASI shim
+    #[value(name = "assemblyscript")]
+    AssemblyScript,
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -125,7 +141,7 @@ impl Init {
             );
         }
 
-        let constructed_manifest = construct_manifest(
+        let mut constructed_manifest = construct_manifest(
             cargo_toml.as_ref(),
             &fallback_package_name,
             self.package_name.as_deref(),
@@ -143,6 +159,26 @@ impl Init {
             let _ = std::fs::create_dir_all(parent);
         }
 
+        let template: Option<Box<dyn TemplateSource>> = match self.template_dir.as_ref() {
+            Some(dir) => Some(Box::new(DirectoryTemplate::new(dir))),
+            None => self
+                .template
+                .map(|t| Box::new(t) as Box<dyn TemplateSource>),
+        };
+
+        if let Some(template) = template {
+            let package_dir = target_file.parent().unwrap_or_else(|| Path::new("."));
+            let wrote_manifest = Self::write_template(
+                template.as_ref(),
+                package_dir,
+                &mut constructed_manifest,
+                self,
+            )?;
+            if wrote_manifest {
+                return Ok(());
+            }
+        }
+
         // generate the wasmer.toml and exit
         Self::write_wasmer_toml(&target_file, &constructed_manifest)
     }
@@ -167,6 +203,37 @@ impl Init {
         Ok(())
     }
 
+    /// Writes the template's starter files next to the wasmer.toml, pointing
+    /// the manifest's modules at whatever the starter code builds. Returns
+    /// `true` if the template provided its own wasmer.toml.
+    fn write_template(
+        template: &dyn TemplateSource,
+        package_dir: &Path,
+        manifest: &mut wasmer_toml::Manifest,
+        args: &Init,
+    ) -> Result<bool, anyhow::Error> {
+        let name = manifest.package.name.clone();
+        let package = name.rsplit('/').next().unwrap_or(&name).to_string();
+
+        if let Some(source) = template.module_source(&package) {
+            for module in manifest.module.iter_mut().flatten() {
+                module.source = source.clone();
+            }
+        }
+
+        let vars = Variables {
+            name,
+            package,
+            version: manifest.package.version.to_string(),
+            description: manifest.package.description.clone(),
+        };
+        let files = templates::render(template.files()?, &vars);
+        let has_manifest = files.iter().any(|f| f.path == Path::new(WASMER_TOML_NAME));
+        templates::write(package_dir, &files, args.overwrite, args.quiet)?;
+
+        Ok(has_manifest)
+    }
+
     fn target_file(&self) -> Result<(String, PathBuf), anyhow::Error> {
         match self.out.as_ref() {
             None => {
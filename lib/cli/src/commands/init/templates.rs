@@ -0,0 +1,320 @@
+//! Starter code generated by `wasmer init --template` and `--template-dir`.
+
+use super::Template;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+/// A source of starter files for a new package.
+pub(super) trait TemplateSource {
+    /// The files making up the template, with paths relative to the
+    /// package directory. Placeholders haven't been substituted yet.
+    fn files(&self) -> Result<Vec<TemplateFile>, anyhow::Error>;
+
+    /// Where the compiled module for `package` ends up, if the template
+    /// knows how it is built.
+    fn module_source(&self, _package: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// A single file in a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct TemplateFile {
+    pub path: PathBuf,
+    pub contents: Vec<u8>,
+}
+
+impl TemplateFile {
+    fn text(path: &str, contents: &str) -> Self {
+        TemplateFile {
+            path: PathBuf::from(path),
+            contents: contents.as_bytes().to_vec(),
+        }
+    }
+}
+
+/// The values substituted for `{{name}}`, `{{package}}`, `{{version}}`
+/// and `{{description}}` in template paths and (UTF-8) file contents.
+#[derive(Debug, Clone)]
+pub(super) struct Variables {
+    /// The full package name, including the namespace.
+    pub name: String,
+    /// The package name without the namespace.
+    pub package: String,
+    pub version: String,
+    pub description: String,
+}
+
+impl Variables {
+    fn substitute(&self, text: &str) -> String {
+        text.replace("{{name}}", &self.name)
+            .replace("{{package}}", &self.package)
+            .replace("{{version}}", &self.version)
+            .replace("{{description}}", &self.description)
+    }
+}
+
+/// Substitute the placeholders in each file. Files that aren't valid UTF-8
+/// are copied verbatim.
+pub(super) fn render(files: Vec<TemplateFile>, vars: &Variables) -> Vec<TemplateFile> {
+    files
+        .into_iter()
+        .map(|file| {
+            let path = PathBuf::from(vars.substitute(&file.path.display().to_string()));
+            let contents = match String::from_utf8(file.contents) {
+                Ok(text) => vars.substitute(&text).into_bytes(),
+                Err(e) => e.into_bytes(),
+            };
+            TemplateFile { path, contents }
+        })
+        .collect()
+}
+
+/// Write the rendered files into `dir`. Nothing is written if any of them
+/// already exists, unless `overwrite` is set.
+pub(super) fn write(
+    dir: &Path,
+    files: &[TemplateFile],
+    overwrite: bool,
+    quiet: bool,
+) -> Result<(), anyhow::Error> {
+    if !overwrite {
+        if let Some(existing) = files.iter().find(|f| dir.join(&f.path).exists()) {
+            anyhow::bail!(
+                "\"{}\" already exists, use --overwrite to replace it",
+                dir.join(&existing.path).display()
+            );
+        }
+    }
+
+    for file in files {
+        let path = dir.join(&file.path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Unable to create \"{}\"", parent.display()))?;
+        }
+        std::fs::write(&path, &file.contents)
+            .with_context(|| format!("Unable to write to \"{}\"", path.display()))?;
+        if !quiet {
+            println!("Created {}", file.path.display());
+        }
+    }
+
+    Ok(())
+}
+
+impl TemplateSource for Template {
+    fn files(&self) -> Result<Vec<TemplateFile>, anyhow::Error> {
+        let files = match self {
+            Template::Python | Template::Js => Vec::new(),
+            Template::Rust => vec![
+                TemplateFile::text("Cargo.toml", RUST_CARGO_TOML),
+                TemplateFile::text("src/main.rs", RUST_MAIN),
+            ],
+            Template::C => vec![
+                TemplateFile::text("main.c", C_MAIN),
+                TemplateFile::text("Makefile", C_MAKEFILE),
+            ],
+            Template::AssemblyScript => vec![
+                TemplateFile::text("package.json", AS_PACKAGE_JSON),
+                TemplateFile::text("asconfig.json", AS_CONFIG),
+                TemplateFile::text("assembly/index.ts", AS_INDEX),
+            ],
+        };
+        Ok(files)
+    }
+
+    fn module_source(&self, package: &str) -> Option<PathBuf> {
+        let path = match self {
+            Template::Python | Template::Js => return None,
+            Template::Rust => format!("target/wasm32-wasi/release/{package}.wasm"),
+            Template::C => format!("{package}.wasm"),
+            Template::AssemblyScript => format!("build/{package}.wasm"),
+        };
+        Some(PathBuf::from(path))
+    }
+}
+
+/// A template loaded from a directory on disk. Every file is copied, and a
+/// `wasmer.toml` in the directory replaces the generated one.
+#[derive(Debug, Clone)]
+pub(super) struct DirectoryTemplate {
+    root: PathBuf,
+}
+
+impl DirectoryTemplate {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        DirectoryTemplate { root: root.into() }
+    }
+}
+
+impl TemplateSource for DirectoryTemplate {
+    fn files(&self) -> Result<Vec<TemplateFile>, anyhow::Error> {
+        if !self.root.is_dir() {
+            anyhow::bail!("\"{}\" is not a directory", self.root.display());
+        }
+
+        let mut files = Vec::new();
+        let entries = walkdir::WalkDir::new(&self.root)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| e.file_name() != ".git");
+
+        for entry in entries {
+            let entry =
+                entry.with_context(|| format!("Unable to read \"{}\"", self.root.display()))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let contents = std::fs::read(entry.path())
+                .with_context(|| format!("Unable to read \"{}\"", entry.path().display()))?;
+            let path = entry
+                .path()
+                .strip_prefix(&self.root)
+                .expect("walkdir only yields children of the root")
+                .to_path_buf();
+            files.push(TemplateFile { path, contents });
+        }
+
+        Ok(files)
+    }
+}
+
+const RUST_CARGO_TOML: &str = r#"[package]
+name = "{{package}}"
+version = "{{version}}"
+description = "{{description}}"
+edition = "2021"
+
+[dependencies]
+"#;
+
+const RUST_MAIN: &str = r#"fn main() {
+    let name = std::env::args().nth(1).unwrap_or_else(|| "world".to_string());
+    println!("Hello, {name}!");
+}
+"#;
+
+const C_MAIN: &str = r#"#include <stdio.h>
+
+int main(int argc, char *argv[]) {
+    const char *name = argc > 1 ? argv[1] : "world";
+    printf("Hello, %s!\n", name);
+    return 0;
+}
+"#;
+
+const C_MAKEFILE: &str = "WASI_SDK_PATH ?= /opt/wasi-sdk
+CC = $(WASI_SDK_PATH)/bin/clang --sysroot=$(WASI_SDK_PATH)/share/wasi-sysroot
+
+{{package}}.wasm: main.c
+\t$(CC) -O2 -o $@ $<
+
+clean:
+\trm -f {{package}}.wasm
+
+.PHONY: clean
+";
+
+const AS_PACKAGE_JSON: &str = r#"{
+  "name": "{{package}}",
+  "version": "{{version}}",
+  "description": "{{description}}",
+  "scripts": {
+    "build": "asc assembly/index.ts --target release"
+  },
+  "devDependencies": {
+    "@assemblyscript/wasi-shim": "^0.1.0",
+    "assemblyscript": "^0.27.0"
+  }
+}
+"#;
+
+const AS_CONFIG: &str = r#"{
+  "extends": "./node_modules/@assemblyscript/wasi-shim/asconfig.json",
+  "targets": {
+    "release": {
+      "outFile": "build/{{package}}.wasm",
+      "optimizeLevel": 3,
+      "shrinkLevel": 0
+    }
+  }
+}
+"#;
+
+const AS_INDEX: &str = r#"import { process } from "@assemblyscript/wasi-shim/assembly/process";
+
+const args = process.argv;
+const name = args.length > 1 ? args[1] : "world";
+console.log(`Hello, ${name}!`);
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> Variables {
+        Variables {
+            name: "ns/hello".to_string(),
+            package: "hello".to_string(),
+            version: "0.1.0".to_string(),
+            description: "Says hello".to_string(),
+        }
+    }
+
+    #[test]
+    fn builtin_templates_are_rendered() {
+        let files = render(Template::C.files().unwrap(), &vars());
+
+        let makefile = files
+            .iter()
+            .find(|f| f.path == Path::new("Makefile"))
+            .unwrap();
+        let makefile = std::str::from_utf8(&makefile.contents).unwrap();
+        assert!(makefile.contains("hello.wasm: main.c"));
+        assert!(!makefile.contains("{{"));
+        assert_eq!(
+            Template::C.module_source("hello"),
+            Some(PathBuf::from("hello.wasm"))
+        );
+        assert!(Template::Python.files().unwrap().is_empty());
+    }
+
+    #[test]
+    fn directory_templates_substitute_paths_and_contents() {
+        let temp = tempfile::tempdir().unwrap();
+        let template = temp.path().join("template");
+        std::fs::create_dir_all(template.join("src")).unwrap();
+        std::fs::create_dir_all(template.join(".git")).unwrap();
+        std::fs::write(template.join(".git").join("HEAD"), "ref").unwrap();
+        std::fs::write(template.join("src").join("{{package}}.c"), "// {{name}}").unwrap();
+        std::fs::write(template.join("logo.bin"), [0xff, 0xfe]).unwrap();
+
+        let files = DirectoryTemplate::new(&template).files().unwrap();
+        let files = render(files, &vars());
+
+        assert_eq!(
+            files,
+            vec![
+                TemplateFile {
+                    path: PathBuf::from("logo.bin"),
+                    contents: vec![0xff, 0xfe],
+                },
+                TemplateFile {
+                    path: Path::new("src").join("hello.c"),
+                    contents: b"// ns/hello".to_vec(),
+                },
+            ]
+        );
+
+        let out = temp.path().join("out");
+        write(&out, &files, false, true).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(out.join("src").join("hello.c")).unwrap(),
+            "// ns/hello"
+        );
+        assert!(write(&out, &files, false, true).is_err());
+        write(&out, &files, true, true).unwrap();
+    }
+}
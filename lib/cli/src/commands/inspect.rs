@@ -2,22 +2,82 @@ use crate::store::StoreOptions;
 use anyhow::{Context, Result};
 use bytesize::ByteSize;
 use clap::Parser;
+use serde::Serialize;
 use std::path::PathBuf;
 use wasmer::*;
 
 #[derive(Debug, Parser)]
-/// The options for the `wasmer validate` subcommand
+/// The options for the `wasmer inspect` subcommand
 pub struct Inspect {
     /// File to validate as WebAssembly
     #[clap(name = "FILE")]
     path: PathBuf,
 
+    /// Print the report as JSON
+    #[clap(long)]
+    json: bool,
+
+    /// Don't compile the module with every available compiler to estimate
+    /// its compiled size
+    #[clap(long)]
+    skip_compiled_size: bool,
+
     #[clap(flatten)]
     store: StoreOptions,
 }
 
+/// Everything `wasmer inspect` knows about a module.
+#[derive(Debug, Serialize)]
+struct Report {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    size: u64,
+    name: Option<String>,
+    /// The ABIs the module was built for (WASI versions or Emscripten).
+    abi: Vec<String>,
+    /// The feature proposals the module can't be validated without.
+    required_features: Option<Vec<&'static str>>,
+    imports: Vec<ExternReport>,
+    exports: Vec<ExternReport>,
+    start_function: Option<String>,
+    custom_sections: Vec<CustomSectionReport>,
+    compiled_sizes: Vec<CompiledSizeReport>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExternReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module: Option<String>,
+    name: String,
+    kind: &'static str,
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limits: Option<Limits>,
+}
+
+#[derive(Debug, Serialize)]
+struct Limits {
+    minimum: u64,
+    maximum: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct CustomSectionReport {
+    name: String,
+    size: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct CompiledSizeReport {
+    compiler: String,
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
 impl Inspect {
-    /// Runs logic for the `validate` subcommand
+    /// Runs logic for the `inspect` subcommand
     pub fn execute(&self) -> Result<()> {
         self.inner_execute()
             .context(format!("failed to inspect `{}`", self.path.display()))
@@ -28,43 +88,271 @@ impl Inspect {
         let module_contents = std::fs::read(&self.path)?;
         let iswasm = is_wasm(&module_contents);
         let module_len = module_contents.len();
-        let module = Module::new(&store, module_contents)?;
-        println!("Type: {}", if !iswasm { "wat" } else { "wasm" });
-        println!("Size: {}", ByteSize(module_len as _));
-        println!("Imports:");
-        println!("  Functions:");
-        for f in module.imports().functions() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
-        }
-        println!("  Memories:");
-        for f in module.imports().memories() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
-        }
-        println!("  Tables:");
-        for f in module.imports().tables() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+        let module = Module::new(&store, &module_contents)?;
+
+        let report = Report {
+            kind: if !iswasm { "wat" } else { "wasm" },
+            size: module_len as u64,
+            name: module.name().map(String::from),
+            abi: detect_abi(&module),
+            required_features: self.required_features(&store, &module_contents),
+            imports: module.imports().map(extern_report).collect(),
+            exports: module.exports().map(extern_report).collect(),
+            start_function: module.start_function().map(|start| match start.name {
+                Some(name) => format!("${name}: {}", start.ty),
+                None => format!("{}: {}", start.index.as_u32(), start.ty),
+            }),
+            custom_sections: module
+                .all_custom_sections()
+                .map(|(name, data)| CustomSectionReport {
+                    name: name.to_string(),
+                    size: data.len() as u64,
+                })
+                .collect(),
+            compiled_sizes: if self.skip_compiled_size {
+                Vec::new()
+            } else {
+                self.compiled_sizes(&module_contents)
+            },
+        };
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print_report(&report);
         }
-        println!("  Globals:");
-        for f in module.imports().globals() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "compiler")]
+    fn required_features(&self, store: &Store, contents: &[u8]) -> Option<Vec<&'static str>> {
+        let wasm = if is_wasm(contents) {
+            std::borrow::Cow::Borrowed(contents)
+        } else {
+            #[cfg(feature = "wat")]
+            {
+                wat2wasm(contents).ok()?
+            }
+            #[cfg(not(feature = "wat"))]
+            {
+                return None;
+            }
+        };
+        let report = Module::validate_detailed(store, &wasm).ok()?;
+        report.required_features.as_ref().map(feature_names)
+    }
+
+    #[cfg(not(feature = "compiler"))]
+    fn required_features(&self, _store: &Store, _contents: &[u8]) -> Option<Vec<&'static str>> {
+        None
+    }
+
+    /// Compiles the module with every compiler in this binary, and measures
+    /// the size of the serialized artifact.
+    #[cfg(feature = "compiler")]
+    fn compiled_sizes(&self, contents: &[u8]) -> Vec<CompiledSizeReport> {
+        crate::store::CompilerType::enabled()
+            .into_iter()
+            .map(|compiler| {
+                let size = self
+                    .store
+                    .get_store_for_compiler(compiler)
+                    .and_then(|store| Ok(Module::new(&store, contents)?.serialize()?.len()));
+                CompiledSizeReport {
+                    compiler: compiler.to_string(),
+                    size: size.as_ref().ok().map(|size| *size as u64),
+                    error: size.err().map(|e| e.to_string()),
+                }
+            })
+            .collect()
+    }
+
+    #[cfg(not(feature = "compiler"))]
+    fn compiled_sizes(&self, _contents: &[u8]) -> Vec<CompiledSizeReport> {
+        Vec::new()
+    }
+}
+
+fn extern_report<T>(item: T) -> ExternReport
+where
+    T: Into<ExternInfo>,
+{
+    let ExternInfo { module, name, ty } = item.into();
+    let (kind, limits) = match &ty {
+        ExternType::Function(_) => ("function", None),
+        ExternType::Global(_) => ("global", None),
+        ExternType::Table(t) => (
+            "table",
+            Some(Limits {
+                minimum: t.minimum as u64,
+                maximum: t.maximum.map(u64::from),
+            }),
+        ),
+        ExternType::Memory(m) => (
+            "memory",
+            Some(Limits {
+                minimum: m.minimum.0 as u64,
+                maximum: m.maximum.map(|p| p.0 as u64),
+            }),
+        ),
+    };
+
+    ExternReport {
+        module,
+        name,
+        kind,
+        ty: extern_type_to_string(&ty),
+        limits,
+    }
+}
+
+/// The parts of an import or an export that are reported.
+struct ExternInfo {
+    module: Option<String>,
+    name: String,
+    ty: ExternType,
+}
+
+impl From<ImportType> for ExternInfo {
+    fn from(import: ImportType) -> Self {
+        ExternInfo {
+            module: Some(import.module().to_string()),
+            name: import.name().to_string(),
+            ty: import.ty().clone(),
         }
-        println!("Exports:");
-        println!("  Functions:");
-        for f in module.exports().functions() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+    }
+}
+
+impl From<ExportType> for ExternInfo {
+    fn from(export: ExportType) -> Self {
+        ExternInfo {
+            module: None,
+            name: export.name().to_string(),
+            ty: export.ty().clone(),
         }
-        println!("  Memories:");
-        for f in module.exports().memories() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+    }
+}
+
+fn extern_type_to_string(ty: &ExternType) -> String {
+    match ty {
+        ExternType::Function(f) => f.to_string(),
+        ExternType::Global(g) => g.to_string(),
+        ExternType::Table(t) => t.to_string(),
+        ExternType::Memory(m) => m.to_string(),
+    }
+}
+
+fn detect_abi(module: &Module) -> Vec<String> {
+    let mut abi = Vec::new();
+    if wasmer_emscripten::is_emscripten_module(module) {
+        abi.push("emscripten".to_string());
+    }
+    if let Some(versions) = wasmer_wasix::get_wasi_versions(module, false) {
+        abi.extend(
+            versions
+                .iter()
+                .map(|version| version.get_namespace_str().to_string()),
+        );
+    }
+    abi
+}
+
+#[cfg(feature = "compiler")]
+pub(crate) fn feature_names(features: &Features) -> Vec<&'static str> {
+    let Features {
+        threads,
+        reference_types,
+        simd,
+        bulk_memory,
+        multi_value,
+        tail_call,
+        module_linking,
+        multi_memory,
+        memory64,
+        exceptions,
+        relaxed_simd,
+        extended_const,
+    } = *features;
+
+    [
+        (threads, "threads"),
+        (reference_types, "reference-types"),
+        (simd, "simd"),
+        (bulk_memory, "bulk-memory"),
+        (multi_value, "multi-value"),
+        (tail_call, "tail-call"),
+        (module_linking, "module-linking"),
+        (multi_memory, "multi-memory"),
+        (memory64, "memory64"),
+        (exceptions, "exceptions"),
+        (relaxed_simd, "relaxed-simd"),
+        (extended_const, "extended-const"),
+    ]
+    .iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, name)| *name)
+    .collect()
+}
+
+fn print_report(report: &Report) {
+    println!("Type: {}", report.kind);
+    println!("Size: {}", ByteSize(report.size));
+    if let Some(name) = &report.name {
+        println!("Name: {name}");
+    }
+    if report.abi.is_empty() {
+        println!("ABI: none");
+    } else {
+        println!("ABI: {}", report.abi.join(", "));
+    }
+    match &report.required_features {
+        Some(features) if features.is_empty() => println!("Required features: none"),
+        Some(features) => println!("Required features: {}", features.join(", ")),
+        None => {}
+    }
+
+    println!("Imports:");
+    print_externs(&report.imports);
+    println!("Exports:");
+    print_externs(&report.exports);
+
+    if let Some(start) = &report.start_function {
+        println!("Start function: {start}");
+    }
+
+    if !report.custom_sections.is_empty() {
+        println!("Custom sections:");
+        for section in &report.custom_sections {
+            println!("  \"{}\": {}", section.name, ByteSize(section.size));
         }
-        println!("  Tables:");
-        for f in module.exports().tables() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+    }
+
+    if !report.compiled_sizes.is_empty() {
+        println!("Compiled size:");
+        for compiled in &report.compiled_sizes {
+            match (compiled.size, &compiled.error) {
+                (Some(size), _) => println!("  {}: {}", compiled.compiler, ByteSize(size)),
+                (None, Some(error)) => println!("  {}: failed ({error})", compiled.compiler),
+                (None, None) => {}
+            }
         }
-        println!("  Globals:");
-        for f in module.exports().globals() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+    }
+}
+
+fn print_externs(externs: &[ExternReport]) {
+    for (kind, title) in [
+        ("function", "Functions"),
+        ("memory", "Memories"),
+        ("table", "Tables"),
+        ("global", "Globals"),
+    ] {
+        println!("  {title}:");
+        for e in externs.iter().filter(|e| e.kind == kind) {
+            match &e.module {
+                Some(module) => println!("    \"{}\".\"{}\": {}", module, e.name, e.ty),
+                None => println!("    \"{}\": {}", e.name, e.ty),
+            }
         }
-        Ok(())
     }
 }
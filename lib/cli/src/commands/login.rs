@@ -12,6 +12,15 @@ pub struct Login {
     /// Login token
     #[clap(name = "TOKEN")]
     pub token: Option<String>,
+    /// Save the login as a named profile (e.g. "staging"), which can
+    /// later be selected with `--profile` or `WASMER_PROFILE`
+    #[clap(long)]
+    pub profile: Option<String>,
+    /// Keep the token in the OS keychain instead of the config file
+    /// (saved as the "default" profile unless `--profile` is given).
+    /// Needs a build with the `keychain` feature.
+    #[clap(long)]
+    pub keychain: bool,
 }
 
 impl Login {
@@ -49,12 +58,39 @@ impl Login {
         }
     }
 
+    /// The profile the login is saved as, if any
+    fn profile(&self) -> Option<&str> {
+        match self.profile.as_deref() {
+            Some(profile) => Some(profile),
+            None if self.keychain => Some("default"),
+            None => None,
+        }
+    }
+
     /// execute [List]
     pub fn execute(&self) -> Result<(), anyhow::Error> {
+        if self.keychain && !wasmer_registry::keychain::is_supported() {
+            anyhow::bail!("This build of wasmer doesn't support the OS keychain");
+        }
         let token = self.get_token_or_ask_user()?;
         let wasmer_dir =
             WasmerConfig::get_wasmer_dir().map_err(|e| anyhow::anyhow!("no wasmer dir: {e}"))?;
-        match wasmer_registry::login::login_and_save_token(&wasmer_dir, &self.registry, &token)? {
+        let user = match self.profile() {
+            Some(profile) => wasmer_registry::login::login_and_save_profile(
+                &wasmer_dir,
+                profile,
+                &self.registry,
+                &token,
+                self.keychain,
+            )?,
+            None => {
+                wasmer_registry::login::login_and_save_token(&wasmer_dir, &self.registry, &token)?
+            }
+        };
+        if let Some(profile) = self.profile() {
+            println!("Login saved as profile {profile:?}");
+        }
+        match user {
             Some(s) => println!("Login for WAPM user {:?} saved", s),
             None => println!(
                 "Error: no user found on registry {:?} with token {:?}. Token saved regardless.",
@@ -70,6 +106,8 @@ fn test_login_2() {
     let login = Login {
         registry: "wapm.dev".to_string(),
         token: None,
+        profile: None,
+        keychain: false,
     };
 
     assert_eq!(
@@ -80,7 +118,19 @@ fn test_login_2() {
     let login = Login {
         registry: "wapm.dev".to_string(),
         token: Some("abc".to_string()),
+        profile: None,
+        keychain: false,
     };
 
     assert_eq!(login.get_token_or_ask_user().unwrap(), "abc");
 }
+
+#[test]
+fn test_login_keychain_profile() {
+    let login = Login::try_parse_from(["login", "--keychain", "abc"]).unwrap();
+    assert_eq!(login.profile(), Some("default"));
+
+    let login = Login::try_parse_from(["login", "--profile=staging", "abc"]).unwrap();
+    assert_eq!(login.profile(), Some("staging"));
+    assert!(!login.keychain);
+}
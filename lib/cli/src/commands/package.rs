@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Error};
+use bytesize::ByteSize;
+use clap::Parser;
+
+use crate::commands::Publish;
+
+/// The options for the `wasmer package` subcommand
+#[derive(Debug, Parser)]
+pub enum Package {
+    /// Build a `.webc` file from a directory containing a `wasmer.toml`
+    #[clap(name = "build")]
+    Build(PackageBuild),
+
+    /// Publish a package to the package registry
+    #[clap(name = "publish")]
+    Publish(Publish),
+}
+
+impl Package {
+    /// Execute the package command
+    pub fn execute(&self) -> Result<(), Error> {
+        match self {
+            Package::Build(build) => build.execute(),
+            Package::Publish(publish) => publish.execute(),
+        }
+    }
+}
+
+/// Build a `.webc` file from a directory containing a `wasmer.toml`.
+///
+/// The commands' atoms and the directories listed in the `[fs]` table are
+/// embedded in the resulting package.
+#[derive(Debug, Parser)]
+pub struct PackageBuild {
+    /// Where to write the package (defaults to `<name>-<version>.webc` in the
+    /// package directory)
+    #[clap(short, long)]
+    pub out: Option<PathBuf>,
+    /// Don't print anything
+    #[clap(long)]
+    pub quiet: bool,
+    /// Directory containing the `wasmer.toml` (defaults to the current dir)
+    #[clap(name = "PACKAGE_PATH")]
+    pub package_path: Option<PathBuf>,
+}
+
+impl PackageBuild {
+    /// Executes `wasmer package build`
+    pub fn execute(&self) -> Result<(), Error> {
+        let dir = match &self.package_path {
+            Some(path) => std::env::current_dir()?.join(path),
+            None => std::env::current_dir()?,
+        };
+        let manifest_path = dir.join("wasmer.toml");
+
+        let manifest = std::fs::read_to_string(&manifest_path)
+            .with_context(|| format!("Unable to read \"{}\"", manifest_path.display()))?;
+        let manifest = wasmer_toml::Manifest::parse(&manifest)
+            .with_context(|| format!("Unable to parse \"{}\"", manifest_path.display()))?;
+
+        let package = webc::wasmer_package::Package::from_manifest(&manifest_path)
+            .with_context(|| format!("Unable to load the package in \"{}\"", dir.display()))?;
+        let webc = package
+            .serialize()
+            .context("Unable to serialize the package")?;
+
+        // Make sure the result can be loaded again before writing it out
+        let container = webc::Container::from_bytes(webc.clone())
+            .context("The generated package is not a valid WEBC file")?;
+
+        let out = match &self.out {
+            Some(out) => out.clone(),
+            None => dir.join(default_file_name(&manifest)),
+        };
+        if let Some(parent) = out.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Unable to create \"{}\"", parent.display()))?;
+        }
+        std::fs::write(&out, &webc)
+            .with_context(|| format!("Unable to write to \"{}\"", out.display()))?;
+
+        if !self.quiet {
+            println!(
+                "Built package `{}@{}` ({} atoms, {} volumes, {}) to {}",
+                manifest.package.name,
+                manifest.package.version,
+                container.atoms().len(),
+                container.volumes().len(),
+                ByteSize(webc.len() as u64),
+                display_path(&out),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn default_file_name(manifest: &wasmer_toml::Manifest) -> String {
+    format!(
+        "{}-{}.webc",
+        manifest.package.name.replace('/', "-"),
+        manifest.package.version
+    )
+}
+
+fn display_path(path: &Path) -> String {
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok())
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_file_name_doesnt_contain_the_namespace_separator() {
+        let manifest = wasmer_toml::Manifest::parse(
+            r#"
+            [package]
+            name = "wasmer/hello"
+            version = "0.1.2"
+            description = "Hello"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(default_file_name(&manifest), "wasmer-hello-0.1.2.webc");
+    }
+}
@@ -0,0 +1,362 @@
+use crate::commands::run::Wasi;
+use crate::store::StoreOptions;
+use crate::utils::read_module_bytes;
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wasmer::*;
+use wasmer_middlewares::profiling::{Profiler, Profiling};
+use wasmer_wasix::metrics::MetricsSink;
+
+/// The options for the `wasmer profile` subcommand
+#[derive(Debug, Parser)]
+pub struct Profile {
+    /// WebAssembly file or package to profile
+    #[clap(name = "FILE")]
+    path: PathBuf,
+
+    /// The atom to run, when FILE is a package with several atoms
+    #[clap(long)]
+    atom: Option<String>,
+
+    /// Where to write the SVG flamegraph
+    #[clap(short, long, default_value = "flamegraph.svg")]
+    output: PathBuf,
+
+    /// Also write the collapsed stacks (the input format of most
+    /// flamegraph tools) to this file
+    #[clap(long)]
+    collapsed: Option<PathBuf>,
+
+    /// Interval between two samples, in microseconds
+    #[clap(long, default_value = "100")]
+    interval: u64,
+
+    #[clap(flatten)]
+    store: StoreOptions,
+
+    #[clap(flatten)]
+    wasi: Wasi,
+
+    /// Application arguments
+    #[clap(value_name = "ARGS")]
+    args: Vec<String>,
+}
+
+/// Records the time spent in each syscall, along with the function that
+/// made the call.
+#[derive(Debug, Default)]
+struct SyscallTimes {
+    profiler: Mutex<Option<Arc<Profiler>>>,
+    times: Mutex<HashMap<(Option<u32>, String), Duration>>,
+}
+
+impl MetricsSink for SyscallTimes {
+    fn syscall(&self, name: &str, duration: Duration) {
+        let caller = match &*self.profiler.lock().unwrap() {
+            Some(profiler) => profiler.current_function(),
+            None => return,
+        };
+        *self
+            .times
+            .lock()
+            .unwrap()
+            .entry((caller, name.to_string()))
+            .or_default() += duration;
+    }
+}
+
+impl Profile {
+    /// Runs logic for the `profile` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .with_context(|| format!("failed to profile `{}`", self.path.display()))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        let profiling = Arc::new(Profiling::new());
+        let (mut store, _compiler_type) = self
+            .store
+            .get_store_with_middlewares(vec![profiling.clone() as Arc<dyn ModuleMiddleware>])?;
+        let bytes = read_module_bytes(&self.path, self.atom.as_deref())?;
+        let module = Module::new(&store, bytes)?;
+
+        let program_name = self
+            .path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let syscalls = Arc::new(SyscallTimes::default());
+        let (instance, wasi_env) = if Wasi::has_wasi_imports(&module) {
+            let builder = self
+                .wasi
+                .prepare(&mut store, &module, program_name.clone(), self.args.clone())?
+                .metrics(syscalls.clone());
+            let (instance, env) = builder
+                .instantiate(module.clone(), &mut store)
+                .context("failed to instantiate WASI module")?;
+            (instance, Some(env))
+        } else {
+            (Instance::new(&mut store, &module, &imports! {})?, None)
+        };
+
+        let profiler = Arc::new(
+            Profiler::new(&mut store, &instance, &profiling)
+                .with_interval(Duration::from_micros(self.interval.max(1))),
+        );
+        *syscalls.profiler.lock().unwrap() = Some(profiler.clone());
+
+        let start = instance
+            .exports
+            .get_function("_start")
+            .context("the module has no `_start` function to profile")?
+            .clone();
+        let (result, report) = profiler.profile(|| start.call(&mut store, &[]));
+        let exit_code = self.wasi.handle_result(result);
+        if let Some(env) = wasi_env {
+            env.cleanup(&mut store, None);
+        }
+
+        let root = module
+            .name()
+            .map(String::from)
+            .unwrap_or_else(|| program_name.clone());
+        let syscalls = std::mem::take(&mut *syscalls.times.lock().unwrap());
+        let stacks = collapse_stacks(&root, &report, &syscalls, &profiler);
+
+        if let Some(path) = &self.collapsed {
+            let collapsed: String = stacks
+                .iter()
+                .map(|(frames, samples)| format!("{} {samples}\n", frames.join(";")))
+                .collect();
+            std::fs::write(path, collapsed)
+                .with_context(|| format!("unable to write to `{}`", path.display()))?;
+        }
+        std::fs::write(
+            &self.output,
+            render_flamegraph(&format!("{program_name} {}", self.args.join(" ")), &stacks),
+        )
+        .with_context(|| format!("unable to write to `{}`", self.output.display()))?;
+
+        eprint!("{report}");
+        eprintln!("Wrote the flamegraph to `{}`", self.output.display());
+
+        let exit_code = exit_code?;
+        if exit_code != 0 {
+            eprintln!("The program exited with status {exit_code}");
+        }
+        Ok(())
+    }
+}
+
+/// Turns the samples and the syscall times into collapsed stacks: one
+/// `(frames, samples)` entry per stack, with the time spent in syscalls
+/// nested under the function that made them.
+fn collapse_stacks(
+    root: &str,
+    report: &wasmer_middlewares::profiling::ProfileReport,
+    syscalls: &HashMap<(Option<u32>, String), Duration>,
+    profiler: &Profiler,
+) -> Vec<(Vec<String>, u64)> {
+    let interval = profiler.interval().as_nanos().max(1);
+    let host = "[host]".to_string();
+
+    // Samples of each frame, before moving the time of the syscalls out.
+    let mut own: BTreeMap<String, u64> = report
+        .functions
+        .iter()
+        .map(|f| (f.name.clone(), f.samples))
+        .collect();
+    if report.idle_samples > 0 {
+        own.insert(host.clone(), report.idle_samples);
+    }
+
+    let mut stacks = Vec::new();
+    for ((caller, syscall), time) in syscalls {
+        let samples = (time.as_nanos() / interval) as u64;
+        if samples == 0 {
+            continue;
+        }
+        let parent = caller
+            .and_then(|index| profiler.function_name(index))
+            .map(String::from)
+            .unwrap_or_else(|| host.clone());
+        if let Some(own) = own.get_mut(&parent) {
+            *own = own.saturating_sub(samples);
+        }
+        stacks.push((
+            vec![root.to_string(), parent, format!("[syscall] {syscall}")],
+            samples,
+        ));
+    }
+    stacks.extend(
+        own.into_iter()
+            .filter(|(_, samples)| *samples > 0)
+            .map(|(name, samples)| (vec![root.to_string(), name], samples)),
+    );
+    stacks.sort();
+    stacks
+}
+
+/// A frame of the flamegraph, and the frames it called.
+#[derive(Debug, Default)]
+struct Frame {
+    samples: u64,
+    children: BTreeMap<String, Frame>,
+}
+
+impl Frame {
+    fn depth(&self) -> usize {
+        1 + self.children.values().map(Frame::depth).max().unwrap_or(0)
+    }
+}
+
+const WIDTH: f64 = 1200.0;
+const FRAME_HEIGHT: f64 = 16.0;
+const TITLE_HEIGHT: f64 = 32.0;
+
+/// Renders collapsed stacks as an SVG flamegraph.
+fn render_flamegraph(title: &str, stacks: &[(Vec<String>, u64)]) -> String {
+    let mut root = Frame::default();
+    for (frames, samples) in stacks {
+        root.samples += samples;
+        let mut frame = &mut root;
+        for name in frames {
+            frame = frame.children.entry(name.clone()).or_default();
+            frame.samples += samples;
+        }
+    }
+
+    let depth = root.depth() - 1;
+    let height = TITLE_HEIGHT + depth as f64 * FRAME_HEIGHT;
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg version="1.1" width="{WIDTH}" height="{height}" xmlns="http://www.w3.org/2000/svg" font-family="monospace" font-size="12">"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="20" text-anchor="middle" font-size="16">{}</text>"#,
+        WIDTH / 2.0,
+        escape(title.trim())
+    );
+
+    let scale = WIDTH / root.samples.max(1) as f64;
+    let mut x = 0.0;
+    for (name, frame) in &root.children {
+        render_frame(&mut svg, name, frame, x, 0, height, scale, root.samples);
+        x += frame.samples as f64 * scale;
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_frame(
+    svg: &mut String,
+    name: &str,
+    frame: &Frame,
+    x: f64,
+    level: usize,
+    height: f64,
+    scale: f64,
+    total: u64,
+) {
+    let width = frame.samples as f64 * scale;
+    let y = height - (level + 1) as f64 * FRAME_HEIGHT;
+    let percent = frame.samples as f64 * 100.0 / total.max(1) as f64;
+    let name = escape(name);
+    // Only write the name when there is room for it.
+    let label_len = ((width - 6.0) / 7.0).max(0.0) as usize;
+    let label: String = if name.chars().count() <= label_len {
+        name.clone()
+    } else if label_len > 2 {
+        name.chars()
+            .take(label_len - 2)
+            .chain("..".chars())
+            .collect()
+    } else {
+        String::new()
+    };
+
+    let _ = writeln!(
+        svg,
+        r#"<g><title>{name} ({} samples, {percent:.2}%)</title><rect x="{x:.2}" y="{y:.2}" width="{width:.2}" height="{}" fill="{}" rx="2"/><text x="{:.2}" y="{:.2}">{label}</text></g>"#,
+        frame.samples,
+        FRAME_HEIGHT - 1.0,
+        color(&name),
+        x + 3.0,
+        y + FRAME_HEIGHT - 4.0,
+    );
+
+    let mut child_x = x;
+    for (child_name, child) in &frame.children {
+        render_frame(
+            svg,
+            child_name,
+            child,
+            child_x,
+            level + 1,
+            height,
+            scale,
+            total,
+        );
+        child_x += child.samples as f64 * scale;
+    }
+}
+
+/// A stable warm color for a frame, syscalls and the host in blue.
+fn color(name: &str) -> String {
+    let hash = name
+        .bytes()
+        .fold(0u32, |hash, b| hash.wrapping_mul(31).wrapping_add(b as u32));
+    if name.starts_with('[') {
+        format!("rgb(80,{},{})", 140 + hash % 60, 200 + hash % 55)
+    } else {
+        format!("rgb({},{},60)", 205 + hash % 50, 80 + hash % 120)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flamegraph_nests_frames() {
+        let stacks = vec![
+            (vec!["app".to_string(), "main".to_string()], 30),
+            (
+                vec![
+                    "app".to_string(),
+                    "main".to_string(),
+                    "[syscall] fd_write".to_string(),
+                ],
+                10,
+            ),
+            (vec!["app".to_string(), "<func3>".to_string()], 60),
+        ];
+
+        let svg = render_flamegraph("app", &stacks);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains("<title>app (100 samples, 100.00%)</title>"));
+        assert!(svg.contains("<title>main (40 samples, 40.00%)</title>"));
+        assert!(svg.contains("<title>[syscall] fd_write (10 samples, 10.00%)</title>"));
+        assert!(svg.contains("<title>&lt;func3&gt; (60 samples, 60.00%)</title>"));
+        // 1 row for the title and 3 levels of frames
+        assert!(svg.contains(r#"height="80""#));
+    }
+}
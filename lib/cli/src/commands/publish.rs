@@ -6,6 +6,9 @@ pub struct Publish {
     /// Registry to publish to
     #[clap(long)]
     pub registry: Option<String>,
+    /// Profile to publish with (by default, the active profile)
+    #[clap(long, env = "WASMER_PROFILE")]
+    pub profile: Option<String>,
     /// Run the publish logic without sending anything to the registry server
     #[clap(long, name = "dry-run")]
     pub dry_run: bool,
@@ -32,6 +35,7 @@ pub struct Publish {
 impl Publish {
     /// Executes `wasmer publish`
     pub fn execute(&self) -> Result<(), anyhow::Error> {
+        crate::utils::use_registry_profile(self.profile.as_deref())?;
         let publish = wasmer_registry::package::builder::Publish {
             registry: self.registry.clone(),
             dry_run: self.dry_run,
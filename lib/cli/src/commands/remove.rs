@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use anyhow::Error;
+use clap::Parser;
+use wasmer_registry::lockfile::Lockfile;
+
+use super::dependencies::{self, ManifestDependencies};
+
+/// Remove dependencies from a package's `wasmer.toml` and `wasmer.lock`.
+#[derive(Debug, Parser)]
+pub struct Remove {
+    /// Directory containing the `wasmer.toml` (defaults to the current dir)
+    #[clap(long)]
+    package_path: Option<PathBuf>,
+    /// The dependencies to remove (e.g. "python/python")
+    #[clap(required = true)]
+    packages: Vec<String>,
+}
+
+impl Remove {
+    /// Execute [`Remove`].
+    pub fn execute(&self) -> Result<(), Error> {
+        let package_dir = dependencies::package_dir(self.package_path.as_deref())?;
+        let mut manifest = ManifestDependencies::open(&package_dir)?;
+        let mut lockfile = Lockfile::load(manifest.dir())?;
+
+        for name in &self.packages {
+            anyhow::ensure!(
+                manifest.remove(name),
+                "{name} isn't a dependency of this package"
+            );
+            println!("Removed {name}");
+        }
+
+        manifest.save_with_lockfile(&mut lockfile)
+    }
+}
@@ -0,0 +1,398 @@
+use crate::store::StoreOptions;
+use crate::utils::read_module_bytes;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use std::convert::TryFrom;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use wasmer::*;
+use wasmer_types::Type as ValueType;
+
+#[cfg(feature = "wasi")]
+use super::run::Wasi;
+
+/// The options for the `wasmer repl` subcommand
+#[derive(Debug, Parser)]
+pub struct Repl {
+    /// WebAssembly file or package to explore
+    #[clap(name = "FILE")]
+    path: PathBuf,
+
+    /// The atom to load, when FILE is a package with several atoms
+    #[clap(long)]
+    atom: Option<String>,
+
+    #[clap(flatten)]
+    store: StoreOptions,
+
+    #[cfg(feature = "wasi")]
+    #[clap(flatten)]
+    wasi: Wasi,
+}
+
+const HELP: &str = "\
+Commands:
+  exports                          List the module's exports
+  call <function> [args...]        Call an exported function
+  global <name> [value]            Print or set an exported global
+  read <offset> <len> [memory]     Print the bytes of a memory
+  write <offset> <bytes> [memory]  Write bytes (0x0102... or \"text\") to a memory
+  help                             Print this message
+  quit                             Leave the REPL";
+
+impl Repl {
+    /// Runs logic for the `repl` subcommand
+    pub fn execute(&self) -> Result<()> {
+        let mut session = self
+            .load()
+            .with_context(|| format!("failed to load `{}`", self.path.display()))?;
+
+        println!(
+            "Loaded `{}`. Type `help` for a list of commands.",
+            self.path.display()
+        );
+
+        let stdin = std::io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            print!("> ");
+            std::io::stdout().flush()?;
+
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => break,
+            };
+            let line = line.trim();
+            if line == "quit" || line == "exit" {
+                break;
+            }
+
+            match session.eval(line) {
+                Ok(output) if output.is_empty() => {}
+                Ok(output) => println!("{output}"),
+                Err(e) => eprintln!("error: {e:#}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Session> {
+        let (mut store, _compiler_type) = self.store.get_store()?;
+        let bytes = read_module_bytes(&self.path, self.atom.as_deref())?;
+        let module = Module::new(&store, bytes)?;
+
+        #[cfg(feature = "wasi")]
+        if Wasi::has_wasi_imports(&module) {
+            let program_name = self
+                .path
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let (_env, instance) = self
+                .wasi
+                .instantiate(&mut store, &module, program_name, Vec::new())
+                .context("failed to instantiate WASI module")?;
+            return Ok(Session::new(store, instance));
+        }
+
+        let instance = Instance::new(&mut store, &module, &imports! {})?;
+        Ok(Session::new(store, instance))
+    }
+}
+
+/// An instantiated module and the commands which can be run against it.
+struct Session {
+    store: Store,
+    instance: Instance,
+}
+
+impl Session {
+    fn new(store: Store, instance: Instance) -> Self {
+        Session { store, instance }
+    }
+
+    /// Evaluates one line of input, returning what should be printed.
+    fn eval(&mut self, line: &str) -> Result<String> {
+        let words = split_words(line)?;
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (command.as_str(), args),
+            None => return Ok(String::new()),
+        };
+
+        match (command, args) {
+            ("help", _) => Ok(HELP.to_string()),
+            ("exports", []) => Ok(self.exports()),
+            ("call", [name, args @ ..]) => self.call(name, args),
+            ("global", [name]) => self.global(name, None),
+            ("global", [name, value]) => self.global(name, Some(value)),
+            ("read", [offset, len]) => self.read(None, offset, len),
+            ("read", [offset, len, memory]) => self.read(Some(memory), offset, len),
+            ("write", [offset, bytes]) => self.write(None, offset, bytes),
+            ("write", [offset, bytes, memory]) => self.write(Some(memory), offset, bytes),
+            _ => bail!("unknown command `{line}`, type `help` for a list of commands"),
+        }
+    }
+
+    fn exports(&self) -> String {
+        self.instance
+            .module()
+            .exports()
+            .map(|export| match export.ty() {
+                ExternType::Function(ty) => format!("func {}: {ty}", export.name()),
+                ExternType::Global(ty) => format!("global {}: {ty}", export.name()),
+                ExternType::Memory(ty) => format!("memory {}: {ty}", export.name()),
+                ExternType::Table(ty) => format!("table {}: {ty}", export.name()),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn call(&mut self, name: &str, args: &[String]) -> Result<String> {
+        let func = self.instance.exports.get_function(name)?.clone();
+        let ty = func.ty(&self.store);
+        if ty.params().len() != args.len() {
+            bail!(
+                "`{name}` expects {} arguments, but received {}",
+                ty.params().len(),
+                args.len()
+            );
+        }
+
+        let args = args
+            .iter()
+            .zip(ty.params())
+            .map(|(arg, ty)| parse_value(arg, *ty))
+            .collect::<Result<Vec<_>>>()?;
+        let results = func.call(&mut self.store, &args)?;
+
+        Ok(results
+            .iter()
+            .map(format_value)
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    fn global(&mut self, name: &str, value: Option<&String>) -> Result<String> {
+        let global = self.instance.exports.get_global(name)?.clone();
+        if let Some(value) = value {
+            let value = parse_value(value, global.ty(&self.store).ty)?;
+            global.set(&mut self.store, value)?;
+        }
+        Ok(format_value(&global.get(&mut self.store)))
+    }
+
+    fn memory(&self, name: Option<&String>) -> Result<Memory> {
+        let memory = match name {
+            Some(name) => self.instance.exports.get_memory(name)?,
+            None => self
+                .instance
+                .exports
+                .iter()
+                .memories()
+                .map(|(_, memory)| memory)
+                .next()
+                .ok_or_else(|| anyhow!("the module doesn't export a memory"))?,
+        };
+        Ok(memory.clone())
+    }
+
+    fn read(&self, memory: Option<&String>, offset: &str, len: &str) -> Result<String> {
+        let memory = self.memory(memory)?;
+        let offset = parse_int::<u64>(offset)?;
+        let mut buf = vec![0; parse_int::<usize>(len)?];
+        memory.view(&self.store).read(offset, &mut buf)?;
+        Ok(hex_dump(offset, &buf))
+    }
+
+    fn write(&self, memory: Option<&String>, offset: &str, bytes: &str) -> Result<String> {
+        let memory = self.memory(memory)?;
+        let offset = parse_int::<u64>(offset)?;
+        let bytes = parse_bytes(bytes)?;
+        memory.view(&self.store).write(offset, &bytes)?;
+        Ok(format!("wrote {} bytes at {offset:#x}", bytes.len()))
+    }
+}
+
+/// Splits a line into words, keeping double-quoted strings together.
+pub(super) fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            let mut word = String::from('"');
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => word.push('\n'),
+                        Some('t') => word.push('\t'),
+                        Some('0') => word.push('\0'),
+                        Some(c) => word.push(c),
+                        None => bail!("unterminated string"),
+                    },
+                    Some(c) => word.push(c),
+                    None => bail!("unterminated string"),
+                }
+            }
+            words.push(word);
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            words.push(word);
+        }
+    }
+
+    Ok(words)
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal integer.
+pub(super) fn parse_int<T>(s: &str) -> Result<T>
+where
+    T: TryFrom<u64> + std::str::FromStr,
+{
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16)
+            .ok()
+            .and_then(|v| T::try_from(v).ok()),
+        None => s.parse().ok(),
+    };
+    parsed.ok_or_else(|| anyhow!("`{s}` is not a valid integer"))
+}
+
+fn parse_value(arg: &str, ty: ValueType) -> Result<Value> {
+    let value = match ty {
+        ValueType::I32 => Value::I32(match arg.strip_prefix("0x") {
+            Some(_) => parse_int::<u32>(arg)? as i32,
+            None => arg.parse()?,
+        }),
+        ValueType::I64 => Value::I64(match arg.strip_prefix("0x") {
+            Some(_) => parse_int::<u64>(arg)? as i64,
+            None => arg.parse()?,
+        }),
+        ValueType::F32 => Value::F32(arg.parse()?),
+        ValueType::F64 => Value::F64(arg.parse()?),
+        ValueType::FuncRef if arg == "null" => Value::FuncRef(None),
+        ValueType::ExternRef if arg == "null" => Value::ExternRef(None),
+        _ => bail!("Don't know how to convert `{arg}` into {ty:?}"),
+    };
+    Ok(value)
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::I32(v) => format!("{v}: i32"),
+        Value::I64(v) => format!("{v}: i64"),
+        Value::F32(v) => format!("{v}: f32"),
+        Value::F64(v) => format!("{v}: f64"),
+        Value::V128(v) => format!("{v:#034x}: v128"),
+        Value::FuncRef(None) => "null: funcref".to_string(),
+        Value::FuncRef(Some(_)) => "<function>: funcref".to_string(),
+        Value::ExternRef(None) => "null: externref".to_string(),
+        Value::ExternRef(Some(_)) => "<extern>: externref".to_string(),
+    }
+}
+
+/// Parses `0x`-prefixed hexadecimal bytes, or a double-quoted string.
+fn parse_bytes(s: &str) -> Result<Vec<u8>> {
+    if let Some(text) = s.strip_prefix('"') {
+        return Ok(text.as_bytes().to_vec());
+    }
+
+    let hex = s
+        .strip_prefix("0x")
+        .ok_or_else(|| anyhow!("expected `0x...` or a quoted string, found `{s}`"))?;
+    if hex.len() % 2 != 0 {
+        bail!("`{s}` has an odd number of hex digits");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| anyhow!("`{s}` is not valid hex"))
+        })
+        .collect()
+}
+
+/// Formats bytes like `xxd`, 16 per line.
+pub(super) fn hex_dump(offset: u64, bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex = chunk
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+            format!("{:08x}: {hex:<47}  {ascii}", offset + 16 * i as u64)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        let mut store = Store::default();
+        let module = Module::new(
+            &store,
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 16) "hello")
+                (global (export "counter") (mut i32) (i32.const 7))
+                (func (export "add") (param i32 i64) (result i64)
+                    (i64.add (i64.extend_i32_s (local.get 0)) (local.get 1))))"#,
+        )
+        .unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        Session::new(store, instance)
+    }
+
+    #[test]
+    fn call_and_globals() {
+        let mut session = session();
+
+        assert_eq!(session.eval("call add 2 0x28").unwrap(), "42: i64");
+        assert!(session.eval("call add 1").is_err());
+        assert_eq!(session.eval("global counter").unwrap(), "7: i32");
+        assert_eq!(session.eval("global counter -1").unwrap(), "-1: i32");
+        assert!(session.eval("exports").unwrap().contains("func add"));
+    }
+
+    #[test]
+    fn read_and_write_memory() {
+        let mut session = session();
+
+        assert!(session
+            .eval("read 16 5")
+            .unwrap()
+            .starts_with("00000010: 68 65 6c 6c 6f"));
+        session.eval("write 0 \"hi there\"").unwrap();
+        session.eval("write 8 0x21ff memory").unwrap();
+        let dump = session.eval("read 0 10").unwrap();
+        assert!(dump.contains("68 69 20 74 68 65 72 65 21 ff"), "{dump}");
+        assert!(dump.ends_with("hi there!."), "{dump}");
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "cache")]
+use crate::cache_stats::CacheStats;
 use crate::common::get_cache_dir;
 use crate::logging;
 use crate::package_source::PackageSource;
@@ -11,7 +13,7 @@ use std::fs::File;
 use std::io::Write;
 use std::net::SocketAddr;
 use std::ops::Deref;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use wasmer::FunctionEnv;
 use wasmer::*;
@@ -20,6 +22,7 @@ use wasmer_types::Type as ValueType;
 use wasmer_wasix::runners::Runner;
 
 mod wasi;
+pub(crate) mod watch;
 
 pub(crate) use wasi::Wasi;
 
@@ -55,6 +58,11 @@ pub struct RunWithoutFile {
     #[clap(long = "command-name", hide = true)]
     pub(crate) command_name: Option<String>,
 
+    /// Override the first argument (`argv[0]`) passed to the program, which
+    /// defaults to the file name of the module
+    #[clap(long = "argv0")]
+    pub(crate) argv0: Option<String>,
+
     /// A prehashed string, used to speed up start times by avoiding hashing the
     /// wasm module. If the specified hash is not found, Wasmer will hash the module
     /// as if no `cache-key` argument was passed.
@@ -80,6 +88,20 @@ pub struct RunWithoutFile {
     #[clap(long = "verbose")]
     pub(crate) verbose: Option<u8>,
 
+    /// Restart the program whenever the module or package changes
+    #[clap(long = "watch")]
+    pub(crate) watch: bool,
+
+    /// With `--watch`, also restart when something changes in a mapped or
+    /// pre-opened directory
+    #[clap(long = "watch-mapped-dirs", requires = "watch")]
+    pub(crate) watch_mapped_dirs: bool,
+
+    /// With `--watch`, keep the files that the program wrote to its root
+    /// filesystem across restarts
+    #[clap(long = "watch-keep-overlay", requires = "watch")]
+    pub(crate) watch_keep_overlay: bool,
+
     #[clap(flatten)]
     pub(crate) wcgi: WcgiOptions,
 
@@ -196,11 +218,19 @@ impl RunWithPathBuf {
         if self.stack_size.is_some() {
             wasmer_vm::set_stack_size(self.stack_size.unwrap());
         }
+        // A resumed program was initialized before its snapshot was taken
+        #[cfg(feature = "wasi")]
+        let should_initialize = !self.wasi.is_resumed();
+        #[cfg(not(feature = "wasi"))]
+        let should_initialize = true;
+
         // If this module exports an _initialize function, run that first.
         if let Ok(initialize) = instance.exports.get_function("_initialize") {
-            initialize
-                .call(store, &[])
-                .with_context(|| "failed to run _initialize function")?;
+            if should_initialize {
+                initialize
+                    .call(store, &[])
+                    .with_context(|| "failed to run _initialize function")?;
+            }
         }
 
         // Do we want to invoke a function?
@@ -243,7 +273,7 @@ impl RunWithPathBuf {
             // TODO: refactor this
             if is_emscripten_module(&module) {
                 let em_env = EmEnv::new();
-                for (k, v) in self.wasi.env_vars.iter() {
+                for (k, v) in self.wasi.envs()?.iter() {
                     em_env.set_env_var(k, v);
                 }
                 // create an EmEnv with default global
@@ -279,8 +309,8 @@ impl RunWithPathBuf {
                     &mut instance,
                     env.into_mut(&mut store),
                     &mut emscripten_globals,
-                    if let Some(cn) = &self.command_name {
-                        cn
+                    if let Some(argv0) = self.argv0.as_ref().or(self.command_name.as_ref()) {
+                        argv0
                     } else {
                         self.path.to_str().unwrap()
                     },
@@ -318,8 +348,9 @@ impl RunWithPathBuf {
                     }
 
                     let program_name = self
-                        .command_name
+                        .argv0
                         .clone()
+                        .or_else(|| self.command_name.clone())
                         .or_else(|| {
                             self.path
                                 .file_name()
@@ -422,7 +453,8 @@ impl RunWithPathBuf {
             .args(args)
             .store(store)
             .addr(self.wcgi.addr)
-            .envs(self.wasi.env_vars.clone())
+            .envs(self.wasi.envs()?)
+            .secrets(self.wasi.secrets.clone())
             .map_directories(self.wasi.mapped_dirs.clone());
         if self.wasi.forward_host_env {
             runner.config().forward_host_env();
@@ -438,7 +470,8 @@ impl RunWithPathBuf {
     }
 
     fn get_store_module(&self) -> Result<(Store, Module)> {
-        let contents = std::fs::read(self.path.clone())?;
+        let mut contents = std::fs::read(self.path.clone())?;
+        strip_shebang(&mut contents);
         #[cfg(not(feature = "jsc"))]
         if wasmer_compiler::Artifact::is_deserializable(&contents) {
             let engine = wasmer_compiler::EngineBuilder::headless();
@@ -481,13 +514,16 @@ impl RunWithPathBuf {
         // as it takes space and the speedup is minimal.
         let mut cache = self.get_cache(compiler_type)?;
         // Try to get the hash from the provided `--cache-key`, otherwise
-        // generate one from the provided file `.wasm` contents.
+        // generate one from the provided file `.wasm` contents and the
+        // target it is compiled for.
         let hash = self
             .cache_key
             .as_ref()
             .and_then(|key| Hash::from_str(key).ok())
-            .unwrap_or_else(|| Hash::generate(contents));
-        match unsafe { cache.load(store, hash) } {
+            .unwrap_or_else(|| Hash::generate_for_target(contents, store.engine().target()));
+        let result = unsafe { cache.load(store, hash) };
+        CacheStats::record(&get_cache_dir(), result.is_ok());
+        match result {
             Ok(module) => Ok(module),
             Err(e) => {
                 match e {
@@ -624,6 +660,10 @@ impl RunWithPathBuf {
 impl Run {
     /// Executes the `wasmer run` command
     pub fn execute(&self) -> Result<(), anyhow::Error> {
+        if self.options.watch {
+            return watch::watch(self);
+        }
+
         // downloads and installs the package if necessary
         let path_to_run = self.path.download_and_get_filepath()?;
         RunWithPathBuf {
@@ -659,7 +699,7 @@ impl Run {
             path: PackageSource::parse(executable).unwrap(),
             options: RunWithoutFile {
                 args: args.to_vec(),
-                command_name: Some(original_executable.to_string()),
+                argv0: Some(original_executable.to_string()),
                 store,
                 wasi: Wasi::for_binfmt_interpreter()?,
                 ..Default::default()
@@ -671,6 +711,55 @@ impl Run {
     fn from_binfmt_args_fallible() -> Result<Run> {
         bail!("binfmt_misc is only available on linux.")
     }
+
+    /// Create Run instance for a wasm script started through its `#!` line
+    /// (e.g. `#!/usr/bin/env wasmer`), if one of the arguments is a script.
+    ///
+    /// The arguments before the script are options for wasmer, and the
+    /// ones after it are passed to the program untouched. Like for native
+    /// scripts, the program's `argv[0]` is the path to the script.
+    pub fn from_shebang_args(args: &[String]) -> Option<Run> {
+        let position = args
+            .iter()
+            .skip(1)
+            .position(|arg| is_script(Path::new(arg)))?
+            + 1;
+        let mut run = Run::try_parse_from(&args[..=position]).ok()?;
+        run.options.args = args[position + 1..].to_vec();
+        if run.options.argv0.is_none() {
+            run.options.argv0 = Some(args[position].clone());
+        }
+        Some(run)
+    }
+}
+
+/// Whether a file starts with a `#!` line.
+fn is_script(path: &Path) -> bool {
+    use std::io::Read;
+
+    let mut magic = [0; 2];
+    path.is_file()
+        && std::fs::File::open(path)
+            .and_then(|mut f| f.read_exact(&mut magic))
+            .is_ok()
+        && &magic == b"#!"
+}
+
+/// Remove the `#!` line of a wasm script. The line break is kept in front
+/// of `.wat` code, so that line numbers in error messages stay correct.
+fn strip_shebang(contents: &mut Vec<u8>) {
+    if !contents.starts_with(b"#!") {
+        return;
+    }
+    let end_of_line = contents
+        .iter()
+        .position(|b| *b == b'\n')
+        .unwrap_or(contents.len());
+    if contents[end_of_line..].starts_with(b"\n\0asm") {
+        contents.drain(..=end_of_line);
+    } else {
+        contents.drain(..end_of_line);
+    }
 }
 
 #[cfg(feature = "coredump")]
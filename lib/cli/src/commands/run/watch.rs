@@ -0,0 +1,196 @@
+//! `wasmer run --watch`: restart the program whenever its inputs change.
+//!
+//! The program runs in a child `wasmer` process started with the same
+//! arguments (minus the watch flags), so that it can be stopped no matter
+//! what the guest is doing when a change comes in.
+
+use super::Run;
+use crate::package_source::PackageSource;
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::{Duration, SystemTime};
+
+/// How often the watched paths are scanned for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+const WATCH_FLAGS: &[&str] = &["--watch", "--watch-mapped-dirs", "--watch-keep-overlay"];
+
+/// Modification time and size of every file below the watched paths
+pub(crate) type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+pub(super) fn watch(run: &Run) -> Result<()> {
+    let source = match &run.path {
+        PackageSource::File(path) => PathBuf::from(path),
+        _ => bail!("--watch only works with local files and directories"),
+    };
+
+    let mut paths = vec![source];
+    if run.options.watch_mapped_dirs {
+        let wasi = &run.options.wasi;
+        paths.extend(wasi.mapped_dirs.iter().map(|dir| dir.host.clone()));
+        paths.extend(wasi.pre_opened_directories.iter().cloned());
+    }
+
+    // The overlay directory lives as long as the watch session
+    let overlay = if run.options.watch_keep_overlay {
+        Some(tempfile::tempdir().context("Unable to create the overlay directory")?)
+    } else {
+        None
+    };
+
+    let exe = std::env::current_exe().context("Unable to find the wasmer executable")?;
+    let args = child_args(
+        std::env::args_os().skip(1),
+        overlay.as_ref().map(|dir| dir.path()),
+    );
+
+    let mut last = snapshot(&paths);
+    let mut child = Some(spawn(&exe, &args)?);
+
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+
+        if let Some(running) = child.as_mut() {
+            if let Some(status) = running.try_wait()? {
+                eprintln!("[wasmer] Program exited with {status}, waiting for changes...");
+                child = None;
+            }
+        }
+
+        let current = snapshot(&paths);
+        if current == last {
+            continue;
+        }
+
+        // Editors tend to write files in several steps, so wait for things
+        // to settle down before restarting
+        last = current;
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let current = snapshot(&paths);
+            if current == last {
+                break;
+            }
+            last = current;
+        }
+
+        if let Some(mut running) = child.take() {
+            let _ = running.kill();
+            let _ = running.wait();
+        }
+        eprintln!("[wasmer] Change detected, restarting...");
+        child = Some(spawn(&exe, &args)?);
+    }
+}
+
+fn spawn(exe: &Path, args: &[OsString]) -> Result<Child> {
+    Command::new(exe)
+        .args(args)
+        .spawn()
+        .with_context(|| format!("Unable to start \"{}\"", exe.display()))
+}
+
+/// The arguments of the child process: ours without the watch flags, and
+/// with `--overlay-dir` when the overlay is kept across restarts. Anything
+/// after `--` belongs to the guest and is left untouched.
+fn child_args(
+    args: impl IntoIterator<Item = OsString>,
+    mut overlay: Option<&Path>,
+) -> Vec<OsString> {
+    let mut child_args = Vec::new();
+    let mut guest_args = false;
+
+    for arg in args {
+        if !guest_args {
+            if arg == "--" {
+                guest_args = true;
+                if let Some(dir) = overlay.take() {
+                    child_args.push(overlay_flag(dir));
+                }
+            } else if WATCH_FLAGS.iter().any(|flag| arg == *flag) {
+                continue;
+            }
+        }
+        child_args.push(arg);
+    }
+
+    if let Some(dir) = overlay {
+        child_args.push(overlay_flag(dir));
+    }
+
+    child_args
+}
+
+fn overlay_flag(dir: &Path) -> OsString {
+    let mut flag = OsString::from("--overlay-dir=");
+    flag.push(dir);
+    flag
+}
+
+pub(crate) fn snapshot(paths: &[PathBuf]) -> Snapshot {
+    let mut files = Snapshot::new();
+    for path in paths {
+        for entry in walkdir::WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            if let Ok(metadata) = entry.metadata() {
+                if metadata.is_file() {
+                    files.insert(
+                        entry.path().to_path_buf(),
+                        (metadata.modified().ok(), metadata.len()),
+                    );
+                }
+            }
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watch_flags_are_not_passed_to_the_child() {
+        let args = [
+            "run",
+            "--watch",
+            "app.wasm",
+            "--watch-keep-overlay",
+            "--",
+            "--watch",
+        ]
+        .iter()
+        .map(OsString::from);
+
+        assert_eq!(
+            child_args(args, Some(Path::new("/tmp/overlay"))),
+            [
+                "run",
+                "app.wasm",
+                "--overlay-dir=/tmp/overlay",
+                "--",
+                "--watch"
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshots_change_when_files_do() {
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("app.wasm");
+        std::fs::write(&file, b"\0asm").unwrap();
+        let paths = vec![temp.path().to_path_buf()];
+
+        let before = snapshot(&paths);
+        assert_eq!(before.len(), 1);
+        assert_eq!(before, snapshot(&paths));
+
+        std::fs::write(&file, b"\0asm\x01\0\0\0").unwrap();
+        assert_ne!(before, snapshot(&paths));
+    }
+}
@@ -32,6 +32,7 @@ use wasmer_wasix::runners::{MappedDirectory, Runner};
 use webc::{metadata::Manifest, v1::DirOrFile, Container};
 
 use crate::{
+    oci::OciReference,
     store::StoreOptions,
     wasmer_home::{DownloadCached, ModuleCache, WasmerHome},
 };
@@ -175,7 +176,8 @@ impl RunUnstable {
                     .args(self.args.clone())
                     .store(store)
                     .addr(self.wcgi.addr)
-                    .envs(self.wasi.env_vars.clone())
+                    .envs(self.wasi.envs()?)
+                    .secrets(self.wasi.secrets.clone())
                     .map_directories(self.wasi.mapped_dirs.clone())
                     .callbacks(Callbacks::new(self.wcgi.addr));
                 if self.wasi.forward_host_env {
@@ -194,7 +196,8 @@ impl RunUnstable {
                         compile_wasm_cached("".to_string(), bytes, &mut cache, engine)
                     })
                     .with_args(self.args.clone())
-                    .with_envs(self.wasi.env_vars.clone())
+                    .with_envs(self.wasi.envs()?)
+                    .with_secrets(self.wasi.secrets.clone())
                     .with_mapped_directories(self.wasi.mapped_dirs.clone());
                 if self.wasi.forward_host_env {
                     runner.set_forward_host_env();
@@ -327,7 +330,7 @@ fn infer_webc_entrypoint(manifest: &Manifest) -> Result<&str, Error> {
     }
 }
 
-fn compile_directory_to_webc(dir: &Path) -> Result<Vec<u8>, Error> {
+pub(crate) fn compile_directory_to_webc(dir: &Path) -> Result<Vec<u8>, Error> {
     let mut files = BTreeMap::new();
     load_files_from_disk(&mut files, dir, dir)?;
 
@@ -373,6 +376,7 @@ enum PackageSource {
     Dir(PathBuf),
     Package(Package),
     Url(Url),
+    Oci(OciReference),
 }
 
 impl PackageSource {
@@ -384,6 +388,10 @@ impl PackageSource {
             return Ok(PackageSource::Dir(path.to_path_buf()));
         }
 
+        if s.starts_with("oci://") {
+            return s.parse().map(PackageSource::Oci);
+        }
+
         if let Ok(url) = Url::parse(s) {
             return Ok(PackageSource::Url(url));
         }
@@ -413,6 +421,10 @@ impl PackageSource {
                 let cached = home.download_url(url)?;
                 Ok(TargetOnDisk::Webc(cached))
             }
+            PackageSource::Oci(reference) => {
+                let cached = home.download_oci(reference)?;
+                Ok(TargetOnDisk::Webc(cached))
+            }
         }
     }
 }
@@ -423,6 +435,7 @@ impl Display for PackageSource {
             PackageSource::File(path) | PackageSource::Dir(path) => write!(f, "{}", path.display()),
             PackageSource::Package(p) => write!(f, "{p}"),
             PackageSource::Url(u) => write!(f, "{u}"),
+            PackageSource::Oci(reference) => write!(f, "{reference}"),
         }
     }
 }
@@ -548,7 +561,7 @@ fn compile_wasm_cached(
 ) -> Result<Module, Error> {
     tracing::debug!("Trying to retrieve module from cache");
 
-    let hash = wasmer_cache::Hash::generate(wasm);
+    let hash = wasmer_cache::Hash::generate_for_target(wasm, engine.target());
     tracing::debug!("Generated hash: {}", hash);
 
     unsafe {
@@ -647,13 +660,13 @@ impl Default for WcgiOptions {
 }
 
 #[derive(Debug)]
-struct Callbacks {
+pub(crate) struct Callbacks {
     stderr: Mutex<LineWriter<std::io::Stderr>>,
     addr: SocketAddr,
 }
 
 impl Callbacks {
-    fn new(addr: SocketAddr) -> Self {
+    pub(crate) fn new(addr: SocketAddr) -> Self {
         Callbacks {
             stderr: Mutex::new(LineWriter::new(std::io::stderr())),
             addr,
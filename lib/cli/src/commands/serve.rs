@@ -0,0 +1,328 @@
+//! `wasmer serve`: serve WCGI packages from a long-running process.
+
+use super::run::watch::{snapshot, Snapshot};
+use super::run::{Wasi, WcgiOptions};
+use super::run_unstable::{compile_directory_to_webc, Callbacks};
+use crate::package_source::PackageSource;
+use crate::store::StoreOptions;
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use wasmer_wasix::runners::wcgi::{
+    Daemon, DaemonConfig, Handler, WcgiRunner, HEALTH_PATH, METRICS_PATH,
+};
+use wasmer_wasix::runtime::task_manager::tokio::TokioTaskManager;
+use wasmer_wasix::runtime::VirtualTaskManager;
+use webc::metadata::{annotations::WCGI_RUNNER_URI, Manifest};
+use webc::Container;
+
+/// How often local packages are checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The options for the `wasmer serve` subcommand
+#[derive(Debug, Clone, Parser)]
+pub struct Serve {
+    /// The packages to serve, as `SOURCE` or `ROUTE=SOURCE` (e.g.
+    /// `/api=./api.webc`). A single package is served at `/`, otherwise each
+    /// package is served below its name.
+    #[clap(value_name = "[ROUTE=]SOURCE", required = true)]
+    packages: Vec<ServedPackage>,
+
+    #[clap(flatten)]
+    wcgi: WcgiOptions,
+
+    /// The most instances of each package that run at the same time
+    #[clap(long, default_value_t = 16)]
+    max_instances: usize,
+
+    /// How many seconds a request waits for a free instance before it is
+    /// turned away with "503 Service Unavailable"
+    #[clap(long, value_name = "SECONDS", default_value_t = 30)]
+    queue_timeout: u64,
+
+    /// Don't reload local packages when they change on disk
+    #[clap(long)]
+    no_reload: bool,
+
+    #[clap(flatten)]
+    store: StoreOptions,
+
+    #[clap(flatten)]
+    wasi: Wasi,
+}
+
+/// A package given on the command line, and where to serve it.
+#[derive(Debug, Clone)]
+struct ServedPackage {
+    route: Option<String>,
+    raw_source: String,
+    source: PackageSource,
+}
+
+impl FromStr for ServedPackage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (route, raw_source) = match s.split_once('=') {
+            Some((route, source)) if route.starts_with('/') => (Some(route.to_string()), source),
+            _ => (None, s),
+        };
+
+        Ok(ServedPackage {
+            route,
+            raw_source: raw_source.to_string(),
+            source: PackageSource::parse(raw_source)?,
+        })
+    }
+}
+
+/// A package that is being served.
+#[derive(Debug)]
+struct Served {
+    route: String,
+    source: String,
+    path: PathBuf,
+    /// Only local packages are reloaded
+    reload: bool,
+    loaded: Snapshot,
+    seen: Snapshot,
+}
+
+impl Serve {
+    /// Execute `wasmer serve`
+    pub fn execute(&self) -> Result<()> {
+        let routes = self.routes()?;
+
+        let daemon = Daemon::new(DaemonConfig {
+            max_instances: self.max_instances,
+            queue_timeout: Duration::from_secs(self.queue_timeout),
+        });
+        let task_manager = TokioTaskManager::shared();
+        let shared: Arc<dyn VirtualTaskManager> = Arc::new(task_manager.clone());
+
+        let mut served = Vec::new();
+        for (route, package) in routes {
+            let path = package.source.download_and_get_filepath()?;
+            let handler = self
+                .load(&path, &shared)
+                .with_context(|| format!("Unable to load \"{}\"", package.raw_source))?;
+            daemon.insert(&route, package.raw_source.clone(), handler)?;
+
+            let files = snapshot(&[path.clone()]);
+            served.push(Served {
+                route,
+                source: package.raw_source,
+                path,
+                reload: matches!(package.source, PackageSource::File(_)) && !self.no_reload,
+                loaded: files.clone(),
+                seen: files,
+            });
+        }
+
+        let addr = self.wcgi.addr;
+        for entry in &served {
+            println!("Serving {} at http://{addr}{}", entry.source, entry.route);
+        }
+        println!(
+            "Health checks at http://{addr}{HEALTH_PATH}, metrics at http://{addr}{METRICS_PATH}"
+        );
+
+        if served.iter().any(|entry| entry.reload) {
+            let this = self.clone();
+            let daemon = daemon.clone();
+            let shared = Arc::clone(&shared);
+            std::thread::spawn(move || this.reload_loop(served, &daemon, &shared));
+        }
+
+        task_manager
+            .runtime_handle()
+            .block_on(daemon.serve(addr, std::future::pending()))
+    }
+
+    /// Work out where each package is served, making sure no two packages
+    /// end up at the same route.
+    fn routes(&self) -> Result<Vec<(String, ServedPackage)>> {
+        let single = self.packages.len() == 1;
+        let mut seen = BTreeSet::new();
+        let mut routes = Vec::new();
+
+        for package in &self.packages {
+            let route = match &package.route {
+                Some(route) => route.clone(),
+                None if single => "/".to_string(),
+                None => format!("/{}", default_route(&package.source)),
+            };
+            let normalized = match route.trim_end_matches('/') {
+                "" => "/",
+                trimmed => trimmed,
+            };
+            if !seen.insert(normalized.to_string()) {
+                bail!(
+                    "More than one package would be served at \"{normalized}\", use ROUTE=SOURCE to pick a different route"
+                );
+            }
+            routes.push((route, package.clone()));
+        }
+
+        Ok(routes)
+    }
+
+    /// Compile a package's WCGI command.
+    fn load(&self, path: &Path, task_manager: &Arc<dyn VirtualTaskManager>) -> Result<Handler> {
+        let container = if path.is_dir() {
+            let webc = compile_directory_to_webc(path).with_context(|| {
+                format!("Unable to bundle \"{}\" as a WEBC package", path.display())
+            })?;
+            Container::from_bytes(webc).context("Unable to parse the generated WEBC file")?
+        } else {
+            Container::from_disk(path.to_path_buf())
+                .with_context(|| format!("Unable to load \"{}\"", path.display()))?
+        };
+        let command = wcgi_command(container.manifest())?.to_string();
+
+        let (store, _compiler_type) = self.store.get_store()?;
+        let mut runner = WcgiRunner::new(&command);
+        runner
+            .config()
+            .store(store)
+            .shared_task_manager(Arc::clone(task_manager))
+            .envs(self.wasi.envs()?)
+            .secrets(self.wasi.secrets.clone())
+            .map_directories(self.wasi.mapped_dirs.clone())
+            .callbacks(Callbacks::new(self.wcgi.addr));
+        if self.wasi.forward_host_env {
+            runner.config().forward_host_env();
+        }
+
+        runner.prepare_handler(&command, &container)
+    }
+
+    /// Swap in new versions of local packages as they change. A package
+    /// that fails to load keeps being served in its previous version.
+    fn reload_loop(
+        &self,
+        mut served: Vec<Served>,
+        daemon: &Daemon,
+        task_manager: &Arc<dyn VirtualTaskManager>,
+    ) {
+        loop {
+            std::thread::sleep(RELOAD_INTERVAL);
+
+            for entry in served.iter_mut().filter(|entry| entry.reload) {
+                let current = snapshot(&[entry.path.clone()]);
+
+                // Wait for writes to settle down before reloading
+                let settled = current == entry.seen;
+                entry.seen = current;
+                if !settled || entry.seen == entry.loaded {
+                    continue;
+                }
+                entry.loaded = entry.seen.clone();
+
+                match self.load(&entry.path, task_manager) {
+                    Ok(handler) => match daemon.insert(&entry.route, entry.source.clone(), handler)
+                    {
+                        Ok(_) => eprintln!("[wasmer] Reloaded {}", entry.source),
+                        Err(e) => eprintln!("[wasmer] Unable to reload {}: {e:?}", entry.source),
+                    },
+                    Err(e) => eprintln!(
+                        "[wasmer] Unable to reload {}, still serving the previous version: {e:?}",
+                        entry.source
+                    ),
+                }
+            }
+        }
+    }
+}
+
+/// Pick the command to serve: the entrypoint if it is a WCGI command,
+/// otherwise the first WCGI command.
+fn wcgi_command(manifest: &Manifest) -> Result<&str> {
+    let is_wcgi = |name: &str| {
+        manifest
+            .commands
+            .get(name)
+            .map(|cmd| cmd.runner.starts_with(WCGI_RUNNER_URI))
+            .unwrap_or(false)
+    };
+
+    if let Some(entrypoint) = manifest.entrypoint.as_deref() {
+        if is_wcgi(entrypoint) {
+            return Ok(entrypoint);
+        }
+    }
+
+    manifest
+        .commands
+        .keys()
+        .map(|name| name.as_str())
+        .find(|name| is_wcgi(name))
+        .context("The package doesn't have any WCGI commands")
+}
+
+/// The route a package is served at when serving several packages.
+fn default_route(source: &PackageSource) -> String {
+    let name = match source {
+        PackageSource::Package(pkg) => pkg.name.clone(),
+        PackageSource::Oci(reference) => reference
+            .repository
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        PackageSource::File(path) => file_stem(Path::new(path)),
+        PackageSource::Url(url) => url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .map(|last| file_stem(Path::new(last)))
+            .unwrap_or_default(),
+    };
+
+    if name.is_empty() {
+        "package".to_string()
+    } else {
+        name
+    }
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serve(args: &[&str]) -> Serve {
+        Serve::try_parse_from(std::iter::once("serve").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn a_single_package_is_served_at_the_root() {
+        let routes = serve(&["./site.webc"]).routes().unwrap();
+        assert_eq!(routes[0].0, "/");
+    }
+
+    #[test]
+    fn several_packages_are_served_below_their_names() {
+        let routes = serve(&["./site.webc", "/v1=./api.webc", "wasmer/static-server@1.0"])
+            .routes()
+            .unwrap();
+        let routes: Vec<_> = routes.iter().map(|(route, _)| route.as_str()).collect();
+        assert_eq!(routes, ["/site", "/v1", "/static-server"]);
+    }
+
+    #[test]
+    fn packages_cant_share_a_route() {
+        let err = serve(&["/api=./a.webc", "/api/=./b.webc"])
+            .routes()
+            .unwrap_err();
+        assert!(err.to_string().contains("\"/api\""));
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Error};
+use clap::Parser;
+use wasmer_registry::lockfile::Lockfile;
+
+use super::dependencies::{self, ManifestDependencies};
+
+/// Upgrade the dependencies of a package's `wasmer.toml` to their latest
+/// versions.
+///
+/// Dependencies are relocked to the latest version matching their version
+/// requirement in the `wasmer.toml`, unless `--incompatible` is given.
+#[derive(Debug, Parser)]
+pub struct Upgrade {
+    /// The registry to resolve dependencies with.
+    #[clap(long, env = "WAPM_REGISTRY")]
+    registry: Option<String>,
+    /// The profile whose registry dependencies are resolved with.
+    #[clap(long, env = "WASMER_PROFILE")]
+    profile: Option<String>,
+    /// Directory containing the `wasmer.toml` (defaults to the current dir)
+    #[clap(long)]
+    package_path: Option<PathBuf>,
+    /// Also upgrade to versions that don't match the version requirement,
+    /// updating it in the `wasmer.toml`
+    #[clap(long)]
+    incompatible: bool,
+    /// The dependencies to upgrade (defaults to all of them)
+    packages: Vec<String>,
+}
+
+impl Upgrade {
+    /// Execute [`Upgrade`].
+    pub fn execute(&self) -> Result<(), Error> {
+        crate::utils::use_registry_profile(self.profile.as_deref())?;
+        let registry = dependencies::registry(self.registry.as_deref())
+            .context("Unable to determine which registry to use")?;
+
+        let package_dir = dependencies::package_dir(self.package_path.as_deref())?;
+        let mut manifest = ManifestDependencies::open(&package_dir)?;
+        let mut lockfile = Lockfile::load(manifest.dir())?;
+
+        for name in &self.packages {
+            anyhow::ensure!(
+                manifest.get(name).is_some(),
+                "{name} isn't a dependency of this package"
+            );
+        }
+
+        for (name, requirement) in manifest.iter() {
+            if !self.packages.is_empty() && !self.packages.contains(&name) {
+                continue;
+            }
+            let previous = lockfile.get(&name).map(|p| p.version.clone());
+
+            let latest = dependencies::lock(&registry, &name, "*")?;
+            let compatible = semver::VersionReq::parse(&requirement)
+                .ok()
+                .zip(semver::Version::parse(&latest.version).ok())
+                .map_or(false, |(req, version)| req.matches(&version));
+
+            let locked = if compatible {
+                latest
+            } else if self.incompatible {
+                manifest.insert(&name, &latest.version)?;
+                latest
+            } else {
+                println!(
+                    "{name}: the latest version {} doesn't match \"{requirement}\", \
+                     use --incompatible to upgrade to it",
+                    latest.version
+                );
+                match previous {
+                    Some(_) => continue,
+                    // Make sure the dependency is locked at all
+                    None => dependencies::lock(&registry, &name, &requirement)?,
+                }
+            };
+
+            match previous {
+                Some(previous) if previous == locked.version => {
+                    println!("{name} is up to date ({previous})")
+                }
+                Some(previous) => println!("Upgraded {name} {previous} -> {}", locked.version),
+                None => println!("Locked {name} to {}", locked.version),
+            }
+            lockfile.insert(locked);
+        }
+
+        manifest.save_with_lockfile(&mut lockfile)
+    }
+}
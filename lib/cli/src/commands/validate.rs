@@ -6,22 +6,85 @@ use wasmer::*;
 
 #[derive(Debug, Parser)]
 /// The options for the `wasmer validate` subcommand
+///
+/// Exits with 0 when the module is valid, 1 when it is invalid, 2 when it
+/// needs features the engine doesn't enable and 3 when it uses a feature
+/// denied with `--deny-feature`.
 pub struct Validate {
     /// File to validate as WebAssembly
     #[clap(name = "FILE")]
     path: PathBuf,
 
+    /// Print the report as JSON
+    #[clap(long)]
+    json: bool,
+
+    /// Fail if the module uses this feature, even when the engine enables
+    /// it (e.g. `threads`, `simd`). Can be given more than once.
+    #[clap(long = "deny-feature", value_name = "FEATURE")]
+    deny_features: Vec<String>,
+
     #[clap(flatten)]
     store: StoreOptions,
 }
 
+/// The module is valid with the engine's features.
+const EXIT_VALID: i32 = 0;
+/// The module is invalid, whatever the features.
+#[cfg(feature = "compiler")]
+const EXIT_INVALID: i32 = 1;
+/// The module is valid, but only with features the engine doesn't enable.
+#[cfg(feature = "compiler")]
+const EXIT_UNSUPPORTED: i32 = 2;
+/// The module uses a feature denied with `--deny-feature`.
+#[cfg(feature = "compiler")]
+const EXIT_DENIED: i32 = 3;
+
 impl Validate {
     /// Runs logic for the `validate` subcommand
     pub fn execute(&self) -> Result<()> {
-        self.inner_execute()
-            .context(format!("failed to validate `{}`", self.path.display()))
+        let code = self
+            .inner_execute()
+            .context(format!("failed to validate `{}`", self.path.display()))?;
+        if code != EXIT_VALID {
+            std::process::exit(code);
+        }
+        Ok(())
     }
-    fn inner_execute(&self) -> Result<()> {
+
+    #[cfg(feature = "compiler")]
+    fn inner_execute(&self) -> Result<i32> {
+        let (store, compiler_type) = self.store.get_store()?;
+        let module_contents = std::fs::read(&self.path)?;
+        if !is_wasm(&module_contents) {
+            bail!("`wasmer validate` only validates WebAssembly files");
+        }
+        for name in &self.deny_features {
+            if !KNOWN_FEATURES.contains(&name.as_str()) {
+                bail!(
+                    "Unknown feature \"{name}\", expected one of: {}",
+                    KNOWN_FEATURES.join(", ")
+                );
+            }
+        }
+
+        let report = Module::validate_detailed(&store, &module_contents)?;
+        let report = Report::new(&report, &compiler_type.to_string(), &self.deny_features);
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            self.print_report(&report);
+        }
+
+        Ok(report.exit_code)
+    }
+
+    #[cfg(not(feature = "compiler"))]
+    fn inner_execute(&self) -> Result<i32> {
+        if self.json || !self.deny_features.is_empty() {
+            bail!("`--json` and `--deny-feature` need a compiler to be enabled");
+        }
         let (store, _compiler_type) = self.store.get_store()?;
         let module_contents = std::fs::read(&self.path)?;
         if !is_wasm(&module_contents) {
@@ -29,6 +92,244 @@ impl Validate {
         }
         Module::validate(&store, &module_contents)?;
         eprintln!("Validation passed for `{}`.", self.path.display());
-        Ok(())
+        Ok(EXIT_VALID)
+    }
+
+    #[cfg(feature = "compiler")]
+    fn print_report(&self, report: &Report) {
+        let path = self.path.display();
+
+        match &report.required_features {
+            Some(required) if required.is_empty() => {
+                eprintln!("`{path}` doesn't require any feature proposal.")
+            }
+            Some(required) => eprintln!("`{path}` requires: {}", required.join(", ")),
+            None => {}
+        }
+
+        for usage in &report.feature_uses {
+            let status = if report.denied_features.contains(&usage.feature) {
+                "denied"
+            } else if report.unsupported_features.contains(&usage.feature) {
+                "not enabled"
+            } else {
+                "enabled"
+            };
+            eprintln!("  {} ({status}): {}", usage.feature, usage.location);
+        }
+
+        match report.exit_code {
+            EXIT_VALID => eprintln!("Validation passed for `{path}`."),
+            EXIT_UNSUPPORTED => eprintln!(
+                "Validation failed for `{path}`: {} isn't enabled for {}",
+                report.unsupported_features.join(", "),
+                report.compiler,
+            ),
+            EXIT_DENIED => eprintln!(
+                "Validation failed for `{path}`: {} is denied",
+                report.denied_features.join(", "),
+            ),
+            _ => match &report.error {
+                Some(error) => eprintln!("Validation failed for `{path}`: {}", error.location),
+                None => eprintln!("Validation failed for `{path}`."),
+            },
+        }
+    }
+}
+
+/// The features that can be given to `--deny-feature`.
+#[cfg(feature = "compiler")]
+const KNOWN_FEATURES: &[&str] = &[
+    "threads",
+    "reference-types",
+    "simd",
+    "bulk-memory",
+    "multi-value",
+    "tail-call",
+    "multi-memory",
+    "memory64",
+    "exceptions",
+    "relaxed-simd",
+    "extended-const",
+];
+
+/// Everything `wasmer validate` found out about a module.
+#[cfg(feature = "compiler")]
+#[derive(Debug, serde::Serialize)]
+struct Report {
+    /// The compiler whose features the module was validated with.
+    compiler: String,
+    valid: bool,
+    enabled_features: Vec<&'static str>,
+    /// Unknown when the module is invalid even with all the features.
+    required_features: Option<Vec<&'static str>>,
+    unsupported_features: Vec<&'static str>,
+    denied_features: Vec<&'static str>,
+    feature_uses: Vec<FeatureUseReport>,
+    error: Option<LocationReport>,
+    exit_code: i32,
+}
+
+#[cfg(feature = "compiler")]
+#[derive(Debug, serde::Serialize)]
+struct FeatureUseReport {
+    feature: &'static str,
+    location: LocationReport,
+}
+
+#[cfg(feature = "compiler")]
+#[derive(Debug, serde::Serialize)]
+struct LocationReport {
+    message: String,
+    offset: usize,
+    function: Option<u32>,
+    operator: Option<String>,
+    #[serde(skip)]
+    display: String,
+}
+
+#[cfg(feature = "compiler")]
+impl std::fmt::Display for LocationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.display)
+    }
+}
+
+#[cfg(feature = "compiler")]
+impl From<&ValidationError> for LocationReport {
+    fn from(error: &ValidationError) -> Self {
+        LocationReport {
+            message: error.message.clone(),
+            offset: error.offset,
+            function: error.function.map(|index| index.as_u32()),
+            operator: error.operator.clone(),
+            display: error.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "compiler")]
+impl Report {
+    fn new(report: &ValidationReport, compiler: &str, deny_features: &[String]) -> Self {
+        use super::inspect::feature_names;
+
+        let required_features = report.required_features.as_ref().map(feature_names);
+        let unsupported_features = report.unsupported_features();
+        let denied_features: Vec<&'static str> = required_features
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|name| deny_features.iter().any(|denied| denied == name))
+            .collect();
+
+        let exit_code = if !unsupported_features.is_empty() {
+            EXIT_UNSUPPORTED
+        } else if report.error.is_some() {
+            EXIT_INVALID
+        } else if !denied_features.is_empty() {
+            EXIT_DENIED
+        } else {
+            EXIT_VALID
+        };
+
+        Report {
+            compiler: compiler.to_string(),
+            valid: report.is_valid(),
+            enabled_features: feature_names(&report.enabled_features),
+            required_features,
+            unsupported_features,
+            denied_features,
+            feature_uses: report
+                .feature_uses
+                .iter()
+                .map(|usage| FeatureUseReport {
+                    feature: usage.feature,
+                    location: LocationReport::from(&usage.location),
+                })
+                .collect(),
+            error: report.error.as_ref().map(LocationReport::from),
+            exit_code,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "compiler"))]
+mod tests {
+    use super::*;
+
+    fn validation_error(message: &str) -> ValidationError {
+        ValidationError {
+            message: message.to_string(),
+            offset: 0x2a,
+            function: None,
+            operator: None,
+        }
+    }
+
+    /// Features with only SIMD, if anything, enabled.
+    fn simd_features(simd: bool) -> Features {
+        let mut features = Features::new();
+        features
+            .threads(false)
+            .bulk_memory(false)
+            .multi_value(false)
+            .simd(simd);
+        features
+    }
+
+    fn simd_report(enabled: bool) -> ValidationReport {
+        let enabled_features = simd_features(enabled);
+        let required_features = simd_features(true);
+
+        ValidationReport {
+            enabled_features,
+            required_features: Some(required_features),
+            error: if enabled {
+                None
+            } else {
+                Some(validation_error("SIMD support is not enabled"))
+            },
+            feature_uses: vec![FeatureUse {
+                feature: "simd",
+                location: validation_error("SIMD support is not enabled"),
+            }],
+        }
+    }
+
+    #[test]
+    fn valid_modules_pass() {
+        let report = Report::new(&simd_report(true), "cranelift", &[]);
+        assert_eq!(report.exit_code, EXIT_VALID);
+        assert_eq!(report.required_features.unwrap(), ["simd"]);
+    }
+
+    #[test]
+    fn features_the_engine_doesnt_enable_are_reported() {
+        let report = Report::new(&simd_report(false), "singlepass", &[]);
+        assert_eq!(report.exit_code, EXIT_UNSUPPORTED);
+        assert_eq!(report.unsupported_features, ["simd"]);
+    }
+
+    #[test]
+    fn denied_features_fail_validation() {
+        let report = Report::new(&simd_report(true), "cranelift", &["simd".to_string()]);
+        assert_eq!(report.exit_code, EXIT_DENIED);
+        assert_eq!(report.denied_features, ["simd"]);
+    }
+
+    #[test]
+    fn invalid_modules_fail_validation() {
+        let report = Report::new(
+            &ValidationReport {
+                enabled_features: simd_features(false),
+                required_features: None,
+                error: Some(validation_error("type mismatch")),
+                feature_uses: Vec::new(),
+            },
+            "cranelift",
+            &["simd".to_string()],
+        );
+        assert_eq!(report.exit_code, EXIT_INVALID);
+        assert!(report.denied_features.is_empty());
     }
 }
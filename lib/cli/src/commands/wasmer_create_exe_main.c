@@ -21,6 +21,25 @@ static void print_wasmer_error() {
   free(error_str);
 }
 
+// The file name of the executable, to run the command named like it
+// (e.g. when the executable is linked to as `pip`)
+static const char *program_name(const char *path) {
+  const char *name = path;
+  for (const char *c = path; *c; c++) {
+    if (*c == '/' || *c == '\\') {
+      name = c + 1;
+    }
+  }
+  return name;
+}
+
+// Whether `selected` names the command, ignoring an `.exe` extension
+static bool is_command(const char *selected, const char *command) {
+  size_t len = strlen(command);
+  return selected != NULL && strncmp(selected, command, len) == 0 &&
+         (selected[len] == '\0' || strcmp(selected + len, ".exe") == 0);
+}
+
 #ifdef WASI
 static void pass_mapdir_arg(wasi_config_t *wasi_config, char *mapdir) {
   int colon_location = strchr(mapdir, ':') - mapdir;
@@ -51,8 +70,15 @@ static void handle_arguments(
   int argc,
   char *argv[], 
   bool command_was_invoked, 
-  int dash_dash_position
+  int dash_dash_position,
+  const char **main_args,
+  int number_of_main_args
 ) {
+  // arguments of the command itself come first
+  for (int i = 0; i < number_of_main_args; ++i) {
+    wasi_config_arg(wasi_config, main_args[i]);
+  }
+
   for (int i = 1; i < argc; ++i) {
     // We probably want special args like `--dir` and `--mapdir` to not be
     // passed directly
@@ -110,6 +136,9 @@ int main(int argc, char *argv[]) {
   wasm_module_t *module = NULL;
 
   const char* selected_atom = "main";
+  const char* selected_command = NULL;
+  const char** main_args = NULL;
+  int number_of_main_args = 0;
   bool command_was_invoked = false;
   int dash_dash_position = argc + 1;
   int number_of_commands = 1;
@@ -129,7 +158,7 @@ int main(int argc, char *argv[]) {
       if ((strcmp(argv[i], "--command") == 0 || strcmp(argv[i], "-c") == 0) && dash_dash_position > i) {
         // next arg is a command
         if ((i + 1) < argc) {
-          selected_atom = argv[i + 1];
+          selected_command = argv[i + 1];
           command_was_invoked = true;
           break;
         } else {
@@ -138,6 +167,11 @@ int main(int argc, char *argv[]) {
         } 
       }
     }
+
+    // otherwise, run the command the executable is named after
+    if (!command_was_invoked) {
+      selected_command = program_name(argv[0]);
+    }
   }
 
   // INSTANTIATE_MODULES
@@ -147,7 +181,7 @@ int main(int argc, char *argv[]) {
 
 #ifdef WASI_PIRITA
   wasi_config_t *wasi_config = wasi_config_new(argv[0]);
-  handle_arguments(wasi_config, argc, argv, command_was_invoked,dash_dash_position);
+  handle_arguments(wasi_config, argc, argv, command_was_invoked, dash_dash_position, main_args, number_of_main_args);
 
   wasm_byte_vec_t volume_bytes = {
     .size = VOLUMES_LENGTH,
@@ -175,7 +209,7 @@ int main(int argc, char *argv[]) {
   }
 #else
   wasi_config_t *wasi_config = wasi_config_new(argv[0]);
-  handle_arguments(wasi_config, argc, argv, command_was_invoked, dash_dash_position);
+  handle_arguments(wasi_config, argc, argv, command_was_invoked, dash_dash_position, main_args, number_of_main_args);
 
   wasi_env_t *wasi_env = wasi_env_new(store, wasi_config);
   if (!wasi_env) {
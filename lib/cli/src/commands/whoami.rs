@@ -7,11 +7,15 @@ pub struct Whoami {
     /// Which registry to check the logged in username for
     #[clap(long, name = "registry")]
     pub registry: Option<String>,
+    /// Which profile to check the logged in username for
+    #[clap(long, env = "WASMER_PROFILE")]
+    pub profile: Option<String>,
 }
 
 impl Whoami {
     /// Execute `wasmer whoami`
     pub fn execute(&self) -> Result<(), anyhow::Error> {
+        crate::utils::use_registry_profile(self.profile.as_deref())?;
         let wasmer_dir =
             WasmerConfig::get_wasmer_dir().map_err(|e| anyhow::anyhow!("no wasmer dir: {e}"))?;
         let (registry, username) =
@@ -16,6 +16,7 @@
 #[macro_use]
 extern crate anyhow;
 
+pub mod cache_stats;
 pub mod commands;
 pub mod common;
 #[macro_use]
@@ -23,6 +24,7 @@ pub mod error;
 pub mod c_gen;
 pub mod cli;
 pub mod logging;
+pub mod oci;
 pub mod package_source;
 pub mod store;
 pub mod suggestions;
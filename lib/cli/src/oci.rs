@@ -0,0 +1,645 @@
+//! Pulling packages from OCI registries, e.g. `oci://ghcr.io/org/tool:1.2`.
+//!
+//! A package is pushed to a registry as an OCI artifact with a single
+//! `.webc` layer. Layers are cached by digest, and registries are
+//! authenticated with the same credentials as `docker`: the `auths`,
+//! `credsStore` and `credHelpers` of `~/.docker/config.json`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Error, Result};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{ACCEPT, WWW_AUTHENTICATE};
+use reqwest::StatusCode;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// The media type of a layer containing a `.webc` file.
+pub const WEBC_MEDIA_TYPE: &str = "application/vnd.wasmer.webc";
+
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.oci.image.manifest.v1+json, \
+     application/vnd.oci.image.index.v1+json, \
+     application/vnd.docker.distribution.manifest.v2+json, \
+     application/vnd.docker.distribution.manifest.list.v2+json";
+
+const DOCKER_HUB: &str = "docker.io";
+const DOCKER_HUB_API: &str = "registry-1.docker.io";
+const DOCKER_HUB_CREDENTIALS: &str = "https://index.docker.io/v1/";
+
+/// A reference to a package in an OCI registry.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OciReference {
+    /// The registry's host, e.g. `ghcr.io` or `localhost:5000`.
+    pub registry: String,
+    /// The repository in the registry, e.g. `org/tool`.
+    pub repository: String,
+    /// A tag (e.g. `1.2`) or a digest (e.g. `sha256:...`).
+    pub reference: String,
+}
+
+impl OciReference {
+    /// Whether the reference is a digest, which always points to the same
+    /// content.
+    pub fn is_digest(&self) -> bool {
+        self.reference.contains(':')
+    }
+
+    fn api_url(&self, path: &str) -> String {
+        let host = if self.registry == DOCKER_HUB {
+            DOCKER_HUB_API
+        } else {
+            &self.registry
+        };
+        let scheme = if host.starts_with("localhost") || host.starts_with("127.0.0.1") {
+            "http"
+        } else {
+            "https"
+        };
+        format!("{scheme}://{host}/v2/{}/{path}", self.repository)
+    }
+
+    /// A file name identifying this reference in the cache.
+    fn cache_key(&self) -> String {
+        self.to_string()
+            .trim_start_matches("oci://")
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+}
+
+impl FromStr for OciReference {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let rest = s
+            .strip_prefix("oci://")
+            .ok_or_else(|| anyhow!("OCI references start with `oci://`, found `{s}`"))?;
+
+        // Like docker, the first component is the registry only if it looks
+        // like a host name.
+        let (registry, rest) = match rest.split_once('/') {
+            Some((host, rest))
+                if host.contains('.') || host.contains(':') || host == "localhost" =>
+            {
+                (host.to_string(), rest)
+            }
+            _ => (DOCKER_HUB.to_string(), rest),
+        };
+
+        let (repository, reference) = match rest.split_once('@') {
+            Some((repository, digest)) => (repository, digest.to_string()),
+            None => match rest.rsplit_once(':') {
+                Some((repository, tag)) if !tag.contains('/') => (repository, tag.to_string()),
+                _ => (rest, "latest".to_string()),
+            },
+        };
+
+        if repository.is_empty()
+            || !repository
+                .chars()
+                .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '.' | '_' | '-' | '/'))
+        {
+            bail!("`{repository}` is not a valid repository name");
+        }
+        if reference.is_empty() {
+            bail!("the tag or digest is missing in `{s}`");
+        }
+
+        let repository = if registry == DOCKER_HUB && !repository.contains('/') {
+            format!("library/{repository}")
+        } else {
+            repository.to_string()
+        };
+
+        Ok(OciReference {
+            registry,
+            repository,
+            reference,
+        })
+    }
+}
+
+impl fmt::Display for OciReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.is_digest() { '@' } else { ':' };
+        write!(
+            f,
+            "oci://{}/{}{separator}{}",
+            self.registry, self.repository, self.reference
+        )
+    }
+}
+
+/// Pulls the `.webc` layer of a package, returning its path in `cache_dir`.
+///
+/// Layers are cached by digest, so pulling a digest that was already pulled
+/// doesn't touch the network. If the registry can't be reached, the last
+/// layer pulled for a tag is used.
+pub fn pull(reference: &OciReference, cache_dir: &Path) -> Result<PathBuf> {
+    let ref_path = cache_dir.join("refs").join(reference.cache_key());
+    let cached = || -> Option<PathBuf> {
+        let digest = std::fs::read_to_string(&ref_path).ok()?;
+        let path = blob_path(cache_dir, digest.trim());
+        path.is_file().then_some(path)
+    };
+
+    if reference.is_digest() {
+        if let Some(path) = cached() {
+            return Ok(path);
+        }
+    }
+
+    let digest = match fetch(reference, cache_dir) {
+        Ok(digest) => digest,
+        Err(e) => match cached() {
+            Some(path) => {
+                tracing::warn!(
+                    error = &*e as &dyn std::error::Error,
+                    "Unable to pull {reference}, falling back to the cached version",
+                );
+                return Ok(path);
+            }
+            None => return Err(e),
+        },
+    };
+
+    if let Some(parent) = ref_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Unable to create \"{}\"", parent.display()))?;
+    }
+    std::fs::write(&ref_path, &digest)
+        .with_context(|| format!("Unable to write to \"{}\"", ref_path.display()))?;
+
+    Ok(blob_path(cache_dir, &digest))
+}
+
+fn blob_path(cache_dir: &Path, digest: &str) -> PathBuf {
+    cache_dir
+        .join("blobs")
+        .join(format!("{}.webc", digest.replace(':', "-")))
+}
+
+/// Downloads the `.webc` layer to the cache (unless it is already there),
+/// returning its digest.
+fn fetch(reference: &OciReference, cache_dir: &Path) -> Result<String> {
+    let mut registry = Registry::new(reference);
+
+    let mut manifest = registry.manifest(&reference.reference)?;
+    if let Some(digest) = select_manifest(&manifest) {
+        manifest = registry.manifest(&digest)?;
+    }
+    let layer = select_layer(&manifest)?;
+
+    let path = blob_path(cache_dir, &layer.digest);
+    if path.is_file() {
+        return Ok(layer.digest);
+    }
+
+    tracing::debug!(digest = %layer.digest, size = layer.size, "Downloading the layer");
+    let bytes = registry
+        .get(
+            &reference.api_url(&format!("blobs/{}", layer.digest)),
+            "*/*",
+        )?
+        .bytes()
+        .with_context(|| format!("Unable to download {}", layer.digest))?;
+
+    let actual = format!("sha256:{}", hex::encode(Sha256::digest(&bytes)));
+    if actual != layer.digest {
+        bail!(
+            "The layer of {reference} is corrupted: expected {}, got {actual}",
+            layer.digest
+        );
+    }
+
+    let dir = path.parent().unwrap_or(cache_dir);
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Unable to create \"{}\"", dir.display()))?;
+    let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+    temp.write_all(&bytes)?;
+    temp.persist(&path)
+        .with_context(|| format!("Unable to save \"{}\"", path.display()))?;
+
+    Ok(layer.digest)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Descriptor {
+    media_type: String,
+    digest: String,
+    size: u64,
+}
+
+impl Descriptor {
+    fn from_json(value: &Value) -> Option<Self> {
+        Some(Descriptor {
+            media_type: value["mediaType"].as_str().unwrap_or_default().to_string(),
+            digest: value["digest"].as_str()?.to_string(),
+            size: value["size"].as_u64().unwrap_or_default(),
+        })
+    }
+}
+
+/// When `manifest` is an index, the digest of the manifest to use.
+fn select_manifest(manifest: &Value) -> Option<String> {
+    let manifests = manifest["manifests"].as_array()?;
+    let is_wasm = |m: &&Value| {
+        m["artifactType"] == WEBC_MEDIA_TYPE
+            || matches!(
+                m["platform"]["architecture"].as_str(),
+                Some("wasm" | "wasm32")
+            )
+    };
+    manifests
+        .iter()
+        .find(is_wasm)
+        .or_else(|| manifests.first())
+        .and_then(|m| m["digest"].as_str())
+        .map(String::from)
+}
+
+/// The layer containing the package: the one with the WEBC media type, or
+/// the only layer.
+fn select_layer(manifest: &Value) -> Result<Descriptor> {
+    let layers: Vec<_> = manifest["layers"]
+        .as_array()
+        .map(|layers| layers.iter().filter_map(Descriptor::from_json).collect())
+        .unwrap_or_default();
+
+    if let Some(layer) = layers.iter().find(|l| l.media_type == WEBC_MEDIA_TYPE) {
+        return Ok(layer.clone());
+    }
+    match layers.as_slice() {
+        [layer] => Ok(layer.clone()),
+        [] => bail!("The manifest has no layers"),
+        _ => bail!("The manifest has no layer with the `{WEBC_MEDIA_TYPE}` media type"),
+    }
+}
+
+#[derive(Debug)]
+enum Auth {
+    Basic(String, String),
+    Bearer(String),
+}
+
+/// A client for the registry of a reference, which authenticates when the
+/// registry asks for it.
+struct Registry<'a> {
+    client: Client,
+    reference: &'a OciReference,
+    auth: Option<Auth>,
+}
+
+impl<'a> Registry<'a> {
+    fn new(reference: &'a OciReference) -> Self {
+        Registry {
+            client: Client::new(),
+            reference,
+            auth: None,
+        }
+    }
+
+    fn manifest(&mut self, reference: &str) -> Result<Value> {
+        let url = self.reference.api_url(&format!("manifests/{reference}"));
+        self.get(&url, MANIFEST_MEDIA_TYPES)?
+            .json()
+            .with_context(|| format!("Invalid manifest for {}", self.reference))
+    }
+
+    fn get(&mut self, url: &str, accept: &str) -> Result<Response> {
+        let mut response = self.request(url, accept).send()?;
+
+        if response.status() == StatusCode::UNAUTHORIZED && self.auth.is_none() {
+            let challenge = response
+                .headers()
+                .get(WWW_AUTHENTICATE)
+                .and_then(|h| h.to_str().ok())
+                .unwrap_or("Basic")
+                .to_string();
+            self.auth = Some(self.authenticate(&challenge)?);
+            response = self.request(url, accept).send()?;
+        }
+
+        response
+            .error_for_status()
+            .with_context(|| format!("Unable to pull {}", self.reference))
+    }
+
+    fn request(&self, url: &str, accept: &str) -> RequestBuilder {
+        let request = self.client.get(url).header(ACCEPT, accept);
+        match &self.auth {
+            Some(Auth::Basic(user, password)) => request.basic_auth(user, Some(password)),
+            Some(Auth::Bearer(token)) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    fn authenticate(&self, challenge: &str) -> Result<Auth> {
+        let credentials = docker_credentials(&self.reference.registry);
+        let (scheme, params) = parse_challenge(challenge);
+
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            let (user, password) = credentials.ok_or_else(|| {
+                anyhow!(
+                    "{} requires credentials, log in with `docker login {}`",
+                    self.reference.registry,
+                    self.reference.registry
+                )
+            })?;
+            return Ok(Auth::Basic(user, password));
+        }
+
+        let realm = params
+            .get("realm")
+            .ok_or_else(|| anyhow!("The registry's authentication challenge has no realm"))?;
+        let scope = params
+            .get("scope")
+            .cloned()
+            .unwrap_or_else(|| format!("repository:{}:pull", self.reference.repository));
+        let mut query = vec![("scope", scope)];
+        if let Some(service) = params.get("service") {
+            query.push(("service", service.clone()));
+        }
+
+        let mut request = self.client.get(realm.as_str()).query(&query);
+        if let Some((user, password)) = &credentials {
+            request = request.basic_auth(user, Some(password));
+        }
+        let token: Value = request
+            .send()?
+            .error_for_status()
+            .with_context(|| format!("Unable to authenticate with {}", self.reference.registry))?
+            .json()?;
+
+        token["token"]
+            .as_str()
+            .or_else(|| token["access_token"].as_str())
+            .map(|token| Auth::Bearer(token.to_string()))
+            .ok_or_else(|| anyhow!("The registry didn't return a token"))
+    }
+}
+
+/// Parses a `WWW-Authenticate` header, e.g.
+/// `Bearer realm="https://ghcr.io/token",service="ghcr.io"`.
+fn parse_challenge(header: &str) -> (String, HashMap<String, String>) {
+    let header = header.trim();
+    let (scheme, rest) = header.split_once(' ').unwrap_or((header, ""));
+
+    let mut params = HashMap::new();
+    let mut rest = rest.trim();
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let value = value.trim_start();
+        let (value, remainder) = match value.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            },
+            None => match value.find(',') {
+                Some(end) => (&value[..end], &value[end..]),
+                None => (value, ""),
+            },
+        };
+        params.insert(key, value.to_string());
+        rest = remainder.trim_start_matches(',').trim();
+    }
+
+    (scheme.to_string(), params)
+}
+
+/// The credentials `docker` would use for a registry.
+fn docker_credentials(registry: &str) -> Option<(String, String)> {
+    let dir = match std::env::var_os("DOCKER_CONFIG") {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::home_dir()?.join(".docker"),
+    };
+    let config = std::fs::read_to_string(dir.join("config.json")).ok()?;
+    let config: Value = serde_json::from_str(&config).ok()?;
+
+    credentials_from_config(&config, registry, run_credential_helper)
+}
+
+fn credentials_from_config(
+    config: &Value,
+    registry: &str,
+    helper: impl Fn(&str, &str) -> Option<(String, String)>,
+) -> Option<(String, String)> {
+    let server = if registry == DOCKER_HUB {
+        DOCKER_HUB_CREDENTIALS
+    } else {
+        registry
+    };
+
+    if let Some(name) = config["credHelpers"][server].as_str() {
+        return helper(name, server);
+    }
+
+    let auths = config["auths"].as_object();
+    let entry = auths.and_then(|auths| {
+        auths
+            .iter()
+            .find(|(key, _)| key.as_str() == server || normalize_host(key) == registry)
+            .map(|(_, entry)| entry)
+    });
+    if let Some(entry) = entry {
+        if let (Some(user), Some(password)) =
+            (entry["username"].as_str(), entry["password"].as_str())
+        {
+            return Some((user.to_string(), password.to_string()));
+        }
+        if let Some(auth) = entry["auth"].as_str() {
+            use base64::Engine;
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(auth)
+                .ok()?;
+            let decoded = String::from_utf8(decoded).ok()?;
+            let (user, password) = decoded.split_once(':')?;
+            return Some((user.to_string(), password.to_string()));
+        }
+    }
+
+    config["credsStore"]
+        .as_str()
+        .and_then(|name| helper(name, server))
+}
+
+/// Strips the scheme and path from a key of `auths`, e.g.
+/// `https://index.docker.io/v1/` becomes `index.docker.io`.
+fn normalize_host(key: &str) -> &str {
+    let key = key.split_once("://").map_or(key, |(_, rest)| rest);
+    key.split('/').next().unwrap_or(key)
+}
+
+/// Asks a docker credential helper (`docker-credential-<name>`) for the
+/// credentials of a server.
+fn run_credential_helper(name: &str, server: &str) -> Option<(String, String)> {
+    let mut child = Command::new(format!("docker-credential-{name}"))
+        .arg("get")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    child.stdin.take()?.write_all(server.as_bytes()).ok()?;
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let credentials: Value = serde_json::from_slice(&output.stdout).ok()?;
+    Some((
+        credentials["Username"].as_str()?.to_string(),
+        credentials["Secret"].as_str()?.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_references() {
+        let inputs = [
+            ("oci://ghcr.io/org/tool:1.2", "ghcr.io", "org/tool", "1.2"),
+            ("oci://ghcr.io/org/tool", "ghcr.io", "org/tool", "latest"),
+            (
+                "oci://localhost:5000/tool@sha256:abcd",
+                "localhost:5000",
+                "tool",
+                "sha256:abcd",
+            ),
+            ("oci://python:3.11", "docker.io", "library/python", "3.11"),
+            ("oci://org/tool", "docker.io", "org/tool", "latest"),
+        ];
+
+        for (src, registry, repository, reference) in inputs {
+            let parsed: OciReference = src.parse().unwrap();
+            assert_eq!(
+                parsed,
+                OciReference {
+                    registry: registry.to_string(),
+                    repository: repository.to_string(),
+                    reference: reference.to_string(),
+                },
+                "{src}"
+            );
+        }
+
+        let digest: OciReference = "oci://ghcr.io/org/tool@sha256:abcd".parse().unwrap();
+        assert!(digest.is_digest());
+        assert_eq!(digest.to_string(), "oci://ghcr.io/org/tool@sha256:abcd");
+        assert_eq!(
+            digest.api_url("manifests/sha256:abcd"),
+            "https://ghcr.io/v2/org/tool/manifests/sha256:abcd"
+        );
+
+        assert!("ghcr.io/org/tool".parse::<OciReference>().is_err());
+        assert!("oci://ghcr.io/Org/Tool".parse::<OciReference>().is_err());
+        assert!("oci://ghcr.io/org/tool:".parse::<OciReference>().is_err());
+    }
+
+    #[test]
+    fn authentication_challenges() {
+        let (scheme, params) = parse_challenge(
+            r#"Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="repository:org/tool:pull""#,
+        );
+
+        assert_eq!(scheme, "Bearer");
+        assert_eq!(params["realm"], "https://ghcr.io/token");
+        assert_eq!(params["service"], "ghcr.io");
+        assert_eq!(params["scope"], "repository:org/tool:pull");
+
+        let (scheme, params) = parse_challenge("Basic realm=registry");
+        assert_eq!(scheme, "Basic");
+        assert_eq!(params["realm"], "registry");
+    }
+
+    #[test]
+    fn credentials_from_the_docker_config() {
+        let config = json!({
+            "auths": {
+                "https://index.docker.io/v1/": { "auth": "dXNlcjpodW50ZXIy" },
+                "quay.io": { "username": "quay", "password": "secret" },
+            },
+            "credHelpers": { "ghcr.io": "gh" },
+            "credsStore": "desktop",
+        });
+        let helper = |name: &str, server: &str| Some((name.to_string(), server.to_string()));
+
+        assert_eq!(
+            credentials_from_config(&config, "docker.io", helper),
+            Some(("user".to_string(), "hunter2".to_string()))
+        );
+        assert_eq!(
+            credentials_from_config(&config, "quay.io", helper),
+            Some(("quay".to_string(), "secret".to_string()))
+        );
+        assert_eq!(
+            credentials_from_config(&config, "ghcr.io", helper),
+            Some(("gh".to_string(), "ghcr.io".to_string()))
+        );
+        assert_eq!(
+            credentials_from_config(&config, "example.com", helper),
+            Some(("desktop".to_string(), "example.com".to_string()))
+        );
+        assert_eq!(credentials_from_config(&json!({}), "ghcr.io", helper), None);
+    }
+
+    #[test]
+    fn pick_the_webc_layer() {
+        let index = json!({
+            "manifests": [
+                { "digest": "sha256:linux", "platform": { "architecture": "amd64", "os": "linux" } },
+                { "digest": "sha256:wasm", "platform": { "architecture": "wasm", "os": "wasi" } },
+            ]
+        });
+        assert_eq!(select_manifest(&index).as_deref(), Some("sha256:wasm"));
+
+        let manifest = json!({
+            "layers": [
+                { "mediaType": "application/vnd.oci.image.config.v1+json", "digest": "sha256:config", "size": 2 },
+                { "mediaType": WEBC_MEDIA_TYPE, "digest": "sha256:webc", "size": 42 },
+            ]
+        });
+        assert_eq!(select_manifest(&manifest), None);
+        assert_eq!(
+            select_layer(&manifest).unwrap(),
+            Descriptor {
+                media_type: WEBC_MEDIA_TYPE.to_string(),
+                digest: "sha256:webc".to_string(),
+                size: 42,
+            }
+        );
+
+        let single = json!({ "layers": [{ "mediaType": "application/octet-stream", "digest": "sha256:only" }] });
+        assert_eq!(select_layer(&single).unwrap().digest, "sha256:only");
+        assert!(select_layer(&json!({ "layers": [] })).is_err());
+    }
+
+    #[test]
+    fn pulled_digests_are_served_from_the_cache() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let reference: OciReference = "oci://ghcr.io/org/tool@sha256:abcd".parse().unwrap();
+        let blob = blob_path(temp.path(), "sha256:1234");
+        std::fs::create_dir_all(blob.parent().unwrap()).unwrap();
+        std::fs::write(&blob, b"\0webc").unwrap();
+        let ref_path = temp.path().join("refs").join(reference.cache_key());
+        std::fs::create_dir_all(ref_path.parent().unwrap()).unwrap();
+        std::fs::write(&ref_path, "sha256:1234").unwrap();
+
+        assert_eq!(pull(&reference, temp.path()).unwrap(), blob);
+    }
+}
@@ -6,6 +6,8 @@ use std::str::FromStr;
 use url::Url;
 use wasmer_registry::WasmerConfig;
 
+use crate::oci::OciReference;
+
 /// Source of a package
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum PackageSource {
@@ -15,6 +17,8 @@ pub enum PackageSource {
     File(String),
     /// Download from a package
     Package(wasmer_registry::Package),
+    /// Pull from an OCI registry
+    Oci(OciReference),
 }
 
 impl Default for PackageSource {
@@ -34,6 +38,10 @@ impl FromStr for PackageSource {
 impl PackageSource {
     /// Parses a package source and transforms it to a URL or a File
     pub fn parse(s: &str) -> Result<Self, String> {
+        if s.starts_with("oci://") {
+            return s.parse().map(Self::Oci).map_err(|e| format!("{e:#}"));
+        }
+
         // If the file is a http:// URL, run the URL
         if let Ok(url) = url::Url::parse(s) {
             if url.scheme() == "http" || url.scheme() == "https" {
@@ -59,6 +67,18 @@ impl PackageSource {
                     Err(anyhow::anyhow!("Could not find local file {f}"))
                 };
             }
+            Self::Oci(reference) => {
+                let wasmer_dir = WasmerConfig::get_wasmer_dir()
+                    .map_err(|e| anyhow::anyhow!("no wasmer dir: {e}"))?;
+                let mut sp = start_spinner(format!("Pulling {reference} ..."));
+                let path = crate::oci::pull(reference, &wasmer_dir.join("cache").join("oci"));
+                if let Some(sp) = sp.take() {
+                    use std::io::Write;
+                    sp.clear();
+                    let _ = std::io::stdout().flush();
+                }
+                return path.with_context(|| format!("Could not pull {reference}"));
+            }
             Self::Url(u) => {
                 let wasmer_dir = WasmerConfig::get_wasmer_dir()
                     .map_err(|e| anyhow::anyhow!("no wasmer dir: {e}"))?;
@@ -193,6 +213,17 @@ fn test_package_source() {
         PackageSource::File("command".to_string()),
     );
 
+    assert_eq!(
+        PackageSource::parse("oci://ghcr.io/org/tool:1.2").unwrap(),
+        PackageSource::Oci(OciReference {
+            registry: "ghcr.io".to_string(),
+            repository: "org/tool".to_string(),
+            reference: "1.2".to_string(),
+        }),
+    );
+
+    assert!(PackageSource::parse("oci://ghcr.io/Org").is_err());
+
     assert_eq!(
         PackageSource::parse("python@latest").unwrap(),
         PackageSource::File("python@latest".to_string()),
@@ -46,15 +46,76 @@ pub struct CompilerOptions {
     #[cfg(any(feature = "singlepass", feature = "cranelift", feature = "llvm"))]
     enable_verifier: bool,
 
+    /// Optimize the generated code for size rather than speed.
+    #[clap(long)]
+    #[cfg(any(feature = "cranelift", feature = "llvm"))]
+    optimize_size: bool,
+
+    /// Canonicalize the NaNs produced by float operations, so that the
+    /// module gives the same results on every platform.
+    #[clap(long)]
+    canonicalize_nans: bool,
+
+    /// Report the compiled functions to a native profiler, so that its
+    /// profiles show the names of the Wasm functions.
+    #[clap(long, value_enum)]
+    profiler: Option<Profiler>,
+
+    /// Translate the DWARF sections of the module and register them with
+    /// the GDB JIT interface, so that GDB and LLDB can step through the
+    /// source of the module.
+    #[clap(long)]
+    debug_info: bool,
+
+    /// How out-of-bounds memory accesses are caught: with guard pages
+    /// where possible, with explicit checks, or with explicit checks and
+    /// no virtual memory reserved beyond the size of the memories.
+    #[clap(long, value_enum)]
+    bounds_checks: Option<BoundsChecks>,
+
     /// LLVM debug directory, where IR and object files will be written to.
     #[cfg(feature = "llvm")]
     #[clap(long)]
     llvm_debug_dir: Option<PathBuf>,
 
+    /// The CPU the LLVM compiler generates code for, such as `skylake`,
+    /// instead of a generic CPU.
+    #[cfg(feature = "llvm")]
+    #[clap(long)]
+    llvm_cpu: Option<String>,
+
+    /// The CPU features the LLVM compiler can use, as an LLVM feature
+    /// string such as `+avx2,+fma`.
+    #[cfg(feature = "llvm")]
+    #[clap(long)]
+    llvm_features: Option<String>,
+
     #[clap(flatten)]
     features: WasmFeatures,
 }
 
+/// The native profilers the compiled functions can be reported to
+#[cfg(feature = "compiler")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Profiler {
+    /// Write a perf map to `/tmp/perf-<pid>.map`
+    Perfmap,
+    /// Write a jitdump file to `jit-<pid>.dump`, for `perf inject --jit`
+    Jitdump,
+}
+
+/// The bounds-check strategies of the memories
+#[cfg(feature = "compiler")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BoundsChecks {
+    /// Guard pages where possible
+    GuardPages,
+    /// Explicit checks
+    Explicit,
+    /// Explicit checks, without reserving virtual memory
+    ExplicitNoReservation,
+}
+
 #[cfg(feature = "compiler")]
 impl CompilerOptions {
     fn get_compiler(&self) -> Result<CompilerType> {
@@ -127,18 +188,49 @@ impl CompilerOptions {
         compiler_config: Box<dyn CompilerConfig>,
     ) -> Result<Engine> {
         let features = self.get_features(compiler_config.default_features_for_target(&target))?;
-        let engine: Engine = wasmer_compiler::EngineBuilder::new(compiler_config)
+        let mut builder = wasmer_compiler::EngineBuilder::new(compiler_config)
             .set_features(Some(features))
-            .set_target(Some(target))
-            .engine();
+            .set_target(Some(target));
+        if self.canonicalize_nans {
+            builder = builder.canonicalize_nans(true);
+        }
+        let mut engine: Engine = builder.engine();
+        if let Some(profiler) = self.profiler {
+            engine.set_profiling_strategy(match profiler {
+                Profiler::Perfmap => ProfilingStrategy::PerfMap,
+                Profiler::Jitdump => ProfilingStrategy::JitDump,
+            });
+        }
+        if self.debug_info {
+            engine.set_debug_info(true);
+        }
+        if let Some(bounds_checks) = self.bounds_checks {
+            let tunables =
+                BaseTunables::for_target(engine.target()).with_bounds_checks(match bounds_checks {
+                    BoundsChecks::GuardPages => BoundsCheckStrategy::GuardPages,
+                    BoundsChecks::Explicit => BoundsCheckStrategy::Explicit,
+                    BoundsChecks::ExplicitNoReservation => {
+                        BoundsCheckStrategy::ExplicitNoReservation
+                    }
+                });
+            engine.set_tunables(tunables);
+        }
 
         Ok(engine)
     }
 
     /// Get the Compiler Config for the current options
-    #[allow(unused_variables)]
     pub(crate) fn get_compiler_config(&self) -> Result<(Box<dyn CompilerConfig>, CompilerType)> {
         let compiler = self.get_compiler()?;
+        self.get_compiler_config_for(compiler)
+    }
+
+    /// Get the Compiler Config for the current options, using the given compiler
+    #[allow(unused_variables)]
+    pub(crate) fn get_compiler_config_for(
+        &self,
+        compiler: CompilerType,
+    ) -> Result<(Box<dyn CompilerConfig>, CompilerType)> {
         let compiler_config: Box<dyn CompilerConfig> = match compiler {
             CompilerType::Headless => bail!("The headless engine can't be chosen"),
             #[cfg(feature = "singlepass")]
@@ -155,6 +247,9 @@ impl CompilerOptions {
                 if self.enable_verifier {
                     config.enable_verifier();
                 }
+                if self.optimize_size {
+                    config.optimize_for_size(true);
+                }
                 Box::new(config)
             }
             #[cfg(feature = "llvm")]
@@ -258,6 +353,12 @@ impl CompilerOptions {
                 if self.enable_verifier {
                     config.enable_verifier();
                 }
+                if self.optimize_size {
+                    config.optimize_for_size(true);
+                }
+                config
+                    .target_cpu(self.llvm_cpu.clone())
+                    .target_features(self.llvm_features.clone());
                 Box::new(config)
             }
             #[cfg(not(all(feature = "singlepass", feature = "cranelift", feature = "llvm",)))]
@@ -275,7 +376,7 @@ impl CompilerOptions {
 }
 
 /// The compiler used for the store
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompilerType {
     /// Singlepass compiler
     Singlepass,
@@ -328,6 +429,28 @@ impl StoreOptions {
         Ok((store, compiler_type))
     }
 
+    /// Gets the store for the host target, with the given middlewares
+    /// applied to every module compiled with it.
+    pub fn get_store_with_middlewares(
+        &self,
+        middlewares: Vec<Arc<dyn ModuleMiddleware>>,
+    ) -> Result<(Store, CompilerType)> {
+        let (mut compiler_config, compiler_type) = self.compiler.get_compiler_config()?;
+        for middleware in middlewares {
+            compiler_config.push_middleware(middleware);
+        }
+        let engine = self.get_engine_with_compiler(Target::default(), compiler_config)?;
+        Ok((Store::new(engine), compiler_type))
+    }
+
+    /// Gets the store for the host target, using the given compiler rather
+    /// than the selected one.
+    pub fn get_store_for_compiler(&self, compiler: CompilerType) -> Result<Store> {
+        let (compiler_config, _) = self.compiler.get_compiler_config_for(compiler)?;
+        let engine = self.get_engine_with_compiler(Target::default(), compiler_config)?;
+        Ok(Store::new(engine))
+    }
+
     #[cfg(feature = "compiler")]
     fn get_engine_with_compiler(
         &self,
@@ -1,8 +1,10 @@
 //! Utility functions for the WebAssembly module
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use std::env;
-use std::path::PathBuf;
-use wasmer_wasix::runners::MappedDirectory;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use virtual_net::{egress::EgressRule, proxy::ProxyConfig, IpCidr};
+use wasmer_wasix::runners::{MappedDirectory, MountOptions};
 
 /// Whether or not Wasmer should print with color
 pub fn wasmer_should_print_color() -> bool {
@@ -21,27 +23,102 @@ fn retrieve_alias_pathbuf(alias: &str, real_dir: &str) -> Result<MappedDirectory
     } else {
         bail!("Directory \"{}\" does not exist", &real_dir);
     }
-    Ok(MappedDirectory {
-        guest: alias.to_string(),
-        host: pb,
-    })
+    Ok(MappedDirectory::new(pb, alias))
+}
+
+/// Parses the comma-separated flags at the end of a mapdir (e.g. `ro` in
+/// `/data:/host/data:ro`), returning `None` if `flags` isn't a list of flags.
+fn parse_mount_flags(flags: &str) -> Option<MountOptions> {
+    let mut options = MountOptions::default();
+
+    for flag in flags.split(',') {
+        match flag {
+            "ro" => options.read_only = true,
+            "rw" => options.read_only = false,
+            "no-create" => options.no_create = true,
+            _ => return None,
+        }
+    }
+
+    Some(options)
 }
 
 /// Parses a mapdir from a string
+///
+/// The mapping may be followed by comma-separated flags, e.g.
+/// `/data:/host/data:ro` or `/data::/host/data:no-create`.
 pub fn parse_mapdir(entry: &str) -> Result<MappedDirectory> {
+    let (mapping, options) = match entry.rsplit_once(':') {
+        Some((mapping, flags)) if mapping.contains(':') => match parse_mount_flags(flags) {
+            Some(options) => (mapping, options),
+            None => (entry, MountOptions::default()),
+        },
+        _ => (entry, MountOptions::default()),
+    };
+
     // We try first splitting by `::`
-    if let [alias, real_dir] = entry.split("::").collect::<Vec<&str>>()[..] {
-        retrieve_alias_pathbuf(alias, real_dir)
+    let dir = if let [alias, real_dir] = mapping.split("::").collect::<Vec<&str>>()[..] {
+        retrieve_alias_pathbuf(alias, real_dir)?
     }
     // And then we try splitting by `:` (for compatibility with previous API)
-    else if let [alias, real_dir] = entry.splitn(2, ':').collect::<Vec<&str>>()[..] {
-        retrieve_alias_pathbuf(alias, real_dir)
+    else if let [alias, real_dir] = mapping.splitn(2, ':').collect::<Vec<&str>>()[..] {
+        retrieve_alias_pathbuf(alias, real_dir)?
     } else {
         bail!(
             "Directory mappings must consist of two paths separate by a `::` or `:`. Found {}",
             &entry
         )
+    };
+
+    Ok(dir.with_options(options))
+}
+
+/// Reads a WebAssembly module from a file. If the file is a package, the
+/// module is the atom called `atom`, or the only atom of the package.
+pub fn read_module_bytes(path: &Path, atom: Option<&str>) -> Result<Vec<u8>> {
+    let contents = std::fs::read(path)?;
+    if !contents.starts_with(b"\0webc") {
+        return Ok(contents);
     }
+
+    let container = webc::Container::from_bytes(contents)?;
+    let mut atoms = container.atoms();
+    let name = match atom {
+        Some(name) => name.to_string(),
+        None if atoms.len() == 1 => atoms.keys().next().cloned().unwrap_or_default(),
+        None => bail!(
+            "the package contains several atoms ({}), pick one with --atom",
+            atoms.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+    };
+    let atom = atoms
+        .remove(&name)
+        .ok_or_else(|| anyhow!("the package has no atom named `{name}`"))?;
+    Ok(atom.to_vec())
+}
+
+/// Selects a registry profile for every registry access of the current
+/// process, like setting `WASMER_PROFILE`.
+pub fn use_registry_profile(profile: Option<&str>) -> Result<()> {
+    let profile = match profile {
+        Some(profile) => profile,
+        None => return Ok(()),
+    };
+
+    let wasmer_dir = wasmer_registry::WasmerConfig::get_wasmer_dir().map_err(|e| anyhow!("{e}"))?;
+    let config =
+        wasmer_registry::WasmerConfig::from_file(&wasmer_dir).map_err(|e| anyhow!("{e}"))?;
+    if !config.registry.profiles.contains_key(profile) {
+        bail!(
+            "there is no profile named {profile:?}, create it with `wasmer login --profile {profile}`"
+        );
+    }
+
+    env::set_var(
+        wasmer_registry::WasmerConfig::ENV_VAR_WASMER_PROFILE,
+        profile,
+    );
+    Ok(())
 }
 
 /// Parses an environment variable.
@@ -68,9 +145,247 @@ pub fn parse_envvar(entry: &str) -> Result<(String, String)> {
     }
 }
 
+/// Parses an `--allow-host` entry: a host name (a leading `*.` matches any
+/// subdomain), an IP address or a CIDR range, optionally followed by a port.
+/// IPv6 addresses need brackets when a port is given, e.g. `[::1]:443`.
+pub fn parse_allow_host(entry: &str) -> Result<EgressRule> {
+    let entry = entry.trim();
+    let (host, port) = if let Some(rest) = entry.strip_prefix('[') {
+        let (host, rest) = rest
+            .split_once(']')
+            .ok_or_else(|| anyhow!("Missing `]` in `{entry}`"))?;
+        match rest {
+            "" => (host, None),
+            _ => match rest.strip_prefix(':') {
+                Some(port) => (host, Some(port)),
+                None => bail!("Expected `:<port>` after `]` in `{entry}`"),
+            },
+        }
+    } else if entry.matches(':').count() == 1 {
+        let (host, port) = entry.split_once(':').unwrap();
+        (host, Some(port))
+    } else {
+        (entry, None)
+    };
+
+    if host.is_empty() {
+        bail!("The host is missing in `{entry}`");
+    }
+
+    let mut rule = EgressRule::allow();
+    if let Some((ip, prefix)) = host.split_once('/') {
+        let ip: IpAddr = ip
+            .parse()
+            .map_err(|_| anyhow!("Invalid address in CIDR range `{host}`"))?;
+        let prefix: u8 = prefix
+            .parse()
+            .map_err(|_| anyhow!("Invalid prefix length in CIDR range `{host}`"))?;
+        rule = rule.cidr(IpCidr { ip, prefix });
+    } else if let Ok(ip) = host.parse::<IpAddr>() {
+        let prefix = if ip.is_ipv4() { 32 } else { 128 };
+        rule = rule.cidr(IpCidr { ip, prefix });
+    } else {
+        rule = rule.host(host);
+    }
+
+    if let Some(port) = port {
+        let port: u16 = port
+            .parse()
+            .map_err(|_| anyhow!("Invalid port `{port}` in `{entry}`"))?;
+        rule = rule.ports(port..=port);
+    }
+
+    Ok(rule)
+}
+
+/// Parses a `--forward-port` entry of the form `[<addr>:]<host port>:<guest port>`.
+/// Without an address the port is opened on all interfaces.
+pub fn parse_forward_port(entry: &str) -> Result<(SocketAddr, u16)> {
+    let entry = entry.trim();
+    let (host, guest_port) = entry.rsplit_once(':').ok_or_else(|| {
+        anyhow!("Port forwards must be of the form `[<addr>:]<host port>:<guest port>`; found `{entry}`")
+    })?;
+    let guest_port: u16 = guest_port
+        .parse()
+        .map_err(|_| anyhow!("Invalid guest port `{guest_port}` in `{entry}`"))?;
+
+    let host = match host.parse::<u16>() {
+        Ok(port) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port),
+        Err(_) => host
+            .parse::<SocketAddr>()
+            .map_err(|_| anyhow!("Invalid host address `{host}` in `{entry}`"))?,
+    };
+
+    Ok((host, guest_port))
+}
+
+/// Parses a `--proxy` URL of the form `<scheme>://[<user>:<password>@]<host>:<port>`,
+/// where the scheme is `socks5` or `http`.
+pub fn parse_proxy(entry: &str) -> Result<ProxyConfig> {
+    let entry = entry.trim();
+    let (scheme, rest) = entry.split_once("://").ok_or_else(|| {
+        anyhow!("Proxies must be of the form `<scheme>://[<user>:<password>@]<host>:<port>`; found `{entry}`")
+    })?;
+    let (credentials, host) = match rest.rsplit_once('@') {
+        Some((credentials, host)) => {
+            let (username, password) = credentials
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Expected `<user>:<password>@` in `{entry}`"))?;
+            (Some((username, password)), host)
+        }
+        None => (None, rest),
+    };
+    let host = host.trim_end_matches('/');
+    let addr = host
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| anyhow!("Unable to resolve the proxy address `{host}` in `{entry}`"))?;
+
+    let config = match scheme {
+        "socks5" => ProxyConfig::socks5(addr),
+        "http" => ProxyConfig::http_connect(addr),
+        _ => bail!("Unsupported proxy scheme `{scheme}` in `{entry}`, expected `socks5` or `http`"),
+    };
+    Ok(match credentials {
+        Some((username, password)) => config.with_credentials(username, password),
+        None => config,
+    })
+}
+
+/// Parses the contents of a dotenv-style file.
+///
+/// Every line which isn't empty or a `#` comment is a `KEY=VALUE` pair,
+/// optionally prefixed with `export`. Single-quoted values are taken as-is.
+/// Unquoted and double-quoted values have `$VAR`, `${VAR}` and
+/// `${VAR:-default}` expanded, looking up the variables defined earlier in
+/// the file first and then `lookup`.
+pub fn parse_env_file(
+    contents: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<(String, String)>> {
+    let mut vars: Vec<(String, String)> = Vec::new();
+
+    for (number, line) in contents.lines().enumerate() {
+        let number = number + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line
+            .strip_prefix("export ")
+            .map(str::trim_start)
+            .unwrap_or(line);
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("line {number}: expected `KEY=VALUE`, found `{line}`"))?;
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            bail!("line {number}: `{key}` is not a valid variable name");
+        }
+
+        let lookup_var = |name: &str| {
+            vars.iter()
+                .rev()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| lookup(name))
+        };
+        let value = value.trim();
+        let value = if let Some(quoted) = value.strip_prefix('\'') {
+            quoted
+                .strip_suffix('\'')
+                .ok_or_else(|| anyhow!("line {number}: missing closing `'`"))?
+                .to_string()
+        } else if let Some(quoted) = value.strip_prefix('"') {
+            let quoted = quoted
+                .strip_suffix('"')
+                .ok_or_else(|| anyhow!("line {number}: missing closing `\"`"))?;
+            expand_vars(quoted, true, lookup_var).map_err(|e| anyhow!("line {number}: {e}"))?
+        } else {
+            // Unquoted values may be followed by a comment
+            let value = match value.find(" #") {
+                Some(comment) => value[..comment].trim_end(),
+                None => value,
+            };
+            expand_vars(value, false, lookup_var).map_err(|e| anyhow!("line {number}: {e}"))?
+        };
+
+        vars.push((key.to_string(), value));
+    }
+
+    Ok(vars)
+}
+
+/// Expands the variables in a value of a dotenv file, and the escape
+/// sequences if it was double-quoted.
+fn expand_vars(
+    value: &str,
+    double_quoted: bool,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('$') => expanded.push('$'),
+                Some('n') if double_quoted => expanded.push('\n'),
+                Some('t') if double_quoted => expanded.push('\t'),
+                Some('r') if double_quoted => expanded.push('\r'),
+                Some(c @ ('"' | '\\')) if double_quoted => expanded.push(c),
+                Some(c) => {
+                    expanded.push('\\');
+                    expanded.push(c);
+                }
+                None => expanded.push('\\'),
+            },
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => bail!("missing closing `}}` in `${{{name}`"),
+                    }
+                }
+                let (name, default) = match name.split_once(":-") {
+                    Some((name, default)) => (name, Some(default)),
+                    None => (name.as_str(), None),
+                };
+                match lookup(name).filter(|value| !value.is_empty()) {
+                    Some(value) => expanded.push_str(&value),
+                    None => expanded.push_str(default.unwrap_or_default()),
+                }
+            }
+            '$' if matches!(chars.peek(), Some(c) if c.is_ascii_alphabetic() || *c == '_') => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                expanded.push_str(&lookup(&name).unwrap_or_default());
+            }
+            c => expanded.push(c),
+        }
+    }
+
+    Ok(expanded)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::parse_envvar;
+    use super::{
+        parse_allow_host, parse_env_file, parse_envvar, parse_forward_port, parse_mapdir,
+        parse_proxy,
+    };
+    use wasmer_wasix::runners::MountOptions;
 
     #[test]
     fn test_parse_envvar() {
@@ -93,4 +408,145 @@ mod tests {
             ("A".into(), "B=C=D".into())
         );
     }
+
+    #[test]
+    fn test_parse_env_file() {
+        let contents = r#"
+            # A comment
+            export NAME=world
+            GREETING="hello ${NAME}\n"
+            RAW='$NAME stays'
+            HOME_DIR=$HOME/app # trailing comment
+            FALLBACK=${MISSING:-default}
+            ESCAPED=\$NAME
+        "#;
+        let host = |name: &str| match name {
+            "HOME" => Some("/home/ferris".to_string()),
+            "NAME" => Some("host".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            parse_env_file(contents, host).unwrap(),
+            [
+                ("NAME".to_string(), "world".to_string()),
+                ("GREETING".to_string(), "hello world\n".to_string()),
+                ("RAW".to_string(), "$NAME stays".to_string()),
+                ("HOME_DIR".to_string(), "/home/ferris/app".to_string()),
+                ("FALLBACK".to_string(), "default".to_string()),
+                ("ESCAPED".to_string(), "$NAME".to_string()),
+            ]
+        );
+
+        let no_host = |_: &str| None;
+        assert!(parse_env_file("NOT A VAR", no_host).is_err());
+        assert!(parse_env_file("BAD-NAME=1", no_host).is_err());
+        assert!(parse_env_file("A=\"unterminated", no_host).is_err());
+        assert!(parse_env_file("A=${B", no_host).is_err());
+    }
+
+    #[test]
+    fn test_parse_allow_host() {
+        let rule = parse_allow_host("example.com:443").unwrap();
+        assert_eq!(rule.hosts, ["example.com"]);
+        assert_eq!(rule.ports, Some(443..=443));
+
+        let rule = parse_allow_host("*.wasmer.io").unwrap();
+        assert_eq!(rule.hosts, ["*.wasmer.io"]);
+        assert_eq!(rule.ports, None);
+
+        let rule = parse_allow_host("10.0.0.0/8").unwrap();
+        assert_eq!(
+            rule.cidrs[0].ip,
+            "10.0.0.0".parse::<std::net::IpAddr>().unwrap()
+        );
+        assert_eq!(rule.cidrs[0].prefix, 8);
+
+        let rule = parse_allow_host("[::1]:53").unwrap();
+        assert_eq!(rule.cidrs[0].prefix, 128);
+        assert_eq!(rule.ports, Some(53..=53));
+
+        let rule = parse_allow_host("fe80::1").unwrap();
+        assert_eq!(rule.cidrs[0].prefix, 128);
+        assert_eq!(rule.ports, None);
+
+        assert!(parse_allow_host(":443").is_err());
+        assert!(parse_allow_host("example.com:https").is_err());
+        assert!(parse_allow_host("10.0.0.0/x").is_err());
+        assert!(parse_allow_host("[::1").is_err());
+    }
+
+    #[test]
+    fn test_parse_forward_port() {
+        assert_eq!(
+            parse_forward_port("8080:80").unwrap(),
+            ("0.0.0.0:8080".parse().unwrap(), 80)
+        );
+        assert_eq!(
+            parse_forward_port("127.0.0.1:8080:80").unwrap(),
+            ("127.0.0.1:8080".parse().unwrap(), 80)
+        );
+        assert_eq!(
+            parse_forward_port("[::1]:8080:80").unwrap(),
+            ("[::1]:8080".parse().unwrap(), 80)
+        );
+        assert!(parse_forward_port("8080").is_err());
+        assert!(parse_forward_port("8080:http").is_err());
+        assert!(parse_forward_port("localhost:8080:80").is_err());
+    }
+
+    #[test]
+    fn test_parse_proxy() {
+        let proxy = parse_proxy("socks5://127.0.0.1:1080").unwrap();
+        assert_eq!(proxy.protocol, virtual_net::proxy::ProxyProtocol::Socks5);
+        assert_eq!(proxy.addr, "127.0.0.1:1080".parse().unwrap());
+        assert_eq!(proxy.credentials, None);
+
+        let proxy = parse_proxy("http://user:p@ss@[::1]:3128/").unwrap();
+        assert_eq!(
+            proxy.protocol,
+            virtual_net::proxy::ProxyProtocol::HttpConnect
+        );
+        assert_eq!(proxy.addr, "[::1]:3128".parse().unwrap());
+        assert_eq!(
+            proxy.credentials,
+            Some(("user".to_string(), "p@ss".to_string()))
+        );
+
+        assert!(parse_proxy("127.0.0.1:1080").is_err());
+        assert!(parse_proxy("ftp://127.0.0.1:21").is_err());
+        assert!(parse_proxy("socks5://127.0.0.1").is_err());
+        assert!(parse_proxy("socks5://user@127.0.0.1:1080").is_err());
+    }
+
+    #[test]
+    fn test_parse_mapdir() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let host = temp.path().display().to_string();
+
+        let dir = parse_mapdir(&format!("/data:{host}")).unwrap();
+        assert_eq!(dir.guest, "/data");
+        assert_eq!(dir.host, temp.path());
+        assert_eq!(dir.options, MountOptions::default());
+
+        let dir = parse_mapdir(&format!("/data::{host}:ro")).unwrap();
+        assert_eq!(dir.guest, "/data");
+        assert_eq!(dir.host, temp.path());
+        assert!(dir.options.read_only);
+
+        let dir = parse_mapdir(&format!("/data:{host}:no-create")).unwrap();
+        assert_eq!(dir.host, temp.path());
+        assert_eq!(
+            dir.options,
+            MountOptions {
+                read_only: false,
+                no_create: true,
+            }
+        );
+
+        let dir = parse_mapdir(&format!("/data:{host}:ro,no-create")).unwrap();
+        assert!(dir.options.read_only && dir.options.no_create);
+
+        assert!(parse_mapdir(&format!("/data:{host}:bogus")).is_err());
+    }
 }
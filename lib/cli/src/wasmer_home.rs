@@ -13,6 +13,8 @@ use wasmer::{AsEngineRef, DeserializeError, Module, SerializeError};
 use wasmer_cache::Hash;
 use wasmer_registry::Package;
 
+use crate::{cache_stats::CacheStats, oci::OciReference};
+
 const DEFAULT_REGISTRY: &str = "https://wapm.io/";
 const CACHE_INVALIDATION_THRESHOLD: Duration = Duration::from_secs(5 * 60);
 
@@ -21,6 +23,7 @@ const CACHE_INVALIDATION_THRESHOLD: Duration = Duration::from_secs(5 * 60);
 pub trait DownloadCached {
     fn download_url(&self, url: &Url) -> Result<PathBuf, Error>;
     fn download_package(&self, pkg: &Package) -> Result<PathBuf, Error>;
+    fn download_oci(&self, reference: &OciReference) -> Result<PathBuf, Error>;
 }
 
 #[derive(Debug, clap::Parser)]
@@ -56,8 +59,11 @@ impl WasmerHome {
 
         self.wasmer_home()
             .ok()
-            .and_then(|home| wasmer_cache::FileSystemCache::new(home.join("cache")).ok())
-            .map(ModuleCache::Enabled)
+            .map(|home| home.join("cache"))
+            .and_then(|dir| {
+                let cache = wasmer_cache::FileSystemCache::new(&dir).ok()?;
+                Some(ModuleCache::Enabled(cache, dir))
+            })
             .unwrap_or(ModuleCache::Disabled)
     }
 }
@@ -199,6 +205,11 @@ impl DownloadCached for WasmerHome {
 
         self.download_url(&url)
     }
+
+    fn download_oci(&self, reference: &OciReference) -> Result<PathBuf, Error> {
+        let cache_dir = self.wasmer_home()?.join("cache").join("oci");
+        crate::oci::pull(reference, &cache_dir)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -338,7 +349,8 @@ fn package_url(registry: &str, pkg: &Package) -> Result<Url, Error> {
 
 #[derive(Debug, Clone)]
 pub enum ModuleCache {
-    Enabled(wasmer_cache::FileSystemCache),
+    /// A cache on disk, and the directory it lives in
+    Enabled(wasmer_cache::FileSystemCache, PathBuf),
     Disabled,
 }
 
@@ -352,14 +364,18 @@ impl wasmer_cache::Cache for ModuleCache {
         key: Hash,
     ) -> Result<Module, Self::DeserializeError> {
         match self {
-            ModuleCache::Enabled(f) => f.load(engine, key),
+            ModuleCache::Enabled(f, dir) => {
+                let result = f.load(engine, key);
+                CacheStats::record(dir, result.is_ok());
+                result
+            }
             ModuleCache::Disabled => Err(DeserializeError::Io(std::io::ErrorKind::NotFound.into())),
         }
     }
 
     fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError> {
         match self {
-            ModuleCache::Enabled(f) => f.store(key, module),
+            ModuleCache::Enabled(f, _) => f.store(key, module),
             ModuleCache::Disabled => Ok(()),
         }
     }
@@ -6,19 +6,37 @@ use cranelift_codegen::MachSrcLoc;
 use std::ops::Range;
 use wasmer_types::{FunctionAddressMap, InstructionAddressMap, SourceLoc};
 
+/// Builds the address map of a compiled function.
+///
+/// A `compact` map leaves out the code without a source location, which
+/// is then attributed to the start of the function, and merges adjacent
+/// ranges of code that come from the same Wasm instruction.
 pub fn get_function_address_map(
     context: &Context,
     range: Range<usize>,
     body_len: usize,
+    compact: bool,
 ) -> FunctionAddressMap {
-    let mut instructions = Vec::new();
+    let mut instructions: Vec<InstructionAddressMap> = Vec::new();
 
     // New-style backend: we have a `MachCompileResult` that will give us `MachSrcLoc` mapping
     // tuples.
     let mcr = context.compiled_code().unwrap();
     for &MachSrcLoc { start, end, loc } in mcr.buffer.get_srclocs_sorted() {
+        let srcloc = SourceLoc::new(loc.bits());
+        if compact {
+            if srcloc.is_default() {
+                continue;
+            }
+            if let Some(last) = instructions.last_mut() {
+                if last.srcloc == srcloc && last.code_offset + last.code_len == start as usize {
+                    last.code_len += (end - start) as usize;
+                    continue;
+                }
+            }
+        }
         instructions.push(InstructionAddressMap {
-            srcloc: SourceLoc::new(loc.bits()),
+            srcloc,
             code_offset: start as usize,
             code_len: (end - start) as usize,
         });
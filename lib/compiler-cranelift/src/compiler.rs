@@ -10,7 +10,7 @@ use crate::trampoline::{
 };
 use crate::translator::{
     compiled_function_unwind_info, irlibcall_to_libcall, irreloc_to_relocationkind,
-    signature_to_cranelift_ir, CraneliftUnwindInfo, FuncTranslator,
+    signature_to_cranelift_ir, CraneliftUnwindInfo, FuncTranslator, InlineCandidates,
 };
 use cranelift_codegen::ir::{ExternalName, UserFuncName};
 use cranelift_codegen::{ir, MachReloc};
@@ -21,8 +21,8 @@ use gimli::write::{Address, EhFrame, FrameTable};
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 use std::sync::Arc;
 use wasmer_compiler::{
-    Compiler, FunctionBinaryReader, FunctionBodyData, MiddlewareBinaryReader, ModuleMiddleware,
-    ModuleMiddlewareChain, ModuleTranslationState,
+    BranchHints, Compiler, FunctionBinaryReader, FunctionBodyData, MiddlewareBinaryReader,
+    ModuleMiddleware, ModuleMiddlewareChain, ModuleTranslationState,
 };
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
@@ -108,6 +108,18 @@ impl Compiler for CraneliftCompiler {
         };
 
         let mut custom_sections = PrimaryMap::new();
+        let branch_hints = BranchHints::from_module(module);
+        // Middlewares must see every operator of every function, so the
+        // bodies can't be inlined when there are some.
+        let inline_candidates =
+            if self.config.inline_budget > 0 && self.config.middlewares.is_empty() {
+                Some(
+                    InlineCandidates::new(module, &function_body_inputs, self.config.inline_budget)
+                        .map_err(CompileError::Wasm)?,
+                )
+            } else {
+                None
+            };
 
         #[cfg(not(feature = "rayon"))]
         let mut func_translator = FuncTranslator::new();
@@ -125,6 +137,7 @@ impl Compiler for CraneliftCompiler {
                     &signatures,
                     &memory_styles,
                     &table_styles,
+                    self.config.relaxed_simd_deterministic,
                 );
                 context.func.name = match get_function_name(func_index) {
                     ExternalName::User(nameref) => {
@@ -156,6 +169,12 @@ impl Compiler for CraneliftCompiler {
                     &mut context.func,
                     &mut func_env,
                     i,
+                    branch_hints.function(func_index),
+                    self.config
+                        .profile
+                        .as_ref()
+                        .and_then(|profile| profile.function(i)),
+                    inline_candidates.as_ref(),
                 )?;
 
                 let mut code_buf: Vec<u8> = Vec::new();
@@ -206,7 +225,12 @@ impl Compiler for CraneliftCompiler {
                 };
 
                 let range = reader.range();
-                let address_map = get_function_address_map(&context, range, code_buf.len());
+                let address_map = get_function_address_map(
+                    &context,
+                    range,
+                    code_buf.len(),
+                    self.config().optimize_for_size,
+                );
 
                 Ok((
                     CompiledFunction {
@@ -237,6 +261,7 @@ impl Compiler for CraneliftCompiler {
                     &signatures,
                     memory_styles,
                     table_styles,
+                    self.config.relaxed_simd_deterministic,
                 );
                 context.func.name = match get_function_name(func_index) {
                     ExternalName::User(nameref) => {
@@ -268,6 +293,12 @@ impl Compiler for CraneliftCompiler {
                     &mut context.func,
                     &mut func_env,
                     *i,
+                    branch_hints.function(func_index),
+                    self.config
+                        .profile
+                        .as_ref()
+                        .and_then(|profile| profile.function(*i)),
+                    inline_candidates.as_ref(),
                 )?;
 
                 let mut code_buf: Vec<u8> = Vec::new();
@@ -318,7 +349,12 @@ impl Compiler for CraneliftCompiler {
                 };
 
                 let range = reader.range();
-                let address_map = get_function_address_map(&context, range, code_buf.len());
+                let address_map = get_function_address_map(
+                    &context,
+                    range,
+                    code_buf.len(),
+                    self.config().optimize_for_size,
+                );
 
                 Ok((
                     CompiledFunction {
@@ -464,7 +500,23 @@ fn translate_ir_trapcode(trap: ir::TrapCode) -> TrapCode {
         ir::TrapCode::BadConversionToInteger => TrapCode::BadConversionToInteger,
         ir::TrapCode::UnreachableCodeReached => TrapCode::UnreachableCodeReached,
         ir::TrapCode::Interrupt => unimplemented!("Interrupts not supported"),
-        ir::TrapCode::User(_user_code) => unimplemented!("User trap code not supported"),
+        // Traps raised by middlewares carry their code as a user code.
+        ir::TrapCode::User(user_code) => match user_code {
+            0 => TrapCode::StackOverflow,
+            1 => TrapCode::HeapAccessOutOfBounds,
+            2 => TrapCode::HeapMisaligned,
+            3 => TrapCode::TableAccessOutOfBounds,
+            4 => TrapCode::IndirectCallToNull,
+            5 => TrapCode::BadSignature,
+            6 => TrapCode::IntegerOverflow,
+            7 => TrapCode::IntegerDivisionByZero,
+            8 => TrapCode::BadConversionToInteger,
+            9 => TrapCode::UnreachableCodeReached,
+            10 => TrapCode::UnalignedAtomic,
+            11 => TrapCode::StackExhausted,
+            12 => TrapCode::OutOfFuel,
+            _ => unimplemented!("User trap code not supported"),
+        },
         // ir::TrapCode::Interrupt => TrapCode::Interrupt,
         // ir::TrapCode::User(user_code) => TrapCode::User(user_code),
     }
@@ -4,7 +4,7 @@ use cranelift_codegen::settings::{self, Configurable};
 use cranelift_codegen::CodegenResult;
 use std::sync::Arc;
 use wasmer_compiler::{Compiler, CompilerConfig, Engine, EngineBuilder, ModuleMiddleware};
-use wasmer_types::{Architecture, CpuFeature, Target};
+use wasmer_types::{Architecture, CpuFeature, ExecutionProfile, Target};
 
 // Runtime Environment
 
@@ -30,9 +30,13 @@ pub enum CraneliftOptLevel {
 #[derive(Debug, Clone)]
 pub struct Cranelift {
     enable_nan_canonicalization: bool,
+    pub(crate) relaxed_simd_deterministic: bool,
     enable_verifier: bool,
     enable_pic: bool,
     opt_level: CraneliftOptLevel,
+    pub(crate) optimize_for_size: bool,
+    pub(crate) profile: Option<Arc<ExecutionProfile>>,
+    pub(crate) inline_budget: usize,
     /// The middleware chain.
     pub(crate) middlewares: Vec<Arc<dyn ModuleMiddleware>>,
 }
@@ -43,9 +47,13 @@ impl Cranelift {
     pub fn new() -> Self {
         Self {
             enable_nan_canonicalization: false,
+            relaxed_simd_deterministic: false,
             enable_verifier: false,
             opt_level: CraneliftOptLevel::Speed,
+            optimize_for_size: false,
             enable_pic: false,
+            profile: None,
+            inline_budget: 0,
             middlewares: vec![],
         }
     }
@@ -59,12 +67,59 @@ impl Cranelift {
         self
     }
 
+    /// Lower the relaxed SIMD instructions deterministically.
+    ///
+    /// By default, relaxed SIMD instructions use the fastest lowering
+    /// available, whose results may depend on the architecture.
+    pub fn relaxed_simd_deterministic(&mut self, enable: bool) -> &mut Self {
+        self.relaxed_simd_deterministic = enable;
+        self
+    }
+
     /// The optimization levels when optimizing the IR.
     pub fn opt_level(&mut self, opt_level: CraneliftOptLevel) -> &mut Self {
         self.opt_level = opt_level;
         self
     }
 
+    /// Optimize for code size rather than speed.
+    ///
+    /// This overrides the `opt_level` with `SpeedAndSize`, and leaves
+    /// out of the address maps the code that has no Wasm source
+    /// location. Such code is attributed to the start of its function
+    /// in backtraces.
+    pub fn optimize_for_size(&mut self, enable: bool) -> &mut Self {
+        self.optimize_for_size = enable;
+        self
+    }
+
+    /// An execution profile of the module, recorded by a previous run
+    /// of an instrumented build (see the `pgo` middleware in
+    /// `wasmer-middlewares`), used to lay out the code.
+    ///
+    /// The side of a conditional branch that was rarely taken is moved
+    /// with the error paths, after the hot code of its function, so
+    /// that the hot code fits in fewer cache lines. Branch hints of the
+    /// module take precedence over the profile.
+    pub fn profile(&mut self, profile: Option<Arc<ExecutionProfile>>) -> &mut Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Inline the small functions of the module at their direct call
+    /// sites, to remove the overhead of calling them.
+    ///
+    /// Only the functions without control flow or calls, and whose body
+    /// has at most `max_operators` operators, are inlined. This covers the
+    /// accessors and the small arithmetic helpers that toolchains tend to
+    /// leave behind. `0`, the default, disables inlining.
+    ///
+    /// Inlining is disabled when the configuration has middlewares.
+    pub fn inline_budget(&mut self, max_operators: usize) -> &mut Self {
+        self.inline_budget = max_operators;
+        self
+    }
+
     /// Generates the ISA for the provided target
     pub fn isa(&self, target: &Target) -> CodegenResult<Box<dyn TargetIsa>> {
         let mut builder =
@@ -170,6 +225,7 @@ impl Cranelift {
             .set(
                 "opt_level",
                 match self.opt_level {
+                    _ if self.optimize_for_size => "speed_and_size",
                     CraneliftOptLevel::None => "none",
                     CraneliftOptLevel::Speed => "speed",
                     CraneliftOptLevel::SpeedAndSize => "speed_and_size",
@@ -217,6 +273,18 @@ impl CompilerConfig for Cranelift {
         self.enable_nan_canonicalization = enable;
     }
 
+    fn relaxed_simd_deterministic(&mut self, enable: bool) {
+        self.relaxed_simd_deterministic = enable;
+    }
+
+    fn optimize_for_size(&mut self, enable: bool) {
+        self.optimize_for_size = enable;
+    }
+
+    fn inline_budget(&mut self, max_operators: usize) {
+        self.inline_budget = max_operators;
+    }
+
     /// Transform it into the compiler
     fn compiler(self: Box<Self>) -> Box<dyn Compiler> {
         Box::new(CraneliftCompiler::new(*self))
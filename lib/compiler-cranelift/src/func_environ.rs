@@ -82,6 +82,10 @@ pub struct FuncEnvironment<'module_environment> {
     /// (it's the same for both local and imported memories).
     memory_copy_sig: Option<ir::SigRef>,
 
+    /// The external function signature for implementing wasm's `memory.copy`
+    /// between two different memories.
+    memory_copy_between_sig: Option<ir::SigRef>,
+
     /// The external function signature for implementing wasm's `memory.fill`
     /// (it's the same for both local and imported memories).
     memory_fill_sig: Option<ir::SigRef>,
@@ -121,6 +125,9 @@ pub struct FuncEnvironment<'module_environment> {
 
     /// The table styles
     table_styles: &'module_environment PrimaryMap<TableIndex, TableStyle>,
+
+    /// Whether relaxed SIMD instructions are lowered deterministically.
+    relaxed_simd_deterministic: bool,
 }
 
 impl<'module_environment> FuncEnvironment<'module_environment> {
@@ -130,6 +137,7 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         signatures: &'module_environment PrimaryMap<SignatureIndex, ir::Signature>,
         memory_styles: &'module_environment PrimaryMap<MemoryIndex, MemoryStyle>,
         table_styles: &'module_environment PrimaryMap<TableIndex, TableStyle>,
+        relaxed_simd_deterministic: bool,
     ) -> Self {
         Self {
             target_config,
@@ -145,6 +153,7 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             table_init_sig: None,
             elem_drop_sig: None,
             memory_copy_sig: None,
+            memory_copy_between_sig: None,
             memory_fill_sig: None,
             memory_init_sig: None,
             table_get_sig: None,
@@ -158,6 +167,7 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             offsets: VMOffsets::new(target_config.pointer_bytes(), module),
             memory_styles,
             table_styles,
+            relaxed_simd_deterministic,
         }
     }
 
@@ -363,6 +373,18 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         sig
     }
 
+    /// Bulk memory operations are only implemented for memories indexed
+    /// with 32-bit addresses.
+    fn check_memory32(&self, index: MemoryIndex, operator: &str) -> WasmResult<()> {
+        if self.module.memories[index].is_memory64() {
+            return Err(WasmError::Unsupported(format!(
+                "{} on 64-bit memories",
+                operator
+            )));
+        }
+        Ok(())
+    }
+
     /// Return the memory.grow function signature to call for the given index, along with the
     /// translated index value to pass to it and its index in `VMBuiltinFunctionsArray`.
     fn get_memory_grow_func(
@@ -600,6 +622,30 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         }
     }
 
+    fn get_memory_copy_between_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.memory_copy_between_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Destination memory index.
+                    AbiParam::new(I32),
+                    // Source memory index.
+                    AbiParam::new(I32),
+                    // Destination address.
+                    AbiParam::new(I32),
+                    // Source address.
+                    AbiParam::new(I32),
+                    // Length.
+                    AbiParam::new(I32),
+                ],
+                returns: vec![],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.memory_copy_between_sig = Some(sig);
+        sig
+    }
+
     fn get_memory_fill_sig(&mut self, func: &mut Function) -> ir::SigRef {
         let sig = self.memory_fill_sig.unwrap_or_else(|| {
             func.import_signature(Signature {
@@ -851,6 +897,27 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
 
         (base, func_addr)
     }
+
+    /// Translates load of the address of the interrupt flag.
+    fn interrupt_flag_address(
+        &mut self,
+        pos: &mut FuncCursor<'_>,
+        index: GlobalIndex,
+    ) -> ir::Value {
+        let pointer_type = self.pointer_type();
+        let vmctx = self.vmctx(pos.func);
+        let base = pos.ins().global_value(pointer_type, vmctx);
+
+        let mut mem_flags = ir::MemFlags::trusted();
+        mem_flags.set_readonly();
+
+        let def_index = self
+            .module
+            .local_global_index(index)
+            .expect("the interrupt flag is a local global");
+        let offset = i32::try_from(self.offsets.vmctx_vmglobal_definition(def_index)).unwrap();
+        pos.ins().load(pointer_type, mem_flags, base, offset)
+    }
 }
 
 impl<'module_environment> TargetEnvironment for FuncEnvironment<'module_environment> {
@@ -865,6 +932,10 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         index >= 1
     }
 
+    fn relaxed_simd_deterministic(&self) -> bool {
+        self.relaxed_simd_deterministic
+    }
+
     fn make_table(&mut self, func: &mut ir::Function, index: TableIndex) -> WasmResult<ir::Table> {
         let pointer_type = self.pointer_type();
 
@@ -1057,19 +1128,25 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
 
     fn translate_custom_global_get(
         &mut self,
-        mut _pos: cranelift_codegen::cursor::FuncCursor<'_>,
-        _index: GlobalIndex,
+        mut pos: cranelift_codegen::cursor::FuncCursor<'_>,
+        index: GlobalIndex,
     ) -> WasmResult<ir::Value> {
-        unreachable!("we don't make any custom globals")
+        // Only the interrupt flag is a custom global. Another thread sets
+        // it, so it's read atomically to keep the optimizer from hoisting
+        // the check out of loops.
+        let addr = self.interrupt_flag_address(&mut pos, index);
+        Ok(pos.ins().atomic_load(I32, ir::MemFlags::trusted(), addr))
     }
 
     fn translate_custom_global_set(
         &mut self,
-        mut _pos: cranelift_codegen::cursor::FuncCursor<'_>,
-        _index: GlobalIndex,
-        _value: ir::Value,
+        mut pos: cranelift_codegen::cursor::FuncCursor<'_>,
+        index: GlobalIndex,
+        value: ir::Value,
     ) -> WasmResult<()> {
-        unreachable!("we don't make any custom globals")
+        let addr = self.interrupt_flag_address(&mut pos, index);
+        pos.ins().atomic_store(ir::MemFlags::trusted(), value, addr);
+        Ok(())
     }
 
     fn make_heap(&mut self, func: &mut ir::Function, index: MemoryIndex) -> WasmResult<ir::Heap> {
@@ -1142,7 +1219,11 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
             min_size: 0.into(),
             offset_guard_size,
             style: heap_style,
-            index_type: I32,
+            index_type: if self.module.memories[index].is_memory64() {
+                I64
+            } else {
+                I32
+            },
         }))
     }
 
@@ -1151,6 +1232,10 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         func: &mut ir::Function,
         index: GlobalIndex,
     ) -> WasmResult<GlobalVariable> {
+        if self.module.interrupt_global == Some(index) {
+            return Ok(GlobalVariable::Custom);
+        }
+
         let pointer_type = self.pointer_type();
 
         let (ptr, offset) = {
@@ -1337,13 +1422,33 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         _heap: ir::Heap,
         val: ir::Value,
     ) -> WasmResult<ir::Value> {
+        let memory64 = self.module.memories[index].is_memory64();
+        // The libcall counts pages with 32 bits: a larger delta can't
+        // succeed anyway, so it is saturated.
+        let val = if memory64 {
+            let max = pos.ins().iconst(I64, i64::from(u32::MAX));
+            let too_large = pos.ins().icmp(IntCC::UnsignedGreaterThan, val, max);
+            let val = pos.ins().select(too_large, max, val);
+            pos.ins().ireduce(I32, val)
+        } else {
+            val
+        };
         let (func_sig, index_arg, func_idx) = self.get_memory_grow_func(pos.func, index);
         let memory_index = pos.ins().iconst(I32, index_arg as i64);
         let (vmctx, func_addr) = self.translate_load_builtin_function_address(&mut pos, func_idx);
         let call_inst = pos
             .ins()
             .call_indirect(func_sig, func_addr, &[vmctx, val, memory_index]);
-        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
+        let result = *pos.func.dfg.inst_results(call_inst).first().unwrap();
+        if memory64 {
+            // Failures are reported as `-1`, which must stay `-1`.
+            let failed = pos.ins().icmp_imm(IntCC::Equal, result, -1);
+            let minus_one = pos.ins().iconst(I64, -1);
+            let result = pos.ins().uextend(I64, result);
+            Ok(pos.ins().select(failed, minus_one, result))
+        } else {
+            Ok(result)
+        }
     }
 
     fn translate_memory_size(
@@ -1358,7 +1463,12 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         let call_inst = pos
             .ins()
             .call_indirect(func_sig, func_addr, &[vmctx, memory_index]);
-        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
+        let result = *pos.func.dfg.inst_results(call_inst).first().unwrap();
+        if self.module.memories[index].is_memory64() {
+            Ok(pos.ins().uextend(I64, result))
+        } else {
+            Ok(result)
+        }
     }
 
     fn translate_memory_copy(
@@ -1366,12 +1476,28 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         mut pos: FuncCursor,
         src_index: MemoryIndex,
         _src_heap: ir::Heap,
-        _dst_index: MemoryIndex,
+        dst_index: MemoryIndex,
         _dst_heap: ir::Heap,
         dst: ir::Value,
         src: ir::Value,
         len: ir::Value,
     ) -> WasmResult<()> {
+        self.check_memory32(src_index, "memory.copy")?;
+        self.check_memory32(dst_index, "memory.copy")?;
+        if src_index != dst_index {
+            let func_sig = self.get_memory_copy_between_sig(pos.func);
+            let func_idx = VMBuiltinFunctionIndex::get_memory_copy_between_index();
+            let dst_index_arg = pos.ins().iconst(I32, dst_index.index() as i64);
+            let src_index_arg = pos.ins().iconst(I32, src_index.index() as i64);
+            let (vmctx, func_addr) =
+                self.translate_load_builtin_function_address(&mut pos, func_idx);
+            pos.ins().call_indirect(
+                func_sig,
+                func_addr,
+                &[vmctx, dst_index_arg, src_index_arg, dst, src, len],
+            );
+            return Ok(());
+        }
         let (func_sig, src_index, func_idx) = self.get_memory_copy_func(pos.func, src_index);
 
         let src_index_arg = pos.ins().iconst(I32, src_index as i64);
@@ -1393,6 +1519,7 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         val: ir::Value,
         len: ir::Value,
     ) -> WasmResult<()> {
+        self.check_memory32(memory_index, "memory.fill")?;
         let (func_sig, memory_index, func_idx) = self.get_memory_fill_func(pos.func, memory_index);
 
         let memory_index_arg = pos.ins().iconst(I32, memory_index as i64);
@@ -1418,6 +1545,7 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         src: ir::Value,
         len: ir::Value,
     ) -> WasmResult<()> {
+        self.check_memory32(memory_index, "memory.init")?;
         let (func_sig, func_idx) = self.get_memory_init_func(pos.func);
 
         let memory_index_arg = pos.ins().iconst(I32, memory_index.index() as i64);
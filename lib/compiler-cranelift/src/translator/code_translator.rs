@@ -221,6 +221,13 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             // We do nothing
         }
         Operator::Unreachable => {
+            // A block that ends with a trap is an error path: it is laid out
+            // after the hot code of the function.
+            if let Some(block) = builder.current_block() {
+                if builder.func.layout.entry_block() != Some(block) {
+                    builder.set_cold_block(block);
+                }
+            }
             builder.ins().trap(ir::TrapCode::UnreachableCodeReached);
             state.reachable = false;
         }
@@ -285,6 +292,14 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let next_block = builder.create_block();
             canonicalise_then_jump(builder, next_block, &[]);
             builder.seal_block(next_block); // Only predecessor is the current block.
+            match (state.branch_hint, &else_data) {
+                (Some(false), _) => builder.set_cold_block(next_block),
+                (Some(true), ElseData::WithElse { else_block }) => {
+                    builder.set_cold_block(*else_block)
+                }
+                // Without an `else`, the other side is the code after the `if`.
+                _ => {}
+            }
             builder.switch_to_block(next_block);
 
             // Here we append an argument to a Block targeted by an argumentless jump instruction
@@ -1062,6 +1077,9 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let timeout = state.pop1(); // 64 (fixed)
             let expected = state.pop1(); // 32 or 64 (per the `Ixx` in `IxxAtomicWait`)
             let addr = state.pop1(); // 32 (fixed)
+            if builder.func.dfg.value_type(addr) == I64 {
+                return Err(wasm_unsupported!("{:?} on 64-bit memories", op));
+            }
             let addr = fold_atomic_mem_addr(addr, memarg, implied_ty, builder);
             assert!(builder.func.dfg.value_type(expected) == implied_ty);
             // `fn translate_atomic_wait` can inspect the type of `expected` to figure out what
@@ -1092,6 +1110,9 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let heap = state.get_heap(builder.func, memarg.memory, environ)?;
             let count = state.pop1(); // 32 (fixed)
             let addr = state.pop1(); // 32 (fixed)
+            if builder.func.dfg.value_type(addr) == I64 {
+                return Err(wasm_unsupported!("{:?} on 64-bit memories", op));
+            }
             let addr = fold_atomic_mem_addr(addr, memarg, I32, builder);
             match environ.translate_atomic_notify(builder.cursor(), heap_index, heap, addr, count) {
                 Ok(res) => {
@@ -2036,23 +2057,110 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         | Operator::I32x4RelaxedTruncSatF32x4U
         | Operator::I32x4RelaxedTruncSatF64x2SZero
         | Operator::I32x4RelaxedTruncSatF64x2UZero
-        | Operator::F32x4RelaxedFma
-        | Operator::F32x4RelaxedFnma
-        | Operator::F64x2RelaxedFma
-        | Operator::F64x2RelaxedFnma
         | Operator::I8x16RelaxedLaneselect
         | Operator::I16x8RelaxedLaneselect
         | Operator::I32x4RelaxedLaneselect
         | Operator::I64x2RelaxedLaneselect
-        | Operator::F32x4RelaxedMin
+        | Operator::I16x8RelaxedQ15mulrS => {
+            // The strict counterparts of these instructions give results
+            // that the relaxed ones are allowed to give, and are already
+            // the fastest lowering Cranelift has.
+            let strict = match op {
+                Operator::I8x16RelaxedSwizzle => Operator::I8x16Swizzle,
+                Operator::I32x4RelaxedTruncSatF32x4S => Operator::I32x4TruncSatF32x4S,
+                Operator::I32x4RelaxedTruncSatF32x4U => Operator::I32x4TruncSatF32x4U,
+                Operator::I32x4RelaxedTruncSatF64x2SZero => Operator::I32x4TruncSatF64x2SZero,
+                Operator::I32x4RelaxedTruncSatF64x2UZero => Operator::I32x4TruncSatF64x2UZero,
+                Operator::I16x8RelaxedQ15mulrS => Operator::I16x8Q15MulrSatS,
+                _ => Operator::V128Bitselect,
+            };
+            translate_operator(module_translation_state, &strict, builder, state, environ)?;
+        }
+        Operator::F32x4RelaxedMin
         | Operator::F32x4RelaxedMax
         | Operator::F64x2RelaxedMin
-        | Operator::F64x2RelaxedMax
-        | Operator::F32x4RelaxedDotBf16x8AddF32x4
-        | Operator::I16x8RelaxedQ15mulrS
-        | Operator::I16x8DotI8x16I7x16S
-        | Operator::I32x4DotI8x16I7x16AddS => {
-            return Err(wasm_unsupported!("proposed relaxed-simd operator {:?}", op));
+        | Operator::F64x2RelaxedMax => {
+            let ty = match op {
+                Operator::F32x4RelaxedMin | Operator::F32x4RelaxedMax => F32X4,
+                _ => F64X2,
+            };
+            let is_min = matches!(op, Operator::F32x4RelaxedMin | Operator::F64x2RelaxedMin);
+            let (a, b) = pop2_with_bitcast(state, ty, builder);
+            let result = if environ.relaxed_simd_deterministic() {
+                if is_min {
+                    builder.ins().fmin(a, b)
+                } else {
+                    builder.ins().fmax(a, b)
+                }
+            } else if is_min {
+                // `a < b ? a : b`, which is a single `minps` on x86.
+                builder.ins().fmin_pseudo(b, a)
+            } else {
+                builder.ins().fmax_pseudo(b, a)
+            };
+            state.push1(result)
+        }
+        Operator::F32x4RelaxedFma
+        | Operator::F32x4RelaxedFnma
+        | Operator::F64x2RelaxedFma
+        | Operator::F64x2RelaxedFnma => {
+            let ty = match op {
+                Operator::F32x4RelaxedFma | Operator::F32x4RelaxedFnma => F32X4,
+                _ => F64X2,
+            };
+            let (a, b, c) = state.pop3();
+            let a = optionally_bitcast_vector(a, ty, builder);
+            let b = optionally_bitcast_vector(b, ty, builder);
+            let c = optionally_bitcast_vector(c, ty, builder);
+            // Not every target Cranelift supports can fuse vector
+            // operations, so the multiplication is always rounded on its
+            // own: this is deterministic either way.
+            let product = builder.ins().fmul(a, b);
+            let result = match op {
+                Operator::F32x4RelaxedFma | Operator::F64x2RelaxedFma => {
+                    builder.ins().fadd(product, c)
+                }
+                _ => builder.ins().fsub(c, product),
+            };
+            state.push1(result)
+        }
+        Operator::I16x8DotI8x16I7x16S => {
+            let (a, b) = pop2_with_bitcast(state, I16X8, builder);
+            state.push1(translate_i8x16_dot(a, b, builder))
+        }
+        Operator::I32x4DotI8x16I7x16AddS => {
+            let (a, b, c) = state.pop3();
+            let a = optionally_bitcast_vector(a, I16X8, builder);
+            let b = optionally_bitcast_vector(b, I16X8, builder);
+            let c = optionally_bitcast_vector(c, I32X4, builder);
+            let dot = translate_i8x16_dot(a, b, builder);
+            let one = builder.ins().iconst(I16, 1);
+            let ones = builder.ins().splat(I16X8, one);
+            let sums = builder.ins().widening_pairwise_dot_product_s(dot, ones);
+            state.push1(builder.ins().iadd(sums, c))
+        }
+        Operator::F32x4RelaxedDotBf16x8AddF32x4 => {
+            let (a, b, c) = state.pop3();
+            let a = optionally_bitcast_vector(a, I32X4, builder);
+            let b = optionally_bitcast_vector(b, I32X4, builder);
+            let c = optionally_bitcast_vector(c, F32X4, builder);
+            // A bfloat16 is the upper half of a float32.
+            let mask = builder.ins().iconst(I32, 0xffff_0000u32 as i64);
+            let mask = builder.ins().splat(I32X4, mask);
+            let mut widen = |value: Value, odd: bool| {
+                let value = if odd {
+                    builder.ins().band(value, mask)
+                } else {
+                    builder.ins().ishl_imm(value, 16)
+                };
+                optionally_bitcast_vector(value, F32X4, builder)
+            };
+            let (a_even, a_odd) = (widen(a, false), widen(a, true));
+            let (b_even, b_odd) = (widen(b, false), widen(b, true));
+            let even = builder.ins().fmul(a_even, b_even);
+            let odd = builder.ins().fmul(a_odd, b_odd);
+            let sum = builder.ins().fadd(even, odd);
+            state.push1(builder.ins().fadd(sum, c))
         }
     };
     Ok(())
@@ -2275,6 +2383,24 @@ fn get_heap_addr(
     }
 }
 
+/// Splits the static offset of a memory access into a part that is added
+/// to the address and a part that fits in the `heap_addr` immediate.
+///
+/// Only memories indexed with 64-bit addresses can have offsets that don't
+/// fit in 32 bits. The addition traps if it wraps around.
+fn fold_large_offset(addr: Value, offset: u64, builder: &mut FunctionBuilder) -> (Value, u32) {
+    match u32::try_from(offset) {
+        Ok(offset) => (addr, offset),
+        Err(_) => {
+            debug_assert_eq!(builder.func.dfg.value_type(addr), I64);
+            let sum = builder.ins().iadd_imm(addr, offset as i64);
+            let wrapped = builder.ins().icmp(IntCC::UnsignedLessThan, sum, addr);
+            builder.ins().trapnz(wrapped, ir::TrapCode::HeapOutOfBounds);
+            (sum, 0)
+        }
+    }
+}
+
 /// Prepare for a load; factors out common functionality between load and load_extend operations.
 fn prepare_load<FE: FuncEnvironment + ?Sized>(
     memarg: &MemArg,
@@ -2284,12 +2410,13 @@ fn prepare_load<FE: FuncEnvironment + ?Sized>(
     environ: &mut FE,
 ) -> WasmResult<(MemFlags, Value, Offset32)> {
     let addr32 = state.pop1();
+    let (addr32, static_offset) = fold_large_offset(addr32, memarg.offset, builder);
 
     let heap = state.get_heap(builder.func, memarg.memory, environ)?;
     let (base, offset) = get_heap_addr(
         heap,
         addr32,
-        memarg.offset as u32,
+        static_offset,
         loaded_bytes,
         environ.pointer_type(),
         builder,
@@ -2336,12 +2463,13 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
 ) -> WasmResult<()> {
     let (addr32, val) = state.pop2();
     let val_ty = builder.func.dfg.value_type(val);
+    let (addr32, static_offset) = fold_large_offset(addr32, memarg.offset, builder);
 
     let heap = state.get_heap(builder.func, memarg.memory, environ)?;
     let (base, offset) = get_heap_addr(
         heap,
         addr32,
-        memarg.offset as u32,
+        static_offset,
         mem_op_size(opcode, val_ty),
         environ.pointer_type(),
         builder,
@@ -2355,6 +2483,21 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
     Ok(())
 }
 
+/// Computes the sums of the products of adjacent signed bytes of `a` and
+/// `b`, given as `i16x8` vectors. The sums wrap around on overflow, which
+/// can only happen when `b` has bytes outside of the 7-bit range.
+fn translate_i8x16_dot(a: Value, b: Value, builder: &mut FunctionBuilder) -> Value {
+    let a_even = builder.ins().ishl_imm(a, 8);
+    let a_even = builder.ins().sshr_imm(a_even, 8);
+    let a_odd = builder.ins().sshr_imm(a, 8);
+    let b_even = builder.ins().ishl_imm(b, 8);
+    let b_even = builder.ins().sshr_imm(b_even, 8);
+    let b_odd = builder.ins().sshr_imm(b, 8);
+    let even = builder.ins().imul(a_even, b_even);
+    let odd = builder.ins().imul(a_odd, b_odd);
+    builder.ins().iadd(even, odd)
+}
+
 fn mem_op_size(opcode: ir::Opcode, ty: Type) -> u32 {
     match opcode {
         ir::Opcode::Istore8 | ir::Opcode::Sload8 | ir::Opcode::Uload8 => 1,
@@ -2414,8 +2557,19 @@ fn finalise_atomic_mem_addr<FE: FuncEnvironment + ?Sized>(
     environ: &mut FE,
 ) -> WasmResult<Value> {
     let access_ty_bytes = access_ty.bytes();
-    let final_lma = if memarg.offset > 0 {
-        assert!(builder.func.dfg.value_type(linear_mem_addr) == I32);
+    let final_lma = if memarg.offset == 0 {
+        linear_mem_addr
+    } else if builder.func.dfg.value_type(linear_mem_addr) == I64 {
+        // 64-bit memories: the sum must not wrap around.
+        let a = builder
+            .ins()
+            .iadd_imm(linear_mem_addr, memarg.offset as i64);
+        let r = builder
+            .ins()
+            .icmp(IntCC::UnsignedLessThan, a, linear_mem_addr);
+        builder.ins().trapnz(r, ir::TrapCode::HeapOutOfBounds);
+        a
+    } else {
         let linear_mem_addr = builder.ins().uextend(I64, linear_mem_addr);
         let a = builder
             .ins()
@@ -2425,8 +2579,6 @@ fn finalise_atomic_mem_addr<FE: FuncEnvironment + ?Sized>(
             .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, a, 0x1_0000_0000i64);
         builder.ins().trapnz(r, ir::TrapCode::HeapOutOfBounds);
         builder.ins().ireduce(I32, a)
-    } else {
-        linear_mem_addr
     };
     // Check the alignment of `linear_mem_addr`.
     if access_ty_bytes != 1 {
@@ -2675,6 +2827,11 @@ fn translate_br_if(
     let next_block = builder.create_block();
     canonicalise_then_jump(builder, next_block, &[]);
     builder.seal_block(next_block); // The only predecessor is the current block.
+                                    // The destination can be reached from other branches, so only a likely
+                                    // branch is used, to move the fallthrough out of the way.
+    if state.branch_hint == Some(true) {
+        builder.set_cold_block(next_block);
+    }
     builder.switch_to_block(next_block);
 }
 
@@ -91,6 +91,12 @@ pub trait FuncEnvironment: TargetEnvironment {
         signature.returns[index].purpose == ir::ArgumentPurpose::Normal
     }
 
+    /// Should relaxed SIMD instructions be lowered so that they give the
+    /// same results on every architecture?
+    fn relaxed_simd_deterministic(&self) -> bool {
+        false
+    }
+
     /// Should the code be structured to use a single `fallthrough_return` instruction at the end
     /// of the function body, rather than `return` instructions as needed? This is used by VMs
     /// to append custom epilogues.
@@ -228,6 +228,9 @@ pub struct FuncTranslationState {
     /// Is the current translation state still reachable? This is false when translating operators
     /// like End, Return, or Unreachable.
     pub(crate) reachable: bool,
+    /// Whether the branch of the operator being translated is likely
+    /// taken, according to the branch hints of the module.
+    pub(crate) branch_hint: Option<bool>,
 
     // Map of global variables that have already been created by `FuncEnvironment::make_global`.
     globals: HashMap<GlobalIndex, GlobalVariable>,
@@ -268,6 +271,7 @@ impl FuncTranslationState {
             //metadata_stack: Vec::new(),
             control_stack: Vec::new(),
             reachable: true,
+            branch_hint: None,
             globals: HashMap::new(),
             heaps: HashMap::new(),
             tables: HashMap::new(),
@@ -280,6 +284,7 @@ impl FuncTranslationState {
         debug_assert!(self.stack.is_empty());
         debug_assert!(self.control_stack.is_empty());
         self.reachable = true;
+        self.branch_hint = None;
         self.globals.clear();
         self.heaps.clear();
         self.tables.clear();
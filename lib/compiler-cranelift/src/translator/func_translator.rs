@@ -10,14 +10,19 @@
 use super::code_translator::{bitcast_arguments, translate_operator, wasm_param_types};
 use super::func_environ::{FuncEnvironment, ReturnMode};
 use super::func_state::FuncTranslationState;
-use super::translation_utils::get_vmctx_value_label;
+use super::inline::{remap_locals, InlineCandidates, InlinedFunction};
+use super::translation_utils::{get_vmctx_value_label, type_to_irtype};
 use cranelift_codegen::entity::EntityRef;
 use cranelift_codegen::ir::{self, Block, InstBuilder, ValueLabel};
 use cranelift_codegen::timing;
 use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
 use wasmer_compiler::wasmparser;
-use wasmer_compiler::{wptype_to_type, FunctionBinaryReader, ModuleTranslationState};
-use wasmer_types::{LocalFunctionIndex, WasmResult};
+use wasmer_compiler::{
+    wptype_to_type, FunctionBinaryReader, FunctionBranchHints, ModuleTranslationState,
+};
+use wasmer_types::{
+    BranchProfile, FunctionExecutionProfile, FunctionIndex, LocalFunctionIndex, WasmResult,
+};
 
 /// WebAssembly to Cranelift IR function translator.
 ///
@@ -56,6 +61,12 @@ impl FuncTranslator {
     /// regarded as WebAssembly local variables. Any signature arguments marked as
     /// `ArgumentPurpose::Normal` are made accessible as WebAssembly local variables.
     ///
+    /// The `branch_hints` of the function, if any, are used to move the unlikely side of hinted
+    /// branches out of the hot path. The execution `profile` of the function, if any, is used the
+    /// same way for the branches that don't have a hint.
+    ///
+    /// The direct calls to the functions of `inline`, if any, are replaced by their body.
+    ///
     pub fn translate<FE: FuncEnvironment + ?Sized>(
         &mut self,
         module_translation_state: &ModuleTranslationState,
@@ -63,9 +74,20 @@ impl FuncTranslator {
         func: &mut ir::Function,
         environ: &mut FE,
         local_function_index: LocalFunctionIndex,
+        branch_hints: Option<&FunctionBranchHints>,
+        profile: Option<&FunctionExecutionProfile>,
+        inline: Option<&InlineCandidates>,
     ) -> WasmResult<()> {
         environ.push_params_on_stack(local_function_index);
-        self.translate_from_reader(module_translation_state, reader, func, environ)
+        self.translate_from_reader(
+            module_translation_state,
+            reader,
+            func,
+            environ,
+            branch_hints,
+            profile,
+            inline,
+        )
     }
 
     /// Translate a binary WebAssembly function from a `FunctionBinaryReader`.
@@ -75,6 +97,9 @@ impl FuncTranslator {
         reader: &mut dyn FunctionBinaryReader,
         func: &mut ir::Function,
         environ: &mut FE,
+        branch_hints: Option<&FunctionBranchHints>,
+        profile: Option<&FunctionExecutionProfile>,
+        inline: Option<&InlineCandidates>,
     ) -> WasmResult<()> {
         let _tt = timing::wasm_translate_function();
         tracing::trace!(
@@ -85,6 +110,9 @@ impl FuncTranslator {
         );
         debug_assert_eq!(func.dfg.num_blocks(), 0, "Function must be empty");
         debug_assert_eq!(func.dfg.num_insts(), 0, "Function must be empty");
+        // Branch hint offsets are relative to the start of the body, which is where
+        // the local declarations begin.
+        let body_offset = reader.original_position();
 
         // This clears the `FunctionBuilderContext`.
         let mut builder = FunctionBuilder::new(func, &mut self.func_ctx);
@@ -106,13 +134,17 @@ impl FuncTranslator {
         builder.append_block_params_for_function_returns(exit_block);
         self.state.initialize(&builder.func.signature, exit_block);
 
-        parse_local_decls(reader, &mut builder, num_params, environ)?;
+        let num_locals = parse_local_decls(reader, &mut builder, num_params, environ)?;
         parse_function_body(
             module_translation_state,
             reader,
             &mut builder,
             &mut self.state,
             environ,
+            branch_hints.map(|hints| (hints, body_offset)),
+            profile,
+            inline,
+            num_locals,
         )?;
 
         builder.finalize();
@@ -155,12 +187,14 @@ fn declare_wasm_parameters<FE: FuncEnvironment + ?Sized>(
 /// Parse the local variable declarations that precede the function body.
 ///
 /// Declare local variables, starting from `num_params`.
+///
+/// Return the number of local variables declared, including the parameters.
 fn parse_local_decls<FE: FuncEnvironment + ?Sized>(
     reader: &mut dyn FunctionBinaryReader,
     builder: &mut FunctionBuilder,
     num_params: usize,
     environ: &mut FE,
-) -> WasmResult<()> {
+) -> WasmResult<usize> {
     let mut next_local = num_params;
     let local_count = reader.read_local_count()?;
 
@@ -170,7 +204,7 @@ fn parse_local_decls<FE: FuncEnvironment + ?Sized>(
         declare_locals(builder, count, ty, &mut next_local, environ)?;
     }
 
-    Ok(())
+    Ok(next_local)
 }
 
 /// Declare `count` local variables of the same type, starting from `next_local`.
@@ -214,23 +248,80 @@ fn declare_locals<FE: FuncEnvironment + ?Sized>(
 /// Parse the function body in `reader`.
 ///
 /// This assumes that the local variable declarations have already been parsed and function
-/// arguments and locals are declared in the builder.
+/// arguments and locals are declared in the builder, as the first `num_locals` variables.
 fn parse_function_body<FE: FuncEnvironment + ?Sized>(
     module_translation_state: &ModuleTranslationState,
     reader: &mut dyn FunctionBinaryReader,
     builder: &mut FunctionBuilder,
     state: &mut FuncTranslationState,
     environ: &mut FE,
+    branch_hints: Option<(&FunctionBranchHints, usize)>,
+    profile: Option<&FunctionExecutionProfile>,
+    inline: Option<&InlineCandidates>,
+    num_locals: usize,
 ) -> WasmResult<()> {
     // The control stack is initialized with a single block representing the whole function.
     debug_assert_eq!(state.control_stack.len(), 1, "State not initialized");
 
+    // Index of the next conditional branch of the function body, as counted
+    // in the execution profile.
+    let mut branch_index = 0;
+
+    // The variables of the inlined functions come after the locals.
+    let mut next_local = num_locals;
+
     // Keep going until the final `End` operator which pops the outermost block.
     while !state.control_stack.is_empty() {
         builder.set_srcloc(cur_srcloc(reader));
+        let offset = reader.original_position();
         let op = reader.read_operator()?;
+        state.branch_hint = match branch_hints {
+            // Operators inserted by middlewares don't advance the reader, and
+            // must not pick up the hint of the operator they were inserted for.
+            Some((hints, body_offset)) if reader.original_position() != offset => {
+                hints.get((offset - body_offset) as u32)
+            }
+            _ => None,
+        };
+        // The profile only counts the branches of the original body, not
+        // the ones inserted by middlewares.
+        if reader.original_position() != offset
+            && matches!(
+                op,
+                wasmparser::Operator::If { .. } | wasmparser::Operator::BrIf { .. }
+            )
+        {
+            if state.branch_hint.is_none() {
+                state.branch_hint = profile
+                    .and_then(|profile| profile.branches.get(branch_index))
+                    .and_then(profile_hint);
+            }
+            branch_index += 1;
+        }
+        if let wasmparser::Operator::Call { function_index } = op {
+            if let Some(function) =
+                inline.and_then(|inline| inline.get(FunctionIndex::from_u32(function_index)))
+            {
+                translate_inlined_function(
+                    module_translation_state,
+                    function,
+                    builder,
+                    state,
+                    environ,
+                    &mut next_local,
+                )?;
+                continue;
+            }
+        }
         environ.before_translate_operator(&op, builder, state)?;
-        translate_operator(module_translation_state, &op, builder, state, environ)?;
+        match reader.trap_code() {
+            // A trap raised by a middleware, with its own code.
+            Some(trap_code) if state.reachable => {
+                builder.ins().trap(ir::TrapCode::User(trap_code as u16));
+                state.reachable = false;
+            }
+            _ => translate_operator(module_translation_state, &op, builder, state, environ)?,
+        }
         environ.after_translate_operator(&op, builder, state)?;
     }
 
@@ -264,6 +355,68 @@ fn parse_function_body<FE: FuncEnvironment + ?Sized>(
     Ok(())
 }
 
+/// A side of a branch is cold when it runs at most once every
+/// `COLD_BRANCH_RATIO` times the other side runs.
+const COLD_BRANCH_RATIO: u64 = 16;
+
+/// Turns the profile of a branch into a hint: whether the branch is
+/// likely taken, or `None` if neither side is cold.
+fn profile_hint(branch: &BranchProfile) -> Option<bool> {
+    if branch.taken + branch.not_taken == 0 {
+        None
+    } else if branch.not_taken.saturating_mul(COLD_BRANCH_RATIO) <= branch.taken {
+        Some(true)
+    } else if branch.taken.saturating_mul(COLD_BRANCH_RATIO) <= branch.not_taken {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Translate the body of `function` in place of a call to it.
+///
+/// The parameters and locals of the function are declared as new local variables, starting
+/// from `next_local`, and the arguments of the call are popped into the parameters.
+fn translate_inlined_function<FE: FuncEnvironment + ?Sized>(
+    module_translation_state: &ModuleTranslationState,
+    function: InlinedFunction,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+    next_local: &mut usize,
+) -> WasmResult<()> {
+    let base = *next_local;
+    for param in function.params.iter() {
+        let local = Variable::new(*next_local);
+        builder.declare_var(local, type_to_irtype(*param, environ.target_config())?);
+        environ.push_local_decl_on_stack(*param);
+        *next_local += 1;
+    }
+    // The last argument is on the top of the stack.
+    for index in (base..*next_local).rev() {
+        let op = wasmparser::Operator::LocalSet {
+            local_index: index as u32,
+        };
+        translate_operator(module_translation_state, &op, builder, state, environ)?;
+    }
+    for &(count, ty) in function.locals.iter() {
+        declare_locals(builder, count, ty, next_local, environ)?;
+    }
+
+    let mut body = function.body;
+    loop {
+        let op = body.read_operator()?;
+        if body.eof() {
+            break;
+        }
+        let op = remap_locals(op, base as u32);
+        environ.before_translate_operator(&op, builder, state)?;
+        translate_operator(module_translation_state, &op, builder, state, environ)?;
+        environ.after_translate_operator(&op, builder, state)?;
+    }
+    Ok(())
+}
+
 /// Get the current source location from a reader.
 fn cur_srcloc(reader: &dyn FunctionBinaryReader) -> ir::SourceLoc {
     // We record source locations as byte code offsets relative to the beginning of the file.
@@ -0,0 +1,149 @@
+//! Inlining of small functions at their direct call sites.
+//!
+//! Only straight-line functions are inlined: functions without control
+//! flow or calls. At a call site, the parameters and locals of such a
+//! function get fresh variables of the caller, the arguments are moved
+//! from the value stack to the parameters, and the body is translated in
+//! place of the `call` with its local indices remapped to these variables.
+
+use std::collections::HashMap;
+use wasmer_compiler::wasmparser::{Operator, ValType};
+use wasmer_compiler::{FunctionBinaryReader, FunctionBodyData, MiddlewareBinaryReader};
+use wasmer_types::entity::PrimaryMap;
+use wasmer_types::{FunctionIndex, LocalFunctionIndex, ModuleInfo, Type, WasmResult};
+
+/// A function that can be inlined.
+struct Candidate<'data> {
+    /// The operators of the body, after the local declarations.
+    body: &'data [u8],
+    /// The offset of `body` in the module.
+    offset: usize,
+    /// The types of the parameters.
+    params: Box<[Type]>,
+    /// The declarations of the locals, after the parameters.
+    locals: Box<[(u32, ValType)]>,
+}
+
+/// A function to inline at a call site.
+pub struct InlinedFunction<'a, 'data> {
+    /// A reader over the body of the function. The last operator of the
+    /// body is the `end` of the function.
+    pub body: MiddlewareBinaryReader<'data>,
+    /// The types of the parameters.
+    pub params: &'a [Type],
+    /// The declarations of the locals, after the parameters.
+    pub locals: &'a [(u32, ValType)],
+}
+
+/// The functions of a module that can be inlined.
+pub struct InlineCandidates<'data> {
+    candidates: HashMap<FunctionIndex, Candidate<'data>>,
+}
+
+impl<'data> InlineCandidates<'data> {
+    /// Finds the local functions of `module` that can be inlined and
+    /// whose body has at most `budget` operators.
+    pub fn new(
+        module: &ModuleInfo,
+        function_body_inputs: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+        budget: usize,
+    ) -> WasmResult<Self> {
+        let mut candidates = HashMap::new();
+        for (local_index, input) in function_body_inputs.iter() {
+            if let Some((start, locals)) = inlinable_body(input, budget)? {
+                let func_index = module.func_index(local_index);
+                let params = module.signatures[module.functions[func_index]].params();
+                candidates.insert(
+                    func_index,
+                    Candidate {
+                        body: &input.data[start - input.module_offset..],
+                        offset: start,
+                        params: params.into(),
+                        locals: locals.into(),
+                    },
+                );
+            }
+        }
+        Ok(Self { candidates })
+    }
+
+    /// The function `index` to inline, if it can be inlined.
+    pub fn get(&self, index: FunctionIndex) -> Option<InlinedFunction<'_, 'data>> {
+        self.candidates
+            .get(&index)
+            .map(|candidate| InlinedFunction {
+                body: MiddlewareBinaryReader::new_with_offset(candidate.body, candidate.offset),
+                params: &candidate.params,
+                locals: &candidate.locals,
+            })
+    }
+}
+
+/// Returns the position of the first operator and the local declarations,
+/// if the function can be inlined.
+fn inlinable_body(
+    input: &FunctionBodyData,
+    budget: usize,
+) -> WasmResult<Option<(usize, Vec<(u32, ValType)>)>> {
+    let mut reader = MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
+    let mut locals = Vec::new();
+    for _ in 0..reader.read_local_count()? {
+        locals.push(reader.read_local_decl()?);
+    }
+
+    let start = reader.original_position();
+    let mut size = 0;
+    loop {
+        let operator = reader.read_operator()?;
+        if let Operator::End = operator {
+            return Ok(Some((start, locals)).filter(|_| reader.eof()));
+        }
+        size += 1;
+        if size > budget || !is_straight_line(&operator) {
+            return Ok(None);
+        }
+    }
+}
+
+/// Returns `operator` with its local index, if any, moved by `base`.
+pub fn remap_locals<'a>(operator: Operator<'a>, base: u32) -> Operator<'a> {
+    match operator {
+        Operator::LocalGet { local_index } => Operator::LocalGet {
+            local_index: local_index + base,
+        },
+        Operator::LocalSet { local_index } => Operator::LocalSet {
+            local_index: local_index + base,
+        },
+        Operator::LocalTee { local_index } => Operator::LocalTee {
+            local_index: local_index + base,
+        },
+        operator => operator,
+    }
+}
+
+/// Whether `operator` can be part of an inlined body.
+fn is_straight_line(operator: &Operator) -> bool {
+    !matches!(
+        operator,
+        Operator::Unreachable
+            | Operator::Block { .. }
+            | Operator::Loop { .. }
+            | Operator::If { .. }
+            | Operator::Else
+            | Operator::Try { .. }
+            | Operator::Catch { .. }
+            | Operator::CatchAll
+            | Operator::Throw { .. }
+            | Operator::Rethrow { .. }
+            | Operator::Delegate { .. }
+            | Operator::End
+            | Operator::Br { .. }
+            | Operator::BrIf { .. }
+            | Operator::BrTable { .. }
+            | Operator::Return
+            | Operator::Call { .. }
+            | Operator::CallIndirect { .. }
+            | Operator::ReturnCall { .. }
+            | Operator::ReturnCallIndirect { .. }
+    )
+}
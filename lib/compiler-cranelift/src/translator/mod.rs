@@ -4,12 +4,14 @@ mod code_translator;
 mod func_environ;
 mod func_state;
 mod func_translator;
+mod inline;
 mod translation_utils;
 mod unwind;
 
 pub use self::func_environ::{FuncEnvironment, GlobalVariable, ReturnMode, TargetEnvironment};
 pub use self::func_state::FuncTranslationState;
 pub use self::func_translator::FuncTranslator;
+pub(crate) use self::inline::InlineCandidates;
 pub use self::translation_utils::{
     get_vmctx_value_label, irlibcall_to_libcall, irreloc_to_relocationkind,
     signature_to_cranelift_ir, type_to_irtype,
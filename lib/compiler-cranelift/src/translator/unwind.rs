@@ -5,6 +5,14 @@
 use cranelift_codegen::isa::unwind::{systemv::UnwindInfo as DwarfFDE, UnwindInfo};
 use cranelift_codegen::print_errors::pretty_error;
 use cranelift_codegen::{isa, Context};
+#[cfg(feature = "unwind")]
+use gimli::read::{BaseAddresses, CallFrameInstruction, CieOrFde, UnwindSection};
+#[cfg(feature = "unwind")]
+use gimli::write::{Address, EhFrame, EndianVec, FrameTable};
+#[cfg(feature = "unwind")]
+use gimli::{LittleEndian, RunTimeEndian};
+#[cfg(feature = "unwind")]
+use target_lexicon::{Architecture, Environment, OperatingSystem};
 use wasmer_types::CompileError;
 use wasmer_types::CompiledFunctionUnwindInfo;
 
@@ -13,6 +21,9 @@ pub(crate) enum CraneliftUnwindInfo {
     #[cfg(feature = "unwind")]
     /// Windows Unwind info
     WindowsX64(Vec<u8>),
+    #[cfg(feature = "unwind")]
+    /// Windows ARM64 unwind info
+    WindowsArm64(Vec<u8>),
     /// Dwarf FDE
     #[cfg(feature = "unwind")]
     Fde(DwarfFDE),
@@ -31,6 +42,10 @@ impl CraneliftUnwindInfo {
             Self::WindowsX64(unwind_info) => {
                 Some(CompiledFunctionUnwindInfo::WindowsX64(unwind_info))
             }
+            #[cfg(feature = "unwind")]
+            Self::WindowsArm64(unwind_info) => {
+                Some(CompiledFunctionUnwindInfo::WindowsArm64(unwind_info))
+            }
             _ => None,
         }
     }
@@ -42,6 +57,12 @@ pub(crate) fn compiled_function_unwind_info(
     isa: &dyn isa::TargetIsa,
     context: &Context,
 ) -> Result<CraneliftUnwindInfo, CompileError> {
+    if matches!(isa.triple().architecture, Architecture::Aarch64(_))
+        && isa.triple().operating_system == OperatingSystem::Windows
+    {
+        return windows_arm64_unwind_info(isa, context);
+    }
+
     let unwind_info = context
         .compiled_code()
         .unwrap()
@@ -60,6 +81,185 @@ pub(crate) fn compiled_function_unwind_info(
     }
 }
 
+/// The DWARF register numbers of the frame pointer and link register.
+#[cfg(feature = "unwind")]
+const AARCH64_FP: u16 = 29;
+#[cfg(feature = "unwind")]
+const AARCH64_LR: u16 = 30;
+
+/// Constructs the Windows ARM64 unwind info of a function.
+///
+/// Cranelift only describes the AArch64 frames as DWARF CFI, so the CFI is
+/// built for the same code by a System V ISA, and translated to a Windows
+/// `.xdata` record.
+#[cfg(feature = "unwind")]
+fn windows_arm64_unwind_info(
+    isa: &dyn isa::TargetIsa,
+    context: &Context,
+) -> Result<CraneliftUnwindInfo, CompileError> {
+    let mut triple = isa.triple().clone();
+    triple.operating_system = OperatingSystem::Linux;
+    triple.environment = Environment::Gnu;
+    let systemv_isa = isa::lookup(triple)
+        .map_err(|error| CompileError::Codegen(error.to_string()))?
+        .finish(isa.flags().clone())
+        .map_err(|error| CompileError::Codegen(error.to_string()))?;
+
+    let compiled_code = context.compiled_code().unwrap();
+    let unwind_info = compiled_code
+        .create_unwind_info(&*systemv_isa)
+        .map_err(|error| CompileError::Codegen(pretty_error(&context.func, error)))?;
+    let (fde, cie) = match (unwind_info, systemv_isa.create_systemv_cie()) {
+        (Some(UnwindInfo::SystemV(fde)), Some(cie)) => (fde, cie),
+        _ => return Ok(CraneliftUnwindInfo::None),
+    };
+
+    let frame = read_frame(fde, cie)
+        .map_err(|error| CompileError::Codegen(format!("invalid unwind info: {}", error)))?;
+    let function_len = compiled_code.code_info().total_size;
+    Ok(CraneliftUnwindInfo::WindowsArm64(
+        frame.to_xdata(function_len)?,
+    ))
+}
+
+/// The frame of an AArch64 function, once its prologue has run.
+#[cfg(feature = "unwind")]
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Aarch64Frame {
+    /// Whether the function has a frame record, pointed to by the frame
+    /// pointer.
+    has_frame_record: bool,
+    /// The callee-saved registers, with their offset from the CFA.
+    saved_registers: Vec<(u16, i64)>,
+}
+
+/// Reads the frame of a function from its CFI.
+#[cfg(feature = "unwind")]
+fn read_frame(
+    fde: DwarfFDE,
+    cie: gimli::write::CommonInformationEntry,
+) -> Result<Aarch64Frame, String> {
+    let mut table = FrameTable::default();
+    let cie_id = table.add_cie(cie);
+    table.add_fde(cie_id, fde.to_fde(Address::Constant(0)));
+    let mut eh_frame = EhFrame(EndianVec::new(RunTimeEndian::Little));
+    table
+        .write_eh_frame(&mut eh_frame)
+        .map_err(|error| error.to_string())?;
+    let bytes = eh_frame.0.into_vec();
+
+    let eh_frame = gimli::read::EhFrame::new(&bytes, LittleEndian);
+    let bases = BaseAddresses::default();
+    let mut frame = Aarch64Frame::default();
+    let mut entries = eh_frame.entries(&bases);
+    while let Some(entry) = entries.next().map_err(|error| error.to_string())? {
+        let fde = match entry {
+            CieOrFde::Fde(partial) => partial
+                .parse(|eh_frame, bases, offset| eh_frame.cie_from_offset(bases, offset))
+                .map_err(|error| error.to_string())?,
+            CieOrFde::Cie(_) => continue,
+        };
+        let data_alignment_factor = fde.cie().data_alignment_factor();
+        let mut instructions = fde.instructions(&eh_frame, &bases);
+        while let Some(instruction) = instructions.next().map_err(|error| error.to_string())? {
+            match instruction {
+                CallFrameInstruction::DefCfa { register, .. }
+                | CallFrameInstruction::DefCfaRegister { register } => {
+                    frame.has_frame_record = register.0 == AARCH64_FP;
+                }
+                CallFrameInstruction::Offset {
+                    register,
+                    factored_offset,
+                } => {
+                    let offset = factored_offset as i64 * data_alignment_factor;
+                    if register.0 != AARCH64_FP && register.0 != AARCH64_LR {
+                        frame.saved_registers.push((register.0, offset));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(frame)
+}
+
+#[cfg(feature = "unwind")]
+impl Aarch64Frame {
+    /// Encodes the frame as a Windows ARM64 `.xdata` record, without
+    /// epilogue scopes.
+    ///
+    /// The unwind codes describe the frame as it is once the prologue has
+    /// run, which is where the functions trap and call other functions:
+    ///
+    /// ```text
+    /// sub sp, sp, #frame_size       // alloc_s
+    /// stp x29, x30, [sp, #saved]    // save_fplr
+    /// str <register>, [sp, #offset] // save_reg or save_freg, for each
+    /// add x29, sp, #saved           // add_fp
+    /// ```
+    ///
+    /// where the saved registers are right below the frame record, as laid
+    /// out by Cranelift.
+    fn to_xdata(&self, function_len: u32) -> Result<Vec<u8>, CompileError> {
+        let unsupported = |what: &str| {
+            CompileError::Codegen(format!(
+                "unsupported frame for Windows ARM64 unwind info: {}",
+                what
+            ))
+        };
+
+        let mut codes = vec![];
+        if self.has_frame_record {
+            // The size of the saved registers, below the frame record at
+            // CFA - 16.
+            let saved_size = self
+                .saved_registers
+                .iter()
+                .map(|(_, offset)| -offset - 16)
+                .max()
+                .unwrap_or(0);
+            let frame_size = saved_size + 16;
+            if frame_size % 16 != 0 || frame_size / 16 > 0x1f {
+                return Err(unsupported("frame size"));
+            }
+
+            // The codes are listed from the last prologue instruction.
+            codes.push(0xe2);
+            codes.push((saved_size / 8) as u8);
+            for &(register, offset) in self.saved_registers.iter().rev() {
+                let offset = ((frame_size + offset) / 8) as u8;
+                let (first, index) = match register {
+                    // x19 to x28: save_reg
+                    19..=28 => (0xd0, register - 19),
+                    // d8 to d15: save_freg
+                    72..=79 => (0xdc, register - 72),
+                    _ => return Err(unsupported("saved register")),
+                };
+                codes.push(first | (index >> 2) as u8);
+                codes.push(((index & 0x3) << 6) as u8 | offset);
+            }
+            // save_fplr
+            codes.push(0x40 | (saved_size / 8) as u8);
+            // alloc_s
+            codes.push((frame_size / 16) as u8);
+        }
+        // end
+        codes.push(0xe4);
+        while codes.len() % 4 != 0 {
+            codes.push(0xe4);
+        }
+
+        if function_len % 4 != 0 || function_len / 4 >= 1 << 18 {
+            return Err(unsupported("function length"));
+        }
+        let code_words = (codes.len() / 4) as u32;
+        let header = (function_len / 4) | (code_words << 27);
+        let mut xdata = header.to_le_bytes().to_vec();
+        xdata.extend_from_slice(&codes);
+        Ok(xdata)
+    }
+}
+
 #[cfg(not(feature = "unwind"))]
 /// Constructs unwind info object from Cranelift IR
 pub(crate) fn compiled_function_unwind_info(
@@ -68,3 +268,51 @@ pub(crate) fn compiled_function_unwind_info(
 ) -> Result<CraneliftUnwindInfo, CompileError> {
     Ok(CraneliftUnwindInfo::None)
 }
+
+#[cfg(all(test, feature = "unwind"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xdata_of_leaf_function() {
+        let xdata = Aarch64Frame::default().to_xdata(8).unwrap();
+        // Two instructions, one word of codes holding `end`.
+        assert_eq!(xdata, [0x02, 0x00, 0x00, 0x08, 0xe4, 0xe4, 0xe4, 0xe4]);
+    }
+
+    #[test]
+    fn xdata_of_frame_with_saved_registers() {
+        let frame = Aarch64Frame {
+            has_frame_record: true,
+            // stp x19, x20, [sp, #-16]!; str d8, [sp, #-16]!
+            saved_registers: vec![(19, -32), (20, -24), (72, -48)],
+        };
+        let xdata = frame.to_xdata(0x100).unwrap();
+        let header = u32::from_le_bytes([xdata[0], xdata[1], xdata[2], xdata[3]]);
+        assert_eq!(header & 0x3ffff, 0x40);
+        assert_eq!(header >> 27, 3);
+        assert_eq!(
+            &xdata[4..],
+            [
+                // add_fp #32
+                0xe2, 0x04, // save_freg d8, [sp, #0]
+                0xdc, 0x00, // save_reg x20, [sp, #24]
+                0xd0, 0x43, // save_reg x19, [sp, #16]
+                0xd0, 0x02, // save_fplr [sp, #32]
+                0x44, // alloc_s #48
+                0x03, // end, and padding
+                0xe4, 0xe4,
+            ]
+        );
+    }
+
+    #[test]
+    fn xdata_rejects_unsupported_frames() {
+        let frame = Aarch64Frame {
+            has_frame_record: true,
+            saved_registers: vec![(0, -32)],
+        };
+        assert!(frame.to_xdata(0x100).is_err());
+        assert!(Aarch64Frame::default().to_xdata(1 << 20).is_err());
+    }
+}
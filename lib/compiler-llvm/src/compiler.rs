@@ -7,10 +7,11 @@ use inkwell::memory_buffer::MemoryBuffer;
 use inkwell::module::{Linkage, Module};
 use inkwell::targets::FileType;
 use inkwell::DLLStorageClass;
-use rayon::iter::ParallelBridge;
 use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use std::sync::Arc;
-use wasmer_compiler::{Compiler, FunctionBodyData, ModuleMiddleware, ModuleTranslationState};
+use wasmer_compiler::{
+    BranchHints, Compiler, FunctionBodyData, ModuleMiddleware, ModuleTranslationState,
+};
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
     Compilation, CompileError, CompileModuleInfo, CustomSection, CustomSectionProtection, Dwarf,
@@ -87,43 +88,62 @@ impl LLVMCompiler {
     ) -> Result<Vec<u8>, CompileError> {
         let target_machine = self.config().target_machine(target);
         let ctx = Context::create();
+        let branch_hints = BranchHints::from_module(&compile_info.module);
 
-        // TODO: https:/github.com/rayon-rs/rayon/issues/822
-
-        let merged_bitcode = function_body_inputs.into_iter().par_bridge().map_init(
-            || {
-                let target_machine = self.config().target_machine(target);
-                FuncTranslator::new(target_machine)
-            },
-            |func_translator, (i, input)| {
-                let module = func_translator.translate_to_module(
-                    &compile_info.module,
-                    module_translation,
-                    &i,
-                    input,
-                    self.config(),
-                    &compile_info.memory_styles,
-                    &compile_info.table_styles,
-                    symbol_registry,
-                )?;
-                Ok(module.write_bitcode_to_memory().as_slice().to_vec())
-            },
-        );
+        // Every piece is translated to bitcode in parallel. Indexed parallel
+        // iterators (rather than `par_bridge`) keep the pieces in module
+        // order, so that the merged module, and the object file emitted
+        // from it, do not depend on how the work was scheduled.
+        let merged_bitcode = function_body_inputs
+            .iter()
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map_init(
+                || {
+                    let target_machine = self.config().target_machine(target);
+                    FuncTranslator::new(target_machine)
+                },
+                |func_translator, (i, input)| {
+                    let module = func_translator.translate_to_module(
+                        &compile_info.module,
+                        module_translation,
+                        &i,
+                        input,
+                        &branch_hints,
+                        self.config(),
+                        &compile_info.memory_styles,
+                        &compile_info.table_styles,
+                        symbol_registry,
+                    )?;
+                    Ok(module.write_bitcode_to_memory().as_slice().to_vec())
+                },
+            );
 
-        let trampolines_bitcode = compile_info.module.signatures.iter().par_bridge().map_init(
-            || {
-                let target_machine = self.config().target_machine(target);
-                FuncTrampoline::new(target_machine)
-            },
-            |func_trampoline, (i, sig)| {
-                let name = symbol_registry.symbol_to_name(Symbol::FunctionCallTrampoline(i));
-                let module = func_trampoline.trampoline_to_module(sig, self.config(), &name)?;
-                Ok(module.write_bitcode_to_memory().as_slice().to_vec())
-            },
-        );
+        let trampolines_bitcode = compile_info
+            .module
+            .signatures
+            .iter()
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map_init(
+                || {
+                    let target_machine = self.config().target_machine(target);
+                    FuncTrampoline::new(target_machine)
+                },
+                |func_trampoline, (i, sig)| {
+                    let name = symbol_registry.symbol_to_name(Symbol::FunctionCallTrampoline(i));
+                    let module = func_trampoline.trampoline_to_module(sig, self.config(), &name)?;
+                    Ok(module.write_bitcode_to_memory().as_slice().to_vec())
+                },
+            );
 
-        let dynamic_trampolines_bitcode =
-            compile_info.module.functions.iter().par_bridge().map_init(
+        let dynamic_trampolines_bitcode = compile_info
+            .module
+            .functions
+            .iter()
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map_init(
                 || {
                     let target_machine = self.config().target_machine(target);
                     (
@@ -239,6 +259,7 @@ impl Compiler for LLVMCompiler {
         let table_styles = &compile_info.table_styles;
 
         let module = &compile_info.module;
+        let branch_hints = BranchHints::from_module(module);
 
         // TODO: merge constants in sections.
 
@@ -262,6 +283,7 @@ impl Compiler for LLVMCompiler {
                         module_translation,
                         i,
                         input,
+                        &branch_hints,
                         self.config(),
                         memory_styles,
                         table_styles,
@@ -9,7 +9,7 @@ use std::fmt::Debug;
 use std::sync::Arc;
 use target_lexicon::Architecture;
 use wasmer_compiler::{Compiler, CompilerConfig, Engine, EngineBuilder, ModuleMiddleware};
-use wasmer_types::{FunctionType, LocalFunctionIndex, Target, Triple};
+use wasmer_types::{ExecutionProfile, FunctionType, LocalFunctionIndex, Target, Triple};
 
 /// The InkWell ModuleInfo type
 pub type InkwellModule<'ctx> = inkwell::module::Module<'ctx>;
@@ -17,6 +17,9 @@ pub type InkwellModule<'ctx> = inkwell::module::Module<'ctx>;
 /// The InkWell MemoryBuffer type
 pub type InkwellMemoryBuffer = inkwell::memory_buffer::MemoryBuffer;
 
+/// The InkWell PassManager type, running passes on a whole module
+pub type InkwellPassManager<'ctx> = inkwell::passes::PassManager<InkwellModule<'ctx>>;
+
 /// The compiled function kind, used for debugging in the `LLVMCallbacks`.
 #[derive(Debug, Clone)]
 pub enum CompiledKind {
@@ -37,13 +40,37 @@ pub trait LLVMCallbacks: Debug + Send + Sync {
     fn obj_memory_buffer(&self, function: &CompiledKind, memory_buffer: &InkwellMemoryBuffer);
 }
 
+/// Customizes the optimization pipeline run on each compiled function
+/// and trampoline.
+///
+/// Each of them is compiled in its own LLVM module, so module passes only
+/// see one function. The verifier pass, when enabled, always runs first.
+pub trait LLVMPassPipeline: Debug + Send + Sync {
+    /// Whether to run the default passes of Wasmer before the custom ones.
+    ///
+    /// Returning `false` gives the pipeline full control over the
+    /// optimization of `function`.
+    fn default_passes(&self, _function: &CompiledKind) -> bool {
+        true
+    }
+
+    /// Adds custom passes to the pipeline of `function`.
+    fn add_passes(&self, function: &CompiledKind, pass_manager: &InkwellPassManager);
+}
+
 #[derive(Debug, Clone)]
 pub struct LLVM {
     pub(crate) enable_nan_canonicalization: bool,
+    pub(crate) relaxed_simd_deterministic: bool,
     pub(crate) enable_verifier: bool,
     pub(crate) opt_level: LLVMOptLevel,
+    pub(crate) optimize_for_size: bool,
     is_pic: bool,
     pub(crate) callbacks: Option<Arc<dyn LLVMCallbacks>>,
+    pub(crate) pass_pipeline: Option<Arc<dyn LLVMPassPipeline>>,
+    target_cpu: Option<String>,
+    target_features: Option<String>,
+    pub(crate) profile: Option<Arc<ExecutionProfile>>,
     /// The middleware chain.
     pub(crate) middlewares: Vec<Arc<dyn ModuleMiddleware>>,
 }
@@ -54,10 +81,16 @@ impl LLVM {
     pub fn new() -> Self {
         Self {
             enable_nan_canonicalization: false,
+            relaxed_simd_deterministic: false,
             enable_verifier: false,
             opt_level: LLVMOptLevel::Aggressive,
+            optimize_for_size: false,
             is_pic: false,
             callbacks: None,
+            pass_pipeline: None,
+            target_cpu: None,
+            target_features: None,
+            profile: None,
             middlewares: vec![],
         }
     }
@@ -68,6 +101,26 @@ impl LLVM {
         self
     }
 
+    /// Optimize for code size rather than speed.
+    ///
+    /// Functions are marked `optsize` and `minsize`, and the passes that
+    /// duplicate code, such as loop unswitching and vectorization, are
+    /// skipped. The `opt_level` still applies to the other passes.
+    pub fn optimize_for_size(&mut self, enable: bool) -> &mut Self {
+        self.optimize_for_size = enable;
+        self
+    }
+
+    /// Lower the relaxed SIMD instructions deterministically.
+    ///
+    /// By default, relaxed SIMD instructions use the fastest lowering
+    /// available: for instance, multiplications and additions are fused
+    /// when the target supports it.
+    pub fn relaxed_simd_deterministic(&mut self, enable: bool) -> &mut Self {
+        self.relaxed_simd_deterministic = enable;
+        self
+    }
+
     /// Callbacks that will triggered in the different compilation
     /// phases in LLVM.
     pub fn callbacks(&mut self, callbacks: Option<Arc<dyn LLVMCallbacks>>) -> &mut Self {
@@ -75,6 +128,66 @@ impl LLVM {
         self
     }
 
+    /// A custom optimization pipeline, replacing or extending the default
+    /// passes.
+    pub fn pass_pipeline(&mut self, pass_pipeline: Option<Arc<dyn LLVMPassPipeline>>) -> &mut Self {
+        self.pass_pipeline = pass_pipeline;
+        self
+    }
+
+    /// The CPU to generate code for, such as `skylake` or `neoverse-n1`,
+    /// instead of a generic CPU of the target architecture.
+    ///
+    /// The code is scheduled for this CPU, and may use all of its
+    /// features. The artifact can then only be run on this CPU or a newer
+    /// one: unlike the features of the [`Target`], the CPU isn't checked
+    /// when the artifact is loaded.
+    pub fn target_cpu(&mut self, cpu: Option<String>) -> &mut Self {
+        self.target_cpu = cpu;
+        self
+    }
+
+    /// The CPU features to generate code for, as an LLVM feature string
+    /// such as `+avx2,+fma,-avx512f`, instead of the features of the
+    /// [`Target`].
+    ///
+    /// Like [`LLVM::target_cpu`], the features aren't checked when the
+    /// artifact is loaded.
+    pub fn target_features(&mut self, features: Option<String>) -> &mut Self {
+        self.target_features = features;
+        self
+    }
+
+    /// Whether to run the default passes on `function`.
+    pub(crate) fn default_passes(&self, function: &CompiledKind) -> bool {
+        self.pass_pipeline
+            .as_ref()
+            .map_or(true, |pipeline| pipeline.default_passes(function))
+    }
+
+    /// Adds the custom passes of `function`, if any.
+    pub(crate) fn add_custom_passes(
+        &self,
+        function: &CompiledKind,
+        pass_manager: &InkwellPassManager,
+    ) {
+        if let Some(ref pipeline) = self.pass_pipeline {
+            pipeline.add_passes(function, pass_manager);
+        }
+    }
+
+    /// An execution profile of the module, recorded by a previous run
+    /// of an instrumented build (see the `pgo` middleware in
+    /// `wasmer-middlewares`), used to guide the optimizations.
+    ///
+    /// The profile must have been recorded for the module being
+    /// compiled. Functions whose conditional branches don't match the
+    /// profile are compiled as if there were no profile.
+    pub fn profile(&mut self, profile: Option<Arc<ExecutionProfile>>) -> &mut Self {
+        self.profile = profile;
+        self
+    }
+
     fn reloc_mode(&self) -> RelocMode {
         if self.is_pic {
             RelocMode::PIC
@@ -195,13 +308,15 @@ impl LLVM {
         let llvm_target_machine = llvm_target
             .create_target_machine(
                 &target_triple,
-                match triple.architecture {
-                    Architecture::Riscv64(_) => "generic-rv64",
-                    _ => "generic",
+                match (&self.target_cpu, triple.architecture) {
+                    (Some(cpu), _) => cpu.as_str(),
+                    (None, Architecture::Riscv64(_)) => "generic-rv64",
+                    (None, _) => "generic",
                 },
-                match triple.architecture {
-                    Architecture::Riscv64(_) => "+m,+a,+c,+d,+f",
-                    _ => &llvm_cpu_features,
+                match (&self.target_features, triple.architecture) {
+                    (Some(features), _) => features.as_str(),
+                    (None, Architecture::Riscv64(_)) => "+m,+a,+c,+d,+f",
+                    (None, _) => &llvm_cpu_features,
                 },
                 self.opt_level,
                 self.reloc_mode(),
@@ -267,6 +382,14 @@ impl CompilerConfig for LLVM {
         self.enable_nan_canonicalization = enable;
     }
 
+    fn relaxed_simd_deterministic(&mut self, enable: bool) {
+        self.relaxed_simd_deterministic = enable;
+    }
+
+    fn optimize_for_size(&mut self, enable: bool) {
+        self.optimize_for_size = enable;
+    }
+
     /// Transform it into the compiler.
     fn compiler(self: Box<Self>) -> Box<dyn Compiler> {
         Box::new(LLVMCompiler::new(*self))
@@ -22,5 +22,6 @@ mod translator;
 
 pub use crate::compiler::LLVMCompiler;
 pub use crate::config::{
-    CompiledKind, InkwellMemoryBuffer, InkwellModule, LLVMCallbacks, LLVMOptLevel, LLVM,
+    CompiledKind, InkwellMemoryBuffer, InkwellModule, InkwellPassManager, LLVMCallbacks,
+    LLVMOptLevel, LLVMPassPipeline, LLVM,
 };
@@ -120,6 +120,10 @@ where
         "wasmer_vm_imported_memory32_atomic_notify".to_string(),
         LibCall::ImportedMemory32AtomicNotify,
     );
+    libcalls.insert(
+        "wasmer_vm_memory32_copy_between".to_string(),
+        LibCall::Memory32CopyBetween,
+    );
 
     let elf = object::File::parse(contents).map_err(map_object_err)?;
 
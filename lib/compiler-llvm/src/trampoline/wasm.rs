@@ -88,7 +88,10 @@ impl FuncTrampoline {
             pass_manager.add_verifier_pass();
         }
 
-        pass_manager.add_early_cse_pass();
+        if config.default_passes(&function) {
+            pass_manager.add_early_cse_pass();
+        }
+        config.add_custom_passes(&function, &pass_manager);
 
         pass_manager.run_on(&module);
 
@@ -208,7 +211,10 @@ impl FuncTrampoline {
             pass_manager.add_verifier_pass();
         }
 
-        pass_manager.add_early_cse_pass();
+        if config.default_passes(&function) {
+            pass_manager.add_early_cse_pass();
+        }
+        config.add_custom_passes(&function, &pass_manager);
 
         pass_manager.run_on(&module);
 
@@ -6,7 +6,7 @@ use super::{
     state::{ControlFrame, ExtraInfo, IfElseState, State},
 };
 use inkwell::{
-    attributes::AttributeLoc,
+    attributes::{Attribute, AttributeLoc},
     builder::Builder,
     context::Context,
     module::{Linkage, Module},
@@ -27,13 +27,15 @@ use crate::object_file::{load_object_file, CompiledFunction};
 use std::convert::TryFrom;
 use wasmer_compiler::wasmparser::{MemArg, Operator};
 use wasmer_compiler::{
-    from_binaryreadererror_wasmerror, wptype_to_type, FunctionBinaryReader, FunctionBodyData,
-    MiddlewareBinaryReader, ModuleMiddlewareChain, ModuleTranslationState,
+    from_binaryreadererror_wasmerror, wptype_to_type, BranchHints, FunctionBinaryReader,
+    FunctionBodyData, FunctionBranchHints, MiddlewareBinaryReader, ModuleMiddlewareChain,
+    ModuleTranslationState,
 };
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::{
     CompileError, FunctionIndex, FunctionType, GlobalIndex, LocalFunctionIndex, MemoryIndex,
-    ModuleInfo, RelocationTarget, SignatureIndex, Symbol, SymbolRegistry, TableIndex, Type,
+    ModuleInfo, RelocationTarget, SignatureIndex, Symbol, SymbolRegistry, TableIndex, TrapCode,
+    Type, WasmError,
 };
 use wasmer_vm::{MemoryStyle, TableStyle, VMOffsets};
 
@@ -66,6 +68,7 @@ impl FuncTranslator {
         module_translation: &ModuleTranslationState,
         local_func_index: &LocalFunctionIndex,
         function_body: &FunctionBodyData,
+        branch_hints: &BranchHints,
         config: &LLVM,
         memory_styles: &PrimaryMap<MemoryIndex, MemoryStyle>,
         _table_styles: &PrimaryMap<TableIndex, TableStyle>,
@@ -105,6 +108,17 @@ impl FuncTranslator {
         }
 
         func.add_attribute(AttributeLoc::Function, intrinsics.stack_probe);
+        if config.optimize_for_size {
+            // `minsize` also lets the backend outline repeated instruction
+            // sequences on the targets that support it.
+            for name in ["optsize", "minsize"] {
+                func.add_attribute(
+                    AttributeLoc::Function,
+                    self.ctx
+                        .create_enum_attribute(Attribute::get_named_enum_kind_id(name), 0),
+                );
+            }
+        }
         func.set_personality_function(intrinsics.personality);
         func.as_global_value().set_section(Some(FUNCTION_SECTION));
         func.set_linkage(Linkage::DLLExport);
@@ -205,6 +219,10 @@ impl FuncTranslator {
             symbol_registry,
             abi: &*self.abi,
             config,
+            conditional_branches: vec![],
+            branch_hints: branch_hints.function(func_index),
+            branch_hint: None,
+            trap_code: None,
         };
         fcg.ctx.add_func(
             func_index,
@@ -216,10 +234,19 @@ impl FuncTranslator {
         while fcg.state.has_control_frames() {
             let pos = reader.current_position() as u32;
             let op = reader.read_operator()?;
+            fcg.branch_hint = match fcg.branch_hints {
+                // Operators inserted by middlewares don't advance the reader,
+                // and must not pick up the hint of the operator they were
+                // inserted for.
+                Some(hints) if reader.current_position() as u32 != pos => hints.get(pos),
+                _ => None,
+            };
+            fcg.trap_code = reader.trap_code();
             fcg.translate_operator(op, pos)?;
         }
 
         fcg.finalize(wasm_fn_type)?;
+        fcg.apply_profile(*local_func_index);
 
         if let Some(ref callbacks) = config.callbacks {
             callbacks.preopt_ir(&function, &module);
@@ -231,35 +258,45 @@ impl FuncTranslator {
             pass_manager.add_verifier_pass();
         }
 
-        pass_manager.add_type_based_alias_analysis_pass();
-        pass_manager.add_sccp_pass();
-        pass_manager.add_prune_eh_pass();
-        pass_manager.add_dead_arg_elimination_pass();
-        pass_manager.add_lower_expect_intrinsic_pass();
-        pass_manager.add_scalar_repl_aggregates_pass();
-        pass_manager.add_instruction_combining_pass();
-        pass_manager.add_jump_threading_pass();
-        pass_manager.add_correlated_value_propagation_pass();
-        pass_manager.add_cfg_simplification_pass();
-        pass_manager.add_reassociate_pass();
-        pass_manager.add_loop_rotate_pass();
-        pass_manager.add_loop_unswitch_pass();
-        pass_manager.add_ind_var_simplify_pass();
-        pass_manager.add_licm_pass();
-        pass_manager.add_loop_vectorize_pass();
-        pass_manager.add_instruction_combining_pass();
-        pass_manager.add_sccp_pass();
-        pass_manager.add_reassociate_pass();
-        pass_manager.add_cfg_simplification_pass();
-        pass_manager.add_gvn_pass();
-        pass_manager.add_memcpy_optimize_pass();
-        pass_manager.add_dead_store_elimination_pass();
-        pass_manager.add_bit_tracking_dce_pass();
-        pass_manager.add_instruction_combining_pass();
-        pass_manager.add_reassociate_pass();
-        pass_manager.add_cfg_simplification_pass();
-        pass_manager.add_slp_vectorize_pass();
-        pass_manager.add_early_cse_pass();
+        if config.default_passes(&function) {
+            pass_manager.add_type_based_alias_analysis_pass();
+            pass_manager.add_sccp_pass();
+            pass_manager.add_prune_eh_pass();
+            pass_manager.add_dead_arg_elimination_pass();
+            pass_manager.add_lower_expect_intrinsic_pass();
+            pass_manager.add_scalar_repl_aggregates_pass();
+            pass_manager.add_instruction_combining_pass();
+            pass_manager.add_jump_threading_pass();
+            pass_manager.add_correlated_value_propagation_pass();
+            pass_manager.add_cfg_simplification_pass();
+            pass_manager.add_reassociate_pass();
+            pass_manager.add_loop_rotate_pass();
+            // Unswitching and vectorizing loops duplicate their bodies.
+            if !config.optimize_for_size {
+                pass_manager.add_loop_unswitch_pass();
+            }
+            pass_manager.add_ind_var_simplify_pass();
+            pass_manager.add_licm_pass();
+            if !config.optimize_for_size {
+                pass_manager.add_loop_vectorize_pass();
+            }
+            pass_manager.add_instruction_combining_pass();
+            pass_manager.add_sccp_pass();
+            pass_manager.add_reassociate_pass();
+            pass_manager.add_cfg_simplification_pass();
+            pass_manager.add_gvn_pass();
+            pass_manager.add_memcpy_optimize_pass();
+            pass_manager.add_dead_store_elimination_pass();
+            pass_manager.add_bit_tracking_dce_pass();
+            pass_manager.add_instruction_combining_pass();
+            pass_manager.add_reassociate_pass();
+            pass_manager.add_cfg_simplification_pass();
+            if !config.optimize_for_size {
+                pass_manager.add_slp_vectorize_pass();
+            }
+            pass_manager.add_early_cse_pass();
+        }
+        config.add_custom_passes(&function, &pass_manager);
 
         pass_manager.run_on(&module);
 
@@ -277,6 +314,7 @@ impl FuncTranslator {
         module_translation: &ModuleTranslationState,
         local_func_index: &LocalFunctionIndex,
         function_body: &FunctionBodyData,
+        branch_hints: &BranchHints,
         config: &LLVM,
         memory_styles: &PrimaryMap<MemoryIndex, MemoryStyle>,
         table_styles: &PrimaryMap<TableIndex, TableStyle>,
@@ -287,6 +325,7 @@ impl FuncTranslator {
             module_translation,
             local_func_index,
             function_body,
+            branch_hints,
             config,
             memory_styles,
             table_styles,
@@ -1062,8 +1101,13 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
         let function = &self.function;
 
         // Compute the offset into the storage.
+        let memory64 = self.wasm_module.memories[memory_index].is_memory64();
         let imm_offset = intrinsics.i64_ty.const_int(memarg.offset as u64, false);
-        let var_offset = builder.build_int_z_extend(var_offset, intrinsics.i64_ty, "");
+        let var_offset = if memory64 {
+            var_offset
+        } else {
+            builder.build_int_z_extend(var_offset, intrinsics.i64_ty, "")
+        };
         let offset = builder.build_int_add(var_offset, imm_offset, "");
 
         // Look up the memory base (as pointer) and bounds (as unsigned integer).
@@ -1079,7 +1123,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     // Bounds check it.
                     let minimum = self.wasm_module.memories[memory_index].minimum;
                     let value_size_v = intrinsics.i64_ty.const_int(value_size as u64, false);
-                    let ptr_in_bounds = if offset.is_const() {
+                    let ptr_in_bounds = if offset.is_const() && !memory64 {
                         // When the offset is constant, if it's below the minimum
                         // memory size, we've statically shown that it's safe.
                         let load_offset_end = offset.const_add(value_size_v);
@@ -1110,12 +1154,31 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                         let current_length =
                             builder.build_int_z_extend(current_length, intrinsics.i64_ty, "");
 
-                        builder.build_int_compare(
+                        let ptr_in_bounds = builder.build_int_compare(
                             IntPredicate::ULE,
                             load_offset_end,
                             current_length,
                             "",
-                        )
+                        );
+                        if memory64 {
+                            // With 64-bit addresses, the additions may wrap around.
+                            let no_wrap = builder.build_int_compare(
+                                IntPredicate::ULE,
+                                var_offset,
+                                offset,
+                                "",
+                            );
+                            let ptr_in_bounds = builder.build_and(ptr_in_bounds, no_wrap, "");
+                            let no_wrap = builder.build_int_compare(
+                                IntPredicate::ULE,
+                                offset,
+                                load_offset_end,
+                                "",
+                            );
+                            builder.build_and(ptr_in_bounds, no_wrap, "")
+                        } else {
+                            ptr_in_bounds
+                        }
                     });
                     if !ptr_in_bounds.is_constant_int()
                         || ptr_in_bounds.get_zero_extended_constant().unwrap() != 1
@@ -1166,6 +1229,11 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     );
                     ptr_to_base
                 }
+                MemoryCache::Static { .. } if memory64 => {
+                    return Err(CompileError::Wasm(WasmError::Unsupported(
+                        "64-bit memories with a static memory style".to_string(),
+                    )));
+                }
                 MemoryCache::Static { base_ptr } => base_ptr,
             };
         let value_ptr = unsafe { builder.build_gep(base_ptr, &[offset], "") };
@@ -1174,6 +1242,38 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             .into_pointer_value())
     }
 
+    /// Computes the sums of the products of adjacent signed bytes of `a`
+    /// and `b`, given as `i16x8` vectors. The sums wrap around on
+    /// overflow, which can only happen when `b` has bytes outside of the
+    /// 7-bit range.
+    fn i8x16_dot(&self, a: VectorValue<'ctx>, b: VectorValue<'ctx>) -> VectorValue<'ctx> {
+        let eight = self.intrinsics.i16_ty.const_int(8, false);
+        let eight = self.splat_vector(eight.as_basic_value_enum(), self.intrinsics.i16x8_ty);
+        let halves = |v: VectorValue<'ctx>| {
+            let even = self.builder.build_left_shift(v, eight, "");
+            let even = self.builder.build_right_shift(even, eight, true, "");
+            let odd = self.builder.build_right_shift(v, eight, true, "");
+            (even, odd)
+        };
+        let (a_even, a_odd) = halves(a);
+        let (b_even, b_odd) = halves(b);
+        let even = self.builder.build_int_mul(a_even, b_even, "");
+        let odd = self.builder.build_int_mul(a_odd, b_odd, "");
+        self.builder.build_int_add(even, odd, "")
+    }
+
+    /// Bulk memory operations and atomic waits are only implemented for
+    /// memories indexed with 32-bit addresses.
+    fn check_memory32(&self, memory: u32, op: &Operator) -> Result<(), CompileError> {
+        if self.wasm_module.memories[MemoryIndex::from_u32(memory)].is_memory64() {
+            return Err(CompileError::Wasm(WasmError::Unsupported(format!(
+                "{:?} on 64-bit memories",
+                op
+            ))));
+        }
+        Ok(())
+    }
+
     fn trap_if_misaligned(&self, _memarg: &MemArg, ptr: PointerValue<'ctx>, align: u8) {
         if align <= 1 {
             return;
@@ -1224,6 +1324,79 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
         self.builder.position_at_end(continue_block);
     }
 
+    /// Attaches branch weights to the conditional `branch` of an `if` or
+    /// `br_if` that has a branch hint. An execution profile, if any,
+    /// overrides them later.
+    fn apply_branch_hint(&self, branch: InstructionValue<'ctx>) {
+        let likely = match self.branch_hint {
+            Some(likely) => likely,
+            None => return,
+        };
+        // The same weights `llvm.expect` lowers to.
+        let (taken, not_taken) = if likely { (2000, 1) } else { (1, 2000) };
+        let weights = self.context.metadata_node(&[
+            self.context.metadata_string("branch_weights").into(),
+            self.intrinsics.i32_ty.const_int(taken, false).into(),
+            self.intrinsics.i32_ty.const_int(not_taken, false).into(),
+        ]);
+        branch
+            .set_metadata(weights, self.context.get_kind_id("prof"))
+            .unwrap();
+    }
+
+    /// Attaches the branch weights of the execution profile to the
+    /// conditional branches of the function, and marks the functions that
+    /// were never called as cold.
+    fn apply_profile(&self, local_func_index: LocalFunctionIndex) {
+        let profile = match self.config.profile.as_ref() {
+            Some(profile) => profile,
+            None => return,
+        };
+        let function = match profile.function(local_func_index) {
+            Some(function) => function,
+            None => return,
+        };
+
+        if function.calls == 0 {
+            self.function.add_attribute(
+                AttributeLoc::Function,
+                self.context
+                    .create_enum_attribute(Attribute::get_named_enum_kind_id("cold"), 0),
+            );
+        }
+
+        // The function body differs from the profiled one, for instance
+        // because a middleware added branches: the profile can't be
+        // trusted.
+        if function.branches.len() != self.conditional_branches.len() {
+            return;
+        }
+
+        let prof_kind = self.context.get_kind_id("prof");
+        for (index, branch) in self.conditional_branches.iter().enumerate() {
+            let (branch, counts) = match (branch, profile.branch(local_func_index, index)) {
+                (Some(branch), Some(counts)) => (branch, counts),
+                _ => continue,
+            };
+            // Branch weights are 32-bit, so large counts are scaled down
+            // while keeping their ratio.
+            let max = counts.taken.max(counts.not_taken);
+            let scale = max / u64::from(u32::MAX) + 1;
+            let weight = |count: u64| -> BasicMetadataValueEnum<'ctx> {
+                self.intrinsics
+                    .i32_ty
+                    .const_int(count / scale, false)
+                    .into()
+            };
+            let weights = self.context.metadata_node(&[
+                self.context.metadata_string("branch_weights").into(),
+                weight(counts.taken),
+                weight(counts.not_taken),
+            ]);
+            branch.set_metadata(weights, prof_kind).unwrap();
+        }
+    }
+
     fn finalize(&mut self, wasm_fn_type: &FunctionType) -> Result<(), CompileError> {
         let func_type = self.function.get_type();
 
@@ -1382,6 +1555,20 @@ pub struct LLVMFunctionCodeGenerator<'ctx, 'a> {
     symbol_registry: &'a dyn SymbolRegistry,
     abi: &'a dyn Abi,
     config: &'a LLVM,
+
+    /// The `if` and `br_if` of the function in order, or `None` for the
+    /// ones in unreachable code. Used to apply the execution profile.
+    conditional_branches: Vec<Option<InstructionValue<'ctx>>>,
+
+    /// The branch hints of the function, from the module's
+    /// `metadata.code.branch_hint` section.
+    branch_hints: Option<&'a FunctionBranchHints>,
+    /// Whether the branch of the operator being translated is likely
+    /// taken, according to `branch_hints`.
+    branch_hint: Option<bool>,
+    /// The trap code of the operator being translated, if it is an
+    /// `unreachable` raised by a middleware.
+    trap_code: Option<TrapCode>,
 }
 
 impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
@@ -1394,12 +1581,19 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
 
         if !self.state.reachable {
             match op {
-                Operator::Block { blockty: _ }
-                | Operator::Loop { blockty: _ }
-                | Operator::If { blockty: _ } => {
+                Operator::Block { blockty: _ } | Operator::Loop { blockty: _ } => {
                     self.unreachable_depth += 1;
                     return Ok(());
                 }
+                Operator::If { blockty: _ } => {
+                    self.unreachable_depth += 1;
+                    self.conditional_branches.push(None);
+                    return Ok(());
+                }
+                Operator::BrIf { relative_depth: _ } => {
+                    self.conditional_branches.push(None);
+                    return Ok(());
+                }
                 Operator::Else => {
                     if self.unreachable_depth != 0 {
                         return Ok(());
@@ -1590,8 +1784,11 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     self.intrinsics.i32_zero,
                     "",
                 );
-                self.builder
-                    .build_conditional_branch(cond_value, *frame.br_dest(), else_block);
+                let branch =
+                    self.builder
+                        .build_conditional_branch(cond_value, *frame.br_dest(), else_block);
+                self.apply_branch_hint(branch);
+                self.conditional_branches.push(Some(branch));
                 self.builder.position_at_end(else_block);
             }
             Operator::BrTable { ref targets } => {
@@ -1691,8 +1888,11 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     "",
                 );
 
-                self.builder
-                    .build_conditional_branch(cond_value, if_then_block, if_else_block);
+                let branch =
+                    self.builder
+                        .build_conditional_branch(cond_value, if_then_block, if_else_block);
+                self.apply_branch_hint(branch);
+                self.conditional_branches.push(Some(branch));
                 self.builder.position_at_end(if_else_block);
                 let block_param_types = self
                     .module_translation
@@ -1876,11 +2076,16 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 }
                 */
 
-                self.builder.build_call(
-                    self.intrinsics.throw_trap,
-                    &[self.intrinsics.trap_unreachable.into()],
-                    "throw",
-                );
+                let trap_code = match self.trap_code {
+                    Some(trap_code) => self
+                        .intrinsics
+                        .i32_ty
+                        .const_int(trap_code as _, false)
+                        .as_basic_value_enum(),
+                    None => self.intrinsics.trap_unreachable,
+                };
+                self.builder
+                    .build_call(self.intrinsics.throw_trap, &[trap_code.into()], "throw");
                 self.builder.build_unreachable();
 
                 self.state.reachable = false;
@@ -2075,6 +2280,15 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                             format!("global {}", global_index.as_u32()),
                             value.as_instruction_value().unwrap(),
                         );
+                        if self.wasm_module.interrupt_global == Some(global_index) {
+                            // Another thread sets the interrupt flag, keep the
+                            // check from being hoisted out of loops.
+                            value
+                                .as_instruction_value()
+                                .unwrap()
+                                .set_volatile(true)
+                                .unwrap();
+                        }
                         self.state.push1(value);
                     }
                 }
@@ -10923,7 +11137,26 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
 
             Operator::MemoryGrow { mem, mem_byte: _ } => {
                 let memory_index = MemoryIndex::from_u32(mem);
+                let memory64 = self.wasm_module.memories[memory_index].is_memory64();
                 let delta = self.state.pop1()?;
+                let delta = if memory64 {
+                    // The libcall counts pages with 32 bits: a larger delta
+                    // can't succeed anyway, so it is saturated.
+                    let delta = delta.into_int_value();
+                    let max = self.intrinsics.i64_ty.const_int(u32::MAX.into(), false);
+                    let too_large =
+                        self.builder
+                            .build_int_compare(IntPredicate::UGT, delta, max, "");
+                    let delta = self
+                        .builder
+                        .build_select(too_large, max, delta, "")
+                        .into_int_value();
+                    self.builder
+                        .build_int_truncate(delta, self.intrinsics.i32_ty, "")
+                        .as_basic_value_enum()
+                } else {
+                    delta
+                };
                 let grow_fn_ptr = self.ctx.memory_grow(memory_index, self.intrinsics);
                 let callable_func = inkwell::values::CallableValue::try_from(grow_fn_ptr).unwrap();
                 let grow = self.builder.build_call(
@@ -10935,7 +11168,29 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     ],
                     "",
                 );
-                self.state.push1(grow.try_as_basic_value().left().unwrap());
+                let grow = grow.try_as_basic_value().left().unwrap();
+                if memory64 {
+                    // Failures are reported as `-1`, which must stay `-1`.
+                    let grow = grow.into_int_value();
+                    let failed = self.builder.build_int_compare(
+                        IntPredicate::EQ,
+                        grow,
+                        self.intrinsics.i32_ty.const_all_ones(),
+                        "",
+                    );
+                    let grow = self
+                        .builder
+                        .build_int_z_extend(grow, self.intrinsics.i64_ty, "");
+                    let grow = self.builder.build_select(
+                        failed,
+                        self.intrinsics.i64_ty.const_all_ones(),
+                        grow,
+                        "",
+                    );
+                    self.state.push1(grow);
+                } else {
+                    self.state.push1(grow);
+                }
             }
             Operator::MemorySize { mem, mem_byte: _ } => {
                 let memory_index = MemoryIndex::from_u32(mem);
@@ -10950,9 +11205,20 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     "",
                 );
                 size.add_attribute(AttributeLoc::Function, self.intrinsics.readonly);
-                self.state.push1(size.try_as_basic_value().left().unwrap());
+                let size = size.try_as_basic_value().left().unwrap();
+                if self.wasm_module.memories[memory_index].is_memory64() {
+                    let size = self.builder.build_int_z_extend(
+                        size.into_int_value(),
+                        self.intrinsics.i64_ty,
+                        "",
+                    );
+                    self.state.push1(size);
+                } else {
+                    self.state.push1(size);
+                }
             }
             Operator::MemoryInit { data_index, mem } => {
+                self.check_memory32(mem, &op)?;
                 let (dest, src, len) = self.state.pop3()?;
                 let mem = self.intrinsics.i32_ty.const_int(mem.into(), false);
                 let segment = self.intrinsics.i32_ty.const_int(data_index.into(), false);
@@ -10978,8 +11244,26 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 );
             }
             Operator::MemoryCopy { dst_mem, src_mem } => {
-                // ignored until we support multiple memories
-                let _dst = dst_mem;
+                self.check_memory32(src_mem, &op)?;
+                self.check_memory32(dst_mem, &op)?;
+                if src_mem != dst_mem {
+                    let (dest_pos, src_pos, len) = self.state.pop3()?;
+                    let dst_index = self.intrinsics.i32_ty.const_int(dst_mem.into(), false);
+                    let src_index = self.intrinsics.i32_ty.const_int(src_mem.into(), false);
+                    self.builder.build_call(
+                        self.intrinsics.memory_copy_between,
+                        &[
+                            vmctx.as_basic_value_enum().into(),
+                            dst_index.into(),
+                            src_index.into(),
+                            dest_pos.into(),
+                            src_pos.into(),
+                            len.into(),
+                        ],
+                        "",
+                    );
+                    return Ok(());
+                }
                 let (memory_copy, src) = if let Some(local_memory_index) = self
                     .wasm_module
                     .local_memory_index(MemoryIndex::from_u32(src_mem))
@@ -11004,6 +11288,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 );
             }
             Operator::MemoryFill { mem } => {
+                self.check_memory32(mem, &op)?;
                 let (memory_fill, mem) = if let Some(local_memory_index) = self
                     .wasm_module
                     .local_memory_index(MemoryIndex::from_u32(mem))
@@ -11240,6 +11525,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 self.state.push1(size);
             }
             Operator::MemoryAtomicWait32 { memarg } => {
+                self.check_memory32(memarg.memory, &op)?;
                 let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let (dst, val, timeout) = self.state.pop3()?;
                 let wait32_fn_ptr = self.ctx.memory_wait32(memory_index, self.intrinsics);
@@ -11262,6 +11548,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 self.state.push1(ret.try_as_basic_value().left().unwrap());
             }
             Operator::MemoryAtomicWait64 { memarg } => {
+                self.check_memory32(memarg.memory, &op)?;
                 let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let (dst, val, timeout) = self.state.pop3()?;
                 let wait64_fn_ptr = self.ctx.memory_wait64(memory_index, self.intrinsics);
@@ -11284,6 +11571,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 self.state.push1(ret.try_as_basic_value().left().unwrap());
             }
             Operator::MemoryAtomicNotify { memarg } => {
+                self.check_memory32(memarg.memory, &op)?;
                 let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let (dst, count) = self.state.pop2()?;
                 let notify_fn_ptr = self.ctx.memory_notify(memory_index, self.intrinsics);
@@ -11304,6 +11592,176 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 );
                 self.state.push1(cnt.try_as_basic_value().left().unwrap());
             }
+            Operator::I8x16RelaxedSwizzle
+            | Operator::I32x4RelaxedTruncSatF32x4S
+            | Operator::I32x4RelaxedTruncSatF32x4U
+            | Operator::I32x4RelaxedTruncSatF64x2SZero
+            | Operator::I32x4RelaxedTruncSatF64x2UZero
+            | Operator::I8x16RelaxedLaneselect
+            | Operator::I16x8RelaxedLaneselect
+            | Operator::I32x4RelaxedLaneselect
+            | Operator::I64x2RelaxedLaneselect
+            | Operator::I16x8RelaxedQ15mulrS => {
+                // The strict counterparts of these instructions give
+                // results that the relaxed ones are allowed to give, and
+                // LLVM optimizes them just as well.
+                let strict = match op {
+                    Operator::I8x16RelaxedSwizzle => Operator::I8x16Swizzle,
+                    Operator::I32x4RelaxedTruncSatF32x4S => Operator::I32x4TruncSatF32x4S,
+                    Operator::I32x4RelaxedTruncSatF32x4U => Operator::I32x4TruncSatF32x4U,
+                    Operator::I32x4RelaxedTruncSatF64x2SZero => Operator::I32x4TruncSatF64x2SZero,
+                    Operator::I32x4RelaxedTruncSatF64x2UZero => Operator::I32x4TruncSatF64x2UZero,
+                    Operator::I16x8RelaxedQ15mulrS => Operator::I16x8Q15MulrSatS,
+                    _ => Operator::V128Bitselect,
+                };
+                self.translate_operator(strict, _source_loc)?;
+            }
+            Operator::F32x4RelaxedMin
+            | Operator::F32x4RelaxedMax
+            | Operator::F64x2RelaxedMin
+            | Operator::F64x2RelaxedMax => {
+                let deterministic = self.config.relaxed_simd_deterministic;
+                let strict = match (op, deterministic) {
+                    (Operator::F32x4RelaxedMin, true) => Operator::F32x4Min,
+                    (Operator::F32x4RelaxedMax, true) => Operator::F32x4Max,
+                    (Operator::F64x2RelaxedMin, true) => Operator::F64x2Min,
+                    (Operator::F64x2RelaxedMax, true) => Operator::F64x2Max,
+                    (Operator::F32x4RelaxedMin, false) => Operator::F32x4PMin,
+                    (Operator::F32x4RelaxedMax, false) => Operator::F32x4PMax,
+                    (Operator::F64x2RelaxedMin, false) => Operator::F64x2PMin,
+                    _ => Operator::F64x2PMax,
+                };
+                if !deterministic {
+                    // `pmin(b, a)` is `a < b ? a : b`, which is a single
+                    // `minps` on x86.
+                    let ((v1, i1), (v2, i2)) = self.state.pop2_extra()?;
+                    self.state.push1_extra(v2, i2);
+                    self.state.push1_extra(v1, i1);
+                }
+                self.translate_operator(strict, _source_loc)?;
+            }
+            Operator::F32x4RelaxedFma
+            | Operator::F32x4RelaxedFnma
+            | Operator::F64x2RelaxedFma
+            | Operator::F64x2RelaxedFnma => {
+                let negate = matches!(op, Operator::F32x4RelaxedFnma | Operator::F64x2RelaxedFnma);
+                let f32x4 = matches!(op, Operator::F32x4RelaxedFma | Operator::F32x4RelaxedFnma);
+                let ((v1, i1), (v2, i2), (v3, i3)) = self.state.pop3_extra()?;
+                if self.config.relaxed_simd_deterministic {
+                    // Round the multiplication on its own, as every
+                    // target can.
+                    let (mul, add, sub) = if f32x4 {
+                        (Operator::F32x4Mul, Operator::F32x4Add, Operator::F32x4Sub)
+                    } else {
+                        (Operator::F64x2Mul, Operator::F64x2Add, Operator::F64x2Sub)
+                    };
+                    if negate {
+                        self.state.push1_extra(v3, i3);
+                    }
+                    self.state.push1_extra(v1, i1);
+                    self.state.push1_extra(v2, i2);
+                    self.translate_operator(mul, _source_loc)?;
+                    if negate {
+                        self.translate_operator(sub, _source_loc)?;
+                    } else {
+                        self.state.push1_extra(v3, i3);
+                        self.translate_operator(add, _source_loc)?;
+                    }
+                } else {
+                    // `llvm.fmuladd` fuses the operations only when the
+                    // target has a fast instruction for it.
+                    let (v1, v2, v3, fmuladd, pending_nan) = if f32x4 {
+                        let (v1, _) = self.v128_into_f32x4(v1, i1);
+                        let (v2, _) = self.v128_into_f32x4(v2, i2);
+                        let (v3, _) = self.v128_into_f32x4(v3, i3);
+                        (
+                            v1,
+                            v2,
+                            v3,
+                            self.intrinsics.fmuladd_f32x4,
+                            ExtraInfo::pending_f32_nan(),
+                        )
+                    } else {
+                        let (v1, _) = self.v128_into_f64x2(v1, i1);
+                        let (v2, _) = self.v128_into_f64x2(v2, i2);
+                        let (v3, _) = self.v128_into_f64x2(v3, i3);
+                        (
+                            v1,
+                            v2,
+                            v3,
+                            self.intrinsics.fmuladd_f64x2,
+                            ExtraInfo::pending_f64_nan(),
+                        )
+                    };
+                    let v1 = if negate {
+                        self.builder.build_float_neg(v1, "")
+                    } else {
+                        v1
+                    };
+                    let res = self
+                        .builder
+                        .build_call(fmuladd, &[v1.into(), v2.into(), v3.into()], "")
+                        .try_as_basic_value()
+                        .left()
+                        .unwrap();
+                    let res = self.builder.build_bitcast(res, self.intrinsics.i128_ty, "");
+                    self.state.push1_extra(res, pending_nan);
+                }
+            }
+            Operator::I16x8DotI8x16I7x16S => {
+                let ((v1, i1), (v2, i2)) = self.state.pop2_extra()?;
+                let (v1, _) = self.v128_into_i16x8(v1, i1);
+                let (v2, _) = self.v128_into_i16x8(v2, i2);
+                let res = self.i8x16_dot(v1, v2);
+                let res = self.builder.build_bitcast(res, self.intrinsics.i128_ty, "");
+                self.state.push1(res);
+            }
+            Operator::I32x4DotI8x16I7x16AddS => {
+                let ((v1, i1), (v2, i2), (v3, i3)) = self.state.pop3_extra()?;
+                let (v1, _) = self.v128_into_i16x8(v1, i1);
+                let (v2, _) = self.v128_into_i16x8(v2, i2);
+                let dot = self.i8x16_dot(v1, v2);
+                let dot = self.builder.build_bitcast(dot, self.intrinsics.i128_ty, "");
+                self.state.push1(dot);
+                self.translate_operator(Operator::I32x4ExtAddPairwiseI16x8S, _source_loc)?;
+                self.state.push1_extra(v3, i3);
+                self.translate_operator(Operator::I32x4Add, _source_loc)?;
+            }
+            Operator::F32x4RelaxedDotBf16x8AddF32x4 => {
+                let ((v1, i1), (v2, i2), (v3, i3)) = self.state.pop3_extra()?;
+                let (v1, _) = self.v128_into_i32x4(v1, i1);
+                let (v2, _) = self.v128_into_i32x4(v2, i2);
+                // A bfloat16 is the upper half of a float32.
+                let sixteen = self.intrinsics.i32_ty.const_int(16, false);
+                let sixteen =
+                    self.splat_vector(sixteen.as_basic_value_enum(), self.intrinsics.i32x4_ty);
+                let mask = self.intrinsics.i32_ty.const_int(0xffff_0000, false);
+                let mask = self.splat_vector(mask.as_basic_value_enum(), self.intrinsics.i32x4_ty);
+                for v in [v1, v2] {
+                    let even = self.builder.build_left_shift(v, sixteen, "");
+                    let even = self
+                        .builder
+                        .build_bitcast(even, self.intrinsics.i128_ty, "");
+                    self.state.push1(even);
+                }
+                self.translate_operator(Operator::F32x4Mul, _source_loc)?;
+                for v in [v1, v2] {
+                    let odd = self.builder.build_and(v, mask, "");
+                    let odd = self.builder.build_bitcast(odd, self.intrinsics.i128_ty, "");
+                    self.state.push1(odd);
+                }
+                self.translate_operator(Operator::F32x4Mul, _source_loc)?;
+                self.translate_operator(Operator::F32x4Add, _source_loc)?;
+                self.state.push1_extra(v3, i3);
+                self.translate_operator(Operator::F32x4Add, _source_loc)?;
+            }
+            // Tail calls must not grow the stack, which can't be guaranteed yet
+            Operator::ReturnCall { .. } | Operator::ReturnCallIndirect { .. } => {
+                return Err(CompileError::Wasm(WasmError::Unsupported(format!(
+                    "tail calls are not supported: {:?}",
+                    op
+                ))));
+            }
             _ => {
                 return Err(CompileError::Codegen(format!(
                     "Operator {:?} unimplemented",
@@ -103,6 +103,9 @@ pub struct Intrinsics<'ctx> {
     pub sqrt_f32x4: FunctionValue<'ctx>,
     pub sqrt_f64x2: FunctionValue<'ctx>,
 
+    pub fmuladd_f32x4: FunctionValue<'ctx>,
+    pub fmuladd_f64x2: FunctionValue<'ctx>,
+
     pub cmp_f32: FunctionValue<'ctx>,
     pub cmp_f64: FunctionValue<'ctx>,
     pub cmp_f32x4: FunctionValue<'ctx>,
@@ -238,6 +241,7 @@ pub struct Intrinsics<'ctx> {
     pub elem_drop: FunctionValue<'ctx>,
     pub memory_copy: FunctionValue<'ctx>,
     pub imported_memory_copy: FunctionValue<'ctx>,
+    pub memory_copy_between: FunctionValue<'ctx>,
     pub memory_fill: FunctionValue<'ctx>,
     pub imported_memory_fill: FunctionValue<'ctx>,
     pub memory_wait32: FunctionValue<'ctx>,
@@ -397,6 +401,15 @@ impl<'ctx> Intrinsics<'ctx> {
         let ret_f64x2_take_f64x2_f64x2 =
             f64x2_ty.fn_type(&[f64x2_ty_basic_md, f64x2_ty_basic_md], false);
 
+        let ret_f32x4_take_f32x4_f32x4_f32x4 = f32x4_ty.fn_type(
+            &[f32x4_ty_basic_md, f32x4_ty_basic_md, f32x4_ty_basic_md],
+            false,
+        );
+        let ret_f64x2_take_f64x2_f64x2_f64x2 = f64x2_ty.fn_type(
+            &[f64x2_ty_basic_md, f64x2_ty_basic_md, f64x2_ty_basic_md],
+            false,
+        );
+
         let ret_f64_take_f32_md = f64_ty.fn_type(&[f32_ty_basic_md, md_ty_basic_md], false);
         let ret_f32_take_f64_md_md =
             f32_ty.fn_type(&[f64_ty_basic_md, md_ty_basic_md, md_ty_basic_md], false);
@@ -500,6 +513,17 @@ impl<'ctx> Intrinsics<'ctx> {
             sqrt_f32x4: module.add_function("llvm.sqrt.v4f32", ret_f32x4_take_f32x4, None),
             sqrt_f64x2: module.add_function("llvm.sqrt.v2f64", ret_f64x2_take_f64x2, None),
 
+            fmuladd_f32x4: module.add_function(
+                "llvm.fmuladd.v4f32",
+                ret_f32x4_take_f32x4_f32x4_f32x4,
+                None,
+            ),
+            fmuladd_f64x2: module.add_function(
+                "llvm.fmuladd.v2f64",
+                ret_f64x2_take_f64x2_f64x2_f64x2,
+                None,
+            ),
+
             ceil_f32: module.add_function("llvm.ceil.f32", ret_f32_take_f32, None),
             ceil_f64: module.add_function("llvm.ceil.f64", ret_f64_take_f64, None),
             ceil_f32x4: module.add_function("llvm.ceil.v4f32", ret_f32x4_take_f32x4, None),
@@ -971,6 +995,21 @@ impl<'ctx> Intrinsics<'ctx> {
                 ),
                 None,
             ),
+            memory_copy_between: module.add_function(
+                "wasmer_vm_memory32_copy_between",
+                void_ty.fn_type(
+                    &[
+                        ctx_ptr_ty_basic_md,
+                        i32_ty_basic_md,
+                        i32_ty_basic_md,
+                        i32_ty_basic_md,
+                        i32_ty_basic_md,
+                        i32_ty_basic_md,
+                    ],
+                    false,
+                ),
+                None,
+            ),
             memory_fill: module.add_function(
                 "wasmer_vm_memory32_fill",
                 void_ty.fn_type(
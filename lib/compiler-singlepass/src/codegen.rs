@@ -18,9 +18,9 @@ use wasmer_types::CompiledFunctionUnwindInfo;
 use wasmer_types::{
     entity::{EntityRef, PrimaryMap},
     CallingConvention, CompileError, FunctionIndex, FunctionType, GlobalIndex, LocalFunctionIndex,
-    LocalMemoryIndex, MemoryIndex, MemoryStyle, ModuleInfo, Relocation, RelocationTarget,
-    SectionIndex, SignatureIndex, TableIndex, TableStyle, TrapCode, Type, VMBuiltinFunctionIndex,
-    VMOffsets,
+    MemoryIndex, MemoryStyle, ModuleInfo, Relocation, RelocationTarget, SectionIndex,
+    SignatureIndex, TableIndex, TableStyle, TrapCode, Type, VMBuiltinFunctionIndex, VMOffsets,
+    WasmError,
 };
 use wasmer_types::{CompiledFunction, CompiledFunctionFrameInfo, FunctionBody};
 
@@ -85,6 +85,10 @@ pub struct FuncGen<'a, M: Machine> {
 
     /// Calling convention to use.
     calling_convention: CallingConvention,
+
+    /// The trap code of the operator being fed, if it is an `unreachable`
+    /// raised by a middleware.
+    trap_code: Option<TrapCode>,
 }
 
 struct SpecialLabelSet {
@@ -695,6 +699,12 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         self.machine.set_srcloc(offset);
     }
 
+    /// Set the trap code of the next operator, if it is an `unreachable`
+    /// raised by a middleware.
+    pub fn set_trap_code(&mut self, trap_code: Option<TrapCode>) {
+        self.trap_code = trap_code;
+    }
+
     fn get_location_released(
         &mut self,
         loc: Location<M::GPR, M::SIMD>,
@@ -1012,29 +1022,336 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         Ok(())
     }
 
-    /// Emits a memory operation.
+    /// Emits the lookup of the function `func_index` in the table
+    /// `table_index` for an indirect call with the signature `index`.
+    ///
+    /// Traps if the index is out of bounds, the element is null, or its
+    /// signature doesn't match. Returns the register holding the
+    /// `VMCallerCheckedAnyfunc` of the callee, which isn't marked as used.
+    fn emit_indirect_callee(
+        &mut self,
+        table_index: TableIndex,
+        index: SignatureIndex,
+        func_index: Location<M::GPR, M::SIMD>,
+    ) -> Result<M::GPR, CompileError> {
+        let table_base = self.machine.acquire_temp_gpr().unwrap();
+        let table_count = self.machine.acquire_temp_gpr().unwrap();
+        let sigidx = self.machine.acquire_temp_gpr().unwrap();
+
+        if let Some(local_table_index) = self.module.local_table_index(table_index) {
+            let (vmctx_offset_base, vmctx_offset_len) = (
+                self.vmoffsets.vmctx_vmtable_definition(local_table_index),
+                self.vmoffsets
+                    .vmctx_vmtable_definition_current_elements(local_table_index),
+            );
+            self.machine.move_location(
+                Size::S64,
+                Location::Memory(self.machine.get_vmctx_reg(), vmctx_offset_base as i32),
+                Location::GPR(table_base),
+            )?;
+            self.machine.move_location(
+                Size::S32,
+                Location::Memory(self.machine.get_vmctx_reg(), vmctx_offset_len as i32),
+                Location::GPR(table_count),
+            )?;
+        } else {
+            // Do an indirection.
+            let import_offset = self.vmoffsets.vmctx_vmtable_import(table_index);
+            self.machine.move_location(
+                Size::S64,
+                Location::Memory(self.machine.get_vmctx_reg(), import_offset as i32),
+                Location::GPR(table_base),
+            )?;
+
+            // Load len.
+            self.machine.move_location(
+                Size::S32,
+                Location::Memory(
+                    table_base,
+                    self.vmoffsets.vmtable_definition_current_elements() as _,
+                ),
+                Location::GPR(table_count),
+            )?;
+
+            // Load base.
+            self.machine.move_location(
+                Size::S64,
+                Location::Memory(table_base, self.vmoffsets.vmtable_definition_base() as _),
+                Location::GPR(table_base),
+            )?;
+        }
+
+        self.machine
+            .location_cmp(Size::S32, func_index, Location::GPR(table_count))?;
+        self.machine
+            .jmp_on_belowequal(self.special_labels.table_access_oob)?;
+        self.machine
+            .move_location(Size::S32, func_index, Location::GPR(table_count))?;
+        self.machine.emit_imul_imm32(
+            Size::S64,
+            self.vmoffsets.size_of_vm_funcref() as u32,
+            table_count,
+        )?;
+        self.machine.location_add(
+            Size::S64,
+            Location::GPR(table_base),
+            Location::GPR(table_count),
+            false,
+        )?;
+
+        // deref the table to get a VMFuncRef
+        self.machine.move_location(
+            Size::S64,
+            Location::Memory(table_count, self.vmoffsets.vm_funcref_anyfunc_ptr() as i32),
+            Location::GPR(table_count),
+        )?;
+        // Trap if the FuncRef is null
+        self.machine
+            .location_cmp(Size::S64, Location::Imm32(0), Location::GPR(table_count))?;
+        self.machine
+            .jmp_on_equal(self.special_labels.indirect_call_null)?;
+        self.machine.move_location(
+            Size::S64,
+            Location::Memory(
+                self.machine.get_vmctx_reg(),
+                self.vmoffsets.vmctx_vmshared_signature_id(index) as i32,
+            ),
+            Location::GPR(sigidx),
+        )?;
+
+        // Trap if signature mismatches.
+        self.machine.location_cmp(
+            Size::S32,
+            Location::GPR(sigidx),
+            Location::Memory(
+                table_count,
+                (self.vmoffsets.vmcaller_checked_anyfunc_type_index() as usize) as i32,
+            ),
+        )?;
+        self.machine
+            .jmp_on_different(self.special_labels.bad_signature)?;
+
+        self.machine.release_gpr(sigidx);
+        self.machine.release_gpr(table_count);
+        self.machine.release_gpr(table_base);
+
+        let gpr_for_call = self.machine.get_grp_for_call();
+        if table_count != gpr_for_call {
+            self.machine.move_location(
+                Size::S64,
+                Location::GPR(table_count),
+                Location::GPR(gpr_for_call),
+            )?;
+        }
+        Ok(gpr_for_call)
+    }
+
+    /// Returns how many of the `params` parameters of a function are
+    /// passed on the stack.
+    fn stack_param_count(&self, params: usize) -> usize {
+        let mut stack_offset = 0;
+        let mut count = 0;
+        for i in 0..params {
+            if let Location::Memory(_, _) = self.machine.get_call_param_location(
+                1 + i,
+                Size::S64,
+                &mut stack_offset,
+                self.calling_convention,
+            ) {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Checks that a tail call to a function of type `callee` can replace
+    /// the frame of the current function.
+    ///
+    /// The arguments the callee takes on the stack go in the area where
+    /// the current function received its own, so there must not be more
+    /// of them.
+    fn check_tail_call(&self, callee: &FunctionType, op: &Operator) -> Result<(), CompileError> {
+        if !self.machine.arch_supports_tail_calls() {
+            return Err(CompileError::Wasm(WasmError::Unsupported(format!(
+                "tail calls are not supported on this architecture: {:?}",
+                op
+            ))));
+        }
+        if self.stack_param_count(callee.params().len())
+            > self.stack_param_count(self.signature.params().len())
+        {
+            return Err(CompileError::Wasm(WasmError::Unsupported(format!(
+                "tail calls to a function taking more arguments on the stack than the caller are not supported: {:?}",
+                op
+            ))));
+        }
+        Ok(())
+    }
+
+    /// Pops the `count` arguments of a tail call off the value stack,
+    /// canonicalizing them if needed.
+    #[allow(clippy::type_complexity)]
+    fn pop_tail_call_params(
+        &mut self,
+        count: usize,
+    ) -> Result<SmallVec<[Location<M::GPR, M::SIMD>; 8]>, CompileError> {
+        let params: SmallVec<[_; 8]> = self
+            .value_stack
+            .drain(self.value_stack.len() - count..)
+            .collect();
+        self.release_locations_only_regs(&params)?;
+        self.release_locations_only_osr_state(params.len())?;
+
+        while let Some(fp) = self.fp_stack.last() {
+            if fp.depth >= self.value_stack.len() {
+                let index = fp.depth - self.value_stack.len();
+                if self.machine.arch_supports_canonicalize_nan()
+                    && self.config.enable_nan_canonicalization
+                {
+                    if let Some(canonicalization) = fp.canonicalization {
+                        let size = canonicalization.to_size();
+                        self.machine
+                            .canonicalize_nan(size, params[index], params[index])?;
+                    }
+                }
+                self.fp_stack.pop().unwrap();
+            } else {
+                break;
+            }
+        }
+        Ok(params)
+    }
+
+    /// Emits a tail call: the arguments `params` replace those of the
+    /// current function, whose frame is torn down before `jump` enters
+    /// the callee. The callee then returns to the caller of the current
+    /// function.
+    ///
+    /// `anyfunc` is the register holding the `VMCallerCheckedAnyfunc` of
+    /// an indirect callee, whose address `jump` finds in the register for
+    /// calls. Direct callees share the vmctx of the current function.
+    fn emit_tail_call<F: FnOnce(&mut Self) -> Result<(), CompileError>>(
+        &mut self,
+        params: &[Location<M::GPR, M::SIMD>],
+        anyfunc: Option<M::GPR>,
+        jump: F,
+    ) -> Result<(), CompileError> {
+        let calling_convention = self.calling_convention;
+        let gpr_for_call = self.machine.get_grp_for_call();
+        let vmctx_param = self
+            .machine
+            .get_simple_param_location(0, calling_convention);
+
+        // The address and the vmctx of an indirect callee stay on the stack
+        // while the arguments are moved.
+        if let Some(anyfunc) = anyfunc {
+            self.machine.emit_push(
+                Size::S64,
+                Location::Memory(
+                    anyfunc,
+                    self.vmoffsets.vmcaller_checked_anyfunc_func_ptr() as i32,
+                ),
+            )?;
+            self.machine.emit_push(
+                Size::S64,
+                Location::Memory(
+                    anyfunc,
+                    self.vmoffsets.vmcaller_checked_anyfunc_vmctx() as i32,
+                ),
+            )?;
+        }
+
+        // Stack arguments overwrite the ones of the current function, which
+        // were copied to its locals by the prologue.
+        let mut stack_offset = 0;
+        #[allow(clippy::type_complexity)]
+        let mut register_params: Vec<(Location<M::GPR, M::SIMD>, M::GPR)> = vec![];
+        for (i, param) in params.iter().enumerate() {
+            match self.machine.get_call_param_location(
+                1 + i,
+                Size::S64,
+                &mut stack_offset,
+                calling_convention,
+            ) {
+                Location::GPR(gpr) => register_params.push((*param, gpr)),
+                loc => self
+                    .machine
+                    .move_location_for_native(Size::S64, *param, loc)?,
+            }
+        }
+
+        // Register arguments go through the stack, so that no register is
+        // overwritten before it is read.
+        for (param, _) in register_params.iter() {
+            match *param {
+                Location::GPR(_) | Location::Imm32(_) | Location::Memory(_, _) => {
+                    self.machine.emit_push(Size::S64, *param)?;
+                }
+                _ => {
+                    self.machine
+                        .move_location(Size::S64, *param, Location::GPR(gpr_for_call))?;
+                    self.machine
+                        .emit_push(Size::S64, Location::GPR(gpr_for_call))?;
+                }
+            }
+        }
+        for (_, gpr) in register_params.iter().rev() {
+            self.machine.emit_pop(Size::S64, Location::GPR(*gpr))?;
+        }
+
+        if anyfunc.is_some() {
+            self.machine.emit_pop(Size::S64, vmctx_param)?;
+            self.machine
+                .emit_pop(Size::S64, Location::GPR(gpr_for_call))?;
+        } else {
+            self.machine.move_location(
+                Size::S64,
+                Location::GPR(self.machine.get_vmctx_reg()),
+                vmctx_param,
+            )?;
+        }
+
+        self.finalize_locals(calling_convention)?;
+        self.machine.emit_function_epilog()?;
+        jump(self)
+    }
+
+    /// The memory index the builtin functions expect: the index among the
+    /// locally defined memories for those, the module-level index for
+    /// imported ones.
+    fn memory_libcall_index(&self, memory_index: MemoryIndex) -> u32 {
+        match self.module.local_memory_index(memory_index) {
+            Some(local_memory_index) => local_memory_index.as_u32(),
+            None => memory_index.as_u32(),
+        }
+    }
+
+    /// Emits an operation on the memory `memory_index`.
     fn op_memory<
         F: FnOnce(&mut Self, bool, bool, i32, Label, Label) -> Result<(), CompileError>,
     >(
         &mut self,
+        memory_index: MemoryIndex,
         cb: F,
     ) -> Result<(), CompileError> {
-        let need_check = match self.memory_styles[MemoryIndex::new(0)] {
+        let need_check = match self.memory_styles[memory_index] {
             MemoryStyle::Static { .. } => false,
             MemoryStyle::Dynamic { .. } => true,
         };
 
-        let offset = if self.module.num_imported_memories != 0 {
-            self.vmoffsets
-                .vmctx_vmmemory_import_definition(MemoryIndex::new(0))
-        } else {
-            self.vmoffsets
-                .vmctx_vmmemory_definition(LocalMemoryIndex::new(0))
+        let local_memory_index = self.module.local_memory_index(memory_index);
+        let offset = match local_memory_index {
+            Some(local_memory_index) => {
+                self.vmoffsets.vmctx_vmmemory_definition(local_memory_index)
+            }
+            None => self
+                .vmoffsets
+                .vmctx_vmmemory_import_definition(memory_index),
         };
         cb(
             self,
             need_check,
-            self.module.num_imported_memories != 0,
+            local_memory_index.is_none(),
             offset as i32,
             self.special_labels.heap_access_oob,
             self.special_labels.unaligned_atomic,
@@ -1172,6 +1489,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             relocations: vec![],
             special_labels,
             calling_convention,
+            trap_code: None,
         };
         fg.emit_head()?;
         Ok(fg)
@@ -2755,118 +3073,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     }
                 }
 
-                let table_base = self.machine.acquire_temp_gpr().unwrap();
-                let table_count = self.machine.acquire_temp_gpr().unwrap();
-                let sigidx = self.machine.acquire_temp_gpr().unwrap();
-
-                if let Some(local_table_index) = self.module.local_table_index(table_index) {
-                    let (vmctx_offset_base, vmctx_offset_len) = (
-                        self.vmoffsets.vmctx_vmtable_definition(local_table_index),
-                        self.vmoffsets
-                            .vmctx_vmtable_definition_current_elements(local_table_index),
-                    );
-                    self.machine.move_location(
-                        Size::S64,
-                        Location::Memory(self.machine.get_vmctx_reg(), vmctx_offset_base as i32),
-                        Location::GPR(table_base),
-                    )?;
-                    self.machine.move_location(
-                        Size::S32,
-                        Location::Memory(self.machine.get_vmctx_reg(), vmctx_offset_len as i32),
-                        Location::GPR(table_count),
-                    )?;
-                } else {
-                    // Do an indirection.
-                    let import_offset = self.vmoffsets.vmctx_vmtable_import(table_index);
-                    self.machine.move_location(
-                        Size::S64,
-                        Location::Memory(self.machine.get_vmctx_reg(), import_offset as i32),
-                        Location::GPR(table_base),
-                    )?;
-
-                    // Load len.
-                    self.machine.move_location(
-                        Size::S32,
-                        Location::Memory(
-                            table_base,
-                            self.vmoffsets.vmtable_definition_current_elements() as _,
-                        ),
-                        Location::GPR(table_count),
-                    )?;
-
-                    // Load base.
-                    self.machine.move_location(
-                        Size::S64,
-                        Location::Memory(table_base, self.vmoffsets.vmtable_definition_base() as _),
-                        Location::GPR(table_base),
-                    )?;
-                }
-
-                self.machine
-                    .location_cmp(Size::S32, func_index, Location::GPR(table_count))?;
-                self.machine
-                    .jmp_on_belowequal(self.special_labels.table_access_oob)?;
-                self.machine
-                    .move_location(Size::S32, func_index, Location::GPR(table_count))?;
-                self.machine.emit_imul_imm32(
-                    Size::S64,
-                    self.vmoffsets.size_of_vm_funcref() as u32,
-                    table_count,
-                )?;
-                self.machine.location_add(
-                    Size::S64,
-                    Location::GPR(table_base),
-                    Location::GPR(table_count),
-                    false,
-                )?;
-
-                // deref the table to get a VMFuncRef
-                self.machine.move_location(
-                    Size::S64,
-                    Location::Memory(table_count, self.vmoffsets.vm_funcref_anyfunc_ptr() as i32),
-                    Location::GPR(table_count),
-                )?;
-                // Trap if the FuncRef is null
-                self.machine.location_cmp(
-                    Size::S64,
-                    Location::Imm32(0),
-                    Location::GPR(table_count),
-                )?;
-                self.machine
-                    .jmp_on_equal(self.special_labels.indirect_call_null)?;
-                self.machine.move_location(
-                    Size::S64,
-                    Location::Memory(
-                        self.machine.get_vmctx_reg(),
-                        self.vmoffsets.vmctx_vmshared_signature_id(index) as i32,
-                    ),
-                    Location::GPR(sigidx),
-                )?;
-
-                // Trap if signature mismatches.
-                self.machine.location_cmp(
-                    Size::S32,
-                    Location::GPR(sigidx),
-                    Location::Memory(
-                        table_count,
-                        (self.vmoffsets.vmcaller_checked_anyfunc_type_index() as usize) as i32,
-                    ),
-                )?;
-                self.machine
-                    .jmp_on_different(self.special_labels.bad_signature)?;
-
-                self.machine.release_gpr(sigidx);
-                self.machine.release_gpr(table_count);
-                self.machine.release_gpr(table_base);
-
-                let gpr_for_call = self.machine.get_grp_for_call();
-                if table_count != gpr_for_call {
-                    self.machine.move_location(
-                        Size::S64,
-                        Location::GPR(table_count),
-                        Location::GPR(gpr_for_call),
-                    )?;
-                }
+                let gpr_for_call = self.emit_indirect_callee(table_index, index, func_index)?;
 
                 self.release_locations_only_osr_state(params.len())?;
 
@@ -3140,7 +3347,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                             .emit_call_register(this.machine.get_grp_for_call())
                     },
                     // [vmctx, memory_index]
-                    iter::once(Location::Imm32(memory_index.index() as u32)),
+                    iter::once(Location::Imm32(self.memory_libcall_index(memory_index))),
                     iter::once(WpType::I64),
                 )?;
                 let ret = self.acquire_locations(
@@ -3224,26 +3431,19 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?;
             }
             Operator::MemoryCopy { dst_mem, src_mem } => {
-                // ignore until we support multiple memories
-                let _dst = dst_mem;
                 let len = self.value_stack.pop().unwrap();
                 let src_pos = self.value_stack.pop().unwrap();
                 let dst_pos = self.value_stack.pop().unwrap();
                 self.release_locations_only_regs(&[len, src_pos, dst_pos])?;
 
                 let memory_index = MemoryIndex::new(src_mem as usize);
-                let (memory_copy_index, memory_index) =
-                    if self.module.local_memory_index(memory_index).is_some() {
-                        (
-                            VMBuiltinFunctionIndex::get_memory_copy_index(),
-                            memory_index,
-                        )
-                    } else {
-                        (
-                            VMBuiltinFunctionIndex::get_imported_memory_copy_index(),
-                            memory_index,
-                        )
-                    };
+                let memory_copy_index = if dst_mem != src_mem {
+                    VMBuiltinFunctionIndex::get_memory_copy_between_index()
+                } else if self.module.local_memory_index(memory_index).is_some() {
+                    VMBuiltinFunctionIndex::get_memory_copy_index()
+                } else {
+                    VMBuiltinFunctionIndex::get_imported_memory_copy_index()
+                };
 
                 self.machine.move_location(
                     Size::S64,
@@ -3257,24 +3457,52 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 // TODO: should this be 3?
                 self.release_locations_only_osr_state(1)?;
 
-                self.emit_call_native(
-                    |this| {
-                        this.machine
-                            .emit_call_register(this.machine.get_grp_for_call())
-                    },
-                    // [vmctx, memory_index, dst, src, len]
-                    [
-                        Location::Imm32(memory_index.index() as u32),
-                        dst_pos,
-                        src_pos,
-                        len,
-                    ]
-                    .iter()
-                    .cloned(),
-                    [WpType::I32, WpType::I64, WpType::I64, WpType::I64]
+                if dst_mem != src_mem {
+                    self.emit_call_native(
+                        |this| {
+                            this.machine
+                                .emit_call_register(this.machine.get_grp_for_call())
+                        },
+                        // [vmctx, dst_memory_index, src_memory_index, dst, src, len]
+                        [
+                            Location::Imm32(dst_mem),
+                            Location::Imm32(src_mem),
+                            dst_pos,
+                            src_pos,
+                            len,
+                        ]
                         .iter()
                         .cloned(),
-                )?;
+                        [
+                            WpType::I32,
+                            WpType::I32,
+                            WpType::I64,
+                            WpType::I64,
+                            WpType::I64,
+                        ]
+                        .iter()
+                        .cloned(),
+                    )?;
+                } else {
+                    self.emit_call_native(
+                        |this| {
+                            this.machine
+                                .emit_call_register(this.machine.get_grp_for_call())
+                        },
+                        // [vmctx, memory_index, dst, src, len]
+                        [
+                            Location::Imm32(self.memory_libcall_index(memory_index)),
+                            dst_pos,
+                            src_pos,
+                            len,
+                        ]
+                        .iter()
+                        .cloned(),
+                        [WpType::I32, WpType::I64, WpType::I64, WpType::I64]
+                            .iter()
+                            .cloned(),
+                    )?;
+                }
                 self.release_locations_only_stack(&[dst_pos, src_pos, len])?;
             }
             Operator::MemoryFill { mem } => {
@@ -3315,9 +3543,14 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                             .emit_call_register(this.machine.get_grp_for_call())
                     },
                     // [vmctx, memory_index, dst, src, len]
-                    [Location::Imm32(memory_index.index() as u32), dst, val, len]
-                        .iter()
-                        .cloned(),
+                    [
+                        Location::Imm32(self.memory_libcall_index(memory_index)),
+                        dst,
+                        val,
+                        len,
+                    ]
+                    .iter()
+                    .cloned(),
                     [WpType::I32, WpType::I64, WpType::I64, WpType::I64]
                         .iter()
                         .cloned(),
@@ -3353,8 +3586,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                             .emit_call_register(this.machine.get_grp_for_call())
                     },
                     // [vmctx, val, memory_index]
-                    iter::once(param_pages)
-                        .chain(iter::once(Location::Imm32(memory_index.index() as u32))),
+                    iter::once(param_pages).chain(iter::once(Location::Imm32(
+                        self.memory_libcall_index(memory_index),
+                    ))),
                     [WpType::I64, WpType::I64].iter().cloned(),
                 )?;
 
@@ -3379,6 +3613,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3408,6 +3643,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 self.fp_stack
                     .push(FloatValue::new(self.value_stack.len() - 1));
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3435,6 +3671,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3462,6 +3699,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3489,6 +3727,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3516,6 +3755,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3539,6 +3779,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_value = self.pop_value_released()?;
                 let target_addr = self.pop_value_released()?;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3564,6 +3805,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let fp = self.fp_stack.pop1()?;
                 let config_nan_canonicalization = self.config.enable_nan_canonicalization;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3588,6 +3830,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_value = self.pop_value_released()?;
                 let target_addr = self.pop_value_released()?;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3611,6 +3854,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_value = self.pop_value_released()?;
                 let target_addr = self.pop_value_released()?;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3638,6 +3882,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3667,6 +3912,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 self.fp_stack
                     .push(FloatValue::new(self.value_stack.len() - 1));
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3694,6 +3940,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3721,6 +3968,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3748,6 +3996,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3775,6 +4024,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3802,6 +4052,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3829,6 +4080,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3853,6 +4105,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_addr = self.pop_value_released()?;
 
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3878,6 +4131,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let fp = self.fp_stack.pop1()?;
                 let config_nan_canonicalization = self.config.enable_nan_canonicalization;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3902,6 +4156,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_value = self.pop_value_released()?;
                 let target_addr = self.pop_value_released()?;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3925,6 +4180,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_value = self.pop_value_released()?;
                 let target_addr = self.pop_value_released()?;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3948,6 +4204,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_value = self.pop_value_released()?;
                 let target_addr = self.pop_value_released()?;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -3969,8 +4226,8 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             }
             Operator::Unreachable => {
                 self.mark_trappable();
-                self.machine
-                    .emit_illegal_op(TrapCode::UnreachableCodeReached)?;
+                let trap_code = self.trap_code.unwrap_or(TrapCode::UnreachableCodeReached);
+                self.machine.emit_illegal_op(trap_code)?;
                 self.unreachable_depth = 1;
             }
             Operator::Return => {
@@ -4259,6 +4516,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4286,6 +4544,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4313,6 +4572,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4336,6 +4596,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_value = self.pop_value_released()?;
                 let target_addr = self.pop_value_released()?;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4359,6 +4620,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_value = self.pop_value_released()?;
                 let target_addr = self.pop_value_released()?;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4382,6 +4644,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_value = self.pop_value_released()?;
                 let target_addr = self.pop_value_released()?;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4409,6 +4672,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4436,6 +4700,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4463,6 +4728,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4490,6 +4756,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4513,6 +4780,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_value = self.pop_value_released()?;
                 let target_addr = self.pop_value_released()?;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4536,6 +4804,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_value = self.pop_value_released()?;
                 let target_addr = self.pop_value_released()?;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4559,6 +4828,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_value = self.pop_value_released()?;
                 let target_addr = self.pop_value_released()?;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4582,6 +4852,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 let target_value = self.pop_value_released()?;
                 let target_addr = self.pop_value_released()?;
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4610,6 +4881,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4639,6 +4911,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4668,6 +4941,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4697,6 +4971,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4726,6 +5001,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4755,6 +5031,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4784,6 +5061,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4813,6 +5091,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4842,6 +5121,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4871,6 +5151,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4900,6 +5181,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4929,6 +5211,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4958,6 +5241,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -4987,6 +5271,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5016,6 +5301,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5045,6 +5331,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5074,6 +5361,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5103,6 +5391,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5132,6 +5421,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5161,6 +5451,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5190,6 +5481,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5219,6 +5511,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5248,6 +5541,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5277,6 +5571,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5306,6 +5601,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5335,6 +5631,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5364,6 +5661,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5393,6 +5691,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5422,6 +5721,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5451,6 +5751,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5480,6 +5781,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5509,6 +5811,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5538,6 +5841,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5567,6 +5871,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5596,6 +5901,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5625,6 +5931,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5654,6 +5961,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5683,6 +5991,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5712,6 +6021,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5741,6 +6051,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5770,6 +6081,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5799,6 +6111,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5829,6 +6142,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5860,6 +6174,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5891,6 +6206,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5922,6 +6238,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5953,6 +6270,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -5984,6 +6302,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -6015,6 +6334,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 )?[0];
                 self.value_stack.push(ret);
                 self.op_memory(
+                    MemoryIndex::new(memarg.memory as usize),
                     |this,
                      need_check,
                      imported_memories,
@@ -6460,7 +6780,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     },
                     // [vmctx, memory_index, dst, src, timeout]
                     [
-                        Location::Imm32(memory_index.index() as u32),
+                        Location::Imm32(self.memory_libcall_index(memory_index)),
                         dst,
                         val,
                         timeout,
@@ -6522,7 +6842,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     },
                     // [vmctx, memory_index, dst, src, timeout]
                     [
-                        Location::Imm32(memory_index.index() as u32),
+                        Location::Imm32(self.memory_libcall_index(memory_index)),
                         dst,
                         val,
                         timeout,
@@ -6581,11 +6901,15 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                         this.machine
                             .emit_call_register(this.machine.get_grp_for_call())
                     },
-                    // [vmctx, memory_index, dst, src, timeout]
-                    [Location::Imm32(memory_index.index() as u32), dst]
-                        .iter()
-                        .cloned(),
-                    [WpType::I32, WpType::I32].iter().cloned(),
+                    // [vmctx, memory_index, dst, cnt]
+                    [
+                        Location::Imm32(self.memory_libcall_index(memory_index)),
+                        dst,
+                        cnt,
+                    ]
+                    .iter()
+                    .cloned(),
+                    [WpType::I32, WpType::I32, WpType::I32].iter().cloned(),
                 )?;
                 self.release_locations_only_stack(&[dst, cnt])?;
                 let ret = self.acquire_locations(
@@ -6599,6 +6923,58 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                     ret,
                 )?;
             }
+            Operator::ReturnCall { function_index } => {
+                let function_index = function_index as usize;
+                let sig_index = self.module.functions[FunctionIndex::new(function_index)];
+                let sig = &self.module.signatures[sig_index];
+                self.check_tail_call(sig, &op)?;
+                let params = self.pop_tail_call_params(sig.params().len())?;
+
+                // Imported functions are called through trampolines placed as custom sections.
+                let reloc_target = if function_index < self.module.num_imported_functions {
+                    RelocationTarget::CustomSection(SectionIndex::new(function_index))
+                } else {
+                    RelocationTarget::LocalFunc(LocalFunctionIndex::new(
+                        function_index - self.module.num_imported_functions,
+                    ))
+                };
+                let calling_convention = self.calling_convention;
+                self.emit_tail_call(&params, None, |this| {
+                    let mut relocations = this
+                        .machine
+                        .emit_tail_call_with_reloc(calling_convention, reloc_target)?;
+                    this.relocations.append(&mut relocations);
+                    Ok(())
+                })?;
+
+                self.release_locations_only_stack(&params)?;
+                let frame_depth = self.control_stack[0].value_stack_depth;
+                self.release_locations_keep_state(frame_depth)?;
+                self.unreachable_depth = 1;
+            }
+            Operator::ReturnCallIndirect {
+                type_index,
+                table_index,
+            } => {
+                let table_index = TableIndex::new(table_index as _);
+                let index = SignatureIndex::new(type_index as usize);
+                let sig = &self.module.signatures[index];
+                self.check_tail_call(sig, &op)?;
+                let param_count = sig.params().len();
+
+                let func_index = self.pop_value_released()?;
+                let params = self.pop_tail_call_params(param_count)?;
+                let anyfunc = self.emit_indirect_callee(table_index, index, func_index)?;
+                self.emit_tail_call(&params, Some(anyfunc), |this| {
+                    this.machine
+                        .emit_tail_call_register(this.machine.get_grp_for_call())
+                })?;
+
+                self.release_locations_only_stack(&params)?;
+                let frame_depth = self.control_stack[0].value_stack_depth;
+                self.release_locations_keep_state(frame_depth)?;
+                self.unreachable_depth = 1;
+            }
             _ => {
                 return Err(CompileError::Codegen(format!(
                     "not yet implemented: {:?}",
@@ -29,7 +29,7 @@ use wasmer_types::{
     Architecture, CallingConvention, Compilation, CompileError, CompileModuleInfo,
     CompiledFunction, CpuFeature, Dwarf, FunctionBody, FunctionIndex, FunctionType,
     LocalFunctionIndex, MemoryIndex, ModuleInfo, OperatingSystem, SectionIndex, TableIndex, Target,
-    TrapCode, TrapInformation, VMOffsets,
+    TrapCode, TrapInformation, VMOffsets, WasmError,
 };
 
 /// A compiler that compiles a WebAssembly module with Singlepass.
@@ -113,6 +113,17 @@ impl Compiler for SinglepassCompiler {
             }
         };
 
+        if compile_info
+            .module
+            .memories
+            .values()
+            .any(|memory| memory.is_memory64())
+        {
+            return Err(CompileError::Wasm(WasmError::Unsupported(
+                "64-bit memories are not supported by the Singlepass compiler".to_string(),
+            )));
+        }
+
         let memory_styles = &compile_info.memory_styles;
         let table_styles = &compile_info.table_styles;
         let vmoffsets = VMOffsets::new(8, &compile_info.module);
@@ -174,6 +185,7 @@ impl Compiler for SinglepassCompiler {
                         while generator.has_control_frames() {
                             generator.set_srcloc(reader.original_position() as u32);
                             let op = reader.read_operator()?;
+                            generator.set_trap_code(reader.trap_code());
                             generator.feed_operator(op)?;
                         }
 
@@ -195,6 +207,7 @@ impl Compiler for SinglepassCompiler {
                         while generator.has_control_frames() {
                             generator.set_srcloc(reader.original_position() as u32);
                             let op = reader.read_operator()?;
+                            generator.set_trap_code(reader.trap_code());
                             generator.feed_operator(op)?;
                         }
 
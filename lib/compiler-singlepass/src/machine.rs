@@ -1144,6 +1144,16 @@ pub trait Machine {
         calling_convention: CallingConvention,
         reloc_target: RelocationTarget,
     ) -> Result<Vec<Relocation>, CompileError>;
+    /// Can the arch replace the frame of the current function for a tail call
+    fn arch_supports_tail_calls(&self) -> bool;
+    /// emit a jump to a function for a tail call, using appropriate relocation
+    fn emit_tail_call_with_reloc(
+        &mut self,
+        calling_convention: CallingConvention,
+        reloc_target: RelocationTarget,
+    ) -> Result<Vec<Relocation>, CompileError>;
+    /// emit a jump to the function whose address is in the register, for a tail call
+    fn emit_tail_call_register(&mut self, register: Self::GPR) -> Result<(), CompileError>;
     /// Add with location directly from the stack
     fn emit_binop_add64(
         &mut self,
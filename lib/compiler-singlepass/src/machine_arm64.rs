@@ -3354,7 +3354,8 @@ impl Machine for MachineARM64 {
             heap_access_oob,
             unaligned_atomic,
             |this, addr| this.emit_relaxed_ldr32(Size::S32, ret, Location::Memory(addr, 0)),
-        )
+        )?;
+        self.assembler.emit_dmb()
     }
     fn i32_atomic_load_8u(
         &mut self,
@@ -3378,7 +3379,8 @@ impl Machine for MachineARM64 {
             heap_access_oob,
             unaligned_atomic,
             |this, addr| this.emit_relaxed_ldr8(Size::S32, ret, Location::Memory(addr, 0)),
-        )
+        )?;
+        self.assembler.emit_dmb()
     }
     fn i32_atomic_load_16u(
         &mut self,
@@ -3402,7 +3404,8 @@ impl Machine for MachineARM64 {
             heap_access_oob,
             unaligned_atomic,
             |this, addr| this.emit_relaxed_ldr16(Size::S32, ret, Location::Memory(addr, 0)),
-        )
+        )?;
+        self.assembler.emit_dmb()
     }
     fn i32_save(
         &mut self,
@@ -3497,7 +3500,10 @@ impl Machine for MachineARM64 {
             offset,
             heap_access_oob,
             unaligned_atomic,
-            |this, addr| this.emit_relaxed_str32(target_value, Location::Memory(addr, 0)),
+            |this, addr| {
+                this.assembler.emit_dmb()?;
+                this.emit_relaxed_str32(target_value, Location::Memory(addr, 0))
+            },
         )?;
         self.assembler.emit_dmb()
     }
@@ -3522,7 +3528,10 @@ impl Machine for MachineARM64 {
             offset,
             heap_access_oob,
             unaligned_atomic,
-            |this, addr| this.emit_relaxed_str8(target_value, Location::Memory(addr, 0)),
+            |this, addr| {
+                this.assembler.emit_dmb()?;
+                this.emit_relaxed_str8(target_value, Location::Memory(addr, 0))
+            },
         )?;
         self.assembler.emit_dmb()
     }
@@ -3547,7 +3556,10 @@ impl Machine for MachineARM64 {
             offset,
             heap_access_oob,
             unaligned_atomic,
-            |this, addr| this.emit_relaxed_str16(target_value, Location::Memory(addr, 0)),
+            |this, addr| {
+                this.assembler.emit_dmb()?;
+                this.emit_relaxed_str16(target_value, Location::Memory(addr, 0))
+            },
         )?;
         self.assembler.emit_dmb()
     }
@@ -4813,6 +4825,22 @@ impl Machine for MachineARM64 {
         Ok(relocations)
     }
 
+    fn arch_supports_tail_calls(&self) -> bool {
+        false
+    }
+
+    fn emit_tail_call_with_reloc(
+        &mut self,
+        _calling_convention: CallingConvention,
+        _reloc_target: RelocationTarget,
+    ) -> Result<Vec<Relocation>, CompileError> {
+        codegen_error!("singlepass can't emit tail calls on arm64")
+    }
+
+    fn emit_tail_call_register(&mut self, _register: GPR) -> Result<(), CompileError> {
+        codegen_error!("singlepass can't emit tail calls on arm64")
+    }
+
     fn emit_binop_add64(
         &mut self,
         loc_a: Location,
@@ -5477,7 +5505,8 @@ impl Machine for MachineARM64 {
             heap_access_oob,
             unaligned_atomic,
             |this, addr| this.emit_relaxed_ldr64(Size::S64, ret, Location::Memory(addr, 0)),
-        )
+        )?;
+        self.assembler.emit_dmb()
     }
     fn i64_atomic_load_8u(
         &mut self,
@@ -5501,7 +5530,8 @@ impl Machine for MachineARM64 {
             heap_access_oob,
             unaligned_atomic,
             |this, addr| this.emit_relaxed_ldr8(Size::S64, ret, Location::Memory(addr, 0)),
-        )
+        )?;
+        self.assembler.emit_dmb()
     }
     fn i64_atomic_load_16u(
         &mut self,
@@ -5525,7 +5555,8 @@ impl Machine for MachineARM64 {
             heap_access_oob,
             unaligned_atomic,
             |this, addr| this.emit_relaxed_ldr16(Size::S64, ret, Location::Memory(addr, 0)),
-        )
+        )?;
+        self.assembler.emit_dmb()
     }
     fn i64_atomic_load_32u(
         &mut self,
@@ -5549,7 +5580,8 @@ impl Machine for MachineARM64 {
             heap_access_oob,
             unaligned_atomic,
             |this, addr| this.emit_relaxed_ldr32(Size::S64, ret, Location::Memory(addr, 0)),
-        )
+        )?;
+        self.assembler.emit_dmb()
     }
     fn i64_save(
         &mut self,
@@ -5668,7 +5700,10 @@ impl Machine for MachineARM64 {
             offset,
             heap_access_oob,
             unaligned_atomic,
-            |this, addr| this.emit_relaxed_str64(target_value, Location::Memory(addr, 0)),
+            |this, addr| {
+                this.assembler.emit_dmb()?;
+                this.emit_relaxed_str64(target_value, Location::Memory(addr, 0))
+            },
         )?;
         self.assembler.emit_dmb()
     }
@@ -5693,7 +5728,10 @@ impl Machine for MachineARM64 {
             offset,
             heap_access_oob,
             unaligned_atomic,
-            |this, addr| this.emit_relaxed_str8(target_value, Location::Memory(addr, 0)),
+            |this, addr| {
+                this.assembler.emit_dmb()?;
+                this.emit_relaxed_str8(target_value, Location::Memory(addr, 0))
+            },
         )?;
         self.assembler.emit_dmb()
     }
@@ -5718,7 +5756,10 @@ impl Machine for MachineARM64 {
             offset,
             heap_access_oob,
             unaligned_atomic,
-            |this, addr| this.emit_relaxed_str16(target_value, Location::Memory(addr, 0)),
+            |this, addr| {
+                this.assembler.emit_dmb()?;
+                this.emit_relaxed_str16(target_value, Location::Memory(addr, 0))
+            },
         )?;
         self.assembler.emit_dmb()
     }
@@ -5743,7 +5784,10 @@ impl Machine for MachineARM64 {
             offset,
             heap_access_oob,
             unaligned_atomic,
-            |this, addr| this.emit_relaxed_str32(target_value, Location::Memory(addr, 0)),
+            |this, addr| {
+                this.assembler.emit_dmb()?;
+                this.emit_relaxed_str32(target_value, Location::Memory(addr, 0))
+            },
         )?;
         self.assembler.emit_dmb()
     }
@@ -3644,9 +3644,9 @@ impl Machine for MachineX86_64 {
             },
         )
     }
-    // x86_64 have a strong memory model, so coherency between all threads (core) is garantied
-    // and aligned move is guarantied to be atomic, too or from memory
-    // so store/load an atomic is a simple mov on x86_64
+    // x86_64 have a strong memory model, so an aligned load is a simple mov.
+    // Stores can still be reordered with later loads though, so a
+    // sequentially consistent store uses xchg, which has an implicit lock.
     fn i32_atomic_save(
         &mut self,
         value: Location,
@@ -3668,14 +3668,7 @@ impl Machine for MachineX86_64 {
             offset,
             heap_access_oob,
             unaligned_atomic,
-            |this, addr| {
-                this.emit_relaxed_binop(
-                    AssemblerX64::emit_mov,
-                    Size::S32,
-                    value,
-                    Location::Memory(addr, 0),
-                )
-            },
+            |this, addr| this.emit_relaxed_atomic_xchg(Size::S32, value, Location::Memory(addr, 0)),
         )
     }
     fn i32_atomic_save_8(
@@ -3699,14 +3692,7 @@ impl Machine for MachineX86_64 {
             offset,
             heap_access_oob,
             unaligned_atomic,
-            |this, addr| {
-                this.emit_relaxed_binop(
-                    AssemblerX64::emit_mov,
-                    Size::S8,
-                    value,
-                    Location::Memory(addr, 0),
-                )
-            },
+            |this, addr| this.emit_relaxed_atomic_xchg(Size::S8, value, Location::Memory(addr, 0)),
         )
     }
     fn i32_atomic_save_16(
@@ -3730,14 +3716,7 @@ impl Machine for MachineX86_64 {
             offset,
             heap_access_oob,
             unaligned_atomic,
-            |this, addr| {
-                this.emit_relaxed_binop(
-                    AssemblerX64::emit_mov,
-                    Size::S16,
-                    value,
-                    Location::Memory(addr, 0),
-                )
-            },
+            |this, addr| this.emit_relaxed_atomic_xchg(Size::S16, value, Location::Memory(addr, 0)),
         )
     }
     // i32 atomic Add with i32
@@ -4554,6 +4533,34 @@ impl Machine for MachineX86_64 {
         Ok(relocations)
     }
 
+    fn arch_supports_tail_calls(&self) -> bool {
+        true
+    }
+
+    fn emit_tail_call_with_reloc(
+        &mut self,
+        _calling_convention: CallingConvention,
+        reloc_target: RelocationTarget,
+    ) -> Result<Vec<Relocation>, CompileError> {
+        let mut relocations = vec![];
+        let next = self.get_label();
+        let reloc_at = self.assembler.get_offset().0 + 1; // skip E9
+        self.assembler.emit_jmp(Condition::None, next)?;
+        self.emit_label(next)?;
+        // The displacement of the jump is patched like the one of a call
+        relocations.push(Relocation {
+            kind: RelocationKind::X86CallPCRel4,
+            reloc_target,
+            offset: reloc_at as u32,
+            addend: -4,
+        });
+        Ok(relocations)
+    }
+
+    fn emit_tail_call_register(&mut self, reg: GPR) -> Result<(), CompileError> {
+        self.assembler.emit_jmp_location(Location::GPR(reg))
+    }
+
     fn emit_binop_add64(
         &mut self,
         loc_a: Location,
@@ -5551,7 +5558,7 @@ impl Machine for MachineX86_64 {
             target_addr,
             memarg,
             true,
-            2,
+            4,
             need_check,
             imported_memories,
             offset,
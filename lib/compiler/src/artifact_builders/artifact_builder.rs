@@ -1,6 +1,8 @@
 //! Define `ArtifactBuild` to allow compiling and instantiating to be
 //! done as separate steps.
 
+#[cfg(feature = "compiler")]
+use super::debug_info::{translate_dwarf, DebugFunction};
 #[cfg(feature = "compiler")]
 use super::trampoline::{libcall_trampoline_len, make_libcall_trampolines};
 use crate::ArtifactCreate;
@@ -13,14 +15,14 @@ use wasmer_types::entity::PrimaryMap;
 #[cfg(feature = "compiler")]
 use wasmer_types::CompileModuleInfo;
 use wasmer_types::{
-    CompileError, CpuFeature, CustomSection, Dwarf, FunctionIndex, LocalFunctionIndex, MemoryIndex,
-    MemoryStyle, ModuleInfo, OwnedDataInitializer, Relocation, SectionIndex, SignatureIndex,
-    TableIndex, TableStyle, Target,
+    CompileError, CpuFeature, CustomSection, DebugSections, Dwarf, FunctionIndex,
+    LocalFunctionIndex, MemoryIndex, MemoryStyle, ModuleInfo, OwnedDataInitializer, Relocation,
+    SectionIndex, SignatureIndex, TableIndex, TableStyle, Target,
 };
 use wasmer_types::{
     CompiledFunctionFrameInfo, FunctionBody, SerializableCompilation, SerializableModule,
 };
-use wasmer_types::{MetadataHeader, SerializeError};
+use wasmer_types::{MetadataHeader, SerializeError, SerializeOptions};
 
 /// A compiled wasm module, ready to be instantiated.
 pub struct ArtifactBuild {
@@ -52,6 +54,14 @@ impl ArtifactBuild {
 
         let compiler = inner_engine.compiler()?;
 
+        // The bodies are handed over to the compiler, keep what the debug
+        // info needs to know about them.
+        let function_body_ranges = translation
+            .function_body_inputs
+            .values()
+            .map(|body| (body.module_offset, body.data.len()))
+            .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+
         // We try to apply the middleware first
         let mut module = translation.module;
         let middlewares = compiler.get_middlewares();
@@ -101,6 +111,33 @@ impl ArtifactBuild {
         custom_section_relocations.push(libcall_trampolines_section.relocations.clone());
         let libcall_trampolines = custom_sections.push(libcall_trampolines_section);
         let libcall_trampoline_len = libcall_trampoline_len(target) as u32;
+
+        let debug_sections = if inner_engine.debug_info() {
+            let functions = function_body_ranges
+                .iter()
+                .map(|(index, (module_offset, module_len))| DebugFunction {
+                    module_offset: *module_offset,
+                    module_len: *module_len,
+                    native_len: function_bodies[index].body.len(),
+                    address_map: &function_frame_info[index].address_map,
+                })
+                .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+            translate_dwarf(&compile_info.module, &functions).map(|sections| {
+                let [debug_abbrev, debug_info, debug_line, debug_ranges] =
+                    sections.map(|section| {
+                        custom_section_relocations.push(section.relocations.clone());
+                        custom_sections.push(section)
+                    });
+                DebugSections {
+                    debug_abbrev,
+                    debug_info,
+                    debug_line,
+                    debug_ranges,
+                }
+            })
+        } else {
+            None
+        };
         let cpu_features = compiler.get_cpu_features_used(target.cpu_features());
 
         let serializable_compilation = SerializableCompilation {
@@ -112,6 +149,7 @@ impl ArtifactBuild {
             custom_sections,
             custom_section_relocations,
             debug: compilation.debug,
+            debug_sections,
             libcall_trampolines,
             libcall_trampoline_len,
         };
@@ -120,6 +158,7 @@ impl ArtifactBuild {
             compile_info,
             data_initializers,
             cpu_features: cpu_features.as_u64(),
+            triple: target.triple().to_string(),
         };
         Ok(Self { serializable })
     }
@@ -184,11 +223,21 @@ impl ArtifactBuild {
         self.serializable.compilation.libcall_trampoline_len as usize
     }
 
+    /// Get the target triple this artifact was compiled for.
+    pub fn triple(&self) -> &str {
+        &self.serializable.triple
+    }
+
     /// Get Debug optional Dwarf ref
     pub fn get_debug_ref(&self) -> &Option<Dwarf> {
         &self.serializable.compilation.debug
     }
 
+    /// Get the optional native debug info sections
+    pub fn get_debug_sections_ref(&self) -> &Option<DebugSections> {
+        &self.serializable.compilation.debug_sections
+    }
+
     /// Get Function Relocations ref
     pub fn get_frame_info_ref(&self) -> &PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo> {
         &self.serializable.compilation.function_frame_info
@@ -207,6 +256,13 @@ impl ArtifactCreate for ArtifactBuild {
         })
     }
 
+    fn add_module_info_custom_section(&mut self, name: String, data: Box<[u8]>) -> bool {
+        Arc::get_mut(&mut self.serializable.compile_info.module).map_or(false, |module_info| {
+            module_info.add_custom_section(name, data);
+            true
+        })
+    }
+
     fn module_info(&self) -> &ModuleInfo {
         &self.serializable.compile_info.module
     }
@@ -232,7 +288,19 @@ impl ArtifactCreate for ArtifactBuild {
     }
 
     fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
-        let serialized_data = self.serializable.serialize()?;
+        Self::serialize_module(&self.serializable)
+    }
+
+    fn serialize_with(&self, options: &SerializeOptions) -> Result<Vec<u8>, SerializeError> {
+        let mut serializable = self.serializable.clone();
+        serializable.strip(options);
+        Self::serialize_module(&serializable)
+    }
+}
+
+impl ArtifactBuild {
+    fn serialize_module(serializable: &SerializableModule) -> Result<Vec<u8>, SerializeError> {
+        let serialized_data = serializable.serialize()?;
         assert!(std::mem::align_of::<SerializableModule>() <= MetadataHeader::ALIGN);
 
         let mut metadata_binary = vec![];
@@ -0,0 +1,656 @@
+//! Translation of the DWARF sections of a WebAssembly module into native
+//! DWARF describing the compiled code.
+//!
+//! Toolchains such as Clang and Rust emit DWARF for WebAssembly in which
+//! addresses are offsets in the code section of the module. The address
+//! maps of the compiled functions tell which instruction each range of
+//! native code comes from, so the line programs can be rewritten in
+//! terms of native addresses. The result is a single compilation unit
+//! with a subprogram for each function and the line table of all of
+//! them. Variables and types are not translated.
+//!
+//! LLVM only maps each function as a whole, so its functions get a
+//! single row, for the line the function starts at.
+//!
+//! Debug info is only an aid, so malformed DWARF is ignored rather than
+//! reported.
+
+use gimli::write::{
+    self, Address, AttributeValue, EndianVec, FileId, LineProgram, LineString, RangeList, Sections,
+    Writer,
+};
+use gimli::{Encoding, EndianSlice, Format, LittleEndian};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{
+    CustomSection, CustomSectionProtection, FunctionAddressMap, LocalFunctionIndex, ModuleInfo,
+    Relocation, RelocationKind, RelocationTarget, SectionBody, SourceLocation,
+};
+
+/// A compiled function, as described by the debug info.
+pub(crate) struct DebugFunction<'a> {
+    /// The offset of the body of the function in the module.
+    pub(crate) module_offset: usize,
+    /// The length of the body of the function in the module.
+    pub(crate) module_len: usize,
+    /// The length of the compiled code.
+    pub(crate) native_len: usize,
+    pub(crate) address_map: &'a FunctionAddressMap,
+}
+
+/// Translates the DWARF sections of `module`, if it has any.
+///
+/// The sections are returned in the order of
+/// [`DebugSections::sections`](wasmer_types::DebugSections::sections).
+pub(crate) fn translate_dwarf(
+    module: &ModuleInfo,
+    functions: &PrimaryMap<LocalFunctionIndex, DebugFunction>,
+) -> Option<[CustomSection; 4]> {
+    let wasm = WasmDebugInfo::read(module).ok()??;
+    write_dwarf(module, &wasm, module.code_section_offset as u64, functions).ok()
+}
+
+/// The lines of source code of a module, used to symbolicate backtraces.
+#[derive(Debug)]
+pub(crate) struct SourceLines {
+    code_section_offset: u64,
+    info: WasmDebugInfo,
+}
+
+impl SourceLines {
+    /// Reads the line programs of `module`, if it has any.
+    pub(crate) fn read(module: &ModuleInfo) -> Option<Self> {
+        let info = WasmDebugInfo::read(module).ok()??;
+        Some(Self {
+            code_section_offset: module.code_section_offset as u64,
+            info,
+        })
+    }
+
+    /// Returns the line of the instruction at `module_offset`.
+    pub(crate) fn lookup(&self, module_offset: usize) -> Option<SourceLocation> {
+        let address = (module_offset as u64).checked_sub(self.code_section_offset)?;
+        let (file, line, column) = self.info.location(address)?;
+        Some(SourceLocation {
+            file: self.info.files[file].clone(),
+            line,
+            column,
+        })
+    }
+}
+
+/// A line of a source file, as `(file, line, column)`.
+type Location = (usize, u64, u64);
+
+/// A subprogram of the DWARF of the module.
+#[derive(Debug)]
+struct WasmSubprogram {
+    name: Option<String>,
+    linkage_name: Option<String>,
+}
+
+/// What is kept from the DWARF of the module.
+#[derive(Debug, Default)]
+struct WasmDebugInfo {
+    name: Option<String>,
+    comp_dir: Option<String>,
+    language: Option<gimli::DwLang>,
+    /// The paths of the source files.
+    files: Vec<String>,
+    /// The rows of all the line programs, sorted by address. The end of a
+    /// sequence has no location.
+    rows: Vec<(u64, Option<Location>)>,
+    /// The subprograms, keyed by their address.
+    subprograms: BTreeMap<u64, WasmSubprogram>,
+}
+
+impl WasmDebugInfo {
+    fn read(module: &ModuleInfo) -> gimli::Result<Option<Self>> {
+        let sections = module
+            .custom_sections
+            .iter()
+            .filter(|(name, _)| name.starts_with(".debug_"))
+            .map(|(name, index)| (name.as_str(), &module.custom_sections_data[*index][..]))
+            .collect::<HashMap<_, _>>();
+        if !sections.contains_key(".debug_info") {
+            return Ok(None);
+        }
+        let dwarf = gimli::Dwarf::load(|id| -> gimli::Result<_> {
+            let data = sections.get(id.name()).copied().unwrap_or(&[]);
+            Ok(EndianSlice::new(data, LittleEndian))
+        })?;
+
+        let mut info = Self::default();
+        let mut file_ids = HashMap::new();
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let comp_dir = unit.comp_dir.map(|dir| dir.to_string_lossy().into_owned());
+            if info.name.is_none() {
+                info.name = unit.name.map(|name| name.to_string_lossy().into_owned());
+                info.comp_dir = comp_dir.clone();
+            }
+
+            let mut entries = unit.entries();
+            while let Some((_, entry)) = entries.next_dfs()? {
+                if entry.tag() == gimli::DW_TAG_compile_unit && info.language.is_none() {
+                    if let Some(gimli::AttributeValue::Language(language)) =
+                        entry.attr_value(gimli::DW_AT_language)?
+                    {
+                        info.language = Some(language);
+                    }
+                }
+                if entry.tag() != gimli::DW_TAG_subprogram {
+                    continue;
+                }
+                let address = match entry.attr_value(gimli::DW_AT_low_pc)? {
+                    Some(value) => match dwarf.attr_address(&unit, value)? {
+                        Some(address) => address,
+                        None => continue,
+                    },
+                    None => continue,
+                };
+                let string = |attr| -> gimli::Result<Option<String>> {
+                    Ok(match entry.attr_value(attr)? {
+                        Some(value) => Some(
+                            dwarf
+                                .attr_string(&unit, value)?
+                                .to_string_lossy()
+                                .into_owned(),
+                        ),
+                        None => None,
+                    })
+                };
+                let subprogram = WasmSubprogram {
+                    name: string(gimli::DW_AT_name)?,
+                    linkage_name: string(gimli::DW_AT_linkage_name)?,
+                };
+                info.subprograms.insert(address, subprogram);
+            }
+
+            let program = match unit.line_program.clone() {
+                Some(program) => program,
+                None => continue,
+            };
+            let mut rows = program.rows();
+            while let Some((header, row)) = rows.next_row()? {
+                if row.end_sequence() {
+                    info.rows.push((row.address(), None));
+                    continue;
+                }
+                let file = match row.file(header) {
+                    Some(file) => file,
+                    None => continue,
+                };
+                let mut path = Path::new(comp_dir.as_deref().unwrap_or("")).to_path_buf();
+                if let Some(directory) = file.directory(header) {
+                    path.push(&*dwarf.attr_string(&unit, directory)?.to_string_lossy());
+                }
+                path.push(
+                    &*dwarf
+                        .attr_string(&unit, file.path_name())?
+                        .to_string_lossy(),
+                );
+                let path = path.to_string_lossy().into_owned();
+                let file = *file_ids.entry(path.clone()).or_insert_with(|| {
+                    info.files.push(path);
+                    info.files.len() - 1
+                });
+                let line = row.line().map_or(0, |line| line.get());
+                let column = match row.column() {
+                    gimli::ColumnType::LeftEdge => 0,
+                    gimli::ColumnType::Column(column) => column.get(),
+                };
+                info.rows.push((row.address(), Some((file, line, column))));
+            }
+        }
+        // A sequence may start where another one ends.
+        info.rows
+            .sort_by_key(|(address, location)| (*address, location.is_some()));
+        Ok(Some(info))
+    }
+
+    /// Returns the location of the instruction at `address`.
+    fn location(&self, address: u64) -> Option<Location> {
+        let index = self.rows.partition_point(|(row, _)| *row <= address);
+        self.rows.get(index.checked_sub(1)?)?.1
+    }
+
+    /// Returns the subprogram of the function whose body is at `range`.
+    fn subprogram(&self, range: std::ops::Range<u64>) -> Option<&WasmSubprogram> {
+        // Depending on the producer, the address of a function is either
+        // the start of its body or of the size before it, which takes at
+        // most 5 bytes.
+        self.subprograms
+            .range(range.start.saturating_sub(5)..range.end)
+            .next()
+            .map(|(_, subprogram)| subprogram)
+    }
+}
+
+fn write_dwarf(
+    module: &ModuleInfo,
+    wasm: &WasmDebugInfo,
+    code_section_offset: u64,
+    functions: &PrimaryMap<LocalFunctionIndex, DebugFunction>,
+) -> write::Result<[CustomSection; 4]> {
+    let encoding = Encoding {
+        format: Format::Dwarf32,
+        version: 4,
+        address_size: 8,
+    };
+    let name = wasm.name.clone().unwrap_or_else(|| module.name());
+    let comp_dir = wasm.comp_dir.clone().unwrap_or_default();
+    let mut line_program = LineProgram::new(
+        encoding,
+        Default::default(),
+        LineString::String(comp_dir.clone().into_bytes()),
+        LineString::String(name.clone().into_bytes()),
+        None,
+    );
+    let directory = line_program.default_directory();
+    let files = wasm
+        .files
+        .iter()
+        .map(|path| {
+            line_program.add_file(
+                LineString::String(path.clone().into_bytes()),
+                directory,
+                None,
+            )
+        })
+        .collect::<Vec<FileId>>();
+
+    let mut dwarf = write::Dwarf::new();
+    let unit_id = dwarf.units.add(write::Unit::new(encoding, line_program));
+    let unit = dwarf.units.get_mut(unit_id);
+    let root = unit.root();
+    let ranges = functions
+        .iter()
+        .map(|(index, function)| write::Range::StartLength {
+            begin: symbol(index),
+            length: function.native_len as u64,
+        })
+        .collect();
+    let ranges = unit.ranges.add(RangeList(ranges));
+    let root_entry = unit.get_mut(root);
+    root_entry.set(gimli::DW_AT_name, AttributeValue::String(name.into_bytes()));
+    root_entry.set(
+        gimli::DW_AT_comp_dir,
+        AttributeValue::String(comp_dir.into_bytes()),
+    );
+    root_entry.set(
+        gimli::DW_AT_producer,
+        AttributeValue::String(b"wasmer".to_vec()),
+    );
+    if let Some(language) = wasm.language {
+        root_entry.set(gimli::DW_AT_language, AttributeValue::Language(language));
+    }
+    root_entry.set(
+        gimli::DW_AT_low_pc,
+        AttributeValue::Address(Address::Constant(0)),
+    );
+    root_entry.set(gimli::DW_AT_ranges, AttributeValue::RangeListRef(ranges));
+    root_entry.set(gimli::DW_AT_stmt_list, AttributeValue::LineProgramRef);
+
+    for (index, function) in functions.iter() {
+        let start = (function.module_offset as u64).saturating_sub(code_section_offset);
+        let end = start + function.module_len as u64;
+
+        // Rows for the instructions of the function, at native offsets.
+        let mut rows = Vec::new();
+        for instruction in &function.address_map.instructions {
+            if instruction.srcloc.is_default() {
+                continue;
+            }
+            let address = (instruction.srcloc.bits() as u64).saturating_sub(code_section_offset);
+            if let Some(location) = wasm.location(address) {
+                if rows.last().map(|(_, last)| *last) != Some(location) {
+                    rows.push((instruction.code_offset as u64, location));
+                }
+            }
+        }
+        if rows.is_empty() {
+            if let Some(location) = wasm.location(start) {
+                rows.push((0, location));
+            }
+        }
+
+        let func_index = module.func_index(index);
+        let subprogram = wasm.subprogram(start..end);
+        let name = subprogram
+            .and_then(|subprogram| subprogram.name.clone())
+            .or_else(|| module.function_names.get(&func_index).cloned())
+            .unwrap_or_else(|| format!("wasm-function[{}]", func_index.as_u32()));
+        let id = unit.add(root, gimli::DW_TAG_subprogram);
+        let entry = unit.get_mut(id);
+        entry.set(gimli::DW_AT_name, AttributeValue::String(name.into_bytes()));
+        if let Some(linkage_name) = subprogram.and_then(|s| s.linkage_name.clone()) {
+            entry.set(
+                gimli::DW_AT_linkage_name,
+                AttributeValue::String(linkage_name.into_bytes()),
+            );
+        }
+        entry.set(gimli::DW_AT_external, AttributeValue::Flag(true));
+        entry.set(gimli::DW_AT_low_pc, AttributeValue::Address(symbol(index)));
+        entry.set(
+            gimli::DW_AT_high_pc,
+            AttributeValue::Udata(function.native_len as u64),
+        );
+        if let Some((_, (file, line, _))) = rows.first() {
+            entry.set(
+                gimli::DW_AT_decl_file,
+                AttributeValue::FileIndex(Some(files[*file])),
+            );
+            entry.set(gimli::DW_AT_decl_line, AttributeValue::Udata(*line));
+        }
+
+        if rows.is_empty() {
+            continue;
+        }
+        let line_program = &mut unit.line_program;
+        line_program.begin_sequence(Some(symbol(index)));
+        for (code_offset, (file, line, column)) in rows {
+            let row = line_program.row();
+            row.address_offset = code_offset;
+            row.file = files[file];
+            row.line = line;
+            row.column = column;
+            line_program.generate_row();
+        }
+        line_program.end_sequence(function.native_len as u64);
+    }
+
+    let mut sections = Sections::new(RelocWriter::default());
+    dwarf.write(&mut sections)?;
+    Ok([
+        sections.debug_abbrev.0.into_section(),
+        sections.debug_info.0.into_section(),
+        sections.debug_line.0.into_section(),
+        sections.debug_ranges.0.into_section(),
+    ])
+}
+
+/// The address of a compiled function, which is only known once it is
+/// loaded.
+fn symbol(index: LocalFunctionIndex) -> Address {
+    Address::Symbol {
+        symbol: index.index(),
+        addend: 0,
+    }
+}
+
+/// Writes a section, recording the addresses of functions as relocations.
+#[derive(Clone)]
+struct RelocWriter {
+    writer: EndianVec<LittleEndian>,
+    relocations: Vec<Relocation>,
+}
+
+impl Default for RelocWriter {
+    fn default() -> Self {
+        Self {
+            writer: EndianVec::new(LittleEndian),
+            relocations: Vec::new(),
+        }
+    }
+}
+
+impl RelocWriter {
+    fn into_section(self) -> CustomSection {
+        CustomSection {
+            protection: CustomSectionProtection::Read,
+            bytes: SectionBody::new_with_vec(self.writer.into_vec()),
+            relocations: self.relocations,
+        }
+    }
+}
+
+impl Writer for RelocWriter {
+    type Endian = LittleEndian;
+
+    fn endian(&self) -> Self::Endian {
+        LittleEndian
+    }
+
+    fn len(&self) -> usize {
+        self.writer.len()
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> write::Result<()> {
+        self.writer.write(bytes)
+    }
+
+    fn write_at(&mut self, offset: usize, bytes: &[u8]) -> write::Result<()> {
+        self.writer.write_at(offset, bytes)
+    }
+
+    fn write_address(&mut self, address: Address, size: u8) -> write::Result<()> {
+        match address {
+            Address::Constant(value) => self.write_udata(value, size),
+            Address::Symbol { symbol, addend } => {
+                self.relocations.push(Relocation {
+                    kind: RelocationKind::Abs8,
+                    reloc_target: RelocationTarget::LocalFunc(LocalFunctionIndex::new(symbol)),
+                    offset: self.len() as u32,
+                    addend,
+                });
+                self.write_udata(0, size)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmer_types::{InstructionAddressMap, SourceLoc};
+
+    /// Builds the DWARF of a module whose function at offset 10 of the code
+    /// section spans lines 3 and 4 of `/src/main.c`.
+    fn wasm_dwarf(module: &mut ModuleInfo) {
+        let encoding = Encoding {
+            format: Format::Dwarf32,
+            version: 4,
+            address_size: 4,
+        };
+        let mut line_program = LineProgram::new(
+            encoding,
+            Default::default(),
+            LineString::String(b"/src".to_vec()),
+            LineString::String(b"main.c".to_vec()),
+            None,
+        );
+        let directory = line_program.default_directory();
+        let file = line_program.add_file(LineString::String(b"main.c".to_vec()), directory, None);
+        line_program.begin_sequence(Some(Address::Constant(10)));
+        for (address_offset, line) in [(0, 3), (5, 4)] {
+            let row = line_program.row();
+            row.address_offset = address_offset;
+            row.file = file;
+            row.line = line;
+            line_program.generate_row();
+        }
+        line_program.end_sequence(10);
+
+        let mut dwarf = write::Dwarf::new();
+        let unit_id = dwarf.units.add(write::Unit::new(encoding, line_program));
+        let unit = dwarf.units.get_mut(unit_id);
+        let root = unit.root();
+        let root_entry = unit.get_mut(root);
+        root_entry.set(
+            gimli::DW_AT_name,
+            AttributeValue::String(b"main.c".to_vec()),
+        );
+        root_entry.set(
+            gimli::DW_AT_comp_dir,
+            AttributeValue::String(b"/src".to_vec()),
+        );
+        root_entry.set(gimli::DW_AT_stmt_list, AttributeValue::LineProgramRef);
+        let subprogram = unit.add(root, gimli::DW_TAG_subprogram);
+        let entry = unit.get_mut(subprogram);
+        entry.set(gimli::DW_AT_name, AttributeValue::String(b"main".to_vec()));
+        entry.set(
+            gimli::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(10)),
+        );
+
+        let mut sections = Sections::new(EndianVec::new(LittleEndian));
+        dwarf.write(&mut sections).unwrap();
+        sections
+            .for_each(|id, section| -> write::Result<()> {
+                let index = module
+                    .custom_sections_data
+                    .push(section.slice().to_vec().into_boxed_slice());
+                module.custom_sections.insert(id.name().to_string(), index);
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn line_programs_are_translated() {
+        let mut module = ModuleInfo::new();
+        module.code_section_offset = 100;
+        wasm_dwarf(&mut module);
+        let address_map = FunctionAddressMap {
+            instructions: vec![
+                InstructionAddressMap {
+                    srcloc: SourceLoc::new(110),
+                    code_offset: 0,
+                    code_len: 16,
+                },
+                InstructionAddressMap {
+                    srcloc: SourceLoc::new(115),
+                    code_offset: 16,
+                    code_len: 16,
+                },
+            ],
+            body_len: 32,
+            ..Default::default()
+        };
+        let mut functions = PrimaryMap::new();
+        functions.push(DebugFunction {
+            module_offset: 110,
+            module_len: 10,
+            native_len: 32,
+            address_map: &address_map,
+        });
+        let sections = translate_dwarf(&module, &functions).unwrap();
+
+        let names = [
+            ".debug_abbrev",
+            ".debug_info",
+            ".debug_line",
+            ".debug_ranges",
+        ];
+        let dwarf = gimli::Dwarf::load(|id| -> gimli::Result<_> {
+            let data = names
+                .iter()
+                .position(|name| *name == id.name())
+                .map_or(&[][..], |index| sections[index].bytes.as_slice());
+            Ok(EndianSlice::new(data, LittleEndian))
+        })
+        .unwrap();
+        let header = dwarf.units().next().unwrap().unwrap();
+        let unit = dwarf.unit(header).unwrap();
+
+        let mut entries = unit.entries();
+        let mut subprograms = Vec::new();
+        while let Some((_, entry)) = entries.next_dfs().unwrap() {
+            if entry.tag() == gimli::DW_TAG_subprogram {
+                let name = entry.attr_value(gimli::DW_AT_name).unwrap().unwrap();
+                let name = dwarf.attr_string(&unit, name).unwrap();
+                subprograms.push(name.to_string_lossy().into_owned());
+            }
+        }
+        assert_eq!(subprograms, vec!["main".to_string()]);
+
+        let mut rows = unit.line_program.clone().unwrap().rows();
+        let mut lines = Vec::new();
+        while let Some((header, row)) = rows.next_row().unwrap() {
+            let path = row
+                .file(header)
+                .map(|file| dwarf.attr_string(&unit, file.path_name()).unwrap());
+            lines.push((
+                row.address(),
+                row.line().map(|line| line.get()),
+                path.map(|path| path.to_string_lossy().into_owned()),
+                row.end_sequence(),
+            ));
+        }
+        let path = Some("/src/main.c".to_string());
+        assert_eq!(
+            lines,
+            vec![
+                (0, Some(3), path.clone(), false),
+                (16, Some(4), path.clone(), false),
+                // The end of the sequence keeps the registers of the last
+                // row.
+                (32, Some(4), path, true),
+            ]
+        );
+
+        // The function addresses are left for the linker: the start of the
+        // subprogram and of the sequence, and both ends of the range.
+        let relocations = sections
+            .iter()
+            .flat_map(|section| &section.relocations)
+            .filter(|relocation| {
+                relocation.reloc_target == RelocationTarget::LocalFunc(LocalFunctionIndex::new(0))
+            })
+            .count();
+        assert_eq!(relocations, 4);
+    }
+
+    #[test]
+    fn source_lines_are_looked_up() {
+        let mut module = ModuleInfo::new();
+        module.code_section_offset = 100;
+        wasm_dwarf(&mut module);
+        let lines = SourceLines::read(&module).unwrap();
+        let location = |line| {
+            Some(SourceLocation {
+                file: "/src/main.c".to_string(),
+                line,
+                column: 0,
+            })
+        };
+        assert_eq!(lines.lookup(110), location(3));
+        assert_eq!(lines.lookup(114), location(3));
+        assert_eq!(lines.lookup(115), location(4));
+        // Past the end of the sequence, and before the code section.
+        assert_eq!(lines.lookup(120), None);
+        assert_eq!(lines.lookup(50), None);
+        assert!(SourceLines::read(&ModuleInfo::new()).is_none());
+    }
+
+    #[test]
+    fn function_addresses_are_relocated() {
+        let mut writer = RelocWriter::default();
+        writer.write_u8(1).unwrap();
+        writer
+            .write_address(symbol(LocalFunctionIndex::new(3)), 8)
+            .unwrap();
+        writer.write_address(Address::Constant(7), 8).unwrap();
+        let section = writer.into_section();
+        assert_eq!(section.bytes.len(), 17);
+        assert_eq!(&section.bytes.as_slice()[9..], &7u64.to_le_bytes());
+        assert_eq!(
+            section.relocations,
+            vec![Relocation {
+                kind: RelocationKind::Abs8,
+                reloc_target: RelocationTarget::LocalFunc(LocalFunctionIndex::new(3)),
+                offset: 1,
+                addend: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn modules_without_dwarf_have_no_debug_info() {
+        let module = ModuleInfo::new();
+        assert!(translate_dwarf(&module, &PrimaryMap::new()).is_none());
+    }
+}
@@ -1,7 +1,11 @@
 //! Generic Artifact abstraction for Wasmer Engines.
 
 mod artifact_builder;
+#[cfg(feature = "compiler")]
+mod debug_info;
 mod trampoline;
 
 pub use self::artifact_builder::ArtifactBuild;
+#[cfg(feature = "compiler")]
+pub(crate) use self::debug_info::SourceLines;
 pub use self::trampoline::*;
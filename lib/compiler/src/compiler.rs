@@ -4,6 +4,7 @@
 use crate::lib::std::boxed::Box;
 use crate::lib::std::sync::Arc;
 use crate::translator::ModuleMiddleware;
+use crate::validation::wasm_features;
 use crate::FunctionBodyData;
 use crate::ModuleTranslationState;
 use enumset::EnumSet;
@@ -14,7 +15,7 @@ use wasmer_types::compilation::target::Target;
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::error::CompileError;
 use wasmer_types::{CpuFeature, Features, LocalFunctionIndex};
-use wasmparser::{Validator, WasmFeatures};
+use wasmparser::Validator;
 
 /// The compiler configuration options.
 pub trait CompilerConfig {
@@ -56,6 +57,37 @@ pub trait CompilerConfig {
         // in case they create an IR that they can verify.
     }
 
+    /// Lower the relaxed SIMD instructions deterministically.
+    ///
+    /// Relaxed SIMD instructions may give different results on
+    /// different architectures, which lets them use the fastest native
+    /// instructions. When enabled, they are lowered so that they give
+    /// the same results everywhere instead.
+    fn relaxed_simd_deterministic(&mut self, _enable: bool) {
+        // By default we do nothing, each backend will need to customize this
+        // in case it supports the relaxed SIMD instructions.
+    }
+
+    /// Optimize for code size rather than speed.
+    ///
+    /// This is meant for embedders that compile many modules ahead of
+    /// time and care more about the size of the artifacts and of the
+    /// resident code than about peak performance.
+    fn optimize_for_size(&mut self, _enable: bool) {
+        // By default we do nothing, each backend will need to customize this
+        // in case it can optimize for size.
+    }
+
+    /// Inline the small functions of the module, whose body has at most
+    /// `max_operators` operators, at their direct call sites. `0`
+    /// disables inlining.
+    ///
+    /// Only Cranelift inlines functions, the other compilers ignore this.
+    fn inline_budget(&mut self, _max_operators: usize) {
+        // By default we do nothing, each backend will need to customize this
+        // in case it can inline functions.
+    }
+
     /// Gets the custom compiler config
     fn compiler(self: Box<Self>) -> Box<dyn Compiler>;
 
@@ -92,25 +124,7 @@ pub trait Compiler: Send {
         features: &Features,
         data: &'data [u8],
     ) -> Result<(), CompileError> {
-        let wasm_features = WasmFeatures {
-            bulk_memory: features.bulk_memory,
-            threads: features.threads,
-            reference_types: features.reference_types,
-            multi_value: features.multi_value,
-            simd: features.simd,
-            tail_call: features.tail_call,
-            multi_memory: features.multi_memory,
-            memory64: features.memory64,
-            exceptions: features.exceptions,
-            deterministic_only: false,
-            extended_const: features.extended_const,
-            relaxed_simd: features.relaxed_simd,
-            mutable_global: true,
-            saturating_float_to_int: true,
-            sign_extension: true,
-            component_model: false,
-        };
-        let mut validator = Validator::new_with_features(wasm_features);
+        let mut validator = Validator::new_with_features(wasm_features(features));
         validator
             .validate_all(data)
             .map_err(|e| CompileError::Validate(format!("{}", e)))?;
@@ -7,8 +7,8 @@ use crate::ArtifactCreate;
 use crate::Features;
 use crate::ModuleEnvironment;
 use crate::{
-    register_frame_info, resolve_imports, FunctionExtent, GlobalFrameInfoRegistration,
-    InstantiationError, Tunables,
+    build_elf_image, register_frame_info, resolve_imports, FunctionExtent, GdbJitImageRegistration,
+    GlobalFrameInfoRegistration, InstantiationError, ProfiledFunction, ProfilingStrategy, Tunables,
 };
 #[cfg(feature = "static-artifact-create")]
 use crate::{Compiler, FunctionBodyData, ModuleTranslationState};
@@ -16,6 +16,7 @@ use crate::{Engine, EngineInner};
 use enumset::EnumSet;
 #[cfg(any(feature = "static-artifact-create", feature = "static-artifact-load"))]
 use std::mem;
+use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -31,11 +32,13 @@ use wasmer_types::MetadataHeader;
 use wasmer_types::SerializableCompilation;
 use wasmer_types::{
     CompileError, CpuFeature, DataInitializer, DeserializeError, FunctionIndex, LocalFunctionIndex,
-    MemoryIndex, ModuleInfo, OwnedDataInitializer, SignatureIndex, TableIndex, Target,
+    MemoryIndex, ModuleInfo, OwnedDataInitializer, SignatureIndex, TableIndex, Target, Triple,
 };
-use wasmer_types::{SerializableModule, SerializeError};
+use wasmer_types::{SerializableModule, SerializeError, SerializeOptions};
 use wasmer_vm::{FunctionBodyPtr, MemoryStyle, TableStyle, VMSharedSignatureIndex, VMTrampoline};
-use wasmer_vm::{InstanceAllocator, StoreObjects, TrapHandlerFn, VMExtern, VMInstance};
+use wasmer_vm::{
+    InstanceAllocator, MemoryImages, StoreObjects, TrapHandlerFn, VMExtern, VMInstance,
+};
 
 pub struct AllocatedArtifact {
     finished_functions: BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
@@ -45,6 +48,9 @@ pub struct AllocatedArtifact {
     /// Some(_) only if this is not a deserialized static artifact
     frame_info_registration: Option<Mutex<Option<GlobalFrameInfoRegistration>>>,
     finished_function_lengths: BoxedSlice<LocalFunctionIndex, usize>,
+    /// Some(_) only if the artifact has debug info
+    _gdb_jit_registration: Option<GdbJitImageRegistration>,
+    memory_images: MemoryImages,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -99,8 +105,8 @@ impl Artifact {
         let module = translation.module;
         let memory_styles: PrimaryMap<MemoryIndex, MemoryStyle> = module
             .memories
-            .values()
-            .map(|memory_type| tunables.memory_style(memory_type))
+            .iter()
+            .map(|(index, memory_type)| tunables.module_memory_style(index, memory_type))
             .collect();
         let table_styles: PrimaryMap<TableIndex, TableStyle> = module
             .tables
@@ -163,6 +169,7 @@ impl Artifact {
 
         let serializable = SerializableModule::deserialize_checked(metadata_slice)?;
         let artifact = ArtifactBuild::from_serializable(serializable);
+        Self::check_triple(&artifact, engine.target())?;
         let mut inner_engine = engine.inner_mut();
         Self::from_parts(&mut inner_engine, artifact, engine.target())
             .map_err(DeserializeError::Compiler)
@@ -196,11 +203,31 @@ impl Artifact {
 
         let serializable = SerializableModule::deserialize(metadata_slice)?;
         let artifact = ArtifactBuild::from_serializable(serializable);
+        Self::check_triple(&artifact, engine.target())?;
         let mut inner_engine = engine.inner_mut();
         Self::from_parts(&mut inner_engine, artifact, engine.target())
             .map_err(DeserializeError::Compiler)
     }
 
+    /// Checks that an artifact compiled for another architecture or
+    /// operating system than the target of the engine is not loaded,
+    /// as its code would be run.
+    fn check_triple(artifact: &ArtifactBuild, target: &Target) -> Result<(), DeserializeError> {
+        let triple = Triple::from_str(artifact.triple()).map_err(|e| {
+            DeserializeError::CorruptedBinary(format!("invalid target triple: {}", e))
+        })?;
+        let expected = target.triple();
+        if triple.architecture != expected.architecture
+            || triple.operating_system != expected.operating_system
+        {
+            return Err(DeserializeError::Incompatible(format!(
+                "The artifact was compiled for `{}` but the engine targets `{}`",
+                triple, expected
+            )));
+        }
+        Ok(())
+    }
+
     /// Construct a `ArtifactBuild` from component parts.
     pub fn from_parts(
         engine_inner: &mut EngineInner,
@@ -266,6 +293,35 @@ impl Artifact {
 
         engine_inner.publish_eh_frame(eh_frame)?;
 
+        Self::register_with_profiler(engine_inner, module_info, &finished_functions);
+
+        let gdb_jit_registration = artifact.get_debug_sections_ref().as_ref().map(|debug| {
+            let code_start = finished_functions
+                .values()
+                .map(|extent| *extent.ptr as usize)
+                .min()
+                .unwrap_or(0);
+            let code_end = finished_functions
+                .values()
+                .map(|extent| *extent.ptr as usize + extent.length)
+                .max()
+                .unwrap_or(0);
+            let code = unsafe {
+                std::slice::from_raw_parts(code_start as *const u8, code_end - code_start)
+            };
+            let sections = debug
+                .sections()
+                .iter()
+                .map(|(name, index)| {
+                    let size = artifact.get_custom_sections_ref()[*index].bytes.len();
+                    (*name, unsafe {
+                        std::slice::from_raw_parts(*custom_sections[*index], size)
+                    })
+                })
+                .collect::<Vec<_>>();
+            GdbJitImageRegistration::register(build_elf_image(code, &sections))
+        });
+
         let finished_function_lengths = finished_functions
             .values()
             .map(|extent| extent.length)
@@ -281,6 +337,7 @@ impl Artifact {
         let finished_dynamic_function_trampolines =
             finished_dynamic_function_trampolines.into_boxed_slice();
         let signatures = signatures.into_boxed_slice();
+        let memory_images = Self::build_memory_images(&artifact);
 
         Ok(Self {
             id: Default::default(),
@@ -292,10 +349,61 @@ impl Artifact {
                 signatures,
                 frame_info_registration: Some(Mutex::new(None)),
                 finished_function_lengths,
+                _gdb_jit_registration: gdb_jit_registration,
+                memory_images,
             }),
         })
     }
 
+    /// Builds the copy-on-write images of the memories of the module,
+    /// shared by all its instances.
+    fn build_memory_images(artifact: &ArtifactBuild) -> MemoryImages {
+        let data_initializers = artifact
+            .data_initializers()
+            .iter()
+            .map(|init| DataInitializer {
+                location: init.location.clone(),
+                data: &init.data,
+            })
+            .collect::<Vec<_>>();
+        MemoryImages::new(artifact.module_info(), &data_initializers)
+    }
+
+    /// Reports the published functions to the profiler of the engine.
+    fn register_with_profiler(
+        engine_inner: &EngineInner,
+        module_info: &ModuleInfo,
+        finished_functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    ) {
+        let profiling_strategy = engine_inner.profiling_strategy();
+        if profiling_strategy == ProfilingStrategy::None {
+            return;
+        }
+        let module_name = module_info.name();
+        let functions = finished_functions
+            .iter()
+            .map(|(local_index, extent)| {
+                let index = module_info.func_index(local_index);
+                let name = match module_info.function_names.get(&index) {
+                    Some(name) => format!("{} ({}[{}])", name, module_name, index.as_u32()),
+                    None => format!("<unnamed> ({}[{}])", module_name, index.as_u32()),
+                };
+                ProfiledFunction {
+                    name,
+                    code: unsafe {
+                        std::slice::from_raw_parts(*extent.ptr as *const u8, extent.length)
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+        if let Err(err) = profiling_strategy.register(&functions) {
+            tracing::warn!(
+                "could not report the compiled functions to the profiler: {}",
+                err
+            );
+        }
+    }
+
     /// Check if the provided bytes look like a serialized `ArtifactBuild`.
     pub fn is_deserializable(bytes: &[u8]) -> bool {
         ArtifactBuild::is_deserializable(bytes)
@@ -323,6 +431,10 @@ impl ArtifactCreate for Artifact {
         self.artifact.set_module_info_name(name)
     }
 
+    fn add_module_info_custom_section(&mut self, name: String, data: Box<[u8]>) -> bool {
+        self.artifact.add_module_info_custom_section(name, data)
+    }
+
     fn create_module_info(&self) -> Arc<ModuleInfo> {
         self.artifact.create_module_info()
     }
@@ -354,6 +466,10 @@ impl ArtifactCreate for Artifact {
     fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
         self.artifact.serialize()
     }
+
+    fn serialize_with(&self, options: &SerializeOptions) -> Result<Vec<u8>, SerializeError> {
+        self.artifact.serialize_with(options)
+    }
 }
 
 impl Artifact {
@@ -539,6 +655,25 @@ impl Artifact {
     pub unsafe fn finish_instantiation(
         &self,
         trap_handler: Option<*const TrapHandlerFn<'static>>,
+        stack_size: Option<usize>,
+        handle: &mut VMInstance,
+    ) -> Result<(), InstantiationError> {
+        self.initialize_instance(handle)?;
+        handle
+            .invoke_start(trap_handler, stack_size)
+            .map_err(InstantiationError::Start)
+    }
+
+    /// Initializes the tables and memories of an instance, without running
+    /// its start function, which can be run later with
+    /// [`VMInstance::invoke_start`].
+    ///
+    /// # Safety
+    ///
+    /// See [`VMInstance::initialize`].
+    #[allow(clippy::result_large_err)]
+    pub unsafe fn initialize_instance(
+        &self,
         handle: &mut VMInstance,
     ) -> Result<(), InstantiationError> {
         let data_initializers = self
@@ -549,8 +684,13 @@ impl Artifact {
                 data: &init.data,
             })
             .collect::<Vec<_>>();
+        let memory_images = &self
+            .allocated
+            .as_ref()
+            .expect("It must be allocated")
+            .memory_images;
         handle
-            .finish_instantiation(trap_handler, &data_initializers)
+            .initialize_with_images(&data_initializers, memory_images)
             .map_err(InstantiationError::Start)
     }
 
@@ -582,8 +722,8 @@ impl Artifact {
 
         let memory_styles: PrimaryMap<MemoryIndex, MemoryStyle> = module
             .memories
-            .values()
-            .map(|memory_type| tunables.memory_style(memory_type))
+            .iter()
+            .map(|(index, memory_type)| tunables.module_memory_style(index, memory_type))
             .collect();
         let table_styles: PrimaryMap<TableIndex, TableStyle> = module
             .tables
@@ -676,6 +816,35 @@ impl Artifact {
         ),
         CompileError,
     > {
+        let mut obj = get_object_for_target(target.triple())
+            .map_err(|err| CompileError::Codegen(format!("{}", err)))?;
+        let (module_info, metadata_length, symbol_registry) = Self::generate_object_into(
+            &mut obj,
+            compiler,
+            data,
+            metadata_prefix,
+            target,
+            tunables,
+            features,
+        )?;
+        Ok((module_info, obj, metadata_length, symbol_registry))
+    }
+
+    /// Compile a module into an existing object file, next to the modules
+    /// that were already compiled into it.
+    ///
+    /// Each module of an object must have its own `metadata_prefix`, so that
+    /// their symbols don't collide. See [`Artifact::generate_object`].
+    #[cfg(feature = "static-artifact-create")]
+    pub fn generate_object_into(
+        obj: &mut Object,
+        compiler: &dyn Compiler,
+        data: &[u8],
+        metadata_prefix: Option<&str>,
+        target: &Target,
+        tunables: &dyn Tunables,
+        features: &Features,
+    ) -> Result<(ModuleInfo, usize, Box<dyn wasmer_types::SymbolRegistry>), CompileError> {
         use wasmer_types::{compilation::symbols::ModuleMetadataSymbolRegistry, SymbolRegistry};
 
         fn to_compile_error(err: impl std::error::Error) -> CompileError {
@@ -718,21 +887,17 @@ impl Artifact {
                 module_translation.as_ref().unwrap(),
                 function_body_inputs,
             )?;
-        let mut obj = get_object_for_target(target_triple).map_err(to_compile_error)?;
-
         let object_name = ModuleMetadataSymbolRegistry {
             prefix: metadata_prefix.unwrap_or_default().to_string(),
         }
         .symbol_to_name(wasmer_types::Symbol::Metadata);
 
-        emit_data(&mut obj, object_name.as_bytes(), &metadata_binary, 1)
-            .map_err(to_compile_error)?;
+        emit_data(obj, object_name.as_bytes(), &metadata_binary, 1).map_err(to_compile_error)?;
 
-        emit_compilation(&mut obj, compilation, &symbol_registry, target_triple)
+        emit_compilation(obj, compilation, &symbol_registry, target_triple)
             .map_err(to_compile_error)?;
         Ok((
             Arc::try_unwrap(metadata.compile_info.module).unwrap(),
-            obj,
             metadata_binary.len(),
             Box::new(symbol_registry),
         ))
@@ -859,6 +1024,8 @@ impl Artifact {
             compile_info: metadata.compile_info,
             data_initializers: metadata.data_initializers,
             cpu_features: metadata.cpu_features,
+            // Static objects are linked into the host executable.
+            triple: Triple::host().to_string(),
         });
 
         let finished_function_lengths = finished_functions
@@ -866,6 +1033,7 @@ impl Artifact {
             .map(|_| 0)
             .collect::<PrimaryMap<LocalFunctionIndex, usize>>()
             .into_boxed_slice();
+        let memory_images = Self::build_memory_images(&artifact);
 
         Ok(Self {
             id: Default::default(),
@@ -879,6 +1047,8 @@ impl Artifact {
                 signatures: signatures.into_boxed_slice(),
                 finished_function_lengths,
                 frame_info_registration: None,
+                _gdb_jit_registration: None,
+                memory_images,
             }),
         })
     }
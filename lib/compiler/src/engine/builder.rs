@@ -1,6 +1,7 @@
 use super::Engine;
 use crate::CompilerConfig;
-use wasmer_types::{Features, Target};
+use enumset::EnumSet;
+use wasmer_types::{CpuFeature, Features, Target, Triple};
 
 /// The Builder contents of `Engine`
 pub struct EngineBuilder {
@@ -40,12 +41,40 @@ impl EngineBuilder {
         self
     }
 
+    /// Set a target for cross-compilation, given its triple and the CPU
+    /// features to compile for.
+    ///
+    /// The features every CPU of `triple` supports are always added, see
+    /// [`CpuFeature::baseline`]. Modules compiled by the resulting engine
+    /// can be serialized, but they can only be instantiated on a host
+    /// matching the target.
+    pub fn set_target_triple(self, triple: Triple, cpu_features: EnumSet<CpuFeature>) -> Self {
+        let cpu_features = cpu_features | CpuFeature::baseline(&triple);
+        self.set_target(Some(Target::new(triple, cpu_features)))
+    }
+
     /// Set the features
     pub fn set_features(mut self, features: Option<Features>) -> Self {
         self.features = features;
         self
     }
 
+    /// Canonicalize the NaNs produced by float operations.
+    ///
+    /// The bits of a NaN produced by a float operation depend on the
+    /// hardware running it. When enabled, every NaN whose bits can be
+    /// observed is replaced by the canonical NaN, so that a module gives
+    /// the same results on every platform. This is meant for embedders
+    /// that need consensus between nodes, such as blockchains.
+    ///
+    /// This does nothing for a headless engine.
+    pub fn canonicalize_nans(mut self, enable: bool) -> Self {
+        if let Some(compiler_config) = self.compiler_config.as_mut() {
+            compiler_config.canonicalize_nans(enable);
+        }
+        self
+    }
+
     /// Build the `Engine` for this configuration
     #[cfg(feature = "compiler")]
     pub fn engine(self) -> Engine {
@@ -56,14 +85,14 @@ impl EngineBuilder {
                 .unwrap_or_else(|| compiler_config.default_features_for_target(&target));
             Engine::new(compiler_config, target, features)
         } else {
-            Engine::headless()
+            Engine::headless_for_target(target)
         }
     }
 
     /// Build the `Engine` for this configuration
     #[cfg(not(feature = "compiler"))]
     pub fn engine(self) -> Engine {
-        Engine::headless()
+        Engine::headless_for_target(self.target.unwrap_or_default())
     }
 
     /// The Wasm features
@@ -155,7 +155,8 @@ impl CodeMemory {
     /// Calculates the allocation size of the given compiled function.
     fn function_allocation_size(func: &FunctionBody) -> usize {
         match &func.unwind_info {
-            Some(CompiledFunctionUnwindInfo::WindowsX64(info)) => {
+            Some(CompiledFunctionUnwindInfo::WindowsX64(info))
+            | Some(CompiledFunctionUnwindInfo::WindowsArm64(info)) => {
                 // Windows unwind information is required to be emitted into code memory
                 // This is because it must be a positive relative offset from the start of the memory
                 // Account for necessary unwind information alignment padding (32-bit alignment)
@@ -181,7 +182,9 @@ impl CodeMemory {
         body.copy_from_slice(&func.body);
         let vmfunc = Self::view_as_mut_vmfunc_slice(body);
 
-        if let Some(CompiledFunctionUnwindInfo::WindowsX64(info)) = &func.unwind_info {
+        if let Some(CompiledFunctionUnwindInfo::WindowsX64(info))
+        | Some(CompiledFunctionUnwindInfo::WindowsArm64(info)) = &func.unwind_info
+        {
             // Windows unwind information is written following the function body
             // Keep unwind information 32-bit aligned (round up to the nearest 4 byte boundary)
             let unwind_start = (func_len + 3) & !3;
@@ -0,0 +1,300 @@
+//! Registration of native debug info with the [GDB JIT interface].
+//!
+//! Debuggers put a breakpoint on `__jit_debug_register_code`, and read the
+//! in-memory object files linked from `__jit_debug_descriptor` when it is
+//! hit. Both GDB and LLDB support it.
+//!
+//! An artifact with debug info registers an ELF image holding its DWARF
+//! sections, already relocated to the addresses of its functions, and a
+//! `.text` section spanning its code.
+//!
+//! [GDB JIT interface]: https://sourceware.org/gdb/onlinedocs/gdb/JIT-Interface.html
+
+use std::ptr;
+use std::sync::Mutex;
+
+const JIT_NOACTION: u32 = 0;
+const JIT_REGISTER_FN: u32 = 1;
+const JIT_UNREGISTER_FN: u32 = 2;
+
+#[repr(C)]
+struct JitCodeEntry {
+    next_entry: *mut JitCodeEntry,
+    prev_entry: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+#[repr(C)]
+struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+/// The list of registered images, read by the debugger.
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+static mut __jit_debug_descriptor: JitDescriptor = JitDescriptor {
+    version: 1,
+    action_flag: JIT_NOACTION,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+/// The debugger puts a breakpoint here to be notified of changes to the
+/// list of images.
+#[no_mangle]
+#[inline(never)]
+extern "C" fn __jit_debug_register_code() {
+    // Keep the function from being merged with another or optimized away.
+    unsafe { ptr::read_volatile(&0u8) };
+}
+
+/// Serializes the changes to `__jit_debug_descriptor`.
+static DESCRIPTOR_LOCK: Mutex<()> = Mutex::new(());
+
+/// An image registered with the debugger, until it is dropped.
+pub(crate) struct GdbJitImageRegistration {
+    entry: Box<JitCodeEntry>,
+    _image: Box<[u8]>,
+}
+
+// The entry is only accessed under `DESCRIPTOR_LOCK`.
+unsafe impl Send for GdbJitImageRegistration {}
+unsafe impl Sync for GdbJitImageRegistration {}
+
+impl GdbJitImageRegistration {
+    /// Registers an ELF image with the debugger.
+    pub(crate) fn register(image: Box<[u8]>) -> Self {
+        let mut entry = Box::new(JitCodeEntry {
+            next_entry: ptr::null_mut(),
+            prev_entry: ptr::null_mut(),
+            symfile_addr: image.as_ptr(),
+            symfile_size: image.len() as u64,
+        });
+        let _guard = DESCRIPTOR_LOCK.lock().unwrap();
+        unsafe {
+            let entry_ptr: *mut JitCodeEntry = &mut *entry;
+            entry.next_entry = __jit_debug_descriptor.first_entry;
+            if !entry.next_entry.is_null() {
+                (*entry.next_entry).prev_entry = entry_ptr;
+            }
+            __jit_debug_descriptor.first_entry = entry_ptr;
+            __jit_debug_descriptor.relevant_entry = entry_ptr;
+            __jit_debug_descriptor.action_flag = JIT_REGISTER_FN;
+            __jit_debug_register_code();
+            __jit_debug_descriptor.action_flag = JIT_NOACTION;
+            __jit_debug_descriptor.relevant_entry = ptr::null_mut();
+        }
+        Self {
+            entry,
+            _image: image,
+        }
+    }
+}
+
+impl Drop for GdbJitImageRegistration {
+    fn drop(&mut self) {
+        let _guard = DESCRIPTOR_LOCK.lock().unwrap();
+        unsafe {
+            let entry_ptr: *mut JitCodeEntry = &mut *self.entry;
+            if self.entry.prev_entry.is_null() {
+                __jit_debug_descriptor.first_entry = self.entry.next_entry;
+            } else {
+                (*self.entry.prev_entry).next_entry = self.entry.next_entry;
+            }
+            if !self.entry.next_entry.is_null() {
+                (*self.entry.next_entry).prev_entry = self.entry.prev_entry;
+            }
+            __jit_debug_descriptor.relevant_entry = entry_ptr;
+            __jit_debug_descriptor.action_flag = JIT_UNREGISTER_FN;
+            __jit_debug_register_code();
+            __jit_debug_descriptor.action_flag = JIT_NOACTION;
+            __jit_debug_descriptor.relevant_entry = ptr::null_mut();
+        }
+    }
+}
+
+/// The `e_machine` of ELF files for the host.
+pub(crate) fn elf_machine() -> u16 {
+    if cfg!(target_arch = "x86_64") {
+        62
+    } else if cfg!(target_arch = "aarch64") {
+        183
+    } else if cfg!(target_arch = "riscv64") {
+        243
+    } else {
+        0
+    }
+}
+
+const ELF_HEADER_SIZE: usize = 64;
+const SECTION_HEADER_SIZE: usize = 64;
+const SHT_PROGBITS: u32 = 1;
+const SHT_STRTAB: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+
+struct SectionHeader {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+}
+
+/// Builds an ELF image with a `.text` section at the address of the code,
+/// for the debugger to know which code the image describes, and the
+/// given debug sections.
+pub(crate) fn build_elf_image(code: &[u8], sections: &[(&str, &[u8])]) -> Box<[u8]> {
+    let mut data = vec![0; ELF_HEADER_SIZE];
+    let mut names = vec![0];
+    let mut add_name = |name: &str| {
+        let offset = names.len() as u32;
+        names.extend_from_slice(name.as_bytes());
+        names.push(0);
+        offset
+    };
+
+    let mut headers = vec![SectionHeader {
+        name: add_name(".text"),
+        kind: SHT_NOBITS,
+        flags: SHF_ALLOC | SHF_EXECINSTR,
+        addr: code.as_ptr() as u64,
+        offset: ELF_HEADER_SIZE as u64,
+        size: code.len() as u64,
+    }];
+    for (name, bytes) in sections {
+        headers.push(SectionHeader {
+            name: add_name(name),
+            kind: SHT_PROGBITS,
+            flags: 0,
+            addr: 0,
+            offset: data.len() as u64,
+            size: bytes.len() as u64,
+        });
+        data.extend_from_slice(bytes);
+    }
+    let shstrtab_name = add_name(".shstrtab");
+    headers.push(SectionHeader {
+        name: shstrtab_name,
+        kind: SHT_STRTAB,
+        flags: 0,
+        addr: 0,
+        offset: data.len() as u64,
+        size: names.len() as u64,
+    });
+    data.extend_from_slice(&names);
+    while data.len() % 8 != 0 {
+        data.push(0);
+    }
+
+    let section_headers_offset = data.len() as u64;
+    // The null section.
+    data.extend_from_slice(&[0; SECTION_HEADER_SIZE]);
+    for header in &headers {
+        data.extend_from_slice(&header.name.to_le_bytes());
+        data.extend_from_slice(&header.kind.to_le_bytes());
+        data.extend_from_slice(&header.flags.to_le_bytes());
+        data.extend_from_slice(&header.addr.to_le_bytes());
+        data.extend_from_slice(&header.offset.to_le_bytes());
+        data.extend_from_slice(&header.size.to_le_bytes());
+        // sh_link and sh_info.
+        data.extend_from_slice(&[0; 8]);
+        // sh_addralign.
+        data.extend_from_slice(&1u64.to_le_bytes());
+        // sh_entsize.
+        data.extend_from_slice(&0u64.to_le_bytes());
+    }
+
+    let section_count = headers.len() as u16 + 1;
+    let mut header = Vec::with_capacity(ELF_HEADER_SIZE);
+    // Magic, 64-bit, little endian, version 1, System V ABI.
+    header.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    header.extend_from_slice(&[0; 8]);
+    // ET_EXEC: the addresses are final.
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&elf_machine().to_le_bytes());
+    header.extend_from_slice(&1u32.to_le_bytes());
+    // e_entry and e_phoff.
+    header.extend_from_slice(&[0; 16]);
+    header.extend_from_slice(&section_headers_offset.to_le_bytes());
+    // e_flags.
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&(ELF_HEADER_SIZE as u16).to_le_bytes());
+    // e_phentsize and e_phnum.
+    header.extend_from_slice(&[0; 4]);
+    header.extend_from_slice(&(SECTION_HEADER_SIZE as u16).to_le_bytes());
+    header.extend_from_slice(&section_count.to_le_bytes());
+    // `.shstrtab` is the last section.
+    header.extend_from_slice(&(section_count - 1).to_le_bytes());
+    data[..ELF_HEADER_SIZE].copy_from_slice(&header);
+    data.into_boxed_slice()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn u16_at(image: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(image[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn u64_at(image: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(image[offset..offset + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn elf_image_layout() {
+        let code = [0xc3; 16];
+        let image = build_elf_image(&code, &[(".debug_info", &[1, 2, 3])]);
+        assert_eq!(&image[..4], b"\x7fELF");
+        // The null section, `.text`, `.debug_info` and `.shstrtab`.
+        assert_eq!(u16_at(&image, 60), 4);
+        assert_eq!(u16_at(&image, 62), 3);
+
+        let section_headers = u64_at(&image, 40) as usize;
+        assert_eq!(section_headers % 8, 0);
+        let text = section_headers + SECTION_HEADER_SIZE;
+        assert_eq!(u64_at(&image, text + 16), code.as_ptr() as u64);
+        assert_eq!(u64_at(&image, text + 32), 16);
+        let debug_info = text + SECTION_HEADER_SIZE;
+        let offset = u64_at(&image, debug_info + 24) as usize;
+        assert_eq!(&image[offset..offset + 3], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn registration_links_entries() {
+        let first = GdbJitImageRegistration::register(vec![1].into_boxed_slice());
+        let second = GdbJitImageRegistration::register(vec![2].into_boxed_slice());
+        let _guard = DESCRIPTOR_LOCK.lock().unwrap();
+        unsafe {
+            let mut entry = __jit_debug_descriptor.first_entry;
+            let mut found = 0;
+            while !entry.is_null() {
+                let addr = (*entry).symfile_addr;
+                if addr == first._image.as_ptr() || addr == second._image.as_ptr() {
+                    found += 1;
+                }
+                entry = (*entry).next_entry;
+            }
+            assert_eq!(found, 2);
+        }
+        drop(_guard);
+        let first_addr = first._image.as_ptr();
+        drop(first);
+        let _guard = DESCRIPTOR_LOCK.lock().unwrap();
+        unsafe {
+            let mut entry = __jit_debug_descriptor.first_entry;
+            while !entry.is_null() {
+                assert_ne!((*entry).symfile_addr, first_addr);
+                entry = (*entry).next_entry;
+            }
+        }
+    }
+}
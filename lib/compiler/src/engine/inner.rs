@@ -8,9 +8,9 @@ use crate::BaseTunables;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::CodeMemory;
 #[cfg(feature = "compiler")]
-use crate::{Compiler, CompilerConfig};
+use crate::{validate_module_detailed, Compiler, CompilerConfig, ValidationReport};
 #[cfg(not(target_arch = "wasm32"))]
-use crate::{FunctionExtent, Tunables};
+use crate::{FunctionExtent, ProfilingStrategy, Tunables};
 #[cfg(not(target_arch = "wasm32"))]
 use memmap2::Mmap;
 #[cfg(not(target_arch = "wasm32"))]
@@ -19,8 +19,8 @@ use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 use std::sync::{Arc, Mutex};
 #[cfg(not(target_arch = "wasm32"))]
 use wasmer_types::{
-    entity::PrimaryMap, DeserializeError, FunctionBody, FunctionIndex, FunctionType,
-    LocalFunctionIndex, SignatureIndex,
+    entity::PrimaryMap, ArtifactVariants, DeserializeError, FunctionBody, FunctionIndex,
+    FunctionType, LocalFunctionIndex, SignatureIndex,
 };
 use wasmer_types::{CompileError, Features, ModuleInfo, Target};
 #[cfg(not(target_arch = "wasm32"))]
@@ -59,10 +59,13 @@ impl Engine {
             inner: Arc::new(Mutex::new(EngineInner {
                 compiler: Some(compiler),
                 features,
+                debug_info: false,
                 #[cfg(not(target_arch = "wasm32"))]
                 code_memory: vec![],
                 #[cfg(not(target_arch = "wasm32"))]
                 signatures: SignatureRegistry::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                profiling_strategy: ProfilingStrategy::default(),
             })),
             target: Arc::new(target),
             engine_id: EngineId::default(),
@@ -109,7 +112,15 @@ impl Engine {
     /// Headless engines can't compile or validate any modules,
     /// they just take already processed Modules (via `Module::serialize`).
     pub fn headless() -> Self {
-        let target = Target::default();
+        Self::headless_for_target(Target::default())
+    }
+
+    /// Create a headless `Engine` that loads artifacts compiled for
+    /// `target`.
+    ///
+    /// Artifacts for a target other than the host can be deserialized
+    /// and inspected, but not instantiated.
+    pub fn headless_for_target(target: Target) -> Self {
         #[cfg(not(target_arch = "wasm32"))]
         let tunables = BaseTunables::for_target(&target);
         Self {
@@ -118,10 +129,14 @@ impl Engine {
                 compiler: None,
                 #[cfg(feature = "compiler")]
                 features: Features::default(),
+                #[cfg(feature = "compiler")]
+                debug_info: false,
                 #[cfg(not(target_arch = "wasm32"))]
                 code_memory: vec![],
                 #[cfg(not(target_arch = "wasm32"))]
                 signatures: SignatureRegistry::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                profiling_strategy: ProfilingStrategy::default(),
             })),
             target: Arc::new(target),
             engine_id: EngineId::default(),
@@ -166,6 +181,13 @@ impl Engine {
         self.inner().validate(binary)
     }
 
+    /// Validates a WebAssembly module, describing precisely why it is
+    /// invalid and which features it requires.
+    #[cfg(feature = "compiler")]
+    pub fn validate_detailed(&self, binary: &[u8]) -> Result<ValidationReport, CompileError> {
+        self.inner().validate_detailed(binary)
+    }
+
     /// Compile a WebAssembly binary
     #[cfg(feature = "compiler")]
     #[cfg(not(target_arch = "wasm32"))]
@@ -197,15 +219,41 @@ impl Engine {
     ///
     /// The serialized content must represent a serialized WebAssembly module.
     pub unsafe fn deserialize(&self, bytes: &[u8]) -> Result<Arc<Artifact>, DeserializeError> {
+        if ArtifactVariants::is_variants(bytes) {
+            let variant = self.select_variant(bytes)?;
+            return Ok(Arc::new(Artifact::deserialize(self, &variant)?));
+        }
         Ok(Arc::new(Artifact::deserialize(self, bytes)?))
     }
 
     /// Deserializes a WebAssembly module
     #[cfg(not(target_arch = "wasm32"))]
     pub fn deserialize_checked(&self, bytes: &[u8]) -> Result<Arc<Artifact>, DeserializeError> {
+        if ArtifactVariants::is_variants(bytes) {
+            let variant = self.select_variant(bytes)?;
+            return Ok(Arc::new(Artifact::deserialize_checked(self, &variant)?));
+        }
         Ok(Arc::new(Artifact::deserialize_checked(self, bytes)?))
     }
 
+    /// Picks the variant of serialized [`ArtifactVariants`] that makes
+    /// the most of the CPU features of the target of this engine.
+    ///
+    /// The variant is copied so that it is suitably aligned.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn select_variant(&self, bytes: &[u8]) -> Result<Vec<u8>, DeserializeError> {
+        let variants = ArtifactVariants::deserialize(bytes)?;
+        variants
+            .select(*self.target().cpu_features())
+            .map(|variant| variant.to_vec())
+            .ok_or_else(|| {
+                DeserializeError::Incompatible(format!(
+                    "None of the artifact variants can run with the CPU features {:?}",
+                    self.target().cpu_features()
+                ))
+            })
+    }
+
     /// Deserializes a WebAssembly module from a path
     #[cfg(not(target_arch = "wasm32"))]
     pub fn deserialize_from_file_checked(
@@ -251,6 +299,24 @@ impl Engine {
         self.tunables = Arc::new(tunables);
     }
 
+    /// Set the profiler that the functions compiled or deserialized from
+    /// now on are reported to, see [`ProfilingStrategy`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_profiling_strategy(&mut self, profiling_strategy: ProfilingStrategy) {
+        self.inner_mut().profiling_strategy = profiling_strategy;
+    }
+
+    /// Set whether the modules compiled from now on carry native debug
+    /// info translated from their DWARF sections.
+    ///
+    /// Once loaded, the debug info is registered with the GDB JIT
+    /// interface, which GDB and LLDB use to set breakpoints and step
+    /// through the source of the guest.
+    #[cfg(feature = "compiler")]
+    pub fn set_debug_info(&mut self, enable: bool) {
+        self.inner_mut().debug_info = enable;
+    }
+
     /// Get a reference to attached Tunable of this engine
     #[cfg(not(target_arch = "wasm32"))]
     pub fn tunables(&self) -> &dyn Tunables {
@@ -276,6 +342,9 @@ pub struct EngineInner {
     #[cfg(feature = "compiler")]
     /// The compiler and cpu features
     features: Features,
+    /// Whether to translate the DWARF sections of the compiled modules.
+    #[cfg(feature = "compiler")]
+    debug_info: bool,
     /// The code memory is responsible of publishing the compiled
     /// functions to memory.
     #[cfg(not(target_arch = "wasm32"))]
@@ -284,6 +353,9 @@ pub struct EngineInner {
     /// performantly.
     #[cfg(not(target_arch = "wasm32"))]
     signatures: SignatureRegistry,
+    /// The profiler the compiled functions are reported to.
+    #[cfg(not(target_arch = "wasm32"))]
+    profiling_strategy: ProfilingStrategy,
 }
 
 impl EngineInner {
@@ -305,12 +377,27 @@ impl EngineInner {
         compiler.validate_module(&self.features, data)
     }
 
+    /// Validate the module, describing precisely why it is invalid and
+    /// which features it requires
+    #[cfg(feature = "compiler")]
+    pub fn validate_detailed(&self, data: &[u8]) -> Result<ValidationReport, CompileError> {
+        // Headless engines can't validate modules.
+        self.compiler()?;
+        Ok(validate_module_detailed(&self.features, data))
+    }
+
     /// The Wasm features
     #[cfg(feature = "compiler")]
     pub fn features(&self) -> &Features {
         &self.features
     }
 
+    /// Whether to translate the DWARF sections of the compiled modules.
+    #[cfg(feature = "compiler")]
+    pub fn debug_info(&self) -> bool {
+        self.debug_info
+    }
+
     /// Allocate compiled functions into memory
     #[cfg(not(target_arch = "wasm32"))]
     #[allow(clippy::type_complexity)]
@@ -430,6 +517,12 @@ impl EngineInner {
     pub fn signatures(&self) -> &SignatureRegistry {
         &self.signatures
     }
+
+    /// The profiler the compiled functions are reported to.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn profiling_strategy(&self) -> ProfilingStrategy {
+        self.profiling_strategy
+    }
 }
 
 #[cfg(feature = "compiler")]
@@ -0,0 +1,305 @@
+//! Notifications about the growth of linear memories.
+//!
+//! [`ObservingTunables`] wraps another [`Tunables`] implementation and
+//! tells a [`MemoryObserver`] every time one of the memories it created
+//! grows, or fails to grow because a limit was reached. This lets an
+//! embedder implement admission control or raise capacity alerts
+//! without writing a memory implementation of its own.
+
+use crate::engine::error::LinkError;
+use crate::engine::tunables::Tunables;
+use std::fmt;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use std::time::Duration;
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{
+    GlobalType, LocalMemoryIndex, MemoryIndex, MemoryType, ModuleInfo, Pages, TableType,
+};
+use wasmer_vm::{
+    InternalStoreHandle, LinearMemory, MemoryError, MemoryStyle, NotifyLocation, StoreObjects,
+    TableStyle, Trap, VMGlobal, VMMemory, VMMemoryDefinition, VMTable, VMTableDefinition,
+    WaiterError,
+};
+
+/// Receives the notifications about the growth of linear memories.
+///
+/// The memory is identified by its index in the module that defines it,
+/// or `None` for memories that were created by the host.
+pub trait MemoryObserver: fmt::Debug + Send + Sync {
+    /// A memory grew from `old` to `new` pages.
+    fn memory_grown(&self, memory: Option<MemoryIndex>, old: Pages, new: Pages) {
+        let _ = (memory, old, new);
+    }
+
+    /// A memory of `current` pages could not grow by `delta` pages.
+    fn memory_grow_denied(
+        &self,
+        memory: Option<MemoryIndex>,
+        current: Pages,
+        delta: Pages,
+        error: &MemoryError,
+    ) {
+        let _ = (memory, current, delta, error);
+    }
+}
+
+/// A [`LinearMemory`] that reports its growth to a [`MemoryObserver`].
+#[derive(Debug)]
+pub struct ObservedMemory {
+    inner: VMMemory,
+    index: Option<MemoryIndex>,
+    observer: Arc<dyn MemoryObserver>,
+}
+
+impl ObservedMemory {
+    /// Wraps a memory so that its growth is reported to `observer`.
+    pub fn new(
+        inner: VMMemory,
+        index: Option<MemoryIndex>,
+        observer: Arc<dyn MemoryObserver>,
+    ) -> Self {
+        Self {
+            inner,
+            index,
+            observer,
+        }
+    }
+
+    fn wrap(&self, inner: Box<dyn LinearMemory + 'static>) -> Box<dyn LinearMemory + 'static> {
+        Box::new(Self::new(
+            VMMemory(inner),
+            self.index,
+            self.observer.clone(),
+        ))
+    }
+}
+
+impl LinearMemory for ObservedMemory {
+    fn ty(&self) -> MemoryType {
+        self.inner.ty()
+    }
+
+    fn size(&self) -> Pages {
+        self.inner.size()
+    }
+
+    fn style(&self) -> MemoryStyle {
+        self.inner.style()
+    }
+
+    fn grow(&mut self, delta: Pages) -> Result<Pages, MemoryError> {
+        let old = self.inner.size();
+        match self.inner.grow(delta) {
+            Ok(ret) => {
+                let new = self.inner.size();
+                if new > old {
+                    self.observer.memory_grown(self.index, old, new);
+                }
+                Ok(ret)
+            }
+            Err(err) => {
+                self.observer
+                    .memory_grow_denied(self.index, old, delta, &err);
+                Err(err)
+            }
+        }
+    }
+
+    fn vmmemory(&self) -> NonNull<VMMemoryDefinition> {
+        self.inner.vmmemory()
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn LinearMemory + 'static>> {
+        self.inner.try_clone().map(|inner| self.wrap(inner))
+    }
+
+    unsafe fn initialize_with_data(&self, start: usize, data: &[u8]) -> Result<(), Trap> {
+        self.inner.initialize_with_data(start, data)
+    }
+
+    fn duplicate(&mut self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError> {
+        let inner = self.inner.duplicate()?;
+        Ok(self.wrap(inner))
+    }
+
+    fn do_wait(
+        &mut self,
+        dst: NotifyLocation,
+        timeout: Option<Duration>,
+    ) -> Result<u32, WaiterError> {
+        self.inner.do_wait(dst, timeout)
+    }
+
+    fn do_notify(&mut self, dst: NotifyLocation, count: u32) -> u32 {
+        self.inner.do_notify(dst, count)
+    }
+}
+
+/// [`Tunables`] that report the growth of every memory they create to a
+/// [`MemoryObserver`], delegating everything else to another
+/// implementation.
+pub struct ObservingTunables<T: Tunables> {
+    inner: T,
+    observer: Arc<dyn MemoryObserver>,
+}
+
+impl<T: Tunables> ObservingTunables<T> {
+    /// Wraps `inner` so that the memories it creates are observed.
+    pub fn new(inner: T, observer: Arc<dyn MemoryObserver>) -> Self {
+        Self { inner, observer }
+    }
+
+    fn observe(&self, memory: VMMemory, index: Option<MemoryIndex>) -> VMMemory {
+        VMMemory(Box::new(ObservedMemory::new(
+            memory,
+            index,
+            self.observer.clone(),
+        )))
+    }
+}
+
+impl<T: Tunables> fmt::Debug for ObservingTunables<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObservingTunables")
+            .field("observer", &self.observer)
+            .finish()
+    }
+}
+
+impl<T: Tunables> Tunables for ObservingTunables<T> {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.inner.memory_style(memory)
+    }
+
+    fn module_memory_style(&self, index: MemoryIndex, memory: &MemoryType) -> MemoryStyle {
+        self.inner.module_memory_style(index, memory)
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.inner.table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<VMMemory, MemoryError> {
+        let memory = self.inner.create_host_memory(ty, style)?;
+        Ok(self.observe(memory, None))
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<VMMemory, MemoryError> {
+        let memory = self
+            .inner
+            .create_vm_memory(ty, style, vm_definition_location)?;
+        Ok(self.observe(memory, None))
+    }
+
+    fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<VMTable, String> {
+        self.inner.create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<VMTable, String> {
+        self.inner
+            .create_vm_table(ty, style, vm_definition_location)
+    }
+
+    fn create_global(&self, ty: GlobalType) -> Result<VMGlobal, String> {
+        self.inner.create_global(ty)
+    }
+
+    // Same as the default implementation, except that the memories know
+    // their index in the module.
+    #[allow(clippy::result_large_err)]
+    unsafe fn create_memories(
+        &self,
+        context: &mut StoreObjects,
+        module: &ModuleInfo,
+        memory_styles: &PrimaryMap<MemoryIndex, MemoryStyle>,
+        memory_definition_locations: &[NonNull<VMMemoryDefinition>],
+    ) -> Result<PrimaryMap<LocalMemoryIndex, InternalStoreHandle<VMMemory>>, LinkError> {
+        let num_imports = module.num_imported_memories;
+        let mut memories: PrimaryMap<LocalMemoryIndex, _> =
+            PrimaryMap::with_capacity(module.memories.len() - num_imports);
+        for (index, mdl) in memory_definition_locations
+            .iter()
+            .enumerate()
+            .take(module.memories.len())
+            .skip(num_imports)
+        {
+            let mi = MemoryIndex::new(index);
+            let ty = &module.memories[mi];
+            let style = &memory_styles[mi];
+            let memory = self
+                .inner
+                .create_vm_memory(ty, style, *mdl)
+                .map_err(|e| LinkError::Resource(format!("Failed to create memory: {}", e)))?;
+            memories.push(InternalStoreHandle::new(
+                context,
+                self.observe(memory, Some(mi)),
+            ));
+        }
+        Ok(memories)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::tunables::BaseTunables;
+    use std::sync::Mutex;
+    use wasmer_types::Target;
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        events: Mutex<Vec<(Pages, Pages, bool)>>,
+    }
+
+    impl MemoryObserver for Recorder {
+        fn memory_grown(&self, _memory: Option<MemoryIndex>, old: Pages, new: Pages) {
+            self.events.lock().unwrap().push((old, new, true));
+        }
+
+        fn memory_grow_denied(
+            &self,
+            _memory: Option<MemoryIndex>,
+            current: Pages,
+            delta: Pages,
+            _error: &MemoryError,
+        ) {
+            self.events.lock().unwrap().push((current, delta, false));
+        }
+    }
+
+    #[test]
+    fn reports_growth_and_denials() {
+        let recorder = Arc::new(Recorder::default());
+        let tunables = ObservingTunables::new(
+            BaseTunables::for_target(&Target::default()),
+            recorder.clone(),
+        );
+        let ty = MemoryType::new(1, Some(3), false);
+        let style = tunables.memory_style(&ty);
+        let mut memory = tunables.create_host_memory(&ty, &style).unwrap();
+
+        memory.grow(Pages(0)).unwrap();
+        memory.grow(Pages(2)).unwrap();
+        memory.grow(Pages(1)).unwrap_err();
+
+        assert_eq!(
+            recorder.events.lock().unwrap().as_slice(),
+            &[(Pages(1), Pages(3), true), (Pages(3), Pages(1), false)]
+        );
+    }
+}
@@ -2,6 +2,15 @@
 
 mod error;
 #[cfg(not(target_arch = "wasm32"))]
+#[cfg_attr(not(feature = "translator"), allow(dead_code))]
+mod gdb_jit;
+#[cfg(not(target_arch = "wasm32"))]
+mod memory_observer;
+#[cfg(unix)]
+mod pooling;
+#[cfg(not(target_arch = "wasm32"))]
+mod profiling;
+#[cfg(not(target_arch = "wasm32"))]
 mod resolver;
 #[cfg(not(target_arch = "wasm32"))]
 mod trap;
@@ -27,11 +36,19 @@ mod unwind;
 
 pub use self::error::{InstantiationError, LinkError};
 #[cfg(not(target_arch = "wasm32"))]
+pub use self::memory_observer::{MemoryObserver, ObservedMemory, ObservingTunables};
+#[cfg(unix)]
+pub use self::pooling::PoolingTunables;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use self::profiling::ProfiledFunction;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::profiling::ProfilingStrategy;
+#[cfg(not(target_arch = "wasm32"))]
 pub use self::resolver::resolve_imports;
 #[cfg(not(target_arch = "wasm32"))]
 pub use self::trap::*;
 #[cfg(not(target_arch = "wasm32"))]
-pub use self::tunables::{BaseTunables, Tunables};
+pub use self::tunables::{BaseTunables, BoundsCheckStrategy, BoundsCheckTunables, Tunables};
 
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
@@ -42,6 +59,9 @@ pub use self::builder::EngineBuilder;
 #[cfg(not(target_arch = "wasm32"))]
 pub use self::code_memory::CodeMemory;
 #[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use self::gdb_jit::{build_elf_image, GdbJitImageRegistration};
+#[cfg(feature = "translator")]
 pub use self::inner::{Engine, EngineInner};
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
@@ -0,0 +1,161 @@
+//! Instantiation from a pool of preallocated memories.
+//!
+//! [`PoolingTunables`] wraps another [`Tunables`] implementation and
+//! takes the memories defined by modules from a [`MemoryPool`], instead
+//! of mapping a new region for each of them. Their slot goes back to the
+//! pool when the store owning the instance is dropped, which fits the
+//! embedders creating a store for every request.
+//!
+//! Tables and `VMContext`s are small heap allocations, which the global
+//! allocator already reuses: they are still created by the wrapped
+//! implementation.
+
+use crate::engine::tunables::Tunables;
+use std::fmt;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use wasmer_types::{GlobalType, MemoryIndex, MemoryType, TableType};
+use wasmer_vm::{
+    MemoryError, MemoryPool, MemoryStyle, TableStyle, VMGlobal, VMMemory, VMMemoryDefinition,
+    VMTable, VMTableDefinition,
+};
+
+/// [`Tunables`] that allocate the memories defined by modules from a
+/// [`MemoryPool`], delegating everything else to another implementation.
+///
+/// Memories that don't fit in a slot of the pool, such as shared
+/// memories, are created by the wrapped implementation. Instantiation
+/// fails when the pool is exhausted.
+pub struct PoolingTunables<T: Tunables> {
+    inner: T,
+    pool: Arc<MemoryPool>,
+}
+
+impl<T: Tunables> PoolingTunables<T> {
+    /// Wraps `inner` so that the memories of the instances come from
+    /// `pool`.
+    pub fn new(inner: T, pool: Arc<MemoryPool>) -> Self {
+        Self { inner, pool }
+    }
+
+    /// Returns the pool the memories are allocated from.
+    pub fn pool(&self) -> &Arc<MemoryPool> {
+        &self.pool
+    }
+}
+
+impl<T: Tunables> fmt::Debug for PoolingTunables<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolingTunables")
+            .field("pool", &self.pool)
+            .finish()
+    }
+}
+
+impl<T: Tunables> Tunables for PoolingTunables<T> {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.inner.memory_style(memory)
+    }
+
+    fn module_memory_style(&self, index: MemoryIndex, memory: &MemoryType) -> MemoryStyle {
+        self.inner.module_memory_style(index, memory)
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.inner.table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<VMMemory, MemoryError> {
+        self.inner.create_host_memory(ty, style)
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<VMMemory, MemoryError> {
+        if self.pool.fits(ty, style) {
+            let memory = self
+                .pool
+                .allocate(ty, style, Some(vm_definition_location))?;
+            Ok(VMMemory(Box::new(memory)))
+        } else {
+            self.inner
+                .create_vm_memory(ty, style, vm_definition_location)
+        }
+    }
+
+    fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<VMTable, String> {
+        self.inner.create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<VMTable, String> {
+        self.inner
+            .create_vm_table(ty, style, vm_definition_location)
+    }
+
+    fn create_global(&self, ty: GlobalType) -> Result<VMGlobal, String> {
+        self.inner.create_global(ty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::tunables::BaseTunables;
+    use wasmer_types::{Pages, Target};
+    use wasmer_vm::LinearMemory;
+
+    #[test]
+    fn module_memories_come_from_the_pool() {
+        let base = BaseTunables::for_target(&Target::default());
+        let pool = MemoryPool::new(
+            1,
+            base.static_memory_bound,
+            base.static_memory_offset_guard_size,
+        )
+        .unwrap();
+        let tunables = PoolingTunables::new(base, pool.clone());
+
+        let ty = MemoryType::new(1, None, false);
+        let style = tunables.memory_style(&ty);
+        let mut definition = VMMemoryDefinition {
+            base: std::ptr::null_mut(),
+            current_length: 0,
+        };
+        let memory =
+            unsafe { tunables.create_vm_memory(&ty, &style, NonNull::from(&mut definition)) }
+                .unwrap();
+        assert_eq!(pool.available(), 0);
+        assert_eq!(memory.size(), Pages(1));
+        assert_eq!(definition.current_length, 0x10000);
+
+        // Shared memories don't fit in the pool.
+        let shared = MemoryType::new(1, Some(1), true);
+        let style = tunables.memory_style(&shared);
+        let mut other = definition;
+        unsafe { tunables.create_vm_memory(&shared, &style, NonNull::from(&mut other)) }.unwrap();
+
+        let err = unsafe {
+            tunables.create_vm_memory(&ty, &tunables.memory_style(&ty), NonNull::from(&mut other))
+        }
+        .unwrap_err();
+        assert_eq!(
+            err,
+            MemoryError::Generic("the memory pool is exhausted".to_string())
+        );
+
+        drop(memory);
+        assert_eq!(pool.available(), 1);
+    }
+}
@@ -0,0 +1,184 @@
+//! Tell native profilers where the compiled functions live.
+//!
+//! Profilers sampling a process that runs JIT-compiled code only see
+//! anonymous executable regions. Two formats let them name those
+//! regions:
+//!
+//! * A [perf map] is a text file, `/tmp/perf-<pid>.map`, with one line
+//!   per function. It is read by `perf report` and by `samply`, which
+//!   also supports macOS.
+//! * A [jitdump] file, `jit-<pid>.dump` in the working directory, also
+//!   records the machine code of each function, so that `perf annotate`
+//!   can disassemble it after `perf inject --jit`. It is only written on
+//!   Linux, and `perf record` must be run with `-k mono`.
+//!
+//! The files are shared by all the engines of the process.
+//!
+//! [perf map]: https://github.com/torvalds/linux/blob/master/tools/perf/Documentation/jit-interface.txt
+//! [jitdump]: https://github.com/torvalds/linux/blob/master/tools/perf/Documentation/jitdump-specification.txt
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+/// Which profiler to tell about compiled functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProfilingStrategy {
+    /// Don't emit any profiling data.
+    #[default]
+    None,
+    /// Append the functions to the perf map of the process.
+    PerfMap,
+    /// Record the functions, including their code, in the jitdump file of
+    /// the process. Falls back to a perf map on hosts other than Linux.
+    JitDump,
+}
+
+/// A compiled function to report to the profiler.
+pub(crate) struct ProfiledFunction<'a> {
+    pub(crate) name: String,
+    pub(crate) code: &'a [u8],
+}
+
+lazy_static::lazy_static! {
+    static ref PERF_MAP: Mutex<Option<File>> = Mutex::new(None);
+}
+
+#[cfg(target_os = "linux")]
+lazy_static::lazy_static! {
+    static ref JIT_DUMP: Mutex<Option<jitdump::JitDumpFile>> = Mutex::new(None);
+}
+
+impl ProfilingStrategy {
+    /// Reports newly published functions to the profiler.
+    ///
+    /// Profiling is best-effort: callers should not fail the compilation
+    /// when this returns an error.
+    pub(crate) fn register(&self, functions: &[ProfiledFunction]) -> io::Result<()> {
+        match self {
+            Self::None => Ok(()),
+            Self::PerfMap => register_perf_map(functions),
+            #[cfg(target_os = "linux")]
+            Self::JitDump => {
+                let mut jit_dump = JIT_DUMP.lock().unwrap();
+                if jit_dump.is_none() {
+                    *jit_dump = Some(jitdump::JitDumpFile::create()?);
+                }
+                let jit_dump = jit_dump.as_mut().unwrap();
+                for function in functions {
+                    jit_dump.code_load(&function.name, function.code)?;
+                }
+                jit_dump.flush()
+            }
+            #[cfg(not(target_os = "linux"))]
+            Self::JitDump => register_perf_map(functions),
+        }
+    }
+}
+
+fn register_perf_map(functions: &[ProfiledFunction]) -> io::Result<()> {
+    let mut perf_map = PERF_MAP.lock().unwrap();
+    if perf_map.is_none() {
+        let path = format!("/tmp/perf-{}.map", std::process::id());
+        *perf_map = Some(File::create(path)?);
+    }
+    let perf_map = perf_map.as_mut().unwrap();
+    let mut lines = String::new();
+    for function in functions {
+        lines.push_str(&format!(
+            "{:x} {:x} {}\n",
+            function.code.as_ptr() as usize,
+            function.code.len(),
+            function.name
+        ));
+    }
+    perf_map.write_all(lines.as_bytes())
+}
+
+#[cfg(target_os = "linux")]
+mod jitdump {
+    use crate::engine::gdb_jit::elf_machine;
+    use memmap2::{Mmap, MmapOptions};
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, BufWriter, Write};
+
+    const MAGIC: u32 = 0x4A69_5444;
+    const VERSION: u32 = 1;
+    const HEADER_SIZE: u32 = 40;
+    const JIT_CODE_LOAD: u32 = 0;
+    /// The size of a code load record, without the name and the code.
+    const CODE_LOAD_SIZE: usize = 56;
+
+    /// The jitdump file of the process.
+    pub(super) struct JitDumpFile {
+        writer: BufWriter<File>,
+        /// `perf record` notices the file through this executable mapping
+        /// of it, which must stay alive while the process runs.
+        _marker: Mmap,
+        code_index: u64,
+    }
+
+    impl JitDumpFile {
+        pub(super) fn create() -> io::Result<Self> {
+            let path = format!("jit-{}.dump", std::process::id());
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)?;
+            let page_size = region::page::size();
+            let marker = unsafe { MmapOptions::new().len(page_size).map_exec(&file)? };
+            let mut writer = BufWriter::new(file);
+            writer.write_all(&MAGIC.to_ne_bytes())?;
+            writer.write_all(&VERSION.to_ne_bytes())?;
+            writer.write_all(&HEADER_SIZE.to_ne_bytes())?;
+            writer.write_all(&u32::from(elf_machine()).to_ne_bytes())?;
+            writer.write_all(&0u32.to_ne_bytes())?;
+            writer.write_all(&std::process::id().to_ne_bytes())?;
+            writer.write_all(&timestamp().to_ne_bytes())?;
+            writer.write_all(&0u64.to_ne_bytes())?;
+            Ok(Self {
+                writer,
+                _marker: marker,
+                code_index: 0,
+            })
+        }
+
+        /// Records a function whose code was just made executable.
+        pub(super) fn code_load(&mut self, name: &str, code: &[u8]) -> io::Result<()> {
+            let address = code.as_ptr() as u64;
+            let total_size = CODE_LOAD_SIZE + name.len() + 1 + code.len();
+            let tid = unsafe { libc::syscall(libc::SYS_gettid) } as u32;
+            let w = &mut self.writer;
+            w.write_all(&JIT_CODE_LOAD.to_ne_bytes())?;
+            w.write_all(&(total_size as u32).to_ne_bytes())?;
+            w.write_all(&timestamp().to_ne_bytes())?;
+            w.write_all(&std::process::id().to_ne_bytes())?;
+            w.write_all(&tid.to_ne_bytes())?;
+            w.write_all(&address.to_ne_bytes())?;
+            w.write_all(&address.to_ne_bytes())?;
+            w.write_all(&(code.len() as u64).to_ne_bytes())?;
+            w.write_all(&self.code_index.to_ne_bytes())?;
+            w.write_all(name.as_bytes())?;
+            w.write_all(&[0])?;
+            w.write_all(code)?;
+            self.code_index += 1;
+            Ok(())
+        }
+
+        pub(super) fn flush(&mut self) -> io::Result<()> {
+            self.writer.flush()
+        }
+    }
+
+    /// The timestamps must come from the clock `perf record -k mono` uses.
+    fn timestamp() -> u64 {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+        ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+    }
+}
@@ -11,6 +11,8 @@
 //! let module: ModuleInfo = ...;
 //! FRAME_INFO.register(module, compiled_functions);
 //! ```
+#[cfg(feature = "compiler")]
+use crate::SourceLines;
 use std::cmp;
 use std::collections::BTreeMap;
 use std::sync::{Arc, RwLock};
@@ -57,6 +59,9 @@ struct ModuleInfoFrameInfo {
     functions: BTreeMap<usize, FunctionInfo>,
     module: Arc<ModuleInfo>,
     frame_infos: PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>,
+    /// The lines of source code of the module, if it has DWARF sections.
+    #[cfg(feature = "compiler")]
+    source_lines: Option<SourceLines>,
 }
 
 impl ModuleInfoFrameInfo {
@@ -130,13 +135,23 @@ impl GlobalFrameInfo {
             None => instr_map.start_srcloc,
         };
         let func_index = module.module.func_index(func.local_index);
-        Some(FrameInfo::new(
+        let frame = FrameInfo::new(
             module.module.name(),
             func_index.index() as u32,
             module.module.function_names.get(&func_index).cloned(),
             instr_map.start_srcloc,
             instr,
-        ))
+        );
+        #[cfg(feature = "compiler")]
+        let frame = match module
+            .source_lines
+            .as_ref()
+            .and_then(|lines| lines.lookup(frame.module_offset()))
+        {
+            Some(location) => frame.with_source_location(location),
+            None => frame,
+        };
+        Some(frame)
     }
 
     /// Fetches trap information about a program counter in a backtrace.
@@ -217,6 +232,9 @@ pub fn register(
     if functions.is_empty() {
         return None;
     }
+    // The DWARF is read before taking the lock, as it may be large.
+    #[cfg(feature = "compiler")]
+    let source_lines = SourceLines::read(&module);
 
     let mut info = FRAME_INFO.write().unwrap();
     // First up assert that our chunk of jit functions doesn't collide with
@@ -236,6 +254,8 @@ pub fn register(
             functions,
             module,
             frame_infos,
+            #[cfg(feature = "compiler")]
+            source_lines,
         },
     );
     assert!(prev.is_none());
@@ -1,11 +1,12 @@
 use crate::engine::error::LinkError;
+use std::collections::HashMap;
 use std::ptr::NonNull;
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
     GlobalType, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex, MemoryType,
     ModuleInfo, Pages, PointerWidth, TableIndex, TableType, Target,
 };
-use wasmer_vm::{InternalStoreHandle, MemoryError, StoreObjects};
+use wasmer_vm::{fuel_global, interrupt_global, InternalStoreHandle, MemoryError, StoreObjects};
 use wasmer_vm::{MemoryStyle, TableStyle};
 use wasmer_vm::{VMGlobal, VMMemory, VMTable};
 use wasmer_vm::{VMMemoryDefinition, VMTableDefinition};
@@ -16,6 +17,15 @@ pub trait Tunables {
     /// Construct a `MemoryStyle` for the provided `MemoryType`
     fn memory_style(&self, memory: &MemoryType) -> MemoryStyle;
 
+    /// Construct a `MemoryStyle` for the memory `index` of a module.
+    ///
+    /// The styles chosen when compiling a module are stored in its artifact,
+    /// and used again when it is deserialized. By default, this is the
+    /// [`Tunables::memory_style`] of the memory type.
+    fn module_memory_style(&self, _index: MemoryIndex, memory: &MemoryType) -> MemoryStyle {
+        self.memory_style(memory)
+    }
+
     /// Construct a `TableStyle` for the provided `TableType`
     fn table_style(&self, table: &TableType) -> TableStyle;
 
@@ -133,19 +143,48 @@ pub trait Tunables {
     ) -> Result<PrimaryMap<LocalGlobalIndex, InternalStoreHandle<VMGlobal>>, LinkError> {
         let num_imports = module.num_imported_globals;
         let mut vmctx_globals = PrimaryMap::with_capacity(module.globals.len() - num_imports);
-
-        for &global_type in module.globals.values().skip(num_imports) {
-            vmctx_globals.push(InternalStoreHandle::new(
-                context,
+        let interrupt = interrupt_global(module);
+        let fuel = fuel_global(module);
+
+        for (index, &global_type) in module.globals.values().skip(num_imports).enumerate() {
+            let index = Some(LocalGlobalIndex::new(index));
+            let global = if index == interrupt {
+                // The flag of the interrupt middleware is shared by the
+                // whole store.
+                VMGlobal::from_interrupt_handle(global_type, context.interrupt_handle())
+            } else if index == fuel {
+                // So is the fuel of the fuel middleware.
+                context.new_fuel_global(global_type)
+            } else {
                 self.create_global(global_type)
-                    .map_err(LinkError::Resource)?,
-            ));
+                    .map_err(LinkError::Resource)?
+            };
+            vmctx_globals.push(InternalStoreHandle::new(context, global));
         }
 
         Ok(vmctx_globals)
     }
 }
 
+/// How out-of-bounds accesses to a memory are caught.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BoundsCheckStrategy {
+    /// Reserve enough virtual memory for the guard pages to catch the
+    /// out-of-bounds accesses when the memory has a small enough maximum,
+    /// and check the accesses explicitly otherwise.
+    #[default]
+    GuardPages,
+    /// Check every access against the current size of the memory, keeping
+    /// a small guard region to catch the accesses with a small offset.
+    Explicit,
+    /// Check every access against the current size of the memory, without
+    /// reserving any virtual memory beyond it.
+    ///
+    /// This is the slowest strategy, meant for targets where virtual memory
+    /// is scarce.
+    ExplicitNoReservation,
+}
+
 /// Tunable parameters for WebAssembly compilation.
 /// This is the reference implementation of the `Tunables` trait,
 /// used by default.
@@ -200,27 +239,59 @@ impl BaseTunables {
             dynamic_memory_offset_guard_size,
         }
     }
+
+    /// Set the bounds-check strategy of the memories.
+    pub fn with_bounds_checks(self, strategy: BoundsCheckStrategy) -> BoundsCheckTunables {
+        BoundsCheckTunables::new(self).with_bounds_checks(strategy)
+    }
+
+    /// Set the bounds-check strategy of the memory `index` of the modules.
+    pub fn with_memory_bounds_checks(
+        self,
+        index: MemoryIndex,
+        strategy: BoundsCheckStrategy,
+    ) -> BoundsCheckTunables {
+        BoundsCheckTunables::new(self).with_memory_bounds_checks(index, strategy)
+    }
+
+    fn memory_style_with(&self, strategy: BoundsCheckStrategy, memory: &MemoryType) -> MemoryStyle {
+        match strategy {
+            BoundsCheckStrategy::GuardPages => {
+                // A heap with a maximum that doesn't exceed the static memory bound specified by the
+                // tunables make it static.
+                //
+                // If the module doesn't declare an explicit maximum treat it as 4GiB.
+                //
+                // 64-bit memories are always dynamic: a static memory relies on the
+                // guard pages to catch out-of-bounds accesses, and no reservation
+                // can cover the range of a 64-bit index.
+                let maximum = memory.maximum.unwrap_or_else(Pages::max_value);
+                if !memory.is_memory64() && maximum <= self.static_memory_bound {
+                    MemoryStyle::Static {
+                        // Bound can be larger than the maximum for performance reasons
+                        bound: self.static_memory_bound,
+                        offset_guard_size: self.static_memory_offset_guard_size,
+                    }
+                } else {
+                    MemoryStyle::Dynamic {
+                        offset_guard_size: self.dynamic_memory_offset_guard_size,
+                    }
+                }
+            }
+            BoundsCheckStrategy::Explicit => MemoryStyle::Dynamic {
+                offset_guard_size: self.dynamic_memory_offset_guard_size,
+            },
+            BoundsCheckStrategy::ExplicitNoReservation => MemoryStyle::Dynamic {
+                offset_guard_size: 0,
+            },
+        }
+    }
 }
 
 impl Tunables for BaseTunables {
     /// Get a `MemoryStyle` for the provided `MemoryType`
     fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
-        // A heap with a maximum that doesn't exceed the static memory bound specified by the
-        // tunables make it static.
-        //
-        // If the module doesn't declare an explicit maximum treat it as 4GiB.
-        let maximum = memory.maximum.unwrap_or_else(Pages::max_value);
-        if maximum <= self.static_memory_bound {
-            MemoryStyle::Static {
-                // Bound can be larger than the maximum for performance reasons
-                bound: self.static_memory_bound,
-                offset_guard_size: self.static_memory_offset_guard_size,
-            }
-        } else {
-            MemoryStyle::Dynamic {
-                offset_guard_size: self.dynamic_memory_offset_guard_size,
-            }
-        }
+        self.memory_style_with(BoundsCheckStrategy::GuardPages, memory)
     }
 
     /// Get a [`TableStyle`] for the provided [`TableType`].
@@ -271,11 +342,102 @@ impl Tunables for BaseTunables {
     }
 }
 
+/// [`BaseTunables`] with a different bounds-check strategy for the
+/// memories, created by [`BaseTunables::with_bounds_checks`] and
+/// [`BaseTunables::with_memory_bounds_checks`].
+#[derive(Clone)]
+pub struct BoundsCheckTunables {
+    base: BaseTunables,
+    bounds_checks: BoundsCheckStrategy,
+    memory_bounds_checks: HashMap<MemoryIndex, BoundsCheckStrategy>,
+}
+
+impl BoundsCheckTunables {
+    fn new(base: BaseTunables) -> Self {
+        Self {
+            base,
+            bounds_checks: BoundsCheckStrategy::default(),
+            memory_bounds_checks: HashMap::new(),
+        }
+    }
+
+    /// Set the bounds-check strategy of the memories.
+    pub fn with_bounds_checks(mut self, strategy: BoundsCheckStrategy) -> Self {
+        self.bounds_checks = strategy;
+        self
+    }
+
+    /// Set the bounds-check strategy of the memory `index` of the modules,
+    /// overriding the one of the other memories.
+    pub fn with_memory_bounds_checks(
+        mut self,
+        index: MemoryIndex,
+        strategy: BoundsCheckStrategy,
+    ) -> Self {
+        self.memory_bounds_checks.insert(index, strategy);
+        self
+    }
+}
+
+impl Tunables for BoundsCheckTunables {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.base.memory_style_with(self.bounds_checks, memory)
+    }
+
+    fn module_memory_style(&self, index: MemoryIndex, memory: &MemoryType) -> MemoryStyle {
+        let strategy = self
+            .memory_bounds_checks
+            .get(&index)
+            .copied()
+            .unwrap_or(self.bounds_checks);
+        self.base.memory_style_with(strategy, memory)
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.base.table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<VMMemory, MemoryError> {
+        self.base.create_host_memory(ty, style)
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<VMMemory, MemoryError> {
+        self.base
+            .create_vm_memory(ty, style, vm_definition_location)
+    }
+
+    fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<VMTable, String> {
+        self.base.create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<VMTable, String> {
+        self.base.create_vm_table(ty, style, vm_definition_location)
+    }
+}
+
 impl Tunables for Box<dyn Tunables + Send + Sync> {
     fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
         self.as_ref().memory_style(memory)
     }
 
+    fn module_memory_style(&self, index: MemoryIndex, memory: &MemoryType) -> MemoryStyle {
+        self.as_ref().module_memory_style(index, memory)
+    }
+
     fn table_style(&self, table: &TableType) -> TableStyle {
         self.as_ref().table_style(table)
     }
@@ -318,6 +480,10 @@ impl Tunables for std::sync::Arc<dyn Tunables + Send + Sync> {
         self.as_ref().memory_style(memory)
     }
 
+    fn module_memory_style(&self, index: MemoryIndex, memory: &MemoryType) -> MemoryStyle {
+        self.as_ref().module_memory_style(index, memory)
+    }
+
     fn table_style(&self, table: &TableType) -> TableStyle {
         self.as_ref().table_style(table)
     }
@@ -2,6 +2,9 @@ cfg_if::cfg_if! {
     if #[cfg(all(windows, target_arch = "x86_64"))] {
         mod windows_x64;
         pub use self::windows_x64::*;
+    } else if #[cfg(all(windows, target_arch = "aarch64"))] {
+        mod windows_arm64;
+        pub use self::windows_arm64::*;
     } else if #[cfg(unix)] {
         mod systemv;
         pub use self::systemv::*;
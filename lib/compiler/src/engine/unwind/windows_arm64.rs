@@ -0,0 +1,105 @@
+//! Module for Windows ARM64 ABI unwind registry.
+use std::collections::HashMap;
+use wasmer_types::CompiledFunctionUnwindInfo;
+
+/// A function table entry pointing to an `.xdata` record.
+#[repr(C)]
+struct RuntimeFunction {
+    begin_address: u32,
+    unwind_data: u32,
+}
+
+extern "system" {
+    fn RtlAddFunctionTable(
+        function_table: *mut RuntimeFunction,
+        entry_count: u32,
+        base_address: u64,
+    ) -> u8;
+    fn RtlDeleteFunctionTable(function_table: *mut RuntimeFunction) -> u8;
+}
+
+/// Represents a registry of function unwind information for Windows ARM64 ABI.
+pub struct UnwindRegistry {
+    // A hashmap mapping the baseaddress with the registered runtime functions
+    functions: HashMap<usize, Vec<RuntimeFunction>>,
+    published: bool,
+}
+
+impl UnwindRegistry {
+    /// Creates a new unwind registry with the given base address.
+    pub fn new() -> Self {
+        Self {
+            functions: HashMap::new(),
+            published: false,
+        }
+    }
+
+    /// Registers a function given the start offset, length, and unwind information.
+    pub fn register(
+        &mut self,
+        base_address: usize,
+        func_start: u32,
+        func_len: u32,
+        info: &CompiledFunctionUnwindInfo,
+    ) -> Result<(), String> {
+        if self.published {
+            return Err("unwind registry has already been published".to_string());
+        }
+
+        match info {
+            CompiledFunctionUnwindInfo::WindowsArm64(_) => {}
+            _ => return Err("unsupported unwind information".to_string()),
+        };
+
+        // The `.xdata` record immediately follows the function, with padding
+        // for 4 byte alignment. The low bits of an aligned address being
+        // zero tell that the entry points to a record rather than holding
+        // packed unwind data.
+        let entry = RuntimeFunction {
+            begin_address: func_start,
+            unwind_data: (func_start + func_len + 3) & !3,
+        };
+        self.functions
+            .entry(base_address)
+            .or_insert_with(Vec::new)
+            .push(entry);
+
+        Ok(())
+    }
+
+    /// Publishes all registered functions.
+    pub fn publish(&mut self, _eh_frame: Option<&[u8]>) -> Result<(), String> {
+        if self.published {
+            return Err("unwind registry has already been published".to_string());
+        }
+
+        self.published = true;
+
+        for (base_address, functions) in self.functions.iter_mut() {
+            unsafe {
+                if RtlAddFunctionTable(
+                    functions.as_mut_ptr(),
+                    functions.len() as u32,
+                    *base_address as u64,
+                ) == 0
+                {
+                    return Err("failed to register function tables".to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for UnwindRegistry {
+    fn drop(&mut self) {
+        if self.published {
+            unsafe {
+                for functions in self.functions.values_mut() {
+                    RtlDeleteFunctionTable(functions.as_mut_ptr());
+                }
+            }
+        }
+    }
+}
@@ -69,13 +69,21 @@ mod compiler;
 #[cfg(feature = "translator")]
 #[macro_use]
 mod translator;
+#[cfg(feature = "translator")]
+mod validation;
+
 #[cfg(feature = "translator")]
 pub use crate::compiler::{Compiler, CompilerConfig};
 #[cfg(feature = "translator")]
 pub use crate::translator::{
-    from_binaryreadererror_wasmerror, translate_module, wptype_to_type, FunctionBinaryReader,
-    FunctionBodyData, FunctionMiddleware, MiddlewareBinaryReader, MiddlewareReaderState,
-    ModuleEnvironment, ModuleMiddleware, ModuleMiddlewareChain, ModuleTranslationState,
+    from_binaryreadererror_wasmerror, translate_module, wptype_to_type, BranchHints,
+    FunctionBinaryReader, FunctionBodyData, FunctionBranchHints, FunctionMiddleware,
+    MiddlewareBinaryReader, MiddlewareReaderState, ModuleEnvironment, ModuleMiddleware,
+    ModuleMiddlewareChain, ModuleTranslationState, BRANCH_HINT_SECTION,
+};
+#[cfg(feature = "translator")]
+pub use crate::validation::{
+    validate_module_detailed, FeatureUse, ValidationError, ValidationReport,
 };
 
 pub use wasmer_types::{Addend, CodeOffset, Features};
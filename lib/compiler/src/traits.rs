@@ -5,10 +5,10 @@ use enumset::EnumSet;
 use std::any::Any;
 use std::sync::Arc;
 use wasmer_types::entity::PrimaryMap;
-use wasmer_types::SerializeError;
 use wasmer_types::{
     CpuFeature, MemoryIndex, MemoryStyle, ModuleInfo, OwnedDataInitializer, TableIndex, TableStyle,
 };
+use wasmer_types::{SerializeError, SerializeOptions};
 
 /// An `Artifact` is the product that the `Engine`
 /// implementation produce and use.
@@ -23,6 +23,10 @@ pub trait ArtifactCreate: Send + Sync + Upcastable {
     /// Sets the `ModuleInfo` name
     fn set_module_info_name(&mut self, name: String) -> bool;
 
+    /// Adds a custom section to the `ModuleInfo`, replacing the section
+    /// with the same name if there is one
+    fn add_module_info_custom_section(&mut self, name: String, data: Box<[u8]>) -> bool;
+
     /// Returns the `ModuleInfo` for instantiation
     fn module_info(&self) -> &ModuleInfo;
 
@@ -43,6 +47,10 @@ pub trait ArtifactCreate: Send + Sync + Upcastable {
 
     /// Serializes an artifact into bytes
     fn serialize(&self) -> Result<Vec<u8>, SerializeError>;
+
+    /// Serializes an artifact into bytes, leaving out what `options` asks
+    /// to strip
+    fn serialize_with(&self, options: &SerializeOptions) -> Result<Vec<u8>, SerializeError>;
 }
 
 // Implementation of `Upcastable` taken from https://users.rust-lang.org/t/why-does-downcasting-not-work-for-subtraits/33286/7 .
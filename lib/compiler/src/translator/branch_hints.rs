@@ -0,0 +1,122 @@
+//! Reading of the `metadata.code.branch_hint` custom section from the
+//! [branch hinting proposal].
+//!
+//! A branch hint tells whether the branch of an `if` or a `br_if` is
+//! likely taken. Compilers can use it to lay out the unlikely side out
+//! of the hot path. Hints are only a performance annotation, so a
+//! malformed section is ignored rather than reported.
+//!
+//! [branch hinting proposal]: https://github.com/WebAssembly/branch-hinting
+
+use std::collections::HashMap;
+use wasmer_types::{FunctionIndex, ModuleInfo};
+use wasmparser::BinaryReader;
+
+/// The name of the custom section holding the branch hints.
+pub const BRANCH_HINT_SECTION: &str = "metadata.code.branch_hint";
+
+/// The branch hints of a single function, keyed by the offset of the
+/// hinted instruction relative to the start of the function body.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FunctionBranchHints {
+    hints: HashMap<u32, bool>,
+}
+
+impl FunctionBranchHints {
+    /// Returns whether the branch of the instruction at `offset` is
+    /// likely taken, if it is hinted.
+    ///
+    /// `offset` is relative to the start of the function body, which is
+    /// where its local declarations begin.
+    pub fn get(&self, offset: u32) -> Option<bool> {
+        self.hints.get(&offset).copied()
+    }
+}
+
+/// The branch hints of all the functions of a module.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BranchHints {
+    functions: HashMap<FunctionIndex, FunctionBranchHints>,
+}
+
+impl BranchHints {
+    /// Reads the branch hints of a module from its custom sections.
+    pub fn from_module(module: &ModuleInfo) -> Self {
+        module
+            .custom_sections(BRANCH_HINT_SECTION)
+            .next()
+            .and_then(|data| Self::parse(&data))
+            .unwrap_or_default()
+    }
+
+    /// Parses the content of a branch hint section, or returns `None` if
+    /// it is malformed.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let mut reader = BinaryReader::new(data);
+        let mut functions = HashMap::new();
+        let count = reader.read_var_u32().ok()?;
+        for _ in 0..count {
+            let function = FunctionIndex::from_u32(reader.read_var_u32().ok()?);
+            let hint_count = reader.read_var_u32().ok()?;
+            let mut hints = HashMap::new();
+            for _ in 0..hint_count {
+                let offset = reader.read_var_u32().ok()?;
+                // The size of the hint is always 1 for now.
+                if reader.read_var_u32().ok()? != 1 {
+                    return None;
+                }
+                let likely = match reader.read_u8().ok()? {
+                    0 => false,
+                    1 => true,
+                    _ => return None,
+                };
+                hints.insert(offset, likely);
+            }
+            functions.insert(function, FunctionBranchHints { hints });
+        }
+        if !reader.eof() {
+            return None;
+        }
+        Some(Self { functions })
+    }
+
+    /// Returns the branch hints of the function at `index`, if any.
+    pub fn function(&self, index: FunctionIndex) -> Option<&FunctionBranchHints> {
+        self.functions.get(&index)
+    }
+
+    /// Returns whether the module has no branch hints.
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hints() {
+        // Function 3 has an unlikely branch at offset 5 and a likely
+        // one at offset 200.
+        let data = [1, 3, 2, 5, 1, 0, 0xc8, 0x01, 1, 1];
+        let hints = BranchHints::parse(&data).unwrap();
+        let function = hints.function(FunctionIndex::from_u32(3)).unwrap();
+        assert_eq!(function.get(5), Some(false));
+        assert_eq!(function.get(200), Some(true));
+        assert_eq!(function.get(6), None);
+        assert!(hints.function(FunctionIndex::from_u32(0)).is_none());
+    }
+
+    #[test]
+    fn malformed_hints_are_ignored() {
+        // Invalid hint value.
+        assert!(BranchHints::parse(&[1, 0, 1, 5, 1, 2]).is_none());
+        // Invalid hint size.
+        assert!(BranchHints::parse(&[1, 0, 1, 5, 2, 0]).is_none());
+        // Truncated.
+        assert!(BranchHints::parse(&[1, 0, 1, 5]).is_none());
+        // Trailing bytes.
+        assert!(BranchHints::parse(&[0, 0]).is_none());
+    }
+}
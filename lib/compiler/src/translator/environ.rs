@@ -14,7 +14,7 @@ use wasmer_types::{
     CustomSectionIndex, DataIndex, DataInitializer, DataInitializerLocation, ElemIndex,
     ExportIndex, FunctionIndex, GlobalIndex, GlobalInit, GlobalType, ImportIndex,
     LocalFunctionIndex, MemoryIndex, MemoryType, ModuleInfo, SignatureIndex, TableIndex,
-    TableInitializer, TableType,
+    TableInitializer, TableType, TrapCode,
 };
 
 /// Contains function data: bytecode and its offset in the module.
@@ -38,6 +38,13 @@ pub trait FunctionBinaryReader<'a> {
     /// Reads the next available `Operator`.
     fn read_operator(&mut self) -> WasmResult<Operator<'a>>;
 
+    /// The code to trap with when the last operator read is an
+    /// `unreachable` raised by a middleware, see
+    /// [`MiddlewareReaderState::push_trap`](super::MiddlewareReaderState::push_trap).
+    fn trap_code(&self) -> Option<TrapCode> {
+        None
+    }
+
     /// Returns the current position.
     fn current_position(&self) -> usize;
 
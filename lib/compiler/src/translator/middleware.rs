@@ -5,7 +5,7 @@ use smallvec::SmallVec;
 use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::ops::{Deref, Range};
-use wasmer_types::{LocalFunctionIndex, MiddlewareError, ModuleInfo, WasmResult};
+use wasmer_types::{LocalFunctionIndex, MiddlewareError, ModuleInfo, TrapCode, WasmResult};
 use wasmparser::{BinaryReader, Operator, ValType};
 
 use super::error::from_binaryreadererror_wasmerror;
@@ -48,6 +48,10 @@ pub struct MiddlewareBinaryReader<'a> {
 
     /// The backing middleware chain for this reader.
     chain: Vec<Box<dyn FunctionMiddleware>>,
+
+    /// The trap code of the last operator read, if a middleware raised it
+    /// with [`MiddlewareReaderState::push_trap`].
+    trap_code: Option<TrapCode>,
 }
 
 /// The state of the binary reader. Exposed to middlewares to push their outputs.
@@ -56,8 +60,9 @@ pub struct MiddlewareReaderState<'a> {
     /// Raw binary reader.
     inner: BinaryReader<'a>,
 
-    /// The pending operations added by the middleware.
-    pending_operations: VecDeque<Operator<'a>>,
+    /// The pending operations added by the middleware, with the trap code
+    /// of the traps they raised.
+    pending_operations: VecDeque<(Operator<'a>, Option<TrapCode>)>,
 }
 
 /// Trait for generating middleware chains from "prototype" (generator) chains.
@@ -94,19 +99,30 @@ impl<T: Deref<Target = dyn ModuleMiddleware>> ModuleMiddlewareChain for [T] {
 impl<'a> MiddlewareReaderState<'a> {
     /// Push an operator.
     pub fn push_operator(&mut self, operator: Operator<'a>) {
-        self.pending_operations.push_back(operator);
+        self.pending_operations.push_back((operator, None));
+    }
+
+    /// Push an `unreachable` that traps with `trap_code` instead of
+    /// [`TrapCode::UnreachableCodeReached`].
+    ///
+    /// The trap is not fed to the next middlewares of the chain.
+    pub fn push_trap(&mut self, trap_code: TrapCode) {
+        self.pending_operations
+            .push_back((Operator::Unreachable, Some(trap_code)));
     }
 }
 
 impl<'a> Extend<Operator<'a>> for MiddlewareReaderState<'a> {
     fn extend<I: IntoIterator<Item = Operator<'a>>>(&mut self, iter: I) {
-        self.pending_operations.extend(iter);
+        self.pending_operations
+            .extend(iter.into_iter().map(|operator| (operator, None)));
     }
 }
 
 impl<'a: 'b, 'b> Extend<&'b Operator<'a>> for MiddlewareReaderState<'a> {
     fn extend<I: IntoIterator<Item = &'b Operator<'a>>>(&mut self, iter: I) {
-        self.pending_operations.extend(iter.into_iter().cloned());
+        self.pending_operations
+            .extend(iter.into_iter().map(|operator| (operator.clone(), None)));
     }
 }
 
@@ -120,6 +136,7 @@ impl<'a> MiddlewareBinaryReader<'a> {
                 pending_operations: VecDeque::new(),
             },
             chain: vec![],
+            trap_code: None,
         }
     }
 
@@ -170,22 +187,35 @@ impl<'a> FunctionBinaryReader<'a> for MiddlewareBinaryReader<'a> {
                 .map_err(from_binaryreadererror_wasmerror)?;
 
             // Fill the initial raw operator into pending buffer.
-            self.state.pending_operations.push_back(raw_op);
+            self.state.pending_operations.push_back((raw_op, None));
 
             // Run the operator through each stage.
             for stage in &mut self.chain {
                 // Take the outputs from the previous stage.
-                let pending: SmallVec<[Operator<'a>; 2]> =
+                let pending: SmallVec<[(Operator<'a>, Option<TrapCode>); 2]> =
                     self.state.pending_operations.drain(0..).collect();
 
-                // ...and feed them into the current stage.
-                for pending_op in pending {
-                    stage.feed(pending_op, &mut self.state)?;
+                // ...and feed them into the current stage, except for the
+                // traps, which keep their place and their code.
+                for (pending_op, trap_code) in pending {
+                    if trap_code.is_some() {
+                        self.state
+                            .pending_operations
+                            .push_back((pending_op, trap_code));
+                    } else {
+                        stage.feed(pending_op, &mut self.state)?;
+                    }
                 }
             }
         }
 
-        Ok(self.state.pending_operations.pop_front().unwrap())
+        let (operator, trap_code) = self.state.pending_operations.pop_front().unwrap();
+        self.trap_code = trap_code;
+        Ok(operator)
+    }
+
+    fn trap_code(&self) -> Option<TrapCode> {
+        self.trap_code
     }
 
     fn current_position(&self) -> usize {
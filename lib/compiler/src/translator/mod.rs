@@ -5,6 +5,7 @@
 //! compilers rather than just Cranelift.
 //!
 //! [cranelift-wasm]: https://crates.io/crates/cranelift-wasm/
+mod branch_hints;
 mod environ;
 mod middleware;
 mod module;
@@ -13,6 +14,7 @@ mod state;
 mod error;
 mod sections;
 
+pub use self::branch_hints::{BranchHints, FunctionBranchHints, BRANCH_HINT_SECTION};
 pub use self::environ::{FunctionBinaryReader, FunctionBodyData, ModuleEnvironment};
 pub use self::middleware::{
     FunctionMiddleware, MiddlewareBinaryReader, MiddlewareReaderState, ModuleMiddleware,
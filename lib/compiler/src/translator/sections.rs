@@ -22,6 +22,7 @@ use wasmer_types::entity::EntityRef;
 use wasmer_types::{
     DataIndex, ElemIndex, FunctionIndex, FunctionType, GlobalIndex, GlobalInit, GlobalType,
     MemoryIndex, MemoryType, Pages, SignatureIndex, TableIndex, TableType, Type, V128,
+    WASM64_MAX_PAGES,
 };
 use wasmer_types::{WasmError, WasmResult};
 use wasmparser::{
@@ -108,24 +109,8 @@ pub fn parse_import_section<'data>(
             TypeRef::Tag(_) => {
                 unimplemented!("exception handling not implemented yet")
             }
-            TypeRef::Memory(WPMemoryType {
-                shared,
-                memory64,
-                initial,
-                maximum,
-            }) => {
-                if memory64 {
-                    unimplemented!("64bit memory not implemented yet");
-                }
-                environ.declare_memory_import(
-                    MemoryType {
-                        minimum: Pages(initial as u32),
-                        maximum: maximum.map(|p| Pages(p as u32)),
-                        shared,
-                    },
-                    module_name,
-                    field_name,
-                )?;
+            TypeRef::Memory(ty) => {
+                environ.declare_memory_import(memory_type(ty)?, module_name, field_name)?;
             }
             TypeRef::Global(ref ty) => {
                 environ.declare_global_import(
@@ -195,6 +180,29 @@ pub fn parse_table_section(
     Ok(())
 }
 
+/// Converts a memory type of `wasmparser`.
+///
+/// 64-bit memories can declare sizes that `Pages` can't represent: the
+/// maximum is clamped, since such memories could never grow that much
+/// anyway, but a minimum that large is refused.
+fn memory_type(ty: WPMemoryType) -> WasmResult<MemoryType> {
+    let WPMemoryType {
+        shared,
+        memory64,
+        initial,
+        maximum,
+    } = ty;
+    let minimum = u32::try_from(initial)
+        .map_err(|_| wasm_unsupported!("memories of more than {} pages", WASM64_MAX_PAGES))?;
+    let minimum = Pages(minimum);
+    let maximum = maximum.map(|p| Pages(u32::try_from(p).unwrap_or(WASM64_MAX_PAGES)));
+    if memory64 {
+        Ok(MemoryType::new64(minimum, maximum, shared))
+    } else {
+        Ok(MemoryType::new(minimum, maximum, shared))
+    }
+}
+
 /// Parses the Memory section of the wasm module.
 pub fn parse_memory_section(
     memories: MemorySectionReader,
@@ -203,20 +211,8 @@ pub fn parse_memory_section(
     environ.reserve_memories(memories.get_count())?;
 
     for entry in memories {
-        let WPMemoryType {
-            shared,
-            memory64,
-            initial,
-            maximum,
-        } = entry.map_err(from_binaryreadererror_wasmerror)?;
-        if memory64 {
-            unimplemented!("64bit memory not implemented yet");
-        }
-        environ.declare_memory(MemoryType {
-            minimum: Pages(initial as u32),
-            maximum: maximum.map(|p| Pages(p as u32)),
-            shared,
-        })?;
+        let ty = entry.map_err(from_binaryreadererror_wasmerror)?;
+        environ.declare_memory(memory_type(ty)?)?;
     }
 
     Ok(())
@@ -425,6 +421,8 @@ pub fn parse_data_section<'data>(
                     .map_err(from_binaryreadererror_wasmerror)?
                 {
                     Operator::I32Const { value } => (None, value as u32 as usize),
+                    // The offsets of the data segments of 64-bit memories.
+                    Operator::I64Const { value } => (None, value as u64 as usize),
                     Operator::GlobalGet { global_index } => {
                         (Some(GlobalIndex::from_u32(global_index)), 0)
                     }
@@ -0,0 +1,266 @@
+//! Validation of WebAssembly modules with detailed diagnostics.
+
+use crate::lib::std::fmt;
+use crate::lib::std::string::{String, ToString};
+use crate::lib::std::vec::Vec;
+use wasmer_types::{Features, FunctionIndex};
+use wasmparser::{BinaryReaderError, Parser, Payload, TypeRef, Validator, WasmFeatures};
+
+/// Where and why a module failed validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// The description of the error.
+    pub message: String,
+    /// The offset of the error in the module binary.
+    pub offset: usize,
+    /// The function whose body is invalid, if the error is in a
+    /// function body.
+    pub function: Option<FunctionIndex>,
+    /// The offending instruction, if the error is in a function body.
+    pub operator: Option<String>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at offset {:#x}", self.message, self.offset)?;
+        if let Some(function) = self.function {
+            write!(f, ", in function {}", function.as_u32())?;
+        }
+        if let Some(ref operator) = self.operator {
+            write!(f, ", at `{}`", operator)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// The result of validating a module with [`validate_module_detailed`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// The features the module was validated with.
+    pub enabled_features: Features,
+    /// The features the module can't be validated without.
+    ///
+    /// They are only known when the module is valid with all the
+    /// features enabled.
+    pub required_features: Option<Features>,
+    /// Why the module is invalid with the enabled features, if it is.
+    pub error: Option<ValidationError>,
+    /// Where the module first needs each of the required features.
+    pub feature_uses: Vec<FeatureUse>,
+}
+
+impl ValidationReport {
+    /// Whether the module is valid with the enabled features.
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// The names of the required features that aren't enabled.
+    pub fn unsupported_features(&self) -> Vec<&'static str> {
+        let required = match self.required_features {
+            Some(ref required) => required,
+            None => return Vec::new(),
+        };
+        FEATURES
+            .iter()
+            .filter(|(_, toggle)| {
+                is_set(required, *toggle) && !is_set(&self.enabled_features, *toggle)
+            })
+            .map(|(name, _)| *name)
+            .collect()
+    }
+}
+
+/// The first place where a module needs a feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureUse {
+    /// The feature, named after its proposal (e.g. `bulk-memory`).
+    pub feature: &'static str,
+    /// Why validation fails without the feature.
+    pub location: ValidationError,
+}
+
+type Toggle = fn(&mut Features) -> &mut bool;
+
+fn is_set(features: &Features, toggle: Toggle) -> bool {
+    *toggle(&mut features.clone())
+}
+
+/// The features that can be required, named after their proposals.
+const FEATURES: [(&str, Toggle); 11] = [
+    ("threads", |f| &mut f.threads),
+    ("reference-types", |f| &mut f.reference_types),
+    ("simd", |f| &mut f.simd),
+    ("bulk-memory", |f| &mut f.bulk_memory),
+    ("multi-value", |f| &mut f.multi_value),
+    ("tail-call", |f| &mut f.tail_call),
+    ("multi-memory", |f| &mut f.multi_memory),
+    ("memory64", |f| &mut f.memory64),
+    ("exceptions", |f| &mut f.exceptions),
+    ("relaxed-simd", |f| &mut f.relaxed_simd),
+    ("extended-const", |f| &mut f.extended_const),
+];
+
+/// The `wasmparser` features matching `features`.
+pub(crate) fn wasm_features(features: &Features) -> WasmFeatures {
+    WasmFeatures {
+        bulk_memory: features.bulk_memory,
+        threads: features.threads,
+        reference_types: features.reference_types,
+        multi_value: features.multi_value,
+        simd: features.simd,
+        tail_call: features.tail_call,
+        multi_memory: features.multi_memory,
+        memory64: features.memory64,
+        exceptions: features.exceptions,
+        deterministic_only: false,
+        extended_const: features.extended_const,
+        relaxed_simd: features.relaxed_simd,
+        mutable_global: true,
+        saturating_float_to_int: true,
+        sign_extension: true,
+        component_model: false,
+    }
+}
+
+/// Features with every proposal enabled.
+fn all_features() -> Features {
+    Features {
+        threads: true,
+        reference_types: true,
+        simd: true,
+        bulk_memory: true,
+        multi_value: true,
+        tail_call: true,
+        module_linking: false,
+        multi_memory: true,
+        memory64: true,
+        exceptions: true,
+        relaxed_simd: true,
+        extended_const: true,
+    }
+}
+
+/// Features with every proposal disabled.
+fn no_features() -> Features {
+    Features {
+        threads: false,
+        reference_types: false,
+        simd: false,
+        bulk_memory: false,
+        multi_value: false,
+        tail_call: false,
+        module_linking: false,
+        multi_memory: false,
+        memory64: false,
+        exceptions: false,
+        relaxed_simd: false,
+        extended_const: false,
+    }
+}
+
+fn validate(features: &Features, data: &[u8]) -> Result<(), BinaryReaderError> {
+    Validator::new_with_features(wasm_features(features))
+        .validate_all(data)
+        .map(|_| ())
+}
+
+/// Validates a module with the given `features`, without compiling it.
+///
+/// Unlike [`Compiler::validate_module`](crate::Compiler::validate_module),
+/// the error says which function and instruction are invalid, and the
+/// report lists the features the module requires.
+pub fn validate_module_detailed(features: &Features, data: &[u8]) -> ValidationReport {
+    let error = validate(features, data)
+        .err()
+        .map(|error| locate_error(data, error));
+    let (required_features, feature_uses) = match required_features(data) {
+        Some((required, uses)) => (Some(required), uses),
+        None => (None, Vec::new()),
+    };
+    ValidationReport {
+        enabled_features: features.clone(),
+        required_features,
+        error,
+        feature_uses,
+    }
+}
+
+/// Finds the features the module requires, and where it first needs each
+/// of them, by validating it with each feature turned off in turn.
+fn required_features(data: &[u8]) -> Option<(Features, Vec<FeatureUse>)> {
+    validate(&all_features(), data).ok()?;
+
+    let mut required = no_features();
+    let mut uses = Vec::new();
+    for (name, toggle) in FEATURES.iter() {
+        let mut features = all_features();
+        *toggle(&mut features) = false;
+        // Relaxed SIMD extends SIMD.
+        if !features.simd {
+            features.relaxed_simd = false;
+        }
+        if let Err(error) = validate(&features, data) {
+            *toggle(&mut required) = true;
+            uses.push(FeatureUse {
+                feature: *name,
+                location: locate_error(data, error),
+            });
+        }
+    }
+    Some((required, uses))
+}
+
+/// Finds the function and instruction at the offset of `error`.
+fn locate_error(data: &[u8], error: BinaryReaderError) -> ValidationError {
+    let offset = error.offset();
+    let mut located = ValidationError {
+        message: error.message().to_string(),
+        offset,
+        function: None,
+        operator: None,
+    };
+
+    let mut num_imported_functions = 0;
+    let mut num_bodies = 0;
+    for payload in Parser::new(0).parse_all(data) {
+        match payload {
+            Ok(Payload::ImportSection(imports)) => {
+                num_imported_functions = imports
+                    .into_iter()
+                    .filter_map(Result::ok)
+                    .filter(|import| matches!(import.ty, TypeRef::Func(_)))
+                    .count() as u32;
+            }
+            Ok(Payload::CodeSectionEntry(body)) => {
+                if body.range().contains(&offset) {
+                    located.function =
+                        Some(FunctionIndex::from_u32(num_imported_functions + num_bodies));
+                    located.operator = operator_at(&body, offset);
+                    break;
+                }
+                num_bodies += 1;
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    located
+}
+
+/// The last operator of `body` that starts at or before `offset`.
+fn operator_at(body: &wasmparser::FunctionBody, offset: usize) -> Option<String> {
+    let mut reader = body.get_operators_reader().ok()?;
+    let mut found = None;
+    while !reader.eof() {
+        let (operator, operator_offset) = match reader.read_with_offset() {
+            Ok(operator) => operator,
+            Err(_) => break,
+        };
+        if operator_offset > offset {
+            break;
+        }
+        found = Some(format!("{:?}", operator));
+    }
+    found
+}
@@ -0,0 +1,293 @@
+//! `fuel` is a middleware that makes WebAssembly code pay for its
+//! execution with the fuel of its store.
+//!
+//! Every basic block checks that the store has enough fuel left for the
+//! cost of its operators, and consumes it before running. When there
+//! isn't enough fuel left, the block traps with
+//! [`TrapCode::OutOfFuel`] without consuming anything. The fuel is shared
+//! by all the instances of a store, survives their instantiation, and is
+//! read and set with [`Store::fuel_remaining`] and [`Store::set_fuel`],
+//! also from host functions. A store starts without fuel.
+//!
+//! Unlike [`Metering`](crate::Metering), which gives every instance a
+//! budget of its own, the fuel doesn't need an instance to be refilled.
+//!
+//! [`Store::fuel_remaining`]: wasmer::Store::fuel_remaining
+//! [`Store::set_fuel`]: wasmer::Store::set_fuel
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use wasmer::wasmparser::{BlockType as WpTypeOrFuncType, Operator};
+use wasmer::{
+    FunctionMiddleware, GlobalInit, GlobalType, LocalFunctionIndex, MiddlewareError,
+    MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::{GlobalIndex, ModuleInfo, TrapCode};
+
+/// The module-level fuel middleware.
+///
+/// # Panic
+///
+/// An instance of `Fuel` should _not_ be shared among different modules,
+/// since it tracks module-specific information like the global index of
+/// the fuel. Attempts to use a `Fuel` instance from multiple modules will
+/// result in a panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::{wasmparser::Operator, CompilerConfig};
+/// use wasmer_middlewares::Fuel;
+///
+/// fn create_fuel_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     // Every operator costs one unit of fuel.
+///     let cost_function = |_operator: &Operator| -> u64 { 1 };
+///
+///     compiler_config.push_middleware(Arc::new(Fuel::new(cost_function)));
+/// }
+/// ```
+pub struct Fuel<F: Fn(&Operator) -> u64 + Send + Sync> {
+    /// Function that maps each operator to its cost in fuel.
+    cost_function: Arc<F>,
+
+    /// The global index of the fuel.
+    global_index: Mutex<Option<GlobalIndex>>,
+}
+
+/// The function-level fuel middleware.
+pub struct FunctionFuel<F: Fn(&Operator) -> u64 + Send + Sync> {
+    /// Function that maps each operator to its cost in fuel.
+    cost_function: Arc<F>,
+
+    /// The global index of the fuel.
+    global_index: GlobalIndex,
+
+    /// Accumulated cost of the current basic block.
+    accumulated_cost: u64,
+}
+
+impl<F: Fn(&Operator) -> u64 + Send + Sync> Fuel<F> {
+    /// Creates a `Fuel` middleware.
+    pub fn new(cost_function: F) -> Self {
+        Self {
+            cost_function: Arc::new(cost_function),
+            global_index: Mutex::new(None),
+        }
+    }
+}
+
+impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for Fuel<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fuel")
+            .field("cost_function", &"<function>")
+            .field("global_index", &self.global_index)
+            .finish()
+    }
+}
+
+impl<F: Fn(&Operator) -> u64 + Send + Sync + 'static> ModuleMiddleware for Fuel<F> {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionFuel {
+            cost_function: self.cost_function.clone(),
+            global_index: self.global_index.lock().unwrap().unwrap(),
+            accumulated_cost: 0,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_index = self.global_index.lock().unwrap();
+
+        if global_index.is_some() {
+            panic!("Fuel::transform_module_info: Attempting to use a `Fuel` middleware from multiple modules.");
+        }
+
+        let fuel = module_info
+            .globals
+            .push(GlobalType::new(Type::I64, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I64Const(0));
+
+        module_info.fuel_global = Some(fuel);
+
+        *global_index = Some(fuel);
+    }
+}
+
+impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for FunctionFuel<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionFuel")
+            .field("cost_function", &"<function>")
+            .field("global_index", &self.global_index)
+            .finish()
+    }
+}
+
+impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMiddleware for FunctionFuel<F> {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        // The cost of the operator is added before the checks, so that
+        // operators like `Call` are paid for before they run.
+        self.accumulated_cost += (self.cost_function)(&operator);
+
+        // Possible sources and targets of a branch. Pay for the previous
+        // basic block.
+        match operator {
+            Operator::Loop { .. } // loop headers are branch targets
+            | Operator::End // block ends are branch targets
+            | Operator::Else // "else" is the "end" of an if branch
+            | Operator::Br { .. } // branch source
+            | Operator::BrTable { .. } // branch source
+            | Operator::BrIf { .. } // branch source
+            | Operator::Call { .. } // function call - branch source
+            | Operator::CallIndirect { .. } // function call - branch source
+            | Operator::ReturnCall { .. } // tail call - branch source
+            | Operator::ReturnCallIndirect { .. } // tail call - branch source
+            | Operator::Return // end of function - branch source
+                if self.accumulated_cost > 0 =>
+            {
+                let fuel = self.global_index.as_u32();
+                let cost = self.accumulated_cost as i64;
+                state.extend(&[
+                    // if unsigned(globals[fuel]) < unsigned(cost) { throw(); }
+                    Operator::GlobalGet { global_index: fuel },
+                    Operator::I64Const { value: cost },
+                    Operator::I64LtU,
+                    Operator::If { blockty: WpTypeOrFuncType::Empty },
+                ]);
+                state.push_trap(TrapCode::OutOfFuel);
+                state.extend(&[
+                    Operator::End,
+
+                    // globals[fuel] -= cost;
+                    Operator::GlobalGet { global_index: fuel },
+                    Operator::I64Const { value: cost },
+                    Operator::I64Sub,
+                    Operator::GlobalSet { global_index: fuel },
+                ]);
+
+                self.accumulated_cost = 0;
+            }
+            _ => {}
+        }
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Instance, Module, Store,
+        TypedFunction,
+    };
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"
+            (module
+            (func $add_one (export "add_one") (param i32) (result i32)
+                local.get 0
+                i32.const 1
+                i32.add)
+            (func $spin (export "spin")
+                (loop $forever
+                    br $forever)))
+            "#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    fn cost_function(_: &Operator) -> u64 {
+        1
+    }
+
+    fn instantiate() -> (Store, Module, Instance) {
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(Fuel::new(cost_function)));
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode()).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        (store, module, instance)
+    }
+
+    fn add_one(store: &Store, instance: &Instance) -> TypedFunction<i32, i32> {
+        instance
+            .exports
+            .get_function("add_one")
+            .unwrap()
+            .typed(store)
+            .unwrap()
+    }
+
+    #[test]
+    fn fuel_is_consumed_and_refilled() {
+        let (mut store, _, instance) = instantiate();
+        let add_one = add_one(&store, &instance);
+
+        // A store starts without fuel.
+        assert_eq!(store.fuel_remaining(), 0);
+        let err = add_one.call(&mut store, 1).unwrap_err();
+        assert_eq!(err.to_trap(), Some(TrapCode::OutOfFuel));
+
+        // `add_one` costs 4: `local.get`, `i32.const`, `i32.add` and `end`.
+        store.set_fuel(10);
+        assert_eq!(add_one.call(&mut store, 1).unwrap(), 2);
+        assert_eq!(add_one.call(&mut store, 2).unwrap(), 3);
+        assert_eq!(store.fuel_remaining(), 2);
+
+        // The block that can't be paid for doesn't consume anything.
+        let err = add_one.call(&mut store, 3).unwrap_err();
+        assert_eq!(err.to_trap(), Some(TrapCode::OutOfFuel));
+        assert_eq!(store.fuel_remaining(), 2);
+
+        store.set_fuel(4);
+        assert_eq!(add_one.call(&mut store, 3).unwrap(), 4);
+        assert_eq!(store.fuel_remaining(), 0);
+    }
+
+    #[test]
+    fn fuel_stops_infinite_loop() {
+        let (mut store, _, instance) = instantiate();
+        let spin: TypedFunction<(), ()> = instance
+            .exports
+            .get_function("spin")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        store.set_fuel(1000);
+        let err = spin.call(&mut store).unwrap_err();
+        assert_eq!(err.to_trap(), Some(TrapCode::OutOfFuel));
+        assert!(store.fuel_remaining() < 1000);
+    }
+
+    #[test]
+    fn fuel_is_shared_by_the_instances_of_a_store() {
+        let (mut store, module, instance) = instantiate();
+        store.set_fuel(8);
+
+        // Instantiating doesn't reset the fuel.
+        let other = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        assert_eq!(store.fuel_remaining(), 8);
+
+        let add_one = add_one(&store, &instance);
+        let other_add_one = self::add_one(&store, &other);
+        assert_eq!(add_one.call(&mut store, 1).unwrap(), 2);
+        assert_eq!(other_add_one.call(&mut store, 1).unwrap(), 2);
+        assert_eq!(store.fuel_remaining(), 0);
+        let err = other_add_one.call(&mut store, 1).unwrap_err();
+        assert_eq!(err.to_trap(), Some(TrapCode::OutOfFuel));
+    }
+}
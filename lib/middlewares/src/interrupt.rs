@@ -0,0 +1,216 @@
+//! `interrupt` is a middleware that lets another thread stop a running
+//! WebAssembly instance.
+//!
+//! The middleware keeps an interruption flag in a global, and checks it
+//! at the start of every function and of every loop iteration. The flag
+//! is shared by all the instances of a store, and the
+//! [`InterruptHandle`] returned by [`Store::interrupt_handle`] sets it
+//! from any thread: the instances then trap at their next check, which
+//! bounds the time between the request and the trap even for guests stuck
+//! in an infinite loop. Time spent in host functions isn't interrupted.
+//!
+//! The flag stays set after the trap, and must be cleared with
+//! [`InterruptHandle::reset`] before the instances can be called again.
+//!
+//! [`Store::interrupt_handle`]: wasmer::Store::interrupt_handle
+
+use std::sync::Mutex;
+use wasmer::wasmparser::{BlockType as WpTypeOrFuncType, Operator};
+pub use wasmer::InterruptHandle;
+use wasmer::{
+    FunctionMiddleware, GlobalInit, GlobalType, LocalFunctionIndex, MiddlewareError,
+    MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::{GlobalIndex, ModuleInfo};
+
+/// The module-level interrupt middleware.
+///
+/// # Panic
+///
+/// An instance of `Interrupt` should _not_ be shared among different
+/// modules, since it tracks module-specific information like the
+/// global index of the flag. Attempts to use an `Interrupt` instance
+/// from multiple modules will result in a panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::Interrupt;
+///
+/// fn create_interrupt_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     compiler_config.push_middleware(Arc::new(Interrupt::new()));
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Interrupt {
+    /// The global index of the interruption flag.
+    global_index: Mutex<Option<GlobalIndex>>,
+}
+
+impl Interrupt {
+    /// Creates an `Interrupt` middleware.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ModuleMiddleware for Interrupt {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionInterrupt {
+            global_index: self.global_index.lock().unwrap().unwrap(),
+            entered: false,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_index = self.global_index.lock().unwrap();
+
+        if global_index.is_some() {
+            panic!("Interrupt::transform_module_info: Attempting to use an `Interrupt` middleware from multiple modules.");
+        }
+
+        let flag = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        module_info.interrupt_global = Some(flag);
+
+        *global_index = Some(flag);
+    }
+}
+
+/// The function-level interrupt middleware.
+#[derive(Debug)]
+pub struct FunctionInterrupt {
+    /// The global index of the interruption flag.
+    global_index: GlobalIndex,
+
+    /// Whether the check at the start of the function has been emitted.
+    entered: bool,
+}
+
+impl FunctionInterrupt {
+    fn check(&self, state: &mut MiddlewareReaderState<'_>) {
+        state.extend(&[
+            // if globals[flag] != 0 { throw(); }
+            Operator::GlobalGet {
+                global_index: self.global_index.as_u32(),
+            },
+            Operator::If {
+                blockty: WpTypeOrFuncType::Empty,
+            },
+            Operator::Unreachable,
+            Operator::End,
+        ]);
+    }
+}
+
+impl FunctionMiddleware for FunctionInterrupt {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if !self.entered {
+            self.entered = true;
+            self.check(state);
+        }
+
+        match operator {
+            // The check is the first thing executed by every iteration.
+            Operator::Loop { .. } => {
+                state.push_operator(operator);
+                self.check(state);
+            }
+            _ => state.push_operator(operator),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::time::Duration;
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Instance, Module, Store,
+        TypedFunction,
+    };
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"
+            (module
+            (func $spin (export "spin")
+                (loop $forever
+                    br $forever))
+            (func $answer (export "answer") (result i32)
+                i32.const 42))
+            "#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    #[test]
+    fn interrupt_stops_infinite_loop() {
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(Interrupt::new()));
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode()).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+
+        let spin: TypedFunction<(), ()> = instance
+            .exports
+            .get_function("spin")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        let answer: TypedFunction<(), i32> = instance
+            .exports
+            .get_function("answer")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        let handle = store.interrupt_handle();
+        assert!(!handle.is_interrupted());
+
+        let interrupter = std::thread::spawn({
+            let handle = handle.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(50));
+                handle.interrupt();
+            }
+        });
+        assert!(spin.call(&mut store).is_err());
+        interrupter.join().unwrap();
+        assert!(handle.is_interrupted());
+
+        // Calls trap until the flag is reset, in every instance of the
+        // store, including the ones created in the meantime.
+        assert!(answer.call(&mut store).is_err());
+        let other = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        let other_answer: TypedFunction<(), i32> = other
+            .exports
+            .get_function("answer")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        assert!(other_answer.call(&mut store).is_err());
+        handle.reset();
+        assert_eq!(answer.call(&mut store).unwrap(), 42);
+        assert_eq!(other_answer.call(&mut store).unwrap(), 42);
+    }
+}
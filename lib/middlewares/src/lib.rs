@@ -1,6 +1,16 @@
+pub mod fuel;
+pub mod interrupt;
 pub mod metering;
+pub mod pgo;
+pub mod profiling;
+pub mod stack_depth;
 
 // The most commonly used symbol are exported at top level of the
 // module. Others are available via modules,
 // e.g. `wasmer_middlewares::metering::get_remaining_points`
+pub use fuel::Fuel;
+pub use interrupt::{Interrupt, InterruptHandle};
 pub use metering::Metering;
+pub use pgo::PgoInstrumentation;
+pub use profiling::Profiling;
+pub use stack_depth::StackDepthLimit;
@@ -226,6 +226,8 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMiddleware for FunctionMeter
             | Operator::BrIf { .. } // branch source
             | Operator::Call { .. } // function call - branch source
             | Operator::CallIndirect { .. } // function call - branch source
+            | Operator::ReturnCall { .. } // tail call - branch source
+            | Operator::ReturnCallIndirect { .. } // tail call - branch source
             | Operator::Return // end of function - branch source
             => {
                 if self.accumulated_cost > 0 {
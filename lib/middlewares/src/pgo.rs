@@ -0,0 +1,398 @@
+//! `pgo` is a middleware for recording an [`ExecutionProfile`] of a
+//! WebAssembly module: how many times each function is called and which
+//! way each of its conditional branches goes.
+//!
+//! The recorded profile can be given back to the LLVM compiler (see
+//! `LLVM::profile`) to compile the module again with profile-guided
+//! optimizations, or to the Cranelift compiler (see
+//! `Cranelift::profile`) to move the cold code out of the hot path.
+//!
+//! Every counter lives in a mutable `i64` global that is exported from
+//! the module, which is why the middleware needs the bytes of the module
+//! upfront: it has to know how many branches each function has before
+//! the functions are compiled.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::Mutex;
+use wasmer::wasmparser::{Operator, Parser, Payload};
+use wasmer::{
+    AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::entity::PrimaryMap;
+use wasmer_types::{
+    BranchProfile, ExecutionProfile, FunctionExecutionProfile, GlobalIndex, ModuleInfo,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BranchKind {
+    /// An `if`: the second counter counts the runs of the `then` arm.
+    If,
+    /// A `br_if`: the second counter counts the fall-throughs.
+    BrIf,
+}
+
+impl BranchKind {
+    fn of(operator: &Operator) -> Option<Self> {
+        match operator {
+            Operator::If { .. } => Some(Self::If),
+            Operator::BrIf { .. } => Some(Self::BrIf),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct BranchCounters {
+    kind: BranchKind,
+    executed: GlobalIndex,
+    second: GlobalIndex,
+}
+
+#[derive(Debug, Clone)]
+struct FunctionCounters {
+    calls: GlobalIndex,
+    branches: Vec<BranchCounters>,
+}
+
+/// The module-level PGO instrumentation middleware.
+///
+/// It should be the first middleware of the chain, so that it sees the
+/// same function bodies as the ones it was created from.
+///
+/// # Panic
+///
+/// An instance of `PgoInstrumentation` should _not_ be shared among
+/// different modules, since it tracks module-specific information like
+/// the global indexes of the counters. Attempts to use a
+/// `PgoInstrumentation` instance from multiple modules will result in a
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::{CompilerConfig, MiddlewareError};
+/// use wasmer_middlewares::PgoInstrumentation;
+///
+/// fn instrument(
+///     compiler_config: &mut dyn CompilerConfig,
+///     wasm: &[u8],
+/// ) -> Result<Arc<PgoInstrumentation>, MiddlewareError> {
+///     let instrumentation = Arc::new(PgoInstrumentation::new(wasm)?);
+///     compiler_config.push_middleware(instrumentation.clone());
+///     Ok(instrumentation)
+/// }
+/// ```
+pub struct PgoInstrumentation {
+    /// The kinds of the branches of each local function, found in the
+    /// module bytes.
+    branches: Vec<Vec<BranchKind>>,
+
+    /// The counters of each local function.
+    counters: Mutex<Option<PrimaryMap<LocalFunctionIndex, FunctionCounters>>>,
+}
+
+impl PgoInstrumentation {
+    /// Creates a `PgoInstrumentation` middleware for the module `wasm`
+    /// (in the binary format).
+    pub fn new(wasm: &[u8]) -> Result<Self, MiddlewareError> {
+        let error = |err: wasmer::wasmparser::BinaryReaderError| {
+            MiddlewareError::new("PgoInstrumentation", err.to_string())
+        };
+
+        let mut branches = Vec::new();
+        for payload in Parser::new(0).parse_all(wasm) {
+            if let Payload::CodeSectionEntry(body) = payload.map_err(error)? {
+                let mut reader = body.get_operators_reader().map_err(error)?;
+                let mut kinds = Vec::new();
+                while !reader.eof() {
+                    if let Some(kind) = BranchKind::of(&reader.read().map_err(error)?) {
+                        kinds.push(kind);
+                    }
+                }
+                branches.push(kinds);
+            }
+        }
+
+        Ok(Self {
+            branches,
+            counters: Mutex::new(None),
+        })
+    }
+
+    fn counters(&self) -> PrimaryMap<LocalFunctionIndex, FunctionCounters> {
+        self.counters
+            .lock()
+            .unwrap()
+            .clone()
+            .expect("PgoInstrumentation was not used to compile a module")
+    }
+}
+
+impl fmt::Debug for PgoInstrumentation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PgoInstrumentation")
+            .field("functions", &self.branches.len())
+            .finish()
+    }
+}
+
+impl ModuleMiddleware for PgoInstrumentation {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        let counters =
+            self.counters.lock().unwrap().as_ref().unwrap()[local_function_index].clone();
+        Box::new(FunctionPgoInstrumentation {
+            counters,
+            entered: false,
+            next_branch: 0,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut counters = self.counters.lock().unwrap();
+
+        if counters.is_some() {
+            panic!("PgoInstrumentation::transform_module_info: Attempting to use a `PgoInstrumentation` middleware from multiple modules.");
+        }
+
+        let num_local_functions = module_info.functions.len() - module_info.num_imported_functions;
+        let mut add_counter = |name: String| {
+            let index = module_info
+                .globals
+                .push(GlobalType::new(Type::I64, Mutability::Var));
+            module_info
+                .global_initializers
+                .push(GlobalInit::I64Const(0));
+            module_info.exports.insert(name, ExportIndex::Global(index));
+            index
+        };
+
+        let mut functions = PrimaryMap::with_capacity(num_local_functions);
+        for index in 0..num_local_functions {
+            let calls = add_counter(format!("wasmer_pgo_{}_calls", index));
+            let branches = self
+                .branches
+                .get(index)
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+                .iter()
+                .enumerate()
+                .map(|(branch, &kind)| BranchCounters {
+                    kind,
+                    executed: add_counter(format!("wasmer_pgo_{}_{}_executed", index, branch)),
+                    second: add_counter(format!("wasmer_pgo_{}_{}_second", index, branch)),
+                })
+                .collect();
+            functions.push(FunctionCounters { calls, branches });
+        }
+
+        *counters = Some(functions);
+    }
+}
+
+/// The function-level PGO instrumentation middleware.
+#[derive(Debug)]
+pub struct FunctionPgoInstrumentation {
+    counters: FunctionCounters,
+
+    /// Whether the call counter has been incremented.
+    entered: bool,
+
+    /// Position of the next conditional branch in the function body.
+    next_branch: usize,
+}
+
+fn increment(state: &mut MiddlewareReaderState<'_>, counter: GlobalIndex) {
+    // Leaves the operand stack untouched, so it can be emitted right
+    // before a branch that consumes its condition.
+    state.extend(&[
+        Operator::GlobalGet {
+            global_index: counter.as_u32(),
+        },
+        Operator::I64Const { value: 1 },
+        Operator::I64Add,
+        Operator::GlobalSet {
+            global_index: counter.as_u32(),
+        },
+    ]);
+}
+
+impl FunctionMiddleware for FunctionPgoInstrumentation {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if !self.entered {
+            self.entered = true;
+            increment(state, self.counters.calls);
+        }
+
+        let kind = match BranchKind::of(&operator) {
+            Some(kind) => kind,
+            None => {
+                state.push_operator(operator);
+                return Ok(());
+            }
+        };
+
+        let branch = self.next_branch;
+        self.next_branch += 1;
+        // Branches that were added by other middlewares are not counted.
+        let counters = match self.counters.branches.get(branch) {
+            Some(counters) if counters.kind == kind => counters.clone(),
+            _ => {
+                state.push_operator(operator);
+                return Ok(());
+            }
+        };
+
+        increment(state, counters.executed);
+        state.push_operator(operator);
+        increment(state, counters.second);
+
+        Ok(())
+    }
+}
+
+/// Reads the profile recorded so far by an [`Instance`] of a module
+/// compiled with the [`PgoInstrumentation`] middleware.
+///
+/// # Panic
+///
+/// The [`Instance`] must have been processed with the
+/// [`PgoInstrumentation`] middleware at compile time, otherwise this will
+/// panic.
+pub fn get_execution_profile(
+    ctx: &mut impl AsStoreMut,
+    instance: &Instance,
+    instrumentation: &PgoInstrumentation,
+) -> ExecutionProfile {
+    let mut read = |name: String| -> u64 {
+        let value: i64 = instance
+            .exports
+            .get_global(&name)
+            .unwrap_or_else(|_| panic!("Can't get `{}` from Instance", name))
+            .get(ctx)
+            .try_into()
+            .unwrap_or_else(|_| panic!("`{}` from Instance has wrong type", name));
+        value as u64
+    };
+
+    let mut profile = ExecutionProfile::default();
+    for (index, counters) in instrumentation.counters().iter() {
+        let index = index.as_u32();
+        let calls = read(format!("wasmer_pgo_{}_calls", index));
+        let branches = counters
+            .branches
+            .iter()
+            .enumerate()
+            .map(|(branch, counters)| {
+                let executed = read(format!("wasmer_pgo_{}_{}_executed", index, branch));
+                let second = read(format!("wasmer_pgo_{}_{}_second", index, branch));
+                match counters.kind {
+                    BranchKind::If => BranchProfile {
+                        taken: second,
+                        not_taken: executed.saturating_sub(second),
+                    },
+                    BranchKind::BrIf => BranchProfile {
+                        taken: executed.saturating_sub(second),
+                        not_taken: second,
+                    },
+                }
+            })
+            .collect();
+        profile
+            .functions
+            .push(FunctionExecutionProfile { calls, branches });
+    }
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"
+            (module
+            (func $count_down (export "count_down") (param $n i32) (result i32)
+                (local $odd i32)
+                (block $done
+                    (loop $continue
+                        local.get $n
+                        i32.eqz
+                        br_if $done
+                        local.get $n
+                        i32.const 1
+                        i32.and
+                        (if
+                            (then
+                                local.get $odd
+                                i32.const 1
+                                i32.add
+                                local.set $odd))
+                        local.get $n
+                        i32.const 1
+                        i32.sub
+                        local.set $n
+                        br $continue))
+                local.get $odd))
+            "#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    #[test]
+    fn records_calls_and_branches() {
+        let wasm = bytecode();
+        let instrumentation = Arc::new(PgoInstrumentation::new(&wasm).unwrap());
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(instrumentation.clone());
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, &wasm).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+
+        let count_down: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("count_down")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        assert_eq!(count_down.call(&mut store, 5).unwrap(), 3);
+        assert_eq!(count_down.call(&mut store, 2).unwrap(), 1);
+
+        let profile = get_execution_profile(&mut store, &instance, &instrumentation);
+        let function = profile.function(LocalFunctionIndex::from_u32(0)).unwrap();
+        assert_eq!(function.calls, 2);
+        assert_eq!(
+            function.branches,
+            vec![
+                // `br_if $done` jumps once per call.
+                BranchProfile {
+                    taken: 2,
+                    not_taken: 7
+                },
+                // The `then` arm runs for 5, 3, 1 and 1.
+                BranchProfile {
+                    taken: 4,
+                    not_taken: 3
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,450 @@
+//! `profiling` is a middleware for measuring how much time is spent
+//! in each function of a WebAssembly module.
+//!
+//! The middleware keeps the index of the function that is currently
+//! executing in a global. While a call is being profiled with a
+//! [`Profiler`], a background thread samples that global at a fixed
+//! interval and attributes every sample to the function it finds
+//! there. Time spent in host functions is attributed to the function
+//! that called them.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wasmer::wasmparser::Operator;
+use wasmer::{
+    AsStoreMut, ExportIndex, Extern, FunctionMiddleware, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{FunctionIndex, GlobalIndex, ModuleInfo};
+use wasmer_vm::{VMExtern, VMGlobalDefinition};
+
+/// Name of the exported global holding the function that is executing.
+const CURRENT_FUNCTION_EXPORT: &str = "wasmer_profiling_current_function";
+
+/// Value of the global when no function of the module is executing.
+const IDLE: i32 = -1;
+
+#[derive(Debug, Clone)]
+struct ProfilingState {
+    /// The global that holds the index of the function being executed.
+    current_function: GlobalIndex,
+    /// Number of imported functions, used to turn local indexes into
+    /// module-wide function indexes.
+    num_imported_functions: usize,
+}
+
+/// The module-level profiling middleware.
+///
+/// # Panic
+///
+/// An instance of `Profiling` should _not_ be shared among different
+/// modules, since it tracks module-specific information like the
+/// global index and the function names. Attempts to use a `Profiling`
+/// instance from multiple modules will result in a panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::Profiling;
+///
+/// fn create_profiling_middleware(compiler_config: &mut dyn CompilerConfig) -> Arc<Profiling> {
+///     let profiling = Arc::new(Profiling::new());
+///     compiler_config.push_middleware(profiling.clone());
+///     profiling
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Profiling {
+    state: Mutex<Option<ProfilingState>>,
+
+    /// Names of all the functions of the module, by function index.
+    names: Mutex<Arc<Vec<String>>>,
+}
+
+impl Profiling {
+    /// Creates a `Profiling` middleware.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the names of the functions of the profiled module, by
+    /// function index. Functions without a name in the module are
+    /// called `<func{index}>`.
+    pub fn function_names(&self) -> Arc<Vec<String>> {
+        self.names.lock().unwrap().clone()
+    }
+}
+
+impl ModuleMiddleware for Profiling {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        let state = self.state.lock().unwrap().clone().unwrap();
+        let function_index = state.num_imported_functions + local_function_index.as_u32() as usize;
+        Box::new(FunctionProfiling {
+            current_function: state.current_function,
+            function_index: function_index as i32,
+            entered: false,
+            depth: 1,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.is_some() {
+            panic!("Profiling::transform_module_info: Attempting to use a `Profiling` middleware from multiple modules.");
+        }
+
+        let current_function = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(IDLE));
+
+        module_info.exports.insert(
+            CURRENT_FUNCTION_EXPORT.to_string(),
+            ExportIndex::Global(current_function),
+        );
+
+        let names = (0..module_info.functions.len())
+            .map(|index| {
+                module_info
+                    .function_names
+                    .get(&FunctionIndex::new(index))
+                    .cloned()
+                    .unwrap_or_else(|| format!("<func{}>", index))
+            })
+            .collect();
+        *self.names.lock().unwrap() = Arc::new(names);
+
+        *state = Some(ProfilingState {
+            current_function,
+            num_imported_functions: module_info.num_imported_functions,
+        });
+    }
+}
+
+/// The function-level profiling middleware.
+#[derive(Debug)]
+pub struct FunctionProfiling {
+    /// The global that holds the index of the function being executed.
+    current_function: GlobalIndex,
+
+    /// Index of the function being transformed.
+    function_index: i32,
+
+    /// Whether the function prologue has been emitted.
+    entered: bool,
+
+    /// Depth of the control stack, the function ends when it reaches 0.
+    depth: usize,
+}
+
+impl FunctionProfiling {
+    fn set_current(&self, state: &mut MiddlewareReaderState<'_>, value: i32) {
+        state.extend(&[
+            Operator::I32Const { value },
+            Operator::GlobalSet {
+                global_index: self.current_function.as_u32(),
+            },
+        ]);
+    }
+}
+
+impl FunctionMiddleware for FunctionProfiling {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if !self.entered {
+            self.entered = true;
+            self.set_current(state, self.function_index);
+        }
+
+        match operator {
+            Operator::Block { .. }
+            | Operator::Loop { .. }
+            | Operator::If { .. }
+            | Operator::Try { .. } => {
+                self.depth += 1;
+                state.push_operator(operator);
+            }
+            Operator::End | Operator::Delegate { .. } => {
+                self.depth -= 1;
+                if self.depth == 0 {
+                    self.set_current(state, IDLE);
+                }
+                state.push_operator(operator);
+            }
+            Operator::Return => {
+                self.set_current(state, IDLE);
+                state.push_operator(operator);
+            }
+            // The callee marks itself as idle when it returns, so the
+            // caller has to take over the global again.
+            Operator::Call { .. } | Operator::CallIndirect { .. } => {
+                state.push_operator(operator);
+                self.set_current(state, self.function_index);
+            }
+            _ => state.push_operator(operator),
+        }
+
+        Ok(())
+    }
+}
+
+/// Time spent in a single function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionProfile {
+    /// Index of the function in the module.
+    pub index: u32,
+    /// Name of the function.
+    pub name: String,
+    /// Number of samples taken while the function was executing.
+    pub samples: u64,
+    /// Estimated time spent in the function.
+    pub time: Duration,
+}
+
+/// The result of profiling a call.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileReport {
+    /// The functions that were sampled at least once, from the most to
+    /// the least expensive.
+    pub functions: Vec<FunctionProfile>,
+    /// Number of samples taken while no function of the module was
+    /// executing (e.g. while the host was running).
+    pub idle_samples: u64,
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.idle_samples + self.functions.iter().map(|f| f.samples).sum::<u64>();
+        for function in &self.functions {
+            writeln!(
+                f,
+                "{:>6.2}% {:>10.3}ms  {}",
+                function.samples as f64 * 100.0 / total.max(1) as f64,
+                function.time.as_secs_f64() * 1000.0,
+                function.name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Pointer to the global definition, read from the sampling thread.
+#[derive(Clone, Copy)]
+struct CurrentFunction(NonNull<VMGlobalDefinition>);
+
+// The global lives in the store, which outlives the profiled call
+// the pointer is used for, and it is only ever read atomically.
+unsafe impl Send for CurrentFunction {}
+unsafe impl Sync for CurrentFunction {}
+
+impl CurrentFunction {
+    fn get(&self) -> i32 {
+        // The global is only ever written by the guest with aligned 32-bit
+        // stores, which makes it safe to read it atomically.
+        unsafe {
+            let value = std::ptr::addr_of!((*self.0.as_ptr()).val) as *const AtomicI32;
+            (*value).load(Ordering::Relaxed)
+        }
+    }
+}
+
+/// Samples the functions of an [`Instance`] compiled with the
+/// [`Profiling`] middleware.
+pub struct Profiler {
+    current_function: CurrentFunction,
+    names: Arc<Vec<String>>,
+    interval: Duration,
+}
+
+impl fmt::Debug for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Profiler")
+            .field("functions", &self.names.len())
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+impl Profiler {
+    /// Creates a profiler for an instance.
+    ///
+    /// # Panic
+    ///
+    /// The [`Instance`] must have been processed with the [`Profiling`]
+    /// middleware at compile time, otherwise this will panic.
+    pub fn new(store: &mut impl AsStoreMut, instance: &Instance, profiling: &Profiling) -> Self {
+        let global = instance
+            .exports
+            .get_global(CURRENT_FUNCTION_EXPORT)
+            .expect("Can't get `wasmer_profiling_current_function` from Instance");
+        let handle = match Extern::Global(global.clone()).to_vm_extern() {
+            VMExtern::Global(handle) => handle,
+            _ => unreachable!(),
+        };
+        let definition = handle.get(store.objects_mut()).vmglobal();
+
+        Self {
+            current_function: CurrentFunction(definition),
+            names: profiling.function_names(),
+            interval: Duration::from_micros(100),
+        }
+    }
+
+    /// Sets the interval between two samples.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The interval between two samples.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Returns the index of the function that is executing right now, if
+    /// any. While the guest is calling into the host, this is the function
+    /// that made the call.
+    pub fn current_function(&self) -> Option<u32> {
+        let index = self.current_function.get();
+        if index < 0 {
+            None
+        } else {
+            Some(index as u32)
+        }
+    }
+
+    /// Returns the name of a function of the profiled module.
+    pub fn function_name(&self, index: u32) -> Option<&str> {
+        self.names.get(index as usize).map(String::as_str)
+    }
+
+    /// Runs `f` (which is expected to call into the instance) while
+    /// sampling the function being executed.
+    pub fn profile<T>(&self, f: impl FnOnce() -> T) -> (T, ProfileReport) {
+        let stop = Arc::new(AtomicBool::new(false));
+        let sampler = {
+            let stop = stop.clone();
+            let current_function = self.current_function;
+            let interval = self.interval;
+            std::thread::spawn(move || {
+                let mut samples: HashMap<i32, u64> = HashMap::new();
+                while !stop.load(Ordering::Acquire) {
+                    *samples.entry(current_function.get()).or_default() += 1;
+                    std::thread::sleep(interval);
+                }
+                samples
+            })
+        };
+
+        let ret = f();
+        stop.store(true, Ordering::Release);
+        let samples = sampler.join().expect("the profiler thread panicked");
+
+        (ret, self.report(samples))
+    }
+
+    fn report(&self, samples: HashMap<i32, u64>) -> ProfileReport {
+        let mut report = ProfileReport::default();
+        for (index, count) in samples {
+            if index < 0 {
+                report.idle_samples += count;
+                continue;
+            }
+            report.functions.push(FunctionProfile {
+                index: index as u32,
+                name: self
+                    .names
+                    .get(index as usize)
+                    .cloned()
+                    .unwrap_or_else(|| format!("<func{}>", index)),
+                samples: count,
+                time: self.interval * count as u32,
+            });
+        }
+        report
+            .functions
+            .sort_by(|a, b| b.samples.cmp(&a.samples).then(a.index.cmp(&b.index)));
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"
+            (module
+            (func $spin (param $n i32)
+                (loop $continue
+                    local.get $n
+                    i32.const 1
+                    i32.sub
+                    local.tee $n
+                    br_if $continue))
+            (func $run (export "run") (param $n i32) (result i32)
+                local.get $n
+                call $spin
+                i32.const 42))
+            "#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    #[test]
+    fn profiler_attributes_samples() {
+        let profiling = Arc::new(Profiling::new());
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(profiling.clone());
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode()).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+
+        assert_eq!(
+            profiling.function_names().as_slice(),
+            &["spin".to_string(), "run".to_string()]
+        );
+
+        let run: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("run")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        let profiler = Profiler::new(&mut store, &instance, &profiling)
+            .with_interval(Duration::from_micros(50));
+        let (ret, report) = profiler.profile(|| run.call(&mut store, 200_000_000));
+
+        assert_eq!(ret.unwrap(), 42);
+        assert_eq!(report.functions[0].name, "spin");
+        assert!(report.functions[0].samples > 0);
+
+        assert_eq!(profiler.current_function(), None);
+        assert_eq!(profiler.function_name(0), Some("spin"));
+    }
+}
@@ -0,0 +1,644 @@
+//! `stack_depth` is a middleware for putting an exact limit on the
+//! number of nested WebAssembly calls.
+//!
+//! Without it, deep recursion only stops when the native stack
+//! overflows, which depends on the platform, the compiler and the size
+//! of every frame. This middleware instead keeps a counter of the active
+//! frames in a global: each function increments it when it is entered
+//! and decrements it when it returns. A call that would go past the
+//! limit traps with [`TrapCode::StackExhausted`], and
+//! [`get_stack_depth`] reports [`StackDepth::Exhausted`].
+//!
+//! The counter lives in the instance and is not decremented by frames
+//! that are unwound by a trap, whatever its cause. After a call that
+//! trapped, the counter must be brought back to zero with
+//! [`reset_stack_depth`] before the instance is used again: until then,
+//! every call starts from the depth at which the trap happened, and
+//! traps right away once the limit was reached.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::Mutex;
+use wasmer::wasmparser::{BlockType as WpTypeOrFuncType, Operator};
+use wasmer::{
+    AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::{GlobalIndex, ModuleInfo, TrapCode};
+
+#[derive(Clone)]
+struct StackDepthGlobalIndexes(GlobalIndex, GlobalIndex, GlobalIndex);
+
+impl StackDepthGlobalIndexes {
+    /// The global index in the current module for the number of active frames.
+    fn current_depth(&self) -> GlobalIndex {
+        self.0
+    }
+
+    /// The global index in the current module for a boolean indicating whether the
+    /// limit has been reached or not.
+    /// This boolean is represented as a i32 global:
+    ///   * 0: the limit has not been reached
+    ///   * 1: the stack has been exhausted
+    fn stack_exhausted(&self) -> GlobalIndex {
+        self.1
+    }
+
+    /// The global index in the current module where the index of a
+    /// `br_table` is kept while checking whether it leaves the function.
+    fn scratch(&self) -> GlobalIndex {
+        self.2
+    }
+}
+
+impl fmt::Debug for StackDepthGlobalIndexes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StackDepthGlobalIndexes")
+            .field("current_depth", &self.current_depth())
+            .field("stack_exhausted", &self.stack_exhausted())
+            .field("scratch", &self.scratch())
+            .finish()
+    }
+}
+
+/// The module-level stack depth middleware.
+///
+/// # Panic
+///
+/// An instance of `StackDepthLimit` should _not_ be shared among
+/// different modules, since it tracks module-specific information
+/// like the global index to store the depth. Attempts to use a
+/// `StackDepthLimit` instance from multiple modules will result in a
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::StackDepthLimit;
+///
+/// fn create_stack_depth_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     // Allow at most 1000 nested calls.
+///     let stack_depth = Arc::new(StackDepthLimit::new(1000));
+///
+///     compiler_config.push_middleware(stack_depth);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct StackDepthLimit {
+    /// Maximum number of active frames.
+    limit: u32,
+
+    /// The global indexes for the stack depth.
+    global_indexes: Mutex<Option<StackDepthGlobalIndexes>>,
+}
+
+/// The function-level stack depth middleware.
+#[derive(Debug)]
+pub struct FunctionStackDepthLimit {
+    /// Maximum number of active frames.
+    limit: u32,
+
+    /// The global indexes for the stack depth.
+    global_indexes: StackDepthGlobalIndexes,
+
+    /// Whether the function prologue has been emitted.
+    entered: bool,
+
+    /// Depth of the control stack, the function ends when it reaches 0.
+    depth: usize,
+}
+
+/// Represents the state of the stack depth counter.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
+pub enum StackDepth {
+    /// The number of frames that are currently active.
+    Current(u32),
+
+    /// A call went past the limit, and the execution has been
+    /// stopped with [`TrapCode::StackExhausted`].
+    Exhausted,
+}
+
+impl StackDepthLimit {
+    /// Creates a `StackDepthLimit` middleware allowing at most `limit`
+    /// nested calls of the functions of the module.
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            global_indexes: Mutex::new(None),
+        }
+    }
+}
+
+impl ModuleMiddleware for StackDepthLimit {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionStackDepthLimit {
+            limit: self.limit,
+            global_indexes: self.global_indexes.lock().unwrap().clone().unwrap(),
+            entered: false,
+            depth: 1,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_indexes = self.global_indexes.lock().unwrap();
+
+        if global_indexes.is_some() {
+            panic!("StackDepthLimit::transform_module_info: Attempting to use a `StackDepthLimit` middleware from multiple modules.");
+        }
+
+        // Append a global for the current depth and initialize it.
+        let current_depth_global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        module_info.exports.insert(
+            "wasmer_stack_depth_current".to_string(),
+            ExportIndex::Global(current_depth_global_index),
+        );
+
+        // Append a global for the exhausted stack boolean and initialize it.
+        let stack_exhausted_global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        module_info.exports.insert(
+            "wasmer_stack_depth_exhausted".to_string(),
+            ExportIndex::Global(stack_exhausted_global_index),
+        );
+
+        // Append a private global for the index of `br_table`s.
+        let scratch_global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        *global_indexes = Some(StackDepthGlobalIndexes(
+            current_depth_global_index,
+            stack_exhausted_global_index,
+            scratch_global_index,
+        ))
+    }
+}
+
+impl FunctionStackDepthLimit {
+    /// Checks the limit and increments the depth when the function is entered.
+    fn enter(&self, state: &mut MiddlewareReaderState<'_>) {
+        let current_depth = self.global_indexes.current_depth().as_u32();
+        state.extend(&[
+            // if unsigned(globals[current_depth]) >= limit { throw(); }
+            Operator::GlobalGet {
+                global_index: current_depth,
+            },
+            Operator::I32Const {
+                value: self.limit as i32,
+            },
+            Operator::I32GeU,
+            Operator::If {
+                blockty: WpTypeOrFuncType::Empty,
+            },
+            Operator::I32Const { value: 1 },
+            Operator::GlobalSet {
+                global_index: self.global_indexes.stack_exhausted().as_u32(),
+            },
+        ]);
+        state.push_trap(TrapCode::StackExhausted);
+        state.extend(&[
+            Operator::End,
+            // globals[current_depth] += 1;
+            Operator::GlobalGet {
+                global_index: current_depth,
+            },
+            Operator::I32Const { value: 1 },
+            Operator::I32Add,
+            Operator::GlobalSet {
+                global_index: current_depth,
+            },
+        ]);
+    }
+
+    /// Decrements the depth when the function returns.
+    fn leave(&self, state: &mut MiddlewareReaderState<'_>) {
+        self.add_depth(state, -1);
+    }
+
+    /// Adds `delta` to the depth.
+    fn add_depth(&self, state: &mut MiddlewareReaderState<'_>, delta: i32) {
+        let current_depth = self.global_indexes.current_depth().as_u32();
+        state.extend(&[
+            // globals[current_depth] += delta;
+            Operator::GlobalGet {
+                global_index: current_depth,
+            },
+            Operator::I32Const { value: delta },
+            Operator::I32Add,
+            Operator::GlobalSet {
+                global_index: current_depth,
+            },
+        ]);
+    }
+
+    /// Whether a branch to `relative_depth` returns from the function.
+    fn leaves_function(&self, relative_depth: u32) -> bool {
+        relative_depth as usize == self.depth - 1
+    }
+
+    /// Decrements the depth before a `br_table` if the index on top of the
+    /// stack selects the function label.
+    fn leave_br_table(&self, state: &mut MiddlewareReaderState<'_>, targets: &[u32], default: u32) {
+        let current_depth = self.global_indexes.current_depth().as_u32();
+        let scratch = self.global_indexes.scratch().as_u32();
+        state.extend(&[
+            Operator::GlobalSet {
+                global_index: scratch,
+            },
+            // globals[current_depth] -= (index == i || ...);
+            Operator::GlobalGet {
+                global_index: current_depth,
+            },
+            Operator::I32Const { value: 0 },
+        ]);
+        for (i, target) in targets.iter().enumerate() {
+            if self.leaves_function(*target) {
+                state.extend(&[
+                    Operator::GlobalGet {
+                        global_index: scratch,
+                    },
+                    Operator::I32Const { value: i as i32 },
+                    Operator::I32Eq,
+                    Operator::I32Or,
+                ]);
+            }
+        }
+        if self.leaves_function(default) {
+            // || unsigned(index) >= targets.len()
+            state.extend(&[
+                Operator::GlobalGet {
+                    global_index: scratch,
+                },
+                Operator::I32Const {
+                    value: targets.len() as i32,
+                },
+                Operator::I32GeU,
+                Operator::I32Or,
+            ]);
+        }
+        state.extend(&[
+            Operator::I32Sub,
+            Operator::GlobalSet {
+                global_index: current_depth,
+            },
+            Operator::GlobalGet {
+                global_index: scratch,
+            },
+        ]);
+    }
+}
+
+impl FunctionMiddleware for FunctionStackDepthLimit {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if !self.entered {
+            self.entered = true;
+            self.enter(state);
+        }
+
+        match operator {
+            Operator::Block { .. }
+            | Operator::Loop { .. }
+            | Operator::If { .. }
+            | Operator::Try { .. } => {
+                self.depth += 1;
+            }
+            Operator::End | Operator::Delegate { .. } => {
+                self.depth -= 1;
+                if self.depth == 0 {
+                    self.leave(state);
+                }
+            }
+            // A tail call replaces the current frame, which has to be
+            // released before the callee enters its own.
+            Operator::Return
+            | Operator::ReturnCall { .. }
+            | Operator::ReturnCallIndirect { .. } => {
+                self.leave(state);
+            }
+            // Branches to the function label return from the function too.
+            Operator::Br { relative_depth } if self.leaves_function(relative_depth) => {
+                self.leave(state);
+            }
+            Operator::BrIf { relative_depth } if self.leaves_function(relative_depth) => {
+                // The condition is on top of the stack: release the frame,
+                // and take it back if the branch isn't taken.
+                self.leave(state);
+                state.push_operator(operator);
+                self.add_depth(state, 1);
+                return Ok(());
+            }
+            Operator::BrTable { ref targets } => {
+                let default = targets.default();
+                let targets = targets
+                    .targets()
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| MiddlewareError::new("StackDepthLimit", err.to_string()))?;
+                if self.leaves_function(default)
+                    || targets.iter().any(|target| self.leaves_function(*target))
+                {
+                    self.leave_br_table(state, &targets, default);
+                }
+            }
+            _ => {}
+        }
+
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// Get the stack depth of an [`Instance`][wasmer::Instance].
+///
+/// Note: This can be used in a headless engine after an ahead-of-time
+/// compilation as all required state lives in the instance.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`StackDepthLimit`] middleware at compile time, otherwise this
+/// will panic.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer::Instance;
+/// use wasmer::AsStoreMut;
+/// use wasmer_middlewares::stack_depth::{get_stack_depth, StackDepth};
+///
+/// /// Check whether the instance ran out of stack.
+/// fn is_stack_exhausted(store: &mut impl AsStoreMut, instance: &Instance) -> bool {
+///     matches!(get_stack_depth(store, instance), StackDepth::Exhausted)
+/// }
+/// ```
+pub fn get_stack_depth(ctx: &mut impl AsStoreMut, instance: &Instance) -> StackDepth {
+    let exhausted: i32 = instance
+        .exports
+        .get_global("wasmer_stack_depth_exhausted")
+        .expect("Can't get `wasmer_stack_depth_exhausted` from Instance")
+        .get(ctx)
+        .try_into()
+        .expect("`wasmer_stack_depth_exhausted` from Instance has wrong type");
+
+    if exhausted > 0 {
+        return StackDepth::Exhausted;
+    }
+
+    let depth: i32 = instance
+        .exports
+        .get_global("wasmer_stack_depth_current")
+        .expect("Can't get `wasmer_stack_depth_current` from Instance")
+        .get(ctx)
+        .try_into()
+        .expect("`wasmer_stack_depth_current` from Instance has wrong type");
+
+    StackDepth::Current(depth as u32)
+}
+
+/// Reset the stack depth of an [`Instance`][wasmer::Instance] to zero
+/// and clear the exhausted flag.
+///
+/// This must be called after a call trapped, whether the stack was
+/// exhausted or not, since the frames that were unwound did not release
+/// their depth.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`StackDepthLimit`] middleware at compile time, otherwise this
+/// will panic.
+pub fn reset_stack_depth(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    instance
+        .exports
+        .get_global("wasmer_stack_depth_current")
+        .expect("Can't get `wasmer_stack_depth_current` from Instance")
+        .set(ctx, 0i32.into())
+        .expect("Can't set `wasmer_stack_depth_current` in Instance");
+
+    instance
+        .exports
+        .get_global("wasmer_stack_depth_exhausted")
+        .expect("Can't get `wasmer_stack_depth_exhausted` from Instance")
+        .set(ctx, 0i32.into())
+        .expect("Can't set `wasmer_stack_depth_exhausted` in Instance");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"
+            (module
+            (func $rec (param $n i32) (result i32)
+                local.get $n
+                i32.eqz
+                if
+                    i32.const 0
+                    return
+                end
+                local.get $n
+                i32.const 1
+                i32.sub
+                call $rec
+                i32.const 1
+                i32.add)
+            (func $fail (param $n i32)
+                local.get $n
+                i32.eqz
+                if
+                    unreachable
+                end
+                local.get $n
+                i32.const 1
+                i32.sub
+                call $fail)
+            (func $early (param $n i32) (result i32)
+                i32.const 1
+                local.get $n
+                br_if 0
+                drop
+                i32.const 0)
+            (func $pick (param $n i32) (result i32)
+                block (result i32)
+                    block (result i32)
+                        local.get $n
+                        local.get $n
+                        br_table 0 2 1
+                    end
+                    i32.const 10
+                    i32.add
+                    return
+                end
+                i32.const 20
+                i32.add)
+            (export "rec" (func $rec))
+            (export "fail" (func $fail))
+            (export "early" (func $early))
+            (export "pick" (func $pick)))
+            "#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    fn instantiate() -> (Store, Instance) {
+        let stack_depth = Arc::new(StackDepthLimit::new(10));
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(stack_depth);
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode()).unwrap();
+
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        (store, instance)
+    }
+
+    #[test]
+    fn stack_depth_limit_works() {
+        let (mut store, instance) = instantiate();
+        assert_eq!(
+            get_stack_depth(&mut store, &instance),
+            StackDepth::Current(0)
+        );
+
+        let rec: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("rec")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        // `rec(9)` uses exactly 10 frames, and releases all of them.
+        assert_eq!(rec.call(&mut store, 9).unwrap(), 9);
+        assert_eq!(
+            get_stack_depth(&mut store, &instance),
+            StackDepth::Current(0)
+        );
+
+        // `rec(10)` needs an 11th frame.
+        let err = rec.call(&mut store, 10).unwrap_err();
+        assert_eq!(err.to_trap(), Some(TrapCode::StackExhausted));
+        assert_eq!(
+            get_stack_depth(&mut store, &instance),
+            StackDepth::Exhausted
+        );
+
+        // The instance can be used again once the depth is reset.
+        reset_stack_depth(&mut store, &instance);
+        assert_eq!(
+            get_stack_depth(&mut store, &instance),
+            StackDepth::Current(0)
+        );
+        assert_eq!(rec.call(&mut store, 3).unwrap(), 3);
+    }
+
+    #[test]
+    fn depth_is_kept_until_reset() {
+        let (mut store, instance) = instantiate();
+        let rec: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("rec")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        let fail: TypedFunction<i32, ()> = instance
+            .exports
+            .get_function("fail")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        // Any trap leaves the frames it unwound counted.
+        let err = fail.call(&mut store, 3).unwrap_err();
+        assert_eq!(err.to_trap(), Some(TrapCode::UnreachableCodeReached));
+        assert_eq!(
+            get_stack_depth(&mut store, &instance),
+            StackDepth::Current(4)
+        );
+
+        // So the next calls have less room, until the depth is reset.
+        let err = rec.call(&mut store, 6).unwrap_err();
+        assert_eq!(err.to_trap(), Some(TrapCode::StackExhausted));
+        let err = rec.call(&mut store, 0).unwrap_err();
+        assert_eq!(err.to_trap(), Some(TrapCode::StackExhausted));
+
+        reset_stack_depth(&mut store, &instance);
+        assert_eq!(rec.call(&mut store, 9).unwrap(), 9);
+        assert_eq!(
+            get_stack_depth(&mut store, &instance),
+            StackDepth::Current(0)
+        );
+    }
+
+    #[test]
+    fn branches_out_of_the_function_release_the_frame() {
+        let (mut store, instance) = instantiate();
+        let early: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("early")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        let pick: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("pick")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        // More calls than the limit, whether the branches are taken or not.
+        for _ in 0..20 {
+            assert_eq!(early.call(&mut store, 1).unwrap(), 1);
+            assert_eq!(early.call(&mut store, 0).unwrap(), 0);
+            assert_eq!(
+                get_stack_depth(&mut store, &instance),
+                StackDepth::Current(0)
+            );
+        }
+
+        // In `br_table 0 2 1`, only the second target is the function
+        // label, the others add 10 or 20 to the index before returning.
+        for _ in 0..20 {
+            assert_eq!(pick.call(&mut store, 0).unwrap(), 10);
+            assert_eq!(pick.call(&mut store, 1).unwrap(), 1);
+            assert_eq!(pick.call(&mut store, 2).unwrap(), 22);
+            assert_eq!(pick.call(&mut store, 7).unwrap(), 27);
+            assert_eq!(
+                get_stack_depth(&mut store, &instance),
+                StackDepth::Current(0)
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use url::Url;
 
@@ -40,6 +41,28 @@ pub struct MultiRegistry {
     /// Map from "RegistryUrl" to "LoginToken", in order to
     /// be able to be able to easily switch between registries
     pub tokens: Vec<RegistryLogin>,
+    /// Currently active profile, which takes precedence over
+    /// `active_registry`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_profile: Option<String>,
+    /// Named logins (e.g. "staging"), to switch between registries
+    /// and accounts
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// A named login, selected with `wasmer login --profile`, the `--profile`
+/// flag of commands talking to a registry or `WASMER_PROFILE`
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone)]
+pub struct Profile {
+    /// Registry URL the profile logs into
+    pub registry: String,
+    /// Login token, unless it is kept in the OS keychain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Whether the login token is kept in the OS keychain
+    #[serde(default)]
+    pub keychain: bool,
 }
 
 #[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone)]
@@ -55,6 +78,8 @@ impl Default for MultiRegistry {
         MultiRegistry {
             active_registry: format_graphql("wapm.io"),
             tokens: Vec::new(),
+            active_profile: None,
+            profiles: BTreeMap::new(),
         }
     }
 }
@@ -125,8 +150,68 @@ impl MultiRegistry {
     }
 
     /// Gets the current (active) registry URL
+    ///
+    /// `WASMER_REGISTRY` overrides the registry, then `WASMER_PROFILE`
+    /// overrides the active profile.
     pub fn get_current_registry(&self) -> String {
-        format_graphql(&self.active_registry)
+        if let Some(registry) = env_override(WasmerConfig::ENV_VAR_WASMER_REGISTRY) {
+            return format_graphql(&registry);
+        }
+        match self.current_profile() {
+            Some((_, profile)) => format_graphql(&profile.registry),
+            None => format_graphql(&self.active_registry),
+        }
+    }
+
+    /// Gets the name and settings of the current (active) profile
+    pub fn current_profile(&self) -> Option<(&str, &Profile)> {
+        let name = env_override(WasmerConfig::ENV_VAR_WASMER_PROFILE)
+            .or_else(|| self.active_profile.clone())?;
+        self.profiles
+            .get_key_value(&name)
+            .map(|(name, profile)| (name.as_str(), profile))
+    }
+
+    /// Makes a profile the current (active) one, or goes back to using
+    /// `active_registry` if `name` is `None`
+    pub fn set_current_profile(&mut self, name: Option<&str>) -> Result<(), String> {
+        match name {
+            Some(name) if !self.profiles.contains_key(name) => {
+                Err(format!("there is no profile named {name:?}"))
+            }
+            Some(name) => {
+                self.active_profile = Some(name.to_string());
+                Ok(())
+            }
+            None => {
+                self.active_profile = None;
+                Ok(())
+            }
+        }
+    }
+
+    /// Saves a profile and makes it the current (active) one. The token
+    /// is only saved in the config when it isn't kept in the keychain.
+    pub fn set_profile(&mut self, name: &str, registry: &str, token: Option<&str>, keychain: bool) {
+        let profile = Profile {
+            registry: format_graphql(registry),
+            token: if keychain {
+                None
+            } else {
+                token.map(|t| t.to_string())
+            },
+            keychain,
+        };
+        self.profiles.insert(name.to_string(), profile);
+        self.active_profile = Some(name.to_string());
+    }
+
+    /// Removes a profile, returning it if it existed
+    pub fn remove_profile(&mut self, name: &str) -> Option<Profile> {
+        if self.active_profile.as_deref() == Some(name) {
+            self.active_profile = None;
+        }
+        self.profiles.remove(name)
     }
 
     pub fn current_login(&self) -> Option<&RegistryLogin> {
@@ -143,11 +228,20 @@ impl MultiRegistry {
             println!("WARNING: Registry {registry:?} will be used, but commands may not succeed.");
         }
         self.active_registry = registry;
+        self.active_profile = None;
     }
 
     /// Returns the login token for the registry
+    ///
+    /// The current profile's token is used when the profile logs into
+    /// `registry`.
     pub fn get_login_token_for_registry(&self, registry: &str) -> Option<String> {
         let registry_formatted = format_graphql(registry);
+        if let Some((name, profile)) = self.current_profile() {
+            if format_graphql(&profile.registry) == registry_formatted {
+                return profile_token(name, profile);
+            }
+        }
         self.tokens
             .iter()
             .filter(|login| login.registry == registry || login.registry == registry_formatted)
@@ -171,13 +265,35 @@ impl MultiRegistry {
         });
         if update_current_registry == UpdateRegistry::Update {
             self.active_registry = format_graphql(registry);
+            self.active_profile = None;
+        }
+    }
+}
+
+/// The login token of a profile, loaded from the keychain if needed
+fn profile_token(name: &str, profile: &Profile) -> Option<String> {
+    if !profile.keychain {
+        return profile.token.clone();
+    }
+    match crate::keychain::load_token(name, &profile.registry) {
+        Ok(token) => token,
+        Err(e) => {
+            log::warn!("Unable to load the token of profile {name:?} from the keychain: {e}");
+            None
         }
     }
 }
 
+/// The value of an environment variable, unless it is unset or empty
+fn env_override(var: &str) -> Option<String> {
+    std::env::var(var).ok().filter(|value| !value.is_empty())
+}
+
 impl WasmerConfig {
     pub(crate) const ENV_VAR_WASMER_REGISTRY_TOKEN: &str = "WASMER_TOKEN";
     pub(crate) const ENV_VAR_WASMER_REGISTRY_TOKEN_LEGACY: &str = "WAPM_REGISTRY_TOKEN";
+    pub const ENV_VAR_WASMER_REGISTRY: &str = "WASMER_REGISTRY";
+    pub const ENV_VAR_WASMER_PROFILE: &str = "WASMER_PROFILE";
 
     /// Save the config to a file
     pub fn save<P: AsRef<Path>>(&self, to: P) -> anyhow::Result<()> {
@@ -285,6 +401,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn profiles_take_precedence_over_the_active_registry() {
+        let mut registries = MultiRegistry::default();
+        registries.set_login_token_for_registry("wapm.io", "io-token", UpdateRegistry::Update);
+        registries.set_profile("staging", "wapm.dev", Some("dev-token"), false);
+
+        assert_eq!(registries.current_profile().unwrap().0, "staging");
+        assert_eq!(
+            registries.get_current_registry(),
+            "https://registry.wapm.dev/graphql"
+        );
+        assert_eq!(
+            registries.get_login_token_for_registry("wapm.dev"),
+            Some("dev-token".to_string())
+        );
+        assert_eq!(
+            registries.get_login_token_for_registry("wapm.io"),
+            Some("io-token".to_string())
+        );
+
+        registries.set_current_profile(None).unwrap();
+        assert_eq!(
+            registries.get_current_registry(),
+            "https://registry.wapm.io/graphql"
+        );
+        assert!(registries.set_current_profile(Some("prod")).is_err());
+
+        registries.set_current_profile(Some("staging")).unwrap();
+        registries.remove_profile("staging");
+        assert!(registries.current_profile().is_none());
+    }
+
+    #[test]
+    fn profiles_in_the_keychain_arent_saved_in_the_config() {
+        let mut registries = MultiRegistry::default();
+        registries.set_profile("prod", "wapm.io", Some("secret"), true);
+
+        let toml = toml::to_string(&registries).unwrap();
+        assert!(!toml.contains("secret"), "{toml}");
+        assert_eq!(toml::from_str::<MultiRegistry>(&toml).unwrap(), registries);
+    }
+
     #[test]
     fn format_registry_urls() {
         let inputs = [
@@ -0,0 +1,63 @@
+//! Login tokens kept in the OS keychain (the macOS Keychain, the Windows
+//! Credential Manager or the Secret Service on Linux) instead of the
+//! config file.
+//!
+//! Tokens are stored under the `wasmer` service, with one entry per
+//! profile and registry.
+
+/// The service all tokens are stored under.
+#[cfg(feature = "keychain")]
+const SERVICE: &str = "wasmer";
+
+/// The keychain entry for a profile's token.
+#[cfg(feature = "keychain")]
+fn entry(profile: &str, registry: &str) -> anyhow::Result<keyring::Entry> {
+    let account = format!("{profile}@{}", crate::format_graphql(registry));
+    Ok(keyring::Entry::new(SERVICE, &account)?)
+}
+
+/// Whether the keychain can be used in this build.
+pub fn is_supported() -> bool {
+    cfg!(feature = "keychain")
+}
+
+/// Save a profile's login token in the keychain.
+#[cfg(feature = "keychain")]
+pub fn store_token(profile: &str, registry: &str, token: &str) -> anyhow::Result<()> {
+    entry(profile, registry)?.set_password(token)?;
+    Ok(())
+}
+
+/// Load a profile's login token from the keychain, if there is one.
+#[cfg(feature = "keychain")]
+pub fn load_token(profile: &str, registry: &str) -> anyhow::Result<Option<String>> {
+    match entry(profile, registry)?.get_password() {
+        Ok(token) => Ok(Some(token)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Remove a profile's login token from the keychain.
+#[cfg(feature = "keychain")]
+pub fn delete_token(profile: &str, registry: &str) -> anyhow::Result<()> {
+    match entry(profile, registry)?.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn store_token(_profile: &str, _registry: &str, _token: &str) -> anyhow::Result<()> {
+    anyhow::bail!("This build of wasmer-registry doesn't support the OS keychain")
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn load_token(_profile: &str, _registry: &str) -> anyhow::Result<Option<String>> {
+    Ok(None)
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn delete_token(_profile: &str, _registry: &str) -> anyhow::Result<()> {
+    Ok(())
+}
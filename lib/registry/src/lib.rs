@@ -13,6 +13,8 @@ mod client;
 pub mod config;
 pub mod graphql;
 pub mod interface;
+pub mod keychain;
+pub mod lockfile;
 pub mod login;
 pub mod package;
 pub mod publish;
@@ -0,0 +1,228 @@
+//! The `wasmer.lock` file, which pins the dependencies of a package's
+//! `wasmer.toml` to exact versions.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use url::Url;
+
+pub static LOCKFILE_NAME: &str = "wasmer.lock";
+
+/// The version of the lockfile format written by this crate.
+pub const LOCKFILE_VERSION: u32 = 1;
+
+const LOCKFILE_HEADER: &str =
+    "# This file is automatically generated by wasmer.\n# It is not intended for manual editing.\n";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub version: u32,
+    /// The locked packages, sorted by name.
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+/// A dependency resolved to an exact version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LockedPackage {
+    /// The `namespace/name` of the package.
+    pub name: String,
+    pub version: String,
+    /// The GraphQL endpoint of the registry the package was resolved from.
+    pub registry: String,
+    pub download_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webc_url: Option<String>,
+    /// The checksum of the `.webc` file, which is also its name in the
+    /// `webc` cache directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+impl Default for Lockfile {
+    fn default() -> Self {
+        Lockfile {
+            version: LOCKFILE_VERSION,
+            packages: Vec::new(),
+        }
+    }
+}
+
+impl Lockfile {
+    /// Loads the `wasmer.lock` next to a `wasmer.toml`, or an empty lockfile
+    /// if there is none yet.
+    pub fn load(package_dir: &Path) -> Result<Self, anyhow::Error> {
+        let path = package_dir.join(LOCKFILE_NAME);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Lockfile::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Unable to read \"{}\"", path.display()))
+            }
+        };
+        let lockfile: Lockfile = toml::from_str(&contents)
+            .with_context(|| format!("Unable to parse \"{}\"", path.display()))?;
+        if lockfile.version > LOCKFILE_VERSION {
+            anyhow::bail!(
+                "\"{}\" was written by a newer version of wasmer (lockfile version {})",
+                path.display(),
+                lockfile.version
+            );
+        }
+        Ok(lockfile)
+    }
+
+    /// Writes the lockfile next to the `wasmer.toml` in `package_dir`.
+    pub fn save(&self, package_dir: &Path) -> Result<(), anyhow::Error> {
+        let path = package_dir.join(LOCKFILE_NAME);
+        let contents = format!("{LOCKFILE_HEADER}{}", toml::to_string_pretty(self)?);
+        std::fs::write(&path, contents)
+            .with_context(|| format!("Unable to write to \"{}\"", path.display()))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|p| p.name == name)
+    }
+
+    /// Adds a package to the lockfile, replacing the previously locked
+    /// version of it.
+    pub fn insert(&mut self, package: LockedPackage) {
+        self.remove(&package.name);
+        self.packages.push(package);
+        self.packages.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<LockedPackage> {
+        let index = self.packages.iter().position(|p| p.name == name)?;
+        Some(self.packages.remove(index))
+    }
+
+    /// Drops the packages that aren't dependencies any more.
+    pub fn retain_dependencies<'a>(&mut self, dependencies: impl IntoIterator<Item = &'a str>) {
+        let dependencies = dependencies.into_iter().collect::<Vec<_>>();
+        self.packages
+            .retain(|p| dependencies.contains(&p.name.as_str()));
+    }
+}
+
+/// Resolves a dependency of a `wasmer.toml` against the registry and
+/// downloads its `.webc` into the cache, so that it can be used offline.
+///
+/// `requirement` is the version requirement from the manifest: an exact
+/// version is locked as is, anything else is matched against the latest
+/// version of the package.
+pub fn lock_dependency(
+    wasmer_dir: &Path,
+    registry: &str,
+    name: &str,
+    requirement: &str,
+) -> Result<LockedPackage, anyhow::Error> {
+    let exact = semver::Version::parse(requirement).ok();
+    let requirement = semver::VersionReq::parse(requirement)
+        .with_context(|| format!("Invalid version requirement {requirement:?} for {name}"))?;
+
+    let version = exact.map(|v| v.to_string());
+    let info = crate::query_package_from_registry(registry, name, version.as_deref())
+        .with_context(|| format!("Unable to resolve {name}"))?;
+    let resolved = semver::Version::parse(&info.version)
+        .with_context(|| format!("{name} has an invalid version {:?}", info.version))?;
+    if !requirement.matches(&resolved) {
+        anyhow::bail!(
+            "The latest version of {name} is {resolved}, which doesn't match \"{requirement}\". \
+             Specify an exact version instead."
+        );
+    }
+
+    let checksum = match info.pirita_url.as_deref() {
+        Some(url) => Some(cache_webc(wasmer_dir, url)?),
+        None => None,
+    };
+
+    Ok(LockedPackage {
+        name: name.to_string(),
+        version: info.version,
+        registry: info.registry,
+        download_url: info.url,
+        webc_url: info.pirita_url,
+        checksum,
+    })
+}
+
+/// Downloads a `.webc` into the cache unless it is there already, returning
+/// its checksum.
+fn cache_webc(wasmer_dir: &Path, url: &str) -> Result<String, anyhow::Error> {
+    let url = Url::parse(url).with_context(|| format!("Invalid package URL {url:?}"))?;
+    let checksum = crate::get_remote_webc_checksum(&url)?;
+    let cached = crate::get_all_installed_webc_packages(wasmer_dir)
+        .iter()
+        .any(|p| p.checksum == checksum);
+    if !cached {
+        crate::install_webc_package(wasmer_dir, &url, &checksum)?;
+    }
+    Ok(checksum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locked(name: &str, version: &str) -> LockedPackage {
+        LockedPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            registry: "https://registry.wapm.io/graphql".to_string(),
+            download_url: format!("https://example.com/{name}-{version}.tar.gz"),
+            webc_url: None,
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn insert_replaces_and_sorts() {
+        let mut lockfile = Lockfile::default();
+        lockfile.insert(locked("syrusakbary/python", "0.1.0"));
+        lockfile.insert(locked("python/python", "0.1.0"));
+        lockfile.insert(locked("syrusakbary/python", "0.2.0"));
+
+        let names = lockfile
+            .packages
+            .iter()
+            .map(|p| (p.name.as_str(), p.version.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [("python/python", "0.1.0"), ("syrusakbary/python", "0.2.0")]
+        );
+
+        lockfile.retain_dependencies(["python/python"]);
+        assert!(lockfile.get("syrusakbary/python").is_none());
+        assert_eq!(lockfile.remove("python/python").unwrap().version, "0.1.0");
+        assert!(lockfile.packages.is_empty());
+    }
+
+    #[test]
+    fn lockfile_roundtrip() {
+        let temp = tempfile::tempdir().unwrap();
+        assert_eq!(Lockfile::load(temp.path()).unwrap(), Lockfile::default());
+
+        let mut lockfile = Lockfile::default();
+        let mut package = locked("python/python", "0.1.0");
+        package.checksum = Some("3ea47cb".to_string());
+        lockfile.insert(package);
+        lockfile.save(temp.path()).unwrap();
+
+        let contents = std::fs::read_to_string(temp.path().join(LOCKFILE_NAME)).unwrap();
+        assert!(contents.starts_with(LOCKFILE_HEADER));
+        assert!(contents.contains("[[package]]"));
+        assert!(contents.contains("download-url = "));
+        assert_eq!(Lockfile::load(temp.path()).unwrap(), lockfile);
+    }
+
+    #[test]
+    fn newer_lockfiles_are_rejected() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(temp.path().join(LOCKFILE_NAME), "version = 2\n").unwrap();
+        assert!(Lockfile::load(temp.path()).is_err());
+    }
+}
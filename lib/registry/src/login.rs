@@ -23,3 +23,32 @@ pub fn login_and_save_token(
     config.save(&path)?;
     crate::utils::get_username_registry_token(&registry, token)
 }
+
+/// Login to a registry under a named profile and save the token, in the OS
+/// keychain if `keychain` is set.
+///
+/// Also sets the profile as the currently active profile.
+pub fn login_and_save_profile(
+    wasmer_dir: &Path,
+    profile: &str,
+    registry: &str,
+    token: &str,
+    keychain: bool,
+) -> Result<Option<String>, anyhow::Error> {
+    let registry = format_graphql(registry);
+    let mut config = WasmerConfig::from_file(wasmer_dir)
+        .map_err(|e| anyhow::anyhow!("config from file: {e}"))?;
+    if keychain {
+        crate::keychain::store_token(profile, &registry, token)?;
+    } else if let Some(previous) = config.registry.profiles.get(profile) {
+        if previous.keychain {
+            crate::keychain::delete_token(profile, &previous.registry)?;
+        }
+    }
+    config
+        .registry
+        .set_profile(profile, &registry, Some(token), keychain);
+    let path = WasmerConfig::get_file_location(wasmer_dir);
+    config.save(&path)?;
+    crate::utils::get_username_registry_token(&registry, token)
+}
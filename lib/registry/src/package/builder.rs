@@ -128,10 +128,12 @@ impl Publish {
         if self.dry_run {
             // dry run: publish is done here
 
-            println!(
-                "Successfully published package `{}@{}`",
-                manifest.package.name, manifest.package.version
-            );
+            if !self.quiet {
+                println!(
+                    "Dry run: package `{}@{}` ({} bytes) was not uploaded",
+                    manifest.package.name, manifest.package.version, archived_data_size
+                );
+            }
 
             log::info!(
                 "Publish succeeded, but package was not published because it was run in dry-run mode"
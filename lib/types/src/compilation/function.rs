@@ -95,6 +95,40 @@ impl Dwarf {
     }
 }
 
+/// The native DWARF sections describing the compiled code in terms of
+/// the source of the WebAssembly module.
+///
+/// They are translated from the DWARF sections of the module, and let
+/// native debuggers set breakpoints and step through the source of the
+/// guest. The addresses in them are relocated to the compiled functions.
+#[cfg_attr(feature = "enable-serde", derive(Deserialize, Serialize))]
+#[derive(
+    RkyvSerialize, RkyvDeserialize, Archive, rkyv::CheckBytes, Debug, PartialEq, Eq, Clone,
+)]
+#[archive(as = "Self")]
+pub struct DebugSections {
+    /// The section index of `.debug_abbrev`.
+    pub debug_abbrev: SectionIndex,
+    /// The section index of `.debug_info`.
+    pub debug_info: SectionIndex,
+    /// The section index of `.debug_line`.
+    pub debug_line: SectionIndex,
+    /// The section index of `.debug_ranges`.
+    pub debug_ranges: SectionIndex,
+}
+
+impl DebugSections {
+    /// Returns the name and the index of each section.
+    pub fn sections(&self) -> [(&'static str, SectionIndex); 4] {
+        [
+            (".debug_abbrev", self.debug_abbrev),
+            (".debug_info", self.debug_info),
+            (".debug_line", self.debug_line),
+            (".debug_ranges", self.debug_ranges),
+        ]
+    }
+}
+
 /// The result of compiling a WebAssembly module's functions.
 #[cfg_attr(feature = "enable-serde", derive(Deserialize, Serialize))]
 #[derive(Debug, PartialEq, Eq)]
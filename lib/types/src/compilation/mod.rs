@@ -3,6 +3,7 @@
 pub mod address_map;
 pub mod function;
 pub mod module;
+pub mod pgo;
 pub mod relocation;
 pub mod section;
 pub mod symbols;
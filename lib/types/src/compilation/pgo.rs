@@ -0,0 +1,77 @@
+//! Execution profiles used for profile-guided optimization.
+//!
+//! A profile is recorded by running a module compiled with an
+//! instrumentation middleware, and is then given to a compiler that
+//! knows how to use it (LLVM or Cranelift) when the module is compiled
+//! again.
+
+use crate::entity::{EntityRef, PrimaryMap};
+use crate::lib::std::vec::Vec;
+use crate::LocalFunctionIndex;
+#[cfg(feature = "enable-serde")]
+use serde::{Deserialize, Serialize};
+
+/// How many times a conditional branch went each way.
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BranchProfile {
+    /// Number of times the branch was taken (the `br_if` jumped, or the
+    /// `then` arm of the `if` ran).
+    pub taken: u64,
+    /// Number of times the branch fell through.
+    pub not_taken: u64,
+}
+
+/// The profile of a single function.
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionExecutionProfile {
+    /// Number of times the function was called.
+    pub calls: u64,
+    /// The conditional branches (`if` and `br_if`) of the function, in
+    /// the order in which they appear in its body.
+    pub branches: Vec<BranchProfile>,
+}
+
+/// The profile of all the local functions of a module.
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionProfile {
+    /// The profile of every local function.
+    pub functions: PrimaryMap<LocalFunctionIndex, FunctionExecutionProfile>,
+}
+
+impl ExecutionProfile {
+    /// The profile of a function, if it was recorded.
+    pub fn function(&self, index: LocalFunctionIndex) -> Option<&FunctionExecutionProfile> {
+        self.functions.get(index)
+    }
+
+    /// The profile of a branch of a function, if it was recorded and the
+    /// branch was executed at least once.
+    pub fn branch(&self, index: LocalFunctionIndex, branch: usize) -> Option<BranchProfile> {
+        self.function(index)
+            .and_then(|function| function.branches.get(branch))
+            .filter(|branch| branch.taken + branch.not_taken > 0)
+            .copied()
+    }
+
+    /// Adds the counts of another profile of the same module to this one.
+    pub fn merge(&mut self, other: &Self) {
+        for (index, function) in other.functions.iter() {
+            while self.functions.len() <= index.index() {
+                self.functions.push(FunctionExecutionProfile::default());
+            }
+            let mine = &mut self.functions[index];
+            mine.calls += function.calls;
+            if mine.branches.len() < function.branches.len() {
+                mine.branches
+                    .resize(function.branches.len(), BranchProfile::default());
+            }
+            for (mine, theirs) in mine.branches.iter_mut().zip(&function.branches) {
+                mine.taken += theirs.taken;
+                mine.not_taken += theirs.not_taken;
+            }
+        }
+    }
+}
@@ -113,6 +113,16 @@ impl CpuFeature {
         // We default to an empty hash set
         EnumSet::new()
     }
+
+    /// Retrieves the features every CPU of the given triple supports,
+    /// which compilers may rely on when cross-compiling for it.
+    pub fn baseline(triple: &Triple) -> EnumSet<Self> {
+        match triple.architecture {
+            // Cranelift requires SSE2, which all x86_64 CPUs have
+            Architecture::X86_64 => Self::SSE2.into(),
+            _ => Self::set(),
+        }
+    }
 }
 
 // This options should map exactly the GCC options indicated
@@ -24,6 +24,9 @@ pub enum CompiledFunctionUnwindInfo {
     /// Windows UNWIND_INFO.
     WindowsX64(Vec<u8>),
 
+    /// Windows ARM64 `.xdata` record.
+    WindowsArm64(Vec<u8>),
+
     /// The unwind info is added to the Dwarf section in `Compilation`.
     Dwarf,
 }
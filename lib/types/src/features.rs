@@ -168,6 +168,12 @@ impl Features {
     ///
     /// This feature gates tail-call functions in WebAssembly.
     ///
+    /// `return_call` and `return_call_indirect` reuse the frame of the
+    /// caller with Singlepass on x86_64, as long as the callee doesn't take
+    /// more arguments on the stack than the caller. The other compilers
+    /// and targets can't guarantee it yet, so modules that use them are
+    /// rejected with `WasmError::Unsupported`.
+    ///
     /// This is `false` by default.
     ///
     /// [proposal]: https://github.com/webassembly/tail-call
@@ -204,7 +210,8 @@ impl Features {
     /// appropriate WebAssembly modules.
     ///
     /// This feature adds the ability to use multiple memories within a
-    /// single Wasm module.
+    /// single Wasm module. Any of them can be imported or exported, and
+    /// `memory.copy` can copy between two different memories.
     ///
     /// This is `false` by default.
     ///
@@ -225,6 +232,12 @@ impl Features {
     /// This feature gates support for linear memory of sizes larger than
     /// 2^32 bits.
     ///
+    /// The Cranelift and LLVM compilers support 64-bit memories, except
+    /// for bulk memory operations and atomic waits and notifications on
+    /// them, which are rejected with an unsupported feature error.
+    /// Singlepass rejects modules with 64-bit memories. Such modules can
+    /// only use WASI through `wasix_64v1`.
+    ///
     /// This is `false` by default.
     ///
     /// [proposal]: https://github.com/WebAssembly/memory64
@@ -232,6 +245,23 @@ impl Features {
         self.memory64 = enable;
         self
     }
+
+    /// Configures whether the WebAssembly relaxed SIMD proposal will be
+    /// enabled.
+    ///
+    /// The [WebAssembly relaxed SIMD proposal][proposal] is not
+    /// currently fully standardized and is undergoing development.
+    /// Its instructions may give different results on different
+    /// platforms. The Cranelift and LLVM compilers can be configured to
+    /// lower them deterministically instead.
+    ///
+    /// This is `false` by default.
+    ///
+    /// [proposal]: https://github.com/WebAssembly/relaxed-simd
+    pub fn relaxed_simd(&mut self, enable: bool) -> &mut Self {
+        self.relaxed_simd = enable;
+        self
+    }
 }
 
 impl Default for Features {
@@ -340,4 +370,11 @@ mod test_features {
         features.memory64(true);
         assert!(features.memory64);
     }
+
+    #[test]
+    fn enable_relaxed_simd() {
+        let mut features = Features::new();
+        features.relaxed_simd(true);
+        assert!(features.relaxed_simd);
+    }
 }
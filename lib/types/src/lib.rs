@@ -60,8 +60,10 @@ mod indexes;
 mod initializers;
 mod libcalls;
 mod memory;
+mod metadata;
 mod module;
 mod serialize;
+mod snapshot;
 mod stack;
 mod store_id;
 mod table;
@@ -76,7 +78,10 @@ pub use crate::compilation::target::{
     Aarch64Architecture, Architecture, BinaryFormat, CallingConvention, CpuFeature, Endianness,
     Environment, OperatingSystem, PointerWidth, Target, Triple, Vendor,
 };
-pub use crate::serialize::{MetadataHeader, SerializableCompilation, SerializableModule};
+pub use crate::serialize::{
+    ArtifactVariants, MetadataHeader, SerializableCompilation, SerializableModule, SerializeOptions,
+};
+pub use crate::snapshot::{GlobalSnapshot, InstanceSnapshot};
 pub use error::{
     CompileError, DeserializeError, ImportError, MemoryError, MiddlewareError,
     ParseCpuFeatureError, PreInstantiationError, SerializeError, WasmError, WasmResult,
@@ -94,9 +99,11 @@ pub use crate::initializers::{
     DataInitializer, DataInitializerLocation, OwnedDataInitializer, TableInitializer,
 };
 pub use crate::memory::{Memory32, Memory64, MemorySize};
-pub use crate::module::{ExportsIterator, ImportKey, ImportsIterator, ModuleInfo};
+pub use crate::metadata::{Producers, ProducersField, TargetFeature, TargetFeaturePrefix};
+pub use crate::module::{ExportsIterator, ImportKey, ImportsIterator, ModuleInfo, StartFunction};
 pub use crate::units::{
-    Bytes, PageCountOutOfRange, Pages, WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
+    Bytes, PageCountOutOfRange, Pages, WASM64_MAX_PAGES, WASM_MAX_PAGES, WASM_MIN_PAGES,
+    WASM_PAGE_SIZE,
 };
 pub use types::{
     ExportType, ExternType, FunctionType, GlobalInit, GlobalType, ImportType, MemoryType,
@@ -122,14 +129,15 @@ pub use crate::compilation::section::{
 
 pub use crate::compilation::address_map::{FunctionAddressMap, InstructionAddressMap};
 pub use crate::compilation::function::{
-    Compilation, CompiledFunction, CompiledFunctionFrameInfo, CustomSections, Dwarf, FunctionBody,
-    Functions,
+    Compilation, CompiledFunction, CompiledFunctionFrameInfo, CustomSections, DebugSections, Dwarf,
+    FunctionBody, Functions,
 };
 pub use crate::compilation::module::CompileModuleInfo;
+pub use crate::compilation::pgo::{BranchProfile, ExecutionProfile, FunctionExecutionProfile};
 pub use crate::compilation::symbols::{Symbol, SymbolRegistry};
 pub use crate::compilation::unwind::CompiledFunctionUnwindInfo;
 
-pub use crate::stack::{FrameInfo, SourceLoc, TrapInformation};
+pub use crate::stack::{FrameInfo, SourceLoc, SourceLocation, TrapInformation};
 pub use crate::store_id::StoreId;
 
 /// Offset in bytes from the beginning of the function.
@@ -135,6 +135,9 @@ pub enum LibCall {
 
     /// memory.atomic.botify for imported memories
     ImportedMemory32AtomicNotify,
+
+    /// memory.copy between two different memories
+    Memory32CopyBetween,
 }
 
 impl LibCall {
@@ -183,6 +186,7 @@ impl LibCall {
             Self::ImportedMemory32AtomicWait64 => "wasmer_vm_imported_memory32_atomic_wait64",
             Self::Memory32AtomicNotify => "wasmer_vm_memory32_atomic_notify",
             Self::ImportedMemory32AtomicNotify => "wasmer_vm_imported_memory32_atomic_notify",
+            Self::Memory32CopyBetween => "wasmer_vm_memory32_copy_between",
         }
     }
 }
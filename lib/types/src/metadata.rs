@@ -0,0 +1,167 @@
+//! Custom sections holding metadata about a module, as defined by the
+//! [WebAssembly tool conventions].
+//!
+//! [WebAssembly tool conventions]: https://github.com/WebAssembly/tool-conventions
+
+use crate::lib::std::string::String;
+use crate::lib::std::vec::Vec;
+
+/// The contents of the `producers` section, describing the tools that
+/// produced a module.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Producers {
+    /// The fields of the section, such as `language`, `processed-by` and
+    /// `sdk`.
+    pub fields: Vec<ProducersField>,
+}
+
+/// A field of the `producers` section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProducersField {
+    /// The name of the field.
+    pub name: String,
+    /// The tools listed in the field, as `(name, version)`.
+    pub values: Vec<(String, String)>,
+}
+
+impl Producers {
+    /// Parses the contents of a `producers` section.
+    ///
+    /// Returns `None` if the section is malformed.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes);
+        let mut fields = Vec::new();
+        for _ in 0..reader.u32()? {
+            let name = reader.string()?;
+            let mut values = Vec::new();
+            for _ in 0..reader.u32()? {
+                values.push((reader.string()?, reader.string()?));
+            }
+            fields.push(ProducersField { name, values });
+        }
+        reader.0.is_empty().then(|| Self { fields })
+    }
+
+    /// Returns the tools listed in the field `name`, such as `language`.
+    pub fn field(&self, name: &str) -> Option<&[(String, String)]> {
+        self.fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| &field.values[..])
+    }
+}
+
+/// How a module relates to a feature of the `target_features` section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetFeaturePrefix {
+    /// `+`: the module uses the feature.
+    Used,
+    /// `=`: the module requires the feature from every module it is
+    /// linked with.
+    Required,
+    /// `-`: the module must not be linked with modules using the feature.
+    Disallowed,
+}
+
+/// An entry of the `target_features` section, listing the features a
+/// module was compiled with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetFeature {
+    /// How the module relates to the feature.
+    pub prefix: TargetFeaturePrefix,
+    /// The name of the feature, such as `simd128` or `bulk-memory`.
+    pub name: String,
+}
+
+impl TargetFeature {
+    /// Parses the contents of a `target_features` section.
+    ///
+    /// Returns `None` if the section is malformed.
+    pub fn parse_section(bytes: &[u8]) -> Option<Vec<Self>> {
+        let mut reader = Reader(bytes);
+        let mut features = Vec::new();
+        for _ in 0..reader.u32()? {
+            let prefix = match reader.byte()? {
+                b'+' => TargetFeaturePrefix::Used,
+                b'=' => TargetFeaturePrefix::Required,
+                b'-' => TargetFeaturePrefix::Disallowed,
+                _ => return None,
+            };
+            features.push(Self {
+                prefix,
+                name: reader.string()?,
+            });
+        }
+        reader.0.is_empty().then(|| features)
+    }
+}
+
+/// Reads the values of custom sections.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let (&byte, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(byte)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u32).checked_shl(shift)?;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        if len > self.0.len() {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn producers() {
+        let bytes = b"\x02\x08language\x01\x04Rust\x061.65.0\x0cprocessed-by\x00";
+        let producers = Producers::parse(bytes).unwrap();
+        assert_eq!(
+            producers.field("language"),
+            Some(&[("Rust".to_string(), "1.65.0".to_string())][..])
+        );
+        assert_eq!(producers.field("processed-by"), Some(&[][..]));
+        assert_eq!(producers.field("sdk"), None);
+        assert_eq!(Producers::parse(&bytes[..bytes.len() - 1]), None);
+    }
+
+    #[test]
+    fn target_features() {
+        let features = TargetFeature::parse_section(b"\x02+\x07simd128-\x07atomics").unwrap();
+        assert_eq!(
+            features,
+            vec![
+                TargetFeature {
+                    prefix: TargetFeaturePrefix::Used,
+                    name: "simd128".to_string(),
+                },
+                TargetFeature {
+                    prefix: TargetFeaturePrefix::Disallowed,
+                    name: "atomics".to_string(),
+                },
+            ]
+        );
+        assert_eq!(TargetFeature::parse_section(b"\x01?\x01a"), None);
+    }
+}
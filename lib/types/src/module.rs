@@ -164,6 +164,11 @@ pub struct ModuleInfo {
     /// The data for each CustomSection in the module.
     pub custom_sections_data: PrimaryMap<CustomSectionIndex, Box<[u8]>>,
 
+    /// The offset of the contents of the code section in the module.
+    ///
+    /// Addresses in the DWARF sections of the module are relative to it.
+    pub code_section_offset: usize,
+
     /// Number of imported functions in the module.
     pub num_imported_functions: usize,
 
@@ -175,6 +180,32 @@ pub struct ModuleInfo {
 
     /// Number of imported globals in the module.
     pub num_imported_globals: usize,
+
+    /// The global the `Interrupt` middleware polls, if it instrumented
+    /// this module.
+    ///
+    /// The VM backs it with the store's interrupt flag instead of a
+    /// regular global definition.
+    pub interrupt_global: Option<GlobalIndex>,
+
+    /// The global the `Fuel` middleware consumes, if it instrumented this
+    /// module.
+    ///
+    /// The VM backs it with the store's fuel instead of a regular global
+    /// definition.
+    pub fuel_global: Option<GlobalIndex>,
+}
+
+/// The start function of a module, which runs when the module is
+/// instantiated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartFunction {
+    /// The index of the function.
+    pub index: FunctionIndex,
+    /// The name of the function, if the module has one for it.
+    pub name: Option<String>,
+    /// The type of the function.
+    pub ty: FunctionType,
 }
 
 /// Mirror version of ModuleInfo that can derive rkyv traits
@@ -197,10 +228,13 @@ pub struct ArchivableModuleInfo {
     globals: PrimaryMap<GlobalIndex, GlobalType>,
     custom_sections: IndexMap<String, CustomSectionIndex>,
     custom_sections_data: PrimaryMap<CustomSectionIndex, Box<[u8]>>,
+    code_section_offset: usize,
     num_imported_functions: usize,
     num_imported_tables: usize,
     num_imported_memories: usize,
     num_imported_globals: usize,
+    interrupt_global: Option<GlobalIndex>,
+    fuel_global: Option<GlobalIndex>,
 }
 
 impl From<ModuleInfo> for ArchivableModuleInfo {
@@ -222,10 +256,13 @@ impl From<ModuleInfo> for ArchivableModuleInfo {
             globals: it.globals,
             custom_sections: it.custom_sections,
             custom_sections_data: it.custom_sections_data,
+            code_section_offset: it.code_section_offset,
             num_imported_functions: it.num_imported_functions,
             num_imported_tables: it.num_imported_tables,
             num_imported_memories: it.num_imported_memories,
             num_imported_globals: it.num_imported_globals,
+            interrupt_global: it.interrupt_global,
+            fuel_global: it.fuel_global,
         }
     }
 }
@@ -250,10 +287,13 @@ impl From<ArchivableModuleInfo> for ModuleInfo {
             globals: it.globals,
             custom_sections: it.custom_sections,
             custom_sections_data: it.custom_sections_data,
+            code_section_offset: it.code_section_offset,
             num_imported_functions: it.num_imported_functions,
             num_imported_tables: it.num_imported_tables,
             num_imported_memories: it.num_imported_memories,
             num_imported_globals: it.num_imported_globals,
+            interrupt_global: it.interrupt_global,
+            fuel_global: it.fuel_global,
         }
     }
 }
@@ -310,10 +350,13 @@ impl PartialEq for ModuleInfo {
             && self.globals == other.globals
             && self.custom_sections == other.custom_sections
             && self.custom_sections_data == other.custom_sections_data
+            && self.code_section_offset == other.code_section_offset
             && self.num_imported_functions == other.num_imported_functions
             && self.num_imported_tables == other.num_imported_tables
             && self.num_imported_memories == other.num_imported_memories
             && self.num_imported_globals == other.num_imported_globals
+            && self.interrupt_global == other.interrupt_global
+            && self.fuel_global == other.fuel_global
     }
 }
 
@@ -414,6 +457,36 @@ impl ModuleInfo {
             })
     }
 
+    /// Returns the start function of the module, if it has one.
+    pub fn start_function_info(&self) -> Option<StartFunction> {
+        let index = self.start_function?;
+        Some(StartFunction {
+            index,
+            name: self.function_names.get(&index).cloned(),
+            ty: self.signatures[self.functions[index]].clone(),
+        })
+    }
+
+    /// Returns an iterator over all the custom sections of the module, as
+    /// `(name, contents)`.
+    pub fn all_custom_sections(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.custom_sections
+            .iter()
+            .map(move |(name, index)| (name.as_str(), &self.custom_sections_data[*index][..]))
+    }
+
+    /// Adds a custom section to the module, replacing the section with the
+    /// same name if there is one.
+    pub fn add_custom_section(&mut self, name: String, data: Box<[u8]>) {
+        match self.custom_sections.get(&name) {
+            Some(index) => self.custom_sections_data[*index] = data,
+            None => {
+                let index = self.custom_sections_data.push(data);
+                self.custom_sections.insert(name, index);
+            }
+        }
+    }
+
     /// Convert a `LocalFunctionIndex` into a `FunctionIndex`.
     pub fn func_index(&self, local_func: LocalFunctionIndex) -> FunctionIndex {
         FunctionIndex::new(self.num_imported_functions + local_func.index())
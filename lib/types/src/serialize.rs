@@ -1,9 +1,9 @@
 use crate::entity::PrimaryMap;
 use crate::{
     compilation::target::CpuFeature, CompileModuleInfo, CompiledFunctionFrameInfo, CustomSection,
-    DeserializeError, Dwarf, Features, FunctionBody, FunctionIndex, LocalFunctionIndex,
-    MemoryIndex, MemoryStyle, ModuleInfo, OwnedDataInitializer, Relocation, SectionIndex,
-    SerializeError, SignatureIndex, TableIndex, TableStyle,
+    DebugSections, DeserializeError, Dwarf, Features, FunctionBody, FunctionIndex,
+    LocalFunctionIndex, MemoryIndex, MemoryStyle, ModuleInfo, OwnedDataInitializer, Relocation,
+    SectionBody, SectionIndex, SerializeError, SignatureIndex, TableIndex, TableStyle,
 };
 use enumset::EnumSet;
 use rkyv::check_archived_value;
@@ -14,9 +14,10 @@ use rkyv::{
 };
 use std::convert::TryInto;
 use std::mem;
+use std::sync::Arc;
 
 /// The compilation related data for a serialized modules
-#[derive(Archive, Default, RkyvDeserialize, RkyvSerialize)]
+#[derive(Archive, Clone, Default, RkyvDeserialize, RkyvSerialize)]
 #[allow(missing_docs)]
 #[archive_attr(derive(CheckBytes))]
 pub struct SerializableCompilation {
@@ -29,6 +30,8 @@ pub struct SerializableCompilation {
     pub custom_section_relocations: PrimaryMap<SectionIndex, Vec<Relocation>>,
     // The section indices corresponding to the Dwarf debug info
     pub debug: Option<Dwarf>,
+    // The section indices of the native debug info, if it was requested
+    pub debug_sections: Option<DebugSections>,
     // Custom section containing libcall trampolines.
     pub libcall_trampolines: SectionIndex,
     // Length of each libcall trampoline.
@@ -51,7 +54,7 @@ impl SerializableCompilation {
 }
 
 /// Serializable struct that is able to serialize from and to a `ArtifactInfo`.
-#[derive(Archive, RkyvDeserialize, RkyvSerialize)]
+#[derive(Archive, Clone, RkyvDeserialize, RkyvSerialize)]
 #[allow(missing_docs)]
 #[archive_attr(derive(CheckBytes))]
 pub struct SerializableModule {
@@ -63,6 +66,44 @@ pub struct SerializableModule {
     pub data_initializers: Box<[OwnedDataInitializer]>,
     /// CPU Feature flags for this compilation
     pub cpu_features: u64,
+    /// The target triple this module was compiled for
+    pub triple: String,
+}
+
+/// What to leave out of a serialized module, to make the artifacts
+/// distributed to production hosts smaller.
+///
+/// Stripped artifacts run the same code. Traps still have their code and a
+/// frame for each function of the stack, but the frames are less detailed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SerializeOptions {
+    /// Remove the DWARF custom sections (`.debug_*`) of the module, and the
+    /// native debug info generated from them. Frames no longer have a
+    /// source location.
+    pub strip_debug_info: bool,
+    /// Remove the name of the module and the names of its functions,
+    /// including the `name` custom section. Frames no longer have a
+    /// function name.
+    pub strip_names: bool,
+    /// Remove every other custom section of the module, such as
+    /// `producers`.
+    pub strip_custom_sections: bool,
+    /// Remove the maps from native code to WebAssembly offsets. Frames
+    /// report the offset of the start of their function instead of the
+    /// offset of the instruction.
+    pub strip_address_maps: bool,
+}
+
+impl SerializeOptions {
+    /// Options stripping everything that isn't needed to run the module.
+    pub fn strip_all() -> Self {
+        Self {
+            strip_debug_info: true,
+            strip_names: true,
+            strip_custom_sections: true,
+            strip_address_maps: true,
+        }
+    }
 }
 
 fn to_serialize_error(err: impl std::error::Error) -> SerializeError {
@@ -83,6 +124,63 @@ impl SerializableModule {
         Ok(serialized_data.to_vec())
     }
 
+    /// Removes the data that `options` asks to leave out.
+    pub fn strip(&mut self, options: &SerializeOptions) {
+        if options.strip_debug_info {
+            if let Some(debug_sections) = self.compilation.debug_sections.take() {
+                // Other sections are referred to by index, so the debug
+                // sections are emptied rather than removed.
+                for (_, index) in debug_sections.sections().iter() {
+                    let section = &mut self.compilation.custom_sections[*index];
+                    section.bytes = SectionBody::default();
+                    section.relocations.clear();
+                    self.compilation.custom_section_relocations[*index].clear();
+                }
+            }
+        }
+        if options.strip_address_maps {
+            for (_, frame_info) in self.compilation.function_frame_info.iter_mut() {
+                frame_info.address_map.instructions = Vec::new();
+            }
+        }
+
+        let module = &self.compile_info.module;
+        let keep_section = |name: &str| {
+            if name.starts_with(".debug_") {
+                !options.strip_debug_info
+            } else if name == "name" {
+                !options.strip_names
+            } else {
+                !options.strip_custom_sections
+            }
+        };
+        let strip_sections = module
+            .custom_sections
+            .keys()
+            .any(|name| !keep_section(name));
+        let strip_names =
+            options.strip_names && (module.name.is_some() || !module.function_names.is_empty());
+        if !strip_sections && !strip_names {
+            return;
+        }
+        let module = Arc::make_mut(&mut self.compile_info.module);
+        if strip_names {
+            module.name = None;
+            module.function_names.clear();
+        }
+        if strip_sections {
+            let sections = mem::take(&mut module.custom_sections);
+            let mut data = mem::take(&mut module.custom_sections_data);
+            for (name, index) in sections {
+                if keep_section(&name) {
+                    let bytes = mem::take(&mut data[index]);
+                    let index = module.custom_sections_data.push(bytes);
+                    module.custom_sections.insert(name, index);
+                }
+            }
+        }
+    }
+
     /// Deserialize a Module from a slice.
     /// The slice must have the following format:
     /// RKYV serialization (any length) + POS (8 bytes)
@@ -207,7 +305,7 @@ pub struct MetadataHeader {
 impl MetadataHeader {
     /// Current ABI version. Increment this any time breaking changes are made
     /// to the format of the serialized data.
-    pub const CURRENT_VERSION: u32 = 4;
+    pub const CURRENT_VERSION: u32 = 11;
 
     /// Magic number to identify wasmer metadata.
     const MAGIC: [u8; 8] = *b"WASMER\0\0";
@@ -261,3 +359,138 @@ impl MetadataHeader {
         Ok(header.len as usize)
     }
 }
+
+/// Serialized artifacts of the same module, each compiled for a
+/// different set of CPU features of the same target triple.
+///
+/// This allows shipping a single file to hosts with different ISA
+/// extensions (for example with and without AVX2): the variant that
+/// makes the most of the engine's CPU features is picked at load time.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ArtifactVariants {
+    variants: Vec<(EnumSet<CpuFeature>, Vec<u8>)>,
+}
+
+impl ArtifactVariants {
+    /// Magic number to identify serialized artifact variants.
+    const MAGIC: [u8; 16] = *b"WASMER-VARIANTS\0";
+
+    /// Creates an empty set of variants.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a serialized artifact compiled for `cpu_features`.
+    pub fn push(&mut self, cpu_features: EnumSet<CpuFeature>, artifact: impl Into<Vec<u8>>) {
+        self.variants.push((cpu_features, artifact.into()));
+    }
+
+    /// Returns the number of variants.
+    pub fn len(&self) -> usize {
+        self.variants.len()
+    }
+
+    /// Returns whether there are no variants.
+    pub fn is_empty(&self) -> bool {
+        self.variants.is_empty()
+    }
+
+    /// Returns whether `bytes` look like serialized artifact variants
+    /// rather than a single artifact.
+    pub fn is_variants(bytes: &[u8]) -> bool {
+        bytes.starts_with(&Self::MAGIC)
+    }
+
+    /// Returns the serialized artifact that can run with `cpu_features`
+    /// and requires the most of them, if any.
+    pub fn select(&self, cpu_features: EnumSet<CpuFeature>) -> Option<&[u8]> {
+        self.variants
+            .iter()
+            .filter(|(required, _)| cpu_features.is_superset(*required))
+            .max_by_key(|(required, _)| required.len())
+            .map(|(_, artifact)| artifact.as_slice())
+    }
+
+    /// Serializes the variants.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = Self::MAGIC.to_vec();
+        bytes.extend_from_slice(&(self.variants.len() as u64).to_le_bytes());
+        for (cpu_features, artifact) in &self.variants {
+            bytes.extend_from_slice(&cpu_features.as_u64().to_le_bytes());
+            bytes.extend_from_slice(&(artifact.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(artifact);
+        }
+        bytes
+    }
+
+    /// Deserializes variants serialized with [`ArtifactVariants::serialize`].
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, DeserializeError> {
+        if !Self::is_variants(bytes) {
+            return Err(DeserializeError::Incompatible(
+                "The provided bytes are not artifact variants".to_string(),
+            ));
+        }
+        let corrupted =
+            || DeserializeError::CorruptedBinary("invalid artifact variants".to_string());
+        let mut rest = &bytes[Self::MAGIC.len()..];
+        let read_u64 = |rest: &mut &[u8]| -> Result<u64, DeserializeError> {
+            let value = rest.get(..8).ok_or_else(corrupted)?;
+            *rest = &rest[8..];
+            Ok(u64::from_le_bytes(value.try_into().unwrap()))
+        };
+        let count = read_u64(&mut rest)?;
+        let mut variants = Vec::new();
+        for _ in 0..count {
+            let cpu_features = EnumSet::try_from_u64(read_u64(&mut rest)?).ok_or_else(|| {
+                DeserializeError::Incompatible(
+                    "An artifact variant requires unknown CPU features".to_string(),
+                )
+            })?;
+            let len = read_u64(&mut rest)?.try_into().map_err(|_| corrupted())?;
+            let artifact = rest.get(..len).ok_or_else(corrupted)?;
+            rest = &rest[len..];
+            variants.push((cpu_features, artifact.to_vec()));
+        }
+        if !rest.is_empty() {
+            return Err(corrupted());
+        }
+        Ok(Self { variants })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn artifact_variants_roundtrip() {
+        let mut variants = ArtifactVariants::new();
+        variants.push(EnumSet::empty(), b"baseline".to_vec());
+        variants.push(CpuFeature::AVX | CpuFeature::AVX2, b"avx2".to_vec());
+        let bytes = variants.serialize();
+        assert!(ArtifactVariants::is_variants(&bytes));
+        assert_eq!(ArtifactVariants::deserialize(&bytes).unwrap(), variants);
+
+        assert!(ArtifactVariants::deserialize(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ArtifactVariants::deserialize(b"WASMER\0\0").is_err());
+    }
+
+    #[test]
+    fn artifact_variants_select() {
+        let mut variants = ArtifactVariants::new();
+        variants.push(EnumSet::empty(), b"baseline".to_vec());
+        variants.push(CpuFeature::AVX | CpuFeature::AVX2, b"avx2".to_vec());
+        variants.push(CpuFeature::AVX.into(), b"avx".to_vec());
+
+        assert_eq!(variants.select(EnumSet::empty()), Some(&b"baseline"[..]));
+        assert_eq!(
+            variants.select(CpuFeature::SSE2 | CpuFeature::AVX),
+            Some(&b"avx"[..])
+        );
+        assert_eq!(
+            variants.select(CpuFeature::AVX | CpuFeature::AVX2 | CpuFeature::LZCNT),
+            Some(&b"avx2"[..])
+        );
+        assert_eq!(ArtifactVariants::new().select(EnumSet::all()), None);
+    }
+}
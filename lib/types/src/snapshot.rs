@@ -0,0 +1,103 @@
+//! Snapshots of the state of an instance.
+
+use crate::entity::PrimaryMap;
+use crate::{
+    DeserializeError, FunctionIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex,
+    SerializeError,
+};
+use rkyv::{
+    check_archived_value, de::deserializers::SharedDeserializeMap,
+    ser::serializers::AllocSerializer, ser::Serializer as RkyvSerializer, Archive, CheckBytes,
+    Deserialize as RkyvDeserialize, Serialize as RkyvSerialize,
+};
+
+/// The state of the memories, globals and tables defined by an instance.
+///
+/// Imported memories, globals and tables are owned by another instance or
+/// by the host, and aren't part of the snapshot.
+#[derive(Archive, RkyvDeserialize, RkyvSerialize, Debug, Clone, PartialEq, Eq)]
+#[archive_attr(derive(CheckBytes))]
+pub struct InstanceSnapshot {
+    /// The contents of each memory.
+    pub memories: PrimaryMap<LocalMemoryIndex, Vec<u8>>,
+    /// The value of each global.
+    pub globals: PrimaryMap<LocalGlobalIndex, GlobalSnapshot>,
+    /// The elements of each table, as the index of the function they
+    /// refer to in the module of the instance.
+    pub tables: PrimaryMap<LocalTableIndex, Vec<Option<FunctionIndex>>>,
+}
+
+/// The value of a global in an [`InstanceSnapshot`].
+///
+/// References can't be stored as they are, since they point into the
+/// instance they were taken from.
+#[derive(Archive, RkyvDeserialize, RkyvSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[archive_attr(derive(CheckBytes), repr(u8))]
+pub enum GlobalSnapshot {
+    /// The raw value of a number or a vector.
+    Value(u128),
+    /// A reference, as the index of the function it refers to in the
+    /// module of the instance, or `None` for a null reference.
+    Ref(Option<FunctionIndex>),
+}
+
+impl InstanceSnapshot {
+    /// Serialize a snapshot into bytes
+    /// The bytes will have the following format:
+    /// RKYV serialization (any length) + POS (8 bytes)
+    pub fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        let mut serializer = AllocSerializer::<4096>::default();
+        let pos = serializer
+            .serialize_value(self)
+            .map_err(|err| SerializeError::Generic(format!("{}", err)))? as u64;
+        let mut serialized_data = serializer.into_serializer().into_inner();
+        serialized_data.extend_from_slice(&pos.to_le_bytes());
+        Ok(serialized_data.to_vec())
+    }
+
+    /// Deserialize a snapshot from a slice, validating the data.
+    /// The slice must have the following format:
+    /// RKYV serialization (any length) + POS (8 bytes)
+    pub fn deserialize(data: &[u8]) -> Result<Self, DeserializeError> {
+        if data.len() < 8 {
+            return Err(DeserializeError::Incompatible(
+                "invalid serialized data".into(),
+            ));
+        }
+        let mut pos: [u8; 8] = Default::default();
+        pos.copy_from_slice(&data[data.len() - 8..]);
+        let pos: u64 = u64::from_le_bytes(pos);
+        let archived = check_archived_value::<Self>(&data[..data.len() - 8], pos as usize)
+            .map_err(|err| DeserializeError::CorruptedBinary(err.to_string()))?;
+        let mut deserializer = SharedDeserializeMap::new();
+        RkyvDeserialize::deserialize(archived, &mut deserializer)
+            .map_err(|e| DeserializeError::CorruptedBinary(format!("{:?}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_roundtrip() {
+        let mut snapshot = InstanceSnapshot {
+            memories: PrimaryMap::new(),
+            globals: PrimaryMap::new(),
+            tables: PrimaryMap::new(),
+        };
+        snapshot.memories.push(vec![1, 2, 3]);
+        snapshot.globals.push(GlobalSnapshot::Value(u128::MAX));
+        snapshot
+            .globals
+            .push(GlobalSnapshot::Ref(Some(FunctionIndex::from_u32(1))));
+        snapshot.globals.push(GlobalSnapshot::Ref(None));
+        snapshot
+            .tables
+            .push(vec![None, Some(FunctionIndex::from_u32(2))]);
+
+        let bytes = snapshot.serialize().unwrap();
+        assert_eq!(InstanceSnapshot::deserialize(&bytes).unwrap(), snapshot);
+        assert!(InstanceSnapshot::deserialize(&bytes[..4]).is_err());
+    }
+}
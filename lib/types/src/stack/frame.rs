@@ -1,5 +1,31 @@
+use crate::lib::std::fmt;
 use crate::SourceLoc;
 
+/// A line of a source file, as described by the DWARF sections of a
+/// WebAssembly module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The path of the source file.
+    pub file: String,
+    /// The line number, starting at 1. `0` if the line is unknown.
+    pub line: u64,
+    /// The column number, starting at 1. `0` if the column is unknown.
+    pub column: u64,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if self.line != 0 {
+            write!(f, ":{}", self.line)?;
+            if self.column != 0 {
+                write!(f, ":{}", self.column)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Description of a frame in a backtrace.
 ///
 /// Each runtime error includes a backtrace of the WebAssembly frames that led
@@ -16,6 +42,8 @@ pub struct FrameInfo {
     func_start: SourceLoc,
     /// The source location of the instruction
     instr: SourceLoc,
+    /// The line of source code of the instruction, if the module has DWARF.
+    source_location: Option<SourceLocation>,
 }
 
 impl FrameInfo {
@@ -33,9 +61,16 @@ impl FrameInfo {
             function_name,
             func_start,
             instr,
+            source_location: None,
         }
     }
 
+    /// Sets the line of source code this frame's program counter was at.
+    pub fn with_source_location(mut self, source_location: SourceLocation) -> Self {
+        self.source_location = Some(source_location);
+        self
+    }
+
     /// Returns the WebAssembly function index for this frame.
     ///
     /// This function index is the index in the function index space of the
@@ -93,4 +128,13 @@ impl FrameInfo {
     pub fn func_offset(&self) -> usize {
         (self.instr.bits() - self.func_start.bits()) as usize
     }
+
+    /// Returns the line of source code this frame's program counter was at.
+    ///
+    /// The location is read from the DWARF sections of the module, so this
+    /// returns `None` when the module was compiled without debug info, or
+    /// when the engine can't read it (headless engines don't).
+    pub fn source_location(&self) -> Option<&SourceLocation> {
+        self.source_location.as_ref()
+    }
 }
@@ -69,6 +69,12 @@ pub enum TrapCode {
 
     /// An atomic memory access was attempted with an unaligned pointer.
     UnalignedAtomic = 10,
+
+    /// A call went past the limit of nested calls set by a middleware.
+    StackExhausted = 11,
+
+    /// The store ran out of the fuel consumed by the instrumented code.
+    OutOfFuel = 12,
 }
 
 impl TrapCode {
@@ -86,6 +92,8 @@ impl TrapCode {
             Self::BadConversionToInteger => "invalid conversion to integer",
             Self::UnreachableCodeReached => "unreachable",
             Self::UnalignedAtomic => "unaligned atomic access",
+            Self::StackExhausted => "call stack depth limit exceeded",
+            Self::OutOfFuel => "all fuel consumed",
         }
     }
 }
@@ -104,6 +112,8 @@ impl Display for TrapCode {
             Self::BadConversionToInteger => "bad_toint",
             Self::UnreachableCodeReached => "unreachable",
             Self::UnalignedAtomic => "unalign_atom",
+            Self::StackExhausted => "stk_limit",
+            Self::OutOfFuel => "out_of_fuel",
         };
         f.write_str(identifier)
     }
@@ -125,6 +135,8 @@ impl FromStr for TrapCode {
             "bad_toint" => Ok(Self::BadConversionToInteger),
             "unreachable" => Ok(Self::UnreachableCodeReached),
             "unalign_atom" => Ok(Self::UnalignedAtomic),
+            "stk_limit" => Ok(Self::StackExhausted),
+            "out_of_fuel" => Ok(Self::OutOfFuel),
             _ => Err(()),
         }
     }
@@ -148,7 +160,7 @@ mod tests {
     use super::*;
 
     // Everything but user-defined codes.
-    const CODES: [TrapCode; 11] = [
+    const CODES: [TrapCode; 13] = [
         TrapCode::StackOverflow,
         TrapCode::HeapAccessOutOfBounds,
         TrapCode::HeapMisaligned,
@@ -160,6 +172,8 @@ mod tests {
         TrapCode::BadConversionToInteger,
         TrapCode::UnreachableCodeReached,
         TrapCode::UnalignedAtomic,
+        TrapCode::StackExhausted,
+        TrapCode::OutOfFuel,
     ];
 
     #[test]
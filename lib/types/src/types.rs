@@ -175,11 +175,13 @@ fn is_memory_compatible(
         minimum: exported_minimum,
         maximum: exported_maximum,
         shared: exported_shared,
+        memory64: exported_memory64,
     } = exported;
     let MemoryType {
         minimum: imported_minimum,
         maximum: imported_maximum,
         shared: imported_shared,
+        memory64: imported_memory64,
     } = imported;
 
     imported_minimum.0 <= imported_runtime_size.unwrap_or(exported_minimum.0)
@@ -187,6 +189,7 @@ fn is_memory_compatible(
             || (!exported_maximum.is_none()
                 && imported_maximum.unwrap() >= exported_maximum.unwrap()))
         && exported_shared == imported_shared
+        && exported_memory64 == imported_memory64
 }
 
 macro_rules! accessors {
@@ -496,6 +499,9 @@ pub struct MemoryType {
     pub maximum: Option<Pages>,
     /// Whether the memory may be shared between multiple threads.
     pub shared: bool,
+    /// Whether the memory is indexed with 64-bit addresses, see
+    /// [`MemoryType::new64`].
+    memory64: bool,
 }
 
 impl MemoryType {
@@ -509,6 +515,35 @@ impl MemoryType {
             minimum: minimum.into(),
             maximum: maximum.map(Into::into),
             shared,
+            memory64: false,
+        }
+    }
+
+    /// Creates a new descriptor for a WebAssembly memory indexed with
+    /// 64-bit addresses, given the specified limits of the memory.
+    pub fn new64<IntoPages>(minimum: IntoPages, maximum: Option<IntoPages>, shared: bool) -> Self
+    where
+        IntoPages: Into<Pages>,
+    {
+        Self {
+            memory64: true,
+            ..Self::new(minimum, maximum, shared)
+        }
+    }
+
+    /// Whether the memory is indexed with 64-bit addresses (from the
+    /// memory64 proposal) rather than 32-bit ones.
+    pub fn is_memory64(&self) -> bool {
+        self.memory64
+    }
+
+    /// The largest number of pages this memory can ever have, whatever
+    /// its declared maximum.
+    pub fn max_pages(&self) -> Pages {
+        if self.memory64 {
+            Pages::max_value64()
+        } else {
+            Pages::max_value()
         }
     }
 }
@@ -516,10 +551,11 @@ impl MemoryType {
 impl fmt::Display for MemoryType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let shared = if self.shared { "shared" } else { "not shared" };
+        let index = if self.memory64 { "i64 " } else { "" };
         if let Some(maximum) = self.maximum {
-            write!(f, "{} ({:?}..{:?})", shared, self.minimum, maximum)
+            write!(f, "{}{} ({:?}..{:?})", index, shared, self.minimum, maximum)
         } else {
-            write!(f, "{} ({:?}..)", shared, self.minimum)
+            write!(f, "{}{} ({:?}..)", index, shared, self.minimum)
         }
     }
 }
@@ -16,6 +16,12 @@ pub const WASM_PAGE_SIZE: usize = 0x10000;
 /// The number of pages we can have before we run out of byte index space.
 pub const WASM_MAX_PAGES: u32 = 0x10000;
 
+/// The number of pages a memory indexed with 64-bit addresses can have.
+///
+/// The memory64 proposal allows up to 2^48 pages, but we are limited by
+/// what `Pages` can represent, which is 256 TiB.
+pub const WASM64_MAX_PAGES: u32 = u32::MAX;
+
 /// The minimum number of pages allowed.
 pub const WASM_MIN_PAGES: u32 = 0x100;
 
@@ -46,6 +52,13 @@ impl Pages {
         Self(WASM_MAX_PAGES)
     }
 
+    /// Returns the largest number of pages of a memory indexed with
+    /// 64-bit addresses.
+    #[inline(always)]
+    pub const fn max_value64() -> Self {
+        Self(WASM64_MAX_PAGES)
+    }
+
     /// Checked addition. Computes `self + rhs`,
     /// returning `None` if overflow occurred.
     pub fn checked_add(self, rhs: Self) -> Option<Self> {
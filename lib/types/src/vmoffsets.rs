@@ -139,9 +139,14 @@ impl VMBuiltinFunctionIndex {
     pub const fn get_imported_memory_atomic_notify_index() -> Self {
         Self(29)
     }
+    /// Returns an index for wasm's `memory.copy` between two different
+    /// memories, each of which may be local or imported.
+    pub const fn get_memory_copy_between_index() -> Self {
+        Self(30)
+    }
     /// Returns the total number of builtin functions.
     pub const fn builtin_functions_total_number() -> u32 {
-        30
+        31
     }
 
     /// Return the index as an u32 number.
@@ -26,6 +26,7 @@ pub mod mem_fs;
 pub mod null_file;
 pub mod passthru_fs;
 pub mod random_file;
+pub mod restricted_fs;
 pub mod special_file;
 pub mod tmp_fs;
 pub mod union_fs;
@@ -55,6 +56,7 @@ pub use null_file::*;
 pub use overlay_fs::OverlayFileSystem;
 pub use passthru_fs::*;
 pub use pipe::*;
+pub use restricted_fs::RestrictedFileSystem;
 pub use special_file::*;
 pub use tmp_fs::*;
 pub use trace_fs::TraceFileSystem;
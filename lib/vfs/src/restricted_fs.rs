@@ -0,0 +1,257 @@
+//! A [`FileSystem`] wrapper which limits what the guest is allowed to change,
+//! e.g. to expose a host folder as read-only.
+
+use std::path::Path;
+
+use crate::{
+    FileOpener, FileSystem, FsError, Metadata, OpenOptions, OpenOptionsConfig, ReadDir, Result,
+    VirtualFile,
+};
+
+/// A [`FileSystem`] which forwards to an inner filesystem, but rejects the
+/// operations that its permissions don't allow with
+/// [`FsError::PermissionDenied`].
+///
+/// - A read-only filesystem can't be modified in any way.
+/// - A filesystem which doesn't allow creation lets existing files be
+///   modified, but no new files or directories can be added.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestrictedFileSystem<F> {
+    inner: F,
+    read_only: bool,
+    allow_create: bool,
+}
+
+impl<F> RestrictedFileSystem<F> {
+    /// Wrap a filesystem without restricting anything.
+    pub fn new(inner: F) -> Self {
+        RestrictedFileSystem {
+            inner,
+            read_only: false,
+            allow_create: true,
+        }
+    }
+
+    /// Reject every operation which would modify the filesystem.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Whether new files and directories may be created.
+    pub fn allow_create(mut self, allow_create: bool) -> Self {
+        self.allow_create = allow_create;
+        self
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn allows_create(&self) -> bool {
+        !self.read_only && self.allow_create
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+}
+
+impl<F> RestrictedFileSystem<F>
+where
+    F: FileSystem,
+{
+    fn check_writable(&self) -> Result<()> {
+        if self.read_only {
+            Err(FsError::PermissionDenied)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_can_create(&self, path: &Path) -> Result<()> {
+        self.check_writable()?;
+
+        if !self.allow_create && self.inner.metadata(path).is_err() {
+            return Err(FsError::PermissionDenied);
+        }
+
+        Ok(())
+    }
+}
+
+impl<F> FileSystem for RestrictedFileSystem<F>
+where
+    F: FileSystem,
+{
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        self.inner.read_dir(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        self.check_can_create(path)?;
+        self.inner.create_dir(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        self.check_writable()?;
+        self.inner.remove_dir(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.check_can_create(to)?;
+        self.inner.rename(from, to)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.symlink_metadata(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        self.check_writable()?;
+        self.inner.remove_file(path)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(self)
+    }
+}
+
+impl<F> FileOpener for RestrictedFileSystem<F>
+where
+    F: FileSystem,
+{
+    fn open(
+        &self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        if conf.would_mutate() {
+            self.check_writable()?;
+        }
+
+        let mut conf = conf.clone();
+
+        if !self.allow_create {
+            if conf.create_new() {
+                return Err(FsError::PermissionDenied);
+            }
+            if conf.create() {
+                // Opening an existing file is fine, but the file mustn't be
+                // created if it doesn't exist yet.
+                self.inner.metadata(path).map_err(|e| match e {
+                    FsError::EntryNotFound => FsError::PermissionDenied,
+                    other => other,
+                })?;
+                conf.create = false;
+            }
+        }
+
+        self.inner.new_open_options().options(conf).open(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::mem_fs;
+
+    fn populated_fs() -> mem_fs::FileSystem {
+        let fs = mem_fs::FileSystem::default();
+        fs.create_dir(Path::new("/dir")).unwrap();
+        fs.new_open_options()
+            .write(true)
+            .create(true)
+            .open("/file.txt")
+            .unwrap();
+        fs
+    }
+
+    #[tokio::test]
+    async fn read_only_rejects_all_modifications() {
+        let fs = RestrictedFileSystem::new(populated_fs()).read_only(true);
+
+        assert_eq!(
+            fs.create_dir(Path::new("/new")),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.remove_dir(Path::new("/dir")),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.remove_file(Path::new("/file.txt")),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.rename(Path::new("/file.txt"), Path::new("/other.txt")),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.new_open_options()
+                .write(true)
+                .open("/file.txt")
+                .unwrap_err(),
+            FsError::PermissionDenied
+        );
+
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open("/file.txt")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .await
+            .unwrap();
+        assert!(fs.metadata(Path::new("/dir")).unwrap().is_dir());
+    }
+
+    #[tokio::test]
+    async fn no_create_allows_existing_files_to_be_modified() {
+        let fs = RestrictedFileSystem::new(populated_fs()).allow_create(false);
+
+        assert_eq!(
+            fs.create_dir(Path::new("/new")),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.new_open_options()
+                .write(true)
+                .create(true)
+                .open("/new.txt")
+                .unwrap_err(),
+            FsError::PermissionDenied
+        );
+        assert_eq!(
+            fs.new_open_options()
+                .write(true)
+                .create_new(true)
+                .open("/file.txt")
+                .unwrap_err(),
+            FsError::PermissionDenied
+        );
+
+        fs.new_open_options()
+            .write(true)
+            .create(true)
+            .open("/file.txt")
+            .unwrap()
+            .write_all(b"hello")
+            .await
+            .unwrap();
+        assert_eq!(fs.metadata(Path::new("/file.txt")).unwrap().len(), 5);
+        assert!(fs.metadata(Path::new("/new.txt")).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! Coredumps of the WebAssembly state of a store, in the
+//! [wasm-coredump] format.
+//!
+//! A coredump is itself a WebAssembly module. Its memories and globals
+//! hold the contents of the memories and globals of the store, and custom
+//! sections describe the instances and the stack at the time of the dump.
+//! Debuggers that understand the format can then inspect a crash after
+//! the process is gone.
+//!
+//! Compiled code doesn't keep the values of locals and of the operand
+//! stack, so every frame is written without them.
+//!
+//! [wasm-coredump]: https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md
+
+use crate::store::StoreObject;
+use crate::{LinearMemory, StoreObjects, VMGlobal, VMInstance, VMMemory};
+use wasmer_types::{FrameInfo, Mutability, Type};
+
+/// Writes a coredump of the memories, globals and instances of `objects`.
+///
+/// `frames` is the stack to record, starting with the most recent frame,
+/// as returned by the backtrace of a trap. Frames are attributed to the
+/// first instance whose module has the same name.
+pub fn write_coredump(
+    objects: &StoreObjects,
+    executable_name: &str,
+    frames: &[FrameInfo],
+) -> Vec<u8> {
+    let instances = VMInstance::list(objects);
+    let memories = VMMemory::list(objects);
+    let globals = VMGlobal::list(objects);
+
+    let mut wasm = b"\0asm".to_vec();
+    wasm.extend_from_slice(&1u32.to_le_bytes());
+
+    let mut section = vec![0];
+    write_name(&mut section, executable_name);
+    write_custom_section(&mut wasm, "core", &section);
+
+    let mut section = Vec::new();
+    write_u32(&mut section, instances.len() as u32);
+    for instance in instances {
+        section.push(0);
+        write_name(&mut section, &instance.module_ref().name());
+    }
+    write_custom_section(&mut wasm, "coremodules", &section);
+
+    // Each instance comes from the module of the same index.
+    let mut section = Vec::new();
+    write_u32(&mut section, instances.len() as u32);
+    for (index, instance) in instances.iter().enumerate() {
+        section.push(0);
+        write_u32(&mut section, index as u32);
+        let memories = instance.memory_handles().collect::<Vec<_>>();
+        write_u32(&mut section, memories.len() as u32);
+        for memory in memories {
+            write_u32(&mut section, memory.index() as u32);
+        }
+        let globals = instance.global_handles().collect::<Vec<_>>();
+        write_u32(&mut section, globals.len() as u32);
+        for global in globals {
+            write_u32(&mut section, global.index() as u32);
+        }
+    }
+    write_custom_section(&mut wasm, "coreinstances", &section);
+
+    let mut section = vec![0];
+    write_name(&mut section, "main");
+    write_u32(&mut section, frames.len() as u32);
+    for frame in frames {
+        let instance = instances
+            .iter()
+            .position(|instance| instance.module_ref().name() == frame.module_name())
+            .unwrap_or(0);
+        section.push(0);
+        write_u32(&mut section, instance as u32);
+        write_u32(&mut section, frame.func_index());
+        write_u32(&mut section, frame.func_offset() as u32);
+        // No locals and no operand stack.
+        write_u32(&mut section, 0);
+        write_u32(&mut section, 0);
+    }
+    write_custom_section(&mut wasm, "corestack", &section);
+
+    // The memory section.
+    let mut section = Vec::new();
+    write_u32(&mut section, memories.len() as u32);
+    for memory in memories {
+        section.push(0);
+        write_u32(&mut section, memory.size().0);
+    }
+    write_section(&mut wasm, 5, &section);
+
+    // The global section.
+    let mut section = Vec::new();
+    write_u32(&mut section, globals.len() as u32);
+    for global in globals {
+        let ty = global.ty();
+        let value = unsafe { global.vmglobal().as_ref().val };
+        section.push(value_type(ty.ty));
+        section.push(match ty.mutability {
+            Mutability::Const => 0,
+            Mutability::Var => 1,
+        });
+        unsafe {
+            match ty.ty {
+                Type::I32 => {
+                    section.push(0x41);
+                    write_i64(&mut section, value.i32 as i64);
+                }
+                Type::I64 => {
+                    section.push(0x42);
+                    write_i64(&mut section, value.i64);
+                }
+                Type::F32 => {
+                    section.push(0x43);
+                    section.extend_from_slice(&value.f32.to_le_bytes());
+                }
+                Type::F64 => {
+                    section.push(0x44);
+                    section.extend_from_slice(&value.f64.to_le_bytes());
+                }
+                Type::V128 => {
+                    section.extend_from_slice(&[0xfd, 0x0c]);
+                    section.extend_from_slice(&value.u128.to_le_bytes());
+                }
+                // References point into the process, so they are written
+                // as null.
+                Type::FuncRef | Type::ExternRef => {
+                    section.push(0xd0);
+                    section.push(value_type(ty.ty));
+                }
+            }
+        }
+        section.push(0x0b);
+    }
+    write_section(&mut wasm, 6, &section);
+
+    // The data section, with the contents of each memory up to its last
+    // non-zero byte.
+    let mut section = Vec::new();
+    write_u32(&mut section, memories.len() as u32);
+    for (index, memory) in memories.iter().enumerate() {
+        let data = unsafe {
+            let definition = memory.vmmemory().as_ref();
+            std::slice::from_raw_parts(definition.base, definition.current_length)
+        };
+        let len = data
+            .iter()
+            .rposition(|byte| *byte != 0)
+            .map_or(0, |last| last + 1);
+        if index == 0 {
+            section.push(0);
+        } else {
+            section.push(2);
+            write_u32(&mut section, index as u32);
+        }
+        // `i32.const 0`
+        section.extend_from_slice(&[0x41, 0, 0x0b]);
+        write_u32(&mut section, len as u32);
+        section.extend_from_slice(&data[..len]);
+    }
+    write_section(&mut wasm, 11, &section);
+
+    wasm
+}
+
+fn value_type(ty: Type) -> u8 {
+    match ty {
+        Type::I32 => 0x7f,
+        Type::I64 => 0x7e,
+        Type::F32 => 0x7d,
+        Type::F64 => 0x7c,
+        Type::V128 => 0x7b,
+        Type::FuncRef => 0x70,
+        Type::ExternRef => 0x6f,
+    }
+}
+
+fn write_section(wasm: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    wasm.push(id);
+    write_u32(wasm, contents.len() as u32);
+    wasm.extend_from_slice(contents);
+}
+
+fn write_custom_section(wasm: &mut Vec<u8>, name: &str, contents: &[u8]) {
+    let mut section = Vec::new();
+    write_name(&mut section, name);
+    section.extend_from_slice(contents);
+    write_section(wasm, 0, &section);
+}
+
+fn write_name(bytes: &mut Vec<u8>, name: &str) {
+    write_u32(bytes, name.len() as u32);
+    bytes.extend_from_slice(name.as_bytes());
+}
+
+fn write_u32(bytes: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn write_i64(bytes: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::InternalStoreHandle;
+    use wasmer_types::{GlobalType, MemoryStyle, MemoryType, Pages, SourceLoc};
+
+    #[test]
+    fn leb128() {
+        let mut bytes = Vec::new();
+        write_u32(&mut bytes, 624485);
+        assert_eq!(bytes, [0xe5, 0x8e, 0x26]);
+        bytes.clear();
+        write_i64(&mut bytes, -123456);
+        assert_eq!(bytes, [0xc0, 0xbb, 0x78]);
+        bytes.clear();
+        write_i64(&mut bytes, 64);
+        assert_eq!(bytes, [0xc0, 0x00]);
+    }
+
+    #[test]
+    fn coredump_has_memories_globals_and_stack() {
+        let mut objects = StoreObjects::default();
+        let memory = VMMemory::new(
+            &MemoryType::new(Pages(1), None, false),
+            &MemoryStyle::Dynamic {
+                offset_guard_size: 0,
+            },
+        )
+        .unwrap();
+        unsafe {
+            let definition = memory.vmmemory().as_ref();
+            *definition.base.add(2) = 42;
+        }
+        InternalStoreHandle::new(&mut objects, memory);
+        let global = VMGlobal::new(GlobalType::new(Type::I32, Mutability::Var));
+        unsafe {
+            global.vmglobal().as_mut().val.i32 = -1;
+        }
+        InternalStoreHandle::new(&mut objects, global);
+        let frame = FrameInfo::new(
+            "guest".to_string(),
+            3,
+            None,
+            SourceLoc::new(10),
+            SourceLoc::new(14),
+        );
+
+        let wasm = write_coredump(&objects, "guest.wasm", &[frame]);
+        assert_eq!(&wasm[..8], b"\0asm\x01\0\0\0");
+        let find = |bytes: &[u8]| wasm.windows(bytes.len()).position(|w| w == bytes);
+        // The `core` section names the executable.
+        assert_eq!(wasm[8..14], [0, 17, 4, b'c', b'o', b'r']);
+        assert!(find(b"\x0aguest.wasm").is_some());
+        // One frame in function 3, at offset 4, without an instance.
+        assert!(find(b"corestack\0\x04main\x01\0\0\x03\x04\0\0").is_some());
+        // A memory of one page, a mutable `i32` global of -1, and the first
+        // three bytes of the memory.
+        assert!(find(&[5, 3, 1, 0, 1]).is_some());
+        assert!(find(&[6, 6, 1, 0x7f, 1, 0x41, 0x7f, 0x0b]).is_some());
+        assert!(find(&[11, 9, 1, 0, 0x41, 0, 0x0b, 3, 0, 0, 42]).is_some());
+    }
+}
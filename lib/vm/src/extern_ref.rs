@@ -25,6 +25,12 @@ impl VMExternObj {
     pub fn as_ref(&self) -> &(dyn Any + Send + Sync + 'static) {
         &*self.contents
     }
+
+    #[allow(clippy::should_implement_trait)]
+    /// Returns a mutable reference to the underlying value.
+    pub fn as_mut(&mut self) -> &mut (dyn Any + Send + Sync + 'static) {
+        &mut *self.contents
+    }
 }
 
 /// Represents an opaque reference to any data within WebAssembly.
@@ -0,0 +1,89 @@
+//! Fuel consumed by the WebAssembly code running in a store.
+//!
+//! Code compiled with the `Fuel` middleware of `wasmer-middlewares` pays
+//! for every basic block before running it, and traps with
+//! `TrapCode::OutOfFuel` when there isn't enough fuel left. The fuel is a
+//! global of the module, recorded in `ModuleInfo::fuel_global`. When such
+//! a module is instantiated, the global is backed by the fuel of the store
+//! instead of a value of its own, so that every instance of the store
+//! draws from the same budget.
+
+use crate::vmcontext::VMGlobalDefinition;
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ptr::NonNull;
+use wasmer_types::{LocalGlobalIndex, ModuleInfo};
+
+/// Returns the global of `module` holding the fuel of the store, if the
+/// module has one.
+pub fn fuel_global(module: &ModuleInfo) -> Option<LocalGlobalIndex> {
+    module
+        .fuel_global
+        .and_then(|index| module.local_global_index(index))
+}
+
+/// The fuel left in a store, as the `i64` global read by the generated
+/// code.
+///
+/// A store starts without fuel.
+pub(crate) struct Fuel(Box<UnsafeCell<VMGlobalDefinition>>);
+
+impl Fuel {
+    /// Returns the definition of the fuel, used by the generated code.
+    pub(crate) fn vmglobal(&self) -> NonNull<VMGlobalDefinition> {
+        unsafe { NonNull::new_unchecked(self.0.get()) }
+    }
+
+    /// Returns the fuel left.
+    pub(crate) fn remaining(&self) -> u64 {
+        unsafe { (*self.0.get()).val.u64 }
+    }
+
+    /// Replaces the fuel left.
+    pub(crate) fn set(&mut self, fuel: u64) {
+        self.0.get_mut().val.u64 = fuel;
+    }
+}
+
+impl Default for Fuel {
+    fn default() -> Self {
+        Self(Box::new(UnsafeCell::new(VMGlobalDefinition::new())))
+    }
+}
+
+impl fmt::Debug for Fuel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Fuel").field(&self.remaining()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmer_types::{GlobalType, Mutability, Type};
+
+    #[test]
+    fn fuel_lives_in_the_definition() {
+        let mut fuel = Fuel::default();
+        assert_eq!(fuel.remaining(), 0);
+        fuel.set(1000);
+        assert_eq!(unsafe { fuel.vmglobal().as_ref().val.i64 }, 1000);
+        unsafe { fuel.vmglobal().as_mut().val.i64 -= 400 };
+        assert_eq!(fuel.remaining(), 600);
+    }
+
+    #[test]
+    fn only_the_recorded_global_is_the_fuel() {
+        let mut module = ModuleInfo::new();
+        module
+            .globals
+            .push(GlobalType::new(Type::I64, Mutability::Var));
+        assert_eq!(fuel_global(&module), None);
+
+        let fuel = module
+            .globals
+            .push(GlobalType::new(Type::I64, Mutability::Var));
+        module.fuel_global = Some(fuel);
+        assert_eq!(fuel_global(&module), module.local_global_index(fuel));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{store::MaybeInstanceOwned, vmcontext::VMGlobalDefinition};
+use crate::{store::MaybeInstanceOwned, vmcontext::VMGlobalDefinition, InterruptHandle};
 use derivative::Derivative;
 use std::{cell::UnsafeCell, ptr::NonNull};
 use wasmer_types::GlobalType;
@@ -10,6 +10,9 @@ pub struct VMGlobal {
     ty: GlobalType,
     #[derivative(Debug = "ignore")]
     vm_global_definition: MaybeInstanceOwned<VMGlobalDefinition>,
+    // Keeps the definition of an interrupt flag alive.
+    #[derivative(Debug = "ignore")]
+    interrupt: Option<InterruptHandle>,
 }
 
 impl VMGlobal {
@@ -22,6 +25,28 @@ impl VMGlobal {
             vm_global_definition: MaybeInstanceOwned::Host(Box::new(UnsafeCell::new(
                 VMGlobalDefinition::new(),
             ))),
+            interrupt: None,
+        }
+    }
+
+    /// Create a global backed by the flag of an [`InterruptHandle`].
+    pub fn from_interrupt_handle(global_type: GlobalType, handle: InterruptHandle) -> Self {
+        Self {
+            ty: global_type,
+            vm_global_definition: MaybeInstanceOwned::Instance(handle.vmglobal()),
+            interrupt: Some(handle),
+        }
+    }
+
+    /// Create a global backed by a definition owned by its store.
+    pub(crate) fn from_store_definition(
+        global_type: GlobalType,
+        definition: NonNull<VMGlobalDefinition>,
+    ) -> Self {
+        Self {
+            ty: global_type,
+            vm_global_definition: MaybeInstanceOwned::Instance(definition),
+            interrupt: None,
         }
     }
 
@@ -43,6 +68,7 @@ impl VMGlobal {
                 vm_global_definition: MaybeInstanceOwned::Host(Box::new(UnsafeCell::new(
                     self.vm_global_definition.as_ptr().as_ref().clone(),
                 ))),
+                interrupt: None,
             }
         }
     }
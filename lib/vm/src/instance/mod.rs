@@ -14,13 +14,13 @@ use crate::store::{InternalStoreHandle, StoreObjects};
 use crate::table::TableElement;
 use crate::trap::{catch_traps, Trap, TrapCode};
 use crate::vmcontext::{
-    memory32_atomic_check32, memory32_atomic_check64, memory_copy, memory_fill,
-    VMBuiltinFunctionsArray, VMCallerCheckedAnyfunc, VMContext, VMFunctionContext,
+    memory32_atomic_check32, memory32_atomic_check64, memory_copy, memory_copy_between,
+    memory_fill, VMBuiltinFunctionsArray, VMCallerCheckedAnyfunc, VMContext, VMFunctionContext,
     VMFunctionImport, VMFunctionKind, VMGlobalDefinition, VMGlobalImport, VMMemoryDefinition,
     VMMemoryImport, VMSharedSignatureIndex, VMTableDefinition, VMTableImport, VMTrampoline,
 };
+use crate::{fuel_global, interrupt_global, LinearMemory, MemoryImages, NotifyLocation};
 use crate::{FunctionBodyPtr, MaybeInstanceOwned, TrapHandlerFn, VMFunctionBody};
-use crate::{LinearMemory, NotifyLocation};
 use crate::{VMFuncRef, VMFunction, VMGlobal, VMMemory, VMTable};
 pub use allocator::InstanceAllocator;
 use memoffset::offset_of;
@@ -36,9 +36,11 @@ use std::slice;
 use std::sync::Arc;
 use wasmer_types::entity::{packed_option::ReservedValue, BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{
-    DataIndex, DataInitializer, ElemIndex, ExportIndex, FunctionIndex, GlobalIndex, GlobalInit,
-    LocalFunctionIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryError,
-    MemoryIndex, ModuleInfo, Pages, SignatureIndex, TableIndex, TableInitializer, VMOffsets,
+    DataIndex, DataInitializer, DeserializeError, ElemIndex, ExportIndex, FunctionIndex,
+    GlobalIndex, GlobalInit, GlobalSnapshot, InstanceSnapshot, LocalFunctionIndex,
+    LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryError, MemoryIndex, ModuleInfo,
+    Pages, RawValue, SerializeError, SignatureIndex, TableIndex, TableInitializer, Type, VMOffsets,
+    WASM_PAGE_SIZE,
 };
 
 /// A WebAssembly instance.
@@ -90,6 +92,9 @@ pub(crate) struct Instance {
     /// will point to elements here for functions imported by this instance.
     imported_funcrefs: BoxedSlice<FunctionIndex, NonNull<VMCallerCheckedAnyfunc>>,
 
+    /// Whether the start function still has to run.
+    start_pending: bool,
+
     /// Additional context used by compiled WebAssembly code. This
     /// field is last, and represents a dynamically-sized array that
     /// extends beyond the nominal end of the struct (similar to a
@@ -327,6 +332,7 @@ impl Instance {
     fn invoke_start_function(
         &self,
         trap_handler: Option<*const TrapHandlerFn<'static>>,
+        stack_size: Option<usize>,
     ) -> Result<(), Trap> {
         let start_index = match self.module.start_function {
             Some(idx) => idx,
@@ -356,7 +362,7 @@ impl Instance {
 
         // Make the call.
         unsafe {
-            catch_traps(trap_handler, || {
+            catch_traps(trap_handler, stack_size, || {
                 mem::transmute::<*const VMFunctionBody, unsafe extern "C" fn(VMFunctionContext)>(
                     callee_address,
                 )(callee_vmctx)
@@ -410,7 +416,7 @@ impl Instance {
             .memories
             .get(memory_index)
             .unwrap_or_else(|| panic!("no memory for index {}", memory_index.index()));
-        mem.get_mut(self.context_mut()).grow(delta.into())
+        self.context_mut().grow_memory(mem, delta.into())
     }
 
     /// Grow imported memory by the specified amount of pages.
@@ -431,7 +437,7 @@ impl Instance {
     {
         let import = self.imported_memory(memory_index);
         let mem = import.handle;
-        mem.get_mut(self.context_mut()).grow(delta.into())
+        self.context_mut().grow_memory(mem, delta.into())
     }
 
     /// Returns the number of allocated wasm pages.
@@ -487,7 +493,7 @@ impl Instance {
             .tables
             .get(table_index)
             .unwrap_or_else(|| panic!("no table for index {}", table_index.index()));
-        table.get_mut(self.context_mut()).grow(delta, init_value)
+        self.context_mut().grow_table(table, delta, init_value)
     }
 
     /// Grow table by the specified amount of elements.
@@ -502,7 +508,7 @@ impl Instance {
     ) -> Option<u32> {
         let import = self.imported_table(table_index);
         let table = import.handle;
-        table.get_mut(self.context_mut()).grow(delta, init_value)
+        self.context_mut().grow_table(table, delta, init_value)
     }
 
     /// Get table element by index.
@@ -574,6 +580,214 @@ impl Instance {
         }
     }
 
+    /// Captures the state of the memories, globals and tables defined by
+    /// this instance.
+    pub(crate) fn snapshot(&self) -> Result<InstanceSnapshot, SerializeError> {
+        // Table elements and references in globals are stored as the
+        // function they refer to.
+        let mut function_indices = HashMap::new();
+        for (index, funcref) in self.funcrefs.iter() {
+            function_indices.insert(
+                funcref as *const VMCallerCheckedAnyfunc,
+                self.module.func_index(index),
+            );
+        }
+        for (index, funcref) in self.imported_funcrefs.iter() {
+            function_indices
+                .entry(funcref.as_ptr() as *const VMCallerCheckedAnyfunc)
+                .or_insert(index);
+        }
+
+        let memories = self
+            .memories
+            .values()
+            .map(|memory| unsafe {
+                let definition = memory.get(self.context()).vmmemory().as_ref();
+                slice::from_raw_parts(definition.base, definition.current_length).to_vec()
+            })
+            .collect();
+        let mut globals = PrimaryMap::with_capacity(self.globals.len());
+        for global in self.globals.values() {
+            let global = global.get(self.context());
+            let value = unsafe { global.vmglobal().as_ref().val };
+            let value = match global.ty().ty {
+                Type::FuncRef | Type::ExternRef if unsafe { value.funcref } == 0 => {
+                    GlobalSnapshot::Ref(None)
+                }
+                Type::FuncRef => function_indices
+                    .get(&(unsafe { value.funcref } as *const VMCallerCheckedAnyfunc))
+                    .map(|index| GlobalSnapshot::Ref(Some(*index)))
+                    .ok_or_else(|| {
+                        SerializeError::Generic(
+                            "a global refers to a function of another instance".to_string(),
+                        )
+                    })?,
+                Type::ExternRef => {
+                    return Err(SerializeError::Generic(
+                        "a global holds an external reference".to_string(),
+                    ))
+                }
+                _ => GlobalSnapshot::Value(unsafe { value.u128 }),
+            };
+            globals.push(value);
+        }
+        let mut tables = PrimaryMap::with_capacity(self.tables.len());
+        for table in self.tables.values() {
+            let table = table.get(self.context());
+            let elements = (0..table.size())
+                .map(|index| match table.get(index) {
+                    Some(TableElement::FuncRef(None)) | Some(TableElement::ExternRef(None)) => {
+                        Ok(None)
+                    }
+                    Some(TableElement::FuncRef(Some(funcref))) => function_indices
+                        .get(&(funcref.0.as_ptr() as *const VMCallerCheckedAnyfunc))
+                        .map(|index| Some(*index))
+                        .ok_or_else(|| {
+                            SerializeError::Generic(
+                                "a table refers to a function of another instance".to_string(),
+                            )
+                        }),
+                    _ => Err(SerializeError::Generic(
+                        "a table holds an external reference".to_string(),
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            tables.push(elements);
+        }
+
+        Ok(InstanceSnapshot {
+            memories,
+            globals,
+            tables,
+        })
+    }
+
+    /// Restores the state of the memories, globals and tables defined by
+    /// this instance from a snapshot.
+    ///
+    /// Memories and tables are grown to their size in the snapshot.
+    pub(crate) fn restore(&mut self, snapshot: &InstanceSnapshot) -> Result<(), DeserializeError> {
+        if snapshot.memories.len() != self.memories.len()
+            || snapshot.globals.len() != self.globals.len()
+            || snapshot.tables.len() != self.tables.len()
+        {
+            return Err(DeserializeError::Incompatible(
+                "the snapshot was taken from an instance of another module".to_string(),
+            ));
+        }
+
+        for (index, data) in snapshot.memories.iter() {
+            let memory = self.memories[index];
+            let current = unsafe {
+                memory
+                    .get(self.context())
+                    .vmmemory()
+                    .as_ref()
+                    .current_length
+            };
+            if data.len() < current || data.len() % WASM_PAGE_SIZE != 0 {
+                return Err(DeserializeError::Incompatible(format!(
+                    "memory {} doesn't fit the snapshot",
+                    index.index()
+                )));
+            }
+            if data.len() > current {
+                let delta = Pages(((data.len() - current) / WASM_PAGE_SIZE) as u32);
+                self.context_mut()
+                    .grow_memory(memory, delta)
+                    .map_err(|err| DeserializeError::Generic(err.to_string()))?;
+            }
+            unsafe {
+                let definition = memory.get(self.context()).vmmemory().as_ref();
+                ptr::copy_nonoverlapping(data.as_ptr(), definition.base, data.len());
+            }
+        }
+
+        // The interruption flag and the fuel belong to the store, which
+        // the snapshot doesn't capture.
+        let interrupt = interrupt_global(&self.module);
+        let fuel = fuel_global(&self.module);
+        for (index, value) in snapshot.globals.iter() {
+            if Some(index) == interrupt || Some(index) == fuel {
+                continue;
+            }
+            let global = self.globals[index].get(self.context());
+            let value = match (value, global.ty().ty) {
+                (GlobalSnapshot::Value(value), ty) if !ty.is_ref() => RawValue { u128: *value },
+                (GlobalSnapshot::Ref(None), Type::FuncRef) => RawValue { funcref: 0 },
+                (GlobalSnapshot::Ref(None), Type::ExternRef) => RawValue { externref: 0 },
+                (GlobalSnapshot::Ref(Some(function)), Type::FuncRef)
+                    if function.index() < self.module.functions.len() =>
+                {
+                    self.func_ref(*function).unwrap().into_raw()
+                }
+                _ => {
+                    return Err(DeserializeError::Incompatible(format!(
+                        "global {} doesn't fit the snapshot",
+                        index.index()
+                    )))
+                }
+            };
+            unsafe {
+                global.vmglobal().as_mut().val = value;
+            }
+        }
+
+        for (index, elements) in snapshot.tables.iter() {
+            let table = self.tables[index];
+            let current = table.get(self.context()).size() as usize;
+            let null = match table.get(self.context()).ty().ty {
+                Type::FuncRef => TableElement::FuncRef(None),
+                _ => TableElement::ExternRef(None),
+            };
+            if elements.len() < current {
+                return Err(DeserializeError::Incompatible(format!(
+                    "table {} doesn't fit the snapshot",
+                    index.index()
+                )));
+            }
+            if elements.len() > current {
+                let delta = (elements.len() - current) as u32;
+                self.context_mut()
+                    .grow_table(table, delta, null.clone())
+                    .ok_or_else(|| {
+                        DeserializeError::Generic(format!(
+                            "failed to grow table {} by `{}`",
+                            index.index(),
+                            delta
+                        ))
+                    })?;
+            }
+            for (i, element) in elements.iter().enumerate() {
+                let element = match (element, &null) {
+                    (None, _) => null.clone(),
+                    (Some(function), TableElement::FuncRef(_))
+                        if function.index() < self.module.functions.len() =>
+                    {
+                        TableElement::FuncRef(self.func_ref(*function))
+                    }
+                    _ => {
+                        return Err(DeserializeError::Incompatible(format!(
+                            "table {} doesn't fit the snapshot",
+                            index.index()
+                        )))
+                    }
+                };
+                table
+                    .get_mut(self.context_mut())
+                    .set(i as u32, element)
+                    .map_err(|_| {
+                        DeserializeError::Incompatible(format!(
+                            "table {} doesn't fit the snapshot",
+                            index.index()
+                        ))
+                    })?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// The `table.init` operation: initializes a portion of a table with a
     /// passive element.
     ///
@@ -692,6 +906,22 @@ impl Instance {
         unsafe { memory_copy(memory, dst, src, len) }
     }
 
+    /// Perform a `memory.copy` between two memories, each of which may be
+    /// locally defined or imported.
+    pub(crate) fn memory_copy_between(
+        &self,
+        dst_index: MemoryIndex,
+        src_index: MemoryIndex,
+        dst: u32,
+        src: u32,
+        len: u32,
+    ) -> Result<(), Trap> {
+        let dst_memory = self.get_memory(dst_index);
+        let src_memory = self.get_memory(src_index);
+        // The following memory copy is not synchronized and is not atomic:
+        unsafe { memory_copy_between(&dst_memory, &src_memory, dst, src, len) }
+    }
+
     /// Perform the `memory.fill` operation on a locally defined memory.
     ///
     /// # Errors
@@ -1056,6 +1286,7 @@ impl VMInstance {
                 passive_data,
                 funcrefs,
                 imported_funcrefs,
+                start_pending: true,
                 vmctx: VMContext {},
             };
 
@@ -1144,18 +1375,64 @@ impl VMInstance {
     pub unsafe fn finish_instantiation(
         &mut self,
         trap_handler: Option<*const TrapHandlerFn<'static>>,
+        stack_size: Option<usize>,
         data_initializers: &[DataInitializer<'_>],
     ) -> Result<(), Trap> {
-        let instance = self.instance_mut();
-
-        // Apply the initializers.
-        initialize_tables(instance)?;
-        initialize_memories(instance, data_initializers)?;
+        self.initialize(data_initializers)?;
 
         // The WebAssembly spec specifies that the start function is
         // invoked automatically at instantiation time.
-        instance.invoke_start_function(trap_handler)?;
-        Ok(())
+        self.invoke_start(trap_handler, stack_size)
+    }
+
+    /// Initializes the tables and memories of the instance, without
+    /// running its start function.
+    ///
+    /// # Safety
+    ///
+    /// Only safe to call immediately after instantiation.
+    pub unsafe fn initialize(
+        &mut self,
+        data_initializers: &[DataInitializer<'_>],
+    ) -> Result<(), Trap> {
+        self.initialize_with_images(data_initializers, &MemoryImages::default())
+    }
+
+    /// Initializes the tables and memories of the instance like
+    /// [`VMInstance::initialize`], mapping the memories that have an
+    /// image in `images` instead of copying their data segments.
+    ///
+    /// # Safety
+    ///
+    /// Only safe to call immediately after instantiation, with the images
+    /// built from `data_initializers`.
+    pub unsafe fn initialize_with_images(
+        &mut self,
+        data_initializers: &[DataInitializer<'_>],
+        images: &MemoryImages,
+    ) -> Result<(), Trap> {
+        let instance = self.instance_mut();
+        initialize_tables(instance)?;
+        initialize_memories(instance, data_initializers, images)
+    }
+
+    /// Runs the start function of the instance, if it has one and it
+    /// hasn't run yet.
+    ///
+    /// # Safety
+    ///
+    /// The instance must have been initialized with
+    /// [`VMInstance::initialize`].
+    pub unsafe fn invoke_start(
+        &mut self,
+        trap_handler: Option<*const TrapHandlerFn<'static>>,
+        stack_size: Option<usize>,
+    ) -> Result<(), Trap> {
+        let instance = self.instance_mut();
+        if !std::mem::replace(&mut instance.start_pending, false) {
+            return Ok(());
+        }
+        instance.invoke_start_function(trap_handler, stack_size)
     }
 
     /// Return a reference to the vmctx used by compiled wasm code.
@@ -1185,6 +1462,16 @@ impl VMInstance {
         self.instance().module_ref()
     }
 
+    /// Returns the handles of the memories defined by this instance.
+    pub(crate) fn memory_handles(&self) -> impl Iterator<Item = &InternalStoreHandle<VMMemory>> {
+        self.instance().memories.values()
+    }
+
+    /// Returns the handles of the globals defined by this instance.
+    pub(crate) fn global_handles(&self) -> impl Iterator<Item = &InternalStoreHandle<VMGlobal>> {
+        self.instance().globals.values()
+    }
+
     /// Lookup an export with the given name.
     pub fn lookup(&mut self, field: &str) -> Option<VMExtern> {
         let export = *self.module_ref().exports.get(field)?;
@@ -1324,6 +1611,22 @@ impl VMInstance {
     pub fn get_local_table(&mut self, index: LocalTableIndex) -> &mut VMTable {
         self.instance_mut().get_local_table(index)
     }
+
+    /// Captures the state of the memories, globals and tables defined by
+    /// this instance.
+    ///
+    /// Fails if a table holds an external reference or a function of
+    /// another instance, which can't be restored into a new instance.
+    pub fn snapshot(&self) -> Result<InstanceSnapshot, SerializeError> {
+        self.instance().snapshot()
+    }
+
+    /// Restores the state of the memories, globals and tables defined by
+    /// this instance from a snapshot taken from an instance of the same
+    /// module.
+    pub fn restore(&mut self, snapshot: &InstanceSnapshot) -> Result<(), DeserializeError> {
+        self.instance_mut().restore(snapshot)
+    }
 }
 
 /// Compute the offset for a memory data initializer.
@@ -1331,11 +1634,19 @@ fn get_memory_init_start(init: &DataInitializer<'_>, instance: &Instance) -> usi
     let mut start = init.location.offset;
 
     if let Some(base) = init.location.base {
-        let val = unsafe {
+        let global = unsafe {
             if let Some(def_index) = instance.module.local_global_index(base) {
-                instance.global(def_index).val.u32
+                instance.global(def_index)
             } else {
-                instance.imported_global(base).definition.as_ref().val.u32
+                instance.imported_global(base).definition.as_ref().clone()
+            }
+        };
+        // 64-bit memories are offset by an `i64` global.
+        let val = unsafe {
+            if instance.module.memories[init.location.memory_index].is_memory64() {
+                global.val.u64
+            } else {
+                u64::from(global.val.u32)
             }
         };
         start += usize::try_from(val).unwrap();
@@ -1439,8 +1750,24 @@ fn initialize_passive_elements(instance: &Instance) {
 fn initialize_memories(
     instance: &mut Instance,
     data_initializers: &[DataInitializer<'_>],
+    images: &MemoryImages,
 ) -> Result<(), Trap> {
+    // The memories initialized from an image don't need their data
+    // segments anymore.
+    let mut imaged = Vec::new();
+    for (index, memory) in instance.memories.iter() {
+        if let Some(image) = images.get(index) {
+            let memory = unsafe { memory.get(instance.context.as_ref().unwrap()) };
+            if unsafe { memory.initialize_with_image(image)? } {
+                imaged.push(instance.module.memory_index(index));
+            }
+        }
+    }
+
     for init in data_initializers {
+        if imaged.contains(&init.location.memory_index) {
+            continue;
+        }
         let memory = instance.get_vmmemory(init.location.memory_index);
 
         let start = get_memory_init_start(init, instance);
@@ -1461,7 +1788,14 @@ fn initialize_memories(
 
 fn initialize_globals(instance: &Instance) {
     let module = Arc::clone(&instance.module);
+    // The interruption flag and the fuel belong to the store, and keep
+    // their value across instantiations.
+    let interrupt = interrupt_global(&module);
+    let fuel = fuel_global(&module);
     for (index, initializer) in module.global_initializers.iter() {
+        if Some(index) == interrupt || Some(index) == fuel {
+            continue;
+        }
         unsafe {
             let to = instance.global_ptr(index).as_ptr();
             match initializer {
@@ -0,0 +1,158 @@
+//! Interruption of the WebAssembly code running in a store.
+//!
+//! Code compiled with the `Interrupt` middleware of `wasmer-middlewares`
+//! checks a flag at the start of every function and loop iteration, and
+//! traps when it is set. The flag is a global of the module, recorded in
+//! `ModuleInfo::interrupt_global`. When such a module is instantiated, the
+//! global is backed by the flag of the store instead of a value of its
+//! own, so that a single [`InterruptHandle`] stops every instance of the
+//! store.
+
+use crate::vmcontext::VMGlobalDefinition;
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+use wasmer_types::{LocalGlobalIndex, ModuleInfo};
+
+/// Returns the global of `module` holding the interruption flag, if the
+/// module has one.
+pub fn interrupt_global(module: &ModuleInfo) -> Option<LocalGlobalIndex> {
+    module
+        .interrupt_global
+        .and_then(|index| module.local_global_index(index))
+}
+
+struct InterruptFlag {
+    definition: NonNull<VMGlobalDefinition>,
+    // Owns the definition, unless it lives in memory provided by the
+    // embedder.
+    _owned: Option<Box<UnsafeCell<VMGlobalDefinition>>>,
+}
+
+// The flag is only accessed atomically.
+unsafe impl Send for InterruptFlag {}
+unsafe impl Sync for InterruptFlag {}
+
+/// A handle to interrupt the WebAssembly code running in a store, from any
+/// thread.
+///
+/// Setting the flag is a single atomic store: [`InterruptHandle::interrupt`]
+/// doesn't allocate or take locks, and is async-signal-safe. The flag stays
+/// set until [`InterruptHandle::reset`] is called, and every call into the
+/// instances of the store traps in the meantime.
+#[derive(Clone)]
+pub struct InterruptHandle(Arc<InterruptFlag>);
+
+impl InterruptHandle {
+    /// Creates a new handle, with a flag that isn't set.
+    pub fn new() -> Self {
+        let owned = Box::new(UnsafeCell::new(VMGlobalDefinition::new()));
+        Self(Arc::new(InterruptFlag {
+            definition: unsafe { NonNull::new_unchecked(owned.get()) },
+            _owned: Some(owned),
+        }))
+    }
+
+    /// Creates a handle whose flag lives at `definition`.
+    ///
+    /// The flag is the native-endian `i32` at the start of the definition.
+    /// Placing it in memory shared with other processes, such as a mapping
+    /// of a shared file, lets a supervisor interrupt the store from another
+    /// process by writing a non-zero value there.
+    ///
+    /// # Safety
+    ///
+    /// `definition` must be valid for reads and writes of a
+    /// `VMGlobalDefinition`, and aligned for it, for as long as the handle
+    /// or a store using it exists. The flag must only be written atomically.
+    pub unsafe fn from_raw(definition: NonNull<VMGlobalDefinition>) -> Self {
+        Self(Arc::new(InterruptFlag {
+            definition,
+            _owned: None,
+        }))
+    }
+
+    /// Returns the definition of the flag, used by the generated code.
+    pub fn vmglobal(&self) -> NonNull<VMGlobalDefinition> {
+        self.0.definition
+    }
+
+    fn flag(&self) -> &AtomicI32 {
+        // The guest only reads the flag, with aligned 32-bit loads, which
+        // makes it safe to access it atomically.
+        unsafe { &*(std::ptr::addr_of!((*self.0.definition.as_ptr()).val) as *const AtomicI32) }
+    }
+
+    /// Requests the running instances to trap at their next check.
+    pub fn interrupt(&self) {
+        self.flag().store(1, Ordering::Relaxed);
+    }
+
+    /// Whether an interruption has been requested and not reset yet.
+    pub fn is_interrupted(&self) -> bool {
+        self.flag().load(Ordering::Relaxed) != 0
+    }
+
+    /// Clears the interruption flag, so that the instances can be called
+    /// again.
+    pub fn reset(&self) {
+        self.flag().store(0, Ordering::Relaxed);
+    }
+}
+
+impl Default for InterruptHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for InterruptHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InterruptHandle")
+            .field("interrupted", &self.is_interrupted())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handles_share_the_flag() {
+        let handle = InterruptHandle::new();
+        let clone = handle.clone();
+        assert!(!clone.is_interrupted());
+        handle.interrupt();
+        assert!(clone.is_interrupted());
+        clone.reset();
+        assert!(!handle.is_interrupted());
+
+        let mut definition = VMGlobalDefinition::new();
+        let raw = unsafe { InterruptHandle::from_raw(NonNull::from(&mut definition)) };
+        raw.interrupt();
+        assert_eq!(unsafe { definition.val.i32 }, 1);
+    }
+    #[test]
+    fn only_the_recorded_global_is_the_flag() {
+        use wasmer_types::{ExportIndex, GlobalType, Mutability, Type};
+
+        let mut module = ModuleInfo::new();
+        let exported = module
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+        module.exports.insert(
+            "wasmer_interrupt_requested".to_string(),
+            ExportIndex::Global(exported),
+        );
+        assert_eq!(interrupt_global(&module), None);
+
+        let flag = module
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+        module.interrupt_global = Some(flag);
+        assert_eq!(interrupt_global(&module), module.local_global_index(flag));
+    }
+}
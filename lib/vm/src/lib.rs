@@ -20,13 +20,19 @@
     )
 )]
 
+mod coredump;
 mod export;
 mod extern_ref;
+mod fuel;
 mod function_env;
 mod global;
 mod imports;
 mod instance;
+mod interrupt;
 mod memory;
+mod memory_image;
+#[cfg(unix)]
+mod memory_pool;
 mod mmap;
 mod probestack;
 mod sig_registry;
@@ -40,21 +46,29 @@ pub mod libcalls;
 
 use std::ptr::NonNull;
 
+pub use crate::coredump::write_coredump;
 pub use crate::export::*;
 pub use crate::extern_ref::{VMExternObj, VMExternRef};
+pub use crate::fuel::fuel_global;
 pub use crate::function_env::VMFunctionEnvironment;
 pub use crate::global::*;
 pub use crate::imports::Imports;
 #[allow(deprecated)]
 pub use crate::instance::{InstanceAllocator, InstanceHandle, VMInstance};
+pub use crate::interrupt::{interrupt_global, InterruptHandle};
 pub use crate::memory::{
     initialize_memory_with_data, LinearMemory, NotifyLocation, VMMemory, VMOwnedMemory,
     VMSharedMemory,
 };
+pub use crate::memory_image::{MemoryImage, MemoryImages};
+#[cfg(unix)]
+pub use crate::memory_pool::{MemoryPool, PooledMemory};
 pub use crate::mmap::Mmap;
 pub use crate::probestack::PROBESTACK;
 pub use crate::sig_registry::SignatureRegistry;
-pub use crate::store::{InternalStoreHandle, MaybeInstanceOwned, StoreHandle, StoreObjects};
+pub use crate::store::{
+    InternalStoreHandle, MaybeInstanceOwned, ResourceLimiter, StoreHandle, StoreObjects,
+};
 pub use crate::table::{TableElement, VMTable};
 #[doc(hidden)]
 pub use crate::threadconditions::ThreadConditions;
@@ -5,6 +5,7 @@
 //!
 //! `Memory` is to WebAssembly linear memories what `Table` is to WebAssembly tables.
 
+use crate::memory_image::MemoryImage;
 use crate::threadconditions::ThreadConditions;
 pub use crate::threadconditions::{NotifyLocation, WaiterError};
 use crate::trap::Trap;
@@ -50,7 +51,9 @@ impl WasmMmap {
 
         let new_pages = self
             .size
-            .checked_add(delta)
+            .0
+            .checked_add(delta.0)
+            .map(Pages)
             .ok_or(MemoryError::CouldNotGrow {
                 current: self.size,
                 attempted_delta: delta,
@@ -69,7 +72,7 @@ impl WasmMmap {
         // Wasm linear memories are never allowed to grow beyond what is
         // indexable. If the memory has no maximum, enforce the greatest
         // limit here.
-        if new_pages >= Pages::max_value() {
+        if new_pages >= conf.memory.max_pages() {
             // Linear memory size would exceed the index range.
             return Err(MemoryError::CouldNotGrow {
                 current: self.size,
@@ -211,18 +214,19 @@ impl VMOwnedMemory {
         style: &MemoryStyle,
         vm_memory_location: Option<NonNull<VMMemoryDefinition>>,
     ) -> Result<Self, MemoryError> {
-        if memory.minimum > Pages::max_value() {
+        if memory.minimum > memory.max_pages() {
             return Err(MemoryError::MinimumMemoryTooLarge {
                 min_requested: memory.minimum,
-                max_allowed: Pages::max_value(),
+                max_allowed: memory.max_pages(),
             });
         }
-        // `maximum` cannot be set to more than `65536` pages.
+        // `maximum` cannot be set to more than `65536` pages (or
+        // `WASM64_MAX_PAGES` for 64-bit memories).
         if let Some(max) = memory.maximum {
-            if max > Pages::max_value() {
+            if max > memory.max_pages() {
                 return Err(MemoryError::MaximumMemoryTooLarge {
                     max_requested: max,
-                    max_allowed: Pages::max_value(),
+                    max_allowed: memory.max_pages(),
                 });
             }
             if max < memory.minimum {
@@ -336,6 +340,18 @@ impl LinearMemory for VMOwnedMemory {
         None
     }
 
+    /// Maps the image copy-on-write over the memory.
+    unsafe fn initialize_with_image(&self, image: &MemoryImage) -> Result<bool, Trap> {
+        let memory = self.vmmemory().as_ref();
+        if image.offset() + image.len() > memory.current_length {
+            return Ok(false);
+        }
+        image
+            .map_at(memory.base)
+            .map_err(|err| Trap::user(Box::new(err)))?;
+        Ok(true)
+    }
+
     /// Copies this memory to a new memory
     fn duplicate(&mut self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError> {
         let forked = Self::duplicate(self)?;
@@ -515,6 +531,11 @@ impl LinearMemory for VMMemory {
         self.0.initialize_with_data(start, data)
     }
 
+    /// Initialize memory with an image
+    unsafe fn initialize_with_image(&self, image: &MemoryImage) -> Result<bool, Trap> {
+        self.0.initialize_with_image(image)
+    }
+
     /// Copies this memory to a new memory
     fn duplicate(&mut self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError> {
         self.0.duplicate()
@@ -649,6 +670,21 @@ where
         initialize_memory_with_data(memory, start, data)
     }
 
+    #[doc(hidden)]
+    /// Initializes the memory by mapping `image` copy-on-write over it,
+    /// instead of copying the data segments it was built from.
+    ///
+    /// Returns `false` when the memory doesn't support images, in which
+    /// case the data segments are copied with
+    /// [`LinearMemory::initialize_with_data`].
+    ///
+    /// # Safety
+    /// This function is unsafe because WebAssembly specification requires that data is always set at initialization time.
+    /// It should be the implementors responsibility to make sure this respects the spec
+    unsafe fn initialize_with_image(&self, _image: &MemoryImage) -> Result<bool, Trap> {
+        Ok(false)
+    }
+
     /// Copies this memory to a new memory
     fn duplicate(&mut self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError>;
 
@@ -0,0 +1,331 @@
+//! Copy-on-write images of the initial contents of memories.
+//!
+//! Copying the data segments of a module into every new instance costs
+//! time proportional to their size, and every instance keeps a private
+//! copy of them even when it never writes to them. On Linux, the data
+//! segments of a memory are instead written once into a sealed `memfd`,
+//! the [`MemoryImage`] of the memory, which every instance maps
+//! copy-on-write over the start of its memory. The pages of the image
+//! are then shared by the instances until they are written to.
+//!
+//! A memory only gets an image when its initialization can't trap: all
+//! its segments must have constant offsets and fit in the minimum size of
+//! the memory. The other memories, and the memories which don't support
+//! images, are initialized by copying the segments.
+
+use std::fs::File;
+use std::io;
+use wasmer_types::entity::{BoxedSlice, PrimaryMap};
+use wasmer_types::{DataInitializer, LocalMemoryIndex, ModuleInfo};
+
+/// Images spanning more than this size must not be mostly empty.
+const SPARSE_IMAGE_SIZE: usize = 1 << 20;
+
+/// The initial contents of a memory, shared by the instances of a module.
+#[derive(Debug)]
+pub struct MemoryImage {
+    // The file holding the contents, mapped at `offset` in the memory.
+    file: File,
+    offset: usize,
+    len: usize,
+}
+
+impl MemoryImage {
+    /// Writes `segments`, pairs of an offset in the memory and their
+    /// data, to a new image.
+    ///
+    /// The image covers the pages of the memory touched by the
+    /// segments. It returns `None` when the segments are empty, or when
+    /// they are so sparse that copying them is cheaper than mapping their
+    /// pages.
+    pub fn new(segments: &[(usize, &[u8])]) -> io::Result<Option<Self>> {
+        let page_size = region::page::size();
+        let data_len = segments.iter().map(|(_, data)| data.len()).sum::<usize>();
+        if data_len == 0 {
+            return Ok(None);
+        }
+        let start = segments
+            .iter()
+            .filter(|(_, data)| !data.is_empty())
+            .map(|(offset, _)| *offset)
+            .min()
+            .unwrap()
+            & !(page_size - 1);
+        let end = segments
+            .iter()
+            .map(|(offset, data)| offset + data.len())
+            .max()
+            .unwrap();
+        let end = (end + page_size - 1) & !(page_size - 1);
+        let len = end - start;
+        if len > SPARSE_IMAGE_SIZE && len > 2 * data_len {
+            return Ok(None);
+        }
+
+        let file = create_image_file(len)?;
+        write_segments(&file, start, segments)?;
+        seal_image_file(&file)?;
+        Ok(Some(Self {
+            file,
+            offset: start,
+            len,
+        }))
+    }
+
+    /// Returns the offset of the image in the memory.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the size of the image, a multiple of the page size.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the image is empty, which it never is.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Maps the image copy-on-write over the memory starting at `base`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be page-aligned, and the `offset + len` bytes following
+    /// it must be accessible pages of an anonymous private mapping, whose
+    /// contents are replaced.
+    #[cfg(target_os = "linux")]
+    pub unsafe fn map_at(&self, base: *mut u8) -> io::Result<()> {
+        use std::os::unix::io::AsRawFd;
+
+        let ptr = libc::mmap(
+            base.add(self.offset) as *mut libc::c_void,
+            self.len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_FIXED,
+            self.file.as_raw_fd(),
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Maps the image copy-on-write over the memory starting at `base`.
+    ///
+    /// # Safety
+    ///
+    /// Images are only created on Linux.
+    #[cfg(not(target_os = "linux"))]
+    pub unsafe fn map_at(&self, _base: *mut u8) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn create_image_file(len: usize) -> io::Result<File> {
+    use std::os::unix::io::FromRawFd;
+
+    let fd = unsafe {
+        libc::memfd_create(
+            b"wasm-memory-image\0".as_ptr() as *const libc::c_char,
+            libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let file = unsafe { File::from_raw_fd(fd) };
+    file.set_len(len as u64)?;
+    Ok(file)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn create_image_file(_len: usize) -> io::Result<File> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(unix)]
+fn write_segments(file: &File, start: usize, segments: &[(usize, &[u8])]) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+
+    // Later segments overwrite the earlier ones, as when they are copied.
+    for (offset, data) in segments {
+        file.write_all_at(data, (offset - start) as u64)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_segments(_file: &File, _start: usize, _segments: &[(usize, &[u8])]) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Seals the image, so that its contents can't change under the
+/// instances mapping it.
+#[cfg(target_os = "linux")]
+fn seal_image_file(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let seals = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE | libc::F_SEAL_SEAL;
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_ADD_SEALS, seals) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn seal_image_file(_file: &File) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// The images of the memories defined by a module.
+#[derive(Debug)]
+pub struct MemoryImages(BoxedSlice<LocalMemoryIndex, Option<MemoryImage>>);
+
+impl MemoryImages {
+    /// Builds the images of the memories defined by `module` from its
+    /// active data segments.
+    ///
+    /// Memories whose initialization could trap, shared memories, and
+    /// memories whose image couldn't be created get no image.
+    pub fn new(module: &ModuleInfo, data_initializers: &[DataInitializer<'_>]) -> Self {
+        let images = module
+            .memories
+            .iter()
+            .skip(module.num_imported_memories)
+            .map(|(index, memory)| {
+                if !cfg!(target_os = "linux") || memory.shared {
+                    return None;
+                }
+                let minimum = memory.minimum.bytes().0;
+                let mut segments = Vec::new();
+                for init in data_initializers
+                    .iter()
+                    .filter(|init| init.location.memory_index == index)
+                {
+                    let offset = init.location.offset;
+                    if init.location.base.is_some()
+                        || !matches!(offset.checked_add(init.data.len()), Some(end) if end <= minimum)
+                    {
+                        return None;
+                    }
+                    segments.push((offset, init.data));
+                }
+                MemoryImage::new(&segments).ok().flatten()
+            })
+            .collect::<PrimaryMap<LocalMemoryIndex, _>>();
+        Self(images.into_boxed_slice())
+    }
+
+    /// Returns the image of a memory defined by the module, if it has one.
+    pub fn get(&self, index: LocalMemoryIndex) -> Option<&MemoryImage> {
+        self.0.get(index).and_then(Option::as_ref)
+    }
+
+    /// Returns the number of memories with an image.
+    pub fn len(&self) -> usize {
+        self.0.values().filter(|image| image.is_some()).count()
+    }
+
+    /// Returns whether no memory has an image.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for MemoryImages {
+    fn default() -> Self {
+        Self(PrimaryMap::new().into_boxed_slice())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::{LinearMemory, VMOwnedMemory};
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::{DataInitializerLocation, MemoryIndex, MemoryStyle, MemoryType, Pages};
+
+    fn init(offset: usize, base: bool, data: &[u8]) -> DataInitializer<'_> {
+        DataInitializer {
+            location: DataInitializerLocation {
+                memory_index: MemoryIndex::new(0),
+                base: if base {
+                    Some(wasmer_types::GlobalIndex::new(0))
+                } else {
+                    None
+                },
+                offset,
+            },
+            data,
+        }
+    }
+
+    fn module(minimum: u32) -> ModuleInfo {
+        let mut module = ModuleInfo::new();
+        module.memories.push(MemoryType::new(minimum, None, false));
+        module
+    }
+
+    #[test]
+    fn images_cover_the_pages_of_the_segments() {
+        let page_size = region::page::size();
+        let image = MemoryImage::new(&[(page_size + 3, b"abc"), (3 * page_size - 1, b"de")])
+            .unwrap()
+            .unwrap();
+        assert_eq!(image.offset(), page_size);
+        assert_eq!(image.len(), 3 * page_size);
+
+        assert!(MemoryImage::new(&[(12, b"")]).unwrap().is_none());
+        // A few bytes at both ends of a large memory aren't worth an image.
+        let sparse = MemoryImage::new(&[(0, b"a"), (16 << 20, b"b")]).unwrap();
+        assert!(sparse.is_none());
+    }
+
+    #[test]
+    fn only_memories_that_cant_trap_get_an_image() {
+        assert_eq!(
+            MemoryImages::new(&module(1), &[init(16, false, b"hello")]).len(),
+            1
+        );
+        // The segment doesn't fit in the minimum size.
+        assert!(MemoryImages::new(&module(1), &[init(0xffff, false, b"hello")]).is_empty());
+        // The offset depends on an imported global.
+        assert!(MemoryImages::new(&module(1), &[init(16, true, b"hello")]).is_empty());
+        assert!(MemoryImages::new(&module(1), &[]).is_empty());
+    }
+
+    #[test]
+    fn instances_share_the_image_copy_on_write() {
+        let module = module(1);
+        let images = MemoryImages::new(
+            &module,
+            &[init(16, false, b"hello"), init(18, false, b"LL")],
+        );
+        let image = images.get(LocalMemoryIndex::new(0)).unwrap();
+        let ty = MemoryType::new(1, None, false);
+        let style = MemoryStyle::Static {
+            bound: Pages(16),
+            offset_guard_size: 0,
+        };
+
+        let memory = |image| unsafe {
+            let memory = VMOwnedMemory::new(&ty, &style).unwrap();
+            assert!(memory.initialize_with_image(image).unwrap());
+            memory
+        };
+        let read = |memory: &VMOwnedMemory, start: usize, len: usize| unsafe {
+            let definition = memory.vmmemory().as_ref();
+            std::slice::from_raw_parts(definition.base.add(start), len).to_vec()
+        };
+
+        let first = memory(image);
+        let second = memory(image);
+        assert_eq!(read(&first, 15, 7), b"\0heLLo\0");
+        unsafe { *first.vmmemory().as_ref().base.add(16) = b'j' };
+        assert_eq!(read(&first, 16, 5), b"jeLLo");
+        assert_eq!(read(&second, 16, 5), b"heLLo");
+    }
+}
@@ -0,0 +1,412 @@
+//! A pool of linear memories reused across instantiations.
+//!
+//! Creating a memory maps its whole reservation, guard pages included,
+//! and dropping it unmaps it again. When instances are created and
+//! dropped at a high rate, these system calls and the TLB flushes of
+//! `munmap` dominate the instantiation. A [`MemoryPool`] reserves a fixed
+//! number of slots once, and hands them out as [`PooledMemory`]s. A
+//! pooled memory grows in place by making more pages of its slot
+//! accessible. When it is dropped, the pages it touched are discarded
+//! and protected again, and the slot goes back to the pool, zeroed.
+
+use crate::mmap::Mmap;
+use crate::store::MaybeInstanceOwned;
+use crate::vmcontext::VMMemoryDefinition;
+use crate::{LinearMemory, VMOwnedMemory};
+use std::cell::UnsafeCell;
+use std::io;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+use wasmer_types::{MemoryError, MemoryStyle, MemoryType, Pages};
+
+/// A fixed number of memory slots, reserved once and reused by the
+/// memories allocated from the pool.
+///
+/// Every slot can hold a memory of up to [`MemoryPool::slot_pages`]
+/// pages, followed by [`MemoryPool::offset_guard_size`] bytes of guard
+/// pages.
+#[derive(Debug)]
+pub struct MemoryPool {
+    // The reservation of all the slots, inaccessible until handed out.
+    reservation: Mmap,
+    slot_pages: Pages,
+    offset_guard_size: usize,
+    slot_size: usize,
+    free: Mutex<Vec<usize>>,
+}
+
+impl MemoryPool {
+    /// Reserves `slots` slots of `slot_pages` pages, each followed by
+    /// `offset_guard_size` bytes of guard pages.
+    ///
+    /// Nothing is committed until the slots are used, but the whole
+    /// reservation counts towards the address space of the process.
+    pub fn new(
+        slots: usize,
+        slot_pages: Pages,
+        offset_guard_size: u64,
+    ) -> Result<Arc<Self>, MemoryError> {
+        let page_size = region::page::size();
+        let offset_guard_size = (offset_guard_size as usize + page_size - 1) & !(page_size - 1);
+        let slot_size = slot_pages
+            .bytes()
+            .0
+            .checked_add(offset_guard_size)
+            .ok_or_else(|| MemoryError::Generic("the slots of the pool are too large".into()))?;
+        let reservation_size = slot_size
+            .checked_mul(slots)
+            .ok_or_else(|| MemoryError::Generic("the pool is too large".into()))?;
+        let reservation =
+            Mmap::accessible_reserved(0, reservation_size).map_err(MemoryError::Region)?;
+        Ok(Arc::new(Self {
+            reservation,
+            slot_pages,
+            offset_guard_size,
+            slot_size,
+            free: Mutex::new((0..slots).rev().collect()),
+        }))
+    }
+
+    /// The maximum number of pages of the memories of the pool.
+    pub fn slot_pages(&self) -> Pages {
+        self.slot_pages
+    }
+
+    /// The size of the guard pages following every slot, in bytes.
+    pub fn offset_guard_size(&self) -> usize {
+        self.offset_guard_size
+    }
+
+    /// The number of slots that aren't in use.
+    pub fn available(&self) -> usize {
+        self.free.lock().unwrap().len()
+    }
+
+    /// Whether a memory of type `ty` with the given style fits in a slot.
+    ///
+    /// Shared memories can't be pooled.
+    pub fn fits(&self, ty: &MemoryType, style: &MemoryStyle) -> bool {
+        !ty.shared
+            && ty.minimum <= Self::capacity(self.slot_pages, style)
+            && style.offset_guard_size() as usize <= self.offset_guard_size
+            && match style {
+                MemoryStyle::Static { bound, .. } => *bound <= self.slot_pages,
+                MemoryStyle::Dynamic { .. } => true,
+            }
+    }
+
+    // Code compiled for a static memory doesn't check the accesses below
+    // its bound, which must stay mapped: the memory can't grow past it.
+    fn capacity(slot_pages: Pages, style: &MemoryStyle) -> Pages {
+        match style {
+            MemoryStyle::Static { bound, .. } => std::cmp::min(*bound, slot_pages),
+            MemoryStyle::Dynamic { .. } => slot_pages,
+        }
+    }
+
+    /// Allocates a memory from a free slot of the pool.
+    ///
+    /// The definition of the memory is owned by the memory itself, or
+    /// stored at `vm_memory_location` when it is given.
+    ///
+    /// # Safety
+    /// - `vm_memory_location`, if given, must point to a valid location in
+    ///   VM memory.
+    pub unsafe fn allocate(
+        self: &Arc<Self>,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_memory_location: Option<NonNull<VMMemoryDefinition>>,
+    ) -> Result<PooledMemory, MemoryError> {
+        if let Some(max) = ty.maximum {
+            if max < ty.minimum {
+                return Err(MemoryError::InvalidMemory {
+                    reason: format!(
+                        "the maximum ({} pages) is less than the minimum ({} pages)",
+                        max.0, ty.minimum.0
+                    ),
+                });
+            }
+        }
+        if !self.fits(ty, style) {
+            return Err(MemoryError::Generic(
+                "the memory doesn't fit in a slot of the pool".to_string(),
+            ));
+        }
+        let slot = self
+            .free
+            .lock()
+            .unwrap()
+            .pop()
+            .ok_or_else(|| MemoryError::Generic("the memory pool is exhausted".to_string()))?;
+
+        let base = self.reservation.as_ptr().add(slot * self.slot_size) as *mut u8;
+        let length = ty.minimum.bytes().0;
+        if length > 0 {
+            if let Err(err) = region::protect(base, length, region::Protection::READ_WRITE) {
+                self.release(slot, 0);
+                return Err(MemoryError::Region(err.to_string()));
+            }
+        }
+        let definition = VMMemoryDefinition {
+            base,
+            current_length: length,
+        };
+        let vm_memory_definition = match vm_memory_location {
+            Some(mut location) => {
+                *location.as_mut() = definition;
+                MaybeInstanceOwned::Instance(location)
+            }
+            None => MaybeInstanceOwned::Host(Box::new(UnsafeCell::new(definition))),
+        };
+
+        Ok(PooledMemory {
+            pool: self.clone(),
+            slot,
+            capacity: Self::capacity(self.slot_pages, style),
+            ty: *ty,
+            style: *style,
+            vm_memory_definition,
+        })
+    }
+
+    /// Discards the first `length` bytes of a slot, protects them again
+    /// and puts the slot back in the pool.
+    fn release(&self, slot: usize, length: usize) {
+        let base = unsafe { self.reservation.as_ptr().add(slot * self.slot_size) as *mut u8 };
+        if length > 0 && unsafe { reset(base, length) }.is_err() {
+            // The slot may still hold the data of the previous memory: it
+            // is leaked rather than handed out again.
+            return;
+        }
+        self.free.lock().unwrap().push(slot);
+    }
+}
+
+/// Zeroes the pages at `base` and makes them inaccessible.
+#[cfg(target_os = "linux")]
+unsafe fn reset(base: *mut u8, length: usize) -> io::Result<()> {
+    // Private anonymous pages read as zeros after being discarded.
+    if libc::madvise(base as _, length, libc::MADV_DONTNEED) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if libc::mprotect(base as _, length, libc::PROT_NONE) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Zeroes the pages at `base` and makes them inaccessible.
+#[cfg(not(target_os = "linux"))]
+unsafe fn reset(base: *mut u8, length: usize) -> io::Result<()> {
+    // `MADV_DONTNEED` doesn't zero the pages everywhere: map fresh ones
+    // over them instead.
+    let ptr = libc::mmap(
+        base as _,
+        length,
+        libc::PROT_NONE,
+        libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_FIXED,
+        -1,
+        0,
+    );
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// A linear memory allocated from a [`MemoryPool`].
+///
+/// The memory never moves, and can grow up to the size of its slot, or
+/// up to the bound of its style if it is static. Its slot goes back to
+/// the pool when it is dropped.
+#[derive(Debug)]
+pub struct PooledMemory {
+    pool: Arc<MemoryPool>,
+    slot: usize,
+    capacity: Pages,
+    ty: MemoryType,
+    style: MemoryStyle,
+    vm_memory_definition: MaybeInstanceOwned<VMMemoryDefinition>,
+}
+
+unsafe impl Send for PooledMemory {}
+unsafe impl Sync for PooledMemory {}
+
+impl PooledMemory {
+    fn definition(&self) -> &VMMemoryDefinition {
+        unsafe { self.vm_memory_definition.as_ptr().as_ref() }
+    }
+}
+
+impl LinearMemory for PooledMemory {
+    fn ty(&self) -> MemoryType {
+        let mut ty = self.ty;
+        ty.minimum = self.size();
+        ty
+    }
+
+    fn size(&self) -> Pages {
+        Pages((self.definition().current_length / wasmer_types::WASM_PAGE_SIZE) as u32)
+    }
+
+    fn style(&self) -> MemoryStyle {
+        self.style
+    }
+
+    fn grow(&mut self, delta: Pages) -> Result<Pages, MemoryError> {
+        let current = self.size();
+        if delta.0 == 0 {
+            return Ok(current);
+        }
+        let error = MemoryError::CouldNotGrow {
+            current,
+            attempted_delta: delta,
+        };
+        let new_pages = current
+            .0
+            .checked_add(delta.0)
+            .map(Pages)
+            .ok_or(error.clone())?;
+        if new_pages > self.capacity
+            || new_pages >= self.ty.max_pages()
+            || matches!(self.ty.maximum, Some(maximum) if new_pages > maximum)
+        {
+            return Err(error);
+        }
+
+        let definition = self.definition();
+        let start = current.bytes().0;
+        let length = delta.bytes().0;
+        unsafe {
+            region::protect(
+                definition.base.add(start),
+                length,
+                region::Protection::READ_WRITE,
+            )
+            .map_err(|err| MemoryError::Region(err.to_string()))?;
+            self.vm_memory_definition.as_ptr().as_mut().current_length = new_pages.bytes().0;
+        }
+        Ok(current)
+    }
+
+    fn vmmemory(&self) -> NonNull<VMMemoryDefinition> {
+        self.vm_memory_definition.as_ptr()
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn LinearMemory + 'static>> {
+        None
+    }
+
+    /// Copies this memory to a new memory, outside of the pool.
+    fn duplicate(&mut self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError> {
+        let copy = VMOwnedMemory::new(&self.ty(), &self.style)?;
+        let definition = self.definition();
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                definition.base,
+                copy.vmmemory().as_ref().base,
+                definition.current_length,
+            );
+        }
+        Ok(Box::new(copy))
+    }
+}
+
+impl Drop for PooledMemory {
+    fn drop(&mut self) {
+        let length = self.definition().current_length;
+        self.pool.release(self.slot, length);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::slice;
+
+    fn write_and_read(memory: &PooledMemory, value: u8) -> u8 {
+        let definition = memory.definition();
+        let bytes =
+            unsafe { slice::from_raw_parts_mut(definition.base, definition.current_length) };
+        let previous = bytes[bytes.len() - 1];
+        bytes[bytes.len() - 1] = value;
+        previous
+    }
+
+    #[test]
+    fn slots_are_reused_zeroed() {
+        let pool = MemoryPool::new(1, Pages(4), 0).unwrap();
+        let ty = MemoryType::new(1, None, false);
+        let style = MemoryStyle::Dynamic {
+            offset_guard_size: 0,
+        };
+
+        let mut memory = unsafe { pool.allocate(&ty, &style, None) }.unwrap();
+        assert_eq!(pool.available(), 0);
+        assert_eq!(memory.grow(Pages(1)).unwrap(), Pages(1));
+        assert_eq!(write_and_read(&memory, 42), 0);
+        let base = memory.definition().base;
+
+        // The pool is exhausted until the memory is dropped.
+        let err = unsafe { pool.allocate(&ty, &style, None) }.unwrap_err();
+        assert_eq!(
+            err,
+            MemoryError::Generic("the memory pool is exhausted".to_string())
+        );
+        drop(memory);
+        assert_eq!(pool.available(), 1);
+
+        let mut memory = unsafe { pool.allocate(&ty, &style, None) }.unwrap();
+        assert_eq!(memory.definition().base, base);
+        assert_eq!(memory.size(), Pages(1));
+        memory.grow(Pages(1)).unwrap();
+        assert_eq!(write_and_read(&memory, 1), 0);
+    }
+
+    #[test]
+    fn memories_grow_within_their_slot() {
+        let pool = MemoryPool::new(2, Pages(4), 0).unwrap();
+        let dynamic = MemoryStyle::Dynamic {
+            offset_guard_size: 0,
+        };
+        let mut memory =
+            unsafe { pool.allocate(&MemoryType::new(1, None, false), &dynamic, None) }.unwrap();
+        assert_eq!(memory.grow(Pages(3)).unwrap(), Pages(1));
+        memory.grow(Pages(1)).unwrap_err();
+        assert_eq!(memory.ty().minimum, Pages(4));
+
+        // Static memories stop at their bound.
+        let bounded = MemoryStyle::Static {
+            bound: Pages(2),
+            offset_guard_size: 0,
+        };
+        let mut memory =
+            unsafe { pool.allocate(&MemoryType::new(1, None, false), &bounded, None) }.unwrap();
+        memory.grow(Pages(1)).unwrap();
+        memory.grow(Pages(1)).unwrap_err();
+
+        let duplicate = memory.duplicate().unwrap();
+        assert_eq!(duplicate.size(), Pages(2));
+    }
+
+    #[test]
+    fn memories_that_dont_fit_are_rejected() {
+        let pool = MemoryPool::new(1, Pages(4), 0x1000).unwrap();
+        let dynamic = MemoryStyle::Dynamic {
+            offset_guard_size: 0x1000,
+        };
+        assert!(pool.fits(&MemoryType::new(4, None, false), &dynamic));
+        assert!(!pool.fits(&MemoryType::new(5, None, false), &dynamic));
+        assert!(!pool.fits(&MemoryType::new(1, Some(4), true), &dynamic));
+        let large_guard = MemoryStyle::Dynamic {
+            offset_guard_size: 0x2000,
+        };
+        assert!(!pool.fits(&MemoryType::new(1, None, false), &large_guard));
+        let large_bound = MemoryStyle::Static {
+            bound: Pages(8),
+            offset_guard_size: 0,
+        };
+        assert!(!pool.fits(&MemoryType::new(1, None, false), &large_bound));
+    }
+}
@@ -1,9 +1,37 @@
+use crate::fuel::Fuel;
 use crate::{
-    VMExternObj, VMFunction, VMFunctionEnvironment, VMGlobal, VMInstance, VMMemory, VMTable,
+    InterruptHandle, LinearMemory, TableElement, VMExternObj, VMFunction, VMFunctionEnvironment,
+    VMGlobal, VMInstance, VMMemory, VMTable,
 };
 use core::slice::Iter;
 use std::{cell::UnsafeCell, fmt, marker::PhantomData, num::NonZeroUsize, ptr::NonNull};
-use wasmer_types::StoreId;
+use wasmer_types::{GlobalType, MemoryError, Pages, StoreId};
+
+/// Decides whether the memories and tables of a store may grow.
+///
+/// The limiter of a store is consulted before every growth of one of its
+/// memories or tables, whether it comes from `memory.grow`, `table.grow`
+/// or the host. Growing by zero doesn't consult it. A denied growth fails
+/// like a growth past the maximum: `memory.grow` and `table.grow` return
+/// -1 to the guest.
+pub trait ResourceLimiter: fmt::Debug + Send {
+    /// Whether a memory of `current` pages may grow to `desired` pages.
+    ///
+    /// `maximum` is the maximum of the memory type, if it has one.
+    fn memory_growing(&mut self, current: Pages, desired: Pages, maximum: Option<Pages>) -> bool {
+        let _ = (current, desired, maximum);
+        true
+    }
+
+    /// Whether a table of `current` elements may grow to `desired`
+    /// elements.
+    ///
+    /// `maximum` is the maximum of the table type, if it has one.
+    fn table_growing(&mut self, current: u32, desired: u32, maximum: Option<u32>) -> bool {
+        let _ = (current, desired, maximum);
+        true
+    }
+}
 
 /// Trait to represent an object managed by a context. This is implemented on
 /// the VM types managed by the context.
@@ -46,6 +74,9 @@ pub struct StoreObjects {
     instances: Vec<VMInstance>,
     extern_objs: Vec<VMExternObj>,
     function_environments: Vec<VMFunctionEnvironment>,
+    limiter: Option<Box<dyn ResourceLimiter>>,
+    interrupt: Option<InterruptHandle>,
+    fuel: Fuel,
 }
 
 impl StoreObjects {
@@ -59,6 +90,90 @@ impl StoreObjects {
         self.id = id;
     }
 
+    /// Sets the limiter consulted before the memories and tables of this
+    /// store grow.
+    pub fn set_resource_limiter(&mut self, limiter: Option<Box<dyn ResourceLimiter>>) {
+        self.limiter = limiter;
+    }
+
+    /// Returns the handle interrupting the instances of this store that
+    /// were compiled with the interrupt middleware.
+    pub fn interrupt_handle(&mut self) -> InterruptHandle {
+        self.interrupt
+            .get_or_insert_with(InterruptHandle::new)
+            .clone()
+    }
+
+    /// Sets the handle interrupting the instances of this store.
+    ///
+    /// Instances keep the handle that was set when they were created.
+    pub fn set_interrupt_handle(&mut self, handle: InterruptHandle) {
+        self.interrupt = Some(handle);
+    }
+
+    /// Returns the fuel left for the instances of this store that were
+    /// compiled with the fuel middleware.
+    pub fn fuel_remaining(&self) -> u64 {
+        self.fuel.remaining()
+    }
+
+    /// Replaces the fuel left for the instances of this store.
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.fuel.set(fuel);
+    }
+
+    /// Creates a global backed by the fuel of this store.
+    pub fn new_fuel_global(&self, global_type: GlobalType) -> VMGlobal {
+        VMGlobal::from_store_definition(global_type, self.fuel.vmglobal())
+    }
+
+    /// Grows a memory by `delta` pages, if the resource limiter allows it.
+    ///
+    /// Returns the previous size of the memory.
+    pub fn grow_memory(
+        &mut self,
+        memory: InternalStoreHandle<VMMemory>,
+        delta: Pages,
+    ) -> Result<Pages, MemoryError> {
+        if delta.0 > 0 {
+            if let Some(limiter) = self.limiter.as_mut() {
+                let vmmemory = &self.memories[memory.index()];
+                let current = vmmemory.size();
+                let desired = Pages(current.0.saturating_add(delta.0));
+                if !limiter.memory_growing(current, desired, vmmemory.ty().maximum) {
+                    return Err(MemoryError::CouldNotGrow {
+                        current,
+                        attempted_delta: delta,
+                    });
+                }
+            }
+        }
+        memory.get_mut(self).grow(delta)
+    }
+
+    /// Grows a table by `delta` elements initialized to `init_value`, if
+    /// the resource limiter allows it.
+    ///
+    /// Returns the previous size of the table, or `None` if it can't grow.
+    pub fn grow_table(
+        &mut self,
+        table: InternalStoreHandle<VMTable>,
+        delta: u32,
+        init_value: TableElement,
+    ) -> Option<u32> {
+        if delta > 0 {
+            if let Some(limiter) = self.limiter.as_mut() {
+                let vmtable = &self.tables[table.index()];
+                let current = vmtable.size();
+                let desired = current.saturating_add(delta);
+                if !limiter.table_growing(current, desired, vmtable.ty().maximum) {
+                    return None;
+                }
+            }
+        }
+        table.get_mut(self).grow(delta, init_value)
+    }
+
     /// Returns a pair of mutable references from two handles.
     ///
     /// Panics if both handles point to the same object.
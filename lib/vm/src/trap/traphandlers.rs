@@ -13,7 +13,7 @@ use corosensei::trap::{CoroutineTrapHandler, TrapHandlerRegs};
 use corosensei::{CoroutineResult, ScopedCoroutine, Yielder};
 use scopeguard::defer;
 use std::any::Any;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::error::Error;
 use std::io;
 use std::mem;
@@ -21,7 +21,7 @@ use std::mem;
 use std::mem::MaybeUninit;
 use std::ptr::{self, NonNull};
 use std::sync::atomic::{compiler_fence, AtomicPtr, AtomicUsize, Ordering};
-use std::sync::{Mutex, Once};
+use std::sync::Once;
 use wasmer_types::TrapCode;
 
 // TrapInformation can be stored in the "Undefined Instruction" itself.
@@ -33,7 +33,12 @@ static DEFAULT_STACK_SIZE: AtomicUsize = AtomicUsize::new(1024 * 1024);
 
 /// Default stack size is 1MB.
 pub fn set_stack_size(size: usize) {
-    DEFAULT_STACK_SIZE.store(size.max(8 * 1024).min(100 * 1024 * 1024), Ordering::Relaxed);
+    DEFAULT_STACK_SIZE.store(clamp_stack_size(size), Ordering::Relaxed);
+}
+
+/// Bounds the size of the stacks that WebAssembly runs on.
+fn clamp_stack_size(size: usize) -> usize {
+    size.max(8 * 1024).min(100 * 1024 * 1024)
 }
 
 cfg_if::cfg_if! {
@@ -88,6 +93,8 @@ unsafe fn process_illegal_op(addr: usize) -> Option<TrapCode> {
             8 => Some(TrapCode::BadConversionToInteger),
             9 => Some(TrapCode::UnreachableCodeReached),
             10 => Some(TrapCode::UnalignedAtomic),
+            11 => Some(TrapCode::StackExhausted),
+            12 => Some(TrapCode::OutOfFuel),
             _ => None,
         },
     }
@@ -507,6 +514,9 @@ cfg_if::cfg_if! {
                 } else if #[cfg(target_arch = "x86")] {
                     pc = context.Rip as usize;
                     sp = context.Rsp as usize;
+                } else if #[cfg(target_arch = "aarch64")] {
+                    pc = context.Pc as usize;
+                    sp = context.Sp as usize;
                 } else {
                     compile_error!("Unsupported platform");
                 }
@@ -530,6 +540,15 @@ cfg_if::cfg_if! {
                     context.Ebp = ebp;
                     context.Ecx = ecx;
                     context.Edx = edx;
+                } else if #[cfg(target_arch = "aarch64")] {
+                    let TrapHandlerRegs { pc, sp, x0, x1, x29, lr } = regs;
+                    context.Pc = pc;
+                    context.Sp = sp;
+                    let registers = context.u.s_mut();
+                    registers.X0 = x0;
+                    registers.X1 = x1;
+                    registers.Fp = x29;
+                    registers.Lr = lr;
                 } else {
                     compile_error!("Unsupported platform");
                 }
@@ -598,6 +617,8 @@ pub unsafe fn resume_panic(payload: Box<dyn Any + Send>) -> ! {
 
 /// Call the wasm function pointed to by `callee`.
 ///
+/// * `stack_size` - the size of the stack to run the function on, or `None`
+///   for the size set with [`set_stack_size`]
 /// * `vmctx` - the callee vmctx argument
 /// * `caller_vmctx` - the caller vmctx argument
 /// * `trampoline` - the jit-generated trampoline whose ABI takes 4 values, the
@@ -613,12 +634,13 @@ pub unsafe fn resume_panic(payload: Box<dyn Any + Send>) -> ! {
 /// function pointers.
 pub unsafe fn wasmer_call_trampoline(
     trap_handler: Option<*const TrapHandlerFn<'static>>,
+    stack_size: Option<usize>,
     vmctx: VMFunctionContext,
     trampoline: VMTrampoline,
     callee: *const VMFunctionBody,
     values_vec: *mut u8,
 ) -> Result<(), Trap> {
-    catch_traps(trap_handler, || {
+    catch_traps(trap_handler, stack_size, || {
         mem::transmute::<_, extern "C" fn(VMFunctionContext, *const VMFunctionBody, *mut u8)>(
             trampoline,
         )(vmctx, callee, values_vec);
@@ -628,11 +650,16 @@ pub unsafe fn wasmer_call_trampoline(
 /// Catches any wasm traps that happen within the execution of `closure`,
 /// returning them as a `Result`.
 ///
+/// `closure` runs on a stack of `stack_size` bytes, or of the size set with
+/// [`set_stack_size`] if `None`. Overflowing it traps with
+/// `TrapCode::StackOverflow`.
+///
 /// # Safety
 ///
 /// Highly unsafe since `closure` won't have any dtors run.
 pub unsafe fn catch_traps<F, R>(
     trap_handler: Option<*const TrapHandlerFn<'static>>,
+    stack_size: Option<usize>,
     closure: F,
 ) -> Result<R, Trap>
 where
@@ -641,12 +668,11 @@ where
     // Ensure that per-thread initialization is done.
     lazy_per_thread_init()?;
 
-    on_wasm_stack(
-        DEFAULT_STACK_SIZE.load(Ordering::Relaxed),
-        trap_handler,
-        closure,
-    )
-    .map_err(UnwindReason::into_trap)
+    let stack_size = stack_size.map_or_else(
+        || DEFAULT_STACK_SIZE.load(Ordering::Relaxed),
+        clamp_stack_size,
+    );
+    on_wasm_stack(stack_size, trap_handler, closure).map_err(UnwindReason::into_trap)
 }
 
 // We need two separate thread-local variables here:
@@ -869,17 +895,23 @@ fn on_wasm_stack<F: FnOnce() -> T, T>(
 ) -> Result<T, UnwindReason> {
     // Allocating a new stack is pretty expensive since it involves several
     // system calls. We therefore keep a cache of pre-allocated stacks which
-    // allows them to be reused multiple times.
-    // FIXME(Amanieu): We should refactor this to avoid the lock.
-    lazy_static::lazy_static! {
-        static ref STACK_POOL: Mutex<Vec<DefaultStack>> = Mutex::new(vec![]);
+    // allows them to be reused multiple times, by calls that need a stack
+    // of the same size. The cache is per thread, so that calls don't
+    // contend on a lock.
+    thread_local! {
+        static STACK_POOL: RefCell<Vec<(usize, DefaultStack)>> = const { RefCell::new(Vec::new()) };
     }
-    let stack = STACK_POOL
-        .lock()
-        .unwrap()
-        .pop()
-        .unwrap_or_else(|| DefaultStack::new(stack_size).unwrap());
-    let mut stack = scopeguard::guard(stack, |stack| STACK_POOL.lock().unwrap().push(stack));
+    let stack = STACK_POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        match pool.iter().rposition(|(size, _)| *size == stack_size) {
+            Some(index) => pool.swap_remove(index).1,
+            None => DefaultStack::new(stack_size).unwrap(),
+        }
+    });
+    let mut stack = scopeguard::guard(stack, |stack| {
+        // The stack is freed instead when the thread is exiting.
+        let _ = STACK_POOL.try_with(|pool| pool.borrow_mut().push((stack_size, stack)));
+    });
 
     // Create a coroutine with a new stack to run the function on.
     let mut coro = ScopedCoroutine::with_stack(&mut *stack, move |yielder, ()| {
@@ -343,6 +343,40 @@ pub(crate) unsafe fn memory_copy(
     Ok(())
 }
 
+/// Perform a `memory.copy` from the memory `src_mem` to the memory
+/// `dst_mem` in an unsynchronized, non-atomic way.
+///
+/// # Errors
+///
+/// Returns a `Trap` error when the source or destination ranges are out of
+/// bounds.
+///
+/// # Safety
+///
+/// The memory definitions must be valid.
+pub(crate) unsafe fn memory_copy_between(
+    dst_mem: &VMMemoryDefinition,
+    src_mem: &VMMemoryDefinition,
+    dst: u32,
+    src: u32,
+    len: u32,
+) -> Result<(), Trap> {
+    if src.checked_add(len).map_or(true, |n| {
+        usize::try_from(n).unwrap() > src_mem.current_length
+    }) || dst.checked_add(len).map_or(true, |m| {
+        usize::try_from(m).unwrap() > dst_mem.current_length
+    }) {
+        return Err(Trap::lib(TrapCode::HeapAccessOutOfBounds));
+    }
+
+    let dst = dst_mem.base.add(usize::try_from(dst).unwrap());
+    let src = src_mem.base.add(usize::try_from(src).unwrap());
+    // Both memories may be the same, so the ranges may overlap.
+    ptr::copy(src, dst, len as usize);
+
+    Ok(())
+}
+
 /// Perform the `memory.fill` operation for the memory in an unsynchronized,
 /// non-atomic way.
 ///
@@ -709,6 +743,8 @@ impl VMBuiltinFunctionsArray {
             wasmer_vm_memory32_atomic_notify as usize;
         ptrs[VMBuiltinFunctionIndex::get_imported_memory_atomic_notify_index().index() as usize] =
             wasmer_vm_imported_memory32_atomic_notify as usize;
+        ptrs[VMBuiltinFunctionIndex::get_memory_copy_between_index().index() as usize] =
+            wasmer_vm_memory32_copy_between as usize;
 
         debug_assert!(ptrs.iter().cloned().all(|p| p != 0));
 
@@ -0,0 +1,1028 @@
+//! In-process network segment shared by multiple instances.
+//!
+//! A [`VirtualBridge`] connects the virtual network interfaces of several
+//! instances running in the same process. Every instance is attached with
+//! [`VirtualBridge::attach`], which hands out a [`BridgePort`] that is used
+//! as the networking implementation of that instance. Guests can then
+//! reach each other over TCP and UDP (by IP address or by the name of the
+//! port) without any traffic touching the host network stack.
+//!
+//! Isolation rules restrict which addresses on the segment may talk to each
+//! other, for instance to stop a frontend from reaching a database directly.
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use crate::unix::UnixStream;
+use crate::{
+    IpCidr, IpRoute, NetworkError, Result, SocketStatus, UnixNamespace, VirtualConnectedSocket,
+    VirtualConnectionlessSocket, VirtualNetworking, VirtualSocket, VirtualTcpListener,
+    VirtualTcpSocket, VirtualUdpSocket,
+};
+
+/// First port handed out when a socket is bound to port zero
+const EPHEMERAL_PORT_START: u16 = 49152;
+/// Maximum number of datagrams queued on a UDP socket before newer ones
+/// are dropped
+const MAX_DATAGRAM_QUEUE: usize = 1024;
+/// Size reported for the send buffer of UDP sockets
+const DEFAULT_BUF_SIZE: usize = 65_536;
+
+/// Decides whether traffic between two addresses is allowed
+#[derive(Debug, Clone)]
+struct BridgeRule {
+    a: IpCidr,
+    b: IpCidr,
+    allow: bool,
+}
+
+impl BridgeRule {
+    fn matches(&self, from: IpAddr, to: IpAddr) -> bool {
+        (self.a.contains(from) && self.b.contains(to))
+            || (self.a.contains(to) && self.b.contains(from))
+    }
+}
+
+#[derive(Debug)]
+struct ListenerState {
+    backlog: VecDeque<(BridgeTcpSocket, SocketAddr)>,
+    max_backlog: usize,
+    waker: Option<Waker>,
+}
+
+#[derive(Debug, Default)]
+struct DatagramState {
+    queue: VecDeque<(Vec<u8>, SocketAddr)>,
+    waker: Option<Waker>,
+    broadcast: bool,
+}
+
+#[derive(Debug)]
+struct BridgeState {
+    /// Addresses of every attached port along with its name
+    hosts: HashMap<IpAddr, String>,
+    listeners: HashMap<SocketAddr, Weak<Mutex<ListenerState>>>,
+    datagrams: HashMap<SocketAddr, Weak<Mutex<DatagramState>>>,
+    rules: Vec<BridgeRule>,
+    default_allow: bool,
+    next_port: u16,
+}
+
+impl BridgeState {
+    fn allowed(&self, from: IpAddr, to: IpAddr) -> bool {
+        if from == to {
+            return true;
+        }
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(from, to))
+            .map(|rule| rule.allow)
+            .unwrap_or(self.default_allow)
+    }
+
+    fn is_port_free(&self, ips: &[IpAddr], port: u16) -> bool {
+        ips.iter().all(|ip| {
+            let addr = SocketAddr::new(*ip, port);
+            let listening = self
+                .listeners
+                .get(&addr)
+                .map(|weak| weak.strong_count() > 0)
+                .unwrap_or(false);
+            let bound = self
+                .datagrams
+                .get(&addr)
+                .map(|weak| weak.strong_count() > 0)
+                .unwrap_or(false);
+            !listening && !bound
+        })
+    }
+
+    fn allocate_port(&mut self, ips: &[IpAddr]) -> Result<u16> {
+        for _ in EPHEMERAL_PORT_START..=u16::MAX {
+            let port = self.next_port;
+            self.next_port = match self.next_port {
+                u16::MAX => EPHEMERAL_PORT_START,
+                port => port + 1,
+            };
+            if self.is_port_free(ips, port) {
+                return Ok(port);
+            }
+        }
+        Err(NetworkError::AddressInUse)
+    }
+}
+
+/// Network segment that connects the virtual network interfaces of
+/// multiple instances within the same process
+#[derive(Debug, Clone)]
+pub struct VirtualBridge {
+    state: Arc<Mutex<BridgeState>>,
+}
+
+impl Default for VirtualBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualBridge {
+    /// Creates an empty segment where all ports can talk to each other
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(BridgeState {
+                hosts: HashMap::new(),
+                listeners: HashMap::new(),
+                datagrams: HashMap::new(),
+                rules: Vec::new(),
+                default_allow: true,
+                next_port: EPHEMERAL_PORT_START,
+            })),
+        }
+    }
+
+    /// Attaches a new instance to the segment with the supplied addresses,
+    /// the name of the port can be resolved by every guest on the segment
+    pub fn attach(&self, name: impl Into<String>, ips: Vec<IpAddr>) -> Result<BridgePort> {
+        let name = name.into();
+        if ips.is_empty() {
+            return Err(NetworkError::InvalidInput);
+        }
+        let mut state = self.state.lock().unwrap();
+        if ips.iter().any(|ip| state.hosts.contains_key(ip)) {
+            return Err(NetworkError::AddressInUse);
+        }
+        for ip in ips.iter() {
+            state.hosts.insert(*ip, name.clone());
+        }
+        Ok(BridgePort {
+            bridge: self.clone(),
+            name,
+            ips,
+            unix_sockets: UnixNamespace::new(),
+        })
+    }
+
+    /// Blocks all traffic between ports that have not been explicitly
+    /// allowed to talk to each other
+    pub fn isolate_all(&self) {
+        self.state.lock().unwrap().default_allow = false;
+    }
+
+    /// Allows traffic (in both directions) between the two ranges, later
+    /// rules take precedence over earlier ones
+    pub fn allow(&self, a: IpCidr, b: IpCidr) {
+        let mut state = self.state.lock().unwrap();
+        state.rules.push(BridgeRule { a, b, allow: true });
+    }
+
+    /// Blocks traffic (in both directions) between the two ranges, later
+    /// rules take precedence over earlier ones
+    pub fn deny(&self, a: IpCidr, b: IpCidr) {
+        let mut state = self.state.lock().unwrap();
+        state.rules.push(BridgeRule { a, b, allow: false });
+    }
+
+    /// Returns the addresses of all the ports attached under this name
+    pub fn lookup(&self, name: &str) -> Vec<IpAddr> {
+        let state = self.state.lock().unwrap();
+        let mut ret: Vec<_> = state
+            .hosts
+            .iter()
+            .filter(|(_, host)| host.eq_ignore_ascii_case(name))
+            .map(|(ip, _)| *ip)
+            .collect();
+        ret.sort();
+        ret
+    }
+
+    fn detach(&self, ips: &[IpAddr]) {
+        let mut state = self.state.lock().unwrap();
+        for ip in ips {
+            state.hosts.remove(ip);
+        }
+        state.listeners.retain(|addr, _| !ips.contains(&addr.ip()));
+        state.datagrams.retain(|addr, _| !ips.contains(&addr.ip()));
+    }
+}
+
+/// Interface of a single instance that is attached to a [`VirtualBridge`]
+pub struct BridgePort {
+    bridge: VirtualBridge,
+    name: String,
+    ips: Vec<IpAddr>,
+    unix_sockets: UnixNamespace,
+}
+
+impl BridgePort {
+    /// Name under which this port can be resolved
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Addresses assigned to this port
+    pub fn ips(&self) -> &[IpAddr] {
+        &self.ips
+    }
+
+    /// Address used as the source of outbound traffic towards the peer
+    fn source_ip(&self, peer: &SocketAddr) -> IpAddr {
+        self.ips
+            .iter()
+            .find(|ip| ip.is_ipv4() == peer.is_ipv4())
+            .copied()
+            .unwrap_or(self.ips[0])
+    }
+
+    /// Maps a local address onto the addresses of this port, unspecified
+    /// and loopback addresses stand for all of them
+    fn local_ips(&self, ip: IpAddr) -> Result<Vec<IpAddr>> {
+        if ip.is_unspecified() || ip.is_loopback() {
+            Ok(self
+                .ips
+                .iter()
+                .filter(|i| i.is_ipv4() == ip.is_ipv4())
+                .copied()
+                .collect())
+        } else if self.ips.contains(&ip) {
+            Ok(vec![ip])
+        } else {
+            Err(NetworkError::AddressNotAvailable)
+        }
+    }
+
+    /// Translates loopback addresses into the address of this port
+    fn resolve_peer(&self, peer: SocketAddr) -> SocketAddr {
+        if peer.ip().is_loopback() {
+            SocketAddr::new(self.source_ip(&peer), peer.port())
+        } else {
+            peer
+        }
+    }
+}
+
+impl Drop for BridgePort {
+    fn drop(&mut self) {
+        self.bridge.detach(&self.ips);
+    }
+}
+
+impl fmt::Debug for BridgePort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BridgePort")
+            .field("name", &self.name)
+            .field("ips", &self.ips)
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for BridgePort {
+    fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        Ok(self
+            .ips
+            .iter()
+            .map(|ip| IpCidr {
+                ip: *ip,
+                prefix: if ip.is_ipv4() { 32 } else { 128 },
+            })
+            .collect())
+    }
+
+    fn route_list(&self) -> Result<Vec<IpRoute>> {
+        Ok(Vec::new())
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        _only_v6: bool,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let ips = self.local_ips(addr.ip())?;
+        let listener = Arc::new(Mutex::new(ListenerState {
+            backlog: VecDeque::new(),
+            max_backlog: 1024,
+            waker: None,
+        }));
+
+        let mut state = self.bridge.state.lock().unwrap();
+        let port = match addr.port() {
+            0 => state.allocate_port(&ips)?,
+            port if state.is_port_free(&ips, port) => port,
+            _ => return Err(NetworkError::AddressInUse),
+        };
+        let addrs: Vec<_> = ips.iter().map(|ip| SocketAddr::new(*ip, port)).collect();
+        for addr in addrs.iter() {
+            state.listeners.insert(*addr, Arc::downgrade(&listener));
+        }
+
+        Ok(Box::new(BridgeTcpListener {
+            bridge: self.bridge.clone(),
+            addr: SocketAddr::new(addr.ip(), port),
+            addrs,
+            state: listener,
+        }))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let ips = self.local_ips(addr.ip())?;
+        let socket = Arc::new(Mutex::new(DatagramState::default()));
+
+        let mut state = self.bridge.state.lock().unwrap();
+        let port = match addr.port() {
+            0 => state.allocate_port(&ips)?,
+            port if state.is_port_free(&ips, port) => port,
+            _ => return Err(NetworkError::AddressInUse),
+        };
+        let addrs: Vec<_> = ips.iter().map(|ip| SocketAddr::new(*ip, port)).collect();
+        for addr in addrs.iter() {
+            state.datagrams.insert(*addr, Arc::downgrade(&socket));
+        }
+
+        Ok(Box::new(BridgeUdpSocket {
+            bridge: self.bridge.clone(),
+            source: SocketAddr::new(self.ips[0], port),
+            addr: SocketAddr::new(addr.ip(), port),
+            addrs,
+            state: socket,
+        }))
+    }
+
+    async fn connect_tcp(
+        &self,
+        _addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let peer = self.resolve_peer(peer);
+        let source_ip = self.source_ip(&peer);
+
+        let mut state = self.bridge.state.lock().unwrap();
+        if !state.allowed(source_ip, peer.ip()) {
+            return Err(NetworkError::ConnectionRefused);
+        }
+        let listener = state
+            .listeners
+            .get(&peer)
+            .and_then(|weak| weak.upgrade())
+            .ok_or(NetworkError::ConnectionRefused)?;
+        let local = SocketAddr::new(source_ip, state.allocate_port(&[source_ip])?);
+        drop(state);
+
+        let mut listener = listener.lock().unwrap();
+        if listener.backlog.len() >= listener.max_backlog {
+            return Err(NetworkError::ConnectionRefused);
+        }
+        let (client, server) = self.unix_sockets.pair();
+        listener.backlog.push_back((
+            BridgeTcpSocket {
+                inner: server,
+                local: peer,
+                peer: local,
+            },
+            local,
+        ));
+        if let Some(waker) = listener.waker.take() {
+            waker.wake();
+        }
+        Ok(Box::new(BridgeTcpSocket {
+            inner: client,
+            local,
+            peer,
+        }))
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        _port: Option<u16>,
+        _dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![ip]);
+        }
+        if host.eq_ignore_ascii_case("localhost") {
+            return Ok(self.ips.clone());
+        }
+        Ok(self.bridge.lookup(host))
+    }
+}
+
+/// Listener for TCP connections from other ports on the segment
+#[derive(Debug)]
+pub struct BridgeTcpListener {
+    bridge: VirtualBridge,
+    addr: SocketAddr,
+    addrs: Vec<SocketAddr>,
+    state: Arc<Mutex<ListenerState>>,
+}
+
+impl Drop for BridgeTcpListener {
+    fn drop(&mut self) {
+        let mut state = self.bridge.state.lock().unwrap();
+        for addr in self.addrs.iter() {
+            state.listeners.remove(addr);
+        }
+    }
+}
+
+impl VirtualTcpListener for BridgeTcpListener {
+    fn try_accept(&mut self) -> Option<Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)>> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .backlog
+            .pop_front()
+            .map(|(sock, addr)| Ok((Box::new(sock) as Box<dyn VirtualTcpSocket + Sync>, addr)))
+    }
+
+    fn poll_accept(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)>> {
+        let mut state = self.state.lock().unwrap();
+        match state.backlog.pop_front() {
+            Some((sock, addr)) => Poll::Ready(Ok((Box::new(sock), addr))),
+            None => {
+                state.waker.replace(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    fn poll_accept_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut state = self.state.lock().unwrap();
+        if !state.backlog.is_empty() {
+            return Poll::Ready(Ok(state.backlog.len()));
+        }
+        state.waker.replace(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn set_ttl(&mut self, _ttl: u8) -> Result<()> {
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        Ok(64)
+    }
+}
+
+/// TCP connection between two ports on the segment
+#[derive(Debug)]
+pub struct BridgeTcpSocket {
+    inner: UnixStream,
+    local: SocketAddr,
+    peer: SocketAddr,
+}
+
+impl VirtualTcpSocket for BridgeTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.inner.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.inner.send_buf_size()
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        Ok(self.peer)
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+impl VirtualConnectedSocket for BridgeTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        self.inner.try_send(data)
+    }
+
+    fn poll_send(&mut self, cx: &mut Context<'_>, data: &[u8]) -> Poll<Result<usize>> {
+        self.inner.poll_send(cx, data)
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.inner.poll_flush(cx)
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.close()
+    }
+
+    fn poll_recv<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &'a mut [MaybeUninit<u8>],
+    ) -> Poll<Result<usize>> {
+        self.inner.poll_recv(cx, buf)
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        self.inner.try_recv(buf)
+    }
+}
+
+impl VirtualSocket for BridgeTcpSocket {
+    fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(64)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.local)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+/// UDP socket bound to a port on the segment
+#[derive(Debug)]
+pub struct BridgeUdpSocket {
+    bridge: VirtualBridge,
+    /// Address that datagrams sent by this socket originate from
+    source: SocketAddr,
+    addr: SocketAddr,
+    addrs: Vec<SocketAddr>,
+    state: Arc<Mutex<DatagramState>>,
+}
+
+impl BridgeUdpSocket {
+    fn source_for(&self, peer: &SocketAddr) -> SocketAddr {
+        self.addrs
+            .iter()
+            .find(|addr| addr.is_ipv4() == peer.is_ipv4())
+            .copied()
+            .unwrap_or(self.source)
+    }
+
+    fn deliver(target: &Mutex<DatagramState>, data: &[u8], from: SocketAddr) {
+        let mut target = target.lock().unwrap();
+        if target.queue.len() >= MAX_DATAGRAM_QUEUE {
+            return;
+        }
+        target.queue.push_back((data.to_vec(), from));
+        if let Some(waker) = target.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        let from = self.source_for(&addr);
+        let state = self.bridge.state.lock().unwrap();
+
+        let is_broadcast = match addr.ip() {
+            IpAddr::V4(ip) => ip.is_broadcast(),
+            IpAddr::V6(_) => false,
+        };
+        if is_broadcast {
+            if !self.state.lock().unwrap().broadcast {
+                return Err(NetworkError::PermissionDenied);
+            }
+            // Every other socket bound to the same port on the segment
+            // receives a copy of the datagram
+            for (target_addr, target) in state.datagrams.iter() {
+                if target_addr.port() != addr.port()
+                    || !target_addr.is_ipv4()
+                    || self.addrs.contains(target_addr)
+                    || !state.allowed(from.ip(), target_addr.ip())
+                {
+                    continue;
+                }
+                if let Some(target) = target.upgrade() {
+                    Self::deliver(&target, data, from);
+                }
+            }
+            return Ok(data.len());
+        }
+
+        let addr = if addr.ip().is_loopback() {
+            SocketAddr::new(from.ip(), addr.port())
+        } else {
+            addr
+        };
+        if !state.allowed(from.ip(), addr.ip()) {
+            // Like a firewall that drops packets, the datagram just vanishes
+            return Ok(data.len());
+        }
+        if let Some(target) = state.datagrams.get(&addr).and_then(|weak| weak.upgrade()) {
+            Self::deliver(&target, data, from);
+        }
+        Ok(data.len())
+    }
+
+    fn recv_from(
+        &mut self,
+        cx: Option<&mut Context<'_>>,
+        buf: &mut [MaybeUninit<u8>],
+    ) -> Poll<Result<(usize, SocketAddr)>> {
+        let mut state = self.state.lock().unwrap();
+        match state.queue.pop_front() {
+            Some((data, from)) => {
+                let amt = data.len().min(buf.len());
+                for (dst, src) in buf.iter_mut().zip(data.into_iter().take(amt)) {
+                    dst.write(src);
+                }
+                Poll::Ready(Ok((amt, from)))
+            }
+            None => match cx {
+                Some(cx) => {
+                    state.waker.replace(cx.waker().clone());
+                    Poll::Pending
+                }
+                None => Poll::Ready(Err(NetworkError::WouldBlock)),
+            },
+        }
+    }
+}
+
+impl Drop for BridgeUdpSocket {
+    fn drop(&mut self) {
+        let mut state = self.bridge.state.lock().unwrap();
+        for addr in self.addrs.iter() {
+            state.datagrams.remove(addr);
+        }
+    }
+}
+
+impl VirtualUdpSocket for BridgeUdpSocket {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.state.lock().unwrap().broadcast = broadcast;
+        Ok(())
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        Ok(self.state.lock().unwrap().broadcast)
+    }
+
+    fn set_multicast_loop_v4(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn set_multicast_loop_v6(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn set_multicast_ttl_v4(&mut self, _ttl: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        Ok(0)
+    }
+
+    fn join_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        Ok(None)
+    }
+}
+
+impl VirtualConnectionlessSocket for BridgeUdpSocket {
+    fn poll_send_to(
+        &mut self,
+        _cx: &mut Context<'_>,
+        data: &[u8],
+        addr: SocketAddr,
+    ) -> Poll<Result<usize>> {
+        Poll::Ready(self.send_to(data, addr))
+    }
+
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        self.send_to(data, addr)
+    }
+
+    fn poll_recv_from<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &'a mut [MaybeUninit<u8>],
+    ) -> Poll<Result<(usize, SocketAddr)>> {
+        self.recv_from(Some(cx), buf)
+    }
+
+    fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        match self.recv_from(None, buf) {
+            Poll::Ready(ret) => ret,
+            Poll::Pending => Err(NetworkError::WouldBlock),
+        }
+    }
+}
+
+impl VirtualSocket for BridgeUdpSocket {
+    fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(64)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut state = self.state.lock().unwrap();
+        match state.queue.front() {
+            Some((data, _)) => Poll::Ready(Ok(data.len().max(1))),
+            None => {
+                state.waker.replace(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Ready(Ok(DEFAULT_BUF_SIZE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHA: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const BETA: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+    const GAMMA: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 1, 1));
+
+    fn any(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port)
+    }
+
+    fn cidr(ip: IpAddr) -> IpCidr {
+        IpCidr { ip, prefix: 32 }
+    }
+
+    fn recv(socket: &mut (dyn VirtualTcpSocket + Sync)) -> Result<Vec<u8>> {
+        let mut buf = [MaybeUninit::new(0u8); 64];
+        let n = socket.try_recv(&mut buf)?;
+        Ok(buf[..n]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect())
+    }
+
+    fn recv_from(socket: &mut (dyn VirtualUdpSocket + Sync)) -> Result<(Vec<u8>, SocketAddr)> {
+        let mut buf = [MaybeUninit::new(0u8); 64];
+        let (n, from) = socket.try_recv_from(&mut buf)?;
+        let data = buf[..n]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect();
+        Ok((data, from))
+    }
+
+    #[tokio::test]
+    async fn two_guests_exchange_streams_by_name() {
+        let bridge = VirtualBridge::new();
+        let alpha = bridge.attach("alpha", vec![ALPHA]).unwrap();
+        let beta = bridge.attach("beta", vec![BETA]).unwrap();
+        let mut listener = beta.listen_tcp(any(80), false, false, false).await.unwrap();
+
+        let addrs = alpha.resolve("BETA", None, None).await.unwrap();
+        assert_eq!(addrs, vec![BETA]);
+        let mut outbound = alpha
+            .connect_tcp(any(0), SocketAddr::new(addrs[0], 80))
+            .await
+            .unwrap();
+        let (mut inbound, from) = listener.try_accept().unwrap().unwrap();
+
+        assert_eq!(from, outbound.addr_local().unwrap());
+        assert_eq!(from.ip(), ALPHA);
+        assert_eq!(inbound.addr_peer().unwrap(), from);
+        assert_eq!(inbound.addr_local().unwrap(), SocketAddr::new(BETA, 80));
+        assert_eq!(outbound.addr_peer().unwrap(), SocketAddr::new(BETA, 80));
+
+        assert_eq!(outbound.try_send(b"hello").unwrap(), 5);
+        assert_eq!(recv(inbound.as_mut()).unwrap(), b"hello");
+        assert_eq!(inbound.try_send(b"world").unwrap(), 5);
+        assert_eq!(recv(outbound.as_mut()).unwrap(), b"world");
+        assert_eq!(
+            recv(outbound.as_mut()).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+
+        outbound.close().unwrap();
+        assert_eq!(recv(inbound.as_mut()).unwrap(), b"");
+    }
+
+    #[tokio::test]
+    async fn two_guests_exchange_datagrams() {
+        let bridge = VirtualBridge::new();
+        let alpha = bridge.attach("alpha", vec![ALPHA]).unwrap();
+        let beta = bridge.attach("beta", vec![BETA]).unwrap();
+        let mut a = alpha.bind_udp(any(0), false, false).await.unwrap();
+        let mut b = beta.bind_udp(any(53), false, false).await.unwrap();
+        let a_port = a.addr_local().unwrap().port();
+        assert_eq!(a_port, EPHEMERAL_PORT_START);
+
+        let b_addr = SocketAddr::new(BETA, 53);
+        assert_eq!(a.try_send_to(b"first", b_addr).unwrap(), 5);
+        assert_eq!(a.try_send_to(b"second", b_addr).unwrap(), 6);
+        let a_addr = SocketAddr::new(ALPHA, a_port);
+        assert_eq!(recv_from(b.as_mut()).unwrap(), (b"first".to_vec(), a_addr));
+        assert_eq!(recv_from(b.as_mut()).unwrap(), (b"second".to_vec(), a_addr));
+        assert_eq!(recv_from(b.as_mut()).unwrap_err(), NetworkError::WouldBlock);
+
+        assert_eq!(b.try_send_to(b"reply", a_addr).unwrap(), 5);
+        assert_eq!(recv_from(a.as_mut()).unwrap(), (b"reply".to_vec(), b_addr));
+
+        // Datagrams to ports nobody is bound to just vanish
+        assert_eq!(
+            a.try_send_to(b"lost", SocketAddr::new(BETA, 54)).unwrap(),
+            4
+        );
+    }
+
+    #[tokio::test]
+    async fn broadcasts_reach_every_other_guest() {
+        let bridge = VirtualBridge::new();
+        let alpha = bridge.attach("alpha", vec![ALPHA]).unwrap();
+        let beta = bridge.attach("beta", vec![BETA]).unwrap();
+        let gamma = bridge.attach("gamma", vec![GAMMA]).unwrap();
+        let mut a = alpha.bind_udp(any(67), false, false).await.unwrap();
+        let mut b = beta.bind_udp(any(67), false, false).await.unwrap();
+        let mut c = gamma.bind_udp(any(67), false, false).await.unwrap();
+
+        let broadcast = SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), 67);
+        assert_eq!(
+            a.try_send_to(b"discover", broadcast).unwrap_err(),
+            NetworkError::PermissionDenied
+        );
+        a.set_broadcast(true).unwrap();
+        assert_eq!(a.try_send_to(b"discover", broadcast).unwrap(), 8);
+
+        let from = SocketAddr::new(ALPHA, 67);
+        assert_eq!(recv_from(b.as_mut()).unwrap(), (b"discover".to_vec(), from));
+        assert_eq!(recv_from(c.as_mut()).unwrap(), (b"discover".to_vec(), from));
+        assert_eq!(recv_from(a.as_mut()).unwrap_err(), NetworkError::WouldBlock);
+    }
+
+    #[tokio::test]
+    async fn isolated_guests_only_reach_the_guests_they_are_allowed_to() {
+        let bridge = VirtualBridge::new();
+        bridge.isolate_all();
+        let alpha = bridge.attach("alpha", vec![ALPHA]).unwrap();
+        let beta = bridge.attach("beta", vec![BETA]).unwrap();
+        let mut listener = beta.listen_tcp(any(80), false, false, false).await.unwrap();
+        let mut a = alpha.bind_udp(any(5000), false, false).await.unwrap();
+        let mut b = beta.bind_udp(any(53), false, false).await.unwrap();
+        let b_addr = SocketAddr::new(BETA, 53);
+
+        assert_eq!(
+            alpha
+                .connect_tcp(any(0), SocketAddr::new(BETA, 80))
+                .await
+                .unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+        assert_eq!(a.try_send_to(b"dropped", b_addr).unwrap(), 7);
+        assert_eq!(recv_from(b.as_mut()).unwrap_err(), NetworkError::WouldBlock);
+
+        // Rules apply in both directions
+        bridge.allow(cidr(BETA), cidr(ALPHA));
+        alpha
+            .connect_tcp(any(0), SocketAddr::new(BETA, 80))
+            .await
+            .unwrap();
+        assert!(listener.try_accept().unwrap().is_ok());
+        assert_eq!(a.try_send_to(b"allowed", b_addr).unwrap(), 7);
+        assert_eq!(recv_from(b.as_mut()).unwrap().0, b"allowed");
+
+        // Later rules take precedence
+        bridge.deny(
+            IpCidr {
+                ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)),
+                prefix: 24,
+            },
+            cidr(BETA),
+        );
+        assert_eq!(
+            alpha
+                .connect_tcp(any(0), SocketAddr::new(BETA, 80))
+                .await
+                .unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+
+        // Guests can always reach themselves
+        let mut own = beta.listen_tcp(any(81), false, false, false).await.unwrap();
+        beta.connect_tcp(any(0), SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 81))
+            .await
+            .unwrap();
+        assert_eq!(own.try_accept().unwrap().unwrap().1.ip(), BETA);
+    }
+
+    #[tokio::test]
+    async fn addresses_and_ports_are_released_when_guests_leave() {
+        let bridge = VirtualBridge::new();
+        let alpha = bridge.attach("alpha", vec![ALPHA]).unwrap();
+        let beta = bridge.attach("beta", vec![BETA]).unwrap();
+        assert_eq!(
+            bridge.attach("other", vec![BETA]).unwrap_err(),
+            NetworkError::AddressInUse
+        );
+
+        let listener = beta.listen_tcp(any(80), false, false, false).await.unwrap();
+        assert_eq!(
+            beta.listen_tcp(any(80), false, false, false)
+                .await
+                .unwrap_err(),
+            NetworkError::AddressInUse
+        );
+        drop(listener);
+        let _listener = beta.listen_tcp(any(80), false, false, false).await.unwrap();
+
+        drop(beta);
+        assert!(bridge.lookup("beta").is_empty());
+        assert_eq!(
+            alpha
+                .connect_tcp(any(0), SocketAddr::new(BETA, 80))
+                .await
+                .unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+        bridge.attach("beta", vec![BETA]).unwrap();
+    }
+}
@@ -0,0 +1,1194 @@
+//! Packet capture of virtual network traffic.
+//!
+//! [`CaptureNetworking`] wraps another [`VirtualNetworking`] implementation
+//! and reports every packet that flows through it to a [`PacketSink`].
+//! Frames on raw sockets are captured as Ethernet frames while the traffic
+//! of TCP and UDP sockets (which never exposes real packets) is turned into
+//! synthetic IP packets, including a TCP handshake and sequence numbers, so
+//! that tools such as Wireshark can follow the streams.
+//!
+//! [`PcapngSink`] writes the packets in the standard pcapng format.
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    DynVirtualNetworking, IpCidr, IpRoute, Result, SocketStatus, StreamSecurity, UnixRights,
+    UnixSocketAddr, VirtualConnectedSocket, VirtualConnectionlessSocket, VirtualIcmpSocket,
+    VirtualNetworking, VirtualRawSocket, VirtualSocket, VirtualTcpListener, VirtualTcpSocket,
+    VirtualUdpSocket,
+};
+
+/// Link layer of a captured packet
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LinkType {
+    /// Ethernet frames (as sent and received on raw sockets)
+    Ethernet,
+    /// Raw IPv4 or IPv6 packets without a link layer header
+    RawIp,
+}
+
+impl LinkType {
+    /// Link type value as registered for pcap and pcapng
+    pub fn code(self) -> u16 {
+        match self {
+            LinkType::Ethernet => 1,
+            LinkType::RawIp => 101,
+        }
+    }
+}
+
+/// Receives every packet that is captured on a virtual network
+pub trait PacketSink: fmt::Debug + Send + Sync + 'static {
+    /// Records a single packet that was captured at a specific time
+    fn record(&self, link: LinkType, timestamp: Duration, data: &[u8]);
+}
+
+/// Sink that writes all the captured packets to a pcapng stream
+pub struct PcapngSink {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl PcapngSink {
+    /// Interface ID of the Ethernet interface in the capture
+    const ETHERNET_INTERFACE: u32 = 0;
+    /// Interface ID of the raw IP interface in the capture
+    const RAW_IP_INTERFACE: u32 = 1;
+
+    /// Starts a new pcapng capture on the supplied writer
+    pub fn new(writer: impl Write + Send + 'static) -> io::Result<Self> {
+        let mut writer: Box<dyn Write + Send> = Box::new(writer);
+
+        // Section header block
+        let mut block = Vec::with_capacity(28);
+        block.extend_from_slice(&0x0A0D0D0Au32.to_le_bytes());
+        block.extend_from_slice(&28u32.to_le_bytes());
+        block.extend_from_slice(&0x1A2B3C4Du32.to_le_bytes());
+        block.extend_from_slice(&1u16.to_le_bytes());
+        block.extend_from_slice(&0u16.to_le_bytes());
+        block.extend_from_slice(&(-1i64).to_le_bytes());
+        block.extend_from_slice(&28u32.to_le_bytes());
+        writer.write_all(&block)?;
+
+        // One interface description block per link type, the order must
+        // match the interface IDs above
+        for link in [LinkType::Ethernet, LinkType::RawIp] {
+            let mut block = Vec::with_capacity(20);
+            block.extend_from_slice(&1u32.to_le_bytes());
+            block.extend_from_slice(&20u32.to_le_bytes());
+            block.extend_from_slice(&link.code().to_le_bytes());
+            block.extend_from_slice(&0u16.to_le_bytes());
+            block.extend_from_slice(&0u32.to_le_bytes());
+            block.extend_from_slice(&20u32.to_le_bytes());
+            writer.write_all(&block)?;
+        }
+        writer.flush()?;
+
+        Ok(Self {
+            writer: Mutex::new(writer),
+        })
+    }
+
+    /// Creates (or truncates) a pcapng file and starts a capture into it
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Self::new(BufWriter::new(file))
+    }
+
+    /// Flushes any buffered packets to the underlying writer
+    pub fn flush(&self) -> io::Result<()> {
+        self.writer.lock().unwrap().flush()
+    }
+}
+
+impl fmt::Debug for PcapngSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PcapngSink").finish()
+    }
+}
+
+impl PacketSink for PcapngSink {
+    fn record(&self, link: LinkType, timestamp: Duration, data: &[u8]) {
+        let interface = match link {
+            LinkType::Ethernet => Self::ETHERNET_INTERFACE,
+            LinkType::RawIp => Self::RAW_IP_INTERFACE,
+        };
+        let padded = (data.len() + 3) & !3;
+        let len = (32 + padded) as u32;
+        let micros = timestamp.as_micros() as u64;
+
+        // Enhanced packet block
+        let mut block = Vec::with_capacity(len as usize);
+        block.extend_from_slice(&6u32.to_le_bytes());
+        block.extend_from_slice(&len.to_le_bytes());
+        block.extend_from_slice(&interface.to_le_bytes());
+        block.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+        block.extend_from_slice(&(micros as u32).to_le_bytes());
+        block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        block.extend_from_slice(data);
+        block.resize(28 + padded, 0);
+        block.extend_from_slice(&len.to_le_bytes());
+
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(err) = writer.write_all(&block) {
+            tracing::warn!("failed to write captured packet - {}", err);
+        }
+    }
+}
+
+impl Drop for PcapngSink {
+    fn drop(&mut self) {
+        self.flush().ok();
+    }
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+const IP_PROTO_TCP: u8 = 6;
+const IP_PROTO_UDP: u8 = 17;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+
+/// Largest payload that is put into a single synthetic packet
+const MAX_SEGMENT: usize = 65_000;
+
+fn checksum_add(mut sum: u32, data: &[u8]) -> u32 {
+    let mut chunks = data.chunks_exact(2);
+    for chunk in chunks.by_ref() {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += u16::from_be_bytes([*last, 0]) as u32;
+    }
+    sum
+}
+
+fn checksum_finish(mut sum: u32) -> u16 {
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+fn to_v6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+/// Wraps a transport segment in an IPv4 or IPv6 header, fixing up the
+/// transport checksum located at `checksum_offset`
+fn ip_packet(
+    src: IpAddr,
+    dst: IpAddr,
+    proto: u8,
+    mut segment: Vec<u8>,
+    checksum_offset: usize,
+) -> Vec<u8> {
+    match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            let mut pseudo = checksum_add(0, &src.octets());
+            pseudo = checksum_add(pseudo, &dst.octets());
+            pseudo += proto as u32 + segment.len() as u32;
+            let sum = checksum_finish(checksum_add(pseudo, &segment));
+            segment[checksum_offset..checksum_offset + 2].copy_from_slice(&sum.to_be_bytes());
+
+            let total = (20 + segment.len()) as u16;
+            let mut packet = Vec::with_capacity(total as usize);
+            packet.extend_from_slice(&[0x45, 0]);
+            packet.extend_from_slice(&total.to_be_bytes());
+            packet.extend_from_slice(&[0, 0, 0x40, 0, 64, proto, 0, 0]);
+            packet.extend_from_slice(&src.octets());
+            packet.extend_from_slice(&dst.octets());
+            let sum = checksum_finish(checksum_add(0, &packet));
+            packet[10..12].copy_from_slice(&sum.to_be_bytes());
+            packet.extend_from_slice(&segment);
+            packet
+        }
+        (src, dst) => {
+            let (src, dst) = (to_v6(src), to_v6(dst));
+            let mut pseudo = checksum_add(0, &src.octets());
+            pseudo = checksum_add(pseudo, &dst.octets());
+            pseudo += proto as u32 + segment.len() as u32;
+            let sum = checksum_finish(checksum_add(pseudo, &segment));
+            segment[checksum_offset..checksum_offset + 2].copy_from_slice(&sum.to_be_bytes());
+
+            let mut packet = Vec::with_capacity(40 + segment.len());
+            packet.extend_from_slice(&[0x60, 0, 0, 0]);
+            packet.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+            packet.extend_from_slice(&[proto, 64]);
+            packet.extend_from_slice(&src.octets());
+            packet.extend_from_slice(&dst.octets());
+            packet.extend_from_slice(&segment);
+            packet
+        }
+    }
+}
+
+fn udp_packet(src: SocketAddr, dst: SocketAddr, payload: &[u8]) -> Vec<u8> {
+    let mut segment = Vec::with_capacity(8 + payload.len());
+    segment.extend_from_slice(&src.port().to_be_bytes());
+    segment.extend_from_slice(&dst.port().to_be_bytes());
+    segment.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+    segment.extend_from_slice(&[0, 0]);
+    segment.extend_from_slice(payload);
+    ip_packet(src.ip(), dst.ip(), IP_PROTO_UDP, segment, 6)
+}
+
+fn tcp_packet(
+    src: SocketAddr,
+    dst: SocketAddr,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut segment = Vec::with_capacity(20 + payload.len());
+    segment.extend_from_slice(&src.port().to_be_bytes());
+    segment.extend_from_slice(&dst.port().to_be_bytes());
+    segment.extend_from_slice(&seq.to_be_bytes());
+    segment.extend_from_slice(&ack.to_be_bytes());
+    segment.extend_from_slice(&[5 << 4, flags, 0xFF, 0xFF, 0, 0, 0, 0]);
+    segment.extend_from_slice(payload);
+    ip_packet(src.ip(), dst.ip(), IP_PROTO_TCP, segment, 16)
+}
+
+/// Networking implementation that captures all the traffic that flows
+/// through it into a [`PacketSink`]
+pub struct CaptureNetworking {
+    inner: DynVirtualNetworking,
+    sink: Arc<dyn PacketSink>,
+}
+
+impl CaptureNetworking {
+    /// Wraps an existing networking implementation and captures all of its
+    /// traffic into the sink
+    pub fn new(inner: DynVirtualNetworking, sink: Arc<dyn PacketSink>) -> Self {
+        Self { inner, sink }
+    }
+
+    /// Wraps an existing networking implementation and writes all of its
+    /// traffic to a pcapng file
+    pub fn pcapng(inner: DynVirtualNetworking, path: impl AsRef<Path>) -> io::Result<Self> {
+        let sink = PcapngSink::create(path)?;
+        Ok(Self::new(inner, Arc::new(sink)))
+    }
+
+    /// Returns the sink that captured packets are sent to
+    pub fn sink(&self) -> &Arc<dyn PacketSink> {
+        &self.sink
+    }
+}
+
+impl fmt::Debug for CaptureNetworking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CaptureNetworking")
+            .field("inner", &self.inner)
+            .field("sink", &self.sink)
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for CaptureNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix)
+    }
+
+    fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip)
+    }
+
+    fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear()
+    }
+
+    fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list()
+    }
+
+    fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac()
+    }
+
+    fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip)
+    }
+
+    fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+    }
+
+    fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr)
+    }
+
+    fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear()
+    }
+
+    fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list()
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        let socket = self.inner.bind_raw().await?;
+        Ok(Box::new(CaptureRawSocket {
+            inner: socket,
+            sink: self.sink.clone(),
+        }))
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let listener = self
+            .inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await?;
+        Ok(Box::new(CaptureTcpListener {
+            inner: listener,
+            sink: self.sink.clone(),
+        }))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let socket = self.inner.bind_udp(addr, reuse_port, reuse_addr).await?;
+        Ok(Box::new(CaptureUdpSocket {
+            inner: socket,
+            sink: self.sink.clone(),
+        }))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        self.inner.bind_icmp(addr).await
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let socket = self.inner.connect_tcp(addr, peer).await?;
+        Ok(Box::new(CaptureTcpSocket::new(
+            socket,
+            self.sink.clone(),
+            true,
+        )))
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        self.inner.resolve(host, port, dns_server).await
+    }
+}
+
+/// Listener whose accepted connections are captured
+#[derive(Debug)]
+pub struct CaptureTcpListener {
+    inner: Box<dyn VirtualTcpListener + Sync>,
+    sink: Arc<dyn PacketSink>,
+}
+
+impl CaptureTcpListener {
+    fn wrap(&self, socket: Box<dyn VirtualTcpSocket + Sync>) -> Box<dyn VirtualTcpSocket + Sync> {
+        Box::new(CaptureTcpSocket::new(socket, self.sink.clone(), false))
+    }
+}
+
+impl VirtualTcpListener for CaptureTcpListener {
+    fn try_accept(&mut self) -> Option<Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)>> {
+        self.inner
+            .try_accept()
+            .map(|ret| ret.map(|(sock, addr)| (self.wrap(sock), addr)))
+    }
+
+    fn poll_accept(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)>> {
+        self.inner
+            .poll_accept(cx)
+            .map_ok(|(sock, addr)| (self.wrap(sock), addr))
+    }
+
+    fn poll_accept_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_accept_ready(cx)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        self.inner.ttl()
+    }
+}
+
+/// TCP socket whose stream is captured as synthetic TCP segments
+#[derive(Debug)]
+pub struct CaptureTcpSocket {
+    inner: Box<dyn VirtualTcpSocket + Sync>,
+    sink: Arc<dyn PacketSink>,
+    local: SocketAddr,
+    peer: SocketAddr,
+    tx_seq: u32,
+    rx_seq: u32,
+    closed: bool,
+}
+
+impl CaptureTcpSocket {
+    fn new(
+        inner: Box<dyn VirtualTcpSocket + Sync>,
+        sink: Arc<dyn PacketSink>,
+        outbound: bool,
+    ) -> Self {
+        let unspecified = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+        let local = inner.addr_local().unwrap_or(unspecified);
+        let peer = inner.addr_peer().unwrap_or(unspecified);
+        let mut ret = Self {
+            inner,
+            sink,
+            local,
+            peer,
+            tx_seq: 0,
+            rx_seq: 0,
+            closed: false,
+        };
+        ret.handshake(outbound);
+        ret
+    }
+
+    fn emit(&self, outbound: bool, flags: u8, payload: &[u8]) {
+        let packet = if outbound {
+            tcp_packet(
+                self.local,
+                self.peer,
+                self.tx_seq,
+                self.rx_seq,
+                flags,
+                payload,
+            )
+        } else {
+            tcp_packet(
+                self.peer,
+                self.local,
+                self.rx_seq,
+                self.tx_seq,
+                flags,
+                payload,
+            )
+        };
+        self.sink.record(LinkType::RawIp, now(), &packet);
+    }
+
+    /// Emits the three-way handshake that opened the connection
+    fn handshake(&mut self, outbound: bool) {
+        self.emit(outbound, TCP_SYN, &[]);
+        if outbound {
+            self.tx_seq = self.tx_seq.wrapping_add(1);
+        } else {
+            self.rx_seq = self.rx_seq.wrapping_add(1);
+        }
+        self.emit(!outbound, TCP_SYN | TCP_ACK, &[]);
+        if outbound {
+            self.rx_seq = self.rx_seq.wrapping_add(1);
+        } else {
+            self.tx_seq = self.tx_seq.wrapping_add(1);
+        }
+        self.emit(outbound, TCP_ACK, &[]);
+    }
+
+    fn capture_sent(&mut self, data: &[u8]) {
+        for chunk in data.chunks(MAX_SEGMENT) {
+            self.emit(true, TCP_PSH | TCP_ACK, chunk);
+            self.tx_seq = self.tx_seq.wrapping_add(chunk.len() as u32);
+        }
+    }
+
+    fn capture_received(&mut self, buf: &[MaybeUninit<u8>], amt: usize) {
+        let data: &[u8] = unsafe { std::mem::transmute(&buf[..amt]) };
+        for chunk in data.chunks(MAX_SEGMENT) {
+            self.emit(false, TCP_PSH | TCP_ACK, chunk);
+            self.rx_seq = self.rx_seq.wrapping_add(chunk.len() as u32);
+        }
+    }
+
+    fn capture_close(&mut self) {
+        if !self.closed {
+            self.closed = true;
+            self.emit(true, TCP_FIN | TCP_ACK, &[]);
+            self.tx_seq = self.tx_seq.wrapping_add(1);
+        }
+    }
+}
+
+impl VirtualTcpSocket for CaptureTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.inner.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.inner.send_buf_size()
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        self.inner.addr_peer()
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        if matches!(how, Shutdown::Write | Shutdown::Both) {
+            self.capture_close();
+        }
+        self.inner.shutdown(how)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    fn send_rights(&mut self, rights: UnixRights) -> Result<()> {
+        self.inner.send_rights(rights)
+    }
+
+    fn recv_rights(&mut self) -> Result<Option<UnixRights>> {
+        self.inner.recv_rights()
+    }
+}
+
+impl VirtualConnectedSocket for CaptureTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let amt = self.inner.try_send(data)?;
+        self.capture_sent(&data[..amt]);
+        Ok(amt)
+    }
+
+    fn poll_send(&mut self, cx: &mut Context<'_>, data: &[u8]) -> Poll<Result<usize>> {
+        let ret = self.inner.poll_send(cx, data);
+        if let Poll::Ready(Ok(amt)) = ret {
+            self.capture_sent(&data[..amt]);
+        }
+        ret
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.inner.poll_flush(cx)
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.capture_close();
+        self.inner.close()
+    }
+
+    fn poll_recv<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &'a mut [MaybeUninit<u8>],
+    ) -> Poll<Result<usize>> {
+        let ret = self.inner.poll_recv(cx, buf);
+        if let Poll::Ready(Ok(amt)) = ret {
+            self.capture_received(buf, amt);
+        }
+        ret
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        let amt = self.inner.try_recv(buf)?;
+        self.capture_received(buf, amt);
+        Ok(amt)
+    }
+}
+
+impl VirtualSocket for CaptureTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+/// UDP socket whose datagrams are captured as synthetic UDP packets
+#[derive(Debug)]
+pub struct CaptureUdpSocket {
+    inner: Box<dyn VirtualUdpSocket + Sync>,
+    sink: Arc<dyn PacketSink>,
+}
+
+impl CaptureUdpSocket {
+    fn capture(&self, src: SocketAddr, dst: SocketAddr, data: &[u8]) {
+        let packet = udp_packet(src, dst, data);
+        self.sink.record(LinkType::RawIp, now(), &packet);
+    }
+
+    fn capture_sent(&self, data: &[u8], addr: SocketAddr) {
+        if let Ok(local) = self.inner.addr_local() {
+            self.capture(local, addr, data);
+        }
+    }
+
+    fn capture_received(&self, buf: &[MaybeUninit<u8>], amt: usize, addr: SocketAddr) {
+        if let Ok(local) = self.inner.addr_local() {
+            let data: &[u8] = unsafe { std::mem::transmute(&buf[..amt]) };
+            self.capture(addr, local, data);
+        }
+    }
+}
+
+impl VirtualUdpSocket for CaptureUdpSocket {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        self.inner.broadcast()
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v4(val)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        self.inner.multicast_loop_v4()
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v6(val)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        self.inner.multicast_loop_v6()
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.inner.multicast_ttl_v4()
+    }
+
+    fn join_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.join_multicast_v4(multiaddr, iface)
+    }
+
+    fn leave_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.leave_multicast_v4(multiaddr, iface)
+    }
+
+    fn join_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.join_multicast_v6(multiaddr, iface)
+    }
+
+    fn leave_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.leave_multicast_v6(multiaddr, iface)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        self.inner.addr_peer()
+    }
+
+    fn connect_unix(&mut self, peer: UnixSocketAddr) -> Result<()> {
+        self.inner.connect_unix(peer)
+    }
+}
+
+impl VirtualConnectionlessSocket for CaptureUdpSocket {
+    fn poll_send_to(
+        &mut self,
+        cx: &mut Context<'_>,
+        data: &[u8],
+        addr: SocketAddr,
+    ) -> Poll<Result<usize>> {
+        let ret = self.inner.poll_send_to(cx, data, addr);
+        if let Poll::Ready(Ok(amt)) = ret {
+            self.capture_sent(&data[..amt], addr);
+        }
+        ret
+    }
+
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        let amt = self.inner.try_send_to(data, addr)?;
+        self.capture_sent(&data[..amt], addr);
+        Ok(amt)
+    }
+
+    fn poll_recv_from<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &'a mut [MaybeUninit<u8>],
+    ) -> Poll<Result<(usize, SocketAddr)>> {
+        let ret = self.inner.poll_recv_from(cx, buf);
+        if let Poll::Ready(Ok((amt, addr))) = ret {
+            self.capture_received(buf, amt, addr);
+        }
+        ret
+    }
+
+    fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        let (amt, addr) = self.inner.try_recv_from(buf)?;
+        self.capture_received(buf, amt, addr);
+        Ok((amt, addr))
+    }
+}
+
+impl VirtualSocket for CaptureUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+/// Raw socket whose Ethernet frames are captured as is
+#[derive(Debug)]
+pub struct CaptureRawSocket {
+    inner: Box<dyn VirtualRawSocket + Sync>,
+    sink: Arc<dyn PacketSink>,
+}
+
+impl CaptureRawSocket {
+    fn capture(&self, data: &[u8]) {
+        self.sink.record(LinkType::Ethernet, now(), data);
+    }
+
+    fn capture_received(&self, buf: &[MaybeUninit<u8>], amt: usize) {
+        let data: &[u8] = unsafe { std::mem::transmute(&buf[..amt]) };
+        self.capture(data);
+    }
+}
+
+impl VirtualRawSocket for CaptureRawSocket {
+    fn poll_send(&mut self, cx: &mut Context<'_>, data: &[u8]) -> Poll<Result<usize>> {
+        let ret = self.inner.poll_send(cx, data);
+        if let Poll::Ready(Ok(amt)) = ret {
+            self.capture(&data[..amt]);
+        }
+        ret
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let amt = self.inner.try_send(data)?;
+        self.capture(&data[..amt]);
+        Ok(amt)
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.inner.poll_flush(cx)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.inner.try_flush()
+    }
+
+    fn poll_recv<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &'a mut [MaybeUninit<u8>],
+    ) -> Poll<Result<usize>> {
+        let ret = self.inner.poll_recv(cx, buf);
+        if let Poll::Ready(Ok(amt)) = ret {
+            self.capture_received(buf, amt);
+        }
+        ret
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        let amt = self.inner.try_recv(buf)?;
+        self.capture_received(buf, amt);
+        Ok(amt)
+    }
+
+    fn set_promiscuous(&mut self, promiscuous: bool) -> Result<()> {
+        self.inner.set_promiscuous(promiscuous)
+    }
+
+    fn promiscuous(&self) -> Result<bool> {
+        self.inner.promiscuous()
+    }
+}
+
+impl VirtualSocket for CaptureRawSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::{BridgePort, VirtualBridge};
+
+    const SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+
+    /// Writer whose output stays readable after it was handed to a sink
+    #[derive(Debug, Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        /// Splits the output into (type, body) blocks, checking that the
+        /// lengths around every block agree
+        fn blocks(&self) -> Vec<(u32, Vec<u8>)> {
+            let data = self.0.lock().unwrap();
+            let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+            let mut blocks = Vec::new();
+            let mut at = 0;
+            while at < data.len() {
+                let len = u32_at(at + 4) as usize;
+                assert_eq!(len % 4, 0, "block at {} isn't padded", at);
+                assert_eq!(u32_at(at + len - 4) as usize, len);
+                blocks.push((u32_at(at), data[at + 8..at + len - 4].to_vec()));
+                at += len;
+            }
+            assert_eq!(at, data.len());
+            blocks
+        }
+
+        /// The packets of all the enhanced packet blocks as (interface,
+        /// packet) pairs
+        fn packets(&self) -> Vec<(u32, Vec<u8>)> {
+            self.blocks()
+                .into_iter()
+                .filter(|(kind, _)| *kind == 6)
+                .map(|(_, body)| {
+                    let word = |at: usize| u32::from_le_bytes(body[at..at + 4].try_into().unwrap());
+                    let captured = word(12) as usize;
+                    assert_eq!(word(16) as usize, captured);
+                    (word(0), body[20..20 + captured].to_vec())
+                })
+                .collect()
+        }
+    }
+
+    /// A synthetic IPv4 packet, decoded after its checksums were verified
+    #[derive(Debug, PartialEq, Eq)]
+    struct Packet {
+        src: SocketAddr,
+        dst: SocketAddr,
+        /// Sequence number, acknowledgement and flags of TCP segments
+        tcp: Option<(u32, u32, u8)>,
+        payload: Vec<u8>,
+    }
+
+    impl Packet {
+        fn parse(packet: &[u8]) -> Self {
+            assert_eq!(packet[0], 0x45);
+            assert_eq!(
+                u16::from_be_bytes([packet[2], packet[3]]) as usize,
+                packet.len()
+            );
+            assert_eq!(checksum_finish(checksum_add(0, &packet[..20])), 0);
+
+            let src = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+            let dst = Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]);
+            let segment = &packet[20..];
+            let mut pseudo = checksum_add(0, &packet[12..20]);
+            pseudo += packet[9] as u32 + segment.len() as u32;
+            assert_eq!(checksum_finish(checksum_add(pseudo, segment)), 0);
+
+            let port = |at: usize| u16::from_be_bytes([segment[at], segment[at + 1]]);
+            let word = |at: usize| u32::from_be_bytes(segment[at..at + 4].try_into().unwrap());
+            let (tcp, payload) = match packet[9] {
+                IP_PROTO_TCP => (Some((word(4), word(8), segment[13])), &segment[20..]),
+                IP_PROTO_UDP => {
+                    assert_eq!(port(4) as usize, segment.len());
+                    (None, &segment[8..])
+                }
+                proto => panic!("unexpected protocol {}", proto),
+            };
+            Self {
+                src: SocketAddr::new(src.into(), port(0)),
+                dst: SocketAddr::new(dst.into(), port(2)),
+                tcp,
+                payload: payload.to_vec(),
+            }
+        }
+    }
+
+    /// Client and server ports on a segment, all the traffic of the client
+    /// is captured into the returned output
+    fn segment(bridge: &VirtualBridge) -> (BridgePort, CaptureNetworking, Output) {
+        let server = bridge.attach("server", vec![SERVER]).unwrap();
+        let client = bridge.attach("client", vec![CLIENT]).unwrap();
+        let output = Output::default();
+        let sink = PcapngSink::new(output.clone()).unwrap();
+        let client = CaptureNetworking::new(Arc::new(client), Arc::new(sink));
+        (server, client, output)
+    }
+
+    fn recv(socket: &mut (dyn VirtualTcpSocket + Sync)) -> Vec<u8> {
+        let mut buf = [MaybeUninit::new(0u8); 64];
+        let n = socket.try_recv(&mut buf).unwrap();
+        buf[..n]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect()
+    }
+
+    #[test]
+    fn captures_start_with_a_section_and_an_interface_per_link_type() {
+        let output = Output::default();
+        let sink = PcapngSink::new(output.clone()).unwrap();
+        sink.record(LinkType::Ethernet, Duration::new(5, 6_000), b"frame");
+
+        let blocks = output.blocks();
+        assert_eq!(blocks.len(), 4);
+
+        let (kind, shb) = &blocks[0];
+        assert_eq!(*kind, 0x0A0D0D0A);
+        assert_eq!(shb[..4], 0x1A2B3C4Du32.to_le_bytes());
+        assert_eq!(shb[4..8], [1, 0, 0, 0]);
+
+        for (interface, link) in [LinkType::Ethernet, LinkType::RawIp].iter().enumerate() {
+            let (kind, idb) = &blocks[1 + interface];
+            assert_eq!(*kind, 1);
+            assert_eq!(idb[..2], link.code().to_le_bytes());
+        }
+
+        let (kind, epb) = &blocks[3];
+        assert_eq!(*kind, 6);
+        assert_eq!(epb[..4], PcapngSink::ETHERNET_INTERFACE.to_le_bytes());
+        assert_eq!(epb[4..8], 0u32.to_le_bytes());
+        assert_eq!(epb[8..12], 5_000_006u32.to_le_bytes());
+        assert_eq!(epb.len(), 20 + 8);
+        assert_eq!(&epb[20..], b"frame\0\0\0");
+    }
+
+    #[tokio::test]
+    async fn tcp_streams_are_captured_as_segments() {
+        let bridge = VirtualBridge::new();
+        let (server, client, output) = segment(&bridge);
+        let mut listener = server
+            .listen_tcp(SocketAddr::new(SERVER, 80), false, false, false)
+            .await
+            .unwrap();
+
+        let mut outbound = client
+            .connect_tcp(
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+                SocketAddr::new(SERVER, 80),
+            )
+            .await
+            .unwrap();
+        let (mut inbound, from) = listener.try_accept().unwrap().unwrap();
+        assert_eq!(outbound.try_send(b"hello").unwrap(), 5);
+        assert_eq!(recv(inbound.as_mut()), b"hello");
+        assert_eq!(inbound.try_send(b"hi").unwrap(), 2);
+        assert_eq!(recv(outbound.as_mut()), b"hi");
+        outbound.close().unwrap();
+
+        let (c, s) = (from, SocketAddr::new(SERVER, 80));
+        let packet = |src, dst, seq, ack, flags, payload: &[u8]| Packet {
+            src,
+            dst,
+            tcp: Some((seq, ack, flags)),
+            payload: payload.to_vec(),
+        };
+        let packets: Vec<_> = output
+            .packets()
+            .into_iter()
+            .map(|(interface, packet)| {
+                assert_eq!(interface, PcapngSink::RAW_IP_INTERFACE);
+                Packet::parse(&packet)
+            })
+            .collect();
+        assert_eq!(
+            packets,
+            vec![
+                packet(c, s, 0, 0, TCP_SYN, b""),
+                packet(s, c, 0, 1, TCP_SYN | TCP_ACK, b""),
+                packet(c, s, 1, 1, TCP_ACK, b""),
+                packet(c, s, 1, 1, TCP_PSH | TCP_ACK, b"hello"),
+                packet(s, c, 1, 6, TCP_PSH | TCP_ACK, b"hi"),
+                packet(c, s, 6, 3, TCP_FIN | TCP_ACK, b""),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn udp_datagrams_are_captured_as_packets() {
+        let bridge = VirtualBridge::new();
+        let (server, client, output) = segment(&bridge);
+        let (c, s) = (SocketAddr::new(CLIENT, 5000), SocketAddr::new(SERVER, 53));
+        let mut inbound = server.bind_udp(s, false, false).await.unwrap();
+        let mut outbound = client.bind_udp(c, false, false).await.unwrap();
+
+        assert_eq!(outbound.try_send_to(b"query", s).unwrap(), 5);
+        let mut buf = [MaybeUninit::new(0u8); 64];
+        assert_eq!(inbound.try_recv_from(&mut buf).unwrap(), (5, c));
+        assert_eq!(inbound.try_send_to(b"answer", c).unwrap(), 6);
+        assert_eq!(outbound.try_recv_from(&mut buf).unwrap(), (6, s));
+
+        let packets: Vec<_> = output
+            .packets()
+            .into_iter()
+            .map(|(_, packet)| Packet::parse(&packet))
+            .collect();
+        assert_eq!(
+            packets,
+            vec![
+                Packet {
+                    src: c,
+                    dst: s,
+                    tcp: None,
+                    payload: b"query".to_vec(),
+                },
+                Packet {
+                    src: s,
+                    dst: c,
+                    tcp: None,
+                    payload: b"answer".to_vec(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,760 @@
+//! Egress policy for guest networking.
+//!
+//! [`EgressNetworking`] wraps another [`VirtualNetworking`] implementation
+//! and evaluates an [`EgressPolicy`] on every outbound connection attempt
+//! (TCP connects, UDP datagrams, ICMP and raw sockets). Denied attempts
+//! fail with [`NetworkError::PermissionDenied`] and are reported on the
+//! `virtual_net::egress` tracing target so they can be audited.
+//!
+//! Rules that match on host names rely on the names that the guest
+//! resolved through the host's resolver. Lookups against a DNS server that
+//! the guest picked are checked like any other UDP traffic to port 53 and
+//! their answers are never trusted for host matching.
+//!
+//! The policy can be swapped at any time through an [`EgressPolicyHandle`]
+//! without having to restart the instance.
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::{
+    DynVirtualNetworking, IpCidr, IpRoute, NetworkError, Result, SocketStatus, StreamSecurity,
+    UnixSocketAddr, VirtualConnectionlessSocket, VirtualIcmpSocket, VirtualNetworking,
+    VirtualRawSocket, VirtualSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+
+/// Number of resolved addresses whose host name is remembered
+const MAX_RESOLVED_NAMES: usize = 1024;
+
+/// What happens to a connection attempt that matches a rule
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EgressAction {
+    Allow,
+    Deny,
+}
+
+/// Protocol of an outbound connection attempt
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EgressProtocol {
+    Tcp,
+    Udp,
+    Icmp,
+    Raw,
+}
+
+/// A single outbound connection attempt made by the guest
+#[derive(Debug, Clone)]
+pub struct EgressAttempt {
+    pub protocol: EgressProtocol,
+    /// Remote address, if the protocol has one
+    pub peer: Option<SocketAddr>,
+    /// Host name that the guest resolved to the remote address, if any
+    pub host: Option<String>,
+}
+
+/// Rule of an egress policy, a rule matches an attempt when all of the
+/// criteria that it specifies match (a rule without criteria matches
+/// everything)
+#[derive(Debug, Clone)]
+pub struct EgressRule {
+    pub action: EgressAction,
+    pub protocol: Option<EgressProtocol>,
+    pub cidrs: Vec<IpCidr>,
+    pub ports: Option<RangeInclusive<u16>>,
+    /// Host names, a leading `*.` matches any subdomain
+    pub hosts: Vec<String>,
+}
+
+impl EgressRule {
+    /// Creates a rule that allows everything (until criteria are added)
+    pub fn allow() -> Self {
+        Self::new(EgressAction::Allow)
+    }
+
+    /// Creates a rule that denies everything (until criteria are added)
+    pub fn deny() -> Self {
+        Self::new(EgressAction::Deny)
+    }
+
+    fn new(action: EgressAction) -> Self {
+        Self {
+            action,
+            protocol: None,
+            cidrs: Vec::new(),
+            ports: None,
+            hosts: Vec::new(),
+        }
+    }
+
+    /// Only matches attempts of this protocol
+    pub fn protocol(mut self, protocol: EgressProtocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// Only matches attempts to an address within this range (may be
+    /// called multiple times)
+    pub fn cidr(mut self, cidr: IpCidr) -> Self {
+        self.cidrs.push(cidr);
+        self
+    }
+
+    /// Only matches attempts to a port within this range
+    pub fn ports(mut self, ports: RangeInclusive<u16>) -> Self {
+        self.ports = Some(ports);
+        self
+    }
+
+    /// Only matches attempts to this host name (may be called multiple times)
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.hosts.push(host.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Returns true if this rule applies to the connection attempt
+    pub fn matches(&self, attempt: &EgressAttempt) -> bool {
+        if let Some(protocol) = self.protocol {
+            if protocol != attempt.protocol {
+                return false;
+            }
+        }
+        if !self.cidrs.is_empty() {
+            match attempt.peer {
+                Some(peer) if self.cidrs.iter().any(|cidr| cidr.contains(peer.ip())) => {}
+                _ => return false,
+            }
+        }
+        if let Some(ports) = self.ports.as_ref() {
+            match attempt.peer {
+                Some(peer) if ports.contains(&peer.port()) => {}
+                _ => return false,
+            }
+        }
+        if !self.hosts.is_empty() {
+            match attempt.host.as_deref() {
+                Some(host) if self.hosts.iter().any(|pattern| host_matches(pattern, host)) => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .map(|prefix| prefix.ends_with('.'))
+            .unwrap_or(false),
+        None => host == pattern,
+    }
+}
+
+/// Ordered allow/deny ruleset, the first matching rule decides the fate of
+/// a connection attempt and the default action applies when none match
+#[derive(Debug, Clone)]
+pub struct EgressPolicy {
+    pub rules: Vec<EgressRule>,
+    pub default: EgressAction,
+}
+
+impl Default for EgressPolicy {
+    fn default() -> Self {
+        Self::allow_all()
+    }
+}
+
+impl EgressPolicy {
+    /// Policy that allows everything not explicitly denied
+    pub fn allow_all() -> Self {
+        Self {
+            rules: Vec::new(),
+            default: EgressAction::Allow,
+        }
+    }
+
+    /// Policy that denies everything not explicitly allowed
+    pub fn deny_all() -> Self {
+        Self {
+            rules: Vec::new(),
+            default: EgressAction::Deny,
+        }
+    }
+
+    /// Appends a rule to the end of the ruleset
+    pub fn with_rule(mut self, rule: EgressRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Decides what to do with a connection attempt
+    pub fn evaluate(&self, attempt: &EgressAttempt) -> EgressAction {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(attempt))
+            .map(|rule| rule.action)
+            .unwrap_or(self.default)
+    }
+}
+
+/// Host names that the guest resolved through the host's resolver, the
+/// oldest addresses are forgotten first
+#[derive(Debug, Default)]
+struct ResolvedNames {
+    hosts: HashMap<IpAddr, String>,
+    order: VecDeque<IpAddr>,
+}
+
+impl ResolvedNames {
+    fn insert(&mut self, addr: IpAddr, host: &str) {
+        if self.hosts.insert(addr, host.to_string()).is_some() {
+            self.order.retain(|known| *known != addr);
+        } else if self.order.len() >= MAX_RESOLVED_NAMES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.hosts.remove(&oldest);
+            }
+        }
+        self.order.push_back(addr);
+    }
+
+    fn get(&self, addr: &IpAddr) -> Option<&String> {
+        self.hosts.get(addr)
+    }
+}
+
+#[derive(Debug, Default)]
+struct EgressState {
+    policy: RwLock<EgressPolicy>,
+    names: Mutex<ResolvedNames>,
+    denied: AtomicU64,
+}
+
+impl EgressState {
+    fn check(&self, protocol: EgressProtocol, peer: Option<SocketAddr>) -> Result<()> {
+        let host = peer.and_then(|peer| self.names.lock().unwrap().get(&peer.ip()).cloned());
+        let attempt = EgressAttempt {
+            protocol,
+            peer,
+            host,
+        };
+        match self.policy.read().unwrap().evaluate(&attempt) {
+            EgressAction::Allow => Ok(()),
+            EgressAction::Deny => {
+                self.denied.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(
+                    target: "virtual_net::egress",
+                    protocol = ?attempt.protocol,
+                    peer = ?attempt.peer,
+                    host = ?attempt.host,
+                    "outbound connection denied by egress policy"
+                );
+                Err(NetworkError::PermissionDenied)
+            }
+        }
+    }
+}
+
+/// Handle used to inspect and replace the egress policy of a running
+/// instance
+#[derive(Debug, Clone)]
+pub struct EgressPolicyHandle {
+    state: Arc<EgressState>,
+}
+
+impl EgressPolicyHandle {
+    /// Replaces the policy, it applies to all subsequent connection attempts
+    pub fn set_policy(&self, policy: EgressPolicy) {
+        *self.state.policy.write().unwrap() = policy;
+    }
+
+    /// Returns a copy of the policy currently in force
+    pub fn policy(&self) -> EgressPolicy {
+        self.state.policy.read().unwrap().clone()
+    }
+
+    /// Number of connection attempts that have been denied so far
+    pub fn denied(&self) -> u64 {
+        self.state.denied.load(Ordering::Relaxed)
+    }
+}
+
+/// Networking implementation that enforces an egress policy on all the
+/// outbound traffic of the guest
+pub struct EgressNetworking {
+    inner: DynVirtualNetworking,
+    state: Arc<EgressState>,
+}
+
+impl EgressNetworking {
+    /// Wraps an existing networking implementation with a policy
+    pub fn new(inner: DynVirtualNetworking, policy: EgressPolicy) -> Self {
+        let state = EgressState {
+            policy: RwLock::new(policy),
+            ..Default::default()
+        };
+        Self {
+            inner,
+            state: Arc::new(state),
+        }
+    }
+
+    /// Returns a handle that can be used to hot-reload the policy
+    pub fn handle(&self) -> EgressPolicyHandle {
+        EgressPolicyHandle {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for EgressNetworking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EgressNetworking")
+            .field("inner", &self.inner)
+            .field("policy", &self.state.policy)
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for EgressNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix)
+    }
+
+    fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip)
+    }
+
+    fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear()
+    }
+
+    fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list()
+    }
+
+    fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac()
+    }
+
+    fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip)
+    }
+
+    fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+    }
+
+    fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr)
+    }
+
+    fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear()
+    }
+
+    fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list()
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        self.state.check(EgressProtocol::Raw, None)?;
+        self.inner.bind_raw().await
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        self.inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let socket = self.inner.bind_udp(addr, reuse_port, reuse_addr).await?;
+        Ok(Box::new(EgressUdpSocket {
+            inner: socket,
+            state: self.state.clone(),
+        }))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        self.state
+            .check(EgressProtocol::Icmp, Some(SocketAddr::new(addr, 0)))?;
+        self.inner.bind_icmp(addr).await
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        self.state.check(EgressProtocol::Tcp, Some(peer))?;
+        self.inner.connect_tcp(addr, peer).await
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        if let Some(server) = dns_server {
+            // The answers of a server chosen by the guest can't be trusted,
+            // they could map any address to an allowed host name
+            self.state
+                .check(EgressProtocol::Udp, Some(SocketAddr::new(server, 53)))?;
+            return self.inner.resolve(host, port, dns_server).await;
+        }
+
+        let addrs = self.inner.resolve(host, port, None).await?;
+        let mut names = self.state.names.lock().unwrap();
+        for addr in addrs.iter() {
+            names.insert(*addr, host);
+        }
+        Ok(addrs)
+    }
+}
+
+/// UDP socket that checks the destination of every datagram it sends
+#[derive(Debug)]
+pub struct EgressUdpSocket {
+    inner: Box<dyn VirtualUdpSocket + Sync>,
+    state: Arc<EgressState>,
+}
+
+impl VirtualUdpSocket for EgressUdpSocket {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        self.inner.broadcast()
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v4(val)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        self.inner.multicast_loop_v4()
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v6(val)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        self.inner.multicast_loop_v6()
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.inner.multicast_ttl_v4()
+    }
+
+    fn join_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.join_multicast_v4(multiaddr, iface)
+    }
+
+    fn leave_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.leave_multicast_v4(multiaddr, iface)
+    }
+
+    fn join_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.join_multicast_v6(multiaddr, iface)
+    }
+
+    fn leave_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.leave_multicast_v6(multiaddr, iface)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        self.inner.addr_peer()
+    }
+
+    fn connect_unix(&mut self, peer: UnixSocketAddr) -> Result<()> {
+        self.inner.connect_unix(peer)
+    }
+}
+
+impl VirtualConnectionlessSocket for EgressUdpSocket {
+    fn poll_send_to(
+        &mut self,
+        cx: &mut Context<'_>,
+        data: &[u8],
+        addr: SocketAddr,
+    ) -> Poll<Result<usize>> {
+        if let Err(err) = self.state.check(EgressProtocol::Udp, Some(addr)) {
+            return Poll::Ready(Err(err));
+        }
+        self.inner.poll_send_to(cx, data, addr)
+    }
+
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        self.state.check(EgressProtocol::Udp, Some(addr))?;
+        self.inner.try_send_to(data, addr)
+    }
+
+    fn poll_recv_from<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &'a mut [MaybeUninit<u8>],
+    ) -> Poll<Result<(usize, SocketAddr)>> {
+        self.inner.poll_recv_from(cx, buf)
+    }
+
+    fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        self.inner.try_recv_from(buf)
+    }
+}
+
+impl VirtualSocket for EgressUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALLOWED: IpAddr = IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34));
+    const OTHER: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+    const ROGUE_DNS: IpAddr = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 53));
+
+    /// Resolves `example.com` to `ALLOWED` through the host's resolver, and
+    /// any name to `OTHER` through any other server. Connections are refused
+    /// so that a refusal tells an allowed attempt apart from a denied one.
+    #[derive(Debug)]
+    struct FakeNetworking;
+
+    #[async_trait::async_trait]
+    impl VirtualNetworking for FakeNetworking {
+        async fn connect_tcp(
+            &self,
+            _addr: SocketAddr,
+            _peer: SocketAddr,
+        ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+            Err(NetworkError::ConnectionRefused)
+        }
+
+        async fn resolve(
+            &self,
+            host: &str,
+            _port: Option<u16>,
+            dns_server: Option<IpAddr>,
+        ) -> Result<Vec<IpAddr>> {
+            match (host, dns_server) {
+                ("example.com", None) => Ok(vec![ALLOWED]),
+                (_, Some(_)) => Ok(vec![OTHER]),
+                _ => Err(NetworkError::AddressNotAvailable),
+            }
+        }
+    }
+
+    fn networking(policy: EgressPolicy) -> EgressNetworking {
+        EgressNetworking::new(Arc::new(FakeNetworking), policy)
+    }
+
+    async fn connect(net: &EgressNetworking, ip: IpAddr, port: u16) -> Result<()> {
+        let local = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+        match net.connect_tcp(local, SocketAddr::new(ip, port)).await {
+            Err(NetworkError::ConnectionRefused) => Ok(()),
+            Err(err) => Err(err),
+            Ok(_) => unreachable!(),
+        }
+    }
+
+    fn cidr(ip: IpAddr, prefix: u8) -> IpCidr {
+        IpCidr { ip, prefix }
+    }
+
+    #[tokio::test]
+    async fn cidr_rules() {
+        let net = networking(
+            EgressPolicy::deny_all().with_rule(EgressRule::allow().cidr(cidr(ALLOWED, 24))),
+        );
+        assert!(connect(&net, ALLOWED, 443).await.is_ok());
+        assert_eq!(
+            connect(&net, OTHER, 443).await,
+            Err(NetworkError::PermissionDenied)
+        );
+
+        net.handle().set_policy(
+            EgressPolicy::allow_all()
+                .with_rule(EgressRule::deny().cidr(cidr(ALLOWED, 32)).ports(440..=450)),
+        );
+        assert_eq!(
+            connect(&net, ALLOWED, 443).await,
+            Err(NetworkError::PermissionDenied)
+        );
+        assert!(connect(&net, ALLOWED, 80).await.is_ok());
+        assert!(connect(&net, OTHER, 443).await.is_ok());
+        assert_eq!(net.handle().denied(), 2);
+    }
+
+    #[tokio::test]
+    async fn host_rules() {
+        let net =
+            networking(EgressPolicy::deny_all().with_rule(EgressRule::allow().host("example.com")));
+        // Not resolved yet, so the address has no name
+        assert_eq!(
+            connect(&net, ALLOWED, 443).await,
+            Err(NetworkError::PermissionDenied)
+        );
+        assert_eq!(
+            net.resolve("example.com", None, None).await.unwrap(),
+            vec![ALLOWED]
+        );
+        assert!(connect(&net, ALLOWED, 443).await.is_ok());
+        assert_eq!(
+            connect(&net, OTHER, 443).await,
+            Err(NetworkError::PermissionDenied)
+        );
+
+        net.handle()
+            .set_policy(EgressPolicy::allow_all().with_rule(EgressRule::deny().host("*.com")));
+        assert_eq!(
+            connect(&net, ALLOWED, 443).await,
+            Err(NetworkError::PermissionDenied)
+        );
+        assert!(connect(&net, OTHER, 443).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn forged_dns_answers_are_not_trusted() {
+        let net = networking(
+            EgressPolicy::deny_all()
+                .with_rule(EgressRule::allow().host("example.com"))
+                .with_rule(
+                    EgressRule::allow()
+                        .protocol(EgressProtocol::Udp)
+                        .cidr(cidr(ROGUE_DNS, 32))
+                        .ports(53..=53),
+                ),
+        );
+        // The guest's own server claims that example.com lives at OTHER
+        assert_eq!(
+            net.resolve("example.com", None, Some(ROGUE_DNS))
+                .await
+                .unwrap(),
+            vec![OTHER]
+        );
+        assert_eq!(
+            connect(&net, OTHER, 443).await,
+            Err(NetworkError::PermissionDenied)
+        );
+    }
+
+    #[tokio::test]
+    async fn dns_servers_are_subject_to_the_policy() {
+        let net =
+            networking(EgressPolicy::deny_all().with_rule(EgressRule::allow().host("example.com")));
+        assert_eq!(
+            net.resolve("example.com", None, Some(ROGUE_DNS)).await,
+            Err(NetworkError::PermissionDenied)
+        );
+        // The host's resolver is always available
+        assert!(net.resolve("example.com", None, None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn default_action_applies_when_no_rule_matches() {
+        let net = networking(EgressPolicy::deny_all());
+        assert_eq!(
+            connect(&net, ALLOWED, 443).await,
+            Err(NetworkError::PermissionDenied)
+        );
+        assert_eq!(
+            net.bind_icmp(ALLOWED).await.err(),
+            Some(NetworkError::PermissionDenied)
+        );
+        assert_eq!(
+            net.bind_raw().await.err(),
+            Some(NetworkError::PermissionDenied)
+        );
+
+        net.handle().set_policy(EgressPolicy::default());
+        assert!(connect(&net, ALLOWED, 443).await.is_ok());
+        assert_eq!(net.handle().denied(), 3);
+    }
+    #[test]
+    fn names_are_bounded() {
+        let mut names = ResolvedNames::default();
+        names.insert(ALLOWED, "example.com");
+        names.insert(OTHER, "other.test");
+        for i in 0..MAX_RESOLVED_NAMES as u32 - 1 {
+            names.insert(IpAddr::V4(Ipv4Addr::from(0x0a00_0000 + i)), "many.test");
+            // Resolving a name again keeps it around
+            names.insert(OTHER, "other.test");
+        }
+        assert_eq!(names.hosts.len(), MAX_RESOLVED_NAMES);
+        assert_eq!(names.order.len(), MAX_RESOLVED_NAMES);
+        assert_eq!(names.get(&ALLOWED), None);
+        assert_eq!(names.get(&OTHER).map(String::as_str), Some("other.test"));
+    }
+}
@@ -0,0 +1,178 @@
+//! Port forwarding into a guest.
+//!
+//! [`TcpForwarder`] accepts connections on one networking implementation
+//! (usually the host) and relays each of them to an address that is only
+//! reachable through another one, such as a service that the guest listens
+//! on behind [`NatNetworking`](crate::nat::NatNetworking).
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::{DynVirtualNetworking, NetworkError, Result, VirtualTcpListener, VirtualTcpSocket};
+
+const RELAY_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Future that relays a single forwarded connection
+pub type RelayFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Relays the connections accepted on a listening socket to a target address
+pub struct TcpForwarder {
+    listener: Box<dyn VirtualTcpListener + Sync>,
+    to: DynVirtualNetworking,
+    target: SocketAddr,
+}
+
+impl TcpForwarder {
+    /// Listens on `listen` through `from`, connections will be relayed to
+    /// `target` through `to` once [`TcpForwarder::run`] is called
+    pub async fn bind(
+        from: &DynVirtualNetworking,
+        listen: SocketAddr,
+        to: DynVirtualNetworking,
+        target: SocketAddr,
+    ) -> Result<Self> {
+        let listener = from.listen_tcp(listen, false, false, true).await?;
+        Ok(Self {
+            listener,
+            to,
+            target,
+        })
+    }
+
+    /// Returns the address that connections are accepted on
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.addr_local()
+    }
+
+    /// Accepts connections until the listener fails, every connection is
+    /// relayed by a future that is handed to `spawn`
+    pub async fn run<S>(mut self, spawn: S) -> Result<()>
+    where
+        S: Fn(RelayFuture),
+    {
+        loop {
+            let (socket, peer) = poll_fn(|cx| self.listener.poll_accept(cx)).await?;
+            let to = self.to.clone();
+            let target = self.target;
+            spawn(Box::pin(async move {
+                let local = match target.ip() {
+                    IpAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+                    IpAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+                };
+                let upstream = match to.connect_tcp(local, target).await {
+                    Ok(upstream) => upstream,
+                    Err(err) => {
+                        tracing::debug!(%peer, %target, error = %err, "unable to forward connection");
+                        return;
+                    }
+                };
+                if let Err(err) = relay(socket, upstream).await {
+                    tracing::debug!(%peer, %target, error = %err, "forwarded connection failed");
+                }
+            }));
+        }
+    }
+}
+
+impl fmt::Debug for TcpForwarder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TcpForwarder")
+            .field("listen", &self.listener.addr_local().ok())
+            .field("target", &self.target)
+            .finish()
+    }
+}
+
+/// Copies bytes in both directions until both sides have finished sending
+pub async fn relay(
+    mut a: Box<dyn VirtualTcpSocket + Sync>,
+    mut b: Box<dyn VirtualTcpSocket + Sync>,
+) -> Result<()> {
+    let mut a_to_b = Pipe::default();
+    let mut b_to_a = Pipe::default();
+    poll_fn(|cx| {
+        let a_done = a_to_b.poll_copy(cx, &mut a, &mut b)?.is_ready();
+        let b_done = b_to_a.poll_copy(cx, &mut b, &mut a)?.is_ready();
+        if a_done && b_done {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// One direction of a relay
+struct Pipe {
+    buf: Box<[MaybeUninit<u8>]>,
+    pos: usize,
+    len: usize,
+    eof: bool,
+    done: bool,
+}
+
+impl Default for Pipe {
+    fn default() -> Self {
+        Self {
+            buf: vec![MaybeUninit::uninit(); RELAY_BUFFER_SIZE].into_boxed_slice(),
+            pos: 0,
+            len: 0,
+            eof: false,
+            done: false,
+        }
+    }
+}
+
+impl Pipe {
+    fn poll_copy(
+        &mut self,
+        cx: &mut Context<'_>,
+        from: &mut Box<dyn VirtualTcpSocket + Sync>,
+        to: &mut Box<dyn VirtualTcpSocket + Sync>,
+    ) -> Poll<Result<()>> {
+        loop {
+            if self.done {
+                return Poll::Ready(Ok(()));
+            }
+            if self.pos == self.len {
+                if self.eof {
+                    match to.poll_flush(cx) {
+                        Poll::Ready(res) => res?,
+                        Poll::Pending => return Poll::Pending,
+                    }
+                    // The other side may already be gone, which is fine
+                    let _ = to.shutdown(Shutdown::Write);
+                    self.done = true;
+                    continue;
+                }
+                let read = match from.poll_recv(cx, &mut self.buf) {
+                    Poll::Ready(res) => res?,
+                    Poll::Pending => return Poll::Pending,
+                };
+                self.pos = 0;
+                self.len = read;
+                self.eof = read == 0;
+                continue;
+            }
+
+            // Safety: the bytes up to `len` were initialized by `poll_recv`
+            let data = unsafe {
+                std::slice::from_raw_parts(
+                    self.buf[self.pos..self.len].as_ptr() as *const u8,
+                    self.len - self.pos,
+                )
+            };
+            let written = match to.poll_send(cx, data) {
+                Poll::Ready(res) => res?,
+                Poll::Pending => return Poll::Pending,
+            };
+            if written == 0 {
+                return Poll::Ready(Err(NetworkError::WriteZero));
+            }
+            self.pos += written;
+        }
+    }
+}
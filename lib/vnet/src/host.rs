@@ -39,6 +39,12 @@ impl Default for LocalNetworking {
 #[async_trait::async_trait]
 #[allow(unused_variables)]
 impl VirtualNetworking for LocalNetworking {
+    #[tracing::instrument(
+        level = "debug",
+        skip(self),
+        fields(otel.kind = "server", net.host.ip = %addr.ip(), net.host.port = addr.port()),
+        err
+    )]
     async fn listen_tcp(
         &self,
         addr: SocketAddr,
@@ -58,15 +64,42 @@ impl VirtualNetworking for LocalNetworking {
         Ok(listener)
     }
 
+    #[tracing::instrument(
+        level = "debug",
+        skip(self),
+        fields(net.transport = "ip_udp", net.host.ip = %addr.ip(), net.host.port = addr.port()),
+        err
+    )]
     async fn bind_udp(
         &self,
         addr: SocketAddr,
-        _reuse_port: bool,
-        _reuse_addr: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
     ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
-        let socket = tokio::net::UdpSocket::bind(addr)
-            .await
+        // Discovery protocols (mDNS, SSDP) share well known ports with any
+        // daemons running on the host, hence the reuse flags must be
+        // applied before the socket is bound
+        let domain = match addr {
+            SocketAddr::V4(_) => socket2::Domain::IPV4,
+            SocketAddr::V6(_) => socket2::Domain::IPV6,
+        };
+        let socket = socket2::Socket::new(domain, socket2::Type::DGRAM, None)
+            .map_err(io_err_into_net_error)?;
+        if reuse_addr {
+            socket
+                .set_reuse_address(true)
+                .map_err(io_err_into_net_error)?;
+        }
+        #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+        if reuse_port {
+            socket.set_reuse_port(true).map_err(io_err_into_net_error)?;
+        }
+        socket
+            .set_nonblocking(true)
             .map_err(io_err_into_net_error)?;
+        socket.bind(&addr.into()).map_err(io_err_into_net_error)?;
+        let socket =
+            tokio::net::UdpSocket::from_std(socket.into()).map_err(io_err_into_net_error)?;
         Ok(Box::new(LocalUdpSocket {
             socket,
             addr,
@@ -74,6 +107,12 @@ impl VirtualNetworking for LocalNetworking {
         }))
     }
 
+    #[tracing::instrument(
+        level = "debug",
+        skip(self, _addr),
+        fields(otel.kind = "client", net.peer.ip = %peer.ip(), net.peer.port = peer.port()),
+        err
+    )]
     async fn connect_tcp(
         &self,
         _addr: SocketAddr,
@@ -86,6 +125,12 @@ impl VirtualNetworking for LocalNetworking {
         Ok(Box::new(LocalTcpStream::new(stream, peer)))
     }
 
+    #[tracing::instrument(
+        level = "debug",
+        skip(self, port, dns_server),
+        fields(otel.kind = "client", net.peer.name = host),
+        err
+    )]
     async fn resolve(
         &self,
         host: &str,
@@ -11,8 +11,10 @@ use std::task::Poll;
 use std::time::Duration;
 use thiserror::Error;
 
+pub use bridge::{BridgePort, VirtualBridge};
 pub use bytes::Bytes;
 pub use bytes::BytesMut;
+pub use unix::{UnixNamespace, UnixRights, UnixSocketAddr};
 
 pub type Result<T> = std::result::Result<T, NetworkError>;
 
@@ -23,6 +25,29 @@ pub struct IpCidr {
     pub prefix: u8,
 }
 
+impl IpCidr {
+    /// Returns true if the IP address falls within this range
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.ip, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let prefix = self.prefix.min(32) as u32;
+                let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+                (u32::from(net) & mask) == (u32::from(ip) & mask)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let prefix = self.prefix.min(128) as u32;
+                let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+                (u128::from(net) & mask) == (u128::from(ip) & mask)
+            }
+            (IpAddr::V6(_), IpAddr::V4(ip)) => self.contains(IpAddr::V6(ip.to_ipv6_mapped())),
+            (IpAddr::V4(_), IpAddr::V6(ip)) => match ip.to_ipv4_mapped() {
+                Some(ip) => self.contains(IpAddr::V4(ip)),
+                None => false,
+            },
+        }
+    }
+}
+
 /// Represents a routing entry in the routing table of the interface
 #[derive(Clone, Debug)]
 pub struct IpRoute {
@@ -346,6 +371,7 @@ pub trait VirtualRawSocket: VirtualSocket + fmt::Debug + Send + Sync + 'static {
     fn promiscuous(&self) -> Result<bool>;
 }
 
+#[allow(unused_variables)]
 pub trait VirtualTcpSocket: VirtualConnectedSocket + fmt::Debug + Send + Sync + 'static {
     /// Sets the receive buffer size which acts as a trottle for how
     /// much data is buffered on this side of the pipe
@@ -384,8 +410,21 @@ pub trait VirtualTcpSocket: VirtualConnectedSocket + fmt::Debug + Send + Sync +
 
     /// Return true if the socket is closed
     fn is_closed(&self) -> bool;
+
+    /// Passes handles (such as file descriptors) to the peer along with
+    /// the data stream, this is only supported by Unix domain sockets
+    fn send_rights(&mut self, rights: UnixRights) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    /// Receives the next batch of handles that were passed by the peer,
+    /// this is only supported by Unix domain sockets
+    fn recv_rights(&mut self) -> Result<Option<UnixRights>> {
+        Err(NetworkError::Unsupported)
+    }
 }
 
+#[allow(unused_variables)]
 pub trait VirtualUdpSocket:
     VirtualConnectionlessSocket + fmt::Debug + Send + Sync + 'static
 {
@@ -445,6 +484,12 @@ pub trait VirtualUdpSocket:
     /// Returns the remote address of this UDP socket if it has been
     /// connected to a specific target destination address
     fn addr_peer(&self) -> Result<Option<SocketAddr>>;
+
+    /// Sets the default destination of this socket to a named Unix domain
+    /// socket, this is only supported by Unix domain sockets
+    fn connect_unix(&mut self, peer: UnixSocketAddr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -530,5 +575,15 @@ pub enum NetworkError {
     UnknownError,
 }
 
+pub mod bridge;
+pub mod capture;
+pub mod egress;
+pub mod forward;
 #[cfg(feature = "host-net")]
 pub mod host;
+pub mod nat;
+pub mod proxy;
+pub mod shaping;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod unix;
@@ -0,0 +1,408 @@
+//! Private virtual interface with outbound access through the host.
+//!
+//! [`NatNetworking`] gives an instance its own private address, much like a
+//! virtual machine behind NAT. Traffic between the instance and itself (or
+//! its subprocesses) never leaves the process, outbound connections are
+//! made through another networking implementation and nothing that the
+//! guest listens on is reachable from the outside.
+//!
+//! UDP sockets of the guest are mapped onto ephemeral ports of the outside
+//! and only accept datagrams from the peers that the guest has sent
+//! datagrams to, like a port-restricted cone NAT.
+use std::collections::{HashSet, VecDeque};
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::task::{Context, Poll};
+
+use crate::{
+    BridgePort, DynVirtualNetworking, IpCidr, IpRoute, NetworkError, Result, SocketStatus,
+    UnixSocketAddr, VirtualBridge, VirtualConnectionlessSocket, VirtualNetworking, VirtualSocket,
+    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+
+/// Default private address of the guest (the same one that is handed out
+/// to guests by user mode networking in QEMU)
+pub const DEFAULT_NAT_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 15);
+
+/// Number of peers that a UDP mapping remembers, the ones that the guest
+/// sent to the longest time ago are forgotten first
+const MAX_MAPPED_PEERS: usize = 1024;
+
+/// Networking implementation that places the guest on a private address
+/// and translates its outbound traffic onto another implementation
+#[derive(Debug)]
+pub struct NatNetworking {
+    outside: DynVirtualNetworking,
+    inside: BridgePort,
+}
+
+impl NatNetworking {
+    /// Places the guest on the default private address
+    pub fn new(outside: DynVirtualNetworking) -> Self {
+        Self::with_addr(outside, DEFAULT_NAT_ADDR)
+    }
+
+    /// Places the guest on a specific private address
+    pub fn with_addr(outside: DynVirtualNetworking, addr: Ipv4Addr) -> Self {
+        let inside = VirtualBridge::new()
+            .attach(
+                "localhost",
+                vec![IpAddr::V4(addr), IpAddr::V6(Ipv6Addr::LOCALHOST)],
+            )
+            .expect("a new bridge has no addresses in use");
+        Self { outside, inside }
+    }
+
+    /// Returns true if the address belongs to the guest itself
+    fn is_inside(&self, ip: IpAddr) -> bool {
+        ip.is_loopback() || self.inside.ips().contains(&ip)
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for NatNetworking {
+    fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inside.ip_list()
+    }
+
+    fn route_list(&self) -> Result<Vec<IpRoute>> {
+        Ok(Vec::new())
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        self.inside
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        // Sockets on the loopback stay private, everything else is mapped
+        // onto an ephemeral port of the outside so that replies to outbound
+        // datagrams arrive
+        if addr.ip().is_loopback() {
+            return self.inside.bind_udp(addr, reuse_port, reuse_addr).await;
+        }
+        let unspecified = match addr {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let outside = self
+            .outside
+            .bind_udp(SocketAddr::new(unspecified, 0), false, false)
+            .await?;
+        let port = match addr.port() {
+            0 => outside.addr_local()?.port(),
+            port => port,
+        };
+        Ok(Box::new(NatUdpSocket {
+            outside,
+            addr: SocketAddr::new(addr.ip(), port),
+            peers: HashSet::new(),
+            order: VecDeque::new(),
+        }))
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        if self.is_inside(peer.ip()) {
+            return self.inside.connect_tcp(addr, peer).await;
+        }
+        self.outside.connect_tcp(addr, peer).await
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        if host.eq_ignore_ascii_case("localhost") {
+            return Ok(vec![
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(Ipv6Addr::LOCALHOST),
+            ]);
+        }
+        self.outside.resolve(host, port, dns_server).await
+    }
+}
+
+/// UDP socket of the guest, mapped onto an ephemeral port of the outside
+#[derive(Debug)]
+pub struct NatUdpSocket {
+    outside: Box<dyn VirtualUdpSocket + Sync>,
+    /// Address of the socket as seen by the guest
+    addr: SocketAddr,
+    /// Peers that the guest has sent datagrams to, the only ones whose
+    /// datagrams are let through
+    peers: HashSet<SocketAddr>,
+    order: VecDeque<SocketAddr>,
+}
+
+impl NatUdpSocket {
+    fn map_peer(&mut self, peer: SocketAddr) {
+        if !self.peers.insert(peer) {
+            return;
+        }
+        self.order.push_back(peer);
+        if self.order.len() > MAX_MAPPED_PEERS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.peers.remove(&oldest);
+            }
+        }
+    }
+
+    fn is_mapped(&self, peer: &SocketAddr) -> bool {
+        let mapped = self.peers.contains(peer);
+        if !mapped {
+            tracing::trace!(
+                addr = %self.addr,
+                %peer,
+                "dropped a datagram from a peer that the guest never sent to"
+            );
+        }
+        mapped
+    }
+}
+
+impl VirtualUdpSocket for NatUdpSocket {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.outside.set_broadcast(broadcast)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        self.outside.broadcast()
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.outside.set_multicast_loop_v4(val)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        self.outside.multicast_loop_v4()
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.outside.set_multicast_loop_v6(val)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        self.outside.multicast_loop_v6()
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.outside.set_multicast_ttl_v4(ttl)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.outside.multicast_ttl_v4()
+    }
+
+    fn join_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        // Multicast groups would let in datagrams from anyone
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        self.outside.addr_peer()
+    }
+
+    fn connect_unix(&mut self, _peer: UnixSocketAddr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+}
+
+impl VirtualConnectionlessSocket for NatUdpSocket {
+    fn poll_send_to(
+        &mut self,
+        cx: &mut Context<'_>,
+        data: &[u8],
+        addr: SocketAddr,
+    ) -> Poll<Result<usize>> {
+        self.map_peer(addr);
+        self.outside.poll_send_to(cx, data, addr)
+    }
+
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        self.map_peer(addr);
+        self.outside.try_send_to(data, addr)
+    }
+
+    fn poll_recv_from<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &'a mut [MaybeUninit<u8>],
+    ) -> Poll<Result<(usize, SocketAddr)>> {
+        loop {
+            match self.outside.poll_recv_from(cx, buf) {
+                Poll::Ready(Ok((_, peer))) if !self.is_mapped(&peer) => continue,
+                ret => return ret,
+            }
+        }
+    }
+
+    fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        loop {
+            match self.outside.try_recv_from(buf) {
+                Ok((_, peer)) if !self.is_mapped(&peer) => continue,
+                ret => return ret,
+            }
+        }
+    }
+}
+
+impl VirtualSocket for NatUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.outside.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.outside.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.outside.status()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.outside.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.outside.poll_write_ready(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    const HOST: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const PEER: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+    const STRANGER: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 3));
+
+    fn any(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port)
+    }
+
+    fn recv(socket: &mut (dyn VirtualUdpSocket + Sync)) -> Result<(Vec<u8>, SocketAddr)> {
+        let mut buf = [MaybeUninit::new(0u8); 64];
+        let (n, from) = socket.try_recv_from(&mut buf)?;
+        let data = buf[..n]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect();
+        Ok((data, from))
+    }
+
+    #[tokio::test]
+    async fn udp_is_mapped_onto_an_ephemeral_port() {
+        let segment = VirtualBridge::new();
+        let host = segment.attach("host", vec![HOST]).unwrap();
+        let peer = segment.attach("peer", vec![PEER]).unwrap();
+        let nat = NatNetworking::new(Arc::new(host));
+
+        let mut guest = nat.bind_udp(any(5353), false, false).await.unwrap();
+        assert_eq!(guest.addr_local().unwrap().port(), 5353);
+        let mut remote = peer.bind_udp(any(9000), false, false).await.unwrap();
+
+        guest
+            .try_send_to(b"ping", SocketAddr::new(PEER, 9000))
+            .unwrap();
+        let (data, mapped) = recv(remote.as_mut()).unwrap();
+        assert_eq!(data, b"ping");
+        // The guest's port is not exposed on the host
+        assert_eq!(mapped.ip(), HOST);
+        assert_ne!(mapped.port(), 5353);
+
+        remote.try_send_to(b"pong", mapped).unwrap();
+        assert_eq!(
+            recv(guest.as_mut()).unwrap(),
+            (b"pong".to_vec(), SocketAddr::new(PEER, 9000))
+        );
+    }
+
+    #[tokio::test]
+    async fn udp_drops_datagrams_from_unknown_peers() {
+        let segment = VirtualBridge::new();
+        let host = segment.attach("host", vec![HOST]).unwrap();
+        let peer = segment.attach("peer", vec![PEER]).unwrap();
+        let stranger = segment.attach("stranger", vec![STRANGER]).unwrap();
+        let nat = NatNetworking::new(Arc::new(host));
+
+        let mut guest = nat.bind_udp(any(0), false, false).await.unwrap();
+        let mut remote = peer.bind_udp(any(9000), false, false).await.unwrap();
+        let mut other = stranger.bind_udp(any(9000), false, false).await.unwrap();
+
+        guest
+            .try_send_to(b"ping", SocketAddr::new(PEER, 9000))
+            .unwrap();
+        let (_, mapped) = recv(remote.as_mut()).unwrap();
+
+        // Knowing the mapped port is not enough to get in
+        other.try_send_to(b"intruder", mapped).unwrap();
+        // Nor is using the right address from the wrong port
+        let mut wrong_port = peer.bind_udp(any(9001), false, false).await.unwrap();
+        wrong_port.try_send_to(b"intruder", mapped).unwrap();
+        remote.try_send_to(b"pong", mapped).unwrap();
+
+        assert_eq!(recv(guest.as_mut()).unwrap().0, b"pong");
+        assert_eq!(recv(guest.as_mut()), Err(NetworkError::WouldBlock));
+    }
+
+    #[tokio::test]
+    async fn loopback_stays_private() {
+        let segment = VirtualBridge::new();
+        let host = segment.attach("host", vec![HOST]).unwrap();
+        let peer = segment.attach("peer", vec![PEER]).unwrap();
+        let nat = NatNetworking::new(Arc::new(host));
+
+        let loopback = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 7000);
+        let mut server = nat.bind_udp(loopback, false, false).await.unwrap();
+        let mut client = nat
+            .bind_udp(SocketAddr::new(loopback.ip(), 0), false, false)
+            .await
+            .unwrap();
+        client.try_send_to(b"hello", loopback).unwrap();
+        assert_eq!(recv(server.as_mut()).unwrap().0, b"hello");
+
+        let mut remote = peer.bind_udp(any(0), false, false).await.unwrap();
+        remote
+            .try_send_to(b"hello", SocketAddr::new(HOST, 7000))
+            .unwrap();
+        assert_eq!(recv(server.as_mut()), Err(NetworkError::WouldBlock));
+
+        assert_eq!(nat.ip_list().unwrap()[0].ip, IpAddr::V4(DEFAULT_NAT_ADDR));
+    }
+}
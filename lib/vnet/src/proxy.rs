@@ -0,0 +1,890 @@
+//! Upstream proxy for guest traffic.
+//!
+//! [`ProxyNetworking`] wraps another [`VirtualNetworking`] implementation
+//! and tunnels every outbound TCP connection of the guest through a SOCKS5
+//! or HTTP CONNECT proxy. The guest sees a regular connection to the
+//! address it asked for while the host only ever talks to the proxy.
+//!
+//! Connectionless traffic (UDP, ICMP and raw sockets) can not be tunnelled
+//! and is refused so that nothing bypasses the proxy.
+use std::collections::HashMap;
+use std::fmt;
+use std::future::poll_fn;
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, Shutdown, SocketAddr};
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::{
+    DynVirtualNetworking, IpCidr, IpRoute, NetworkError, Result, SocketStatus, StreamSecurity,
+    UnixRights, VirtualConnectedSocket, VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket,
+    VirtualSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+
+/// Protocol spoken with the upstream proxy
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProxyProtocol {
+    /// SOCKS version 5 (RFC 1928)
+    Socks5,
+    /// HTTP `CONNECT` tunnels (RFC 7231)
+    HttpConnect,
+}
+
+/// Upstream proxy that guest connections are routed through
+#[derive(Clone)]
+pub struct ProxyConfig {
+    pub protocol: ProxyProtocol,
+    pub addr: SocketAddr,
+    /// Username and password used to authenticate with the proxy
+    pub credentials: Option<(String, String)>,
+}
+
+impl ProxyConfig {
+    /// Proxy that speaks SOCKS5
+    pub fn socks5(addr: SocketAddr) -> Self {
+        Self {
+            protocol: ProxyProtocol::Socks5,
+            addr,
+            credentials: None,
+        }
+    }
+
+    /// Proxy that accepts HTTP CONNECT requests
+    pub fn http_connect(addr: SocketAddr) -> Self {
+        Self {
+            protocol: ProxyProtocol::HttpConnect,
+            addr,
+            credentials: None,
+        }
+    }
+
+    /// Authenticates with the proxy using a username and password
+    pub fn with_credentials(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.credentials = Some((username.into(), password.into()));
+        self
+    }
+}
+
+impl fmt::Debug for ProxyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyConfig")
+            .field("protocol", &self.protocol)
+            .field("addr", &self.addr)
+            .field(
+                "credentials",
+                &self.credentials.as_ref().map(|(user, _)| user),
+            )
+            .finish()
+    }
+}
+
+/// Where a tunnelled connection should end up
+#[derive(Debug, Clone)]
+enum ProxyTarget {
+    Addr(SocketAddr),
+    Host(String, u16),
+}
+
+impl ProxyTarget {
+    fn authority(&self) -> String {
+        match self {
+            ProxyTarget::Addr(addr) => addr.to_string(),
+            ProxyTarget::Host(host, port) => format!("{}:{}", host, port),
+        }
+    }
+}
+
+/// First address of the range that is handed out for host names which are
+/// resolved by the proxy (198.18.0.0/15 is reserved for benchmarking and
+/// never routed)
+const REMOTE_DNS_BASE: u32 = 0xC612_0000;
+const REMOTE_DNS_SIZE: u32 = 1 << 17;
+
+#[derive(Debug, Default)]
+struct ProxyNames {
+    /// Host names that were resolved by the guest
+    by_addr: HashMap<IpAddr, String>,
+    /// Placeholder addresses handed out when resolution is left to the proxy
+    by_name: HashMap<String, IpAddr>,
+}
+
+/// Networking implementation that routes all outbound connections through
+/// an upstream proxy
+pub struct ProxyNetworking {
+    inner: DynVirtualNetworking,
+    config: ProxyConfig,
+    remote_dns: bool,
+    names: Mutex<ProxyNames>,
+}
+
+impl ProxyNetworking {
+    /// Wraps an existing networking implementation, which will only be
+    /// used to reach the proxy itself
+    pub fn new(inner: DynVirtualNetworking, config: ProxyConfig) -> Self {
+        Self {
+            inner,
+            config,
+            remote_dns: false,
+            names: Mutex::new(ProxyNames::default()),
+        }
+    }
+
+    /// Leaves name resolution to the proxy, which is required when the host
+    /// itself can not resolve names. The guest receives placeholder
+    /// addresses that are turned back into host names when connecting.
+    pub fn with_remote_dns(mut self, remote_dns: bool) -> Self {
+        self.remote_dns = remote_dns;
+        self
+    }
+
+    fn target_for(&self, peer: SocketAddr) -> ProxyTarget {
+        let names = self.names.lock().unwrap();
+        match names.by_addr.get(&peer.ip()) {
+            Some(host) => ProxyTarget::Host(host.clone(), peer.port()),
+            None => ProxyTarget::Addr(peer),
+        }
+    }
+
+    fn placeholder_for(&self, host: &str) -> Result<IpAddr> {
+        let mut names = self.names.lock().unwrap();
+        if let Some(addr) = names.by_name.get(host) {
+            return Ok(*addr);
+        }
+        let idx = names.by_name.len() as u32;
+        if idx >= REMOTE_DNS_SIZE {
+            return Err(NetworkError::InsufficientMemory);
+        }
+        let addr = IpAddr::V4(Ipv4Addr::from(REMOTE_DNS_BASE + idx));
+        names.by_name.insert(host.to_string(), addr);
+        names.by_addr.insert(addr, host.to_string());
+        Ok(addr)
+    }
+
+    async fn handshake(
+        &self,
+        socket: &mut Box<dyn VirtualTcpSocket + Sync>,
+        target: &ProxyTarget,
+    ) -> Result<()> {
+        match self.config.protocol {
+            ProxyProtocol::Socks5 => socks5_handshake(socket, &self.config, target).await,
+            ProxyProtocol::HttpConnect => http_handshake(socket, &self.config, target).await,
+        }
+    }
+}
+
+impl fmt::Debug for ProxyNetworking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyNetworking")
+            .field("inner", &self.inner)
+            .field("config", &self.config)
+            .field("remote_dns", &self.remote_dns)
+            .finish()
+    }
+}
+
+async fn write_all(socket: &mut Box<dyn VirtualTcpSocket + Sync>, mut data: &[u8]) -> Result<()> {
+    while !data.is_empty() {
+        let amt = poll_fn(|cx| socket.poll_send(cx, data)).await?;
+        if amt == 0 {
+            return Err(NetworkError::WriteZero);
+        }
+        data = &data[amt..];
+    }
+    poll_fn(|cx| socket.poll_flush(cx)).await
+}
+
+async fn read_exact(socket: &mut Box<dyn VirtualTcpSocket + Sync>, len: usize) -> Result<Vec<u8>> {
+    let mut buf = vec![MaybeUninit::<u8>::uninit(); len];
+    let mut read = 0;
+    while read < len {
+        let amt = poll_fn(|cx| socket.poll_recv(cx, &mut buf[read..])).await?;
+        if amt == 0 {
+            return Err(NetworkError::UnexpectedEof);
+        }
+        read += amt;
+    }
+    Ok(buf
+        .into_iter()
+        .map(|b| unsafe { b.assume_init() })
+        .collect())
+}
+
+async fn socks5_handshake(
+    socket: &mut Box<dyn VirtualTcpSocket + Sync>,
+    config: &ProxyConfig,
+    target: &ProxyTarget,
+) -> Result<()> {
+    // Method negotiation
+    let method = if config.credentials.is_some() { 2 } else { 0 };
+    write_all(socket, &[5, 1, method]).await?;
+    let reply = read_exact(socket, 2).await?;
+    if reply[0] != 5 || reply[1] != method {
+        tracing::warn!("SOCKS5 proxy refused the authentication method");
+        return Err(NetworkError::PermissionDenied);
+    }
+
+    // Username and password authentication (RFC 1929)
+    if let Some((username, password)) = config.credentials.as_ref() {
+        if username.len() > 255 || password.len() > 255 {
+            return Err(NetworkError::InvalidInput);
+        }
+        let mut req = vec![1, username.len() as u8];
+        req.extend_from_slice(username.as_bytes());
+        req.push(password.len() as u8);
+        req.extend_from_slice(password.as_bytes());
+        write_all(socket, &req).await?;
+        let reply = read_exact(socket, 2).await?;
+        if reply[1] != 0 {
+            tracing::warn!("SOCKS5 proxy rejected the credentials");
+            return Err(NetworkError::PermissionDenied);
+        }
+    }
+
+    // Connect request
+    let mut req = vec![5, 1, 0];
+    match target {
+        ProxyTarget::Addr(SocketAddr::V4(addr)) => {
+            req.push(1);
+            req.extend_from_slice(&addr.ip().octets());
+            req.extend_from_slice(&addr.port().to_be_bytes());
+        }
+        ProxyTarget::Addr(SocketAddr::V6(addr)) => {
+            req.push(4);
+            req.extend_from_slice(&addr.ip().octets());
+            req.extend_from_slice(&addr.port().to_be_bytes());
+        }
+        ProxyTarget::Host(host, port) => {
+            if host.len() > 255 {
+                return Err(NetworkError::InvalidInput);
+            }
+            req.push(3);
+            req.push(host.len() as u8);
+            req.extend_from_slice(host.as_bytes());
+            req.extend_from_slice(&port.to_be_bytes());
+        }
+    }
+    write_all(socket, &req).await?;
+
+    let reply = read_exact(socket, 4).await?;
+    if reply[0] != 5 {
+        return Err(NetworkError::InvalidData);
+    }
+    match reply[1] {
+        0 => {}
+        2 => return Err(NetworkError::PermissionDenied),
+        3 | 4 => return Err(NetworkError::AddressNotAvailable),
+        5 => return Err(NetworkError::ConnectionRefused),
+        6 => return Err(NetworkError::TimedOut),
+        7 | 8 => return Err(NetworkError::Unsupported),
+        _ => return Err(NetworkError::ConnectionAborted),
+    }
+
+    // Skip the bound address
+    let addr_len = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => read_exact(socket, 1).await?[0] as usize,
+        _ => return Err(NetworkError::InvalidData),
+    };
+    read_exact(socket, addr_len + 2).await?;
+    Ok(())
+}
+
+async fn http_handshake(
+    socket: &mut Box<dyn VirtualTcpSocket + Sync>,
+    config: &ProxyConfig,
+    target: &ProxyTarget,
+) -> Result<()> {
+    let authority = target.authority();
+    let mut req = format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n", authority, authority);
+    if let Some((username, password)) = config.credentials.as_ref() {
+        let token = base64_encode(format!("{}:{}", username, password).as_bytes());
+        req.push_str(&format!("Proxy-Authorization: Basic {}\r\n", token));
+    }
+    req.push_str("\r\n");
+    write_all(socket, req.as_bytes()).await?;
+
+    // Read the response one byte at a time so that none of the tunnelled
+    // data is consumed
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() > 8192 {
+            return Err(NetworkError::InvalidData);
+        }
+        head.push(read_exact(socket, 1).await?[0]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let status = head
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or(NetworkError::InvalidData)?;
+    match status {
+        200..=299 => Ok(()),
+        403 | 407 => {
+            tracing::warn!("HTTP proxy refused the tunnel (status {})", status);
+            Err(NetworkError::PermissionDenied)
+        }
+        502 | 503 | 504 => Err(NetworkError::AddressNotAvailable),
+        _ => Err(NetworkError::ConnectionRefused),
+    }
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut ret = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                ret.push(ALPHABET[((n >> (18 - i * 6)) & 0x3F) as usize] as char);
+            } else {
+                ret.push('=');
+            }
+        }
+    }
+    ret
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for ProxyNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix)
+    }
+
+    fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip)
+    }
+
+    fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear()
+    }
+
+    fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list()
+    }
+
+    fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac()
+    }
+
+    fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip)
+    }
+
+    fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+    }
+
+    fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr)
+    }
+
+    fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear()
+    }
+
+    fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list()
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        self.inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await
+    }
+
+    async fn bind_udp(
+        &self,
+        _addr: SocketAddr,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    async fn bind_icmp(&self, _addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        Err(NetworkError::Unsupported)
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let target = self.target_for(peer);
+        let mut socket = self.inner.connect_tcp(addr, self.config.addr).await?;
+        if let Err(err) = self.handshake(&mut socket, &target).await {
+            tracing::debug!(
+                "proxy failed to connect to {} - {}",
+                target.authority(),
+                err
+            );
+            socket.close().ok();
+            return Err(err);
+        }
+        Ok(Box::new(ProxiedTcpSocket {
+            inner: socket,
+            peer,
+        }))
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        if self.remote_dns {
+            if let Ok(ip) = host.parse::<IpAddr>() {
+                return Ok(vec![ip]);
+            }
+            return Ok(vec![self.placeholder_for(host)?]);
+        }
+        let addrs = self.inner.resolve(host, port, dns_server).await?;
+        let mut names = self.names.lock().unwrap();
+        for addr in addrs.iter() {
+            names.by_addr.insert(*addr, host.to_string());
+        }
+        Ok(addrs)
+    }
+}
+
+/// TCP socket that is tunnelled through the proxy, it reports the address
+/// that the guest connected to rather than the address of the proxy
+#[derive(Debug)]
+pub struct ProxiedTcpSocket {
+    inner: Box<dyn VirtualTcpSocket + Sync>,
+    peer: SocketAddr,
+}
+
+impl VirtualTcpSocket for ProxiedTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.inner.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.inner.send_buf_size()
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        Ok(self.peer)
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    fn send_rights(&mut self, rights: UnixRights) -> Result<()> {
+        self.inner.send_rights(rights)
+    }
+
+    fn recv_rights(&mut self) -> Result<Option<UnixRights>> {
+        self.inner.recv_rights()
+    }
+}
+
+impl VirtualConnectedSocket for ProxiedTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        self.inner.try_send(data)
+    }
+
+    fn poll_send(&mut self, cx: &mut Context<'_>, data: &[u8]) -> Poll<Result<usize>> {
+        self.inner.poll_send(cx, data)
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.inner.poll_flush(cx)
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.close()
+    }
+
+    fn poll_recv<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &'a mut [MaybeUninit<u8>],
+    ) -> Poll<Result<usize>> {
+        self.inner.poll_recv(cx, buf)
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        self.inner.try_recv(buf)
+    }
+}
+
+impl VirtualSocket for ProxiedTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{BridgePort, VirtualBridge};
+
+    const SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+    const PROXY: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 3)), 1080);
+
+    /// What the guest asked the proxy for
+    #[derive(Debug, PartialEq, Eq)]
+    struct Request {
+        target: String,
+        /// `user:password` for SOCKS5, the `Proxy-Authorization` header
+        /// for HTTP
+        auth: Option<String>,
+    }
+
+    /// Ports of a server, a client and a proxy on a segment, the client
+    /// only ever talks to the proxy
+    struct Segment {
+        _bridge: VirtualBridge,
+        _server: BridgePort,
+        _proxy: BridgePort,
+        client: ProxyNetworking,
+        listener: Box<dyn VirtualTcpListener + Sync>,
+    }
+
+    impl Segment {
+        async fn new(config: ProxyConfig) -> Self {
+            let bridge = VirtualBridge::new();
+            let server = bridge.attach("server", vec![SERVER]).unwrap();
+            let proxy = bridge.attach("proxy", vec![PROXY.ip()]).unwrap();
+            let client = bridge.attach("client", vec![CLIENT]).unwrap();
+            let listener = proxy.listen_tcp(PROXY, false, false, false).await.unwrap();
+            Self {
+                _bridge: bridge,
+                _server: server,
+                _proxy: proxy,
+                client: ProxyNetworking::new(Arc::new(client), config),
+                listener,
+            }
+        }
+
+        /// Connects the guest to `peer` while the proxy answers with
+        /// `reply`, returning the guest socket and the proxy end of the
+        /// tunnel
+        async fn connect(
+            &mut self,
+            peer: SocketAddr,
+            reply: u16,
+        ) -> (
+            Result<Box<dyn VirtualTcpSocket + Sync>>,
+            Box<dyn VirtualTcpSocket + Sync>,
+            Request,
+        ) {
+            let any = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+            let (client, listener) = (&self.client, &mut self.listener);
+            let proxy = async {
+                let (mut socket, _) = poll_fn(|cx| listener.poll_accept(cx)).await?;
+                let request = match client.config.protocol {
+                    ProxyProtocol::Socks5 => socks5_proxy(&mut socket, reply as u8).await?,
+                    ProxyProtocol::HttpConnect => http_proxy(&mut socket, reply).await?,
+                };
+                Ok::<_, NetworkError>((socket, request))
+            };
+            let (guest, proxy) = tokio::join!(client.connect_tcp(any, peer), proxy);
+            let (tunnel, request) = proxy.unwrap();
+            (guest, tunnel, request)
+        }
+    }
+
+    /// Serves the SOCKS5 side of a single connection
+    async fn socks5_proxy(
+        socket: &mut Box<dyn VirtualTcpSocket + Sync>,
+        reply: u8,
+    ) -> Result<Request> {
+        let head = read_exact(socket, 2).await?;
+        assert_eq!(head[0], 5);
+        let methods = read_exact(socket, head[1] as usize).await?;
+        let method = if methods.contains(&2) { 2 } else { 0 };
+        write_all(socket, &[5, method]).await?;
+
+        let mut auth = None;
+        if method == 2 {
+            let head = read_exact(socket, 2).await?;
+            let username = read_exact(socket, head[1] as usize).await?;
+            let len = read_exact(socket, 1).await?[0];
+            let password = read_exact(socket, len as usize).await?;
+            auth = Some(format!(
+                "{}:{}",
+                String::from_utf8(username).unwrap(),
+                String::from_utf8(password).unwrap()
+            ));
+            write_all(socket, &[1, 0]).await?;
+        }
+
+        let head = read_exact(socket, 4).await?;
+        assert_eq!(head[..3], [5, 1, 0]);
+        let host = match head[3] {
+            1 => {
+                let ip = read_exact(socket, 4).await?;
+                Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]).to_string()
+            }
+            3 => {
+                let len = read_exact(socket, 1).await?[0];
+                String::from_utf8(read_exact(socket, len as usize).await?).unwrap()
+            }
+            atyp => panic!("unexpected address type {}", atyp),
+        };
+        let port = read_exact(socket, 2).await?;
+        let target = format!("{}:{}", host, u16::from_be_bytes([port[0], port[1]]));
+        write_all(socket, &[5, reply, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+        Ok(Request { target, auth })
+    }
+
+    /// Serves the HTTP CONNECT side of a single connection
+    async fn http_proxy(
+        socket: &mut Box<dyn VirtualTcpSocket + Sync>,
+        status: u16,
+    ) -> Result<Request> {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(read_exact(socket, 1).await?[0]);
+        }
+        let head = String::from_utf8(head).unwrap();
+        let mut lines = head.lines();
+        let target = lines
+            .next()
+            .and_then(|line| line.strip_prefix("CONNECT "))
+            .and_then(|line| line.strip_suffix(" HTTP/1.1"))
+            .unwrap()
+            .to_string();
+        let auth = lines
+            .find_map(|line| line.strip_prefix("Proxy-Authorization: "))
+            .map(str::to_string);
+        let response = format!("HTTP/1.1 {} Whatever\r\nVia: test\r\n\r\n", status);
+        write_all(socket, response.as_bytes()).await?;
+        Ok(Request { target, auth })
+    }
+
+    /// Sends `data` in one direction of the tunnel and checks it comes out
+    /// at the other end
+    async fn ping(
+        from: &mut Box<dyn VirtualTcpSocket + Sync>,
+        to: &mut Box<dyn VirtualTcpSocket + Sync>,
+        data: &[u8],
+    ) {
+        write_all(from, data).await.unwrap();
+        assert_eq!(read_exact(to, data.len()).await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn socks5_tunnels_to_the_address() {
+        let mut segment = Segment::new(ProxyConfig::socks5(PROXY)).await;
+        let peer = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)), 80);
+        let (guest, mut tunnel, request) = segment.connect(peer, 0).await;
+        let mut guest = guest.unwrap();
+        assert_eq!(
+            request,
+            Request {
+                target: "203.0.113.7:80".to_string(),
+                auth: None,
+            }
+        );
+
+        // The guest sees the peer it asked for, not the proxy
+        assert_eq!(guest.addr_peer().unwrap(), peer);
+        ping(&mut guest, &mut tunnel, b"ping").await;
+        ping(&mut tunnel, &mut guest, b"pong").await;
+    }
+
+    #[tokio::test]
+    async fn socks5_sends_credentials_and_the_names_the_guest_resolved() {
+        let config = ProxyConfig::socks5(PROXY).with_credentials("user", "secret");
+        let mut segment = Segment::new(config).await;
+        let addrs = segment.client.resolve("server", None, None).await.unwrap();
+        assert_eq!(addrs, vec![SERVER]);
+
+        let (guest, _, request) = segment.connect(SocketAddr::new(SERVER, 443), 0).await;
+        assert!(guest.is_ok());
+        assert_eq!(
+            request,
+            Request {
+                target: "server:443".to_string(),
+                auth: Some("user:secret".to_string()),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn http_connect_tunnels_to_the_authority() {
+        let config = ProxyConfig::http_connect(PROXY).with_credentials("user", "pass");
+        let mut segment = Segment::new(config).await;
+        let (guest, mut tunnel, request) = segment.connect(SocketAddr::new(SERVER, 80), 200).await;
+        let mut guest = guest.unwrap();
+        assert_eq!(
+            request,
+            Request {
+                target: "192.0.2.1:80".to_string(),
+                auth: Some("Basic dXNlcjpwYXNz".to_string()),
+            }
+        );
+
+        // Nothing after the response head is swallowed
+        ping(&mut tunnel, &mut guest, b"early").await;
+        ping(&mut guest, &mut tunnel, b"ping").await;
+    }
+
+    #[tokio::test]
+    async fn remote_dns_leaves_names_to_the_proxy() {
+        let mut segment = Segment::new(ProxyConfig::http_connect(PROXY)).await;
+        segment.client.remote_dns = true;
+
+        let first = segment
+            .client
+            .resolve("a.example", None, None)
+            .await
+            .unwrap();
+        let second = segment
+            .client
+            .resolve("b.example", None, None)
+            .await
+            .unwrap();
+        assert_eq!(first, vec![IpAddr::V4(Ipv4Addr::new(198, 18, 0, 0))]);
+        assert_eq!(second, vec![IpAddr::V4(Ipv4Addr::new(198, 18, 0, 1))]);
+        assert_eq!(
+            segment
+                .client
+                .resolve("a.example", None, None)
+                .await
+                .unwrap(),
+            first
+        );
+
+        let (guest, _, request) = segment.connect(SocketAddr::new(second[0], 443), 200).await;
+        assert!(guest.is_ok());
+        assert_eq!(request.target, "b.example:443");
+    }
+
+    #[tokio::test]
+    async fn refusals_of_the_proxy_are_reported() {
+        let peer = SocketAddr::new(SERVER, 80);
+
+        let mut segment = Segment::new(ProxyConfig::socks5(PROXY)).await;
+        let (guest, _, _) = segment.connect(peer, 5).await;
+        assert_eq!(guest.unwrap_err(), NetworkError::ConnectionRefused);
+
+        let mut segment = Segment::new(ProxyConfig::http_connect(PROXY)).await;
+        let (guest, _, _) = segment.connect(peer, 407).await;
+        assert_eq!(guest.unwrap_err(), NetworkError::PermissionDenied);
+        let (guest, _, _) = segment.connect(peer, 502).await;
+        assert_eq!(guest.unwrap_err(), NetworkError::AddressNotAvailable);
+    }
+
+    #[tokio::test]
+    async fn connectionless_traffic_is_refused() {
+        let segment = Segment::new(ProxyConfig::socks5(PROXY)).await;
+        let any = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+        assert_eq!(
+            segment
+                .client
+                .bind_udp(any, false, false)
+                .await
+                .unwrap_err(),
+            NetworkError::Unsupported
+        );
+        assert_eq!(
+            segment.client.bind_raw().await.unwrap_err(),
+            NetworkError::Unsupported
+        );
+    }
+}
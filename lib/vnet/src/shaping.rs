@@ -0,0 +1,1172 @@
+//! Traffic shaping of virtual network interfaces.
+//!
+//! [`ShapingNetworking`] wraps another [`VirtualNetworking`] implementation
+//! and degrades the TCP and UDP traffic that flows through it according to a
+//! [`ShapingConfig`]. All the sockets of the wrapped interface share the
+//! same token buckets, so the limits apply to the interface as a whole
+//! rather than to individual connections.
+//!
+//! Latency and jitter are injected on the inbound path (which delays the
+//! full round trip) while packet loss only applies to UDP datagrams, as TCP
+//! streams never lose data.
+//!
+//! Time is read from the tokio clock, so pausing it (as tests do) pauses
+//! the shaping as well. Tasks waiting on the shaping are woken up by tokio
+//! timers, so the sockets must be polled from a tokio runtime with the time
+//! driver enabled.
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use tokio::time::{Instant, Sleep};
+
+use crate::{
+    DynVirtualNetworking, IpCidr, IpRoute, NetworkError, Result, SocketStatus, StreamSecurity,
+    UnixRights, UnixSocketAddr, VirtualConnectedSocket, VirtualConnectionlessSocket,
+    VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket, VirtualSocket, VirtualTcpListener,
+    VirtualTcpSocket, VirtualUdpSocket,
+};
+
+/// Size of the buffer used to pull data out of the inner sockets
+const READ_BUF_SIZE: usize = 65_536;
+
+/// Describes how the traffic of a virtual network interface is degraded
+#[derive(Debug, Clone)]
+pub struct ShapingConfig {
+    /// Maximum rate of outbound traffic in bytes per second
+    pub egress_rate: Option<u64>,
+    /// Maximum rate of inbound traffic in bytes per second
+    pub ingress_rate: Option<u64>,
+    /// Number of bytes that can be sent or received in a single burst
+    /// before the rate limits kick in
+    pub burst: u64,
+    /// Fixed delay added to all inbound traffic
+    pub latency: Duration,
+    /// Random delay of up to this amount added on top of the latency
+    pub jitter: Duration,
+    /// Probability (between 0.0 and 1.0) that a datagram is dropped
+    pub loss: f64,
+}
+
+impl Default for ShapingConfig {
+    fn default() -> Self {
+        Self {
+            egress_rate: None,
+            ingress_rate: None,
+            burst: 65_536,
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            loss: 0.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, burst: u64) -> Self {
+        let burst = burst.max(1) as f64;
+        Self {
+            rate: rate.max(1) as f64,
+            burst,
+            tokens: burst,
+            last: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last = now;
+    }
+
+    /// Time at which the bucket will hold at least `amt` tokens
+    fn ready_at(&self, amt: f64) -> Instant {
+        let missing = (amt - self.tokens).max(0.0);
+        self.last + Duration::from_secs_f64(missing / self.rate)
+    }
+
+    /// Takes up to `want` tokens (or exactly `want` if `partial` is false)
+    fn take(&mut self, want: usize, partial: bool) -> std::result::Result<usize, Instant> {
+        self.refill();
+        let need = if partial {
+            1.0
+        } else {
+            (want as f64).min(self.burst)
+        };
+        if self.tokens < need {
+            return Err(self.ready_at(need));
+        }
+        let amt = if partial {
+            want.min(self.tokens as usize)
+        } else {
+            want
+        };
+        self.tokens -= amt as f64;
+        Ok(amt)
+    }
+
+    /// Checks that there is at least one token, without taking it
+    fn ready(&mut self) -> std::result::Result<(), Instant> {
+        self.refill();
+        if self.tokens < 1.0 {
+            return Err(self.ready_at(1.0));
+        }
+        Ok(())
+    }
+
+    /// Takes tokens for data that was already transferred, which may leave
+    /// the bucket in debt
+    fn consume(&mut self, amt: usize) {
+        self.tokens -= amt as f64;
+    }
+
+    /// Returns tokens that were taken but not used
+    fn refund(&mut self, amt: usize) {
+        self.tokens = (self.tokens + amt as f64).min(self.burst);
+    }
+}
+
+/// Wakes up the task polling an operation once the shaping allows it to
+/// make progress
+#[derive(Debug, Default)]
+struct Delay {
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl Delay {
+    /// Registers the waker of `cx` to be woken up at `deadline`, replacing
+    /// whatever was registered before
+    fn wait(&mut self, cx: Option<&mut Context<'_>>, deadline: Instant) {
+        let cx = match cx {
+            Some(cx) => cx,
+            None => return,
+        };
+        let sleep = match self.sleep.as_mut() {
+            Some(sleep) => {
+                if sleep.deadline() != deadline {
+                    sleep.as_mut().reset(deadline);
+                }
+                sleep
+            }
+            None => self
+                .sleep
+                .insert(Box::pin(tokio::time::sleep_until(deadline))),
+        };
+        if sleep.as_mut().poll(cx).is_ready() {
+            cx.waker().wake_by_ref();
+        }
+    }
+}
+
+/// State shared between all the sockets of a shaped interface
+#[derive(Debug)]
+struct Shaper {
+    config: ShapingConfig,
+    egress: Option<Mutex<TokenBucket>>,
+    ingress: Option<Mutex<TokenBucket>>,
+    rng: Mutex<u64>,
+}
+
+impl Shaper {
+    fn new(config: ShapingConfig) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Self {
+            egress: config
+                .egress_rate
+                .map(|rate| Mutex::new(TokenBucket::new(rate, config.burst))),
+            ingress: config
+                .ingress_rate
+                .map(|rate| Mutex::new(TokenBucket::new(rate, config.burst))),
+            rng: Mutex::new(seed | 1),
+            config,
+        }
+    }
+
+    /// Returns a random number between 0.0 and 1.0 (xorshift64*)
+    fn random(&self) -> f64 {
+        let mut state = self.rng.lock().unwrap();
+        *state ^= *state >> 12;
+        *state ^= *state << 25;
+        *state ^= *state >> 27;
+        let val = state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (val >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn lose(&self) -> bool {
+        self.config.loss > 0.0 && self.random() < self.config.loss
+    }
+
+    /// Point in time at which data that has just arrived may be delivered
+    fn deliver_at(&self) -> Instant {
+        let mut delay = self.config.latency;
+        if self.config.jitter > Duration::ZERO {
+            delay += self.config.jitter.mul_f64(self.random());
+        }
+        Instant::now() + delay
+    }
+
+    /// Takes the tokens needed to send `want` bytes, or returns when enough
+    /// of them will be available
+    fn take_egress(&self, want: usize, partial: bool) -> std::result::Result<usize, Instant> {
+        match self.egress.as_ref() {
+            Some(bucket) => bucket.lock().unwrap().take(want, partial),
+            None => Ok(want),
+        }
+    }
+
+    fn refund_egress(&self, amt: usize) {
+        if let Some(bucket) = self.egress.as_ref() {
+            bucket.lock().unwrap().refund(amt);
+        }
+    }
+
+    /// Checks that data may be received, or returns when it will be
+    fn ingress_ready(&self) -> std::result::Result<(), Instant> {
+        match self.ingress.as_ref() {
+            Some(bucket) => bucket.lock().unwrap().ready(),
+            None => Ok(()),
+        }
+    }
+
+    fn consume_ingress(&self, amt: usize) {
+        if let Some(bucket) = self.ingress.as_ref() {
+            bucket.lock().unwrap().consume(amt);
+        }
+    }
+}
+
+fn pending_to_would_block<T>(ret: Poll<Result<T>>) -> Result<T> {
+    match ret {
+        Poll::Ready(ret) => ret,
+        Poll::Pending => Err(NetworkError::WouldBlock),
+    }
+}
+
+fn would_block_to_pending<T>(ret: Result<T>) -> Poll<Result<T>> {
+    match ret {
+        Err(NetworkError::WouldBlock) => Poll::Pending,
+        ret => Poll::Ready(ret),
+    }
+}
+
+fn copy_out(src: &[u8], buf: &mut [MaybeUninit<u8>]) -> usize {
+    let amt = src.len().min(buf.len());
+    for (dst, src) in buf.iter_mut().zip(src[..amt].iter()) {
+        dst.write(*src);
+    }
+    amt
+}
+
+/// Networking implementation that applies rate limits, latency, jitter and
+/// packet loss to all the traffic that flows through it
+pub struct ShapingNetworking {
+    inner: DynVirtualNetworking,
+    shaper: Arc<Shaper>,
+}
+
+impl ShapingNetworking {
+    /// Wraps an existing networking implementation
+    pub fn new(inner: DynVirtualNetworking, config: ShapingConfig) -> Self {
+        Self {
+            inner,
+            shaper: Arc::new(Shaper::new(config)),
+        }
+    }
+
+    /// Returns the shaping configuration of this interface
+    pub fn config(&self) -> &ShapingConfig {
+        &self.shaper.config
+    }
+}
+
+impl fmt::Debug for ShapingNetworking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShapingNetworking")
+            .field("inner", &self.inner)
+            .field("config", &self.shaper.config)
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for ShapingNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix)
+    }
+
+    fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip)
+    }
+
+    fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear()
+    }
+
+    fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list()
+    }
+
+    fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac()
+    }
+
+    fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip)
+    }
+
+    fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+    }
+
+    fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr)
+    }
+
+    fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear()
+    }
+
+    fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list()
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        self.inner.bind_raw().await
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let listener = self
+            .inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await?;
+        Ok(Box::new(ShapedTcpListener {
+            inner: listener,
+            shaper: self.shaper.clone(),
+        }))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let socket = self.inner.bind_udp(addr, reuse_port, reuse_addr).await?;
+        Ok(Box::new(ShapedUdpSocket::new(socket, self.shaper.clone())))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        self.inner.bind_icmp(addr).await
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let socket = self.inner.connect_tcp(addr, peer).await?;
+        Ok(Box::new(ShapedTcpSocket::new(socket, self.shaper.clone())))
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        self.inner.resolve(host, port, dns_server).await
+    }
+}
+
+/// Listener whose accepted connections are shaped
+#[derive(Debug)]
+pub struct ShapedTcpListener {
+    inner: Box<dyn VirtualTcpListener + Sync>,
+    shaper: Arc<Shaper>,
+}
+
+impl ShapedTcpListener {
+    fn wrap(&self, socket: Box<dyn VirtualTcpSocket + Sync>) -> Box<dyn VirtualTcpSocket + Sync> {
+        Box::new(ShapedTcpSocket::new(socket, self.shaper.clone()))
+    }
+}
+
+impl VirtualTcpListener for ShapedTcpListener {
+    fn try_accept(&mut self) -> Option<Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)>> {
+        self.inner
+            .try_accept()
+            .map(|ret| ret.map(|(sock, addr)| (self.wrap(sock), addr)))
+    }
+
+    fn poll_accept(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)>> {
+        self.inner
+            .poll_accept(cx)
+            .map_ok(|(sock, addr)| (self.wrap(sock), addr))
+    }
+
+    fn poll_accept_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_accept_ready(cx)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        self.inner.ttl()
+    }
+}
+
+/// TCP socket whose stream is rate limited and delayed
+#[derive(Debug)]
+pub struct ShapedTcpSocket {
+    inner: Box<dyn VirtualTcpSocket + Sync>,
+    shaper: Arc<Shaper>,
+    /// Data that was received but is still held back, an empty chunk
+    /// marks the end of the stream
+    rx: VecDeque<(Instant, Bytes)>,
+    rx_eof: bool,
+    rx_delay: Delay,
+    tx_delay: Delay,
+    read_buf: Vec<MaybeUninit<u8>>,
+}
+
+impl ShapedTcpSocket {
+    fn new(inner: Box<dyn VirtualTcpSocket + Sync>, shaper: Arc<Shaper>) -> Self {
+        Self {
+            inner,
+            shaper,
+            rx: VecDeque::new(),
+            rx_eof: false,
+            rx_delay: Delay::default(),
+            tx_delay: Delay::default(),
+            read_buf: Vec::new(),
+        }
+    }
+
+    fn recv(
+        &mut self,
+        mut cx: Option<&mut Context<'_>>,
+        buf: &mut [MaybeUninit<u8>],
+    ) -> Poll<Result<usize>> {
+        loop {
+            // Deliver anything that has been held back long enough
+            if let Some((deadline, data)) = self.rx.front_mut() {
+                if *deadline <= Instant::now() {
+                    if data.is_empty() {
+                        self.rx.pop_front();
+                        return Poll::Ready(Ok(0));
+                    }
+                    let amt = copy_out(data, buf);
+                    let _ = data.split_to(amt);
+                    if data.is_empty() {
+                        self.rx.pop_front();
+                    }
+                    return Poll::Ready(Ok(amt));
+                }
+            }
+
+            if self.rx_eof {
+                return self.hold(cx, None);
+            }
+            if let Err(deadline) = self.shaper.ingress_ready() {
+                return self.hold(cx, Some(deadline));
+            }
+
+            if self.read_buf.is_empty() {
+                self.read_buf
+                    .resize_with(READ_BUF_SIZE, MaybeUninit::uninit);
+            }
+            let ret = match cx.as_deref_mut() {
+                Some(cx) => self.inner.poll_recv(cx, &mut self.read_buf[..]),
+                None => would_block_to_pending(self.inner.try_recv(&mut self.read_buf[..])),
+            };
+            match ret {
+                Poll::Ready(Ok(amt)) => {
+                    let data: &[u8] = unsafe { std::mem::transmute(&self.read_buf[..amt]) };
+                    self.shaper.consume_ingress(amt);
+                    if amt == 0 {
+                        self.rx_eof = true;
+                    }
+                    let deadline = self.shaper.deliver_at();
+                    let deadline = match self.rx.back() {
+                        Some((last, _)) => deadline.max(*last),
+                        None => deadline,
+                    };
+                    self.rx.push_back((deadline, Bytes::copy_from_slice(data)));
+                }
+                Poll::Ready(Err(err)) => {
+                    if self.rx.is_empty() {
+                        return Poll::Ready(Err(err));
+                    }
+                    return self.hold(cx, None);
+                }
+                Poll::Pending => return self.hold(cx, None),
+            }
+        }
+    }
+
+    /// Waits for the next chunk of held back data to become deliverable,
+    /// or for the ingress limit to let more data in
+    fn hold(
+        &mut self,
+        cx: Option<&mut Context<'_>>,
+        ingress: Option<Instant>,
+    ) -> Poll<Result<usize>> {
+        let deadline = self.rx.front().map(|(deadline, _)| *deadline);
+        if let Some(deadline) = deadline.into_iter().chain(ingress).min() {
+            self.rx_delay.wait(cx, deadline);
+        }
+        Poll::Pending
+    }
+
+    fn send(&mut self, cx: Option<&mut Context<'_>>, data: &[u8]) -> Poll<Result<usize>> {
+        let allowed = match self.shaper.take_egress(data.len(), true) {
+            Ok(amt) => amt,
+            Err(deadline) => {
+                self.tx_delay.wait(cx, deadline);
+                return Poll::Pending;
+            }
+        };
+        let data = &data[..allowed];
+        let ret = match cx {
+            Some(cx) => self.inner.poll_send(cx, data),
+            None => would_block_to_pending(self.inner.try_send(data)),
+        };
+        match &ret {
+            Poll::Ready(Ok(amt)) => self.shaper.refund_egress(allowed - *amt),
+            _ => self.shaper.refund_egress(allowed),
+        }
+        ret
+    }
+}
+
+impl VirtualTcpSocket for ShapedTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.inner.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.inner.send_buf_size()
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        self.inner.addr_peer()
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    fn send_rights(&mut self, rights: UnixRights) -> Result<()> {
+        self.inner.send_rights(rights)
+    }
+
+    fn recv_rights(&mut self) -> Result<Option<UnixRights>> {
+        self.inner.recv_rights()
+    }
+}
+
+impl VirtualConnectedSocket for ShapedTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        pending_to_would_block(self.send(None, data))
+    }
+
+    fn poll_send(&mut self, cx: &mut Context<'_>, data: &[u8]) -> Poll<Result<usize>> {
+        self.send(Some(cx), data)
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.inner.poll_flush(cx)
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.close()
+    }
+
+    fn poll_recv<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &'a mut [MaybeUninit<u8>],
+    ) -> Poll<Result<usize>> {
+        self.recv(Some(cx), buf)
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        pending_to_would_block(self.recv(None, buf))
+    }
+}
+
+impl VirtualSocket for ShapedTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        match self.rx.front() {
+            Some((deadline, data)) if *deadline <= Instant::now() => Poll::Ready(Ok(data.len())),
+            Some((deadline, _)) => {
+                let deadline = *deadline;
+                self.rx_delay.wait(Some(cx), deadline);
+                Poll::Pending
+            }
+            None if self.rx_eof => Poll::Ready(Ok(0)),
+            None => self.inner.poll_read_ready(cx),
+        }
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+/// UDP socket whose datagrams are rate limited, delayed and dropped
+#[derive(Debug)]
+pub struct ShapedUdpSocket {
+    inner: Box<dyn VirtualUdpSocket + Sync>,
+    shaper: Arc<Shaper>,
+    rx: VecDeque<(Instant, Bytes, SocketAddr)>,
+    rx_delay: Delay,
+    tx_delay: Delay,
+    read_buf: Vec<MaybeUninit<u8>>,
+}
+
+impl ShapedUdpSocket {
+    fn new(inner: Box<dyn VirtualUdpSocket + Sync>, shaper: Arc<Shaper>) -> Self {
+        Self {
+            inner,
+            shaper,
+            rx: VecDeque::new(),
+            rx_delay: Delay::default(),
+            tx_delay: Delay::default(),
+            read_buf: Vec::new(),
+        }
+    }
+
+    fn recv_from(
+        &mut self,
+        mut cx: Option<&mut Context<'_>>,
+        buf: &mut [MaybeUninit<u8>],
+    ) -> Poll<Result<(usize, SocketAddr)>> {
+        loop {
+            if let Some((deadline, _, _)) = self.rx.front() {
+                if *deadline <= Instant::now() {
+                    let (_, data, addr) = self.rx.pop_front().unwrap();
+                    return Poll::Ready(Ok((copy_out(&data, buf), addr)));
+                }
+            }
+
+            if let Err(deadline) = self.shaper.ingress_ready() {
+                return self.hold(cx, Some(deadline));
+            }
+
+            if self.read_buf.is_empty() {
+                self.read_buf
+                    .resize_with(READ_BUF_SIZE, MaybeUninit::uninit);
+            }
+            let ret = match cx.as_deref_mut() {
+                Some(cx) => self.inner.poll_recv_from(cx, &mut self.read_buf[..]),
+                None => would_block_to_pending(self.inner.try_recv_from(&mut self.read_buf[..])),
+            };
+            match ret {
+                Poll::Ready(Ok((amt, addr))) => {
+                    self.shaper.consume_ingress(amt);
+                    if self.shaper.lose() {
+                        continue;
+                    }
+                    let data: &[u8] = unsafe { std::mem::transmute(&self.read_buf[..amt]) };
+                    let deadline = self.shaper.deliver_at();
+                    self.rx
+                        .push_back((deadline, Bytes::copy_from_slice(data), addr));
+                    self.rx
+                        .make_contiguous()
+                        .sort_by_key(|(deadline, _, _)| *deadline);
+                }
+                Poll::Ready(Err(err)) => {
+                    if self.rx.is_empty() {
+                        return Poll::Ready(Err(err));
+                    }
+                    return self.hold(cx, None);
+                }
+                Poll::Pending => return self.hold(cx, None),
+            }
+        }
+    }
+
+    fn hold(
+        &mut self,
+        cx: Option<&mut Context<'_>>,
+        ingress: Option<Instant>,
+    ) -> Poll<Result<(usize, SocketAddr)>> {
+        let deadline = self.rx.front().map(|(deadline, _, _)| *deadline);
+        if let Some(deadline) = deadline.into_iter().chain(ingress).min() {
+            self.rx_delay.wait(cx, deadline);
+        }
+        Poll::Pending
+    }
+
+    fn send_to(
+        &mut self,
+        cx: Option<&mut Context<'_>>,
+        data: &[u8],
+        addr: SocketAddr,
+    ) -> Poll<Result<usize>> {
+        if let Err(deadline) = self.shaper.take_egress(data.len(), false) {
+            self.tx_delay.wait(cx, deadline);
+            return Poll::Pending;
+        }
+        if self.shaper.lose() {
+            return Poll::Ready(Ok(data.len()));
+        }
+        let ret = match cx {
+            Some(cx) => self.inner.poll_send_to(cx, data, addr),
+            None => would_block_to_pending(self.inner.try_send_to(data, addr)),
+        };
+        if !matches!(ret, Poll::Ready(Ok(_))) {
+            self.shaper.refund_egress(data.len());
+        }
+        ret
+    }
+}
+
+impl VirtualUdpSocket for ShapedUdpSocket {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        self.inner.broadcast()
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v4(val)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        self.inner.multicast_loop_v4()
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v6(val)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        self.inner.multicast_loop_v6()
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.inner.multicast_ttl_v4()
+    }
+
+    fn join_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.join_multicast_v4(multiaddr, iface)
+    }
+
+    fn leave_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.leave_multicast_v4(multiaddr, iface)
+    }
+
+    fn join_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.join_multicast_v6(multiaddr, iface)
+    }
+
+    fn leave_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.leave_multicast_v6(multiaddr, iface)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        self.inner.addr_peer()
+    }
+
+    fn connect_unix(&mut self, peer: UnixSocketAddr) -> Result<()> {
+        self.inner.connect_unix(peer)
+    }
+}
+
+impl VirtualConnectionlessSocket for ShapedUdpSocket {
+    fn poll_send_to(
+        &mut self,
+        cx: &mut Context<'_>,
+        data: &[u8],
+        addr: SocketAddr,
+    ) -> Poll<Result<usize>> {
+        self.send_to(Some(cx), data, addr)
+    }
+
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        pending_to_would_block(self.send_to(None, data, addr))
+    }
+
+    fn poll_recv_from<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &'a mut [MaybeUninit<u8>],
+    ) -> Poll<Result<(usize, SocketAddr)>> {
+        self.recv_from(Some(cx), buf)
+    }
+
+    fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        pending_to_would_block(self.recv_from(None, buf))
+    }
+}
+
+impl VirtualSocket for ShapedUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        match self.rx.front() {
+            Some((deadline, data, _)) if *deadline <= Instant::now() => Poll::Ready(Ok(data.len())),
+            Some((deadline, _, _)) => {
+                let deadline = *deadline;
+                self.rx_delay.wait(Some(cx), deadline);
+                Poll::Pending
+            }
+            None => self.inner.poll_read_ready(cx),
+        }
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::advance;
+
+    use super::*;
+    use crate::{BridgePort, VirtualBridge};
+
+    const SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+
+    /// Server and client ports on a segment, where the traffic of the
+    /// client is shaped
+    fn segment(bridge: &VirtualBridge, config: ShapingConfig) -> (BridgePort, ShapingNetworking) {
+        let server = bridge.attach("server", vec![SERVER]).unwrap();
+        let client = bridge.attach("client", vec![CLIENT]).unwrap();
+        (server, ShapingNetworking::new(Arc::new(client), config))
+    }
+
+    /// Connects the client to the server, returning the (shaped) client
+    /// end and the server end
+    async fn connect(
+        server: &BridgePort,
+        client: &ShapingNetworking,
+    ) -> (
+        Box<dyn VirtualTcpSocket + Sync>,
+        Box<dyn VirtualTcpSocket + Sync>,
+    ) {
+        let mut listener = server
+            .listen_tcp(SocketAddr::new(SERVER, 80), false, false, false)
+            .await
+            .unwrap();
+        let outbound = client
+            .connect_tcp(
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+                SocketAddr::new(SERVER, 80),
+            )
+            .await
+            .unwrap();
+        let (inbound, _) = listener.try_accept().unwrap().unwrap();
+        (outbound, inbound)
+    }
+
+    fn recv(socket: &mut (dyn VirtualTcpSocket + Sync)) -> Result<Vec<u8>> {
+        let mut buf = vec![MaybeUninit::new(0u8); READ_BUF_SIZE];
+        let n = socket.try_recv(&mut buf)?;
+        Ok(buf[..n]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn egress_is_limited_to_the_rate() {
+        let bridge = VirtualBridge::new();
+        let config = ShapingConfig {
+            egress_rate: Some(1_000),
+            burst: 1_000,
+            ..Default::default()
+        };
+        let (server, client) = segment(&bridge, config);
+        let (mut outbound, mut inbound) = connect(&server, &client).await;
+
+        // The burst goes out straight away, the rest has to wait
+        assert_eq!(outbound.try_send(&[1; 1_500]).unwrap(), 1_000);
+        assert_eq!(
+            outbound.try_send(&[1; 500]).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+        assert_eq!(recv(inbound.as_mut()).unwrap().len(), 1_000);
+
+        advance(Duration::from_millis(250)).await;
+        assert_eq!(outbound.try_send(&[1; 500]).unwrap(), 250);
+        advance(Duration::from_secs(10)).await;
+        assert_eq!(outbound.try_send(&[1; 1_500]).unwrap(), 1_000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ingress_is_held_back_for_the_latency() {
+        let bridge = VirtualBridge::new();
+        let config = ShapingConfig {
+            latency: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let (server, client) = segment(&bridge, config);
+        let (mut outbound, mut inbound) = connect(&server, &client).await;
+
+        assert_eq!(inbound.try_send(b"hello").unwrap(), 5);
+        assert_eq!(
+            recv(outbound.as_mut()).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+        advance(Duration::from_millis(99)).await;
+        assert_eq!(
+            recv(outbound.as_mut()).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+        advance(Duration::from_millis(1)).await;
+        assert_eq!(recv(outbound.as_mut()).unwrap(), b"hello");
+
+        // Outbound traffic isn't delayed
+        assert_eq!(outbound.try_send(b"world").unwrap(), 5);
+        assert_eq!(recv(inbound.as_mut()).unwrap(), b"world");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pending_operations_are_woken_up_by_the_tokio_clock() {
+        let bridge = VirtualBridge::new();
+        let config = ShapingConfig {
+            egress_rate: Some(1_000),
+            burst: 1_000,
+            latency: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let (server, client) = segment(&bridge, config);
+        let (mut outbound, mut inbound) = connect(&server, &client).await;
+
+        // The paused clock only moves forward when a timer is due, so these
+        // complete as soon as the shaping lets them
+        let start = Instant::now();
+        assert_eq!(inbound.try_send(b"hello").unwrap(), 5);
+        let mut buf = vec![MaybeUninit::new(0u8); READ_BUF_SIZE];
+        let n = std::future::poll_fn(|cx| outbound.poll_recv(cx, &mut buf))
+            .await
+            .unwrap();
+        assert_eq!(n, 5);
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+
+        let start = Instant::now();
+        assert_eq!(outbound.try_send(&[1; 1_000]).unwrap(), 1_000);
+        let n = std::future::poll_fn(|cx| outbound.poll_send(cx, &[1; 500]))
+            .await
+            .unwrap();
+        assert!(n > 0);
+        assert!(start.elapsed() > Duration::ZERO);
+        assert_eq!(recv(inbound.as_mut()).unwrap().len(), 1_000 + n);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ingress_beyond_the_burst_is_paid_back_before_more_is_read() {
+        let bridge = VirtualBridge::new();
+        let config = ShapingConfig {
+            ingress_rate: Some(1_000),
+            burst: 1_000,
+            ..Default::default()
+        };
+        let (server, client) = segment(&bridge, config);
+        let (mut outbound, mut inbound) = connect(&server, &client).await;
+
+        assert_eq!(inbound.try_send(&[1; 3_000]).unwrap(), 3_000);
+        assert_eq!(recv(outbound.as_mut()).unwrap().len(), 3_000);
+
+        assert_eq!(inbound.try_send(b"more").unwrap(), 4);
+        advance(Duration::from_secs(2)).await;
+        assert_eq!(
+            recv(outbound.as_mut()).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+        advance(Duration::from_millis(1)).await;
+        assert_eq!(recv(outbound.as_mut()).unwrap(), b"more");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn datagrams_are_lost() {
+        let bridge = VirtualBridge::new();
+        let config = ShapingConfig {
+            loss: 1.0,
+            ..Default::default()
+        };
+        let (server, client) = segment(&bridge, config);
+        let (c, s) = (SocketAddr::new(CLIENT, 5000), SocketAddr::new(SERVER, 53));
+        let mut inbound = server.bind_udp(s, false, false).await.unwrap();
+        let mut outbound = client.bind_udp(c, false, false).await.unwrap();
+        let mut buf = [MaybeUninit::new(0u8); 64];
+
+        // Lost datagrams still look like they were sent
+        assert_eq!(outbound.try_send_to(b"query", s).unwrap(), 5);
+        assert_eq!(
+            inbound.try_recv_from(&mut buf).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+
+        assert_eq!(inbound.try_send_to(b"answer", c).unwrap(), 6);
+        assert_eq!(
+            outbound.try_recv_from(&mut buf).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn datagrams_are_delayed_by_the_latency_and_jitter() {
+        let bridge = VirtualBridge::new();
+        let config = ShapingConfig {
+            latency: Duration::from_millis(10),
+            jitter: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let (server, client) = segment(&bridge, config);
+        let (c, s) = (SocketAddr::new(CLIENT, 5000), SocketAddr::new(SERVER, 53));
+        let mut inbound = server.bind_udp(s, false, false).await.unwrap();
+        let mut outbound = client.bind_udp(c, false, false).await.unwrap();
+        let mut buf = [MaybeUninit::new(0u8); 64];
+
+        for i in 0..16u8 {
+            inbound.try_send_to(&[i], c).unwrap();
+        }
+        assert_eq!(
+            outbound.try_recv_from(&mut buf).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+
+        // Nothing arrives before the latency, everything by the maximum
+        // jitter on top of it
+        advance(Duration::from_millis(9)).await;
+        assert_eq!(
+            outbound.try_recv_from(&mut buf).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+        advance(Duration::from_millis(51)).await;
+        let mut received = Vec::new();
+        while let Ok((n, from)) = outbound.try_recv_from(&mut buf) {
+            assert_eq!((n, from), (1, s));
+            received.push(unsafe { buf[0].assume_init() });
+        }
+        received.sort_unstable();
+        assert_eq!(received, (0..16).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,1003 @@
+//! Transparent TLS for guest sockets.
+//!
+//! [`TlsNetworking`] wraps another [`VirtualNetworking`] implementation and
+//! terminates or originates TLS on behalf of the guest. The guest only ever
+//! sees plaintext on its sockets while the host side of the connection is
+//! encrypted using `rustls`, which means that small guests do not need to
+//! bundle their own TLS stack.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{Read, Write};
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Shutdown, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use rustls::{
+    Certificate, ClientConfig, ClientConnection, Connection, OwnedTrustAnchor, PrivateKey,
+    RootCertStore, ServerConfig, ServerConnection, ServerName,
+};
+#[allow(unused_imports, dead_code)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::{
+    DynVirtualNetworking, IpCidr, IpRoute, NetworkError, Result, SocketStatus, StreamSecurity,
+    VirtualConnectedSocket, VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket, VirtualSocket,
+    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+
+/// Size of the chunks of ciphertext that are read from the inner socket
+const TLS_READ_CHUNK: usize = 16 * 1024;
+
+/// Amount of buffered ciphertext after which sends will apply backpressure
+const TLS_SEND_HIGH_WATER: usize = 64 * 1024;
+
+/// Number of host name look ups that are remembered for SNI
+const MAX_RESOLVED_NAMES: usize = 256;
+
+/// Set of certificate authorities that are trusted when the runtime
+/// originates TLS connections on behalf of a guest
+#[derive(Clone)]
+pub struct TlsTrust {
+    roots: RootCertStore,
+}
+
+impl TlsTrust {
+    /// Creates a trust store that does not trust any certificate authority
+    pub fn empty() -> Self {
+        Self {
+            roots: RootCertStore::empty(),
+        }
+    }
+
+    /// Creates a trust store that trusts the Mozilla set of root
+    /// certificate authorities (as bundled by `webpki-roots`)
+    pub fn webpki_roots() -> Self {
+        let mut roots = RootCertStore::empty();
+        roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+        Self { roots }
+    }
+
+    /// Adds a DER encoded certificate authority to the trust store
+    pub fn add_der(&mut self, der: impl Into<Vec<u8>>) -> Result<&mut Self> {
+        self.roots.add(&Certificate(der.into())).map_err(|err| {
+            warn!("invalid TLS trust anchor - {}", err);
+            NetworkError::InvalidData
+        })?;
+        Ok(self)
+    }
+
+    /// Adds all the certificate authorities found in a PEM bundle
+    pub fn add_pem(&mut self, pem: &[u8]) -> Result<&mut Self> {
+        for der in parse_pem_certs(pem)? {
+            self.add_der(der)?;
+        }
+        Ok(self)
+    }
+
+    /// Returns the number of certificate authorities that are trusted
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    /// Returns true if no certificate authorities are trusted
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+}
+
+impl Default for TlsTrust {
+    fn default() -> Self {
+        Self::webpki_roots()
+    }
+}
+
+impl fmt::Debug for TlsTrust {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsTrust")
+            .field("roots", &self.roots.len())
+            .finish()
+    }
+}
+
+/// Certificate and private key used when the runtime terminates TLS
+/// connections on behalf of a guest
+#[derive(Clone)]
+pub struct TlsIdentity {
+    chain: Vec<Certificate>,
+    key: PrivateKey,
+}
+
+impl TlsIdentity {
+    /// Creates an identity from a DER encoded certificate chain and private key
+    pub fn from_der(chain: Vec<Vec<u8>>, key: Vec<u8>) -> Self {
+        Self {
+            chain: chain.into_iter().map(Certificate).collect(),
+            key: PrivateKey(key),
+        }
+    }
+
+    /// Creates an identity from a PEM encoded certificate chain and a PEM
+    /// encoded (PKCS#8, RSA or SEC1) private key
+    pub fn from_pem(chain: &[u8], key: &[u8]) -> Result<Self> {
+        let chain = parse_pem_certs(chain)?;
+        if chain.is_empty() {
+            return Err(NetworkError::InvalidData);
+        }
+
+        let mut reader = std::io::BufReader::new(key);
+        loop {
+            match rustls_pemfile::read_one(&mut reader).map_err(|_| NetworkError::InvalidData)? {
+                Some(rustls_pemfile::Item::PKCS8Key(key))
+                | Some(rustls_pemfile::Item::RSAKey(key))
+                | Some(rustls_pemfile::Item::ECKey(key)) => {
+                    return Ok(Self::from_der(chain, key));
+                }
+                Some(_) => continue,
+                None => return Err(NetworkError::InvalidData),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for TlsIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsIdentity")
+            .field("chain", &self.chain.len())
+            .finish()
+    }
+}
+
+fn parse_pem_certs(pem: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut reader = std::io::BufReader::new(pem);
+    rustls_pemfile::certs(&mut reader).map_err(|_| NetworkError::InvalidData)
+}
+
+/// A host name that was looked up by the guest
+#[derive(Debug)]
+struct ResolvedName {
+    host: String,
+    addrs: Vec<IpAddr>,
+    /// Set once the guest connected to one of the addresses
+    connected: bool,
+}
+
+/// Host names that were looked up by the guest, used for SNI and
+/// certificate verification when the guest then connects by IP address.
+///
+/// Several names may resolve to the same address (e.g. virtual hosts behind
+/// a CDN), so the guest is assumed to connect in the order it looked them
+/// up: the oldest look up that has not been connected to yet wins. Once a
+/// look up was connected to, it keeps being used for that address so that
+/// guests which cache their look ups still get the right name.
+#[derive(Debug, Default)]
+struct ResolvedNames {
+    names: VecDeque<ResolvedName>,
+}
+
+impl ResolvedNames {
+    fn insert(&mut self, host: &str, addrs: Vec<IpAddr>) {
+        if self.names.len() >= MAX_RESOLVED_NAMES {
+            self.names.pop_front();
+        }
+        self.names.push_back(ResolvedName {
+            host: host.to_string(),
+            addrs,
+            connected: false,
+        });
+    }
+
+    /// Returns the host name that the guest means when connecting to `ip`
+    fn host_for(&self, ip: IpAddr) -> Option<String> {
+        self.names
+            .iter()
+            .find(|name| !name.connected && name.addrs.contains(&ip))
+            .or_else(|| {
+                self.names
+                    .iter()
+                    .rev()
+                    .find(|name| name.addrs.contains(&ip))
+            })
+            .map(|name| name.host.clone())
+    }
+
+    /// Records that the guest connected to `ip` using `host`
+    fn connected(&mut self, ip: IpAddr, host: &str) {
+        let idx = self
+            .names
+            .iter()
+            .position(|name| !name.connected && name.host == host && name.addrs.contains(&ip));
+        if let Some(mut name) = idx.and_then(|idx| self.names.remove(idx)) {
+            name.connected = true;
+            self.names.push_back(name);
+        }
+    }
+}
+
+/// Networking implementation that transparently wraps guest TCP
+/// connections in TLS.
+///
+/// Outbound connections to any of the configured ports are encrypted by
+/// the host (TLS origination) while inbound connections accepted on any
+/// of the configured listening ports are decrypted by the host before the
+/// guest sees them (TLS termination). Everything else is passed through to
+/// the inner networking implementation untouched.
+pub struct TlsNetworking {
+    inner: DynVirtualNetworking,
+    client: Option<Arc<ClientConfig>>,
+    client_ports: HashSet<u16>,
+    server_name: Option<String>,
+    servers: HashMap<u16, Arc<ServerConfig>>,
+    names: Mutex<ResolvedNames>,
+}
+
+impl TlsNetworking {
+    /// Wraps an existing networking implementation, by default no
+    /// connections are encrypted
+    pub fn new(inner: DynVirtualNetworking) -> Self {
+        Self {
+            inner,
+            client: None,
+            client_ports: HashSet::new(),
+            server_name: None,
+            servers: HashMap::new(),
+            names: Mutex::new(ResolvedNames::default()),
+        }
+    }
+
+    /// Originates TLS for all outbound connections to any of the supplied
+    /// ports, verifying the remote peer against the supplied trust store
+    pub fn with_origination(
+        mut self,
+        trust: TlsTrust,
+        ports: impl IntoIterator<Item = u16>,
+    ) -> Self {
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(trust.roots)
+            .with_no_client_auth();
+        self.client = Some(Arc::new(config));
+        self.client_ports.extend(ports);
+        self
+    }
+
+    /// Overrides the server name used for SNI and certificate verification
+    /// whenever the guest connects to an address it did not resolve itself
+    pub fn with_server_name(mut self, name: impl Into<String>) -> Self {
+        self.server_name = Some(name.into());
+        self
+    }
+
+    /// Terminates TLS for all connections accepted on the supplied port
+    /// using the supplied identity
+    pub fn with_termination(mut self, port: u16, identity: TlsIdentity) -> Result<Self> {
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(identity.chain, identity.key)
+            .map_err(|err| {
+                warn!("invalid TLS identity - {}", err);
+                NetworkError::InvalidData
+            })?;
+        self.servers.insert(port, Arc::new(config));
+        Ok(self)
+    }
+
+    fn server_name_for(&self, peer: &SocketAddr) -> Option<String> {
+        let names = self.names.lock().unwrap();
+        names
+            .host_for(peer.ip())
+            .or_else(|| self.server_name.clone())
+    }
+}
+
+impl fmt::Debug for TlsNetworking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsNetworking")
+            .field("inner", &self.inner)
+            .field("client_ports", &self.client_ports)
+            .field("server_ports", &self.servers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for TlsNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix)
+    }
+
+    fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip)
+    }
+
+    fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear()
+    }
+
+    fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list()
+    }
+
+    fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac()
+    }
+
+    fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip)
+    }
+
+    fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+    }
+
+    fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr)
+    }
+
+    fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear()
+    }
+
+    fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list()
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        self.inner.bind_raw().await
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let listener = self
+            .inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await?;
+        match self.servers.get(&addr.port()) {
+            Some(config) => Ok(Box::new(TlsTcpListener {
+                inner: listener,
+                config: config.clone(),
+            })),
+            None => Ok(listener),
+        }
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        self.inner.bind_udp(addr, reuse_port, reuse_addr).await
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        self.inner.bind_icmp(addr).await
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let client = match self.client.as_ref() {
+            Some(client) if self.client_ports.contains(&peer.port()) => client.clone(),
+            _ => return self.inner.connect_tcp(addr, peer).await,
+        };
+
+        let name = self.server_name_for(&peer).ok_or_else(|| {
+            warn!(
+                "refusing to originate TLS to {} as its server name is unknown",
+                peer
+            );
+            NetworkError::AddressNotAvailable
+        })?;
+        let server_name =
+            ServerName::try_from(name.as_str()).map_err(|_| NetworkError::InvalidInput)?;
+        let tls = ClientConnection::new(client, server_name).map_err(|err| {
+            warn!("failed to create TLS session - {}", err);
+            NetworkError::InvalidInput
+        })?;
+
+        let socket = self.inner.connect_tcp(addr, peer).await?;
+        self.names.lock().unwrap().connected(peer.ip(), &name);
+        Ok(Box::new(TlsTcpSocket::new(socket, tls.into())))
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        let addrs = self.inner.resolve(host, port, dns_server).await?;
+        if self.client.is_some() && !addrs.is_empty() && host.parse::<IpAddr>().is_err() {
+            let mut names = self.names.lock().unwrap();
+            names.insert(host, addrs.clone());
+        }
+        Ok(addrs)
+    }
+}
+
+/// Listener that terminates TLS on every connection that it accepts
+#[derive(Debug)]
+pub struct TlsTcpListener {
+    inner: Box<dyn VirtualTcpListener + Sync>,
+    config: Arc<ServerConfig>,
+}
+
+impl TlsTcpListener {
+    fn wrap(
+        &self,
+        socket: Box<dyn VirtualTcpSocket + Sync>,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let tls = ServerConnection::new(self.config.clone()).map_err(|err| {
+            warn!("failed to create TLS session - {}", err);
+            NetworkError::ConnectionAborted
+        })?;
+        Ok(Box::new(TlsTcpSocket::new(socket, tls.into())))
+    }
+}
+
+impl VirtualTcpListener for TlsTcpListener {
+    fn try_accept(&mut self) -> Option<Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)>> {
+        self.inner
+            .try_accept()
+            .map(|ret| ret.and_then(|(sock, addr)| Ok((self.wrap(sock)?, addr))))
+    }
+
+    fn poll_accept(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)>> {
+        self.inner
+            .poll_accept(cx)
+            .map(|ret| ret.and_then(|(sock, addr)| Ok((self.wrap(sock)?, addr))))
+    }
+
+    fn poll_accept_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_accept_ready(cx)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        self.inner.ttl()
+    }
+}
+
+/// TCP socket that exposes plaintext to the guest while the inner socket
+/// carries the TLS encrypted stream
+pub struct TlsTcpSocket {
+    inner: Box<dyn VirtualTcpSocket + Sync>,
+    tls: Connection,
+    /// Ciphertext that is waiting to be written to the inner socket
+    tx_buf: Vec<u8>,
+    /// Number of plaintext bytes that can be read without blocking
+    rx_ready: usize,
+    /// Set when the inner socket reached the end of its stream
+    eof: bool,
+}
+
+impl TlsTcpSocket {
+    fn new(inner: Box<dyn VirtualTcpSocket + Sync>, tls: Connection) -> Self {
+        Self {
+            inner,
+            tls,
+            tx_buf: Vec::new(),
+            rx_ready: 0,
+            eof: false,
+        }
+    }
+
+    /// Moves any pending TLS records into the transmit buffer
+    fn encode(&mut self) -> Result<()> {
+        while self.tls.wants_write() {
+            self.tls
+                .write_tls(&mut self.tx_buf)
+                .map_err(tls_io_err_into_net_error)?;
+        }
+        Ok(())
+    }
+
+    /// Feeds ciphertext that was received from the peer into the TLS session
+    fn decode(&mut self, mut data: &[u8]) -> Result<()> {
+        while !data.is_empty() {
+            self.tls
+                .read_tls(&mut data)
+                .map_err(tls_io_err_into_net_error)?;
+            let state = self.tls.process_new_packets().map_err(|err| {
+                debug!("TLS session failed - {}", err);
+                NetworkError::InvalidData
+            })?;
+            self.rx_ready = state.plaintext_bytes_to_read();
+        }
+        Ok(())
+    }
+
+    /// Reads any plaintext that has already been decrypted
+    fn read_plaintext(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self.tls.reader().read(buf) {
+            Ok(amt) => {
+                self.rx_ready = self.rx_ready.saturating_sub(amt);
+                Ok(amt)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock && self.eof => Ok(0),
+            Err(err) => Err(tls_io_err_into_net_error(err)),
+        }
+    }
+
+    fn poll_transmit(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.encode()?;
+        while !self.tx_buf.is_empty() {
+            match self.inner.poll_send(cx, &self.tx_buf) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(NetworkError::WriteZero)),
+                Poll::Ready(Ok(amt)) => {
+                    self.tx_buf.drain(..amt);
+                }
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn try_transmit(&mut self) -> Result<()> {
+        self.encode()?;
+        while !self.tx_buf.is_empty() {
+            match self.inner.try_send(&self.tx_buf) {
+                Ok(0) => return Err(NetworkError::WriteZero),
+                Ok(amt) => {
+                    self.tx_buf.drain(..amt);
+                }
+                Err(NetworkError::WouldBlock) => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_receive(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut chunk = [MaybeUninit::<u8>::uninit(); TLS_READ_CHUNK];
+        match self.inner.poll_recv(cx, &mut chunk) {
+            Poll::Ready(Ok(0)) => {
+                self.eof = true;
+                Poll::Ready(Ok(0))
+            }
+            Poll::Ready(Ok(amt)) => {
+                let data: &[u8] = unsafe { std::mem::transmute(&chunk[..amt]) };
+                self.decode(data)?;
+                Poll::Ready(Ok(amt))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn try_receive(&mut self) -> Result<usize> {
+        let mut chunk = [MaybeUninit::<u8>::uninit(); TLS_READ_CHUNK];
+        match self.inner.try_recv(&mut chunk) {
+            Ok(0) => {
+                self.eof = true;
+                Ok(0)
+            }
+            Ok(amt) => {
+                let data: &[u8] = unsafe { std::mem::transmute(&chunk[..amt]) };
+                self.decode(data)?;
+                Ok(amt)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl fmt::Debug for TlsTcpSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsTcpSocket")
+            .field("inner", &self.inner)
+            .field("handshaking", &self.tls.is_handshaking())
+            .field("tx_buf", &self.tx_buf.len())
+            .field("rx_ready", &self.rx_ready)
+            .finish()
+    }
+}
+
+impl VirtualTcpSocket for TlsTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.inner.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.inner.send_buf_size()
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        self.inner.addr_peer()
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        if matches!(how, Shutdown::Write | Shutdown::Both) {
+            self.tls.send_close_notify();
+            self.try_transmit()?;
+        }
+        self.inner.shutdown(how)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+impl VirtualConnectedSocket for TlsTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        self.try_transmit()?;
+        if self.tx_buf.len() >= TLS_SEND_HIGH_WATER {
+            return Err(NetworkError::WouldBlock);
+        }
+        let amt = self
+            .tls
+            .writer()
+            .write(data)
+            .map_err(tls_io_err_into_net_error)?;
+        self.try_transmit()?;
+        Ok(amt)
+    }
+
+    fn poll_send(&mut self, cx: &mut Context<'_>, data: &[u8]) -> Poll<Result<usize>> {
+        match self.poll_transmit(cx) {
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            Poll::Pending if self.tx_buf.len() >= TLS_SEND_HIGH_WATER => return Poll::Pending,
+            _ => {}
+        }
+        let amt = self
+            .tls
+            .writer()
+            .write(data)
+            .map_err(tls_io_err_into_net_error)?;
+        if let Poll::Ready(Err(err)) = self.poll_transmit(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(amt))
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        match self.poll_transmit(cx) {
+            Poll::Ready(Ok(())) => self.inner.poll_flush(cx),
+            other => other,
+        }
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.tls.send_close_notify();
+        self.try_transmit().ok();
+        self.inner.close()
+    }
+
+    fn poll_recv(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [MaybeUninit<u8>],
+    ) -> Poll<Result<usize>> {
+        let buf = zeroed(buf);
+        loop {
+            match self.read_plaintext(buf) {
+                Err(NetworkError::WouldBlock) => {}
+                ret => return Poll::Ready(ret),
+            }
+
+            // The handshake may need to write before it can read anything
+            if let Poll::Ready(Err(err)) = self.poll_transmit(cx) {
+                return Poll::Ready(Err(err));
+            }
+
+            match self.poll_receive(cx) {
+                Poll::Ready(Ok(_)) => continue,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        let buf = zeroed(buf);
+        loop {
+            match self.read_plaintext(buf) {
+                Err(NetworkError::WouldBlock) => {}
+                ret => return ret,
+            }
+            self.try_transmit()?;
+            self.try_receive()?;
+        }
+    }
+}
+
+impl VirtualSocket for TlsTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        match self.inner.status()? {
+            SocketStatus::Opened if self.tls.is_handshaking() => Ok(SocketStatus::Opening),
+            status => Ok(status),
+        }
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        loop {
+            if self.rx_ready > 0 {
+                return Poll::Ready(Ok(self.rx_ready));
+            }
+            if self.eof {
+                return Poll::Ready(Ok(0));
+            }
+            if let Poll::Ready(Err(err)) = self.poll_transmit(cx) {
+                return Poll::Ready(Err(err));
+            }
+            match self.poll_receive(cx) {
+                Poll::Ready(Ok(_)) => continue,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        match self.poll_transmit(cx) {
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending if self.tx_buf.len() >= TLS_SEND_HIGH_WATER => Poll::Pending,
+            _ => self.inner.poll_write_ready(cx),
+        }
+    }
+}
+
+/// Initializes a receive buffer, `rustls` reads plaintext through
+/// `std::io::Read` which is only defined for initialized memory
+fn zeroed(buf: &mut [MaybeUninit<u8>]) -> &mut [u8] {
+    for byte in buf.iter_mut() {
+        *byte = MaybeUninit::new(0);
+    }
+    // Every byte of the buffer was initialized above
+    unsafe { &mut *(buf as *mut [MaybeUninit<u8>] as *mut [u8]) }
+}
+
+fn tls_io_err_into_net_error(err: std::io::Error) -> NetworkError {
+    use std::io::ErrorKind;
+    match err.kind() {
+        ErrorKind::WouldBlock => NetworkError::WouldBlock,
+        ErrorKind::UnexpectedEof => NetworkError::UnexpectedEof,
+        ErrorKind::InvalidData => NetworkError::InvalidData,
+        ErrorKind::InvalidInput => NetworkError::InvalidInput,
+        ErrorKind::BrokenPipe => NetworkError::BrokenPipe,
+        ErrorKind::ConnectionReset => NetworkError::ConnectionReset,
+        ErrorKind::ConnectionAborted => NetworkError::ConnectionAborted,
+        ErrorKind::WriteZero => NetworkError::WriteZero,
+        _ => NetworkError::IOError,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::VirtualBridge;
+
+    const SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+
+    /// Server and client sides of a segment where the server terminates
+    /// TLS on port 443 with a self-signed certificate for `server.test`
+    /// that the client trusts
+    fn segment(segment: &VirtualBridge) -> (TlsNetworking, TlsNetworking) {
+        let cert = rcgen::generate_simple_self_signed(vec!["server.test".to_string()]).unwrap();
+        let der = cert.serialize_der().unwrap();
+        let identity = TlsIdentity::from_der(vec![der.clone()], cert.serialize_private_key_der());
+        let mut trust = TlsTrust::empty();
+        trust.add_der(der).unwrap();
+
+        let server = segment.attach("server.test", vec![SERVER]).unwrap();
+        let client = segment.attach("client.test", vec![CLIENT]).unwrap();
+        let server = TlsNetworking::new(Arc::new(server))
+            .with_termination(443, identity)
+            .unwrap();
+        let client = TlsNetworking::new(Arc::new(client)).with_origination(trust, [443]);
+        (server, client)
+    }
+
+    fn any(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port)
+    }
+
+    /// Receives plaintext on `socket`, giving `peer` the chance to answer
+    /// the handshake in between
+    fn recv(
+        socket: &mut (dyn VirtualTcpSocket + Sync),
+        peer: &mut (dyn VirtualTcpSocket + Sync),
+    ) -> Result<Vec<u8>> {
+        let mut buf = [MaybeUninit::new(0u8); 64];
+        for _ in 0..8 {
+            match socket.try_recv(&mut buf) {
+                Ok(n) => {
+                    return Ok(buf[..n]
+                        .iter()
+                        .map(|b| unsafe { b.assume_init() })
+                        .collect())
+                }
+                Err(NetworkError::WouldBlock) => {}
+                Err(err) => return Err(err),
+            }
+            match peer.try_recv(&mut [MaybeUninit::new(0u8); 64]) {
+                Ok(_) | Err(NetworkError::WouldBlock) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Err(NetworkError::WouldBlock)
+    }
+
+    #[tokio::test]
+    async fn the_guests_only_see_plaintext() {
+        let bridge = VirtualBridge::new();
+        let (server, client) = segment(&bridge);
+        let mut listener = server
+            .listen_tcp(any(443), false, false, false)
+            .await
+            .unwrap();
+
+        let addrs = client.resolve("server.test", None, None).await.unwrap();
+        assert_eq!(addrs, vec![SERVER]);
+        let mut outbound = client
+            .connect_tcp(any(0), SocketAddr::new(SERVER, 443))
+            .await
+            .unwrap();
+        let (mut inbound, from) = listener.try_accept().unwrap().unwrap();
+        assert_eq!(from.ip(), CLIENT);
+        assert_eq!(outbound.status().unwrap(), SocketStatus::Opening);
+
+        assert_eq!(outbound.try_send(b"hello").unwrap(), 5);
+        assert_eq!(recv(inbound.as_mut(), outbound.as_mut()).unwrap(), b"hello");
+        assert_eq!(outbound.status().unwrap(), SocketStatus::Opened);
+
+        assert_eq!(inbound.try_send(b"world").unwrap(), 5);
+        assert_eq!(recv(outbound.as_mut(), inbound.as_mut()).unwrap(), b"world");
+    }
+
+    #[tokio::test]
+    async fn the_server_name_must_be_known() {
+        let bridge = VirtualBridge::new();
+        let (_server, client) = segment(&bridge);
+
+        // The guest connects by address without looking the server up
+        let ret = client
+            .connect_tcp(any(0), SocketAddr::new(SERVER, 443))
+            .await;
+        assert_eq!(ret.unwrap_err(), NetworkError::AddressNotAvailable);
+    }
+
+    #[tokio::test]
+    async fn the_certificate_must_match_the_server_name() {
+        let bridge = VirtualBridge::new();
+        let (server, client) = segment(&bridge);
+        let client = client.with_server_name("other.test");
+        let mut listener = server
+            .listen_tcp(any(443), false, false, false)
+            .await
+            .unwrap();
+
+        let mut outbound = client
+            .connect_tcp(any(0), SocketAddr::new(SERVER, 443))
+            .await
+            .unwrap();
+        let (mut inbound, _) = listener.try_accept().unwrap().unwrap();
+
+        outbound.try_send(b"hello").unwrap();
+        inbound.try_recv(&mut [MaybeUninit::new(0u8); 64]).ok();
+        assert_eq!(
+            recv(outbound.as_mut(), inbound.as_mut()),
+            Err(NetworkError::InvalidData)
+        );
+    }
+
+    #[test]
+    fn names_are_used_in_the_order_they_were_looked_up() {
+        let shared = vec![SERVER];
+        let mut names = ResolvedNames::default();
+        names.insert("a.test", shared.clone());
+        names.insert("b.test", shared.clone());
+
+        assert_eq!(names.host_for(SERVER).as_deref(), Some("a.test"));
+        names.connected(SERVER, "a.test");
+        assert_eq!(names.host_for(SERVER).as_deref(), Some("b.test"));
+        names.connected(SERVER, "b.test");
+
+        // Connecting again without a new look up reuses the latest name
+        assert_eq!(names.host_for(SERVER).as_deref(), Some("b.test"));
+        assert_eq!(names.host_for(CLIENT), None);
+    }
+
+    #[test]
+    fn names_are_bounded() {
+        let mut names = ResolvedNames::default();
+        names.insert("first.test", vec![CLIENT]);
+        for _ in 0..MAX_RESOLVED_NAMES {
+            names.insert("server.test", vec![SERVER]);
+        }
+        assert_eq!(names.names.len(), MAX_RESOLVED_NAMES);
+        assert_eq!(names.host_for(CLIENT), None);
+    }
+}
@@ -0,0 +1,788 @@
+//! In-process implementation of Unix domain sockets (`AF_UNIX`).
+//!
+//! Sockets are registered in a [`UnixNamespace`] which maps path names and
+//! abstract names onto listeners and datagram sockets. Processes that share
+//! the same namespace can connect to one another and pass handles (such as
+//! file descriptors) along with their data, similar to `SCM_RIGHTS`.
+//!
+//! The sockets implement the same traits as their TCP/UDP counterparts so
+//! they can be used anywhere a stream or datagram socket is expected. As
+//! the traits are IP based the local and peer addresses of these sockets are
+//! reported as [`unix_placeholder_addr`], use [`UnixNamespace`] to find out
+//! the real names.
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use crate::{
+    NetworkError, Result, SocketStatus, VirtualConnectedSocket, VirtualConnectionlessSocket,
+    VirtualSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+
+/// Default amount of data that can be buffered in one direction of a stream
+const DEFAULT_BUF_SIZE: usize = 256 * 1024;
+
+/// Maximum number of datagrams that will be queued on a datagram socket
+const MAX_DATAGRAM_QUEUE: usize = 1024;
+
+/// Handles that are passed along with the data on a Unix socket
+pub type UnixRights = Vec<Arc<dyn Any + Send + Sync>>;
+
+/// Address that a Unix domain socket is bound to
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum UnixSocketAddr {
+    /// Socket that has not been bound to any name
+    Unnamed,
+    /// Socket that is bound to a path on the file system
+    Pathname(String),
+    /// Socket that is bound in the abstract namespace (Linux extension)
+    Abstract(Vec<u8>),
+}
+
+impl UnixSocketAddr {
+    /// Parses the address using the same conventions as `sockaddr_un`, which
+    /// means a leading nul byte denotes an abstract address
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        match bytes.split_first() {
+            None => Self::Unnamed,
+            Some((0, name)) => Self::Abstract(name.to_vec()),
+            Some(_) => {
+                let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                Self::Pathname(String::from_utf8_lossy(&bytes[..end]).to_string())
+            }
+        }
+    }
+
+    /// Encodes the address using the same conventions as `sockaddr_un`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Unnamed => Vec::new(),
+            Self::Pathname(path) => path.as_bytes().to_vec(),
+            Self::Abstract(name) => {
+                let mut ret = Vec::with_capacity(name.len() + 1);
+                ret.push(0);
+                ret.extend_from_slice(name);
+                ret
+            }
+        }
+    }
+
+    /// Returns true if this address is in the abstract namespace
+    pub fn is_abstract(&self) -> bool {
+        matches!(self, Self::Abstract(_))
+    }
+}
+
+/// Address reported by the socket traits for Unix domain sockets
+pub fn unix_placeholder_addr() -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+}
+
+#[derive(Debug)]
+enum UnixBinding {
+    Listener(Weak<Mutex<ListenerState>>),
+    Datagram(Weak<Mutex<DatagramState>>),
+}
+
+impl UnixBinding {
+    fn is_alive(&self) -> bool {
+        match self {
+            Self::Listener(weak) => weak.strong_count() > 0,
+            Self::Datagram(weak) => weak.strong_count() > 0,
+        }
+    }
+}
+
+/// Registry of all the named Unix domain sockets that can see each other
+#[derive(Debug, Clone, Default)]
+pub struct UnixNamespace {
+    bindings: Arc<Mutex<HashMap<UnixSocketAddr, UnixBinding>>>,
+}
+
+impl UnixNamespace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn bind(&self, addr: &UnixSocketAddr, binding: UnixBinding) -> Result<()> {
+        if *addr == UnixSocketAddr::Unnamed {
+            return Ok(());
+        }
+        let mut bindings = self.bindings.lock().unwrap();
+        if let Some(existing) = bindings.get(addr) {
+            if existing.is_alive() {
+                return Err(NetworkError::AddressInUse);
+            }
+        }
+        bindings.insert(addr.clone(), binding);
+        Ok(())
+    }
+
+    /// Removes a name from the namespace (equivalent to unlinking the
+    /// socket file of a path name socket)
+    pub fn unlink(&self, addr: &UnixSocketAddr) -> Result<()> {
+        let mut bindings = self.bindings.lock().unwrap();
+        bindings
+            .remove(addr)
+            .map(|_| ())
+            .ok_or(NetworkError::AddressNotAvailable)
+    }
+
+    /// Returns true if a live socket is bound to this address
+    pub fn is_bound(&self, addr: &UnixSocketAddr) -> bool {
+        let bindings = self.bindings.lock().unwrap();
+        bindings.get(addr).map(|b| b.is_alive()).unwrap_or(false)
+    }
+
+    /// Starts listening for stream connections on a particular address
+    pub fn listen(&self, addr: UnixSocketAddr, backlog: usize) -> Result<UnixListener> {
+        let state = Arc::new(Mutex::new(ListenerState {
+            backlog: VecDeque::new(),
+            max_backlog: backlog.max(1),
+            waker: None,
+        }));
+        self.bind(&addr, UnixBinding::Listener(Arc::downgrade(&state)))?;
+        Ok(UnixListener {
+            namespace: self.clone(),
+            addr,
+            state,
+        })
+    }
+
+    /// Connects a new stream socket to a listener bound to the address
+    pub fn connect(&self, addr: &UnixSocketAddr) -> Result<UnixStream> {
+        let listener = {
+            let bindings = self.bindings.lock().unwrap();
+            match bindings.get(addr) {
+                Some(UnixBinding::Listener(weak)) => weak.upgrade(),
+                Some(UnixBinding::Datagram(_)) => return Err(NetworkError::InvalidInput),
+                None => None,
+            }
+        }
+        .ok_or(NetworkError::ConnectionRefused)?;
+
+        let mut listener = listener.lock().unwrap();
+        if listener.backlog.len() >= listener.max_backlog {
+            return Err(NetworkError::WouldBlock);
+        }
+        let (local, remote) = UnixStream::pair_with_addrs(UnixSocketAddr::Unnamed, addr.clone());
+        listener.backlog.push_back(remote);
+        if let Some(waker) = listener.waker.take() {
+            waker.wake();
+        }
+        Ok(local)
+    }
+
+    /// Creates a pair of connected, unnamed stream sockets
+    pub fn pair(&self) -> (UnixStream, UnixStream) {
+        UnixStream::pair_with_addrs(UnixSocketAddr::Unnamed, UnixSocketAddr::Unnamed)
+    }
+
+    /// Binds a datagram socket to a particular address
+    pub fn bind_datagram(&self, addr: UnixSocketAddr) -> Result<UnixDatagram> {
+        let state = Arc::new(Mutex::new(DatagramState::default()));
+        self.bind(&addr, UnixBinding::Datagram(Arc::downgrade(&state)))?;
+        Ok(UnixDatagram {
+            namespace: self.clone(),
+            addr,
+            peer: None,
+            state,
+        })
+    }
+
+    fn datagram_target(&self, addr: &UnixSocketAddr) -> Result<Arc<Mutex<DatagramState>>> {
+        let bindings = self.bindings.lock().unwrap();
+        match bindings.get(addr) {
+            Some(UnixBinding::Datagram(weak)) => {
+                weak.upgrade().ok_or(NetworkError::ConnectionRefused)
+            }
+            Some(UnixBinding::Listener(_)) => Err(NetworkError::InvalidInput),
+            None => Err(NetworkError::ConnectionRefused),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ListenerState {
+    backlog: VecDeque<UnixStream>,
+    max_backlog: usize,
+    waker: Option<Waker>,
+}
+
+/// Listening Unix domain stream socket
+#[derive(Debug)]
+pub struct UnixListener {
+    namespace: UnixNamespace,
+    addr: UnixSocketAddr,
+    state: Arc<Mutex<ListenerState>>,
+}
+
+impl UnixListener {
+    /// Address that this listener is bound to
+    pub fn unix_addr(&self) -> &UnixSocketAddr {
+        &self.addr
+    }
+
+    /// Namespace that this listener is registered in
+    pub fn namespace(&self) -> &UnixNamespace {
+        &self.namespace
+    }
+}
+
+impl VirtualTcpListener for UnixListener {
+    fn try_accept(&mut self) -> Option<Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)>> {
+        let mut state = self.state.lock().unwrap();
+        state.backlog.pop_front().map(|sock| {
+            Ok((
+                Box::new(sock) as Box<dyn VirtualTcpSocket + Sync>,
+                unix_placeholder_addr(),
+            ))
+        })
+    }
+
+    fn poll_accept(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)>> {
+        let mut state = self.state.lock().unwrap();
+        match state.backlog.pop_front() {
+            Some(sock) => Poll::Ready(Ok((Box::new(sock), unix_placeholder_addr()))),
+            None => {
+                state.waker.replace(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    fn poll_accept_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut state = self.state.lock().unwrap();
+        if state.backlog.is_empty() {
+            state.waker.replace(cx.waker().clone());
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(state.backlog.len()))
+        }
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(unix_placeholder_addr())
+    }
+
+    fn set_ttl(&mut self, _ttl: u8) -> Result<()> {
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        Ok(0)
+    }
+}
+
+#[derive(Debug)]
+struct PipeState {
+    buf: VecDeque<u8>,
+    capacity: usize,
+    rights: VecDeque<UnixRights>,
+    /// Set when the sending side will never send any more data
+    closed: bool,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
+}
+
+impl PipeState {
+    fn new() -> Self {
+        Self {
+            buf: VecDeque::new(),
+            capacity: DEFAULT_BUF_SIZE,
+            rights: VecDeque::new(),
+            closed: false,
+            read_waker: None,
+            write_waker: None,
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+        if let Some(waker) = self.write_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+type Pipe = Arc<Mutex<PipeState>>;
+
+/// Connected Unix domain stream socket
+pub struct UnixStream {
+    local: UnixSocketAddr,
+    peer: UnixSocketAddr,
+    tx: Pipe,
+    rx: Pipe,
+    shutdown: Option<Shutdown>,
+}
+
+impl UnixStream {
+    fn pair_with_addrs(a: UnixSocketAddr, b: UnixSocketAddr) -> (Self, Self) {
+        let a_to_b = Arc::new(Mutex::new(PipeState::new()));
+        let b_to_a = Arc::new(Mutex::new(PipeState::new()));
+        (
+            Self {
+                local: a.clone(),
+                peer: b.clone(),
+                tx: a_to_b.clone(),
+                rx: b_to_a.clone(),
+                shutdown: None,
+            },
+            Self {
+                local: b,
+                peer: a,
+                tx: b_to_a,
+                rx: a_to_b,
+                shutdown: None,
+            },
+        )
+    }
+
+    /// Address of the local side of this stream
+    pub fn unix_addr_local(&self) -> &UnixSocketAddr {
+        &self.local
+    }
+
+    /// Address of the remote side of this stream
+    pub fn unix_addr_peer(&self) -> &UnixSocketAddr {
+        &self.peer
+    }
+
+    fn write_closed(&self) -> bool {
+        matches!(self.shutdown, Some(Shutdown::Write) | Some(Shutdown::Both))
+    }
+
+    fn read_closed(&self) -> bool {
+        matches!(self.shutdown, Some(Shutdown::Read) | Some(Shutdown::Both))
+    }
+
+    fn send_inner(&mut self, cx: Option<&mut Context<'_>>, data: &[u8]) -> Poll<Result<usize>> {
+        if self.write_closed() {
+            return Poll::Ready(Err(NetworkError::BrokenPipe));
+        }
+        let mut tx = self.tx.lock().unwrap();
+        if tx.closed {
+            return Poll::Ready(Err(NetworkError::BrokenPipe));
+        }
+        let free = tx.capacity.saturating_sub(tx.buf.len());
+        if free == 0 {
+            return match cx {
+                Some(cx) => {
+                    tx.write_waker.replace(cx.waker().clone());
+                    Poll::Pending
+                }
+                None => Poll::Ready(Err(NetworkError::WouldBlock)),
+            };
+        }
+        let amt = free.min(data.len());
+        tx.buf.extend(&data[..amt]);
+        if let Some(waker) = tx.read_waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(amt))
+    }
+
+    fn recv_inner(
+        &mut self,
+        cx: Option<&mut Context<'_>>,
+        buf: &mut [MaybeUninit<u8>],
+    ) -> Poll<Result<usize>> {
+        if self.read_closed() {
+            return Poll::Ready(Ok(0));
+        }
+        let mut rx = self.rx.lock().unwrap();
+        if rx.buf.is_empty() {
+            if rx.closed {
+                return Poll::Ready(Ok(0));
+            }
+            return match cx {
+                Some(cx) => {
+                    rx.read_waker.replace(cx.waker().clone());
+                    Poll::Pending
+                }
+                None => Poll::Ready(Err(NetworkError::WouldBlock)),
+            };
+        }
+        let amt = rx.buf.len().min(buf.len());
+        for (dst, src) in buf.iter_mut().zip(rx.buf.drain(..amt)) {
+            dst.write(src);
+        }
+        if let Some(waker) = rx.write_waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(amt))
+    }
+}
+
+impl fmt::Debug for UnixStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnixStream")
+            .field("local", &self.local)
+            .field("peer", &self.peer)
+            .field("shutdown", &self.shutdown)
+            .finish()
+    }
+}
+
+impl Drop for UnixStream {
+    fn drop(&mut self) {
+        self.tx.lock().unwrap().close();
+        self.rx.lock().unwrap().close();
+    }
+}
+
+impl VirtualTcpSocket for UnixStream {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.rx.lock().unwrap().capacity = size.max(1);
+        Ok(())
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        Ok(self.rx.lock().unwrap().capacity)
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.tx.lock().unwrap().capacity = size.max(1);
+        Ok(())
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        Ok(self.tx.lock().unwrap().capacity)
+    }
+
+    fn set_nodelay(&mut self, _nodelay: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        Ok(unix_placeholder_addr())
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        if matches!(how, Shutdown::Write | Shutdown::Both) {
+            self.tx.lock().unwrap().close();
+        }
+        self.shutdown = Some(how);
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.rx.lock().unwrap().closed && self.tx.lock().unwrap().closed
+    }
+
+    fn send_rights(&mut self, rights: UnixRights) -> Result<()> {
+        if self.write_closed() {
+            return Err(NetworkError::BrokenPipe);
+        }
+        let mut tx = self.tx.lock().unwrap();
+        tx.rights.push_back(rights);
+        if let Some(waker) = tx.read_waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    fn recv_rights(&mut self) -> Result<Option<UnixRights>> {
+        Ok(self.rx.lock().unwrap().rights.pop_front())
+    }
+}
+
+impl VirtualConnectedSocket for UnixStream {
+    fn set_linger(&mut self, _linger: Option<Duration>) -> Result<()> {
+        Ok(())
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        Ok(None)
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        match self.send_inner(None, data) {
+            Poll::Ready(ret) => ret,
+            Poll::Pending => Err(NetworkError::WouldBlock),
+        }
+    }
+
+    fn poll_send(&mut self, cx: &mut Context<'_>, data: &[u8]) -> Poll<Result<usize>> {
+        self.send_inner(Some(cx), data)
+    }
+
+    fn poll_flush(&mut self, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.tx.lock().unwrap().close();
+        Ok(())
+    }
+
+    fn poll_recv<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &'a mut [MaybeUninit<u8>],
+    ) -> Poll<Result<usize>> {
+        self.recv_inner(Some(cx), buf)
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        match self.recv_inner(None, buf) {
+            Poll::Ready(ret) => ret,
+            Poll::Pending => Err(NetworkError::WouldBlock),
+        }
+    }
+}
+
+impl VirtualSocket for UnixStream {
+    fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(0)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(unix_placeholder_addr())
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(match self.is_closed() {
+            true => SocketStatus::Closed,
+            false => SocketStatus::Opened,
+        })
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut rx = self.rx.lock().unwrap();
+        if !rx.buf.is_empty() || !rx.rights.is_empty() {
+            return Poll::Ready(Ok(rx.buf.len().max(1)));
+        }
+        if rx.closed {
+            return Poll::Ready(Ok(0));
+        }
+        rx.read_waker.replace(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut tx = self.tx.lock().unwrap();
+        if tx.closed {
+            return Poll::Ready(Err(NetworkError::BrokenPipe));
+        }
+        let free = tx.capacity.saturating_sub(tx.buf.len());
+        if free > 0 {
+            return Poll::Ready(Ok(free));
+        }
+        tx.write_waker.replace(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[derive(Debug, Default)]
+struct DatagramState {
+    queue: VecDeque<(Vec<u8>, UnixSocketAddr)>,
+    waker: Option<Waker>,
+}
+
+/// Unix domain datagram socket
+#[derive(Debug)]
+pub struct UnixDatagram {
+    namespace: UnixNamespace,
+    addr: UnixSocketAddr,
+    peer: Option<UnixSocketAddr>,
+    state: Arc<Mutex<DatagramState>>,
+}
+
+impl UnixDatagram {
+    /// Address that this socket is bound to
+    pub fn unix_addr_local(&self) -> &UnixSocketAddr {
+        &self.addr
+    }
+
+    /// Sends a datagram to a socket bound to the supplied address
+    pub fn send_to_unix(&mut self, data: &[u8], addr: &UnixSocketAddr) -> Result<usize> {
+        let target = self.namespace.datagram_target(addr)?;
+        let mut target = target.lock().unwrap();
+        if target.queue.len() >= MAX_DATAGRAM_QUEUE {
+            return Err(NetworkError::WouldBlock);
+        }
+        target.queue.push_back((data.to_vec(), self.addr.clone()));
+        if let Some(waker) = target.waker.take() {
+            waker.wake();
+        }
+        Ok(data.len())
+    }
+
+    /// Receives a datagram along with the address of the sender
+    pub fn poll_recv_from_unix(
+        &mut self,
+        cx: Option<&mut Context<'_>>,
+        buf: &mut [MaybeUninit<u8>],
+    ) -> Poll<Result<(usize, UnixSocketAddr)>> {
+        let mut state = self.state.lock().unwrap();
+        match state.queue.pop_front() {
+            Some((data, from)) => {
+                // Like any other datagram socket the remainder is truncated
+                let amt = data.len().min(buf.len());
+                for (dst, src) in buf.iter_mut().zip(data.into_iter().take(amt)) {
+                    dst.write(src);
+                }
+                Poll::Ready(Ok((amt, from)))
+            }
+            None => match cx {
+                Some(cx) => {
+                    state.waker.replace(cx.waker().clone());
+                    Poll::Pending
+                }
+                None => Poll::Ready(Err(NetworkError::WouldBlock)),
+            },
+        }
+    }
+
+    fn default_peer(&self) -> Result<UnixSocketAddr> {
+        self.peer.clone().ok_or(NetworkError::NotConnected)
+    }
+}
+
+impl VirtualUdpSocket for UnixDatagram {
+    fn set_broadcast(&mut self, _broadcast: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn set_multicast_loop_v4(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn set_multicast_loop_v6(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn set_multicast_ttl_v4(&mut self, _ttl: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        Ok(0)
+    }
+
+    fn join_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        Ok(self.peer.as_ref().map(|_| unix_placeholder_addr()))
+    }
+
+    fn connect_unix(&mut self, peer: UnixSocketAddr) -> Result<()> {
+        self.namespace.datagram_target(&peer)?;
+        self.peer.replace(peer);
+        Ok(())
+    }
+}
+
+impl VirtualConnectionlessSocket for UnixDatagram {
+    fn poll_send_to(
+        &mut self,
+        _cx: &mut Context<'_>,
+        data: &[u8],
+        _addr: SocketAddr,
+    ) -> Poll<Result<usize>> {
+        // IP addresses can not be used to address Unix sockets so datagrams
+        // always go to the connected peer
+        let peer = self.default_peer()?;
+        Poll::Ready(self.send_to_unix(data, &peer))
+    }
+
+    fn try_send_to(&mut self, data: &[u8], _addr: SocketAddr) -> Result<usize> {
+        let peer = self.default_peer()?;
+        self.send_to_unix(data, &peer)
+    }
+
+    fn poll_recv_from<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &'a mut [MaybeUninit<u8>],
+    ) -> Poll<Result<(usize, SocketAddr)>> {
+        self.poll_recv_from_unix(Some(cx), buf)
+            .map_ok(|(amt, _)| (amt, unix_placeholder_addr()))
+    }
+
+    fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        match self.poll_recv_from_unix(None, buf) {
+            Poll::Ready(ret) => ret.map(|(amt, _)| (amt, unix_placeholder_addr())),
+            Poll::Pending => Err(NetworkError::WouldBlock),
+        }
+    }
+}
+
+impl VirtualSocket for UnixDatagram {
+    fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(0)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(unix_placeholder_addr())
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut state = self.state.lock().unwrap();
+        match state.queue.front() {
+            Some((data, _)) => Poll::Ready(Ok(data.len().max(1))),
+            None => {
+                state.waker.replace(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Ready(Ok(DEFAULT_BUF_SIZE))
+    }
+}
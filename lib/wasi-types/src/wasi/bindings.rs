@@ -254,6 +254,12 @@ pub enum Errno {
     Memviolation,
     #[doc = " An unknown error has occured"]
     Unknown,
+    #[doc = " Extension: Too many sockets are open in this instance."]
+    Socklimit,
+    #[doc = " Extension: Too many connections are pending in this instance."]
+    Pendlimit,
+    #[doc = " Extension: Too many connections were made by this instance recently."]
+    Connratelimit,
 }
 impl Errno {
     pub fn name(&self) -> &'static str {
@@ -338,6 +344,9 @@ impl Errno {
             Errno::Shutdown => "shutdown",
             Errno::Memviolation => "memviolation",
             Errno::Unknown => "unknown",
+            Errno::Socklimit => "socklimit",
+            Errno::Pendlimit => "pendlimit",
+            Errno::Connratelimit => "connratelimit",
         }
     }
     pub fn message(&self) -> &'static str {
@@ -422,6 +431,11 @@ impl Errno {
             Errno::Shutdown => "Cannot send after socket shutdown.",
             Errno::Memviolation => "Memory access violation.",
             Errno::Unknown => "An unknown error has occured",
+            Errno::Socklimit => "Extension: Too many sockets are open in this instance.",
+            Errno::Pendlimit => "Extension: Too many connections are pending in this instance.",
+            Errno::Connratelimit => {
+                "Extension: Too many connections were made by this instance recently."
+            }
         }
     }
 }
@@ -2763,6 +2777,9 @@ unsafe impl wasmer::FromToNativeWasmType for Errno {
             77 => Self::Shutdown,
             78 => Self::Memviolation,
             79 => Self::Unknown,
+            80 => Self::Socklimit,
+            81 => Self::Pendlimit,
+            82 => Self::Connratelimit,
 
             _ => Self::Unknown,
         }
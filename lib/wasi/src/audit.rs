@@ -0,0 +1,195 @@
+//! Audit log of security-relevant guest actions.
+//!
+//! When an [`AuditLog`] is attached to an instance (see
+//! [`WasiEnvBuilder::audit_log`](crate::WasiEnvBuilder::audit_log)) it is
+//! told about every file the guest opens outside of its preopened
+//! directories, every outbound connection, every process it spawns and
+//! every time it reads its environment, together with whether the runtime
+//! allowed the action. [`JsonLinesAuditLog`] writes those records as one
+//! JSON document per line.
+use std::{
+    io::Write,
+    net::SocketAddr,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::WasiProcessId;
+
+/// Whether the runtime let an audited action go ahead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditDecision {
+    Allowed,
+    Denied,
+}
+
+impl AuditDecision {
+    /// Denied when the action failed because of missing permissions, allowed
+    /// otherwise (including when it failed for an unrelated reason).
+    pub fn from_errno(errno: wasmer_wasix_types::wasi::Errno) -> Self {
+        use wasmer_wasix_types::wasi::Errno;
+        match errno {
+            Errno::Access | Errno::Perm | Errno::Notcapable => Self::Denied,
+            _ => Self::Allowed,
+        }
+    }
+}
+
+/// The action that is being audited.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum AuditAction {
+    /// A file was opened outside of the preopened directories.
+    FileOpen { path: String, write: bool },
+    /// An outbound connection was made.
+    NetConnect { addr: SocketAddr },
+    /// A new process was spawned.
+    ProcSpawn { name: String, args: Vec<String> },
+    /// The environment variables were read. Only their names are recorded
+    /// so that secrets do not end up in the log.
+    EnvRead { names: Vec<String> },
+}
+
+/// A single record of the audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditEvent {
+    /// Milliseconds since the unix epoch.
+    pub timestamp: u64,
+    pub pid: u32,
+    #[serde(flatten)]
+    pub action: AuditAction,
+    pub decision: AuditDecision,
+}
+
+impl AuditEvent {
+    /// Creates a record stamped with the current time.
+    pub fn new(pid: WasiProcessId, action: AuditAction, decision: AuditDecision) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        Self {
+            timestamp,
+            pid: pid.raw(),
+            action,
+            decision,
+        }
+    }
+
+    /// Serializes the record as a single line of JSON (without the line feed).
+    pub fn to_json_line(&self) -> String {
+        serde_json::to_string(self).expect("audit events are always serializable")
+    }
+}
+
+/// Receives the records of the audit log.
+pub trait AuditLog: std::fmt::Debug + Send + Sync {
+    fn record(&self, event: AuditEvent);
+}
+
+/// [`AuditLog`] that writes every record as a JSON line to a writer.
+#[derive(Debug)]
+pub struct JsonLinesAuditLog<W> {
+    writer: Mutex<W>,
+}
+
+impl<W> JsonLinesAuditLog<W>
+where
+    W: Write + std::fmt::Debug + Send,
+{
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Returns the writer that the records were written to.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap()
+    }
+}
+
+impl<W> AuditLog for JsonLinesAuditLog<W>
+where
+    W: Write + std::fmt::Debug + Send,
+{
+    fn record(&self, event: AuditEvent) {
+        let mut line = event.to_json_line();
+        line.push('\n');
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(err) = writer
+            .write_all(line.as_bytes())
+            .and_then(|_| writer.flush())
+        {
+            tracing::warn!("failed to write an audit record - {}", err);
+        }
+    }
+}
+
+/// [`AuditLog`] that keeps the records in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryAuditLog {
+    events: Mutex<Vec<AuditEvent>>,
+}
+
+impl InMemoryAuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the records collected so far.
+    pub fn events(&self) -> Vec<AuditEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl AuditLog for InMemoryAuditLog {
+    fn record(&self, event: AuditEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_lines() {
+        let log = JsonLinesAuditLog::new(Vec::new());
+        log.record(AuditEvent {
+            timestamp: 1234,
+            pid: 7,
+            action: AuditAction::NetConnect {
+                addr: "10.0.0.1:443".parse().unwrap(),
+            },
+            decision: AuditDecision::Denied,
+        });
+        log.record(AuditEvent {
+            timestamp: 1235,
+            pid: 7,
+            action: AuditAction::EnvRead {
+                names: vec!["HOME".to_string()],
+            },
+            decision: AuditDecision::Allowed,
+        });
+
+        let out = String::from_utf8(log.into_inner()).unwrap();
+        let mut lines = out.lines();
+        assert_eq!(
+            lines.next(),
+            Some(
+                r#"{"timestamp":1234,"pid":7,"action":"net_connect","addr":"10.0.0.1:443","decision":"denied"}"#
+            )
+        );
+        assert_eq!(
+            lines.next(),
+            Some(
+                r#"{"timestamp":1235,"pid":7,"action":"env_read","names":["HOME"],"decision":"allowed"}"#
+            )
+        );
+        assert_eq!(lines.next(), None);
+    }
+}
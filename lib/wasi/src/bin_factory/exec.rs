@@ -32,7 +32,14 @@ pub async fn spawn_exec(
     let module = match (module, binary.entry.as_ref()) {
         (Some(a), _) => a,
         (None, Some(entry)) => {
-            let module = Module::new(&store, &entry[..]).map_err(|err| {
+            let module = tracing::debug_span!(
+                "compile_module",
+                %key,
+                package.name = binary.package_name.as_str(),
+                wasm.size = entry.len(),
+            )
+            .in_scope(|| Module::new(&store, &entry[..]))
+            .map_err(|err| {
                 error!(
                     "failed to compile module [{}, len={}] - {}",
                     name,
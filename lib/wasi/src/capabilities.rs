@@ -6,6 +6,7 @@ pub struct Capabilities {
     pub insecure_allow_all: bool,
     pub http_client: HttpClientCapabilityV1,
     pub threading: CapabilityThreadingV1,
+    pub networking: CapabilityNetworkingV1,
 }
 
 impl Capabilities {
@@ -14,6 +15,7 @@ impl Capabilities {
             insecure_allow_all: false,
             http_client: Default::default(),
             threading: Default::default(),
+            networking: Default::default(),
         }
     }
 }
@@ -32,3 +34,23 @@ pub struct CapabilityThreadingV1 {
     /// [`None`] means no limit.
     pub max_threads: Option<usize>,
 }
+
+/// Defines networking related limits.
+#[derive(Debug, Default, Clone)]
+pub struct CapabilityNetworkingV1 {
+    /// Maximum number of sockets that can be open at the same time.
+    ///
+    /// [`None`] means no limit.
+    pub max_sockets: Option<usize>,
+
+    /// Maximum number of outbound connections that can be in progress at
+    /// the same time.
+    ///
+    /// [`None`] means no limit.
+    pub max_pending_connections: Option<usize>,
+
+    /// Maximum number of connections that can be made within a time window.
+    ///
+    /// [`None`] means no limit.
+    pub max_connections_per_window: Option<(usize, std::time::Duration)>,
+}
@@ -0,0 +1,351 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Cursor, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Where the bytes of a journal are kept.
+///
+/// A backend only stores bytes, the entries are encoded, checksummed,
+/// compressed and encrypted by [`LogFile`](super::LogFile) before they get
+/// here. Besides the [`FileBackend`] and the [`ObjectStoreBackend`] any
+/// storage that can replace its contents in one go and append to them can
+/// hold a journal.
+pub trait JournalBackend: fmt::Debug + Send + Sync {
+    /// Reads the journal from the start, failing with
+    /// [`io::ErrorKind::NotFound`] if there isn't one yet.
+    fn open(&self) -> io::Result<Box<dyn Read + Send>>;
+
+    /// Replaces the journal with `data`. Either the old or the new journal
+    /// has to survive a crash.
+    fn replace(&self, data: &[u8]) -> io::Result<()>;
+
+    /// Appends `data` to the journal. It may be buffered until the next
+    /// [`JournalBackend::flush`].
+    fn append(&self, data: &[u8]) -> io::Result<()>;
+
+    /// Makes everything appended so far durable.
+    fn flush(&self) -> io::Result<()>;
+
+    /// Where the journal is, for messages.
+    fn location(&self) -> String;
+}
+
+/// Keeps the journal in a file of the host.
+#[derive(Debug)]
+pub struct FileBackend {
+    path: PathBuf,
+    file: Mutex<Option<BufWriter<File>>>,
+}
+
+impl FileBackend {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileBackend {
+            path: path.into(),
+            file: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl JournalBackend for FileBackend {
+    fn open(&self) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(BufReader::new(File::open(&self.path)?)))
+    }
+
+    /// The data is written to a temporary file first, which then takes the
+    /// place of the journal.
+    fn replace(&self, data: &[u8]) -> io::Result<()> {
+        let dir = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+        temp.write_all(data)?;
+        temp.as_file().sync_all()?;
+        temp.persist(&self.path).map_err(|e| e.error)?;
+
+        let file = std::fs::OpenOptions::new().append(true).open(&self.path)?;
+        *self.file.lock().unwrap() = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    fn append(&self, data: &[u8]) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        if file.is_none() {
+            let opened = std::fs::OpenOptions::new().append(true).open(&self.path)?;
+            *file = Some(BufWriter::new(opened));
+        }
+        file.as_mut().unwrap().write_all(data)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            file.flush()?;
+            file.get_ref().sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn location(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+/// A store of immutable objects, like S3.
+pub trait ObjectStore: fmt::Debug + Send + Sync {
+    /// Fails with [`io::ErrorKind::NotFound`] if there is no such object.
+    fn get(&self, key: &str) -> io::Result<Vec<u8>>;
+
+    fn put(&self, key: &str, data: Vec<u8>) -> io::Result<()>;
+
+    /// The keys of the objects that start with `prefix`.
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>>;
+
+    fn delete(&self, key: &str) -> io::Result<()>;
+
+    /// Where the object with this key is, for messages.
+    fn location(&self, key: &str) -> String {
+        key.to_string()
+    }
+}
+
+/// Keeps the journal in an [`ObjectStore`].
+///
+/// Objects can't be appended to, so the journal is split into segments:
+/// what is appended between two flushes is uploaded as one object under
+/// `{prefix}/{generation}/{segment}`. Replacing the journal uploads it as
+/// the first segment of a new generation, then deletes the older ones.
+/// Readers only look at the latest generation, so a crash in between
+/// leaves the new journal behind.
+///
+/// Nothing leaves the host before it is flushed, see
+/// [`JournalConfig::sync`](super::JournalConfig::sync) to flush every entry.
+#[derive(Debug)]
+pub struct ObjectStoreBackend<S> {
+    store: S,
+    prefix: String,
+    state: Mutex<Segments>,
+}
+
+#[derive(Debug, Default)]
+struct Segments {
+    generation: u64,
+    next: u64,
+    pending: Vec<u8>,
+}
+
+impl<S: ObjectStore> ObjectStoreBackend<S> {
+    pub fn new(store: S, prefix: impl Into<String>) -> Self {
+        ObjectStoreBackend {
+            store,
+            prefix: prefix.into().trim_end_matches('/').to_string(),
+            state: Mutex::new(Segments::default()),
+        }
+    }
+
+    fn key(&self, generation: u64, segment: u64) -> String {
+        format!("{}/{generation:020}/{segment:020}", self.prefix)
+    }
+
+    /// The segments of the journal, sorted.
+    fn segments(&self) -> io::Result<Vec<(u64, u64)>> {
+        let prefix = format!("{}/", self.prefix);
+        let mut segments: Vec<_> = self
+            .store
+            .list(&prefix)?
+            .iter()
+            .filter_map(|key| {
+                let (generation, segment) = key.strip_prefix(&prefix)?.split_once('/')?;
+                Some((generation.parse().ok()?, segment.parse().ok()?))
+            })
+            .collect();
+        segments.sort_unstable();
+        Ok(segments)
+    }
+}
+
+impl<S: ObjectStore> JournalBackend for ObjectStoreBackend<S> {
+    fn open(&self) -> io::Result<Box<dyn Read + Send>> {
+        let segments = self.segments()?;
+        let latest = match segments.last() {
+            Some((generation, _)) => *generation,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("there is no journal at \"{}\"", self.location()),
+                ))
+            }
+        };
+
+        let mut data = Vec::new();
+        for (index, (_, segment)) in segments
+            .iter()
+            .filter(|(generation, _)| *generation == latest)
+            .enumerate()
+        {
+            // Segments are uploaded in order, after a gap the journal
+            // can't be trusted
+            if *segment != index as u64 {
+                tracing::warn!(
+                    location = %self.location(),
+                    segment,
+                    "Ignoring the journal segments after a gap",
+                );
+                break;
+            }
+            data.extend(self.store.get(&self.key(latest, *segment))?);
+        }
+        Ok(Box::new(Cursor::new(data)))
+    }
+
+    fn replace(&self, data: &[u8]) -> io::Result<()> {
+        let segments = self.segments()?;
+        let mut state = self.state.lock().unwrap();
+        let generation = segments
+            .last()
+            .map(|(generation, _)| *generation)
+            .unwrap_or_default()
+            .max(state.generation)
+            + 1;
+        self.store.put(&self.key(generation, 0), data.to_vec())?;
+        *state = Segments {
+            generation,
+            next: 1,
+            pending: Vec::new(),
+        };
+        drop(state);
+
+        for (old, segment) in segments {
+            if let Err(e) = self.store.delete(&self.key(old, segment)) {
+                tracing::warn!(
+                    location = %self.location(),
+                    generation = old,
+                    segment,
+                    error = &e as &dyn std::error::Error,
+                    "Unable to delete an old journal segment",
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn append(&self, data: &[u8]) -> io::Result<()> {
+        self.state.lock().unwrap().pending.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.pending.is_empty() {
+            return Ok(());
+        }
+        let key = self.key(state.generation, state.next);
+        // Kept until it is uploaded, so that the next flush tries again
+        self.store.put(&key, state.pending.clone())?;
+        state.pending.clear();
+        state.next += 1;
+        Ok(())
+    }
+
+    fn location(&self) -> String {
+        self.store.location(&self.prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct MemoryStore(Mutex<BTreeMap<String, Vec<u8>>>);
+
+    impl ObjectStore for &MemoryStore {
+        fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+            self.0
+                .lock()
+                .unwrap()
+                .get(key)
+                .cloned()
+                .ok_or_else(|| io::ErrorKind::NotFound.into())
+        }
+
+        fn put(&self, key: &str, data: Vec<u8>) -> io::Result<()> {
+            self.0.lock().unwrap().insert(key.to_string(), data);
+            Ok(())
+        }
+
+        fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|key| key.starts_with(prefix))
+                .cloned()
+                .collect())
+        }
+
+        fn delete(&self, key: &str) -> io::Result<()> {
+            self.0.lock().unwrap().remove(key);
+            Ok(())
+        }
+    }
+
+    fn read(backend: &dyn JournalBackend) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        backend.open()?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn journals_are_kept_in_segments() {
+        let store = MemoryStore::default();
+        let backend = ObjectStoreBackend::new(&store, "journals/app/");
+        assert_eq!(read(&backend).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        backend.replace(b"head").unwrap();
+        backend.append(b" one").unwrap();
+        // Nothing is uploaded before it is flushed
+        assert_eq!(read(&backend).unwrap(), b"head");
+        backend.flush().unwrap();
+        backend.append(b" two").unwrap();
+        backend.flush().unwrap();
+        backend.flush().unwrap();
+        assert_eq!(read(&backend).unwrap(), b"head one two");
+        assert_eq!(store.0.lock().unwrap().len(), 3);
+
+        backend.replace(b"compacted").unwrap();
+        assert_eq!(read(&backend).unwrap(), b"compacted");
+        assert_eq!(
+            store.0.lock().unwrap().keys().collect::<Vec<_>>(),
+            ["journals/app/00000000000000000002/00000000000000000000"]
+        );
+
+        // Another host picks up where this one left off
+        let other = ObjectStoreBackend::new(&store, "journals/app");
+        other.replace(b"moved").unwrap();
+        assert_eq!(read(&backend).unwrap(), b"moved");
+    }
+
+    #[test]
+    fn files_are_replaced_and_appended_to() {
+        let temp = tempfile::tempdir().unwrap();
+        let backend = FileBackend::new(temp.path().join("app.journal"));
+        assert_eq!(read(&backend).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        backend.replace(b"head").unwrap();
+        backend.append(b" one").unwrap();
+        backend.flush().unwrap();
+        assert_eq!(read(&backend).unwrap(), b"head one");
+        backend.replace(b"compacted").unwrap();
+        assert_eq!(read(&backend).unwrap(), b"compacted");
+    }
+}
@@ -0,0 +1,554 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use wasmer::WASM_PAGE_SIZE;
+
+use super::{Checkpoint, FdEntry, JournalEntry, SocketEvent};
+use crate::snapshot::{FdSnapshot, FileSnapshot, ProcessSnapshot};
+
+/// What the journal knows about a path of the filesystem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PathState {
+    Dir,
+    /// A file created (or truncated) by the process, so all of its contents
+    /// are known
+    File(Vec<u8>),
+    /// A file that existed before the process touched it, only the changes
+    /// are known
+    Changed(Vec<JournalEntry>),
+    Removed {
+        is_dir: bool,
+    },
+}
+
+/// The state of a process as of the last checkpoint of its journal.
+#[derive(Debug, Default)]
+pub struct JournalState {
+    module: Option<String>,
+    memory: Vec<u8>,
+    checkpoint: Option<Checkpoint>,
+    fds: BTreeMap<u32, FdEntry>,
+    sockets: BTreeMap<u32, Vec<SocketEvent>>,
+    paths: BTreeMap<PathBuf, PathState>,
+    exit_code: Option<u32>,
+}
+
+impl JournalState {
+    /// Folds the entries of a journal, up to its last checkpoint, into the
+    /// state they describe. Without a checkpoint all of the entries are
+    /// folded.
+    pub fn fold(entries: &[JournalEntry]) -> Self {
+        let (folded, rest) = split_at_last_checkpoint(entries);
+        let mut state = JournalState::default();
+        for entry in folded {
+            state.apply(entry);
+        }
+        if let Some(code) = rest.iter().find_map(|entry| match entry {
+            JournalEntry::Exit { code } => Some(*code),
+            _ => None,
+        }) {
+            state.exit_code = Some(code);
+        }
+        state
+    }
+
+    /// Whether the process exited after the last checkpoint.
+    pub fn has_exited(&self) -> bool {
+        self.exit_code.is_some()
+    }
+
+    /// The linear memory of the process, as of the last checkpoint.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn apply(&mut self, entry: &JournalEntry) {
+        match entry {
+            JournalEntry::InitModule { module } => {
+                // A new run of the process starts from scratch, only the
+                // changes to the filesystem survive it
+                let paths = std::mem::take(&mut self.paths);
+                *self = JournalState {
+                    module: Some(module.clone()),
+                    paths,
+                    ..Default::default()
+                };
+            }
+            JournalEntry::UpdateMemoryRegion { start, data } => {
+                let start = *start as usize;
+                let end = start + data.len();
+                if self.memory.len() < end {
+                    self.memory.resize(end, 0);
+                }
+                self.memory[start..end].copy_from_slice(data);
+            }
+            JournalEntry::Checkpoint(checkpoint) => {
+                let size = checkpoint.memory_size as usize;
+                if self.memory.len() < size {
+                    self.memory.resize(size, 0);
+                }
+                self.checkpoint = Some(checkpoint.clone());
+            }
+            JournalEntry::OpenFd(fd) => {
+                self.sockets.remove(&fd.fd);
+                self.fds.insert(fd.fd, fd.clone());
+            }
+            JournalEntry::CloseFd { fd } => {
+                self.fds.remove(fd);
+                self.sockets.remove(fd);
+            }
+            JournalEntry::RenumberFd { from, to } => {
+                self.fds.remove(to);
+                self.sockets.remove(to);
+                if let Some(mut fd) = self.fds.get(from).cloned() {
+                    fd.fd = *to;
+                    self.fds.insert(*to, fd);
+                }
+                if let Some(events) = self.sockets.get(from).cloned() {
+                    self.sockets.insert(*to, events);
+                }
+            }
+            JournalEntry::SetFileLength { path, len } => match self.paths.get_mut(path) {
+                Some(PathState::File(contents)) => contents.resize(*len as usize, 0),
+                Some(PathState::Changed(changes)) if *len != 0 => changes.push(entry.clone()),
+                // Only what is left of a file that was emptied is known
+                _ if *len == 0 => {
+                    self.paths.insert(path.clone(), PathState::File(Vec::new()));
+                }
+                _ => {
+                    self.paths
+                        .insert(path.clone(), PathState::Changed(vec![entry.clone()]));
+                }
+            },
+            JournalEntry::WriteFile { path, offset, data } => match self.paths.get_mut(path) {
+                Some(PathState::File(contents)) => {
+                    let start = *offset as usize;
+                    let end = start + data.len();
+                    if contents.len() < end {
+                        contents.resize(end, 0);
+                    }
+                    contents[start..end].copy_from_slice(data);
+                }
+                Some(PathState::Changed(changes)) => changes.push(entry.clone()),
+                _ => {
+                    self.paths
+                        .insert(path.clone(), PathState::Changed(vec![entry.clone()]));
+                }
+            },
+            JournalEntry::CreateDirectory { path } => {
+                self.paths.insert(path.clone(), PathState::Dir);
+            }
+            JournalEntry::RemoveFile { path } => {
+                self.paths
+                    .insert(path.clone(), PathState::Removed { is_dir: false });
+            }
+            JournalEntry::RemoveDirectory { path } => {
+                self.remove_children(path);
+                self.paths
+                    .insert(path.clone(), PathState::Removed { is_dir: true });
+            }
+            JournalEntry::Rename { from, to } => self.rename(from, to),
+            JournalEntry::Socket { fd, event } => {
+                self.sockets.entry(*fd).or_default().push(event.clone());
+            }
+            JournalEntry::Exit { code } => self.exit_code = Some(*code),
+            // Inputs and output are only needed to replay a run
+            JournalEntry::Output { .. }
+            | JournalEntry::ClockTime { .. }
+            | JournalEntry::Random { .. }
+            | JournalEntry::Read { .. }
+            | JournalEntry::Poll { .. } => {}
+        }
+    }
+
+    fn remove_children(&mut self, dir: &Path) {
+        self.paths
+            .retain(|path, _| path == dir || !path.starts_with(dir));
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) {
+        let moved: Vec<_> = self
+            .paths
+            .keys()
+            .filter(|path| path.starts_with(from))
+            .cloned()
+            .collect();
+        let is_dir = matches!(self.paths.get(from), Some(PathState::Dir));
+
+        self.remove_children(to);
+        for path in moved {
+            let state = self.paths.remove(&path).unwrap();
+            let new_path = to.join(path.strip_prefix(from).unwrap());
+            let state = match state {
+                PathState::Changed(changes) => PathState::Changed(
+                    changes
+                        .into_iter()
+                        .map(|change| retarget(change, &new_path))
+                        .collect(),
+                ),
+                state => state,
+            };
+            self.paths.insert(new_path, state);
+        }
+
+        // Whatever wasn't known about is renamed when the journal is replayed
+        if !self.paths.contains_key(to) {
+            self.paths.insert(
+                to.to_path_buf(),
+                PathState::Changed(vec![JournalEntry::Rename {
+                    from: from.to_path_buf(),
+                    to: to.to_path_buf(),
+                }]),
+            );
+        }
+        self.paths
+            .insert(from.to_path_buf(), PathState::Removed { is_dir });
+    }
+
+    /// The smallest set of entries that fold into this state.
+    pub fn to_entries(&self) -> Vec<JournalEntry> {
+        let mut entries = Vec::new();
+        if let Some(module) = self.module.as_ref() {
+            entries.push(JournalEntry::InitModule {
+                module: module.clone(),
+            });
+        }
+
+        // Parents sort before their children
+        for (path, state) in self.paths.iter() {
+            match state {
+                PathState::Removed { is_dir: false } => {
+                    entries.push(JournalEntry::RemoveFile { path: path.clone() })
+                }
+                PathState::Removed { is_dir: true } => {
+                    entries.push(JournalEntry::RemoveDirectory { path: path.clone() })
+                }
+                _ => {}
+            }
+        }
+        for (path, state) in self.paths.iter() {
+            match state {
+                PathState::Dir => {
+                    entries.push(JournalEntry::CreateDirectory { path: path.clone() })
+                }
+                PathState::File(contents) => {
+                    entries.push(JournalEntry::SetFileLength {
+                        path: path.clone(),
+                        len: 0,
+                    });
+                    if !contents.is_empty() {
+                        entries.push(JournalEntry::WriteFile {
+                            path: path.clone(),
+                            offset: 0,
+                            data: contents.clone(),
+                        });
+                    }
+                }
+                PathState::Changed(changes) => entries.extend(changes.iter().cloned()),
+                PathState::Removed { .. } => {}
+            }
+        }
+
+        entries.extend(self.fds.values().cloned().map(JournalEntry::OpenFd));
+        for (fd, events) in self.sockets.iter() {
+            entries.extend(events.iter().map(|event| JournalEntry::Socket {
+                fd: *fd,
+                event: event.clone(),
+            }));
+        }
+
+        if let Some(checkpoint) = self.checkpoint.as_ref() {
+            entries.extend(memory_regions(&self.memory));
+            entries.push(JournalEntry::Checkpoint(checkpoint.clone()));
+        }
+        if let Some(code) = self.exit_code {
+            entries.push(JournalEntry::Exit { code });
+        }
+        entries
+    }
+
+    /// The snapshot of the process as of the last checkpoint, or `None` if
+    /// it never took one.
+    ///
+    /// Directories and files created by the process are part of the
+    /// snapshot. Files that existed before are assumed to still be there,
+    /// the way the process left them.
+    pub fn to_snapshot(&self) -> Option<ProcessSnapshot> {
+        let module = self.module.clone()?;
+        let checkpoint = self.checkpoint.as_ref()?;
+
+        let fds = self
+            .fds
+            .values()
+            .map(|fd| FdSnapshot {
+                fd: fd.fd,
+                path: fd.path.clone(),
+                is_dir: fd.is_dir,
+                rights: fd.rights,
+                rights_inheriting: fd.rights_inheriting,
+                flags: fd.flags,
+                open_flags: fd.open_flags,
+                offset: checkpoint
+                    .fd_offsets
+                    .iter()
+                    .find_map(|(n, offset)| if *n == fd.fd { Some(*offset) } else { None })
+                    .unwrap_or_default(),
+            })
+            .collect();
+        let files = self
+            .paths
+            .iter()
+            .filter_map(|(path, state)| match state {
+                PathState::Dir => Some(FileSnapshot {
+                    path: path.clone(),
+                    contents: None,
+                }),
+                PathState::File(contents) => Some(FileSnapshot {
+                    path: path.clone(),
+                    contents: Some(contents.clone()),
+                }),
+                PathState::Changed(_) | PathState::Removed { .. } => None,
+            })
+            .collect();
+
+        Some(ProcessSnapshot {
+            module,
+            memory64: checkpoint.memory64,
+            memory: self.memory.clone(),
+            memory_stack: checkpoint.memory_stack.clone(),
+            rewind_stack: checkpoint.rewind_stack.clone(),
+            store_data: checkpoint.store_data.clone(),
+            current_dir: checkpoint.current_dir.clone(),
+            fds,
+            files,
+            monotonic_clock: checkpoint.monotonic_clock,
+        })
+    }
+}
+
+/// Compacts the entries of a journal: everything up to the last checkpoint
+/// is folded into the smallest set of entries that produce the same state,
+/// the entries after it are kept as they are.
+pub fn compact(entries: &[JournalEntry]) -> Vec<JournalEntry> {
+    let (folded, rest) = split_at_last_checkpoint(entries);
+    if folded.is_empty() {
+        return rest.to_vec();
+    }
+
+    let mut state = JournalState::default();
+    for entry in folded {
+        state.apply(entry);
+    }
+    let mut compacted = state.to_entries();
+    compacted.extend(rest.iter().cloned());
+    compacted
+}
+
+/// Splits the entries after the last checkpoint off. Without a checkpoint
+/// every entry is in the first half.
+fn split_at_last_checkpoint(entries: &[JournalEntry]) -> (&[JournalEntry], &[JournalEntry]) {
+    match entries
+        .iter()
+        .rposition(|entry| matches!(entry, JournalEntry::Checkpoint(_)))
+    {
+        Some(index) => entries.split_at(index + 1),
+        None => (entries, &[]),
+    }
+}
+
+fn retarget(change: JournalEntry, path: &Path) -> JournalEntry {
+    match change {
+        JournalEntry::WriteFile { offset, data, .. } => JournalEntry::WriteFile {
+            path: path.to_path_buf(),
+            offset,
+            data,
+        },
+        JournalEntry::SetFileLength { len, .. } => JournalEntry::SetFileLength {
+            path: path.to_path_buf(),
+            len,
+        },
+        other => other,
+    }
+}
+
+/// The pages of memory that aren't zeroed, adjacent pages are merged.
+fn memory_regions(memory: &[u8]) -> Vec<JournalEntry> {
+    let mut regions: Vec<(usize, usize)> = Vec::new();
+    for (index, page) in memory.chunks(WASM_PAGE_SIZE).enumerate() {
+        if page.iter().all(|b| *b == 0) {
+            continue;
+        }
+        let start = index * WASM_PAGE_SIZE;
+        let end = start + page.len();
+        match regions.last_mut() {
+            Some((_, last_end)) if *last_end == start => *last_end = end,
+            _ => regions.push((start, end)),
+        }
+    }
+    regions
+        .into_iter()
+        .map(|(start, end)| JournalEntry::UpdateMemoryRegion {
+            start: start as u64,
+            data: memory[start..end].to_vec(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint(memory_size: u64) -> JournalEntry {
+        JournalEntry::Checkpoint(Checkpoint {
+            memory_size,
+            memory64: false,
+            memory_stack: vec![1],
+            rewind_stack: vec![2],
+            store_data: vec![3],
+            current_dir: "/".to_string(),
+            fd_offsets: vec![(5, 3)],
+            monotonic_clock: 0,
+        })
+    }
+
+    fn write(path: &str, offset: u64, data: &[u8]) -> JournalEntry {
+        JournalEntry::WriteFile {
+            path: PathBuf::from(path),
+            offset,
+            data: data.to_vec(),
+        }
+    }
+
+    fn journal() -> Vec<JournalEntry> {
+        let page = WASM_PAGE_SIZE as u64;
+        vec![
+            JournalEntry::InitModule {
+                module: "abc".to_string(),
+            },
+            JournalEntry::CreateDirectory {
+                path: PathBuf::from("/tmp"),
+            },
+            JournalEntry::SetFileLength {
+                path: PathBuf::from("/tmp/log.txt"),
+                len: 0,
+            },
+            JournalEntry::OpenFd(FdEntry {
+                fd: 5,
+                path: PathBuf::from("/tmp/log.txt"),
+                is_dir: false,
+                rights: 3,
+                rights_inheriting: 3,
+                flags: 0,
+                open_flags: 2,
+            }),
+            write("/tmp/log.txt", 0, b"hello"),
+            write("/tmp/log.txt", 5, b" world"),
+            write("/etc/hosts", 10, b"# edited"),
+            JournalEntry::UpdateMemoryRegion {
+                start: 0,
+                data: vec![1; WASM_PAGE_SIZE],
+            },
+            checkpoint(2 * page),
+            JournalEntry::UpdateMemoryRegion {
+                start: page,
+                data: vec![2; WASM_PAGE_SIZE],
+            },
+            JournalEntry::UpdateMemoryRegion {
+                start: 0,
+                data: vec![0; WASM_PAGE_SIZE],
+            },
+            checkpoint(3 * page),
+            write("/tmp/log.txt", 11, b"!"),
+        ]
+    }
+
+    #[test]
+    fn journals_fold_into_snapshots() {
+        let state = JournalState::fold(&journal());
+        assert!(!state.has_exited());
+
+        let snapshot = state.to_snapshot().unwrap();
+        assert_eq!(snapshot.memory.len(), 3 * WASM_PAGE_SIZE);
+        assert!(snapshot.memory[..WASM_PAGE_SIZE].iter().all(|b| *b == 0));
+        assert!(snapshot.memory[WASM_PAGE_SIZE..2 * WASM_PAGE_SIZE]
+            .iter()
+            .all(|b| *b == 2));
+        assert_eq!(snapshot.fds[0].offset, 3);
+
+        // Writes after the last checkpoint happen again when resuming
+        let files: Vec<_> = snapshot
+            .files
+            .iter()
+            .map(|f| (f.path.to_str().unwrap(), f.contents.as_deref()))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                ("/tmp", None),
+                ("/tmp/log.txt", Some(b"hello world".as_slice()))
+            ]
+        );
+    }
+
+    #[test]
+    fn compaction_keeps_the_state() {
+        let entries = journal();
+        let compacted = compact(&entries);
+        assert!(compacted.len() < entries.len());
+        assert_eq!(compacted.last(), entries.last());
+
+        // Only the non-zero page is left
+        let regions = compacted
+            .iter()
+            .filter(|e| matches!(e, JournalEntry::UpdateMemoryRegion { .. }))
+            .count();
+        assert_eq!(regions, 1);
+
+        let original = JournalState::fold(&entries).to_snapshot().unwrap();
+        let compacted = JournalState::fold(&compacted).to_snapshot().unwrap();
+        assert_eq!(original.memory, compacted.memory);
+        assert_eq!(original.files.len(), compacted.files.len());
+        assert_eq!(original.fds[0].offset, compacted.fds[0].offset);
+    }
+
+    #[test]
+    fn removed_and_renamed_paths() {
+        let mut entries = journal();
+        entries.extend([
+            JournalEntry::Rename {
+                from: PathBuf::from("/tmp"),
+                to: PathBuf::from("/var"),
+            },
+            JournalEntry::RemoveFile {
+                path: PathBuf::from("/etc/hosts"),
+            },
+            JournalEntry::CloseFd { fd: 5 },
+            checkpoint(3 * WASM_PAGE_SIZE as u64),
+        ]);
+
+        let snapshot = JournalState::fold(&entries).to_snapshot().unwrap();
+        let paths: Vec<_> = snapshot.files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [PathBuf::from("/var"), PathBuf::from("/var/log.txt")]
+        );
+        assert!(snapshot.fds.is_empty());
+
+        let compacted = compact(&entries);
+        assert!(compacted.contains(&JournalEntry::RemoveFile {
+            path: PathBuf::from("/etc/hosts")
+        }));
+        assert!(compacted.contains(&JournalEntry::RemoveDirectory {
+            path: PathBuf::from("/tmp")
+        }));
+    }
+
+    #[test]
+    fn exited_processes_are_not_recovered() {
+        let mut entries = journal();
+        entries.push(JournalEntry::Exit { code: 0 });
+        assert!(JournalState::fold(&entries).has_exited());
+    }
+}
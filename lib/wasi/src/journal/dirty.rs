@@ -0,0 +1,157 @@
+use std::{
+    convert::TryInto,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::FileExt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use wasmer::WASM_PAGE_SIZE;
+
+/// Set while a [`SoftDirty`] exists.
+///
+/// This is process-wide, not per instance: the soft-dirty bits belong to
+/// the whole host process and clearing them for one linear memory would
+/// hide the writes to every other one. Only one linear memory of the host
+/// process (e.g. the first of several instances being journaled) has its
+/// dirty pages tracked at a time, the others are hashed.
+static CLAIMED: AtomicBool = AtomicBool::new(false);
+
+/// The bit of a pagemap entry that is set once the page is written to
+const SOFT_DIRTY_BIT: u64 = 1 << 55;
+
+/// Tracks which pages of a linear memory were written to with the
+/// soft-dirty bits of the kernel, so that finding out what changed doesn't
+/// mean reading all of memory.
+#[derive(Debug)]
+pub(crate) struct SoftDirty {
+    pagemap: File,
+    clear_refs: File,
+    host_page_size: usize,
+}
+
+impl SoftDirty {
+    /// Returns `None` if the kernel doesn't track soft-dirty pages, or if
+    /// another linear memory of this host process is tracked already (see
+    /// [`CLAIMED`]). Anything else in the host process that relies on the
+    /// soft-dirty bits is disturbed while the returned tracker is used.
+    pub(crate) fn claim() -> Option<Self> {
+        if CLAIMED.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+        match SoftDirty::open() {
+            Ok(soft_dirty) => Some(soft_dirty),
+            Err(e) => {
+                tracing::debug!(
+                    error = &e as &dyn std::error::Error,
+                    "Soft-dirty pages aren't available, memory will be hashed instead",
+                );
+                CLAIMED.store(false, Ordering::SeqCst);
+                None
+            }
+        }
+    }
+
+    fn open() -> io::Result<Self> {
+        let host_page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        if host_page_size <= 0 || WASM_PAGE_SIZE % host_page_size as usize != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unexpected host page size",
+            ));
+        }
+        let mut soft_dirty = SoftDirty {
+            pagemap: File::open("/proc/self/pagemap")?,
+            clear_refs: OpenOptions::new()
+                .write(true)
+                .open("/proc/self/clear_refs")?,
+            host_page_size: host_page_size as usize,
+        };
+
+        // Kernels built without CONFIG_MEM_SOFT_DIRTY accept the writes to
+        // clear_refs but never set the bit
+        let mut probe = vec![0u8; 2 * soft_dirty.host_page_size];
+        let offset = probe.as_ptr().align_offset(soft_dirty.host_page_size);
+        soft_dirty.clear()?;
+        unsafe { std::ptr::write_volatile(probe.as_mut_ptr().add(offset), 1) };
+        let written = soft_dirty.is_dirty(probe.as_ptr() as usize + offset)?;
+        if !written {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "the kernel doesn't track soft-dirty pages",
+            ));
+        }
+        Ok(soft_dirty)
+    }
+
+    /// Forgets which pages were written to.
+    pub(crate) fn clear(&mut self) -> io::Result<()> {
+        self.clear_refs.write_all(b"4")
+    }
+
+    /// Returns the indexes of the wasm pages of the `len` bytes of linear
+    /// memory at `base` that were written to since the last call, and
+    /// starts over.
+    ///
+    /// The bits are read and then cleared, a write in between would be
+    /// lost. Nothing may write to the memory while this runs, i.e. all the
+    /// other threads of the guest have to be stopped.
+    pub(crate) fn take_dirty(&mut self, base: usize, len: usize) -> io::Result<Vec<usize>> {
+        let per_wasm_page = WASM_PAGE_SIZE / self.host_page_size;
+        let mut entries = vec![0u8; (len / self.host_page_size) * 8];
+        let offset = (base / self.host_page_size) * 8;
+        self.pagemap.read_exact_at(&mut entries, offset as u64)?;
+        self.clear()?;
+
+        let dirty = entries
+            .chunks(per_wasm_page * 8)
+            .enumerate()
+            .filter(|(_, page)| {
+                page.chunks(8).any(|entry| {
+                    let entry = u64::from_ne_bytes(entry.try_into().unwrap());
+                    entry & SOFT_DIRTY_BIT != 0
+                })
+            })
+            .map(|(index, _)| index)
+            .collect();
+        Ok(dirty)
+    }
+
+    fn is_dirty(&self, address: usize) -> io::Result<bool> {
+        let mut entry = [0u8; 8];
+        let offset = (address / self.host_page_size) * 8;
+        self.pagemap.read_exact_at(&mut entry, offset as u64)?;
+        Ok(u64::from_ne_bytes(entry) & SOFT_DIRTY_BIT != 0)
+    }
+}
+
+impl Drop for SoftDirty {
+    fn drop(&mut self) {
+        CLAIMED.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_pages_written_to_are_dirty() {
+        // Not every kernel tracks soft-dirty pages
+        let mut soft_dirty = match SoftDirty::claim() {
+            Some(soft_dirty) => soft_dirty,
+            None => return,
+        };
+        let mut memory = vec![0u8; 5 * WASM_PAGE_SIZE];
+        let start = memory.as_ptr().align_offset(WASM_PAGE_SIZE);
+        let base = memory.as_ptr() as usize + start;
+        let len = 4 * WASM_PAGE_SIZE;
+        memory[start..start + len].fill(1);
+        soft_dirty.take_dirty(base, len).unwrap();
+
+        memory[start + WASM_PAGE_SIZE + 10] = 2;
+        memory[start + 3 * WASM_PAGE_SIZE] = 3;
+        assert_eq!(soft_dirty.take_dirty(base, len).unwrap(), [1, 3]);
+        assert!(soft_dirty.take_dirty(base, len).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,350 @@
+use std::{convert::TryFrom, io::Read, path::Path, sync::Arc};
+
+use sha2::{Digest, Sha256};
+
+use super::{FileBackend, JournalBackend, JournalEntry, JournalError};
+use crate::storage::StorageOptions;
+
+/// Identifies journal files
+const MAGIC: &[u8; 8] = b"wasmerjl";
+
+/// Bumped whenever the encoding of [`JournalEntry`] changes
+const FORMAT_VERSION: u32 = 3;
+
+/// The magic, the version and how the records are stored
+const HEADER_LEN: usize = MAGIC.len() + 4 + 1;
+
+/// Every record is its length, a checksum and the entry itself
+const RECORD_HEADER_LEN: usize = 4 + 4;
+
+/// An append-only journal, kept by a [`JournalBackend`].
+///
+/// Entries are only ever appended, so a crash can at worst leave a partly
+/// written record at the end of the journal. Every record carries a
+/// checksum and reading stops at the first one that doesn't add up, which
+/// drops that record and nothing else.
+///
+/// The entries can be compressed and encrypted, see [`StorageOptions`].
+/// Every record is sealed on its own and bound to its position in the
+/// journal, so records can't be moved around either.
+#[derive(Debug)]
+pub struct LogFile {
+    backend: Arc<dyn JournalBackend>,
+    sync: bool,
+    storage: StorageOptions,
+    /// The number of records in the journal
+    records: u64,
+    /// The size of the journal in bytes
+    size: u64,
+}
+
+impl LogFile {
+    /// Replaces the journal file at `path` with `entries` and opens it for
+    /// appending.
+    pub fn rewrite(
+        path: &Path,
+        entries: &[JournalEntry],
+        sync: bool,
+        storage: &StorageOptions,
+    ) -> Result<Self, JournalError> {
+        LogFile::rewrite_in(Arc::new(FileBackend::new(path)), entries, sync, storage)
+    }
+
+    /// Replaces the journal kept by `backend` with `entries` and opens it
+    /// for appending. A crash leaves either the old or the new journal
+    /// behind.
+    pub fn rewrite_in(
+        backend: Arc<dyn JournalBackend>,
+        entries: &[JournalEntry],
+        sync: bool,
+        storage: &StorageOptions,
+    ) -> Result<Self, JournalError> {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        data.push(storage.flags());
+        for (index, entry) in entries.iter().enumerate() {
+            data.extend(encode(entry, storage, index as u64)?);
+        }
+        backend.replace(&data)?;
+
+        Ok(LogFile {
+            backend,
+            sync,
+            storage: storage.clone(),
+            records: entries.len() as u64,
+            size: data.len() as u64,
+        })
+    }
+
+    /// Reads every complete entry of the journal file at `path`, decrypting
+    /// them with the key of `storage` if they are encrypted.
+    pub fn read(path: &Path, storage: &StorageOptions) -> Result<Vec<JournalEntry>, JournalError> {
+        LogFile::read_from(&FileBackend::new(path), storage)
+    }
+
+    /// Like [`LogFile::read`], for the journal kept by `backend`.
+    pub fn read_from(
+        backend: &dyn JournalBackend,
+        storage: &StorageOptions,
+    ) -> Result<Vec<JournalEntry>, JournalError> {
+        decode_all(backend.open()?, storage)
+    }
+
+    pub fn backend(&self) -> &Arc<dyn JournalBackend> {
+        &self.backend
+    }
+
+    /// The size of the journal in bytes, including what isn't flushed yet.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The number of entries in the journal.
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    pub fn append(&mut self, entry: &JournalEntry) -> Result<(), JournalError> {
+        let record = encode(entry, &self.storage, self.records)?;
+        self.backend.append(&record)?;
+        self.records += 1;
+        self.size += record.len() as u64;
+        if self.sync {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Makes sure everything appended so far is durable.
+    pub fn flush(&mut self) -> Result<(), JournalError> {
+        self.backend.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for LogFile {
+    fn drop(&mut self) {
+        if let Err(e) = self.backend.flush() {
+            tracing::warn!(
+                location = %self.backend.location(),
+                error = &e as &dyn std::error::Error,
+                "Unable to flush the journal",
+            );
+        }
+    }
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = Sha256::digest(payload);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn encode(
+    entry: &JournalEntry,
+    storage: &StorageOptions,
+    index: u64,
+) -> Result<Vec<u8>, JournalError> {
+    let payload = bincode::serialize(entry).map_err(|e| JournalError::Encode(e.to_string()))?;
+    let payload = storage
+        .seal(payload, &index.to_le_bytes())
+        .map_err(|e| JournalError::Encode(e.to_string()))?;
+    let len = u32::try_from(payload.len())
+        .map_err(|_| JournalError::Encode(format!("{} byte entry", payload.len())))?;
+
+    let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+    record.extend_from_slice(&len.to_le_bytes());
+    record.extend_from_slice(&checksum(&payload));
+    record.extend_from_slice(&payload);
+    Ok(record)
+}
+
+fn decode_all(
+    mut reader: impl Read,
+    storage: &StorageOptions,
+) -> Result<Vec<JournalEntry>, JournalError> {
+    let mut header = [0; HEADER_LEN];
+    let read = read_full(&mut reader, &mut header)?;
+    if read < MAGIC.len() + 4 || &header[..MAGIC.len()] != MAGIC {
+        return Err(JournalError::Corrupt("not a journal file".to_string()));
+    }
+    let mut version = [0; 4];
+    version.copy_from_slice(&header[MAGIC.len()..HEADER_LEN - 1]);
+    let version = u32::from_le_bytes(version);
+    if version != FORMAT_VERSION {
+        return Err(JournalError::UnsupportedVersion(version));
+    }
+    if read < HEADER_LEN {
+        return Err(JournalError::Corrupt("truncated header".to_string()));
+    }
+    let flags = header[HEADER_LEN - 1];
+    storage.check_flags(flags)?;
+
+    let mut entries = Vec::new();
+    while let Some(entry) = decode_record(&mut reader, storage, flags, entries.len() as u64)? {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Decodes the next record, `None` at the end of the journal. A record that
+/// was only partly written ends the journal too.
+fn decode_record(
+    reader: &mut impl Read,
+    storage: &StorageOptions,
+    flags: u8,
+    index: u64,
+) -> Result<Option<JournalEntry>, JournalError> {
+    let mut header = [0; RECORD_HEADER_LEN];
+    match read_full(reader, &mut header)? {
+        0 => return Ok(None),
+        RECORD_HEADER_LEN => {}
+        _ => return Ok(incomplete()),
+    }
+    let mut len = [0; 4];
+    len.copy_from_slice(&header[..4]);
+    let len = u32::from_le_bytes(len) as u64;
+
+    let mut payload = Vec::new();
+    reader.take(len).read_to_end(&mut payload)?;
+    if payload.len() as u64 != len || checksum(&payload) != header[4..] {
+        return Ok(incomplete());
+    }
+    // The checksum adds up, so the record is complete and anything wrong
+    // with it is a wrong key or tampering
+    let payload = storage
+        .unseal(flags, payload, &index.to_le_bytes())
+        .map_err(|e| JournalError::Corrupt(format!("record {index} can't be read ({e})")))?;
+    match bincode::deserialize(&payload) {
+        Ok(entry) => Ok(Some(entry)),
+        Err(_) => Ok(incomplete()),
+    }
+}
+
+fn incomplete() -> Option<JournalEntry> {
+    tracing::warn!("Ignoring the incomplete record at the end of the journal");
+    None
+}
+
+/// Reads until `buf` is full or the end of the file, returning how much
+/// was read.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize, JournalError> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn entries() -> Vec<JournalEntry> {
+        vec![
+            JournalEntry::InitModule {
+                module: "abc".to_string(),
+            },
+            JournalEntry::CreateDirectory {
+                path: PathBuf::from("/tmp"),
+            },
+            JournalEntry::WriteFile {
+                path: PathBuf::from("/tmp/log.txt"),
+                offset: 0,
+                data: b"hello".to_vec(),
+            },
+        ]
+    }
+
+    #[test]
+    fn entries_are_appended() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("app.journal");
+
+        let mut log =
+            LogFile::rewrite(&path, &entries()[..1], false, &StorageOptions::new()).unwrap();
+        for entry in &entries()[1..] {
+            log.append(entry).unwrap();
+        }
+        assert_eq!(log.records(), 3);
+        let size = log.size();
+        drop(log);
+
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), size);
+        assert_eq!(
+            LogFile::read(&path, &StorageOptions::new()).unwrap(),
+            entries()
+        );
+    }
+
+    #[test]
+    fn torn_records_are_dropped() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("app.journal");
+        drop(LogFile::rewrite(&path, &entries(), false, &StorageOptions::new()).unwrap());
+
+        // A crash in the middle of the last record
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+        assert_eq!(
+            LogFile::read(&path, &StorageOptions::new()).unwrap(),
+            entries()[..2]
+        );
+
+        // A record that was only partly flushed
+        drop(LogFile::rewrite(&path, &entries()[..2], false, &StorageOptions::new()).unwrap());
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(
+            LogFile::read(&path, &StorageOptions::new()).unwrap(),
+            entries()[..1]
+        );
+    }
+
+    #[test]
+    fn foreign_files_are_rejected() {
+        assert!(matches!(
+            decode_all(&b"\0asm\x01\0\0\0\0\0\0\0"[..], &StorageOptions::new()),
+            Err(JournalError::Corrupt(_))
+        ));
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&99u32.to_le_bytes());
+        assert!(matches!(
+            decode_all(&bytes[..], &StorageOptions::new()),
+            Err(JournalError::UnsupportedVersion(99))
+        ));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn encrypted_records_need_the_key() {
+        use crate::storage::EncryptionKey;
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("app.journal");
+        let storage = StorageOptions::new().with_encryption(EncryptionKey::new([7; 32]));
+        let mut log = LogFile::rewrite(&path, &entries()[..1], false, &storage).unwrap();
+        for entry in &entries()[1..] {
+            log.append(entry).unwrap();
+        }
+        drop(log);
+
+        assert_eq!(LogFile::read(&path, &storage).unwrap(), entries());
+        assert!(LogFile::read(&path, &StorageOptions::new()).is_err());
+        let other = StorageOptions::new().with_encryption(EncryptionKey::new([8; 32]));
+        assert!(matches!(
+            LogFile::read(&path, &other),
+            Err(JournalError::Corrupt(_))
+        ));
+    }
+}
@@ -0,0 +1,912 @@
+//! A journal of the state-mutating events of a process, the basis of
+//! checkpoint/restore and crash recovery.
+//!
+//! When a [`Journal`] is attached to an instance (see
+//! [`WasiEnvBuilder::journal`](crate::WasiEnvBuilder::journal)) the syscalls
+//! that change the state of the process append a [`JournalEntry`] to an
+//! append-only [`LogFile`]: file descriptors being opened, renumbered and
+//! closed, writes to files and changes to the directory tree, the lifecycle
+//! of sockets, the output of the process and its exit. A [`JournalFilter`]
+//! can leave some of these out. The journal is kept in a file, or in any
+//! other [`JournalBackend`] like an [`ObjectStoreBackend`].
+//!
+//! Every now and then the process takes a checkpoint. Just like for a
+//! [snapshot](crate::snapshot) the stack is unwound out of a syscall with
+//! asyncify, but instead of exiting, the pages of linear memory that changed
+//! since the previous checkpoint are journaled together with the stack and
+//! the process carries on. Folding the entries up to the last checkpoint
+//! gives back a [`ProcessSnapshot`], so a process that crashed or was killed
+//! can be resumed from where the journal left off.
+//!
+//! The journal only grows while the process runs. It is compacted with
+//! [`compact`] when it is reopened, which folds everything up to the last
+//! checkpoint into the smallest set of entries that produce the same state.
+//! With a [`Retention`] policy it is also compacted while the process runs,
+//! whenever it gets too large or too old.
+//!
+//! A journal can also [record the inputs](JournalConfig::with_inputs) of the
+//! process: the clocks it reads, the random bytes and the data it is handed
+//! and the events it polls. A run journaled with its inputs can be
+//! [replayed](Replay) deterministically.
+use std::{
+    borrow::Cow,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use wasmer::{
+    AsStoreMut, FunctionEnvMut, MemoryAccessError, MemorySize, MemoryView, OnCalledAction,
+    WASM_PAGE_SIZE,
+};
+use wasmer_wasix_types::wasi::Errno;
+
+use crate::{
+    snapshot::{ProcessSnapshot, SnapshotError},
+    storage::StorageOptions,
+    syscalls::{rewind, unwind},
+    WasiEnv, WasiError,
+};
+
+mod backend;
+mod compact;
+#[cfg(target_os = "linux")]
+mod dirty;
+mod log_file;
+mod policy;
+mod replay;
+#[cfg(feature = "s3")]
+mod s3;
+
+pub(crate) use self::replay::errno;
+#[cfg(feature = "s3")]
+pub use self::s3::{S3Credentials, S3Store};
+pub use self::{
+    backend::{FileBackend, JournalBackend, ObjectStore, ObjectStoreBackend},
+    compact::{compact, JournalState},
+    log_file::LogFile,
+    policy::{EventClass, JournalFilter, Retention},
+    replay::{Divergence, Replay},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum JournalError {
+    #[error("unable to read or write the journal")]
+    Io(#[from] std::io::Error),
+    #[error("the journal is corrupt: {0}")]
+    Corrupt(String),
+    #[error("the journal was written by an incompatible version of wasmer (format {0})")]
+    UnsupportedVersion(u32),
+    #[error("unable to encode a journal entry: {0}")]
+    Encode(String),
+    #[error("unable to recover the process from the journal")]
+    Snapshot(#[from] SnapshotError),
+}
+
+/// A state-mutating event of a process.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JournalEntry {
+    /// A process was started from the module with this fingerprint. The
+    /// entries before it belong to a previous run.
+    InitModule {
+        module: String,
+    },
+    /// Part of linear memory changed since the previous checkpoint
+    UpdateMemoryRegion {
+        start: u64,
+        data: Vec<u8>,
+    },
+    /// The process was unwound out of a syscall, the memory regions
+    /// journaled right before it are part of the checkpoint
+    Checkpoint(Checkpoint),
+    /// A file or directory was opened
+    OpenFd(FdEntry),
+    CloseFd {
+        fd: u32,
+    },
+    /// A file descriptor was copied to another number
+    RenumberFd {
+        from: u32,
+        to: u32,
+    },
+    /// A file was created or resized, `len` is 0 for new and truncated files
+    SetFileLength {
+        path: PathBuf,
+        len: u64,
+    },
+    WriteFile {
+        path: PathBuf,
+        offset: u64,
+        data: Vec<u8>,
+    },
+    CreateDirectory {
+        path: PathBuf,
+    },
+    RemoveFile {
+        path: PathBuf,
+    },
+    RemoveDirectory {
+        path: PathBuf,
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Sockets can't be restored, their lifecycle is journaled so that
+    /// it is known which connections a restored process lost.
+    Socket {
+        fd: u32,
+        event: SocketEvent,
+    },
+    /// The process exited, there is nothing left to recover
+    Exit {
+        code: u32,
+    },
+    /// A clock was read. Inputs are only journaled when the journal
+    /// [records them](JournalConfig::with_inputs) and don't change the state
+    /// that is recovered.
+    ClockTime {
+        clock: u32,
+        time: i64,
+    },
+    /// Random bytes were handed to the process
+    Random {
+        data: Vec<u8>,
+    },
+    /// Data was read from a file descriptor, or the read failed with an
+    /// errno
+    Read {
+        fd: u32,
+        result: Result<Vec<u8>, u16>,
+    },
+    /// The events `poll_oneoff` returned, as laid out in linear memory
+    Poll {
+        result: Result<Vec<u8>, u16>,
+    },
+    /// Data was written to stdout or stderr
+    Output {
+        fd: u32,
+        data: Vec<u8>,
+    },
+}
+
+/// The state of the process that isn't in its linear memory, captured when
+/// it was unwound.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The size of linear memory at the time of the checkpoint
+    pub memory_size: u64,
+    pub memory64: bool,
+    pub memory_stack: Vec<u8>,
+    pub rewind_stack: Vec<u8>,
+    /// The globals, serialized by [`crate::utils::store::capture_snapshot`]
+    pub store_data: Vec<u8>,
+    pub current_dir: String,
+    /// The offsets of the journaled files that are open
+    pub fd_offsets: Vec<(u32, u64)>,
+    pub monotonic_clock: i64,
+}
+
+impl std::fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Checkpoint")
+            .field("memory_size", &self.memory_size)
+            .field("memory64", &self.memory64)
+            .field("memory_stack_len", &self.memory_stack.len())
+            .field("rewind_stack_len", &self.rewind_stack.len())
+            .field("current_dir", &self.current_dir)
+            .field("fd_offsets", &self.fd_offsets)
+            .field("monotonic_clock", &self.monotonic_clock)
+            .finish()
+    }
+}
+
+/// A file descriptor of a file or directory opened by the process.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FdEntry {
+    pub fd: u32,
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub rights: u64,
+    pub rights_inheriting: u64,
+    pub flags: u16,
+    pub open_flags: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SocketEvent {
+    Opened {
+        family: u16,
+        ty: u16,
+    },
+    Bound {
+        addr: SocketAddr,
+    },
+    Listening,
+    Connected {
+        peer: SocketAddr,
+    },
+    /// The socket was accepted by the listening socket `listener`
+    Accepted {
+        listener: u32,
+        peer: SocketAddr,
+    },
+}
+
+/// Where the journal is kept and how often the process takes checkpoints.
+#[derive(Debug, Clone)]
+pub struct JournalConfig {
+    pub backend: Arc<dyn JournalBackend>,
+    /// Take a checkpoint whenever this much time passed since the previous
+    /// one. Without it, checkpoints are only taken when requested with
+    /// [`Journal::request_checkpoint`].
+    pub checkpoint_interval: Option<Duration>,
+    /// Flush every entry to the backend before the syscall returns
+    pub sync: bool,
+    /// Journal the inputs of the process too, so that the run can be
+    /// replayed
+    pub record_inputs: bool,
+    /// Let the kernel track the pages of memory the process writes to, so
+    /// that checkpoints only read those. Otherwise all of memory is read
+    /// and hashed at every checkpoint. Only available on Linux, and only
+    /// for one journal per host process.
+    pub track_dirty_pages: bool,
+    /// How the entries are compressed and encrypted
+    pub storage: StorageOptions,
+    /// Which events are journaled
+    pub filter: JournalFilter,
+    /// When the journal is compacted while the process runs
+    pub retention: Retention,
+}
+
+impl JournalConfig {
+    /// Keeps the journal in the file at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        JournalConfig::from_backend(Arc::new(FileBackend::new(path)))
+    }
+
+    /// Keeps the journal in `backend`, e.g. an [`ObjectStoreBackend`].
+    pub fn from_backend(backend: Arc<dyn JournalBackend>) -> Self {
+        JournalConfig {
+            backend,
+            checkpoint_interval: None,
+            sync: false,
+            record_inputs: false,
+            track_dirty_pages: true,
+            storage: StorageOptions::default(),
+            filter: JournalFilter::default(),
+            retention: Retention::default(),
+        }
+    }
+
+    pub fn with_checkpoint_interval(mut self, interval: Duration) -> Self {
+        self.checkpoint_interval = Some(interval);
+        self
+    }
+
+    pub fn with_sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    pub fn with_inputs(mut self, record_inputs: bool) -> Self {
+        self.record_inputs = record_inputs;
+        self
+    }
+
+    pub fn with_dirty_page_tracking(mut self, track_dirty_pages: bool) -> Self {
+        self.track_dirty_pages = track_dirty_pages;
+        self
+    }
+
+    pub fn with_storage(mut self, storage: StorageOptions) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: JournalFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_retention(mut self, retention: Retention) -> Self {
+        self.retention = retention;
+        self
+    }
+}
+
+/// The journal of a running process.
+#[derive(Debug)]
+pub struct Journal {
+    log: Mutex<LogFile>,
+    memory: Mutex<MemoryTracker>,
+    checkpoint_interval: Option<Duration>,
+    last_checkpoint: Mutex<Instant>,
+    checkpoint_requested: AtomicBool,
+    recovered: Option<ProcessSnapshot>,
+    record_inputs: bool,
+    filter: JournalFilter,
+    retention: Retention,
+    storage: StorageOptions,
+    sync: bool,
+    last_compaction: Mutex<Instant>,
+    /// The entries that were left out for being too large
+    oversized: AtomicU64,
+}
+
+impl Journal {
+    /// Opens the journal kept by `config.backend`, creating it if needed.
+    ///
+    /// If the journal holds a checkpoint of a process that didn't exit, it
+    /// is compacted and the process can be resumed from
+    /// [`Journal::recovered`]. Otherwise the journal starts afresh.
+    pub fn open(config: JournalConfig) -> Result<Self, JournalError> {
+        let backend = config.backend;
+        let entries = match LogFile::read_from(&*backend, &config.storage) {
+            Ok(entries) => entries,
+            Err(JournalError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        let state = JournalState::fold(&entries);
+        let (log, memory, recovered) = match state.to_snapshot() {
+            Some(snapshot) if !state.has_exited() => {
+                tracing::debug!(
+                    location = %backend.location(),
+                    entries = entries.len(),
+                    "Recovering the process from its journal",
+                );
+                // Whatever happened after the checkpoint happens again
+                let log = LogFile::rewrite_in(
+                    backend,
+                    &state.to_entries(),
+                    config.sync,
+                    &config.storage,
+                )?;
+                let memory = MemoryTracker::from_image(state.memory());
+                (log, memory, Some(snapshot))
+            }
+            _ => {
+                let log = LogFile::rewrite_in(backend, &[], config.sync, &config.storage)?;
+                (log, MemoryTracker::default(), None)
+            }
+        };
+
+        let memory = if config.track_dirty_pages {
+            memory.with_dirty_page_tracking()
+        } else {
+            memory
+        };
+
+        Ok(Journal {
+            log: Mutex::new(log),
+            memory: Mutex::new(memory),
+            checkpoint_interval: config.checkpoint_interval,
+            last_checkpoint: Mutex::new(Instant::now()),
+            checkpoint_requested: AtomicBool::new(false),
+            recovered,
+            record_inputs: config.record_inputs,
+            filter: config.filter,
+            retention: config.retention,
+            storage: config.storage,
+            sync: config.sync,
+            last_compaction: Mutex::new(Instant::now()),
+            oversized: AtomicU64::new(0),
+        })
+    }
+
+    /// The state of the process the journal was recovered from, if any.
+    /// Pass it to [`WasiEnvBuilder::resume_from`](crate::WasiEnvBuilder::resume_from)
+    /// to carry on where the process left off.
+    pub fn recovered(&self) -> Option<&ProcessSnapshot> {
+        self.recovered.as_ref()
+    }
+
+    /// Appends an entry to the journal, unless the filter of the journal
+    /// leaves it out.
+    pub fn record(&self, entry: &JournalEntry) -> Result<(), JournalError> {
+        if !self.filter.admits(entry) {
+            if self.filter.includes(entry) {
+                tracing::debug!(
+                    class = ?EventClass::of(entry),
+                    "Leaving an entry that is too large out of the journal",
+                );
+                self.oversized.fetch_add(1, Ordering::Relaxed);
+            }
+            return Ok(());
+        }
+        self.log.lock().unwrap().append(entry)
+    }
+
+    /// The number of entries that were left out of the journal for being
+    /// larger than [`JournalFilter::with_max_entry_size`].
+    pub fn oversized_entries(&self) -> u64 {
+        self.oversized.load(Ordering::Relaxed)
+    }
+
+    /// Whether the inputs of the process are journaled too.
+    pub fn records_inputs(&self) -> bool {
+        self.record_inputs
+    }
+
+    /// Makes the process take a checkpoint the next time it enters a
+    /// syscall that supports it.
+    pub fn request_checkpoint(&self) {
+        self.checkpoint_requested.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn checkpoint_due(&self) -> bool {
+        if self.checkpoint_requested.load(Ordering::SeqCst) {
+            return true;
+        }
+        match self.checkpoint_interval {
+            Some(interval) => self.last_checkpoint.lock().unwrap().elapsed() >= interval,
+            None => false,
+        }
+    }
+
+    /// Journals the memory that changed and the checkpoint itself, see
+    /// [`MemoryTracker::update_view`] for `exclusive`.
+    fn record_checkpoint(
+        &self,
+        memory: &MemoryView,
+        exclusive: bool,
+        checkpoint: Checkpoint,
+    ) -> Result<(), JournalError> {
+        let regions = self
+            .memory
+            .lock()
+            .unwrap()
+            .update_view(memory, exclusive)
+            .map_err(|e| JournalError::Encode(e.to_string()))?;
+        let mut log = self.log.lock().unwrap();
+        for region in regions.iter() {
+            log.append(region)?;
+        }
+        log.append(&JournalEntry::Checkpoint(checkpoint))?;
+        log.flush()?;
+
+        let since_compaction = self.last_compaction.lock().unwrap().elapsed();
+        if self.retention.is_exceeded(log.size(), since_compaction) {
+            self.compact(&mut log)?;
+        }
+
+        self.checkpoint_requested.store(false, Ordering::SeqCst);
+        *self.last_checkpoint.lock().unwrap() = Instant::now();
+        Ok(())
+    }
+
+    /// Folds the journal up to its last checkpoint, which was just taken.
+    fn compact(&self, log: &mut LogFile) -> Result<(), JournalError> {
+        let before = log.size();
+        let backend = log.backend().clone();
+        let entries = LogFile::read_from(&*backend, &self.storage)?;
+        *log = LogFile::rewrite_in(backend, &compact(&entries), self.sync, &self.storage)?;
+        *self.last_compaction.lock().unwrap() = Instant::now();
+
+        tracing::debug!(
+            location = %log.backend().location(),
+            before,
+            after = log.size(),
+            "Compacted the journal",
+        );
+        Ok(())
+    }
+}
+
+/// Keeps a hash of every page of linear memory as of the last checkpoint,
+/// so that only the pages that changed are journaled.
+///
+/// With dirty page tracking only the pages the kernel saw being written to
+/// are read and hashed, the rest of memory is left alone. The dirty pages
+/// are only used while no other thread of the guest runs.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryTracker {
+    pages: Vec<PageHash>,
+    #[cfg(target_os = "linux")]
+    soft_dirty: Option<dirty::SoftDirty>,
+    /// Where linear memory was at the last update, the dirty pages are
+    /// only known if it didn't move since
+    base: Option<usize>,
+}
+
+impl MemoryTracker {
+    pub(crate) fn from_image(memory: &[u8]) -> Self {
+        MemoryTracker {
+            pages: memory.chunks(WASM_PAGE_SIZE).map(hash_page).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Tracks the pages that are written to if the host supports it.
+    pub(crate) fn with_dirty_page_tracking(self) -> Self {
+        MemoryTracker {
+            #[cfg(target_os = "linux")]
+            soft_dirty: dirty::SoftDirty::claim(),
+            ..self
+        }
+    }
+
+    /// Returns the regions of memory that changed, adjacent pages are
+    /// merged into one region.
+    pub(crate) fn update(&mut self, memory: &[u8]) -> Vec<JournalEntry> {
+        self.base = None;
+        self.diff(memory.chunks(WASM_PAGE_SIZE).map(Cow::Borrowed).enumerate())
+    }
+
+    /// Like [`MemoryTracker::update`], but only reads the pages that were
+    /// written to when they are tracked.
+    ///
+    /// `exclusive` tells whether the calling thread is the only thread of
+    /// the guest. The dirty pages are only trusted then, as a write from
+    /// another thread while they are taken would be missed. Otherwise all
+    /// of memory is hashed, which leaves the dirty bits alone.
+    pub(crate) fn update_view(
+        &mut self,
+        memory: &MemoryView,
+        exclusive: bool,
+    ) -> Result<Vec<JournalEntry>, MemoryAccessError> {
+        let base = memory.data_ptr() as usize;
+
+        #[cfg(target_os = "linux")]
+        if let Some(soft_dirty) = self.soft_dirty.as_mut().filter(|_| exclusive) {
+            let dirty = if self.base == Some(base) {
+                soft_dirty.take_dirty(base, memory.data_size() as usize)
+            } else {
+                // Everything is read below, the writes from now on count
+                soft_dirty.clear().map(|_| Vec::new())
+            };
+            match dirty {
+                Ok(dirty) if self.base == Some(base) => {
+                    let pages = dirty
+                        .into_iter()
+                        .map(|index| {
+                            let mut page = vec![0; WASM_PAGE_SIZE];
+                            memory.read((index * WASM_PAGE_SIZE) as u64, &mut page)?;
+                            Ok((index, Cow::Owned(page)))
+                        })
+                        .collect::<Result<Vec<_>, MemoryAccessError>>()?;
+                    return Ok(self.diff(pages));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!(
+                        error = &e as &dyn std::error::Error,
+                        "Unable to track the dirty pages of linear memory, hashing all of it instead",
+                    );
+                    self.soft_dirty = None;
+                }
+            }
+        }
+
+        let regions = self.update(&memory.copy_to_vec()?);
+        self.base = Some(base);
+        Ok(regions)
+    }
+
+    /// Hashes the pages that may have changed, in ascending order, the
+    /// pages that aren't passed didn't change.
+    fn diff<'a>(
+        &mut self,
+        pages: impl IntoIterator<Item = (usize, Cow<'a, [u8]>)>,
+    ) -> Vec<JournalEntry> {
+        // Memory that didn't exist before is zeroed
+        let zero_page = hash_page(&vec![0; WASM_PAGE_SIZE]);
+        let mut regions = Vec::new();
+        let mut current: Option<(usize, Vec<u8>)> = None;
+
+        for (index, page) in pages {
+            if index >= self.pages.len() {
+                self.pages.resize(index + 1, zero_page);
+            }
+            let hash = hash_page(&page);
+            if std::mem::replace(&mut self.pages[index], hash) == hash {
+                regions.extend(current.take());
+                continue;
+            }
+
+            let start = index * WASM_PAGE_SIZE;
+            match current.as_mut() {
+                Some((region_start, data)) if *region_start + data.len() == start => {
+                    data.extend_from_slice(&page);
+                }
+                _ => {
+                    regions.extend(current.take());
+                    current = Some((start, page.into_owned()));
+                }
+            }
+        }
+        regions.extend(current);
+
+        regions
+            .into_iter()
+            .map(|(start, data)| JournalEntry::UpdateMemoryRegion {
+                start: start as u64,
+                data,
+            })
+            .collect()
+    }
+}
+
+/// A page that changed must never hash to the same value as before, or
+/// the change would be left out of the journal, hence SHA-256.
+type PageHash = [u8; 32];
+
+fn hash_page(page: &[u8]) -> PageHash {
+    Sha256::digest(page).into()
+}
+
+/// Unwinds the stack out of the current syscall, journals a checkpoint and
+/// rewinds straight back into the syscall. Use [`maybe_snapshot!`] rather
+/// than calling this directly.
+#[must_use = "you must return the result immediately so the stack can unwind"]
+pub(crate) fn unwind_and_checkpoint<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+) -> Result<Errno, WasiError> {
+    let journal = match ctx.data().journal.clone() {
+        Some(journal) => journal,
+        None => return Ok(Errno::Success),
+    };
+
+    tracing::trace!("Unwinding to take a checkpoint");
+    unwind::<M, _>(ctx, move |mut ctx, memory_stack, rewind_stack| {
+        let memory_stack = memory_stack.freeze();
+        let rewind_stack = rewind_stack.freeze();
+        let store_data = crate::utils::store::capture_snapshot(&mut ctx.as_store_mut())
+            .serialize()
+            .map(bytes::Bytes::from);
+
+        let result = store_data
+            .map_err(|e| JournalError::Encode(e.to_string()))
+            .and_then(|store_data| {
+                let env = ctx.data();
+                let memory = env.memory_view(&ctx);
+                let checkpoint = Checkpoint {
+                    memory_size: memory.data_size(),
+                    memory64: std::mem::size_of::<M::Offset>() == 8,
+                    memory_stack: memory_stack.to_vec(),
+                    rewind_stack: rewind_stack.to_vec(),
+                    store_data: store_data.to_vec(),
+                    current_dir: env.state.fs.current_dir.lock().unwrap().clone(),
+                    fd_offsets: fd_offsets(env),
+                    monotonic_clock: crate::snapshot::monotonic_clock(env),
+                };
+                let exclusive = env.active_threads() <= 1;
+                journal.record_checkpoint(&memory, exclusive, checkpoint)?;
+                Ok(store_data)
+            });
+
+        let store_data = match result {
+            Ok(store_data) => store_data,
+            Err(e) => {
+                tracing::error!(
+                    error = &e as &dyn std::error::Error,
+                    "Unable to journal a checkpoint",
+                );
+                return OnCalledAction::Trap(Box::new(WasiError::Exit(Errno::Io.into())));
+            }
+        };
+        tracing::debug!("Journaled a checkpoint");
+
+        // Carry on as if nothing happened
+        match rewind::<M>(ctx, memory_stack, rewind_stack, store_data) {
+            Errno::Success => OnCalledAction::InvokeAgain,
+            err => {
+                tracing::warn!("checkpoint failed - could not rewind the stack - errno={err}");
+                OnCalledAction::Trap(Box::new(WasiError::Exit(err.into())))
+            }
+        }
+    })
+}
+
+/// The offsets of the files and directories the process opened, the ones
+/// the runtime opened for it are left alone.
+fn fd_offsets(env: &WasiEnv) -> Vec<(u32, u64)> {
+    let fs = &env.state.fs;
+    let preopens = fs.preopen_fds.read().unwrap().clone();
+    let fd_map = fs.fd_map.read().unwrap();
+    let mut offsets: Vec<_> = fd_map
+        .iter()
+        .filter(|(fd, entry)| !entry.is_stdio && !preopens.contains(fd))
+        .map(|(fd, entry)| (*fd, entry.offset.load(Ordering::SeqCst)))
+        .collect();
+    offsets.sort_unstable();
+    offsets
+}
+
+/// The journal entry of a file descriptor that was just opened, `None` for
+/// anything but files and directories.
+pub(crate) fn fd_entry(env: &WasiEnv, fd: u32) -> Option<FdEntry> {
+    use crate::fs::Kind;
+    use std::ops::Deref;
+
+    let entry = env.state.fs.get_fd(fd).ok()?;
+    if entry.is_stdio {
+        return None;
+    }
+    let guard = entry.inode.read();
+    let (path, is_dir) = match guard.deref() {
+        Kind::File { path, fd: None, .. } => (path.clone(), false),
+        Kind::Dir { path, .. } => (path.clone(), true),
+        _ => return None,
+    };
+    Some(FdEntry {
+        fd,
+        path,
+        is_dir,
+        rights: entry.rights.bits(),
+        rights_inheriting: entry.rights_inheriting.bits(),
+        flags: entry.flags.bits(),
+        open_flags: entry.open_flags,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changed_pages_are_journaled() {
+        let mut memory = vec![0; 4 * WASM_PAGE_SIZE];
+        memory[10] = 1;
+        memory[2 * WASM_PAGE_SIZE + 5] = 2;
+        memory[3 * WASM_PAGE_SIZE + 5] = 3;
+
+        let mut tracker = MemoryTracker::default();
+        let regions = tracker.update(&memory);
+        let starts: Vec<_> = regions
+            .iter()
+            .map(|r| match r {
+                JournalEntry::UpdateMemoryRegion { start, data } => (*start, data.len()),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            starts,
+            [
+                (0, WASM_PAGE_SIZE),
+                (2 * WASM_PAGE_SIZE as u64, 2 * WASM_PAGE_SIZE)
+            ]
+        );
+
+        memory[3 * WASM_PAGE_SIZE + 6] = 4;
+        memory.extend_from_slice(&[0; WASM_PAGE_SIZE]);
+        let regions = tracker.update(&memory);
+        assert_eq!(regions.len(), 1);
+        assert!(matches!(
+            &regions[0],
+            JournalEntry::UpdateMemoryRegion { start, data }
+                if *start == 3 * WASM_PAGE_SIZE as u64 && data.len() == WASM_PAGE_SIZE
+        ));
+
+        assert!(tracker.update(&memory).is_empty());
+        assert!(MemoryTracker::from_image(&memory)
+            .update(&memory)
+            .is_empty());
+    }
+
+    #[test]
+    fn processes_are_recovered_from_their_last_checkpoint() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("app.journal");
+        let checkpoint = Checkpoint {
+            memory_size: WASM_PAGE_SIZE as u64,
+            memory64: false,
+            memory_stack: vec![1],
+            rewind_stack: vec![2],
+            store_data: vec![3],
+            current_dir: "/".to_string(),
+            fd_offsets: Vec::new(),
+            monotonic_clock: 0,
+        };
+        let entries = vec![
+            JournalEntry::InitModule {
+                module: "abc".to_string(),
+            },
+            JournalEntry::UpdateMemoryRegion {
+                start: 0,
+                data: vec![7; WASM_PAGE_SIZE],
+            },
+            JournalEntry::Checkpoint(checkpoint),
+        ];
+        drop(LogFile::rewrite(&path, &entries, false, &StorageOptions::new()).unwrap());
+
+        let journal = Journal::open(JournalConfig::new(&path)).unwrap();
+        let snapshot = journal.recovered().unwrap();
+        assert!(snapshot.memory.iter().all(|b| *b == 7));
+        // Pages that didn't change since aren't journaled again
+        let memory = snapshot.memory.clone();
+        assert!(journal.memory.lock().unwrap().update(&memory).is_empty());
+
+        journal.record(&JournalEntry::Exit { code: 0 }).unwrap();
+        drop(journal);
+        let journal = Journal::open(JournalConfig::new(&path)).unwrap();
+        assert!(journal.recovered().is_none());
+        assert!(LogFile::read(&path, &StorageOptions::new())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn journals_are_filtered_and_compacted_as_they_grow() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("app.journal");
+        let config = JournalConfig::new(&path)
+            .with_dirty_page_tracking(false)
+            .with_filter(
+                JournalFilter::new()
+                    .exclude(EventClass::Stdio)
+                    .with_max_entry_size(1024),
+            )
+            .with_retention(Retention::new().with_max_size(4096));
+        let journal = Journal::open(config).unwrap();
+
+        let mut store = wasmer::Store::default();
+        let memory =
+            wasmer::Memory::new(&mut store, wasmer::MemoryType::new(1, None, false)).unwrap();
+        let checkpoint = Checkpoint {
+            memory_size: WASM_PAGE_SIZE as u64,
+            memory64: false,
+            memory_stack: Vec::new(),
+            rewind_stack: Vec::new(),
+            store_data: Vec::new(),
+            current_dir: "/".to_string(),
+            fd_offsets: Vec::new(),
+            monotonic_clock: 0,
+        };
+        let write = |data: Vec<u8>| JournalEntry::WriteFile {
+            path: PathBuf::from("/tmp/log.txt"),
+            offset: 0,
+            data,
+        };
+
+        journal
+            .record(&JournalEntry::InitModule {
+                module: "abc".to_string(),
+            })
+            .unwrap();
+        journal
+            .record(&JournalEntry::SetFileLength {
+                path: PathBuf::from("/tmp/log.txt"),
+                len: 0,
+            })
+            .unwrap();
+        for i in 0..10 {
+            journal.record(&write(vec![i; 512])).unwrap();
+            journal
+                .record(&JournalEntry::Output {
+                    fd: 1,
+                    data: b"hello".to_vec(),
+                })
+                .unwrap();
+        }
+        journal.record(&write(vec![0; 2048])).unwrap();
+        assert_eq!(journal.oversized_entries(), 1);
+        let before = journal.log.lock().unwrap().size();
+        assert!(before > 4096);
+
+        journal
+            .record_checkpoint(&memory.view(&store), true, checkpoint)
+            .unwrap();
+        let entries = LogFile::read(&path, &StorageOptions::new()).unwrap();
+        assert!(journal.log.lock().unwrap().size() < before);
+        assert!(!entries
+            .iter()
+            .any(|entry| matches!(entry, JournalEntry::Output { .. })));
+        // The writes that overwrote each other were folded into one
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|entry| matches!(entry, JournalEntry::WriteFile { .. }))
+                .count(),
+            1
+        );
+        assert!(matches!(entries.last(), Some(JournalEntry::Checkpoint(_))));
+    }
+}
@@ -0,0 +1,249 @@
+use std::{collections::HashSet, fmt, str::FromStr, time::Duration};
+
+use super::JournalEntry;
+
+/// The kinds of events a [`JournalFilter`] can leave out of a journal.
+///
+/// The start and exit of the process, its memory and its checkpoints
+/// aren't part of any class, they are always journaled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventClass {
+    /// File descriptors and changes to the directory tree
+    Filesystem,
+    /// Data written to files
+    FileWrites,
+    Sockets,
+    /// Data written to stdout and stderr
+    Stdio,
+    Clocks,
+    Random,
+    /// Data read from file descriptors
+    Reads,
+    Polls,
+}
+
+impl EventClass {
+    pub const ALL: &'static [EventClass] = &[
+        EventClass::Filesystem,
+        EventClass::FileWrites,
+        EventClass::Sockets,
+        EventClass::Stdio,
+        EventClass::Clocks,
+        EventClass::Random,
+        EventClass::Reads,
+        EventClass::Polls,
+    ];
+
+    /// The class of an entry, `None` for the entries that are always
+    /// journaled.
+    pub fn of(entry: &JournalEntry) -> Option<Self> {
+        match entry {
+            JournalEntry::InitModule { .. }
+            | JournalEntry::UpdateMemoryRegion { .. }
+            | JournalEntry::Checkpoint(_)
+            | JournalEntry::Exit { .. } => None,
+            JournalEntry::OpenFd(_)
+            | JournalEntry::CloseFd { .. }
+            | JournalEntry::RenumberFd { .. }
+            | JournalEntry::SetFileLength { .. }
+            | JournalEntry::CreateDirectory { .. }
+            | JournalEntry::RemoveFile { .. }
+            | JournalEntry::RemoveDirectory { .. }
+            | JournalEntry::Rename { .. } => Some(EventClass::Filesystem),
+            JournalEntry::WriteFile { .. } => Some(EventClass::FileWrites),
+            JournalEntry::Socket { .. } => Some(EventClass::Sockets),
+            JournalEntry::Output { .. } => Some(EventClass::Stdio),
+            JournalEntry::ClockTime { .. } => Some(EventClass::Clocks),
+            JournalEntry::Random { .. } => Some(EventClass::Random),
+            JournalEntry::Read { .. } => Some(EventClass::Reads),
+            JournalEntry::Poll { .. } => Some(EventClass::Polls),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            EventClass::Filesystem => "filesystem",
+            EventClass::FileWrites => "file-writes",
+            EventClass::Sockets => "sockets",
+            EventClass::Stdio => "stdio",
+            EventClass::Clocks => "clocks",
+            EventClass::Random => "random",
+            EventClass::Reads => "reads",
+            EventClass::Polls => "polls",
+        }
+    }
+}
+
+impl fmt::Display for EventClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for EventClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EventClass::ALL
+            .iter()
+            .copied()
+            .find(|class| class.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = EventClass::ALL.iter().map(|c| c.name()).collect();
+                format!(
+                    "unknown event class \"{s}\", expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Which events are journaled.
+///
+/// What is left out of a journal is lost: a process recovered from a
+/// journal without [`EventClass::FileWrites`] doesn't get the data it wrote
+/// to its files back, and a run journaled without one of its inputs can
+/// only be replayed with the same filter. A run with entries that were left
+/// out for their size can't be replayed at all.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JournalFilter {
+    excluded: HashSet<EventClass>,
+    max_entry_size: Option<u64>,
+}
+
+impl JournalFilter {
+    /// Journals every event.
+    pub fn new() -> Self {
+        JournalFilter::default()
+    }
+
+    pub fn exclude(mut self, class: EventClass) -> Self {
+        self.excluded.insert(class);
+        self
+    }
+
+    /// Leaves out the entries of any class that take more than `bytes` to
+    /// encode, e.g. large writes and reads.
+    pub fn with_max_entry_size(mut self, bytes: u64) -> Self {
+        self.max_entry_size = Some(bytes);
+        self
+    }
+
+    /// Whether the class of `entry` is journaled.
+    pub fn includes(&self, entry: &JournalEntry) -> bool {
+        match EventClass::of(entry) {
+            Some(class) => !self.excluded.contains(&class),
+            None => true,
+        }
+    }
+
+    /// Whether `entry` is journaled, taking its size into account too.
+    pub fn admits(&self, entry: &JournalEntry) -> bool {
+        if !self.includes(entry) {
+            return false;
+        }
+        match (EventClass::of(entry), self.max_entry_size) {
+            (Some(_), Some(max)) => bincode::serialized_size(entry)
+                .map(|size| size <= max)
+                .unwrap_or(false),
+            _ => true,
+        }
+    }
+}
+
+/// How much history a journal keeps before it is compacted.
+///
+/// A journal only grows while the process runs. With a retention policy it
+/// is compacted right after the checkpoint that takes it over a limit,
+/// folding everything up to that checkpoint into the entries that produce
+/// the same state. Compaction drops the inputs of the process, so a
+/// compacted run can't be replayed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Retention {
+    pub max_size: Option<u64>,
+    pub max_age: Option<Duration>,
+}
+
+impl Retention {
+    /// Keeps everything until the journal is reopened.
+    pub fn new() -> Self {
+        Retention::default()
+    }
+
+    /// Compacts once the journal takes more than `bytes` on disk.
+    pub fn with_max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Compacts once `age` passed since the journal was last compacted.
+    pub fn with_max_age(mut self, age: Duration) -> Self {
+        self.max_age = Some(age);
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_size.is_some() || self.max_age.is_some()
+    }
+
+    /// Whether a journal of `size` bytes, compacted `age` ago, should be
+    /// compacted again.
+    pub(crate) fn is_exceeded(&self, size: u64, age: Duration) -> bool {
+        self.max_size.map_or(false, |max| size > max) || self.max_age.map_or(false, |max| age > max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn excluded_and_oversized_entries_are_left_out() {
+        let filter = JournalFilter::new()
+            .exclude(EventClass::Stdio)
+            .exclude(EventClass::Reads)
+            .with_max_entry_size(64);
+
+        let output = JournalEntry::Output {
+            fd: 1,
+            data: b"hello".to_vec(),
+        };
+        assert!(!filter.includes(&output));
+        let write = |len| JournalEntry::WriteFile {
+            path: PathBuf::from("/tmp/log.txt"),
+            offset: 0,
+            data: vec![0; len],
+        };
+        assert!(filter.admits(&write(8)));
+        assert!(filter.includes(&write(1024)));
+        assert!(!filter.admits(&write(1024)));
+
+        // Memory is needed to recover the process, whatever its size
+        let memory = JournalEntry::UpdateMemoryRegion {
+            start: 0,
+            data: vec![0; 1024],
+        };
+        assert!(filter.admits(&memory));
+    }
+
+    #[test]
+    fn classes_are_parsed_by_name() {
+        for class in EventClass::ALL {
+            assert_eq!(class.to_string().parse::<EventClass>(), Ok(*class));
+        }
+        assert!("stdout".parse::<EventClass>().is_err());
+    }
+
+    #[test]
+    fn retention_limits() {
+        let retention = Retention::new()
+            .with_max_size(1024)
+            .with_max_age(Duration::from_secs(60));
+        assert!(!retention.is_exceeded(512, Duration::from_secs(10)));
+        assert!(retention.is_exceeded(2048, Duration::from_secs(10)));
+        assert!(retention.is_exceeded(512, Duration::from_secs(61)));
+        assert!(!Retention::new().is_exceeded(u64::MAX, Duration::MAX));
+    }
+}
@@ -0,0 +1,305 @@
+use std::{fmt, path::Path, sync::Mutex};
+
+use wasmer::FromToNativeWasmType;
+use wasmer_wasix_types::wasi::Errno;
+
+use super::{JournalBackend, JournalEntry, JournalError, JournalFilter, LogFile};
+use crate::storage::StorageOptions;
+
+/// Replays a run that was journaled with its inputs.
+///
+/// The process is started from scratch and, instead of reading the clocks,
+/// drawing random bytes, reading from file descriptors and polling, it is
+/// handed the inputs that were journaled. Every state change it makes is
+/// checked against the journal, the first one that differs is a
+/// [`Divergence`] and ends the process.
+///
+/// Only the last run in the journal is replayed, and only if it ran from
+/// the start: a run that was recovered from a checkpoint can't be replayed.
+/// A run journaled with a [`JournalFilter`] is replayed with the same
+/// filter, the events it leaves out aren't checked and the inputs it leaves
+/// out are read for real.
+#[derive(Debug)]
+pub struct Replay {
+    entries: Vec<JournalEntry>,
+    filter: JournalFilter,
+    position: Mutex<usize>,
+    divergence: Mutex<Option<Divergence>>,
+}
+
+impl Replay {
+    /// Reads the journal at `path`.
+    pub fn open(path: &Path, storage: &StorageOptions) -> Result<Self, JournalError> {
+        LogFile::read(path, storage).map(Replay::new)
+    }
+
+    /// Reads the journal kept by `backend`.
+    pub fn from_backend(
+        backend: &dyn JournalBackend,
+        storage: &StorageOptions,
+    ) -> Result<Self, JournalError> {
+        LogFile::read_from(backend, storage).map(Replay::new)
+    }
+
+    pub fn new(entries: Vec<JournalEntry>) -> Self {
+        let start = entries
+            .iter()
+            .rposition(|entry| matches!(entry, JournalEntry::InitModule { .. }))
+            .unwrap_or(0);
+        // Checkpoints are taken whenever they are due, they aren't part of
+        // what the process did
+        let entries = entries
+            .into_iter()
+            .skip(start)
+            .filter(|entry| {
+                !matches!(
+                    entry,
+                    JournalEntry::UpdateMemoryRegion { .. } | JournalEntry::Checkpoint(_)
+                )
+            })
+            .collect();
+
+        Replay {
+            entries,
+            filter: JournalFilter::default(),
+            position: Mutex::new(0),
+            divergence: Mutex::new(None),
+        }
+    }
+
+    pub fn with_filter(mut self, filter: JournalFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Whether events like `entry` were journaled, and so are replayed.
+    pub(crate) fn replays(&self, entry: &JournalEntry) -> bool {
+        self.filter.includes(entry)
+    }
+
+    /// Checks an entry of the replayed process against the journal and
+    /// returns the journaled one. For inputs, only what the process asked
+    /// for has to match, e.g. the clock it reads.
+    pub(crate) fn next(&self, actual: &JournalEntry) -> Result<JournalEntry, Divergence> {
+        let mut divergence = self.divergence.lock().unwrap();
+        if let Some(divergence) = divergence.as_ref() {
+            return Err(divergence.clone());
+        }
+
+        let mut position = self.position.lock().unwrap();
+        match self.entries.get(*position) {
+            Some(expected) if is_same_event(expected, actual) => {
+                *position += 1;
+                Ok(expected.clone())
+            }
+            expected => {
+                let diverged = Divergence {
+                    index: *position,
+                    expected: expected.cloned(),
+                    actual: Some(actual.clone()),
+                };
+                *divergence = Some(diverged.clone());
+                Err(diverged)
+            }
+        }
+    }
+
+    /// The first divergence of the replayed process, if any.
+    pub fn divergence(&self) -> Option<Divergence> {
+        self.divergence.lock().unwrap().clone()
+    }
+
+    /// Checks that the replayed process did everything that was journaled,
+    /// call it once the process exited.
+    pub fn finish(&self) -> Result<(), Divergence> {
+        if let Some(divergence) = self.divergence() {
+            return Err(divergence);
+        }
+        let position = *self.position.lock().unwrap();
+        match self.entries.get(position) {
+            Some(expected) => Err(Divergence {
+                index: position,
+                expected: Some(expected.clone()),
+                actual: None,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+fn is_same_event(expected: &JournalEntry, actual: &JournalEntry) -> bool {
+    match (expected, actual) {
+        (JournalEntry::ClockTime { clock: a, .. }, JournalEntry::ClockTime { clock: b, .. }) => {
+            a == b
+        }
+        (JournalEntry::Random { data: a }, JournalEntry::Random { data: b }) => a.len() == b.len(),
+        (JournalEntry::Read { fd: a, .. }, JournalEntry::Read { fd: b, .. }) => a == b,
+        (JournalEntry::Poll { .. }, JournalEntry::Poll { .. }) => true,
+        _ => expected == actual,
+    }
+}
+
+/// The errno of a journaled input that failed.
+pub(crate) fn errno(code: u16) -> Errno {
+    Errno::from_native(code as i32)
+}
+
+/// The replayed process did something else than what was journaled.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub struct Divergence {
+    /// The index of the entry in the replayed run
+    pub index: usize,
+    /// `None` if the process did more than what was journaled
+    pub expected: Option<JournalEntry>,
+    /// `None` if the process exited before doing everything that was
+    /// journaled
+    pub actual: Option<JournalEntry>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at entry {}, expected ", self.index)?;
+        match self.expected.as_ref() {
+            Some(entry) => write!(f, "{}", Summary(entry))?,
+            None => write!(f, "the end of the journal")?,
+        }
+        write!(f, " but the process ")?;
+        match self.actual.as_ref() {
+            Some(entry) => write!(f, "did {}", Summary(entry)),
+            None => write!(f, "exited"),
+        }
+    }
+}
+
+/// Describes an entry without the data it carries.
+struct Summary<'a>(&'a JournalEntry);
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            JournalEntry::WriteFile { path, offset, data } => write!(
+                f,
+                "WriteFile {{ path: {path:?}, offset: {offset}, len: {} }}",
+                data.len()
+            ),
+            JournalEntry::Output { fd, data } => {
+                write!(f, "Output {{ fd: {fd}, len: {} }}", data.len())
+            }
+            JournalEntry::Random { data } => write!(f, "Random {{ len: {} }}", data.len()),
+            JournalEntry::Read { fd, result } => match result {
+                Ok(data) => write!(f, "Read {{ fd: {fd}, len: {} }}", data.len()),
+                Err(code) => write!(f, "Read {{ fd: {fd}, errno: {} }}", errno(*code)),
+            },
+            JournalEntry::Poll { result } => match result {
+                Ok(events) => write!(f, "Poll {{ len: {} }}", events.len()),
+                Err(code) => write!(f, "Poll {{ errno: {} }}", errno(*code)),
+            },
+            entry => write!(f, "{entry:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::journal::Checkpoint;
+
+    fn init() -> JournalEntry {
+        JournalEntry::InitModule {
+            module: "abc".to_string(),
+        }
+    }
+
+    #[test]
+    fn inputs_are_handed_back_in_order() {
+        let entries = vec![
+            JournalEntry::Exit { code: 1 },
+            init(),
+            JournalEntry::ClockTime { clock: 1, time: 42 },
+            JournalEntry::UpdateMemoryRegion {
+                start: 0,
+                data: vec![0; 16],
+            },
+            JournalEntry::Checkpoint(Checkpoint {
+                memory_size: 16,
+                memory64: false,
+                memory_stack: Vec::new(),
+                rewind_stack: Vec::new(),
+                store_data: Vec::new(),
+                current_dir: "/".to_string(),
+                fd_offsets: Vec::new(),
+                monotonic_clock: 0,
+            }),
+            JournalEntry::Random {
+                data: vec![1, 2, 3],
+            },
+            JournalEntry::Exit { code: 0 },
+        ];
+        let replay = Replay::new(entries);
+
+        assert_eq!(replay.next(&init()).unwrap(), init());
+        assert_eq!(
+            replay
+                .next(&JournalEntry::ClockTime { clock: 1, time: 0 })
+                .unwrap(),
+            JournalEntry::ClockTime { clock: 1, time: 42 }
+        );
+        assert_eq!(
+            replay
+                .next(&JournalEntry::Random { data: vec![0; 3] })
+                .unwrap(),
+            JournalEntry::Random {
+                data: vec![1, 2, 3]
+            }
+        );
+        assert!(replay.finish().is_err());
+        replay.next(&JournalEntry::Exit { code: 0 }).unwrap();
+        assert_eq!(replay.finish(), Ok(()));
+    }
+
+    #[test]
+    fn the_first_divergence_sticks() {
+        let written = JournalEntry::CreateDirectory {
+            path: PathBuf::from("/tmp/a"),
+        };
+        let replay = Replay::new(vec![
+            init(),
+            written.clone(),
+            JournalEntry::Read {
+                fd: 3,
+                result: Ok(b"hello".to_vec()),
+            },
+        ]);
+        replay.next(&init()).unwrap();
+
+        let other = JournalEntry::CreateDirectory {
+            path: PathBuf::from("/tmp/b"),
+        };
+        let divergence = replay.next(&other).unwrap_err();
+        assert_eq!(divergence.index, 1);
+        assert_eq!(divergence.expected, Some(written.clone()));
+        assert_eq!(
+            divergence.to_string(),
+            "at entry 1, expected CreateDirectory { path: \"/tmp/a\" } \
+             but the process did CreateDirectory { path: \"/tmp/b\" }"
+        );
+
+        // Even if the process gets back on track
+        assert_eq!(replay.next(&written), Err(divergence.clone()));
+        assert_eq!(replay.finish(), Err(divergence));
+    }
+
+    #[test]
+    fn filtered_events_are_not_replayed() {
+        let replay = Replay::new(vec![init(), JournalEntry::Exit { code: 0 }])
+            .with_filter(JournalFilter::new().exclude(crate::journal::EventClass::Stdio));
+        let output = JournalEntry::Output {
+            fd: 1,
+            data: b"hello".to_vec(),
+        };
+        assert!(!replay.replays(&output));
+        assert!(replay.replays(&init()));
+    }
+}
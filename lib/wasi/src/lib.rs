@@ -46,7 +46,14 @@ mod syscalls;
 mod utils;
 pub mod wapm;
 
+pub mod audit;
 pub mod capabilities;
+pub mod journal;
+pub mod metrics;
+pub mod migration;
+pub mod parking;
+pub mod snapshot;
+pub mod storage;
 
 /// WAI based bindings.
 mod bindings;
@@ -84,10 +91,16 @@ pub use virtual_net::{UnsupportedVirtualNetworking, VirtualNetworking};
 pub use virtual_net::host::{
     io_err_into_net_error, LocalNetworking, LocalTcpListener, LocalTcpStream, LocalUdpSocket,
 };
+#[cfg(feature = "tls")]
+pub use virtual_net::tls::{TlsIdentity, TlsNetworking, TlsTrust};
 use wasmer_wasix_types::wasi::{BusErrno, Errno, ExitCode};
 
 pub use crate::{
     fs::{default_fs_backing, Fd, WasiFs, WasiInodes, VIRTUAL_ROOT_FD},
+    net::{
+        limits::{SocketLimitStats, SocketLimiter, SocketLimits},
+        NetworkMode,
+    },
     os::{
         task::{
             control_plane::WasiControlPlane,
@@ -122,6 +135,8 @@ pub enum WasiError {
     Exit(ExitCode),
     #[error("The WASI version could not be determined")]
     UnknownWasiVersion,
+    #[error("Modules with a 64-bit memory can't use {}, only wasix_64v1", .0.get_namespace_str())]
+    UnsupportedMemory64(WasiVersion),
 }
 
 /// Represents the ID of a WASI calling thread
@@ -236,6 +251,8 @@ pub enum WasiRuntimeError {
     Runtime(#[from] RuntimeError),
     #[error("Memory access error")]
     Thread(#[from] WasiThreadError),
+    #[error("Unable to resume from the snapshot")]
+    Snapshot(#[from] crate::snapshot::SnapshotError),
 }
 
 impl WasiRuntimeError {
@@ -591,14 +608,19 @@ fn wasix_exports_32(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "sock_join_multicast_v6" => Function::new_typed_with_env(&mut store, env, sock_join_multicast_v6::<Memory32>),
         "sock_leave_multicast_v6" => Function::new_typed_with_env(&mut store, env, sock_leave_multicast_v6::<Memory32>),
         "sock_bind" => Function::new_typed_with_env(&mut store, env, sock_bind::<Memory32>),
+        "sock_bind_unix" => Function::new_typed_with_env(&mut store, env, sock_bind_unix::<Memory32>),
         "sock_listen" => Function::new_typed_with_env(&mut store, env, sock_listen::<Memory32>),
         "sock_accept" => Function::new_typed_with_env(&mut store, env, sock_accept::<Memory32>),
         "sock_connect" => Function::new_typed_with_env(&mut store, env, sock_connect::<Memory32>),
+        "sock_connect_unix" => Function::new_typed_with_env(&mut store, env, sock_connect_unix::<Memory32>),
+        "sock_pair" => Function::new_typed_with_env(&mut store, env, sock_pair::<Memory32>),
         "sock_recv" => Function::new_typed_with_env(&mut store, env, sock_recv::<Memory32>),
         "sock_recv_from" => Function::new_typed_with_env(&mut store, env, sock_recv_from::<Memory32>),
         "sock_send" => Function::new_typed_with_env(&mut store, env, sock_send::<Memory32>),
         "sock_send_to" => Function::new_typed_with_env(&mut store, env, sock_send_to::<Memory32>),
         "sock_send_file" => Function::new_typed_with_env(&mut store, env, sock_send_file::<Memory32>),
+        "sock_send_fds" => Function::new_typed_with_env(&mut store, env, sock_send_fds::<Memory32>),
+        "sock_recv_fds" => Function::new_typed_with_env(&mut store, env, sock_recv_fds::<Memory32>),
         "sock_shutdown" => Function::new_typed_with_env(&mut store, env, sock_shutdown),
         "resolve" => Function::new_typed_with_env(&mut store, env, resolve::<Memory32>),
     };
@@ -714,14 +736,19 @@ fn wasix_exports_64(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "sock_join_multicast_v6" => Function::new_typed_with_env(&mut store, env, sock_join_multicast_v6::<Memory64>),
         "sock_leave_multicast_v6" => Function::new_typed_with_env(&mut store, env, sock_leave_multicast_v6::<Memory64>),
         "sock_bind" => Function::new_typed_with_env(&mut store, env, sock_bind::<Memory64>),
+        "sock_bind_unix" => Function::new_typed_with_env(&mut store, env, sock_bind_unix::<Memory64>),
         "sock_listen" => Function::new_typed_with_env(&mut store, env, sock_listen::<Memory64>),
         "sock_accept" => Function::new_typed_with_env(&mut store, env, sock_accept::<Memory64>),
         "sock_connect" => Function::new_typed_with_env(&mut store, env, sock_connect::<Memory64>),
+        "sock_connect_unix" => Function::new_typed_with_env(&mut store, env, sock_connect_unix::<Memory64>),
+        "sock_pair" => Function::new_typed_with_env(&mut store, env, sock_pair::<Memory64>),
         "sock_recv" => Function::new_typed_with_env(&mut store, env, sock_recv::<Memory64>),
         "sock_recv_from" => Function::new_typed_with_env(&mut store, env, sock_recv_from::<Memory64>),
         "sock_send" => Function::new_typed_with_env(&mut store, env, sock_send::<Memory64>),
         "sock_send_to" => Function::new_typed_with_env(&mut store, env, sock_send_to::<Memory64>),
         "sock_send_file" => Function::new_typed_with_env(&mut store, env, sock_send_file::<Memory64>),
+        "sock_send_fds" => Function::new_typed_with_env(&mut store, env, sock_send_fds::<Memory64>),
+        "sock_recv_fds" => Function::new_typed_with_env(&mut store, env, sock_recv_fds::<Memory64>),
         "sock_shutdown" => Function::new_typed_with_env(&mut store, env, sock_shutdown),
         "resolve" => Function::new_typed_with_env(&mut store, env, resolve::<Memory64>),
     };
@@ -793,6 +820,11 @@ fn import_object_for_all_wasi_versions(
         }
     }
 
+    let metrics = env.as_ref(store).control_plane.metrics().cloned();
+    if let Some(sink) = metrics {
+        imports = crate::metrics::instrument_imports(store, env, imports, sink);
+    }
+
     (imports, init)
 }
 
@@ -843,6 +875,9 @@ fn mem_error_to_wasi(err: MemoryAccessError) -> Errno {
         MemoryAccessError::HeapOutOfBounds => Errno::Memviolation,
         MemoryAccessError::Overflow => Errno::Overflow,
         MemoryAccessError::NonUtf8String => Errno::Inval,
+        MemoryAccessError::Misaligned => Errno::Inval,
+        MemoryAccessError::Unterminated => Errno::Inval,
+        MemoryAccessError::NotShared => Errno::Inval,
         _ => Errno::Unknown,
     }
 }
@@ -852,6 +887,9 @@ fn mem_error_to_bus(err: MemoryAccessError) -> BusErrno {
         MemoryAccessError::HeapOutOfBounds => BusErrno::Memviolation,
         MemoryAccessError::Overflow => BusErrno::Memviolation,
         MemoryAccessError::NonUtf8String => BusErrno::Badrequest,
+        MemoryAccessError::Misaligned => BusErrno::Badrequest,
+        MemoryAccessError::Unterminated => BusErrno::Badrequest,
+        MemoryAccessError::NotShared => BusErrno::Badrequest,
         _ => BusErrno::Unknown,
     }
 }
@@ -180,3 +180,31 @@ macro_rules! get_input_str_bus_ok {
         wasi_try_mem_bus_ok!($data.read_utf8_string($memory, $len))
     }};
 }
+
+/// Takes a snapshot of the process if one was requested, by unwinding the
+/// stack out of the current syscall. When the process is resumed from the
+/// snapshot the syscall is entered again and carries on as usual. Journal
+/// checkpoints are taken the same way, but the process carries on right
+/// away. Parked processes are unwound here too, and thawed ones rewound. A
+/// migrating process copies its memory here until it is ready to be
+/// stopped.
+macro_rules! maybe_snapshot {
+    ($ctx:ident, $memory_size:ty) => {
+        if $crate::syscalls::handle_rewind::<$memory_size>(&mut $ctx) {
+            tracing::debug!("Resumed from a snapshot");
+            if let Some(parking) = $ctx.data().parking.as_ref() {
+                parking.resumed();
+            }
+        } else if $ctx.data().snapshot_requested() {
+            return $crate::snapshot::unwind_and_save::<$memory_size>($ctx);
+        } else if $ctx.data().checkpoint_requested() {
+            return $crate::journal::unwind_and_checkpoint::<$memory_size>($ctx);
+        } else if $ctx.data().park_requested() {
+            return $crate::parking::unwind_and_park::<$memory_size>($ctx);
+        } else if $ctx.data().migration_requested()
+            && $crate::migration::pre_copy::<$memory_size>(&mut $ctx)
+        {
+            return $crate::migration::unwind_and_migrate::<$memory_size>($ctx);
+        }
+    };
+}
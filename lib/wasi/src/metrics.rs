@@ -0,0 +1,430 @@
+//! Per-instance metrics.
+//!
+//! A [`MetricsSink`] that is attached to an instance (see
+//! [`WasiEnvBuilder::metrics`](crate::WasiEnvBuilder::metrics)) is told
+//! about every syscall the guest makes, the bytes that flow through its
+//! file descriptors and sockets, growth of its linear memory and the time
+//! it spends executing guest code. [`InstanceMetrics`] keeps those numbers
+//! in memory and [`PrometheusEncoder`] renders them in the Prometheus text
+//! exposition format.
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use wasmer::{AsStoreMut, Extern, Function, FunctionEnv, FunctionEnvMut, Imports, Value};
+
+use crate::{net::limits::SocketLimitStats, WasiEnv};
+
+/// Receives the measurements made while an instance runs.
+///
+/// All the methods have empty default implementations so that a sink only
+/// needs to implement the measurements it is interested in.
+pub trait MetricsSink: std::fmt::Debug + Send + Sync {
+    /// A syscall returned after running for `duration`.
+    fn syscall(&self, name: &str, duration: Duration) {
+        let _ = (name, duration);
+    }
+
+    /// Bytes were read through a file descriptor.
+    fn bytes_read(&self, bytes: u64) {
+        let _ = bytes;
+    }
+
+    /// Bytes were written through a file descriptor.
+    fn bytes_written(&self, bytes: u64) {
+        let _ = bytes;
+    }
+
+    /// Bytes were received on a socket.
+    fn net_received(&self, bytes: u64) {
+        let _ = bytes;
+    }
+
+    /// Bytes were sent on a socket.
+    fn net_sent(&self, bytes: u64) {
+        let _ = bytes;
+    }
+
+    /// The linear memory grew from `old_pages` to `new_pages`.
+    fn memory_grown(&self, old_pages: u32, new_pages: u32) {
+        let _ = (old_pages, new_pages);
+    }
+
+    /// The guest executed its own code for `duration` without calling
+    /// into the host.
+    fn guest_time(&self, duration: Duration) {
+        let _ = duration;
+    }
+}
+
+/// Counters of a single syscall.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallStats {
+    pub calls: u64,
+    pub time: Duration,
+}
+
+/// Snapshot of the measurements collected by [`InstanceMetrics`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub syscalls: BTreeMap<String, SyscallStats>,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub net_received: u64,
+    pub net_sent: u64,
+    pub memory_grow_events: u64,
+    pub memory_pages: u32,
+    pub guest_time: Duration,
+}
+
+/// In-memory [`MetricsSink`] that accumulates everything it is told.
+#[derive(Debug, Default)]
+pub struct InstanceMetrics {
+    syscalls: Mutex<BTreeMap<String, SyscallStats>>,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    net_received: AtomicU64,
+    net_sent: AtomicU64,
+    memory_grow_events: AtomicU64,
+    memory_pages: AtomicU32,
+    guest_time_nanos: AtomicU64,
+}
+
+impl InstanceMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the measurements collected so far.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            syscalls: self.syscalls.lock().unwrap().clone(),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            net_received: self.net_received.load(Ordering::Relaxed),
+            net_sent: self.net_sent.load(Ordering::Relaxed),
+            memory_grow_events: self.memory_grow_events.load(Ordering::Relaxed),
+            memory_pages: self.memory_pages.load(Ordering::Relaxed),
+            guest_time: Duration::from_nanos(self.guest_time_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl MetricsSink for InstanceMetrics {
+    fn syscall(&self, name: &str, duration: Duration) {
+        let mut syscalls = self.syscalls.lock().unwrap();
+        if !syscalls.contains_key(name) {
+            syscalls.insert(name.to_string(), SyscallStats::default());
+        }
+        let stats = syscalls.get_mut(name).unwrap();
+        stats.calls += 1;
+        stats.time += duration;
+    }
+
+    fn bytes_read(&self, bytes: u64) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn bytes_written(&self, bytes: u64) {
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn net_received(&self, bytes: u64) {
+        self.net_received.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn net_sent(&self, bytes: u64) {
+        self.net_sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn memory_grown(&self, _old_pages: u32, new_pages: u32) {
+        self.memory_grow_events.fetch_add(1, Ordering::Relaxed);
+        self.memory_pages.fetch_max(new_pages, Ordering::Relaxed);
+    }
+
+    fn guest_time(&self, duration: Duration) {
+        self.guest_time_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Renders metrics in the Prometheus text exposition format.
+///
+/// Every sample carries the labels the encoder was created with, which
+/// makes it possible to concatenate the output of several instances.
+#[derive(Debug, Clone, Default)]
+pub struct PrometheusEncoder {
+    labels: Vec<(String, String)>,
+}
+
+impl PrometheusEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a label to every sample (e.g. `instance="my-app"`).
+    pub fn with_label(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.push((name.into(), value.into()));
+        self
+    }
+
+    /// Encodes a snapshot of [`InstanceMetrics`].
+    pub fn encode(&self, snapshot: &MetricsSnapshot) -> String {
+        let mut out = String::new();
+
+        self.header(
+            &mut out,
+            "wasi_syscalls_total",
+            "counter",
+            "Syscalls made by the guest.",
+        );
+        for (name, stats) in &snapshot.syscalls {
+            self.sample(
+                &mut out,
+                "wasi_syscalls_total",
+                Some(("syscall", name)),
+                stats.calls,
+            );
+        }
+        self.header(
+            &mut out,
+            "wasi_syscall_seconds_total",
+            "counter",
+            "Time spent in the host handling syscalls.",
+        );
+        for (name, stats) in &snapshot.syscalls {
+            self.sample(
+                &mut out,
+                "wasi_syscall_seconds_total",
+                Some(("syscall", name)),
+                stats.time.as_secs_f64(),
+            );
+        }
+
+        let counters = [
+            (
+                "wasi_fd_read_bytes_total",
+                "Bytes read through file descriptors.",
+                snapshot.bytes_read,
+            ),
+            (
+                "wasi_fd_written_bytes_total",
+                "Bytes written through file descriptors.",
+                snapshot.bytes_written,
+            ),
+            (
+                "wasi_net_received_bytes_total",
+                "Bytes received on sockets.",
+                snapshot.net_received,
+            ),
+            (
+                "wasi_net_sent_bytes_total",
+                "Bytes sent on sockets.",
+                snapshot.net_sent,
+            ),
+            (
+                "wasi_memory_grow_events_total",
+                "Number of times the linear memory grew.",
+                snapshot.memory_grow_events,
+            ),
+        ];
+        for (name, help, value) in counters {
+            self.header(&mut out, name, "counter", help);
+            self.sample(&mut out, name, None, value);
+        }
+
+        self.header(
+            &mut out,
+            "wasi_memory_pages",
+            "gauge",
+            "Size of the linear memory in pages after it last grew.",
+        );
+        self.sample(&mut out, "wasi_memory_pages", None, snapshot.memory_pages);
+        self.header(
+            &mut out,
+            "wasi_guest_cpu_seconds_total",
+            "counter",
+            "Time spent executing guest code.",
+        );
+        self.sample(
+            &mut out,
+            "wasi_guest_cpu_seconds_total",
+            None,
+            snapshot.guest_time.as_secs_f64(),
+        );
+
+        out
+    }
+
+    /// Encodes the counters of the socket limits of an instance.
+    pub fn encode_socket_limits(&self, stats: &SocketLimitStats) -> String {
+        let mut out = String::new();
+        let gauges = [
+            (
+                "wasi_sockets_open",
+                "Sockets that are currently open.",
+                stats.open_sockets as u64,
+            ),
+            (
+                "wasi_connections_pending",
+                "Outbound connections in progress.",
+                stats.pending_connections as u64,
+            ),
+        ];
+        for (name, help, value) in gauges {
+            self.header(&mut out, name, "gauge", help);
+            self.sample(&mut out, name, None, value);
+        }
+        self.header(
+            &mut out,
+            "wasi_connections_total",
+            "counter",
+            "Connections made by the instance.",
+        );
+        self.sample(&mut out, "wasi_connections_total", None, stats.connections);
+        self.header(
+            &mut out,
+            "wasi_socket_limit_rejections_total",
+            "counter",
+            "Sockets and connections refused because of a limit.",
+        );
+        for (limit, value) in [
+            ("sockets", stats.rejected_sockets),
+            ("pending", stats.rejected_pending),
+            ("rate", stats.rejected_rate),
+        ] {
+            self.sample(
+                &mut out,
+                "wasi_socket_limit_rejections_total",
+                Some(("limit", limit)),
+                value,
+            );
+        }
+        out
+    }
+
+    fn header(&self, out: &mut String, name: &str, ty: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, ty);
+    }
+
+    fn sample(
+        &self,
+        out: &mut String,
+        name: &str,
+        extra: Option<(&str, &str)>,
+        value: impl std::fmt::Display,
+    ) {
+        out.push_str(name);
+        let labels = self
+            .labels
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .chain(extra);
+        let mut first = true;
+        for (key, value) in labels {
+            out.push(if first { '{' } else { ',' });
+            first = false;
+            let _ = write!(out, "{}=\"{}\"", key, escape_label(value));
+        }
+        if !first {
+            out.push('}');
+        }
+        let _ = writeln!(out, " {}", value);
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Wraps every imported host function so that the sink sees the syscalls
+/// the guest makes, the time it spends between them and the growth of its
+/// memory (which is only noticed on the next syscall).
+pub(crate) fn instrument_imports(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+    imports: Imports,
+    sink: Arc<dyn MetricsSink>,
+) -> Imports {
+    let last_exit = Arc::new(Mutex::new(Instant::now()));
+    let memory_pages = Arc::new(AtomicU32::new(0));
+
+    let mut instrumented = Imports::new();
+    for ((namespace, name), export) in &imports {
+        let export = match export {
+            Extern::Function(inner) => {
+                let ty = inner.ty(store);
+                let sink = sink.clone();
+                let last_exit = last_exit.clone();
+                let memory_pages = memory_pages.clone();
+                let name = name.clone();
+                Extern::Function(Function::new_with_env(
+                    store,
+                    env,
+                    ty,
+                    move |mut ctx: FunctionEnvMut<WasiEnv>, args: &[Value]| {
+                        let start = Instant::now();
+                        sink.guest_time(
+                            start.saturating_duration_since(*last_exit.lock().unwrap()),
+                        );
+
+                        let (env, store) = ctx.data_and_store_mut();
+                        if let Some(handles) = env.inner.as_ref() {
+                            let pages = handles.memory.view(&store).size().0;
+                            let old = memory_pages.swap(pages, Ordering::Relaxed);
+                            if old != 0 && old < pages {
+                                sink.memory_grown(old, pages);
+                            }
+                        }
+
+                        let ret = inner.call(&mut ctx, args);
+
+                        let end = Instant::now();
+                        sink.syscall(&name, end.saturating_duration_since(start));
+                        *last_exit.lock().unwrap() = end;
+                        ret.map(|values| values.into_vec())
+                    },
+                ))
+            }
+            other => other,
+        };
+        instrumented.define(&namespace, &name, export);
+    }
+    instrumented
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prometheus_encoding() {
+        let metrics = InstanceMetrics::new();
+        metrics.syscall("fd_write", Duration::from_millis(500));
+        metrics.syscall("fd_write", Duration::from_millis(500));
+        metrics.bytes_written(42);
+        metrics.memory_grown(17, 18);
+
+        let out = PrometheusEncoder::new()
+            .with_label("instance", "a\"b")
+            .encode(&metrics.snapshot());
+
+        assert!(out.contains("# TYPE wasi_syscalls_total counter\n"));
+        assert!(out.contains("wasi_syscalls_total{instance=\"a\\\"b\",syscall=\"fd_write\"} 2\n"));
+        assert!(out
+            .contains("wasi_syscall_seconds_total{instance=\"a\\\"b\",syscall=\"fd_write\"} 1\n"));
+        assert!(out.contains("wasi_fd_written_bytes_total{instance=\"a\\\"b\"} 42\n"));
+        assert!(out.contains("wasi_memory_grow_events_total{instance=\"a\\\"b\"} 1\n"));
+        assert!(out.contains("wasi_memory_pages{instance=\"a\\\"b\"} 18\n"));
+    }
+}
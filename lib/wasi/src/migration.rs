@@ -0,0 +1,694 @@
+//! Live migration of a running process to a wasmer process on another host.
+//!
+//! Once the [`SnapshotTrigger`] of a [`MigrationConfig`] fires, the process
+//! connects to the target and copies its linear memory over in rounds while
+//! it keeps running: every time it enters a syscall that supports
+//! [snapshots](crate::snapshot) the pages that changed since the previous
+//! round are sent. When the pages left over fit in the pause budget at the
+//! throughput of the previous round, the process stops the same way it does
+//! for a snapshot and sends the rest: the last dirty pages, the stack, the
+//! globals, its open files and the in-memory filesystem. The target folds
+//! all of it into a [`ProcessSnapshot`] (see [`receive`]) and resumes it,
+//! while the process exits on the source.
+//!
+//! A process stopped while polling re-enters `poll_oneoff` on the target,
+//! so its pending timeouts are armed again there, and its monotonic clock
+//! carries on from where it was. If the rounds don't converge, or anything
+//! goes wrong before the target confirmed it has everything, the migration
+//! is abandoned and the process carries on where it is.
+//!
+//! The state is sent as [`JournalEntry`]s, the same way it is journaled.
+use std::{
+    convert::TryFrom,
+    io::{BufReader, BufWriter, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use wasmer::{
+    AsStoreMut, FunctionEnvMut, MemorySize, MemoryView, Module, OnCalledAction, WASM_PAGE_SIZE,
+};
+use wasmer_wasix_types::wasi::Errno;
+
+use crate::{
+    journal::{Checkpoint, FdEntry, JournalEntry, JournalState, MemoryTracker},
+    snapshot::{fingerprint, ProcessSnapshot, SnapshotError, SnapshotTrigger},
+    syscalls::{rewind, unwind},
+    WasiEnv, WasiError,
+};
+
+/// Identifies migration streams
+const MAGIC: &[u8; 8] = b"wasmermg";
+
+/// Bumped whenever the messages of the protocol change
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Sent back by the target once it received the whole process
+const ACK: u8 = 1;
+
+/// The memory sent in a single message, larger regions are split up
+const MAX_REGION_LEN: usize = 64 * WASM_PAGE_SIZE;
+
+/// The least time between two rounds of copying memory
+const ROUND_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long the source waits for the target to confirm it received the
+/// process
+const ACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error("unable to talk to the other host")]
+    Io(#[from] std::io::Error),
+    #[error("the other host doesn't speak the migration protocol: {0}")]
+    Protocol(String),
+    #[error("the other host speaks an incompatible version of the migration protocol ({0})")]
+    UnsupportedVersion(u32),
+    #[error("the process runs a different module")]
+    ModuleMismatch,
+    #[error("the process changes its memory faster than it can be migrated")]
+    NotConverging,
+    #[error("unable to encode a message: {0}")]
+    Encode(String),
+    #[error("unable to capture the process")]
+    Snapshot(#[from] SnapshotError),
+}
+
+/// Where and when a process migrates itself.
+#[derive(Debug, Clone)]
+pub struct MigrationConfig {
+    /// The `host:port` a target is waiting on, see [`receive`]
+    pub target: String,
+    pub trigger: SnapshotTrigger,
+    /// How long the process may be stopped while the last of its state is
+    /// sent
+    pub pause_budget: Duration,
+    /// The rounds of copying memory after which the migration is abandoned
+    /// if the rest still doesn't fit in the pause budget
+    pub max_rounds: usize,
+    /// The directories of the virtual filesystem whose contents are sent
+    /// along, see [`SnapshotConfig::fs_dirs`](crate::snapshot::SnapshotConfig::fs_dirs)
+    pub fs_dirs: Vec<PathBuf>,
+}
+
+impl MigrationConfig {
+    pub fn new(target: impl Into<String>, trigger: SnapshotTrigger) -> Self {
+        MigrationConfig {
+            target: target.into(),
+            trigger,
+            pause_budget: Duration::from_millis(300),
+            max_rounds: 30,
+            fs_dirs: Vec::new(),
+        }
+    }
+
+    pub fn with_pause_budget(mut self, budget: Duration) -> Self {
+        self.pause_budget = budget;
+        self
+    }
+
+    pub fn with_max_rounds(mut self, rounds: usize) -> Self {
+        self.max_rounds = rounds;
+        self
+    }
+
+    /// Sends the contents of a directory of the virtual filesystem along
+    pub fn with_fs_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.fs_dirs.push(dir.into());
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Message {
+    /// Opens the stream, with the fingerprint of the module
+    Start {
+        module: String,
+    },
+    State(JournalEntry),
+    /// Everything was sent, the last state was a checkpoint
+    Done,
+}
+
+/// The migration of a running process.
+#[derive(Debug)]
+pub struct Migration {
+    config: MigrationConfig,
+    state: Mutex<MigrationState>,
+}
+
+#[derive(Debug)]
+enum MigrationState {
+    Idle,
+    PreCopy(PreCopy),
+    /// The process migrated, or gave up on it and carries on
+    Finished,
+}
+
+/// A migration copying memory while the process runs.
+#[derive(Debug)]
+struct PreCopy {
+    stream: BufWriter<TcpStream>,
+    memory: MemoryTracker,
+    rounds: usize,
+    last_round: Instant,
+    /// The bytes sent by the last round
+    dirty: usize,
+    /// Bytes per second, as measured by the last round
+    throughput: f64,
+}
+
+impl Migration {
+    pub fn new(config: MigrationConfig) -> Self {
+        Migration {
+            config,
+            state: Mutex::new(MigrationState::Idle),
+        }
+    }
+
+    pub fn config(&self) -> &MigrationConfig {
+        &self.config
+    }
+
+    pub(crate) fn is_requested(&self) -> bool {
+        self.config.trigger.is_requested()
+            && !matches!(*self.state.lock().unwrap(), MigrationState::Finished)
+    }
+
+    fn connect(&self, module: String) -> Result<PreCopy, MigrationError> {
+        tracing::debug!(target = %self.config.target, "Migrating the process");
+        let stream = TcpStream::connect(&self.config.target)?;
+        stream.set_nodelay(true)?;
+        let mut stream = BufWriter::new(stream);
+        stream.write_all(MAGIC)?;
+        stream.write_all(&PROTOCOL_VERSION.to_le_bytes())?;
+        write_message(&mut stream, &Message::Start { module })?;
+
+        Ok(PreCopy {
+            stream,
+            memory: MemoryTracker::default().with_dirty_page_tracking(),
+            rounds: 0,
+            last_round: Instant::now(),
+            dirty: 0,
+            throughput: 0.0,
+        })
+    }
+
+    /// Sends the state of the stopped process, then waits for the target
+    /// to confirm it received it.
+    fn finish(
+        &self,
+        pre_copy: &mut PreCopy,
+        snapshot: &ProcessSnapshot,
+    ) -> Result<(), MigrationError> {
+        let stream = &mut pre_copy.stream;
+        for entry in pre_copy.memory.update(&snapshot.memory) {
+            send_memory(stream, entry)?;
+        }
+        for entry in state_entries(snapshot) {
+            write_message(stream, &Message::State(entry))?;
+        }
+        write_message(stream, &Message::Done)?;
+        stream.flush()?;
+
+        let stream = stream.get_mut();
+        stream.set_read_timeout(Some(ACK_TIMEOUT))?;
+        let mut ack = [0];
+        stream.read_exact(&mut ack)?;
+        if ack[0] != ACK {
+            return Err(MigrationError::Protocol(format!(
+                "unexpected reply {}",
+                ack[0]
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl PreCopy {
+    /// Whether the pages left over can be sent within `budget`.
+    fn converged(&self, budget: Duration) -> bool {
+        self.rounds > 0
+            && self.throughput > 0.0
+            && self.dirty as f64 / self.throughput <= budget.as_secs_f64()
+    }
+
+    /// Sends the pages of memory that changed since the previous round,
+    /// see [`MemoryTracker::update_view`] for `exclusive`.
+    fn round(&mut self, memory: &MemoryView, exclusive: bool) -> Result<(), MigrationError> {
+        let started = Instant::now();
+        let mut dirty = 0;
+        let regions = self
+            .memory
+            .update_view(memory, exclusive)
+            .map_err(|e| MigrationError::Encode(e.to_string()))?;
+        for entry in regions {
+            if let JournalEntry::UpdateMemoryRegion { data, .. } = &entry {
+                dirty += data.len();
+            }
+            send_memory(&mut self.stream, entry)?;
+        }
+        self.stream.flush()?;
+
+        let elapsed = started.elapsed().as_secs_f64();
+        if dirty > 0 && elapsed > 0.0 {
+            self.throughput = dirty as f64 / elapsed;
+        }
+        self.dirty = dirty;
+        self.rounds += 1;
+        self.last_round = Instant::now();
+        tracing::trace!(
+            round = self.rounds,
+            dirty,
+            "Copied the memory of the process"
+        );
+        Ok(())
+    }
+}
+
+/// Copies the memory of the process to the target while it runs, returns
+/// whether it is time to stop the process and send the rest. Use
+/// [`maybe_snapshot!`] rather than calling this directly.
+pub(crate) fn pre_copy<M: MemorySize>(ctx: &mut FunctionEnvMut<'_, WasiEnv>) -> bool {
+    let env = ctx.data();
+    let migration = match env.migration.clone() {
+        Some(migration) => migration,
+        None => return false,
+    };
+    let mut state = migration.state.lock().unwrap();
+
+    if let MigrationState::Idle = *state {
+        let module = fingerprint(env.inner().instance.module());
+        match migration.connect(module) {
+            Ok(pre_copy) => *state = MigrationState::PreCopy(pre_copy),
+            Err(e) => {
+                tracing::error!(
+                    target = %migration.config.target,
+                    error = &e as &dyn std::error::Error,
+                    "Unable to migrate the process",
+                );
+                *state = MigrationState::Finished;
+                return false;
+            }
+        }
+    }
+    let pre_copy = match &mut *state {
+        MigrationState::PreCopy(pre_copy) => pre_copy,
+        _ => return false,
+    };
+
+    if pre_copy.rounds > 0 && pre_copy.last_round.elapsed() < ROUND_INTERVAL {
+        return false;
+    }
+    if pre_copy.converged(migration.config.pause_budget) {
+        return true;
+    }
+
+    let result = if pre_copy.rounds >= migration.config.max_rounds {
+        Err(MigrationError::NotConverging)
+    } else {
+        pre_copy.round(&env.memory_view(&ctx), env.active_threads() <= 1)
+    };
+    if let Err(e) = result {
+        tracing::error!(
+            target = %migration.config.target,
+            error = &e as &dyn std::error::Error,
+            "Abandoned the migration of the process",
+        );
+        *state = MigrationState::Finished;
+    }
+    false
+}
+
+/// Unwinds the stack out of the current syscall and sends the rest of the
+/// process to the target, then exits. If that fails the process is rewound
+/// into the syscall and carries on. Use [`maybe_snapshot!`] rather than
+/// calling this directly.
+#[must_use = "you must return the result immediately so the stack can unwind"]
+pub(crate) fn unwind_and_migrate<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+) -> Result<Errno, WasiError> {
+    let migration = match ctx.data().migration.clone() {
+        Some(migration) => migration,
+        None => return Ok(Errno::Success),
+    };
+
+    tracing::debug!("Unwinding to stop the process for its migration");
+    unwind::<M, _>(ctx, move |mut ctx, memory_stack, rewind_stack| {
+        let stopped = Instant::now();
+        let memory_stack = memory_stack.freeze();
+        let rewind_stack = rewind_stack.freeze();
+        let store_data =
+            match crate::utils::store::capture_snapshot(&mut ctx.as_store_mut()).serialize() {
+                Ok(store_data) => Bytes::from(store_data),
+                Err(e) => {
+                    tracing::error!("Unable to capture the globals of the process ({e})");
+                    return OnCalledAction::Trap(Box::new(WasiError::Exit(Errno::Io.into())));
+                }
+            };
+
+        let mut state = migration.state.lock().unwrap();
+        let result = match std::mem::replace(&mut *state, MigrationState::Finished) {
+            MigrationState::PreCopy(mut pre_copy) => crate::snapshot::capture::<M>(
+                &mut ctx,
+                memory_stack.clone(),
+                rewind_stack.clone(),
+                &migration.config.fs_dirs,
+            )
+            .map_err(MigrationError::from)
+            .and_then(|snapshot| migration.finish(&mut pre_copy, &snapshot)),
+            _ => Err(MigrationError::Protocol("not connected".to_string())),
+        };
+        drop(state);
+
+        match result {
+            Ok(()) => {
+                tracing::info!(
+                    target = %migration.config.target,
+                    pause = ?stopped.elapsed(),
+                    "Migrated the process",
+                );
+                return OnCalledAction::Trap(Box::new(WasiError::Exit(Errno::Success.into())));
+            }
+            Err(e) => tracing::error!(
+                target = %migration.config.target,
+                error = &e as &dyn std::error::Error,
+                "Unable to migrate the process, it carries on here",
+            ),
+        }
+
+        match rewind::<M>(ctx, memory_stack, rewind_stack, store_data) {
+            Errno::Success => OnCalledAction::InvokeAgain,
+            err => {
+                tracing::warn!("migration failed - could not rewind the stack - errno={err}");
+                OnCalledAction::Trap(Box::new(WasiError::Exit(err.into())))
+            }
+        }
+    })
+}
+
+/// Waits for a process running `module` to be migrated to this host, and
+/// returns it once all of its state arrived. Pass it to
+/// [`WasiEnvBuilder::resume_from`](crate::WasiEnvBuilder::resume_from) to
+/// carry on where it left off.
+pub fn receive(listener: &TcpListener, module: &Module) -> Result<ProcessSnapshot, MigrationError> {
+    let expected = fingerprint(module);
+    loop {
+        let (stream, peer) = listener.accept()?;
+        tracing::debug!(%peer, "Receiving a migrated process");
+        match receive_from(stream, &expected) {
+            Ok(snapshot) => return Ok(snapshot),
+            // Somebody who isn't migrating a process knocked on the wrong door
+            Err(MigrationError::Protocol(e)) => {
+                tracing::warn!(%peer, "Ignoring a connection that isn't a migration ({e})");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn receive_from<S: Read + Write>(
+    mut stream: S,
+    expected: &str,
+) -> Result<ProcessSnapshot, MigrationError> {
+    let mut reader = BufReader::new(&mut stream);
+    let mut header = [0; MAGIC.len() + 4];
+    reader.read_exact(&mut header)?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(MigrationError::Protocol("not a migration".to_string()));
+    }
+    let mut version = [0; 4];
+    version.copy_from_slice(&header[MAGIC.len()..]);
+    let version = u32::from_le_bytes(version);
+    if version != PROTOCOL_VERSION {
+        return Err(MigrationError::UnsupportedVersion(version));
+    }
+
+    let mut state = JournalState::default();
+    loop {
+        match read_message(&mut reader)? {
+            Message::Start { module } if module == expected => {
+                state.apply(&JournalEntry::InitModule { module });
+            }
+            Message::Start { .. } => return Err(MigrationError::ModuleMismatch),
+            Message::State(entry) => state.apply(&entry),
+            Message::Done => break,
+        }
+    }
+    let snapshot = state
+        .to_snapshot()
+        .ok_or_else(|| MigrationError::Protocol("incomplete process".to_string()))?;
+
+    drop(reader);
+    stream.write_all(&[ACK])?;
+    stream.flush()?;
+    Ok(snapshot)
+}
+
+/// The state of a stopped process, besides its memory, as entries that fold
+/// back into it.
+fn state_entries(snapshot: &ProcessSnapshot) -> Vec<JournalEntry> {
+    let mut entries = Vec::new();
+    // Parents come before their children
+    for file in snapshot.files.iter() {
+        match &file.contents {
+            None => entries.push(JournalEntry::CreateDirectory {
+                path: file.path.clone(),
+            }),
+            Some(contents) => {
+                entries.push(JournalEntry::SetFileLength {
+                    path: file.path.clone(),
+                    len: 0,
+                });
+                entries.push(JournalEntry::WriteFile {
+                    path: file.path.clone(),
+                    offset: 0,
+                    data: contents.clone(),
+                });
+            }
+        }
+    }
+    entries.extend(snapshot.fds.iter().map(|fd| {
+        JournalEntry::OpenFd(FdEntry {
+            fd: fd.fd,
+            path: fd.path.clone(),
+            is_dir: fd.is_dir,
+            rights: fd.rights,
+            rights_inheriting: fd.rights_inheriting,
+            flags: fd.flags,
+            open_flags: fd.open_flags,
+        })
+    }));
+    entries.push(JournalEntry::Checkpoint(Checkpoint {
+        memory_size: snapshot.memory.len() as u64,
+        memory64: snapshot.memory64,
+        memory_stack: snapshot.memory_stack.clone(),
+        rewind_stack: snapshot.rewind_stack.clone(),
+        store_data: snapshot.store_data.clone(),
+        current_dir: snapshot.current_dir.clone(),
+        fd_offsets: snapshot.fds.iter().map(|fd| (fd.fd, fd.offset)).collect(),
+        monotonic_clock: snapshot.monotonic_clock,
+    }));
+    entries
+}
+
+/// Sends a region of memory, split up so no message gets too large.
+fn send_memory(stream: &mut impl Write, region: JournalEntry) -> Result<(), MigrationError> {
+    match region {
+        JournalEntry::UpdateMemoryRegion { start, data } => {
+            for (index, chunk) in data.chunks(MAX_REGION_LEN).enumerate() {
+                let entry = JournalEntry::UpdateMemoryRegion {
+                    start: start + (index * MAX_REGION_LEN) as u64,
+                    data: chunk.to_vec(),
+                };
+                write_message(stream, &Message::State(entry))?;
+            }
+            Ok(())
+        }
+        other => write_message(stream, &Message::State(other)),
+    }
+}
+
+fn write_message(stream: &mut impl Write, message: &Message) -> Result<(), MigrationError> {
+    let payload = bincode::serialize(message).map_err(|e| MigrationError::Encode(e.to_string()))?;
+    let len = u32::try_from(payload.len())
+        .map_err(|_| MigrationError::Encode(format!("{} byte message", payload.len())))?;
+    stream.write_all(&len.to_le_bytes())?;
+    stream.write_all(&payload)?;
+    Ok(())
+}
+
+fn read_message(stream: &mut impl Read) -> Result<Message, MigrationError> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+    let mut payload = vec![0; u32::from_le_bytes(len) as usize];
+    stream.read_exact(&mut payload)?;
+    bincode::deserialize(&payload).map_err(|e| MigrationError::Protocol(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::snapshot::{FdSnapshot, FileSnapshot};
+
+    fn snapshot() -> ProcessSnapshot {
+        let mut memory = vec![0; 3 * WASM_PAGE_SIZE];
+        memory[10] = 1;
+        memory[2 * WASM_PAGE_SIZE] = 2;
+        ProcessSnapshot {
+            module: "abc".to_string(),
+            memory64: false,
+            memory,
+            memory_stack: vec![4],
+            rewind_stack: vec![5, 6],
+            store_data: vec![7],
+            current_dir: "/tmp".to_string(),
+            fds: vec![FdSnapshot {
+                fd: 5,
+                path: PathBuf::from("/tmp/log.txt"),
+                is_dir: false,
+                rights: 3,
+                rights_inheriting: 3,
+                flags: 1,
+                open_flags: 2,
+                offset: 42,
+            }],
+            files: vec![
+                FileSnapshot {
+                    path: PathBuf::from("/tmp"),
+                    contents: None,
+                },
+                FileSnapshot {
+                    path: PathBuf::from("/tmp/log.txt"),
+                    contents: Some(b"hello".to_vec()),
+                },
+            ],
+            monotonic_clock: 1_000,
+        }
+    }
+
+    /// A stream that reads what the source sent and keeps what the target
+    /// replies.
+    struct Duplex {
+        sent: Cursor<Vec<u8>>,
+        replies: Vec<u8>,
+    }
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.sent.read(buf)
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.replies.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn stream(module: &str, snapshot: &ProcessSnapshot) -> Vec<u8> {
+        let mut sent = MAGIC.to_vec();
+        sent.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+        write_message(
+            &mut sent,
+            &Message::Start {
+                module: module.to_string(),
+            },
+        )
+        .unwrap();
+
+        // A round while the process runs, then the rest once it stopped
+        let mut memory = MemoryTracker::default();
+        let mut running = snapshot.memory.clone();
+        running[2 * WASM_PAGE_SIZE] = 0;
+        for entry in memory.update(&running) {
+            send_memory(&mut sent, entry).unwrap();
+        }
+        for entry in memory.update(&snapshot.memory) {
+            send_memory(&mut sent, entry).unwrap();
+        }
+        for entry in state_entries(snapshot) {
+            write_message(&mut sent, &Message::State(entry)).unwrap();
+        }
+        write_message(&mut sent, &Message::Done).unwrap();
+        sent
+    }
+
+    #[test]
+    fn processes_arrive_the_way_they_left() {
+        let original = snapshot();
+        let mut duplex = Duplex {
+            sent: Cursor::new(stream("abc", &original)),
+            replies: Vec::new(),
+        };
+
+        let received = receive_from(&mut duplex, "abc").unwrap();
+        assert_eq!(duplex.replies, [ACK]);
+        assert_eq!(received.memory, original.memory);
+        assert_eq!(received.rewind_stack, original.rewind_stack);
+        assert_eq!(received.current_dir, "/tmp");
+        assert_eq!(received.fds[0].offset, 42);
+        assert_eq!(received.files.len(), 2);
+        assert_eq!(
+            received.files[1].contents.as_deref(),
+            Some(b"hello".as_slice())
+        );
+        assert_eq!(received.monotonic_clock, 1_000);
+    }
+
+    #[test]
+    fn other_modules_are_refused() {
+        let mut duplex = Duplex {
+            sent: Cursor::new(stream("xyz", &snapshot())),
+            replies: Vec::new(),
+        };
+        assert!(matches!(
+            receive_from(&mut duplex, "abc"),
+            Err(MigrationError::ModuleMismatch)
+        ));
+        assert!(duplex.replies.is_empty());
+
+        let mut duplex = Duplex {
+            sent: Cursor::new(b"GET / HTTP/1.1\r\n".to_vec()),
+            replies: Vec::new(),
+        };
+        assert!(matches!(
+            receive_from(&mut duplex, "abc"),
+            Err(MigrationError::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn large_regions_are_split_up() {
+        let mut sent = Vec::new();
+        let region = JournalEntry::UpdateMemoryRegion {
+            start: 0,
+            data: vec![1; MAX_REGION_LEN + WASM_PAGE_SIZE],
+        };
+        send_memory(&mut sent, region).unwrap();
+
+        let mut sent = Cursor::new(sent);
+        let starts: Vec<_> = (0..2)
+            .map(|_| match read_message(&mut sent).unwrap() {
+                Message::State(JournalEntry::UpdateMemoryRegion { start, data }) => {
+                    (start, data.len())
+                }
+                other => panic!("unexpected message {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            starts,
+            [(0, MAX_REGION_LEN), (MAX_REGION_LEN as u64, WASM_PAGE_SIZE)]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Per-instance limits on sockets and connections.
+//!
+//! The [`SocketLimiter`] lives in the control plane so that all the
+//! processes of an instance share the same budget.
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use wasmer_wasix_types::wasi::Errno;
+
+/// Limits that are applied to the sockets of an instance.
+#[derive(Debug, Default, Clone)]
+pub struct SocketLimits {
+    /// Maximum number of sockets that can be open at the same time.
+    pub max_sockets: Option<usize>,
+    /// Maximum number of outbound connections that can be in progress
+    /// at the same time.
+    pub max_pending_connections: Option<usize>,
+    /// Maximum number of connections (accepted or initiated) that can be
+    /// made within a time window.
+    pub max_connections_per_window: Option<(usize, Duration)>,
+}
+
+/// Snapshot of the counters maintained by a [`SocketLimiter`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SocketLimitStats {
+    /// Number of sockets that are currently open.
+    pub open_sockets: usize,
+    /// Number of outbound connections that are currently in progress.
+    pub pending_connections: usize,
+    /// Total number of connections that have been made.
+    pub connections: u64,
+    /// Number of sockets that were refused because of `max_sockets`.
+    pub rejected_sockets: u64,
+    /// Number of connections that were refused because of
+    /// `max_pending_connections`.
+    pub rejected_pending: u64,
+    /// Number of connections that were refused because of
+    /// `max_connections_per_window`.
+    pub rejected_rate: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    open_sockets: AtomicUsize,
+    pending_connections: AtomicUsize,
+    connections: AtomicU64,
+    rejected_sockets: AtomicU64,
+    rejected_pending: AtomicU64,
+    rejected_rate: AtomicU64,
+}
+
+/// Enforces [`SocketLimits`] and keeps track of how often they were hit.
+#[derive(Debug, Clone)]
+pub struct SocketLimiter {
+    limits: SocketLimits,
+    counters: Arc<Counters>,
+    /// Times at which the connections within the current window were made.
+    window: Arc<Mutex<VecDeque<Instant>>>,
+}
+
+impl SocketLimiter {
+    pub fn new(limits: SocketLimits) -> Self {
+        Self {
+            limits,
+            counters: Default::default(),
+            window: Default::default(),
+        }
+    }
+
+    pub fn limits(&self) -> &SocketLimits {
+        &self.limits
+    }
+
+    /// Reserves a slot for a new socket which is given back when the
+    /// returned permit is dropped.
+    pub fn acquire_socket(&self) -> Result<SocketPermit, Errno> {
+        let counters = &self.counters;
+        let count = counters.open_sockets.fetch_add(1, Ordering::SeqCst);
+        if let Some(max) = self.limits.max_sockets {
+            if count >= max {
+                counters.open_sockets.fetch_sub(1, Ordering::SeqCst);
+                counters.rejected_sockets.fetch_add(1, Ordering::Relaxed);
+                tracing::debug!(max, "socket limit reached");
+                return Err(Errno::Socklimit);
+            }
+        }
+        Ok(SocketPermit(counters.clone()))
+    }
+
+    /// Records a new connection against the rate limit.
+    pub fn register_connection(&self) -> Result<(), Errno> {
+        if let Some((max, window)) = self.limits.max_connections_per_window {
+            let now = Instant::now();
+            let mut times = self.window.lock().unwrap();
+            while let Some(front) = times.front() {
+                if now.duration_since(*front) < window {
+                    break;
+                }
+                times.pop_front();
+            }
+            if times.len() >= max {
+                self.counters.rejected_rate.fetch_add(1, Ordering::Relaxed);
+                tracing::debug!(max, ?window, "connection rate limit reached");
+                return Err(Errno::Connratelimit);
+            }
+            times.push_back(now);
+        }
+        self.counters.connections.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Starts an outbound connection, which counts as pending until the
+    /// returned guard is dropped. The connection is also recorded against
+    /// the rate limit.
+    pub fn begin_connect(&self) -> Result<PendingConnectionGuard, Errno> {
+        let counters = &self.counters;
+        let count = counters.pending_connections.fetch_add(1, Ordering::SeqCst);
+        let guard = PendingConnectionGuard(counters.clone());
+        if let Some(max) = self.limits.max_pending_connections {
+            if count >= max {
+                counters.rejected_pending.fetch_add(1, Ordering::Relaxed);
+                tracing::debug!(max, "pending connection limit reached");
+                return Err(Errno::Pendlimit);
+            }
+        }
+        self.register_connection()?;
+        Ok(guard)
+    }
+
+    /// Returns the current values of the counters.
+    pub fn stats(&self) -> SocketLimitStats {
+        let counters = &self.counters;
+        SocketLimitStats {
+            open_sockets: counters.open_sockets.load(Ordering::SeqCst),
+            pending_connections: counters.pending_connections.load(Ordering::SeqCst),
+            connections: counters.connections.load(Ordering::Relaxed),
+            rejected_sockets: counters.rejected_sockets.load(Ordering::Relaxed),
+            rejected_pending: counters.rejected_pending.load(Ordering::Relaxed),
+            rejected_rate: counters.rejected_rate.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for SocketLimiter {
+    fn default() -> Self {
+        Self::new(SocketLimits::default())
+    }
+}
+
+/// Slot of an open socket that is released when dropped.
+#[derive(Debug)]
+pub struct SocketPermit(Arc<Counters>);
+
+impl Drop for SocketPermit {
+    fn drop(&mut self) {
+        self.0.open_sockets.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Outbound connection that is in progress, released when dropped.
+#[derive(Debug)]
+pub struct PendingConnectionGuard(Arc<Counters>);
+
+impl Drop for PendingConnectionGuard {
+    fn drop(&mut self) {
+        self.0.pending_connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_socket_limit_releases_on_drop() {
+        let limiter = SocketLimiter::new(SocketLimits {
+            max_sockets: Some(2),
+            ..Default::default()
+        });
+
+        let s1 = limiter.acquire_socket().unwrap();
+        let _s2 = limiter.acquire_socket().unwrap();
+        assert_eq!(limiter.acquire_socket().unwrap_err(), Errno::Socklimit);
+
+        drop(s1);
+        let _s3 = limiter.acquire_socket().unwrap();
+
+        let stats = limiter.stats();
+        assert_eq!(stats.open_sockets, 2);
+        assert_eq!(stats.rejected_sockets, 1);
+    }
+
+    #[test]
+    fn test_pending_and_rate_limits() {
+        let limiter = SocketLimiter::new(SocketLimits {
+            max_pending_connections: Some(1),
+            max_connections_per_window: Some((2, Duration::from_secs(60))),
+            ..Default::default()
+        });
+
+        let c1 = limiter.begin_connect().unwrap();
+        assert_eq!(limiter.begin_connect().unwrap_err(), Errno::Pendlimit);
+        drop(c1);
+
+        let _c2 = limiter.begin_connect().unwrap();
+        assert_eq!(
+            limiter.register_connection().unwrap_err(),
+            Errno::Connratelimit
+        );
+
+        let stats = limiter.stats();
+        assert_eq!(stats.pending_connections, 1);
+        assert_eq!(stats.connections, 2);
+        assert_eq!(stats.rejected_pending, 1);
+        assert_eq!(stats.rejected_rate, 1);
+    }
+}
@@ -1,10 +1,13 @@
 use std::{
     intrinsics::transmute,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
     time::Duration,
 };
 
-use virtual_net::{IpCidr, IpRoute, NetworkError};
+use virtual_net::{DynVirtualNetworking, IpCidr, IpRoute, NetworkError, UnixSocketAddr};
 use wasmer::{MemoryView, WasmPtr};
 use wasmer_types::MemorySize;
 use wasmer_wasix_types::{
@@ -16,8 +19,127 @@ use wasmer_wasix_types::{
     wasi::{Addressfamily, Errno},
 };
 
+use crate::fs::{Kind, WasiFs, WasiInodes, VIRTUAL_ROOT_FD};
+
+pub mod limits;
 pub mod socket;
 
+/// Network isolation of an instance, similar to the network modes offered
+/// by container runtimes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkMode {
+    /// No networking at all, every socket operation fails
+    None,
+    /// Only the loopback interface, which is enough for a guest to talk to
+    /// itself and to its own subprocesses
+    LoopbackOnly,
+    /// A private address with outbound connectivity through the host, the
+    /// guest can not be reached from the outside
+    VirtualNat,
+    /// Direct access to the networking of the host
+    HostPassthrough,
+}
+
+impl NetworkMode {
+    /// Creates the networking implementation for this mode, the host
+    /// networking is used by the modes that need outbound connectivity
+    pub fn networking(&self, host: DynVirtualNetworking) -> DynVirtualNetworking {
+        match self {
+            NetworkMode::None => Arc::new(virtual_net::UnsupportedVirtualNetworking::default()),
+            NetworkMode::LoopbackOnly => {
+                let port = virtual_net::VirtualBridge::new()
+                    .attach(
+                        "localhost",
+                        vec![
+                            IpAddr::V4(Ipv4Addr::LOCALHOST),
+                            IpAddr::V6(Ipv6Addr::LOCALHOST),
+                        ],
+                    )
+                    .expect("a new bridge has no addresses in use");
+                Arc::new(port)
+            }
+            NetworkMode::VirtualNat => Arc::new(virtual_net::nat::NatNetworking::new(host)),
+            NetworkMode::HostPassthrough => host,
+        }
+    }
+}
+
+impl std::fmt::Display for NetworkMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            NetworkMode::None => "none",
+            NetworkMode::LoopbackOnly => "loopback-only",
+            NetworkMode::VirtualNat => "virtual-nat",
+            NetworkMode::HostPassthrough => "host-passthrough",
+        };
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for NetworkMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(NetworkMode::None),
+            "loopback-only" | "loopback" => Ok(NetworkMode::LoopbackOnly),
+            "virtual-nat" | "virtual" => Ok(NetworkMode::VirtualNat),
+            "host-passthrough" | "host" => Ok(NetworkMode::HostPassthrough),
+            _ => Err(format!(
+                "unknown network mode `{}` (expected none, loopback-only, virtual-nat or host-passthrough)",
+                s
+            )),
+        }
+    }
+}
+
+/// Reads the name of a Unix domain socket, which follows the same rules
+/// as `sun_path` (a leading nul byte denotes an abstract name).
+pub(crate) fn read_unix_addr<M: MemorySize>(
+    memory: &MemoryView,
+    fs: &WasiFs,
+    inodes: &WasiInodes,
+    ptr: WasmPtr<u8, M>,
+    len: M::Offset,
+) -> Result<UnixSocketAddr, Errno> {
+    let bytes = ptr
+        .slice(memory, len)
+        .and_then(|slice| slice.read_to_vec())
+        .map_err(crate::mem_error_to_wasi)?;
+    match UnixSocketAddr::from_bytes(&bytes) {
+        UnixSocketAddr::Pathname(path) => {
+            resolve_unix_path(fs, inodes, &path).map(UnixSocketAddr::Pathname)
+        }
+        addr => Ok(addr),
+    }
+}
+
+/// Resolves the path name of a Unix domain socket through the file system,
+/// the same way `path_open` does, and returns the full path of the socket.
+///
+/// The directory holding the socket has to exist. Relative paths start
+/// from the current directory, and `.`, `..` and symbolic links are
+/// followed, so every way of naming a socket leads to the same path.
+pub(crate) fn resolve_unix_path(
+    fs: &WasiFs,
+    inodes: &WasiInodes,
+    path: &str,
+) -> Result<String, Errno> {
+    let (parent, name) =
+        fs.get_parent_inode_at_path(inodes, VIRTUAL_ROOT_FD, Path::new(path), true)?;
+    if name == "." || name == ".." || name.contains('/') {
+        return Err(Errno::Inval);
+    }
+
+    let guard = parent.read();
+    let dir = match guard.deref() {
+        Kind::Dir { path, .. } => path.clone(),
+        Kind::Root { .. } => PathBuf::from("/"),
+        _ => return Err(Errno::Notdir),
+    };
+    Ok(dir.join(name).to_string_lossy().into_owned())
+}
+
 #[allow(dead_code)]
 pub(crate) fn read_ip<M: MemorySize>(
     memory: &MemoryView,
@@ -392,3 +514,128 @@ pub fn net_error_into_wasi_err(net_error: NetworkError) -> Errno {
         NetworkError::UnknownError => Errno::Io,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{mem::MaybeUninit, net::SocketAddr};
+
+    use virtual_net::{VirtualBridge, VirtualNetworking};
+
+    use super::*;
+
+    const HOST: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const PEER: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+
+    fn any(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port)
+    }
+
+    /// Stands in for the host network, with a peer on the same segment
+    fn segment() -> (DynVirtualNetworking, virtual_net::BridgePort) {
+        let segment = VirtualBridge::new();
+        let host = segment.attach("host", vec![HOST]).unwrap();
+        let peer = segment.attach("peer", vec![PEER]).unwrap();
+        (Arc::new(host), peer)
+    }
+
+    #[tokio::test]
+    async fn isolated_mode_has_no_networking() {
+        let (host, _peer) = segment();
+        let net = NetworkMode::None.networking(host);
+        assert_eq!(
+            net.bind_udp(any(0), false, false).await.err(),
+            Some(NetworkError::Unsupported)
+        );
+        assert_eq!(
+            net.connect_tcp(any(0), SocketAddr::new(PEER, 80))
+                .await
+                .err(),
+            Some(NetworkError::Unsupported)
+        );
+    }
+
+    #[tokio::test]
+    async fn loopback_mode_only_reaches_itself() {
+        let (host, peer) = segment();
+        let _listener = peer.listen_tcp(any(80), false, false, false).await.unwrap();
+        let net = NetworkMode::LoopbackOnly.networking(host);
+
+        let local = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8080);
+        let _server = net.listen_tcp(local, false, false, false).await.unwrap();
+        assert!(net.connect_tcp(any(0), local).await.is_ok());
+        assert_eq!(
+            net.connect_tcp(any(0), SocketAddr::new(PEER, 80))
+                .await
+                .err(),
+            Some(NetworkError::ConnectionRefused)
+        );
+    }
+
+    #[tokio::test]
+    async fn nat_mode_goes_out_through_the_host() {
+        let (host, peer) = segment();
+        let _listener = peer.listen_tcp(any(80), false, false, false).await.unwrap();
+        let mut remote = peer.bind_udp(any(53), false, false).await.unwrap();
+        let net = NetworkMode::VirtualNat.networking(host);
+
+        assert_eq!(
+            net.ip_list().unwrap()[0].ip,
+            IpAddr::V4(virtual_net::nat::DEFAULT_NAT_ADDR)
+        );
+        assert!(net
+            .connect_tcp(any(0), SocketAddr::new(PEER, 80))
+            .await
+            .is_ok());
+
+        let mut socket = net.bind_udp(any(53), false, false).await.unwrap();
+        socket
+            .try_send_to(b"query", SocketAddr::new(PEER, 53))
+            .unwrap();
+        let mut buf = [MaybeUninit::new(0u8); 16];
+        let (_, from) = remote.try_recv_from(&mut buf).unwrap();
+        assert_eq!(from.ip(), HOST);
+        assert_ne!(from.port(), 53);
+    }
+
+    #[tokio::test]
+    async fn host_mode_passes_the_host_through() {
+        let (host, peer) = segment();
+        let _listener = peer.listen_tcp(any(80), false, false, false).await.unwrap();
+        let net = NetworkMode::HostPassthrough.networking(host.clone());
+
+        assert!(Arc::ptr_eq(&net, &host));
+        assert!(net
+            .connect_tcp(any(0), SocketAddr::new(PEER, 80))
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn unix_socket_paths_are_resolved_by_the_file_system() {
+        let tmp = virtual_fs::TmpFileSystem::new();
+        virtual_fs::FileSystem::create_dir(&tmp, Path::new("/tmp")).unwrap();
+        let inodes = WasiInodes::new();
+        let fs = WasiFs::new_with_preopen(
+            &inodes,
+            &[],
+            &["/".to_string()],
+            crate::fs::WasiFsRoot::Sandbox(Arc::new(tmp)),
+        )
+        .unwrap();
+        fs.set_is_wasix(true);
+        let resolve = |path: &str| resolve_unix_path(&fs, &inodes, path);
+
+        let socket = Ok("/tmp/app.sock".to_string());
+        assert_eq!(resolve("/tmp/app.sock"), socket);
+        assert_eq!(resolve("/tmp/../tmp/./app.sock"), socket);
+        assert_eq!(resolve("/tmp/app.sock/"), socket);
+        fs.set_current_dir("/tmp");
+        assert_eq!(resolve("app.sock"), socket);
+        assert_eq!(resolve("../tmp/app.sock"), socket);
+
+        // The sandbox can't be left, and the directory has to exist
+        assert_eq!(resolve("/../../app.sock"), Ok("/app.sock".to_string()));
+        assert_eq!(resolve("/missing/app.sock"), Err(Errno::Noent));
+        assert_eq!(resolve("/tmp/.."), Err(Errno::Inval));
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     mem::MaybeUninit,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     pin::Pin,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
     task::Poll,
     time::Duration,
 };
@@ -11,15 +11,18 @@ use std::{
 #[cfg(feature = "enable-serde")]
 use serde_derive::{Deserialize, Serialize};
 use virtual_net::{
-    VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket, VirtualTcpListener, VirtualTcpSocket,
-    VirtualUdpSocket,
+    UnixNamespace, UnixRights, UnixSocketAddr, VirtualIcmpSocket, VirtualNetworking,
+    VirtualRawSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
 };
 use wasmer_types::MemorySize;
 use wasmer_wasix_types::wasi::{
     Addressfamily, Errno, Fdflags, Rights, SockProto, Sockoption, Socktype,
 };
 
-use crate::{net::net_error_into_wasi_err, VirtualTaskManager};
+use crate::{
+    net::{limits::SocketPermit, net_error_into_wasi_err},
+    VirtualTaskManager,
+};
 
 #[derive(Debug)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
@@ -40,9 +43,11 @@ pub enum InodeSocketKind {
         ty: Socktype,
         pt: SockProto,
         addr: Option<SocketAddr>,
+        unix_addr: Option<UnixSocketAddr>,
         only_v6: bool,
         reuse_port: bool,
         reuse_addr: bool,
+        broadcast: bool,
         send_buf_size: Option<usize>,
         recv_buf_size: Option<usize>,
         write_timeout: Option<Duration>,
@@ -168,6 +173,8 @@ pub(crate) struct InodeSocketNotifications {
 //#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub(crate) struct InodeSocketInner {
     pub protected: RwLock<InodeSocketProtected>,
+    /// Slot that this socket holds against the socket limit of the instance
+    pub permit: Mutex<Option<SocketPermit>>,
 }
 
 #[derive(Debug, Clone)]
@@ -184,10 +191,25 @@ impl InodeSocket {
                     kind,
                     notifications: Default::default(),
                 }),
+                permit: Mutex::new(None),
             }),
         }
     }
 
+    /// Attaches the slot that the socket holds against the socket limit
+    pub(crate) fn with_permit(self, permit: SocketPermit) -> Self {
+        self.inner.permit.lock().unwrap().replace(permit);
+        self
+    }
+
+    /// Moves the socket limit slot of another socket over to this one, used
+    /// when a socket is replaced by a new state machine
+    pub(crate) fn inherit_permit(&self, other: &InodeSocket) {
+        if let Some(permit) = other.inner.permit.lock().unwrap().take() {
+            self.inner.permit.lock().unwrap().replace(permit);
+        }
+    }
+
     pub async fn bind(
         &self,
         tasks: &dyn VirtualTaskManager,
@@ -209,6 +231,7 @@ impl InodeSocket {
                     addr,
                     reuse_port,
                     reuse_addr,
+                    broadcast,
                     ..
                 } => {
                     match *family {
@@ -239,9 +262,10 @@ impl InodeSocket {
                         Socktype::Dgram => {
                             let reuse_port = *reuse_port;
                             let reuse_addr = *reuse_addr;
+                            let broadcast = *broadcast;
                             drop(inner);
 
-                            net.bind_udp(addr, reuse_port, reuse_addr)
+                            (net.bind_udp(addr, reuse_port, reuse_addr), broadcast)
                         }
                         _ => return Err(Errno::Inval),
                     }
@@ -250,9 +274,13 @@ impl InodeSocket {
             }
         };
 
+        let (socket, broadcast) = socket;
         tokio::select! {
             socket = socket => {
-                let socket = socket.map_err(net_error_into_wasi_err)?;
+                let mut socket = socket.map_err(net_error_into_wasi_err)?;
+                if broadcast {
+                    socket.set_broadcast(true).map_err(net_error_into_wasi_err)?;
+                }
                 Ok(Some(InodeSocket::new(InodeSocketKind::UdpSocket { socket, peer: None })))
             },
             _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
@@ -263,7 +291,8 @@ impl InodeSocket {
         &self,
         tasks: &dyn VirtualTaskManager,
         net: &dyn VirtualNetworking,
-        _backlog: usize,
+        unix_sockets: &UnixNamespace,
+        backlog: usize,
     ) -> Result<Option<InodeSocket>, Errno> {
         let timeout = self
             .opt_time(TimeType::AcceptTimeout)
@@ -274,6 +303,21 @@ impl InodeSocket {
         let socket = {
             let inner = self.inner.protected.read().unwrap();
             match &inner.kind {
+                InodeSocketKind::PreSocket {
+                    family: Addressfamily::Unix,
+                    ty: Socktype::Stream,
+                    unix_addr,
+                    ..
+                } => {
+                    let addr = unix_addr.clone().unwrap_or(UnixSocketAddr::Unnamed);
+                    let socket = unix_sockets
+                        .listen(addr, backlog)
+                        .map_err(net_error_into_wasi_err)?;
+                    return Ok(Some(InodeSocket::new(InodeSocketKind::TcpListener {
+                        socket: Box::new(socket),
+                        accept_timeout: Some(timeout),
+                    })));
+                }
                 InodeSocketKind::PreSocket {
                     ty,
                     addr,
@@ -319,6 +363,128 @@ impl InodeSocket {
         }
     }
 
+    /// Binds a Unix domain socket to a name in the namespace.
+    ///
+    /// Stream sockets only claim the name once they start listening while
+    /// datagram sockets are bound immediately.
+    pub fn bind_unix(
+        &self,
+        unix_sockets: &UnixNamespace,
+        set_addr: UnixSocketAddr,
+    ) -> Result<Option<InodeSocket>, Errno> {
+        let mut inner = self.inner.protected.write().unwrap();
+        match &mut inner.kind {
+            InodeSocketKind::PreSocket {
+                family,
+                ty,
+                unix_addr,
+                ..
+            } => {
+                if *family != Addressfamily::Unix {
+                    return Err(Errno::Afnosupport);
+                }
+                match *ty {
+                    Socktype::Stream => {
+                        if unix_sockets.is_bound(&set_addr) {
+                            return Err(Errno::Addrinuse);
+                        }
+                        unix_addr.replace(set_addr);
+                        Ok(None)
+                    }
+                    Socktype::Dgram => {
+                        let socket = unix_sockets
+                            .bind_datagram(set_addr)
+                            .map_err(net_error_into_wasi_err)?;
+                        Ok(Some(InodeSocket::new(InodeSocketKind::UdpSocket {
+                            socket: Box::new(socket),
+                            peer: None,
+                        })))
+                    }
+                    _ => Err(Errno::Inval),
+                }
+            }
+            _ => Err(Errno::Inval),
+        }
+    }
+
+    /// Connects a Unix domain socket to a socket bound to a name in the
+    /// namespace.
+    pub fn connect_unix(
+        &mut self,
+        unix_sockets: &UnixNamespace,
+        peer: UnixSocketAddr,
+    ) -> Result<Option<InodeSocket>, Errno> {
+        let mut inner = self.inner.protected.write().unwrap();
+        match &mut inner.kind {
+            InodeSocketKind::PreSocket {
+                family,
+                ty,
+                unix_addr,
+                write_timeout,
+                read_timeout,
+                ..
+            } => {
+                if *family != Addressfamily::Unix {
+                    return Err(Errno::Afnosupport);
+                }
+                match *ty {
+                    Socktype::Stream => {
+                        let socket = unix_sockets
+                            .connect(&peer)
+                            .map_err(net_error_into_wasi_err)?;
+                        Ok(Some(InodeSocket::new(InodeSocketKind::TcpStream {
+                            socket: Box::new(socket),
+                            write_timeout: *write_timeout,
+                            read_timeout: *read_timeout,
+                        })))
+                    }
+                    Socktype::Dgram => {
+                        let addr = unix_addr.take().unwrap_or(UnixSocketAddr::Unnamed);
+                        let mut socket = unix_sockets
+                            .bind_datagram(addr)
+                            .map_err(net_error_into_wasi_err)?;
+                        socket.connect_unix(peer).map_err(net_error_into_wasi_err)?;
+                        Ok(Some(InodeSocket::new(InodeSocketKind::UdpSocket {
+                            socket: Box::new(socket),
+                            peer: None,
+                        })))
+                    }
+                    _ => Err(Errno::Notsup),
+                }
+            }
+            InodeSocketKind::UdpSocket { socket, .. } => {
+                socket.connect_unix(peer).map_err(net_error_into_wasi_err)?;
+                Ok(None)
+            }
+            _ => Err(Errno::Notsup),
+        }
+    }
+
+    /// Passes handles to the peer of a connected Unix domain socket.
+    pub fn send_rights(&self, rights: UnixRights) -> Result<(), Errno> {
+        let mut inner = self.inner.protected.write().unwrap();
+        match &mut inner.kind {
+            InodeSocketKind::TcpStream { socket, .. } => {
+                socket.send_rights(rights).map_err(net_error_into_wasi_err)
+            }
+            InodeSocketKind::PreSocket { .. } => Err(Errno::Notconn),
+            _ => Err(Errno::Notsup),
+        }
+    }
+
+    /// Receives the next batch of handles passed by the peer of a connected
+    /// Unix domain socket.
+    pub fn recv_rights(&self) -> Result<Option<UnixRights>, Errno> {
+        let mut inner = self.inner.protected.write().unwrap();
+        match &mut inner.kind {
+            InodeSocketKind::TcpStream { socket, .. } => {
+                socket.recv_rights().map_err(net_error_into_wasi_err)
+            }
+            InodeSocketKind::PreSocket { .. } => Err(Errno::Notconn),
+            _ => Err(Errno::Notsup),
+        }
+    }
+
     pub async fn accept(
         &self,
         tasks: &dyn VirtualTaskManager,
@@ -563,12 +729,14 @@ impl InodeSocket {
                 only_v6,
                 reuse_port,
                 reuse_addr,
+                broadcast,
                 ..
             } => {
                 match option {
                     WasiSocketOption::OnlyV6 => *only_v6 = val,
                     WasiSocketOption::ReusePort => *reuse_port = val,
                     WasiSocketOption::ReuseAddr => *reuse_addr = val,
+                    WasiSocketOption::Broadcast => *broadcast = val,
                     _ => return Err(Errno::Inval),
                 };
             }
@@ -608,11 +776,13 @@ impl InodeSocket {
                 only_v6,
                 reuse_port,
                 reuse_addr,
+                broadcast,
                 ..
             } => match option {
                 WasiSocketOption::OnlyV6 => *only_v6,
                 WasiSocketOption::ReusePort => *reuse_port,
                 WasiSocketOption::ReuseAddr => *reuse_addr,
+                WasiSocketOption::Broadcast => *broadcast,
                 _ => return Err(Errno::Inval),
             },
             InodeSocketKind::Raw(sock) => match option {
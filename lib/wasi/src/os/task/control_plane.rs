@@ -6,7 +6,14 @@ use std::{
     },
 };
 
-use crate::{WasiProcess, WasiProcessId};
+use virtual_net::UnixNamespace;
+
+use crate::{
+    audit::AuditLog,
+    metrics::MetricsSink,
+    net::limits::{SocketLimiter, SocketLimits},
+    WasiProcess, WasiProcessId,
+};
 
 #[derive(Debug, Clone)]
 pub struct WasiControlPlane {
@@ -39,12 +46,21 @@ impl WasiControlPlaneHandle {
 pub struct ControlPlaneConfig {
     /// Total number of tasks (processes + threads) that can be spawned.
     pub max_task_count: Option<usize>,
+    /// Limits on the sockets and connections of all processes.
+    pub socket_limits: SocketLimits,
+    /// Sink that receives the metrics of all processes.
+    pub metrics: Option<Arc<dyn MetricsSink>>,
+    /// Log that records the security-relevant actions of all processes.
+    pub audit_log: Option<Arc<dyn AuditLog>>,
 }
 
 impl ControlPlaneConfig {
     pub fn new() -> Self {
         Self {
             max_task_count: None,
+            socket_limits: SocketLimits::default(),
+            metrics: None,
+            audit_log: None,
         }
     }
 }
@@ -62,6 +78,12 @@ struct State {
     /// Total number of active tasks (threads) across all processes.
     task_count: Arc<AtomicUsize>,
 
+    /// Unix domain sockets that are visible to all processes.
+    unix_sockets: UnixNamespace,
+
+    /// Socket and connection limits shared by all processes.
+    socket_limiter: SocketLimiter,
+
     /// Mutable state.
     mutable: RwLock<MutableState>,
 }
@@ -79,8 +101,10 @@ impl WasiControlPlane {
     pub fn new(config: ControlPlaneConfig) -> Self {
         Self {
             state: Arc::new(State {
+                socket_limiter: SocketLimiter::new(config.socket_limits.clone()),
                 config,
                 task_count: Arc::new(AtomicUsize::new(0)),
+                unix_sockets: UnixNamespace::new(),
                 mutable: RwLock::new(MutableState {
                     process_seed: 0,
                     processes: Default::default(),
@@ -93,6 +117,26 @@ impl WasiControlPlane {
         WasiControlPlaneHandle::new(&self.state)
     }
 
+    /// Namespace in which the Unix domain sockets of all processes live.
+    pub fn unix_sockets(&self) -> &UnixNamespace {
+        &self.state.unix_sockets
+    }
+
+    /// Limits on the sockets of all processes, along with their counters.
+    pub fn socket_limiter(&self) -> &SocketLimiter {
+        &self.state.socket_limiter
+    }
+
+    /// Sink that receives the metrics of all processes, if any.
+    pub fn metrics(&self) -> Option<&Arc<dyn MetricsSink>> {
+        self.state.config.metrics.as_ref()
+    }
+
+    /// Log that records the security-relevant actions of all processes, if any.
+    pub fn audit_log(&self) -> Option<&Arc<dyn AuditLog>> {
+        self.state.config.audit_log.as_ref()
+    }
+
     /// Get the current count of active tasks (threads).
     fn active_task_count(&self) -> usize {
         self.state.task_count.load(Ordering::SeqCst)
@@ -202,6 +246,7 @@ mod tests {
     fn test_control_plane_task_limits() {
         let p = WasiControlPlane::new(ControlPlaneConfig {
             max_task_count: Some(2),
+            ..Default::default()
         });
 
         let p1 = p.new_process().unwrap();
@@ -219,6 +264,7 @@ mod tests {
     fn test_control_plane_task_limits_with_dropped_threads() {
         let p = WasiControlPlane::new(ControlPlaneConfig {
             max_task_count: Some(2),
+            ..Default::default()
         });
 
         let p1 = p.new_process().unwrap();
@@ -0,0 +1,714 @@
+//! Scale-to-zero: parking idle processes and thawing them on demand.
+//!
+//! A [`Supervisor`] runs a process on a thread of its own and parks it once
+//! it went [`ParkingConfig::idle_after`] without a request, or when asked to
+//! with [`Supervisor::park`]. The syscall the process waits in
+//! (`poll_oneoff` or `fd_read`) is interrupted, the stack is unwound with
+//! asyncify and a [snapshot](crate::snapshot) of the process is written to
+//! [`ParkingConfig::path`]. The process then exits, and its store is dropped
+//! along with all of its memory.
+//!
+//! The next request thaws it (see [`Supervisor::activate`]): the module is
+//! instantiated again from the snapshot and the process re-enters the
+//! syscall it was parked in, as if it had been waiting all along. Its
+//! monotonic clock carries on from where it was, relative timeouts start
+//! over. The supervisor keeps track of how long parking and thawing take in
+//! its [`ParkingStats`].
+//!
+//! The limits of snapshots apply: only modules built with asyncify can be
+//! parked, and sockets, pipes and the other threads of the process aren't
+//! captured. Its stdio is whatever the builder of the supervisor gives it
+//! every time it is instantiated.
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, Weak,
+    },
+    task::Waker,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use wasmer::{AsStoreMut, FunctionEnvMut, MemorySize, Module, OnCalledAction};
+use wasmer_wasix_types::wasi::Errno;
+
+use crate::{
+    snapshot::{capture, supports_snapshots, ProcessSnapshot, SnapshotError},
+    storage::StorageOptions,
+    syscalls::{rewind, unwind},
+    WasiEnv, WasiEnvBuilder, WasiError, WasiRuntimeError,
+};
+
+/// Bounds how often a [`Supervisor`] checks whether its process is idle
+const MIN_IDLE_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+const MAX_IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParkingError {
+    #[error("the module wasn't built with asyncify, so it can't be parked")]
+    Unsupported,
+    #[error("unable to start a thread for the process")]
+    Io(#[from] std::io::Error),
+    #[error("the process is handling requests")]
+    Busy,
+    #[error("the process didn't enter a syscall it can be parked in within {0:?}")]
+    Timeout(Duration),
+    #[error("unable to park the process: {0}")]
+    Park(String),
+    #[error("unable to thaw the process")]
+    Thaw(#[from] SnapshotError),
+    #[error("the process exited ({0})")]
+    Exited(String),
+}
+
+/// Where a parked process is kept and when it is parked.
+#[derive(Debug, Clone)]
+pub struct ParkingConfig {
+    /// The file the snapshot of the parked process is written to
+    pub path: PathBuf,
+    /// The directories of the virtual filesystem whose contents are kept
+    /// with the process, see
+    /// [`SnapshotConfig::fs_dirs`](crate::snapshot::SnapshotConfig::fs_dirs)
+    pub fs_dirs: Vec<PathBuf>,
+    /// How the snapshot is compressed and encrypted
+    pub storage: StorageOptions,
+    /// Park the process once it went this long without a request. Without
+    /// it, the process is only parked by [`Supervisor::park`].
+    pub idle_after: Option<Duration>,
+    /// How long [`Supervisor::park`] waits for the process to enter a
+    /// syscall it can be parked in
+    pub park_timeout: Duration,
+}
+
+impl ParkingConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        ParkingConfig {
+            path: path.into(),
+            fs_dirs: Vec::new(),
+            storage: StorageOptions::default(),
+            idle_after: None,
+            park_timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Keeps the contents of a directory of the virtual filesystem
+    pub fn with_fs_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.fs_dirs.push(dir.into());
+        self
+    }
+
+    pub fn with_storage(mut self, storage: StorageOptions) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    pub fn with_idle_after(mut self, idle_after: Duration) -> Self {
+        self.idle_after = Some(idle_after);
+        self
+    }
+
+    pub fn with_park_timeout(mut self, timeout: Duration) -> Self {
+        self.park_timeout = timeout;
+        self
+    }
+}
+
+/// How long parking or thawing took.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Latency {
+    pub count: u64,
+    pub last: Duration,
+    pub max: Duration,
+    pub total: Duration,
+}
+
+impl Latency {
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(self.total.as_secs_f64() / self.count as f64)
+    }
+
+    fn record(&mut self, latency: Duration) {
+        self.count += 1;
+        self.last = latency;
+        self.max = self.max.max(latency);
+        self.total += latency;
+    }
+}
+
+/// What a [`Supervisor`] measured so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParkingStats {
+    /// From the request to park until the process was written out and its
+    /// store dropped
+    pub park: Latency,
+    /// From the request to thaw until the process was back in the syscall
+    /// it was parked in
+    pub thaw: Latency,
+    /// The size of the snapshot the process was last parked in
+    pub parked_bytes: u64,
+}
+
+/// What a supervised process is doing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Running,
+    Parking,
+    Parked,
+    Thawing,
+    /// The process exited by itself, or couldn't be thawed
+    Exited(String),
+}
+
+/// The part of a [`Supervisor`] the process it runs has access to.
+pub struct Parking {
+    config: ParkingConfig,
+    requested: AtomicBool,
+    /// The syscall the process waits in, to be woken up once parking is
+    /// requested
+    waker: Mutex<Option<Waker>>,
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Debug)]
+struct State {
+    phase: Phase,
+    /// Why the last attempt to park the process failed
+    failure: Option<String>,
+    stats: ParkingStats,
+}
+
+#[derive(Debug)]
+enum Phase {
+    Running,
+    /// Waiting for the process to enter a syscall it can be parked in
+    Parking {
+        since: Instant,
+    },
+    /// The process was unwound and is being written out
+    Saving {
+        since: Instant,
+    },
+    Parked,
+    Thawing {
+        since: Instant,
+    },
+    Exited(String),
+}
+
+impl fmt::Debug for Parking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Parking")
+            .field("config", &self.config)
+            .field("requested", &self.requested)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Parking {
+    fn new(config: ParkingConfig) -> Self {
+        Parking {
+            config,
+            requested: AtomicBool::new(false),
+            waker: Mutex::new(None),
+            state: Mutex::new(State {
+                phase: Phase::Running,
+                failure: None,
+                stats: ParkingStats::default(),
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Like [`Parking::is_requested`], `waker` is woken up once parking is
+    /// requested.
+    pub(crate) fn is_requested_or_subscribe(&self, waker: &Waker) -> bool {
+        if self.is_requested() {
+            return true;
+        }
+        *self.waker.lock().unwrap() = Some(waker.clone());
+        // The request may have come in before the waker was stored
+        self.is_requested()
+    }
+
+    fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    fn set_phase(&self, state: &mut State, phase: Phase) {
+        state.phase = phase;
+        self.changed.notify_all();
+    }
+
+    /// Called once the process was unwound, returns whether it should still
+    /// be parked.
+    fn start_saving(&self) -> bool {
+        let mut state = self.lock();
+        match state.phase {
+            Phase::Parking { since } => {
+                self.set_phase(&mut state, Phase::Saving { since });
+                true
+            }
+            // Parking timed out in the meantime
+            _ => {
+                self.requested.store(false, Ordering::SeqCst);
+                false
+            }
+        }
+    }
+
+    /// Called when the process couldn't be written out, it carries on.
+    fn saving_failed(&self, error: &SnapshotError) {
+        let mut state = self.lock();
+        self.requested.store(false, Ordering::SeqCst);
+        state.failure = Some(error.to_string());
+        self.set_phase(&mut state, Phase::Running);
+    }
+
+    /// Called when the stack of the process was rewound into a syscall.
+    pub(crate) fn resumed(&self) {
+        let mut state = self.lock();
+        if let Phase::Thawing { since } = state.phase {
+            let latency = since.elapsed();
+            state.stats.thaw.record(latency);
+            tracing::debug!(?latency, "Thawed the process");
+            self.set_phase(&mut state, Phase::Running);
+        }
+    }
+
+    /// Called when the process is gone, because it was parked or because it
+    /// exited.
+    fn stopped(&self, result: Result<(), WasiRuntimeError>) {
+        let mut state = self.lock();
+        self.requested.store(false, Ordering::SeqCst);
+
+        if let Phase::Saving { since } = state.phase {
+            let latency = since.elapsed();
+            state.stats.park.record(latency);
+            state.stats.parked_bytes = std::fs::metadata(&self.config.path)
+                .map(|m| m.len())
+                .unwrap_or_default();
+            tracing::info!(
+                path = %self.config.path.display(),
+                ?latency,
+                bytes = state.stats.parked_bytes,
+                "Parked the process",
+            );
+            self.set_phase(&mut state, Phase::Parked);
+            return;
+        }
+
+        let reason = match result {
+            Ok(()) => "exit code 0".to_string(),
+            Err(e) => match e.as_exit_code() {
+                Some(code) => format!("exit code {}", code.raw()),
+                None => e.to_string(),
+            },
+        };
+        tracing::debug!(%reason, "The supervised process exited");
+        self.set_phase(&mut state, Phase::Exited(reason));
+    }
+}
+
+/// Unwinds the stack out of the current syscall and writes the process out,
+/// then exits. If that fails the process is rewound into the syscall and
+/// carries on. Use [`maybe_snapshot!`] rather than calling this directly.
+#[must_use = "you must return the result immediately so the stack can unwind"]
+pub(crate) fn unwind_and_park<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+) -> Result<Errno, WasiError> {
+    let parking = match ctx.data().parking.clone() {
+        Some(parking) => parking,
+        None => return Ok(Errno::Success),
+    };
+
+    tracing::debug!("Unwinding to park the process");
+    unwind::<M, _>(ctx, move |mut ctx, memory_stack, rewind_stack| {
+        let memory_stack = memory_stack.freeze();
+        let rewind_stack = rewind_stack.freeze();
+
+        if parking.start_saving() {
+            let config = &parking.config;
+            let result = capture::<M>(
+                &mut ctx,
+                memory_stack.clone(),
+                rewind_stack.clone(),
+                &config.fs_dirs,
+            )
+            .and_then(|snapshot| snapshot.write_with(&config.path, &config.storage));
+
+            match result {
+                Ok(()) => {
+                    return OnCalledAction::Trap(Box::new(WasiError::Exit(Errno::Success.into())))
+                }
+                Err(e) => {
+                    tracing::error!(
+                        path = %config.path.display(),
+                        error = &e as &dyn std::error::Error,
+                        "Unable to park the process, it carries on",
+                    );
+                    parking.saving_failed(&e);
+                }
+            }
+        }
+
+        let store_data =
+            match crate::utils::store::capture_snapshot(&mut ctx.as_store_mut()).serialize() {
+                Ok(store_data) => Bytes::from(store_data),
+                Err(e) => {
+                    tracing::error!("Unable to capture the globals of the process ({e})");
+                    return OnCalledAction::Trap(Box::new(WasiError::Exit(Errno::Io.into())));
+                }
+            };
+        match rewind::<M>(ctx, memory_stack, rewind_stack, store_data) {
+            Errno::Success => OnCalledAction::InvokeAgain,
+            err => {
+                tracing::warn!("parking failed - could not rewind the stack - errno={err}");
+                OnCalledAction::Trap(Box::new(WasiError::Exit(err.into())))
+            }
+        }
+    })
+}
+
+/// Runs a process, parks it while it is idle and thaws it on demand, see
+/// [the module docs](self).
+#[derive(Debug, Clone)]
+pub struct Supervisor {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    module: Module,
+    setup: Box<dyn Fn() -> WasiEnvBuilder + Send + Sync>,
+    parking: Arc<Parking>,
+    /// The requests being handled, see [`Supervisor::activate`]
+    active: AtomicUsize,
+    last_active: Mutex<Instant>,
+}
+
+impl fmt::Debug for Inner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Inner")
+            .field("module", &self.module)
+            .field("parking", &self.parking)
+            .field("active", &self.active)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Keeps a supervised process from being parked for idleness while a
+/// request is handled, see [`Supervisor::activate`].
+#[derive(Debug)]
+pub struct Activity {
+    inner: Arc<Inner>,
+}
+
+impl Drop for Activity {
+    fn drop(&mut self) {
+        *self.inner.last_active.lock().unwrap() = Instant::now();
+        self.inner.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Supervisor {
+    /// Starts running `module`. The process is set up by `setup` every time
+    /// it is instantiated, i.e. now and whenever it is thawed.
+    pub fn start(
+        module: Module,
+        config: ParkingConfig,
+        setup: impl Fn() -> WasiEnvBuilder + Send + Sync + 'static,
+    ) -> Result<Self, ParkingError> {
+        if !supports_snapshots(&module) {
+            return Err(ParkingError::Unsupported);
+        }
+
+        let idle_after = config.idle_after;
+        let inner = Arc::new(Inner {
+            module,
+            setup: Box::new(setup),
+            parking: Arc::new(Parking::new(config)),
+            active: AtomicUsize::new(0),
+            last_active: Mutex::new(Instant::now()),
+        });
+        Inner::spawn(&inner, None)?;
+
+        if let Some(idle_after) = idle_after {
+            let weak = Arc::downgrade(&inner);
+            std::thread::Builder::new()
+                .name("wasmer-idle-watch".to_string())
+                .spawn(move || watch_idle(weak, idle_after))?;
+        }
+
+        Ok(Supervisor { inner })
+    }
+
+    /// Parks the process, waiting until it is written out. Returns how long
+    /// that took.
+    pub fn park(&self) -> Result<Duration, ParkingError> {
+        if self.inner.active.load(Ordering::SeqCst) > 0 {
+            return Err(ParkingError::Busy);
+        }
+        self.inner.park()
+    }
+
+    /// Thaws the process if it is parked, waiting until it is back where it
+    /// was parked. Returns how long that took.
+    pub fn thaw(&self) -> Result<Duration, ParkingError> {
+        Inner::thaw(&self.inner)
+    }
+
+    /// Thaws the process if it is parked, and keeps it from being parked for
+    /// idleness until the returned [`Activity`] is dropped. Call it for every
+    /// request to the process.
+    pub fn activate(&self) -> Result<Activity, ParkingError> {
+        self.inner.active.fetch_add(1, Ordering::SeqCst);
+        let activity = Activity {
+            inner: Arc::clone(&self.inner),
+        };
+        Inner::thaw(&self.inner)?;
+        Ok(activity)
+    }
+
+    pub fn status(&self) -> Status {
+        match &self.inner.parking.lock().phase {
+            Phase::Running => Status::Running,
+            Phase::Parking { .. } | Phase::Saving { .. } => Status::Parking,
+            Phase::Parked => Status::Parked,
+            Phase::Thawing { .. } => Status::Thawing,
+            Phase::Exited(reason) => Status::Exited(reason.clone()),
+        }
+    }
+
+    pub fn stats(&self) -> ParkingStats {
+        self.inner.parking.lock().stats.clone()
+    }
+}
+
+impl Inner {
+    /// Runs the process on a thread of its own, until it exits or is
+    /// parked.
+    fn spawn(inner: &Arc<Inner>, resume: Option<ProcessSnapshot>) -> std::io::Result<()> {
+        let inner = Arc::clone(inner);
+        std::thread::Builder::new()
+            .name("wasmer-supervised".to_string())
+            .spawn(move || {
+                let mut builder = (inner.setup)();
+                builder.set_parking(Arc::clone(&inner.parking));
+                if let Some(snapshot) = resume {
+                    builder.set_resume_from(snapshot);
+                }
+                let result = builder.run(inner.module.clone());
+                inner.parking.stopped(result);
+            })?;
+        Ok(())
+    }
+
+    fn park(&self) -> Result<Duration, ParkingError> {
+        let parking = &self.parking;
+        let mut state = parking.lock();
+        loop {
+            match &state.phase {
+                Phase::Running => break,
+                Phase::Parked => return Ok(Duration::ZERO),
+                Phase::Exited(reason) => return Err(ParkingError::Exited(reason.clone())),
+                // Someone else is parking or thawing the process
+                _ => state = parking.changed.wait(state).unwrap(),
+            }
+        }
+
+        let since = Instant::now();
+        state.failure = None;
+        parking.set_phase(&mut state, Phase::Parking { since });
+        parking.request();
+
+        let timeout = parking.config.park_timeout;
+        loop {
+            state = match &state.phase {
+                Phase::Parked => return Ok(state.stats.park.last),
+                Phase::Running => {
+                    let failure = state.failure.take();
+                    return Err(ParkingError::Park(
+                        failure.unwrap_or_else(|| "it was thawed again".to_string()),
+                    ));
+                }
+                Phase::Exited(reason) => return Err(ParkingError::Exited(reason.clone())),
+                Phase::Parking { .. } if since.elapsed() >= timeout => {
+                    parking.requested.store(false, Ordering::SeqCst);
+                    parking.set_phase(&mut state, Phase::Running);
+                    return Err(ParkingError::Timeout(timeout));
+                }
+                Phase::Parking { .. } => {
+                    let left = timeout.saturating_sub(since.elapsed());
+                    parking.changed.wait_timeout(state, left).unwrap().0
+                }
+                // Too late to back out, the snapshot is being written
+                _ => parking.changed.wait(state).unwrap(),
+            };
+        }
+    }
+
+    fn thaw(inner: &Arc<Inner>) -> Result<Duration, ParkingError> {
+        let parking = &inner.parking;
+        let mut state = parking.lock();
+        loop {
+            match &state.phase {
+                Phase::Running => return Ok(Duration::ZERO),
+                Phase::Parked => break,
+                Phase::Exited(reason) => return Err(ParkingError::Exited(reason.clone())),
+                _ => state = parking.changed.wait(state).unwrap(),
+            }
+        }
+
+        let since = Instant::now();
+        parking.set_phase(&mut state, Phase::Thawing { since });
+        drop(state);
+
+        let config = &parking.config;
+        let started = ProcessSnapshot::read_with(&config.path, &config.storage)
+            .map_err(ParkingError::from)
+            .and_then(|snapshot| Ok(Inner::spawn(inner, Some(snapshot))?));
+        if let Err(e) = started {
+            let mut state = parking.lock();
+            parking.set_phase(&mut state, Phase::Parked);
+            return Err(e);
+        }
+
+        let mut state = parking.lock();
+        loop {
+            match &state.phase {
+                Phase::Thawing { .. } => state = parking.changed.wait(state).unwrap(),
+                Phase::Exited(reason) => return Err(ParkingError::Exited(reason.clone())),
+                _ => break,
+            }
+        }
+        *inner.last_active.lock().unwrap() = Instant::now();
+        Ok(state.stats.thaw.last)
+    }
+}
+
+/// Parks the process of a [`Supervisor`] whenever it went `idle_after`
+/// without a request, until the supervisor and its process are gone.
+fn watch_idle(inner: Weak<Inner>, idle_after: Duration) {
+    let interval = (idle_after / 4).clamp(MIN_IDLE_CHECK_INTERVAL, MAX_IDLE_CHECK_INTERVAL);
+    loop {
+        std::thread::sleep(interval);
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+
+        let running = match inner.parking.lock().phase {
+            Phase::Running => true,
+            Phase::Exited(_) => return,
+            _ => false,
+        };
+        let idle = inner.active.load(Ordering::SeqCst) == 0
+            && inner.last_active.lock().unwrap().elapsed() >= idle_after;
+        if running && idle {
+            if let Err(e) = inner.park() {
+                tracing::debug!(
+                    error = &e as &dyn std::error::Error,
+                    "Unable to park the idle process",
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::task::ArcWake;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Woken(AtomicBool);
+
+    impl ArcWake for Woken {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn parking_wakes_up_the_waiting_syscall() {
+        let parking = Parking::new(ParkingConfig::new("app.parked"));
+        let woken = Arc::new(Woken::default());
+        let waker = futures::task::waker(Arc::clone(&woken));
+
+        assert!(!parking.is_requested_or_subscribe(&waker));
+        parking.request();
+        assert!(woken.0.load(Ordering::SeqCst));
+        assert!(parking.is_requested_or_subscribe(&waker));
+    }
+
+    #[test]
+    fn park_and_thaw_latencies_are_measured() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("app.parked");
+        std::fs::write(&path, [0; 64]).unwrap();
+        let parking = Parking::new(ParkingConfig::new(&path));
+
+        parking.lock().phase = Phase::Parking {
+            since: Instant::now(),
+        };
+        parking.request();
+        assert!(parking.start_saving());
+        parking.stopped(Err(WasiError::Exit(Errno::Success.into()).into()));
+        assert!(matches!(parking.lock().phase, Phase::Parked));
+        assert!(!parking.is_requested());
+
+        // Checkpoints rewind the process too, those aren't thaws
+        parking.resumed();
+        assert!(matches!(parking.lock().phase, Phase::Parked));
+        parking.lock().phase = Phase::Thawing {
+            since: Instant::now(),
+        };
+        parking.resumed();
+        assert!(matches!(parking.lock().phase, Phase::Running));
+
+        let stats = parking.lock().stats.clone();
+        assert_eq!(stats.park.count, 1);
+        assert_eq!(stats.thaw.count, 1);
+        assert_eq!(stats.parked_bytes, 64);
+
+        parking.stopped(Ok(()));
+        assert!(matches!(&parking.lock().phase, Phase::Exited(reason) if reason == "exit code 0"));
+    }
+
+    #[test]
+    fn a_timed_out_park_is_not_saved() {
+        let parking = Parking::new(ParkingConfig::new("app.parked"));
+        parking.request();
+        assert!(!parking.start_saving());
+        assert!(!parking.is_requested());
+    }
+
+    #[test]
+    fn latencies_are_summed_up() {
+        let mut latency = Latency::default();
+        assert_eq!(latency.mean(), Duration::ZERO);
+        latency.record(Duration::from_millis(10));
+        latency.record(Duration::from_millis(30));
+        assert_eq!(latency.count, 2);
+        assert_eq!(latency.last, Duration::from_millis(30));
+        assert_eq!(latency.max, Duration::from_millis(30));
+        assert_eq!(latency.mean(), Duration::from_millis(20));
+    }
+}
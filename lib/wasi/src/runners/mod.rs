@@ -20,8 +20,49 @@ pub type CompileModule = dyn Fn(&Engine, &[u8]) -> Result<Module, Error> + Send
 pub struct MappedDirectory {
     pub host: std::path::PathBuf,
     pub guest: String,
+    #[serde(default)]
+    pub options: MountOptions,
 }
 
+impl MappedDirectory {
+    pub fn new(host: impl Into<std::path::PathBuf>, guest: impl Into<String>) -> Self {
+        MappedDirectory {
+            host: host.into(),
+            guest: guest.into(),
+            options: MountOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: MountOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+/// Restrictions on what the guest may do with a [`MappedDirectory`].
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct MountOptions {
+    /// The guest may read the directory, but not modify it.
+    #[serde(default)]
+    pub read_only: bool,
+    /// The guest may modify existing files, but not create new files or
+    /// directories.
+    #[serde(default)]
+    pub no_create: bool,
+}
+
+impl MountOptions {
+    /// Wrap the filesystem backing a mount so these restrictions are enforced.
+    pub fn restrict<F>(&self, fs: F) -> virtual_fs::RestrictedFileSystem<F> {
+        virtual_fs::RestrictedFileSystem::new(fs)
+            .read_only(self.read_only)
+            .allow_create(!self.no_create)
+    }
+}
+
+#[tracing::instrument(level = "debug", skip_all, fields(wasm.size = wasm.len()), err)]
 pub(crate) fn default_compile(engine: &Engine, wasm: &[u8]) -> Result<Module, Error> {
     let module = Module::new(engine, wasm)?;
     Ok(module)
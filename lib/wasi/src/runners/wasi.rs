@@ -103,6 +103,29 @@ impl WasiRunner {
         }
     }
 
+    /// Builder method to mark environment variables as secrets, so their
+    /// values are redacted from logs.
+    pub fn with_secrets<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.set_secrets(names);
+        self
+    }
+
+    /// Mark environment variables as secrets, so their values are redacted
+    /// from logs.
+    pub fn set_secrets<I, S>(&mut self, names: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.wasi
+            .secrets
+            .extend(names.into_iter().map(|name| name.into()));
+    }
+
     pub fn with_forward_host_env(mut self) -> Self {
         self.set_forward_host_env();
         self
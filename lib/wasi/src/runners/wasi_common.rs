@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -8,16 +8,46 @@ use anyhow::{Context, Error};
 use virtual_fs::{FileSystem, FsError, OverlayFileSystem, RootFileSystemBuilder};
 use webc::metadata::annotations::Wasi as WasiAnnotation;
 
-use crate::{runners::MappedDirectory, WasiEnvBuilder};
+use crate::{
+    runners::{MappedDirectory, MountOptions},
+    WasiEnvBuilder,
+};
 
-#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Default, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct CommonWasiOptions {
     pub(crate) args: Vec<String>,
     pub(crate) env: HashMap<String, String>,
+    /// Environment variables whose values must never be logged.
+    #[serde(default)]
+    pub(crate) secrets: HashSet<String>,
     pub(crate) forward_host_env: bool,
     pub(crate) mapped_dirs: Vec<MappedDirectory>,
 }
 
+impl std::fmt::Debug for CommonWasiOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let env: HashMap<&str, &str> = self
+            .env
+            .iter()
+            .map(|(key, value)| {
+                if self.secrets.contains(key) {
+                    (key.as_str(), "<redacted>")
+                } else {
+                    (key.as_str(), value.as_str())
+                }
+            })
+            .collect();
+
+        f.debug_struct("CommonWasiOptions")
+            .field("args", &self.args)
+            .field("env", &env)
+            .field("secrets", &self.secrets)
+            .field("forward_host_env", &self.forward_host_env)
+            .field("mapped_dirs", &self.mapped_dirs)
+            .finish()
+    }
+}
+
 impl CommonWasiOptions {
     pub(crate) fn prepare_webc_env(
         &self,
@@ -63,6 +93,7 @@ impl CommonWasiOptions {
         }
 
         builder.add_envs(self.env.clone());
+        builder.add_secrets(self.secrets.iter().cloned());
     }
 
     fn populate_args(&self, wasi: &WasiAnnotation, builder: &mut WasiEnvBuilder) {
@@ -85,11 +116,17 @@ fn prepare_filesystem(
         let host_fs: Arc<dyn FileSystem + Send + Sync> = Arc::new(crate::default_fs_backing());
 
         for dir in mapped_dirs {
-            let MappedDirectory { host, guest } = dir;
+            let MappedDirectory {
+                host,
+                guest,
+                options,
+            } = dir;
             let guest = PathBuf::from(guest);
             tracing::debug!(
                 guest=%guest.display(),
                 host=%host.display(),
+                read_only=options.read_only,
+                no_create=options.no_create,
                 "Mounting host folder",
             );
 
@@ -99,8 +136,15 @@ fn prepare_filesystem(
                 })?;
             }
 
+            let mount_fs: Arc<dyn FileSystem + Send + Sync> = if *options == MountOptions::default()
+            {
+                Arc::clone(&host_fs)
+            } else {
+                Arc::new(options.restrict(Arc::clone(&host_fs)))
+            };
+
             root_fs
-                .mount(guest.clone(), &host_fs, host.clone())
+                .mount(guest.clone(), &mount_fs, host.clone())
                 .with_context(|| {
                     format!(
                         "Unable to mount \"{}\" to \"{}\"",
@@ -276,16 +320,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn secrets_are_hidden_but_still_passed_to_the_guest() {
+        let args = CommonWasiOptions {
+            env: vec![("TOKEN".to_string(), "hunter2".to_string())]
+                .into_iter()
+                .collect(),
+            secrets: vec!["TOKEN".to_string()].into_iter().collect(),
+            ..Default::default()
+        };
+        let mut builder = WasiEnvBuilder::new("python");
+        let fs = Arc::new(virtual_fs::EmptyFileSystem::default());
+
+        args.prepare_webc_env(&mut builder, fs, &WasiAnnotation::new("python"))
+            .unwrap();
+
+        assert!(!format!("{args:?}").contains("hunter2"));
+        assert!(!format!("{builder:?}").contains("hunter2"));
+        assert_eq!(
+            builder.get_env(),
+            [("TOKEN".to_string(), b"hunter2".to_vec())]
+        );
+    }
+
     #[test]
     fn python_use_case() {
         let temp = TempDir::new().unwrap();
         let sub_dir = temp.path().join("path").join("to");
         std::fs::create_dir_all(&sub_dir).unwrap();
         std::fs::write(sub_dir.join("file.txt"), b"Hello, World!").unwrap();
-        let mapping = [MappedDirectory {
-            guest: "/home".to_string(),
-            host: sub_dir,
-        }];
+        let mapping = [MappedDirectory::new(sub_dir, "/home")];
         let container = Container::from_bytes(PYTHON).unwrap();
         let webc_fs = WebcVolumeFileSystem::mount_all(&container);
 
@@ -302,4 +366,29 @@ mod tests {
             .unwrap()
             .is_file());
     }
+
+    #[test]
+    fn read_only_mapped_directory() {
+        let temp = TempDir::new().unwrap();
+        std::fs::write(temp.path().join("file.txt"), b"Hello, World!").unwrap();
+        let mapping = [
+            MappedDirectory::new(temp.path(), "/data").with_options(MountOptions {
+                read_only: true,
+                no_create: false,
+            }),
+        ];
+        let container = Container::from_bytes(PYTHON).unwrap();
+        let webc_fs = WebcVolumeFileSystem::mount_all(&container);
+
+        let fs = prepare_filesystem(&mapping, Arc::new(webc_fs), |_| Ok(())).unwrap();
+
+        assert!(fs.metadata("/data/file.txt".as_ref()).unwrap().is_file());
+        assert!(fs
+            .new_open_options()
+            .write(true)
+            .open("/data/file.txt")
+            .is_err());
+        assert!(fs.remove_file("/data/file.txt".as_ref()).is_err());
+        assert!(temp.path().join("file.txt").exists());
+    }
 }
@@ -0,0 +1,559 @@
+use std::{
+    fmt::Write as _,
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Error};
+use futures::{FutureExt, StreamExt};
+use http::{header, uri::PathAndQuery, Request, Response, StatusCode, Uri};
+use hyper::{service::Service, Body};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tower::{make::Shared, ServiceBuilder};
+use tower_http::{catch_panic::CatchPanicLayer, trace::TraceLayer};
+
+use crate::runners::wcgi::Handler;
+
+/// Requests to this path are answered by the [`Daemon`] itself with the state
+/// of every route, as JSON.
+pub const HEALTH_PATH: &str = "/_wasmer/health";
+/// Requests to this path are answered by the [`Daemon`] itself with per-route
+/// counters, in the Prometheus text format.
+pub const METRICS_PATH: &str = "/_wasmer/metrics";
+
+/// Routes may not live below this prefix, it is used by the daemon itself.
+const RESERVED_PREFIX: &str = "/_wasmer";
+
+/// How a [`Daemon`] manages the instances behind each route.
+#[derive(Debug, Clone)]
+pub struct DaemonConfig {
+    /// The most instances a route runs at the same time.
+    pub max_instances: usize,
+    /// How long a request may wait for a free instance before it is turned
+    /// away with `503 Service Unavailable`.
+    pub queue_timeout: Duration,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig {
+            max_instances: 16,
+            queue_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A HTTP server that routes requests to several WCGI [`Handler`]s by path
+/// prefix.
+///
+/// Each route has a pool of up to [`DaemonConfig::max_instances`] instances
+/// sharing one compiled module, so a request only pays for instantiation.
+/// Requests that arrive while the pool is exhausted wait for a free slot.
+///
+/// Routes can be replaced while the server is running (e.g. because the
+/// package was updated). Requests which were already routed keep using the
+/// old handler until they complete, and the pool's limit covers both.
+#[derive(Debug, Clone)]
+pub struct Daemon {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    config: DaemonConfig,
+    /// Sorted so longer prefixes are matched first.
+    routes: RwLock<Vec<Arc<Route>>>,
+    started: Instant,
+    unrouted: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Route {
+    prefix: String,
+    source: String,
+    generation: u64,
+    handler: Handler,
+    instances: Arc<Semaphore>,
+    stats: Arc<RouteStats>,
+}
+
+#[derive(Debug, Default)]
+struct RouteStats {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    rejected: AtomicU64,
+    in_flight: AtomicU64,
+    busy_micros: AtomicU64,
+    reloads: AtomicU64,
+}
+
+impl Daemon {
+    pub fn new(mut config: DaemonConfig) -> Self {
+        config.max_instances = config.max_instances.max(1);
+
+        Daemon {
+            inner: Arc::new(Inner {
+                config,
+                routes: RwLock::new(Vec::new()),
+                started: Instant::now(),
+                unrouted: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Serve `handler` below `prefix`, replacing whatever was served there
+    /// before.
+    ///
+    /// `source` is only used for reporting (e.g. the package's path or
+    /// name). Returns `true` if an existing route was replaced.
+    pub fn insert(
+        &self,
+        prefix: &str,
+        source: impl Into<String>,
+        handler: Handler,
+    ) -> Result<bool, Error> {
+        let prefix = normalize_prefix(prefix)?;
+        let mut routes = self.inner.routes.write().unwrap();
+
+        let previous = routes
+            .iter()
+            .position(|route| route.prefix == prefix)
+            .map(|index| routes.remove(index));
+
+        let (generation, instances, stats) = match &previous {
+            Some(old) => {
+                old.stats.reloads.fetch_add(1, Ordering::Relaxed);
+                (
+                    old.generation + 1,
+                    Arc::clone(&old.instances),
+                    Arc::clone(&old.stats),
+                )
+            }
+            None => (
+                1,
+                Arc::new(Semaphore::new(self.inner.config.max_instances)),
+                Arc::default(),
+            ),
+        };
+
+        tracing::info!(%prefix, generation, program = handler.program_name(), "Serving a route");
+
+        routes.push(Arc::new(Route {
+            prefix,
+            source: source.into(),
+            generation,
+            handler,
+            instances,
+            stats,
+        }));
+        routes.sort_by(|a, b| b.prefix.len().cmp(&a.prefix.len()));
+
+        Ok(previous.is_some())
+    }
+
+    /// Stop serving anything below `prefix`. Requests that are in progress
+    /// are left to complete.
+    pub fn remove(&self, prefix: &str) -> bool {
+        let prefix = match normalize_prefix(prefix) {
+            Ok(p) => p,
+            Err(_) => return false,
+        };
+        let mut routes = self.inner.routes.write().unwrap();
+        let len = routes.len();
+        routes.retain(|route| route.prefix != prefix);
+        routes.len() != len
+    }
+
+    /// The prefixes currently being served, longest first.
+    pub fn prefixes(&self) -> Vec<String> {
+        let routes = self.inner.routes.read().unwrap();
+        routes.iter().map(|route| route.prefix.clone()).collect()
+    }
+
+    /// Accept connections on `addr` until `shutdown` resolves, then wait for
+    /// the requests in progress to complete.
+    pub async fn serve(
+        &self,
+        addr: SocketAddr,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), Error> {
+        let service = ServiceBuilder::new()
+            .layer(TraceLayer::new_for_http())
+            .layer(CatchPanicLayer::new())
+            .service(self.clone());
+
+        let server = hyper::Server::try_bind(&addr)
+            .with_context(|| format!("Unable to listen on {addr}"))?;
+        tracing::info!(address = %addr, "Starting the server");
+
+        server
+            .serve(Shared::new(service))
+            .with_graceful_shutdown(async {
+                shutdown.await;
+                tracing::info!("Shutting down gracefully");
+            })
+            .await
+            .context("The server failed")
+    }
+
+    async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, Error> {
+        match req.uri().path() {
+            HEALTH_PATH => return Ok(self.health()),
+            METRICS_PATH => return Ok(self.metrics()),
+            _ => {}
+        }
+
+        let route = match self.route(req.uri().path()) {
+            Some(route) => route,
+            None => {
+                self.inner.unrouted.fetch_add(1, Ordering::Relaxed);
+                return Ok(text_response(
+                    StatusCode::NOT_FOUND,
+                    "Nothing is served at this path\n",
+                ));
+            }
+        };
+
+        route.stats.requests.fetch_add(1, Ordering::Relaxed);
+
+        let permit = tokio::time::timeout(
+            self.inner.config.queue_timeout,
+            Arc::clone(&route.instances).acquire_owned(),
+        )
+        .await;
+        let permit = match permit {
+            Ok(Ok(permit)) => permit,
+            _ => {
+                route.stats.rejected.fetch_add(1, Ordering::Relaxed);
+                return Ok(text_response(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "All instances are busy, try again later\n",
+                ));
+            }
+        };
+        let in_flight = InFlight::new(Arc::clone(&route.stats), permit);
+
+        let (mut parts, body) = req.into_parts();
+        parts.uri = strip_prefix(&parts.uri, &route.prefix);
+
+        match route.handler.handle(Request::from_parts(parts, body)).await {
+            Ok(response) => {
+                if response.status().is_server_error() {
+                    route.stats.errors.fetch_add(1, Ordering::Relaxed);
+                }
+
+                // The instance keeps its slot in the pool until the response
+                // has been streamed back in full.
+                let (parts, body) = response.into_parts();
+                let body = Body::wrap_stream(body.map(move |chunk| {
+                    let _ = &in_flight;
+                    chunk
+                }));
+
+                Ok(Response::from_parts(parts, body))
+            }
+            Err(e) => {
+                route.stats.errors.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(
+                    prefix = %route.prefix,
+                    error = &*e as &dyn std::error::Error,
+                    "Unable to handle the request",
+                );
+                Ok(text_response(
+                    StatusCode::BAD_GATEWAY,
+                    "The package was unable to handle the request\n",
+                ))
+            }
+        }
+    }
+
+    fn route(&self, path: &str) -> Option<Arc<Route>> {
+        let routes = self.inner.routes.read().unwrap();
+        routes
+            .iter()
+            .find(|route| matches_prefix(&route.prefix, path))
+            .cloned()
+    }
+
+    fn health(&self) -> Response<Body> {
+        let routes = self.inner.routes.read().unwrap();
+
+        let body = serde_json::json!({
+            "status": if routes.is_empty() { "starting" } else { "ok" },
+            "uptime_secs": self.inner.started.elapsed().as_secs(),
+            "routes": routes
+                .iter()
+                .map(|route| {
+                    serde_json::json!({
+                        "prefix": route.prefix,
+                        "source": route.source,
+                        "program": route.handler.program_name(),
+                        "generation": route.generation,
+                        "in_flight": route.stats.in_flight.load(Ordering::Relaxed),
+                        "max_instances": self.inner.config.max_instances,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        });
+
+        let status = if routes.is_empty() {
+            StatusCode::SERVICE_UNAVAILABLE
+        } else {
+            StatusCode::OK
+        };
+
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn metrics(&self) -> Response<Body> {
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(Body::from(self.render_metrics()))
+            .unwrap()
+    }
+
+    fn render_metrics(&self) -> String {
+        let routes = self.inner.routes.read().unwrap();
+        let mut out = String::new();
+
+        let counters: [(&str, &str, fn(&RouteStats) -> &AtomicU64); 5] = [
+            ("requests_total", "Requests routed to the package", |s| {
+                &s.requests
+            }),
+            (
+                "errors_total",
+                "Requests the package failed to handle",
+                |s| &s.errors,
+            ),
+            (
+                "rejected_total",
+                "Requests turned away because every instance was busy",
+                |s| &s.rejected,
+            ),
+            (
+                "reloads_total",
+                "Times the package was replaced while serving",
+                |s| &s.reloads,
+            ),
+            ("in_flight", "Instances handling a request right now", |s| {
+                &s.in_flight
+            }),
+        ];
+
+        for (name, help, counter) in counters {
+            let kind = if name == "in_flight" {
+                "gauge"
+            } else {
+                "counter"
+            };
+            let _ = writeln!(out, "# HELP wasmer_serve_{name} {help}.");
+            let _ = writeln!(out, "# TYPE wasmer_serve_{name} {kind}");
+            for route in routes.iter() {
+                let value = counter(&route.stats).load(Ordering::Relaxed);
+                let _ = writeln!(
+                    out,
+                    "wasmer_serve_{name}{{route=\"{}\"}} {value}",
+                    route.prefix
+                );
+            }
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP wasmer_serve_busy_seconds_total Time instances spent handling requests."
+        );
+        let _ = writeln!(out, "# TYPE wasmer_serve_busy_seconds_total counter");
+        for route in routes.iter() {
+            let micros = route.stats.busy_micros.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "wasmer_serve_busy_seconds_total{{route=\"{}\"}} {}",
+                route.prefix,
+                micros as f64 / 1_000_000.0
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP wasmer_serve_unrouted_requests_total Requests that matched no route."
+        );
+        let _ = writeln!(out, "# TYPE wasmer_serve_unrouted_requests_total counter");
+        let _ = writeln!(
+            out,
+            "wasmer_serve_unrouted_requests_total {}",
+            self.inner.unrouted.load(Ordering::Relaxed)
+        );
+
+        out
+    }
+}
+
+impl Service<Request<Body>> for Daemon {
+    type Response = Response<Body>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Back-pressure is applied per route, once we know where the request
+        // is going.
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let daemon = self.clone();
+        let fut = async move { daemon.handle(request).await };
+        fut.boxed()
+    }
+}
+
+/// Book-keeping for a request that holds one of its route's instances.
+struct InFlight {
+    stats: Arc<RouteStats>,
+    started: Instant,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl InFlight {
+    fn new(stats: Arc<RouteStats>, permit: OwnedSemaphorePermit) -> Self {
+        stats.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight {
+            stats,
+            started: Instant::now(),
+            _permit: permit,
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let micros = self.started.elapsed().as_micros() as u64;
+        self.stats.busy_micros.fetch_add(micros, Ordering::Relaxed);
+        self.stats.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn text_response(status: StatusCode, body: &'static str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn normalize_prefix(prefix: &str) -> Result<String, Error> {
+    anyhow::ensure!(
+        prefix.starts_with('/'),
+        "Routes must start with a \"/\", found \"{prefix}\""
+    );
+
+    let prefix = match prefix.trim_end_matches('/') {
+        "" => "/".to_string(),
+        trimmed => trimmed.to_string(),
+    };
+    anyhow::ensure!(
+        !matches_prefix(RESERVED_PREFIX, &prefix),
+        "Routes below \"{RESERVED_PREFIX}\" are reserved"
+    );
+
+    Ok(prefix)
+}
+
+fn matches_prefix(prefix: &str, path: &str) -> bool {
+    if prefix == "/" {
+        return true;
+    }
+
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Remove the route's prefix from the request's path, so the package sees
+/// the same paths no matter where it is mounted.
+fn strip_prefix(uri: &Uri, prefix: &str) -> Uri {
+    if prefix == "/" {
+        return uri.clone();
+    }
+
+    let path = match uri.path().strip_prefix(prefix) {
+        Some("") | None => "/",
+        Some(rest) => rest,
+    };
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path.to_string(),
+    };
+
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = path_and_query.parse::<PathAndQuery>().ok();
+    Uri::from_parts(parts).unwrap_or_else(|_| uri.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_are_normalized() {
+        assert_eq!(normalize_prefix("/").unwrap(), "/");
+        assert_eq!(normalize_prefix("/api/").unwrap(), "/api");
+        assert_eq!(normalize_prefix("/api/v1").unwrap(), "/api/v1");
+        assert!(normalize_prefix("api").is_err());
+        assert!(normalize_prefix("/_wasmer").is_err());
+        assert!(normalize_prefix("/_wasmer/other").is_err());
+        assert!(normalize_prefix("/_wasmerish").is_ok());
+    }
+
+    #[test]
+    fn prefixes_match_whole_segments() {
+        assert!(matches_prefix("/", "/anything"));
+        assert!(matches_prefix("/api", "/api"));
+        assert!(matches_prefix("/api", "/api/users"));
+        assert!(!matches_prefix("/api", "/apis"));
+        assert!(!matches_prefix("/api", "/"));
+    }
+
+    #[test]
+    fn the_prefix_is_stripped_from_the_request() {
+        let uri: Uri = "/api/users?page=2".parse().unwrap();
+        assert_eq!(strip_prefix(&uri, "/api"), "/users?page=2");
+
+        let uri: Uri = "/api".parse().unwrap();
+        assert_eq!(strip_prefix(&uri, "/api"), "/");
+
+        let uri: Uri = "/index.html".parse().unwrap();
+        assert_eq!(strip_prefix(&uri, "/"), "/index.html");
+    }
+
+    #[test]
+    fn unrouted_requests_are_counted() {
+        let daemon = Daemon::new(DaemonConfig::default());
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let response = rt
+            .block_on(daemon.handle(Request::new(Body::empty())))
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let health = Request::get(HEALTH_PATH).body(Body::empty()).unwrap();
+        let response = rt.block_on(daemon.handle(health)).unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let metrics = daemon.render_metrics();
+        assert!(metrics.contains("wasmer_serve_unrouted_requests_total 1\n"));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, ops::Deref, pin::Pin, sync::Arc, task::Poll};
+use std::{collections::HashMap, pin::Pin, sync::Arc, task::Poll};
 
 use anyhow::Error;
 use futures::{Future, FutureExt, StreamExt, TryFutureExt};
@@ -19,8 +19,10 @@ use crate::{
 
 /// The shared object that manages the instantiaion of WASI executables and
 /// communicating with them via the CGI protocol.
+///
+/// Every request gets a fresh instance of the (already compiled) module.
 #[derive(Clone, Debug)]
-pub(crate) struct Handler(Arc<SharedState>);
+pub struct Handler(Arc<SharedState>);
 
 impl Handler {
     pub(crate) fn new(state: SharedState) -> Self {
@@ -39,19 +41,20 @@ impl Handler {
 
         tracing::debug!("Creating the WebAssembly instance");
 
-        let mut builder = WasiEnvBuilder::new(&self.program_name);
+        let mut builder = WasiEnvBuilder::new(&self.0.program_name);
 
-        (self.setup_builder)(&mut builder)?;
+        (self.0.setup_builder)(&mut builder)?;
 
         // Note: We want to apply the CGI environment variables *after*
         // anything specified by WASI annotations so users get a chance to
         // override things like $DOCUMENT_ROOT and $SCRIPT_FILENAME.
         let mut request_specific_env = HashMap::new();
-        self.dialect
+        self.0
+            .dialect
             .prepare_environment_variables(parts, &mut request_specific_env);
         builder.add_envs(request_specific_env);
 
-        let rt = PluggableRuntime::new(Arc::clone(&self.task_manager));
+        let rt = PluggableRuntime::new(Arc::clone(&self.0.task_manager));
 
         let builder = builder
             .stdin(Box::new(req_body_receiver))
@@ -64,10 +67,10 @@ impl Handler {
             })
             .runtime(Arc::new(rt));
 
-        let module = self.module.clone();
+        let module = self.0.module.clone();
 
         tracing::debug!(
-            dialect=%self.dialect,
+            dialect=%self.0.dialect,
             "Calling into the WCGI executable",
         );
 
@@ -78,8 +81,8 @@ impl Handler {
             .map_err(Error::from)
             .and_then(|r| async { r.map_err(Error::from) });
 
-        let handle = self.task_manager.runtime().clone();
-        let callbacks = Arc::clone(&self.callbacks);
+        let handle = self.0.task_manager.runtime().clone();
+        let callbacks = Arc::clone(&self.0.callbacks);
 
         handle.spawn(
             async move {
@@ -88,7 +91,7 @@ impl Handler {
             .in_current_span(),
         );
 
-        self.task_manager.runtime().spawn(
+        self.0.task_manager.runtime().spawn(
             async move {
                 if let Err(e) =
                     drive_request_to_completion(&handle, done, body, req_body_sender).await
@@ -105,6 +108,7 @@ impl Handler {
         let mut res_body_receiver = tokio::io::BufReader::new(res_body_receiver);
 
         let parts = self
+            .0
             .dialect
             .extract_response_header(&mut res_body_receiver)
             .await?;
@@ -127,11 +131,14 @@ impl Handler {
     }
 }
 
-impl Deref for Handler {
-    type Target = Arc<SharedState>;
+impl Handler {
+    /// The name the guest sees as `argv[0]`.
+    pub fn program_name(&self) -> &str {
+        &self.0.program_name
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    pub(crate) fn task_manager(&self) -> &Arc<dyn VirtualTaskManager> {
+        &self.0.task_manager
     }
 }
 
@@ -1,5 +1,10 @@
+mod daemon;
 mod handler;
 mod runner;
 
-pub use self::runner::{Callbacks, Config, WcgiRunner};
+pub use self::{
+    daemon::{Daemon, DaemonConfig, HEALTH_PATH, METRICS_PATH},
+    handler::Handler,
+    runner::{Callbacks, Config, WcgiRunner},
+};
 pub use futures::future::AbortHandle;
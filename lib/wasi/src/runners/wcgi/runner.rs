@@ -46,18 +46,8 @@ impl WcgiRunner {
 
     #[tracing::instrument(skip(self, ctx))]
     fn run(&mut self, command_name: &str, ctx: &RunnerContext<'_>) -> Result<(), Error> {
-        let wasi: Wasi = ctx
-            .command()
-            .annotation("wasi")
-            .context("Unable to retrieve the WASI metadata")?
-            .unwrap_or_else(|| Wasi::new(command_name));
-
-        let module = self
-            .load_module(&wasi, ctx)
-            .context("Couldn't load the module")?;
-
-        let handler = self.create_handler(module, &wasi, ctx)?;
-        let task_manager = Arc::clone(&handler.task_manager);
+        let handler = self.handler(command_name, ctx)?;
+        let task_manager = Arc::clone(handler.task_manager());
         let callbacks = Arc::clone(&self.config.callbacks);
 
         let service = ServiceBuilder::new()
@@ -104,6 +94,22 @@ impl WcgiRunner {
     }
 }
 
+impl WcgiRunner {
+    fn handler(&mut self, command_name: &str, ctx: &RunnerContext<'_>) -> Result<Handler, Error> {
+        let wasi: Wasi = ctx
+            .command()
+            .annotation("wasi")
+            .context("Unable to retrieve the WASI metadata")?
+            .unwrap_or_else(|| Wasi::new(command_name));
+
+        let module = self
+            .load_module(&wasi, ctx)
+            .context("Couldn't load the module")?;
+
+        self.create_handler(module, &wasi, ctx)
+    }
+}
+
 impl WcgiRunner {
     pub fn new(program_name: impl Into<String>) -> Self {
         WcgiRunner {
@@ -126,6 +132,42 @@ impl WcgiRunner {
         self
     }
 
+    /// Compile one of the container's WCGI commands and get a [`Handler`]
+    /// that serves requests with it, without starting a HTTP server.
+    ///
+    /// This lets several commands share one server (see
+    /// [`Daemon`][crate::runners::wcgi::Daemon]).
+    pub fn prepare_handler(
+        &mut self,
+        command_name: &str,
+        container: &Container,
+    ) -> Result<Handler, Error> {
+        let command = container
+            .manifest()
+            .commands
+            .get(command_name)
+            .with_context(|| format!("No metadata found for the command, \"{command_name}\""))?;
+        anyhow::ensure!(
+            WcgiRunner::supports(command)?,
+            "The \"{command_name}\" command isn't a WCGI command"
+        );
+
+        let ctx = self.context(command, container);
+        self.handler(command_name, &ctx)
+    }
+
+    fn context<'a>(&self, command: &'a Command, container: &'a Container) -> RunnerContext<'a> {
+        let store = self.config.store.clone().unwrap_or_default();
+
+        RunnerContext {
+            container,
+            command,
+            engine: store.engine().clone(),
+            store,
+            compile: self.compile.clone(),
+        }
+    }
+
     fn load_module(&mut self, wasi: &Wasi, ctx: &RunnerContext<'_>) -> Result<Module, Error> {
         let atom_name = &wasi.atom;
         let atom = ctx
@@ -243,16 +285,7 @@ impl crate::runners::Runner for WcgiRunner {
         command: &Command,
         container: &Container,
     ) -> Result<Self::Output, Error> {
-        let store = self.config.store.clone().unwrap_or_default();
-
-        let ctx = RunnerContext {
-            container,
-            command,
-            engine: store.engine().clone(),
-            store,
-            compile: self.compile.clone(),
-        };
-
+        let ctx = self.context(command, container);
         WcgiRunner::run(self, command_name, &ctx)
     }
 }
@@ -274,6 +307,12 @@ impl Config {
         self
     }
 
+    /// Use a task manager that is shared with other runners.
+    pub fn shared_task_manager(&mut self, task_manager: Arc<dyn VirtualTaskManager>) -> &mut Self {
+        self.task_manager = Some(task_manager);
+        self
+    }
+
     pub fn addr(&mut self, addr: SocketAddr) -> &mut Self {
         self.addr = addr;
         self
@@ -314,6 +353,19 @@ impl Config {
         self
     }
 
+    /// Mark environment variables as secrets, so their values are redacted
+    /// from logs.
+    pub fn secrets<I, S>(&mut self, names: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.wasi
+            .secrets
+            .extend(names.into_iter().map(|name| name.into()));
+        self
+    }
+
     /// Forward all of the host's environment variables to the guest.
     pub fn forward_host_env(&mut self) -> &mut Self {
         self.wasi.forward_host_env = true;
@@ -203,3 +203,45 @@ impl WasiRuntime for PluggableRuntime {
         self.module_cache.clone()
     }
 }
+
+/// Runtime that uses its own networking implementation but otherwise
+/// behaves exactly like the runtime it wraps
+#[derive(Debug)]
+pub(crate) struct NetworkingOverrideRuntime {
+    pub inner: Arc<dyn WasiRuntime + Send + Sync>,
+    pub networking: DynVirtualNetworking,
+}
+
+impl WasiRuntime for NetworkingOverrideRuntime {
+    fn networking(&self) -> &DynVirtualNetworking {
+        &self.networking
+    }
+
+    fn task_manager(&self) -> &Arc<dyn VirtualTaskManager> {
+        self.inner.task_manager()
+    }
+
+    fn package_resolver(&self) -> Arc<dyn PackageResolver + Send + Sync> {
+        self.inner.package_resolver()
+    }
+
+    fn module_cache(&self) -> Arc<dyn ModuleCache + Send + Sync> {
+        self.inner.module_cache()
+    }
+
+    fn engine(&self) -> Option<wasmer::Engine> {
+        self.inner.engine()
+    }
+
+    fn new_store(&self) -> wasmer::Store {
+        self.inner.new_store()
+    }
+
+    fn http_client(&self) -> Option<&DynHttpClient> {
+        self.inner.http_client()
+    }
+
+    fn tty(&self) -> Option<&(dyn TtyBridge + Send + Sync)> {
+        self.inner.tty()
+    }
+}
@@ -61,18 +61,31 @@ impl<R> PackageResolver for InMemoryCache<R>
 where
     R: PackageResolver + Send + Sync,
 {
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(
+            package.name = ident.full_name.as_str(),
+            package.version = %ident.version,
+            cache.hit = tracing::field::Empty,
+        ),
+        err
+    )]
     async fn resolve_package(
         &self,
         ident: &WebcIdentifier,
         client: &(dyn HttpClient + Send + Sync),
     ) -> Result<BinaryPackage, ResolverError> {
+        let span = tracing::Span::current();
         if let Some(cached) = self.lookup(&ident.full_name, &ident.version) {
             // Cache hit!
+            span.record("cache.hit", true);
             tracing::debug!(package=?ident, "The resolved package was already cached");
             return Ok(cached);
         }
 
         // the slow path
+        span.record("cache.hit", false);
         let pkg = self.resolver.resolve_package(ident, client).await?;
 
         tracing::debug!(
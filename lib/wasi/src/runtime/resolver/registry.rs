@@ -79,6 +79,17 @@ impl RegistryResolver {
 
 #[async_trait::async_trait]
 impl PackageResolver for RegistryResolver {
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(
+            otel.kind = "client",
+            package.name = pkg.full_name.as_str(),
+            package.version = %pkg.version,
+            url.full = %self.registry_endpoint,
+        ),
+        err
+    )]
     async fn resolve_package(
         &self,
         pkg: &WebcIdentifier,
@@ -0,0 +1,733 @@
+//! Snapshots of a running process that can be resumed later, possibly by
+//! another host process.
+//!
+//! A snapshot is taken when the process enters one of the syscalls that
+//! check for it (reads, writes, polling and accepting connections) after
+//! the [`SnapshotTrigger`] fired. The stack is unwound with asyncify, the
+//! same way `proc_fork` does it, and the linear memory, the globals, the
+//! open files and the writable parts of the virtual filesystem are written
+//! out before the process exits.
+//!
+//! Resuming instantiates the same module, puts all of that back and
+//! rewinds the stack into the syscall, which then runs as if nothing had
+//! happened. Only modules that export the asyncify functions (e.g. built
+//! with `wasm-opt --asyncify`) can be snapshotted, see
+//! [`supports_snapshots`]. Sockets, pipes and the other threads of the
+//! process aren't captured.
+use std::{
+    fmt,
+    fs::File,
+    io::{BufReader, BufWriter},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use virtual_fs::{AsyncReadExt, AsyncWriteExt, FileSystem, FsError};
+use wasmer::{
+    AsStoreMut, FunctionEnvMut, Memory32, Memory64, MemorySize, Module, OnCalledAction, Pages,
+    WASM_PAGE_SIZE,
+};
+use wasmer_wasix_types::wasi::{Errno, Fdflags, Rights, Snapshot0Clockid};
+
+use crate::{
+    fs::{Kind, VIRTUAL_ROOT_FD},
+    runtime::task_manager::VirtualTaskManagerExt,
+    storage::StorageOptions,
+    syscalls::{platform_clock_time_get, rewind, unwind},
+    WasiEnv, WasiError, WasiFunctionEnv,
+};
+
+/// Identifies snapshot files
+const MAGIC: &[u8; 8] = b"wasmersn";
+
+/// Bumped whenever the layout of [`ProcessSnapshot`] changes
+const FORMAT_VERSION: u32 = 2;
+
+/// The exports that asyncify adds to a module
+const ASYNCIFY_EXPORTS: &[&str] = &[
+    "asyncify_start_unwind",
+    "asyncify_stop_unwind",
+    "asyncify_start_rewind",
+    "asyncify_stop_rewind",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("unable to read or write the snapshot")]
+    Io(#[from] std::io::Error),
+    #[error("the snapshot is corrupt: {0}")]
+    Corrupt(String),
+    #[error("the snapshot was written by an incompatible version of wasmer (format {0})")]
+    UnsupportedVersion(u32),
+    #[error("the snapshot was taken from a different module")]
+    ModuleMismatch,
+    #[error("unable to capture the process: {0}")]
+    Capture(String),
+    #[error("unable to restore the process: {0}")]
+    Restore(String),
+}
+
+/// Decides when a process should snapshot itself.
+#[derive(Clone)]
+pub struct SnapshotTrigger(Arc<dyn Fn() -> bool + Send + Sync>);
+
+impl SnapshotTrigger {
+    /// Takes a snapshot once `requested` returns true. It is polled every
+    /// time the process enters a syscall that supports snapshots, so it
+    /// must be cheap.
+    pub fn new(requested: impl Fn() -> bool + Send + Sync + 'static) -> Self {
+        SnapshotTrigger(Arc::new(requested))
+    }
+
+    /// Takes a snapshot once `flag` is set
+    pub fn from_flag(flag: Arc<AtomicBool>) -> Self {
+        SnapshotTrigger::new(move || flag.load(Ordering::SeqCst))
+    }
+
+    pub fn is_requested(&self) -> bool {
+        (self.0)()
+    }
+}
+
+impl fmt::Debug for SnapshotTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotTrigger").finish_non_exhaustive()
+    }
+}
+
+/// Where and when a process snapshots itself.
+#[derive(Debug, Clone)]
+pub struct SnapshotConfig {
+    /// The file the snapshot is written to
+    pub path: PathBuf,
+    /// The directories of the virtual filesystem whose contents are part of
+    /// the snapshot. These should be the directories that only exist in
+    /// memory, host directories survive the process anyway.
+    pub fs_dirs: Vec<PathBuf>,
+    pub trigger: SnapshotTrigger,
+    /// How the snapshot is compressed and encrypted
+    pub storage: StorageOptions,
+}
+
+impl SnapshotConfig {
+    pub fn new(path: impl Into<PathBuf>, trigger: SnapshotTrigger) -> Self {
+        SnapshotConfig {
+            path: path.into(),
+            fs_dirs: Vec::new(),
+            trigger,
+            storage: StorageOptions::default(),
+        }
+    }
+
+    /// Includes the contents of a directory of the virtual filesystem
+    pub fn with_fs_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.fs_dirs.push(dir.into());
+        self
+    }
+
+    pub fn with_storage(mut self, storage: StorageOptions) -> Self {
+        self.storage = storage;
+        self
+    }
+}
+
+/// Whether a module exports the asyncify functions needed to unwind and
+/// rewind its stack.
+pub fn supports_snapshots(module: &Module) -> bool {
+    ASYNCIFY_EXPORTS
+        .iter()
+        .all(|name| module.exports().functions().any(|f| f.name() == *name))
+}
+
+/// Everything needed to resume a process where it left off.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProcessSnapshot {
+    /// See [`fingerprint`]
+    pub(crate) module: String,
+    pub(crate) memory64: bool,
+    pub(crate) memory: Vec<u8>,
+    pub(crate) memory_stack: Vec<u8>,
+    pub(crate) rewind_stack: Vec<u8>,
+    /// The globals, serialized by [`crate::utils::store::capture_snapshot`]
+    pub(crate) store_data: Vec<u8>,
+    pub(crate) current_dir: String,
+    pub(crate) fds: Vec<FdSnapshot>,
+    pub(crate) files: Vec<FileSnapshot>,
+    /// The monotonic clock of the process, which carries on from here when
+    /// it is resumed
+    pub(crate) monotonic_clock: i64,
+}
+
+/// A file descriptor that was opened by the process (rather than preopened
+/// by the runtime).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct FdSnapshot {
+    pub(crate) fd: u32,
+    pub(crate) path: PathBuf,
+    pub(crate) is_dir: bool,
+    pub(crate) rights: u64,
+    pub(crate) rights_inheriting: u64,
+    pub(crate) flags: u16,
+    pub(crate) open_flags: u16,
+    pub(crate) offset: u64,
+}
+
+/// A directory (without contents) or a file of the virtual filesystem.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct FileSnapshot {
+    pub(crate) path: PathBuf,
+    pub(crate) contents: Option<Vec<u8>>,
+}
+
+impl fmt::Debug for ProcessSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProcessSnapshot")
+            .field("module", &self.module)
+            .field("memory64", &self.memory64)
+            .field("memory_len", &self.memory.len())
+            .field("memory_stack_len", &self.memory_stack.len())
+            .field("rewind_stack_len", &self.rewind_stack.len())
+            .field("current_dir", &self.current_dir)
+            .field("fds", &self.fds)
+            .field("files", &self.files.len())
+            .field("monotonic_clock", &self.monotonic_clock)
+            .finish()
+    }
+}
+
+impl ProcessSnapshot {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SnapshotError> {
+        let mut bytes = Vec::new();
+        self.encode(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        ProcessSnapshot::decode(bytes)
+    }
+
+    fn encode(&self, mut writer: impl std::io::Write) -> Result<(), SnapshotError> {
+        // The async traits of virtual-fs are in scope for the rest of the file
+        use std::io::Write;
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        bincode::serialize_into(writer, self).map_err(|e| SnapshotError::Capture(e.to_string()))
+    }
+
+    fn decode(mut reader: impl std::io::Read) -> Result<Self, SnapshotError> {
+        use std::io::Read;
+
+        let mut header = [0; MAGIC.len() + 4];
+        match reader.read_exact(&mut header) {
+            Ok(()) if &header[..MAGIC.len()] == MAGIC => {}
+            Err(e) if e.kind() != std::io::ErrorKind::UnexpectedEof => return Err(e.into()),
+            _ => return Err(SnapshotError::Corrupt("not a snapshot file".to_string())),
+        }
+        let mut version = [0; 4];
+        version.copy_from_slice(&header[MAGIC.len()..]);
+        let version = u32::from_le_bytes(version);
+        if version != FORMAT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        bincode::deserialize_from(reader).map_err(|e| SnapshotError::Corrupt(e.to_string()))
+    }
+
+    pub fn read_from(path: &Path) -> Result<Self, SnapshotError> {
+        ProcessSnapshot::read_with(path, &StorageOptions::default())
+    }
+
+    /// Reads a snapshot, decompressing and decrypting it as needed with
+    /// the key of `storage`.
+    pub fn read_with(path: &Path, storage: &StorageOptions) -> Result<Self, SnapshotError> {
+        let file = BufReader::new(File::open(path)?);
+        ProcessSnapshot::decode(storage.reader(file)?)
+    }
+
+    /// Writes the snapshot to a temporary file first, so that a previous
+    /// snapshot at `path` is only replaced by a complete one.
+    pub fn write_to(&self, path: &Path) -> Result<(), SnapshotError> {
+        self.write_with(path, &StorageOptions::default())
+    }
+
+    /// Like [`ProcessSnapshot::write_to`], compressing and encrypting the
+    /// snapshot as `storage` says while it is written out.
+    pub fn write_with(&self, path: &Path, storage: &StorageOptions) -> Result<(), SnapshotError> {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+        {
+            let mut writer = storage.writer(BufWriter::new(temp.as_file_mut()))?;
+            self.encode(&mut writer)?;
+            writer.finish()?;
+        }
+        temp.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+}
+
+/// Identifies a module by its imports and exports, which is enough to tell
+/// apart the modules a snapshot could accidentally be resumed with.
+pub(crate) fn fingerprint(module: &Module) -> String {
+    let mut hasher = Sha256::new();
+    for import in module.imports() {
+        hasher.update(format!(
+            "{}.{}:{:?};",
+            import.module(),
+            import.name(),
+            import.ty()
+        ));
+    }
+    for export in module.exports() {
+        hasher.update(format!("{}:{:?};", export.name(), export.ty()));
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Unwinds the stack out of the current syscall, then writes a snapshot of
+/// the process and exits. Use [`maybe_snapshot!`] rather than calling this
+/// directly.
+#[must_use = "you must return the result immediately so the stack can unwind"]
+pub(crate) fn unwind_and_save<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+) -> Result<Errno, WasiError> {
+    let config = match ctx.data().snapshot.clone() {
+        Some(config) => config,
+        None => return Ok(Errno::Success),
+    };
+
+    tracing::debug!(path = %config.path.display(), "Unwinding to take a snapshot");
+    unwind::<M, _>(ctx, move |mut ctx, memory_stack, rewind_stack| {
+        let result = capture::<M>(
+            &mut ctx,
+            memory_stack.freeze(),
+            rewind_stack.freeze(),
+            &config.fs_dirs,
+        )
+        .and_then(|snapshot| snapshot.write_with(&config.path, &config.storage));
+
+        let exit_code = match result {
+            Ok(()) => {
+                tracing::info!(path = %config.path.display(), "Saved a snapshot of the process");
+                Errno::Success
+            }
+            Err(e) => {
+                tracing::error!(
+                    path = %config.path.display(),
+                    error = &e as &dyn std::error::Error,
+                    "Unable to save a snapshot of the process",
+                );
+                Errno::Io
+            }
+        };
+        OnCalledAction::Trap(Box::new(WasiError::Exit(exit_code.into())))
+    })
+}
+
+/// Captures the process after its stack was unwound, including the contents
+/// of `fs_dirs`.
+pub(crate) fn capture<M: MemorySize>(
+    ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+    memory_stack: Bytes,
+    rewind_stack: Bytes,
+    fs_dirs: &[PathBuf],
+) -> Result<ProcessSnapshot, SnapshotError> {
+    let store_data = crate::utils::store::capture_snapshot(&mut ctx.as_store_mut())
+        .serialize()
+        .map_err(|e| SnapshotError::Capture(e.to_string()))?;
+
+    let env = ctx.data();
+    let memory = env
+        .memory_view(&ctx)
+        .copy_to_vec()
+        .map_err(|e| SnapshotError::Capture(e.to_string()))?;
+    let module = fingerprint(env.inner().instance.module());
+
+    let fs = &env.state.fs;
+    let current_dir = fs.current_dir.lock().unwrap().clone();
+    let fds = {
+        let preopens = fs.preopen_fds.read().unwrap().clone();
+        let fd_map = fs.fd_map.read().unwrap();
+        let mut fds = Vec::new();
+        for (fd, entry) in fd_map.iter() {
+            if entry.is_stdio || preopens.contains(fd) {
+                continue;
+            }
+            let guard = entry.inode.read();
+            let (path, is_dir) = match guard.deref() {
+                Kind::File { path, fd: None, .. } => (path.clone(), false),
+                Kind::Dir { path, .. } => (path.clone(), true),
+                _ => {
+                    tracing::debug!(fd, "Leaving a file descriptor out of the snapshot");
+                    continue;
+                }
+            };
+            fds.push(FdSnapshot {
+                fd: *fd,
+                path,
+                is_dir,
+                rights: entry.rights.bits(),
+                rights_inheriting: entry.rights_inheriting.bits(),
+                flags: entry.flags.bits(),
+                open_flags: entry.open_flags,
+                offset: entry.offset.load(Ordering::SeqCst),
+            });
+        }
+        fds.sort_by_key(|fd| fd.fd);
+        fds
+    };
+
+    let root = &fs.root_fs;
+    let mut files = Vec::new();
+    env.tasks().block_on(async {
+        for dir in fs_dirs.iter() {
+            capture_dir(root, dir, &mut files).await?;
+        }
+        Ok::<_, SnapshotError>(())
+    })?;
+
+    Ok(ProcessSnapshot {
+        module,
+        memory64: std::mem::size_of::<M::Offset>() == 8,
+        memory,
+        memory_stack: memory_stack.to_vec(),
+        rewind_stack: rewind_stack.to_vec(),
+        store_data,
+        current_dir,
+        fds,
+        files,
+        monotonic_clock: monotonic_clock(env),
+    })
+}
+
+/// The monotonic clock as the process sees it.
+pub(crate) fn monotonic_clock(env: &WasiEnv) -> i64 {
+    let clock = Snapshot0Clockid::Monotonic;
+    let now = platform_clock_time_get(clock, 1).unwrap_or_default();
+    let offset = env.state.clock_offset.lock().unwrap();
+    now + offset.get(&clock).copied().unwrap_or_default()
+}
+
+/// Makes the monotonic clock of the process carry on from `clock`, which
+/// keeps the deadlines it computed from it meaningful on another host.
+fn set_monotonic_clock(env: &WasiEnv, clock: i64) {
+    let id = Snapshot0Clockid::Monotonic;
+    let now = platform_clock_time_get(id, 1).unwrap_or_default();
+    env.state
+        .clock_offset
+        .lock()
+        .unwrap()
+        .insert(id, clock - now);
+}
+
+/// Captures a directory and everything below it, parents before their
+/// children
+async fn capture_dir(
+    fs: &dyn FileSystem,
+    dir: &Path,
+    files: &mut Vec<FileSnapshot>,
+) -> Result<(), SnapshotError> {
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = match fs.read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(FsError::EntryNotFound) => continue,
+            Err(e) => return Err(SnapshotError::Capture(format!("{}: {e}", dir.display()))),
+        };
+        files.push(FileSnapshot {
+            path: dir,
+            contents: None,
+        });
+
+        let mut entries: Vec<_> = entries.filter_map(|e| e.ok()).collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut subdirs = Vec::new();
+        for entry in entries {
+            let file_type = match entry.file_type() {
+                Ok(t) => t,
+                Err(_) => continue,
+            };
+            if file_type.is_dir() {
+                subdirs.push(entry.path);
+            } else if file_type.is_file() {
+                let mut contents = Vec::new();
+                let read = async {
+                    let mut file = fs.new_open_options().read(true).open(&entry.path)?;
+                    file.read_to_end(&mut contents).await?;
+                    Ok::<_, std::io::Error>(())
+                };
+                read.await.map_err(|e| {
+                    SnapshotError::Capture(format!("{}: {e}", entry.path.display()))
+                })?;
+                files.push(FileSnapshot {
+                    path: entry.path,
+                    contents: Some(contents),
+                });
+            }
+        }
+        pending.extend(subdirs.into_iter().rev());
+    }
+
+    Ok(())
+}
+
+/// Puts the state of a snapshot back into a freshly instantiated process,
+/// and rewinds its stack so that calling its entrypoint resumes the
+/// syscall the snapshot was taken in.
+pub(crate) fn restore(
+    func_env: &WasiFunctionEnv,
+    store: &mut impl AsStoreMut,
+    snapshot: &ProcessSnapshot,
+) -> Result<(), SnapshotError> {
+    let env = func_env.data(&*store);
+    if fingerprint(env.inner().instance.module()) != snapshot.module {
+        return Err(SnapshotError::ModuleMismatch);
+    }
+
+    // Linear memory
+    let memory = env.inner().memory.clone();
+    let current = memory.view(&*store).data_size();
+    let wanted = snapshot.memory.len() as u64;
+    if wanted > current {
+        let pages = (wanted - current + WASM_PAGE_SIZE as u64 - 1) / WASM_PAGE_SIZE as u64;
+        memory
+            .grow(store, Pages(pages as u32))
+            .map_err(|e| SnapshotError::Restore(e.to_string()))?;
+    }
+    memory
+        .view(&*store)
+        .write(0, &snapshot.memory)
+        .map_err(|e| SnapshotError::Restore(e.to_string()))?;
+
+    // Filesystem and file descriptors
+    let env = func_env.data(&*store);
+    env.tasks()
+        .block_on(restore_files(&env.state.fs.root_fs, &snapshot.files))?;
+    restore_fds(env, snapshot)?;
+    set_monotonic_clock(env, snapshot.monotonic_clock);
+
+    // Globals and the stack
+    let ctx = func_env.env.clone().into_mut(store);
+    let memory_stack = Bytes::from(snapshot.memory_stack.clone());
+    let rewind_stack = Bytes::from(snapshot.rewind_stack.clone());
+    let store_data = Bytes::from(snapshot.store_data.clone());
+    let errno = if snapshot.memory64 {
+        rewind::<Memory64>(ctx, memory_stack, rewind_stack, store_data)
+    } else {
+        rewind::<Memory32>(ctx, memory_stack, rewind_stack, store_data)
+    };
+    match errno {
+        Errno::Success => Ok(()),
+        err => Err(SnapshotError::Restore(format!(
+            "unable to rewind the stack ({err})"
+        ))),
+    }
+}
+
+async fn restore_files(fs: &dyn FileSystem, files: &[FileSnapshot]) -> Result<(), SnapshotError> {
+    // Parents come before their children, see `capture_dir`
+    for file in files {
+        let result = match &file.contents {
+            None => match fs.create_dir(&file.path) {
+                Ok(()) | Err(FsError::AlreadyExists) => Ok(()),
+                Err(e) => Err(e.into()),
+            },
+            Some(contents) => {
+                let write = async {
+                    let mut f = fs
+                        .new_open_options()
+                        .write(true)
+                        .create(true)
+                        .truncate(true)
+                        .open(&file.path)?;
+                    f.write_all(contents).await?;
+                    f.flush().await
+                };
+                write.await
+            }
+        };
+        result.map_err(|e: std::io::Error| {
+            SnapshotError::Restore(format!("{}: {e}", file.path.display()))
+        })?;
+    }
+    Ok(())
+}
+
+fn restore_fds(env: &WasiEnv, snapshot: &ProcessSnapshot) -> Result<(), SnapshotError> {
+    let state = &env.state;
+    let fs = &state.fs;
+    fs.set_current_dir(&snapshot.current_dir);
+
+    let root = fs
+        .get_fd_inode(VIRTUAL_ROOT_FD)
+        .map_err(|e| SnapshotError::Restore(format!("no root directory ({e})")))?;
+
+    for fd in snapshot.fds.iter() {
+        let name = fd
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let kind = if fd.is_dir {
+            Kind::Dir {
+                parent: root.downgrade(),
+                path: fd.path.clone(),
+                entries: Default::default(),
+            }
+        } else {
+            let handle = state
+                .fs_new_open_options()
+                .read(fd.open_flags & crate::fs::Fd::READ != 0)
+                .write(fd.open_flags & crate::fs::Fd::WRITE != 0)
+                .append(fd.open_flags & crate::fs::Fd::APPEND != 0)
+                .open(&fd.path)
+                .map_err(|e| SnapshotError::Restore(format!("{}: {e}", fd.path.display())))?;
+            Kind::File {
+                handle: Some(Arc::new(std::sync::RwLock::new(handle))),
+                path: fd.path.clone(),
+                fd: None,
+            }
+        };
+
+        let inode = fs
+            .create_inode(&state.inodes, kind, false, name)
+            .map_err(|e| SnapshotError::Restore(format!("{}: {e}", fd.path.display())))?;
+        fs.create_fd_ext(
+            Rights::from_bits_truncate(fd.rights),
+            Rights::from_bits_truncate(fd.rights_inheriting),
+            Fdflags::from_bits_truncate(fd.flags),
+            fd.open_flags,
+            inode,
+            fd.fd,
+        )
+        .map_err(|e| SnapshotError::Restore(format!("fd {}: {e}", fd.fd)))?;
+        if let Ok(entry) = fs.get_fd(fd.fd) {
+            entry.offset.store(fd.offset, Ordering::SeqCst);
+        }
+        fs.next_fd.fetch_max(fd.fd + 1, Ordering::SeqCst);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> ProcessSnapshot {
+        ProcessSnapshot {
+            module: "abc".to_string(),
+            memory64: false,
+            memory: vec![1, 2, 3],
+            memory_stack: vec![4],
+            rewind_stack: vec![5, 6],
+            store_data: vec![7],
+            current_dir: "/tmp".to_string(),
+            fds: vec![FdSnapshot {
+                fd: 5,
+                path: PathBuf::from("/tmp/log.txt"),
+                is_dir: false,
+                rights: 3,
+                rights_inheriting: 3,
+                flags: 1,
+                open_flags: 2,
+                offset: 42,
+            }],
+            files: vec![
+                FileSnapshot {
+                    path: PathBuf::from("/tmp"),
+                    contents: None,
+                },
+                FileSnapshot {
+                    path: PathBuf::from("/tmp/log.txt"),
+                    contents: Some(b"hello".to_vec()),
+                },
+            ],
+            monotonic_clock: 1_000,
+        }
+    }
+
+    #[test]
+    fn snapshots_roundtrip_through_a_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("app.snapshot");
+
+        snapshot().write_to(&path).unwrap();
+        let restored = ProcessSnapshot::read_from(&path).unwrap();
+
+        assert_eq!(restored.memory, [1, 2, 3]);
+        assert_eq!(restored.rewind_stack, [5, 6]);
+        assert_eq!(restored.fds[0].offset, 42);
+        assert_eq!(restored.files.len(), 2);
+    }
+
+    #[test]
+    fn foreign_files_are_rejected() {
+        let mut bytes = snapshot().to_bytes().unwrap();
+        assert!(matches!(
+            ProcessSnapshot::from_bytes(b"\0asm\x01\0\0\0"),
+            Err(SnapshotError::Corrupt(_))
+        ));
+
+        bytes[MAGIC.len()] = 99;
+        assert!(matches!(
+            ProcessSnapshot::from_bytes(&bytes),
+            Err(SnapshotError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn filesystem_contents_are_captured_and_restored() {
+        let source = virtual_fs::mem_fs::FileSystem::default();
+        source.create_dir(Path::new("/tmp")).unwrap();
+        source.create_dir(Path::new("/tmp/logs")).unwrap();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut f = source
+                .new_open_options()
+                .write(true)
+                .create(true)
+                .open("/tmp/logs/out.txt")
+                .unwrap();
+            f.write_all(b"hello").await.unwrap();
+        });
+
+        let mut files = Vec::new();
+        rt.block_on(capture_dir(&source, Path::new("/tmp"), &mut files))
+            .unwrap();
+        let paths: Vec<_> = files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("/tmp"),
+                PathBuf::from("/tmp/logs"),
+                PathBuf::from("/tmp/logs/out.txt")
+            ]
+        );
+
+        let target = virtual_fs::mem_fs::FileSystem::default();
+        rt.block_on(restore_files(&target, &files)).unwrap();
+        let mut contents = Vec::new();
+        rt.block_on(async {
+            let mut f = target
+                .new_open_options()
+                .read(true)
+                .open("/tmp/logs/out.txt")
+                .unwrap();
+            f.read_to_end(&mut contents).await.unwrap();
+        });
+        assert_eq!(contents, b"hello");
+    }
+}
@@ -1,7 +1,7 @@
 //! Builder system for configuring a [`WasiState`] and creating it.
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -15,10 +15,18 @@ use wasmer_wasix_types::wasi::Errno;
 #[cfg(feature = "sys")]
 use crate::PluggableRuntime;
 use crate::{
+    audit::AuditLog,
     bin_factory::BinFactory,
     capabilities::Capabilities,
     fs::{WasiFs, WasiFsRoot, WasiInodes},
+    journal::{Journal, Replay},
+    metrics::MetricsSink,
+    migration::{Migration, MigrationConfig},
+    net::{limits::SocketLimits, NetworkMode},
     os::task::control_plane::{ControlPlaneConfig, ControlPlaneError, WasiControlPlane},
+    parking::Parking,
+    runtime::NetworkingOverrideRuntime,
+    snapshot::{ProcessSnapshot, SnapshotConfig},
     state::WasiState,
     syscalls::types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO},
     WasiEnv, WasiFunctionEnv, WasiRuntime, WasiRuntimeError,
@@ -48,6 +56,9 @@ pub struct WasiEnvBuilder {
     pub(super) args: Vec<String>,
     /// Environment variables.
     pub(super) envs: Vec<(String, Vec<u8>)>,
+    /// The names of the environment variables whose values must not be
+    /// logged.
+    pub(super) secrets: HashSet<String>,
     /// Pre-opened directories that will be accessible from WASI.
     pub(super) preopens: Vec<PreopenedDir>,
     /// Pre-opened virtual directories that will be accessible from WASI.
@@ -60,6 +71,15 @@ pub struct WasiEnvBuilder {
     pub(super) stdin: Option<Box<dyn VirtualFile + Send + Sync + 'static>>,
     pub(super) fs: Option<WasiFsRoot>,
     pub(super) runtime: Option<Arc<dyn crate::WasiRuntime + Send + Sync + 'static>>,
+    pub(super) network_mode: Option<NetworkMode>,
+    pub(super) metrics: Option<Arc<dyn MetricsSink>>,
+    pub(super) audit_log: Option<Arc<dyn AuditLog>>,
+    pub(super) snapshot: Option<SnapshotConfig>,
+    pub(super) resume: Option<ProcessSnapshot>,
+    pub(super) journal: Option<Arc<Journal>>,
+    pub(super) migration: Option<MigrationConfig>,
+    pub(super) replay: Option<Arc<Replay>>,
+    pub(super) parking: Option<Arc<Parking>>,
 
     /// List of webc dependencies to be injected.
     pub(super) uses: Vec<String>,
@@ -75,7 +95,7 @@ impl std::fmt::Debug for WasiEnvBuilder {
         // TODO: update this when stable
         f.debug_struct("WasiEnvBuilder")
             .field("args", &self.args)
-            .field("envs", &self.envs)
+            .field("envs", &RedactedEnvs(&self.envs, &self.secrets))
             .field("preopens", &self.preopens)
             .field("uses", &self.uses)
             .field("setup_fs_fn exists", &self.setup_fs_fn.is_some())
@@ -83,6 +103,33 @@ impl std::fmt::Debug for WasiEnvBuilder {
             .field("stderr_override exists", &self.stderr.is_some())
             .field("stdin_override exists", &self.stdin.is_some())
             .field("runtime_override_exists", &self.runtime.is_some())
+            .field("network_mode", &self.network_mode)
+            .field("metrics", &self.metrics)
+            .field("audit_log", &self.audit_log)
+            .field("snapshot", &self.snapshot)
+            .field("resume", &self.resume)
+            .field("journal", &self.journal)
+            .field("migration", &self.migration)
+            .field("replay", &self.replay)
+            .field("parking", &self.parking)
+            .finish()
+    }
+}
+
+/// Formats environment variables with the value of secrets hidden.
+struct RedactedEnvs<'a>(&'a [(String, Vec<u8>)], &'a HashSet<String>);
+
+impl std::fmt::Debug for RedactedEnvs<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let RedactedEnvs(envs, secrets) = self;
+        f.debug_list()
+            .entries(envs.iter().map(|(key, value)| {
+                if secrets.contains(key) {
+                    format!("{key}=<redacted>")
+                } else {
+                    format!("{key}={}", String::from_utf8_lossy(value))
+                }
+            }))
             .finish()
     }
 }
@@ -204,6 +251,45 @@ impl WasiEnvBuilder {
         &self.envs
     }
 
+    /// Mark an environment variable as a secret, so that its value is
+    /// redacted whenever the environment is logged.
+    ///
+    /// The guest still sees the real value.
+    pub fn secret(mut self, name: impl Into<String>) -> Self {
+        self.add_secret(name);
+        self
+    }
+
+    /// Mark an environment variable as a secret, so that its value is
+    /// redacted whenever the environment is logged.
+    pub fn add_secret(&mut self, name: impl Into<String>) {
+        self.secrets.insert(name.into());
+    }
+
+    /// Mark multiple environment variables as secrets.
+    pub fn secrets<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.add_secrets(names);
+        self
+    }
+
+    /// Mark multiple environment variables as secrets.
+    pub fn add_secrets<I, S>(&mut self, names: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.secrets.extend(names.into_iter().map(Into::into));
+    }
+
+    /// Get the names of the environment variables marked as secrets.
+    pub fn get_secrets(&self) -> &HashSet<String> {
+        &self.secrets
+    }
+
     /// Get a mutable reference to the configured environment variables.
     pub fn get_env_mut(&mut self) -> &mut Vec<(String, Vec<u8>)> {
         &mut self.envs
@@ -546,6 +632,112 @@ impl WasiEnvBuilder {
         self.runtime = Some(runtime);
     }
 
+    /// Sets the network isolation of the instance, overriding the
+    /// networking of the runtime
+    pub fn network_mode(mut self, mode: NetworkMode) -> Self {
+        self.set_network_mode(mode);
+        self
+    }
+
+    /// Sets the network isolation of the instance, overriding the
+    /// networking of the runtime
+    pub fn set_network_mode(&mut self, mode: NetworkMode) {
+        self.network_mode = Some(mode);
+    }
+
+    /// Sets the sink that receives the metrics of the instance
+    pub fn metrics(mut self, sink: Arc<dyn MetricsSink>) -> Self {
+        self.set_metrics(sink);
+        self
+    }
+
+    /// Sets the sink that receives the metrics of the instance
+    pub fn set_metrics(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.metrics = Some(sink);
+    }
+
+    /// Sets the log that records the security-relevant actions of the instance
+    pub fn audit_log(mut self, log: Arc<dyn AuditLog>) -> Self {
+        self.set_audit_log(log);
+        self
+    }
+
+    /// Sets the log that records the security-relevant actions of the instance
+    pub fn set_audit_log(&mut self, log: Arc<dyn AuditLog>) {
+        self.audit_log = Some(log);
+    }
+
+    /// Makes the process snapshot itself when the trigger of `config` fires,
+    /// see [`crate::snapshot`]
+    pub fn snapshot(mut self, config: SnapshotConfig) -> Self {
+        self.set_snapshot(config);
+        self
+    }
+
+    /// Makes the process snapshot itself when the trigger of `config` fires,
+    /// see [`crate::snapshot`]
+    pub fn set_snapshot(&mut self, config: SnapshotConfig) {
+        self.snapshot = Some(config);
+    }
+
+    /// Resumes the process from a snapshot instead of starting it afresh.
+    ///
+    /// The module must be the one the snapshot was taken from. Once
+    /// instantiated, calling `_start` picks up where the process left off.
+    pub fn resume_from(mut self, snapshot: ProcessSnapshot) -> Self {
+        self.set_resume_from(snapshot);
+        self
+    }
+
+    /// Resumes the process from a snapshot instead of starting it afresh.
+    pub fn set_resume_from(&mut self, snapshot: ProcessSnapshot) {
+        self.resume = Some(snapshot);
+    }
+
+    /// Records the state changes of the process to a journal, see
+    /// [`crate::journal`]
+    pub fn journal(mut self, journal: Arc<Journal>) -> Self {
+        self.set_journal(journal);
+        self
+    }
+
+    /// Records the state changes of the process to a journal, see
+    /// [`crate::journal`]
+    pub fn set_journal(&mut self, journal: Arc<Journal>) {
+        self.journal = Some(journal);
+    }
+
+    /// Makes the process migrate itself to another host when the trigger of
+    /// `config` fires, see [`crate::migration`]
+    pub fn migration(mut self, config: MigrationConfig) -> Self {
+        self.set_migration(config);
+        self
+    }
+
+    /// Makes the process migrate itself to another host when the trigger of
+    /// `config` fires, see [`crate::migration`]
+    pub fn set_migration(&mut self, config: MigrationConfig) {
+        self.migration = Some(config);
+    }
+
+    /// Replays a journaled run instead of letting the process read clocks,
+    /// random bytes and file descriptors, see [`Replay`]
+    pub fn replay(mut self, replay: Arc<Replay>) -> Self {
+        self.set_replay(replay);
+        self
+    }
+
+    /// Replays a journaled run instead of letting the process read clocks,
+    /// random bytes and file descriptors, see [`Replay`]
+    pub fn set_replay(&mut self, replay: Arc<Replay>) {
+        self.replay = Some(replay);
+    }
+
+    /// Lets a [`Supervisor`](crate::parking::Supervisor) park the process
+    pub(crate) fn set_parking(&mut self, parking: Arc<Parking>) {
+        self.parking = Some(parking);
+    }
+
     pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
         self.set_capabilities(capabilities);
         self
@@ -705,6 +897,14 @@ impl WasiEnvBuilder {
             }
         });
 
+        let runtime: Arc<dyn WasiRuntime + Send + Sync> = match self.network_mode {
+            Some(mode) => Arc::new(NetworkingOverrideRuntime {
+                networking: mode.networking(runtime.networking().clone()),
+                inner: runtime,
+            }),
+            None => runtime,
+        };
+
         let uses = self.uses;
         let map_commands = self.map_commands;
 
@@ -714,6 +914,13 @@ impl WasiEnvBuilder {
 
         let plane_config = ControlPlaneConfig {
             max_task_count: capabilities.threading.max_threads,
+            socket_limits: SocketLimits {
+                max_sockets: capabilities.networking.max_sockets,
+                max_pending_connections: capabilities.networking.max_pending_connections,
+                max_connections_per_window: capabilities.networking.max_connections_per_window,
+            },
+            metrics: self.metrics,
+            audit_log: self.audit_log,
         };
         let control_plane = WasiControlPlane::new(plane_config);
 
@@ -729,6 +936,14 @@ impl WasiEnvBuilder {
             process: None,
             thread: None,
             call_initialize: true,
+            snapshot: self.snapshot.map(Arc::new),
+            resume: self.resume.map(Arc::new),
+            journal: self.journal,
+            migration: self
+                .migration
+                .map(|config| Arc::new(Migration::new(config))),
+            replay: self.replay,
+            parking: self.parking,
         };
 
         Ok(init)
@@ -783,12 +998,20 @@ impl WasiEnvBuilder {
         module: Module,
         store: &mut impl AsStoreMut,
     ) -> Result<(), WasiRuntimeError> {
-        let (instance, env) = self.instantiate(module, store)?;
+        let (instance, env) = tracing::debug_span!("instance_startup")
+            .in_scope(|| self.instantiate(module, store))?;
 
         let start = instance.exports.get_function("_start")?;
 
         env.data(store).thread.set_status_running();
-        let res = crate::run_wasi_func_start(start, store);
+
+        // All the syscalls made by the main thread are grouped under this span
+        let res = tracing::debug_span!(
+            "wasi_run",
+            process.pid = u32::from(env.data(store).pid()),
+            thread.id = u32::from(env.data(store).tid()),
+        )
+        .in_scope(|| crate::run_wasi_func_start(start, store));
 
         tracing::trace!(
             "wasi[{}:{}]::main exit (code = {:?})",
@@ -977,4 +1200,19 @@ mod test {
             WasiStateCreationError::ArgumentContainsNulByte(_)
         ));
     }
+
+    #[test]
+    fn secrets_are_redacted_from_debug_output() {
+        let builder = WasiEnvBuilder::new("test_prog")
+            .env("USER", "ferris")
+            .env("API_TOKEN", "hunter2")
+            .secret("API_TOKEN");
+
+        let debug = format!("{builder:?}");
+
+        assert!(debug.contains("USER=ferris"), "{debug}");
+        assert!(debug.contains("API_TOKEN=<redacted>"), "{debug}");
+        assert!(!debug.contains("hunter2"), "{debug}");
+        assert_eq!(builder.get_env()[1].1, b"hunter2");
+    }
 }
@@ -1,4 +1,6 @@
-use std::{collections::HashMap, ops::Deref, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap, ops::Deref, path::PathBuf, sync::Arc, task::Waker, time::Duration,
+};
 
 use derivative::Derivative;
 use rand::Rng;
@@ -20,6 +22,8 @@ use crate::{
     capabilities::Capabilities,
     fs::{WasiFsRoot, WasiInodes},
     import_object_for_all_wasi_versions,
+    journal::{Journal, JournalEntry, Replay},
+    migration::Migration,
     os::{
         command::builtins::cmd_wasmer::CmdWasmer,
         task::{
@@ -28,7 +32,9 @@ use crate::{
             thread::{WasiThread, WasiThreadHandle, WasiThreadId},
         },
     },
+    parking::Parking,
     runtime::SpawnType,
+    snapshot::{ProcessSnapshot, SnapshotConfig},
     syscalls::{__asyncify_light, platform_clock_time_get},
     SpawnedMemory, VirtualTaskManager, WasiControlPlane, WasiEnvBuilder, WasiError,
     WasiFunctionEnv, WasiRuntime, WasiRuntimeError, WasiStateCreationError, WasiVFork,
@@ -218,6 +224,19 @@ pub struct WasiEnvInit {
     /// Whether to call the `_initialize` function in the WASI module.
     /// Will be true for regular new instances, but false for threads.
     pub call_initialize: bool,
+
+    /// Where and when the process snapshots itself
+    pub snapshot: Option<Arc<SnapshotConfig>>,
+    /// The snapshot the process is resumed from
+    pub resume: Option<Arc<ProcessSnapshot>>,
+    /// The journal the process records its state changes to
+    pub journal: Option<Arc<Journal>>,
+    /// Where and when the process migrates itself
+    pub migration: Option<Arc<Migration>>,
+    /// The journaled run the process replays
+    pub replay: Option<Arc<Replay>>,
+    /// The supervisor that parks the process while it is idle
+    pub parking: Option<Arc<Parking>>,
 }
 
 impl WasiEnvInit {
@@ -253,6 +272,12 @@ impl WasiEnvInit {
             process: None,
             thread: None,
             call_initialize: self.call_initialize,
+            snapshot: None,
+            resume: None,
+            journal: None,
+            migration: None,
+            replay: None,
+            parking: None,
         }
     }
 }
@@ -286,6 +311,17 @@ pub struct WasiEnv {
     pub runtime: Arc<dyn WasiRuntime + Send + Sync + 'static>,
 
     pub capabilities: Capabilities,
+
+    /// Where and when the process snapshots itself
+    pub(crate) snapshot: Option<Arc<SnapshotConfig>>,
+    /// The journal the process records its state changes to
+    pub(crate) journal: Option<Arc<Journal>>,
+    /// Where and when the process migrates itself
+    pub(crate) migration: Option<Arc<Migration>>,
+    /// The journaled run the process replays
+    pub(crate) replay: Option<Arc<Replay>>,
+    /// The supervisor that parks the process while it is idle
+    pub(crate) parking: Option<Arc<Parking>>,
 }
 
 impl std::fmt::Debug for WasiEnv {
@@ -330,6 +366,11 @@ impl WasiEnv {
             owned_handles: self.owned_handles.clone(),
             runtime: self.runtime.clone(),
             capabilities: self.capabilities.clone(),
+            snapshot: self.snapshot.clone(),
+            journal: self.journal.clone(),
+            migration: self.migration.clone(),
+            replay: self.replay.clone(),
+            parking: self.parking.clone(),
         }
     }
 
@@ -359,6 +400,11 @@ impl WasiEnv {
             owned_handles: Vec::new(),
             runtime: self.runtime.clone(),
             capabilities: self.capabilities.clone(),
+            snapshot: None,
+            journal: None,
+            migration: None,
+            replay: None,
+            parking: None,
         };
         Ok((new_env, handle))
     }
@@ -398,6 +444,11 @@ impl WasiEnv {
             runtime: init.runtime,
             bin_factory: init.bin_factory,
             capabilities: init.capabilities,
+            snapshot: init.snapshot,
+            journal: init.journal,
+            migration: init.migration,
+            replay: init.replay,
+            parking: init.parking,
         };
         env.owned_handles.push(thread);
 
@@ -412,17 +463,22 @@ impl WasiEnv {
 
     // FIXME: use custom error type
     #[allow(clippy::result_large_err)]
+    #[tracing::instrument(level = "debug", skip_all, fields(process.pid = tracing::field::Empty), err)]
     pub(crate) fn instantiate(
         mut init: WasiEnvInit,
         module: Module,
         store: &mut impl AsStoreMut,
     ) -> Result<(Instance, WasiFunctionEnv), WasiRuntimeError> {
+        crate::utils::check_memory64(&module)?;
+
         let call_initialize = init.call_initialize;
         let spawn_type = init.spawn_type.take();
+        let resume = init.resume.take();
 
         let env = Self::from_init(init)?;
 
         let pid = env.process.pid();
+        tracing::Span::current().record("process.pid", u32::from(pid));
 
         let mut store = store.as_store_mut();
 
@@ -480,6 +536,24 @@ impl WasiEnv {
             return Err(err.into());
         }
 
+        if let Some(snapshot) = resume {
+            if let Err(err) = crate::snapshot::restore(&func_env, &mut store, &snapshot) {
+                tracing::error!("wasi[{}]::snapshot restore error ({})", pid, err);
+                func_env
+                    .data(&store)
+                    .blocking_cleanup(Some(Errno::Noexec.into()));
+                return Err(err.into());
+            }
+            // The process was initialized before the snapshot was taken
+            return Ok((instance, func_env));
+        }
+
+        func_env.data(&store).record_journal(|| {
+            Some(JournalEntry::InitModule {
+                module: crate::snapshot::fingerprint(&module),
+            })
+        });
+
         // If this module exports an _initialize function, run that first.
         if call_initialize {
             if let Ok(initialize) = instance.exports.get_function("_initialize") {
@@ -522,6 +596,120 @@ impl WasiEnv {
         self.process.active_threads()
     }
 
+    /// Whether the process should snapshot itself now. Only the main thread
+    /// of a single-threaded process can be snapshotted.
+    pub(crate) fn snapshot_requested(&self) -> bool {
+        match self.snapshot.as_ref() {
+            Some(config) => {
+                self.thread.is_main() && self.active_threads() <= 1 && config.trigger.is_requested()
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the process should take a checkpoint for its journal now,
+    /// see [`WasiEnv::snapshot_requested`].
+    pub(crate) fn checkpoint_requested(&self) -> bool {
+        match self.journal.as_ref() {
+            Some(journal) => {
+                self.thread.is_main() && self.active_threads() <= 1 && journal.checkpoint_due()
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the process should migrate itself now, see
+    /// [`WasiEnv::snapshot_requested`].
+    pub(crate) fn migration_requested(&self) -> bool {
+        match self.migration.as_ref() {
+            Some(migration) => {
+                self.thread.is_main() && self.active_threads() <= 1 && migration.is_requested()
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the process should park itself now, see
+    /// [`WasiEnv::snapshot_requested`].
+    pub(crate) fn park_requested(&self) -> bool {
+        match self.parking.as_ref() {
+            Some(parking) => {
+                self.thread.is_main() && self.active_threads() <= 1 && parking.is_requested()
+            }
+            None => false,
+        }
+    }
+
+    /// Like [`WasiEnv::park_requested`], but wakes `waker` up once parking
+    /// is requested, so that a syscall waiting for something else can be
+    /// interrupted.
+    pub(crate) fn park_requested_or_subscribe(&self, waker: &Waker) -> bool {
+        match self.parking.as_ref() {
+            Some(parking) if self.thread.is_main() && self.active_threads() <= 1 => {
+                parking.is_requested_or_subscribe(waker)
+            }
+            _ => false,
+        }
+    }
+
+    /// Appends an entry to the journal of the process, if it has one, or
+    /// checks it against the run that is replayed. The entry is only built
+    /// when it is needed.
+    pub(crate) fn record_journal(&self, entry: impl FnOnce() -> Option<JournalEntry>) {
+        if let Some(replay) = self.replay.as_ref() {
+            if let Some(entry) = entry() {
+                if replay.replays(&entry) {
+                    self.replay_entry(replay, &entry).ok();
+                }
+            }
+            return;
+        }
+        let journal = match self.journal.as_ref() {
+            Some(journal) => journal,
+            None => return,
+        };
+        if let Some(entry) = entry() {
+            if let Err(err) = journal.record(&entry) {
+                tracing::error!(
+                    error = &err as &dyn std::error::Error,
+                    ?entry,
+                    "Unable to journal a state change",
+                );
+            }
+        }
+    }
+
+    /// Appends an input of the process to its journal, if the journal
+    /// records inputs.
+    pub(crate) fn record_input(&self, entry: impl FnOnce() -> Option<JournalEntry>) {
+        match self.journal.as_ref() {
+            Some(journal) if journal.records_inputs() => self.record_journal(entry),
+            _ => {}
+        }
+    }
+
+    /// When the process is replayed, returns the input that was journaled
+    /// in place of `actual` rather than letting the syscall run.
+    pub(crate) fn replayed_input(
+        &self,
+        actual: JournalEntry,
+    ) -> Option<Result<JournalEntry, Errno>> {
+        let replay = self.replay.as_ref()?;
+        if !replay.replays(&actual) {
+            return None;
+        }
+        Some(self.replay_entry(replay, &actual))
+    }
+
+    /// A divergence ends the process, there is nothing left to replay.
+    fn replay_entry(&self, replay: &Replay, actual: &JournalEntry) -> Result<JournalEntry, Errno> {
+        replay.next(actual).map_err(|divergence| {
+            tracing::error!(%divergence, "The process diverged from the journaled run");
+            self.process.terminate(Errno::Notrecoverable.into());
+            Errno::Notrecoverable
+        })
+    }
+
     /// Porcesses any signals that are batched up or any forced exit codes
     pub fn process_signals_and_exit(
         ctx: &mut FunctionEnvMut<'_, Self>,
@@ -0,0 +1,719 @@
+//! Compression and encryption of the snapshots and journals a process
+//! leaves on disk, which hold everything the guest had in memory.
+//!
+//! Snapshots are streamed through a [`StorageOptions::writer`]: compressed
+//! with zstd, then cut into chunks that are encrypted one by one with
+//! AES-256-GCM. The nonce of every chunk is made of a random prefix, the
+//! index of the chunk and whether it is the last one, so chunks can't be
+//! reordered, dropped or cut off without it being noticed. Journals are
+//! appended to one record at a time, so their records are
+//! [sealed](StorageOptions::seal) on their own instead.
+//!
+//! The key is provided by the embedder, nothing is encrypted without one.
+//! Files written without compression or encryption are left as they were,
+//! and a file that isn't encrypted is refused when a key is given so that
+//! it can't be swapped for one that wasn't.
+use std::{
+    convert::TryFrom,
+    fmt,
+    io::{self, Read, Write},
+};
+
+/// Identifies compressed or encrypted files
+const MAGIC: &[u8; 8] = b"wasmersl";
+
+/// Bumped whenever the layout of compressed or encrypted files changes
+const FORMAT_VERSION: u32 = 1;
+
+/// The data is compressed with zstd
+pub(crate) const COMPRESSED: u8 = 1;
+
+/// The data is encrypted with AES-256-GCM
+pub(crate) const ENCRYPTED: u8 = 2;
+
+/// The most plaintext that goes into one encrypted chunk
+#[cfg(feature = "encryption")]
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// Set in the length of the last chunk
+#[cfg(feature = "encryption")]
+const LAST_CHUNK: u32 = 1 << 31;
+
+const NONCE_PREFIX_LEN: usize = 7;
+
+/// A 256-bit key for AES-GCM.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        EncryptionKey(key)
+    }
+
+    /// Accepts the 32 bytes of the key, or 64 hex digits surrounded by
+    /// whitespace.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if let Ok(key) = <[u8; 32]>::try_from(bytes) {
+            return Some(EncryptionKey(key));
+        }
+        let text = std::str::from_utf8(bytes).ok()?.trim();
+        let mut key = [0; 32];
+        hex::decode_to_slice(text, &mut key).ok()?;
+        Some(EncryptionKey(key))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// How snapshots and journals are stored.
+#[derive(Debug, Clone, Default)]
+pub struct StorageOptions {
+    /// The zstd level to compress with, `None` to leave the data as is
+    pub compression_level: Option<i32>,
+    /// The key to encrypt with, and to decrypt what is read back
+    pub key: Option<EncryptionKey>,
+}
+
+impl StorageOptions {
+    pub fn new() -> Self {
+        StorageOptions::default()
+    }
+
+    pub fn with_compression(mut self, level: i32) -> Self {
+        self.compression_level = Some(level);
+        self
+    }
+
+    pub fn with_encryption(mut self, key: EncryptionKey) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    pub(crate) fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.compression_level.is_some() {
+            flags |= COMPRESSED;
+        }
+        if self.key.is_some() {
+            flags |= ENCRYPTED;
+        }
+        flags
+    }
+
+    /// Checks that data written with `flags` can be read back with these
+    /// options.
+    pub(crate) fn check_flags(&self, flags: u8) -> io::Result<()> {
+        if flags & !(COMPRESSED | ENCRYPTED) != 0 {
+            return Err(invalid_data(format!("unknown storage flags {flags:#x}")));
+        }
+        match (flags & ENCRYPTED != 0, self.key.is_some()) {
+            (true, false) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "the file is encrypted, a key is needed to read it",
+                ))
+            }
+            (false, true) => return Err(invalid_data("the file isn't encrypted")),
+            _ => {}
+        }
+        if flags & COMPRESSED != 0 && !cfg!(feature = "compression") {
+            return Err(unsupported("compression"));
+        }
+        Ok(())
+    }
+
+    /// Compresses and encrypts everything written to `inner`. Call
+    /// [`StorageWriter::finish`] once done, or the data can't be read back.
+    pub(crate) fn writer<'a, W: Write + 'a>(&self, mut inner: W) -> io::Result<StorageWriter<'a>> {
+        let flags = self.flags();
+        if flags == 0 {
+            return Ok(StorageWriter(Box::new(Plain(inner))));
+        }
+
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        header.push(flags);
+        let mut writer: Box<dyn FinishWrite + 'a> = match self.key.as_ref() {
+            Some(key) => {
+                let mut prefix = [0; NONCE_PREFIX_LEN];
+                getrandom::getrandom(&mut prefix)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+                header.extend_from_slice(&prefix);
+                inner.write_all(&header)?;
+                encryptor(inner, key, prefix, header)?
+            }
+            None => {
+                inner.write_all(&header)?;
+                Box::new(Plain(inner))
+            }
+        };
+        if let Some(level) = self.compression_level {
+            writer = compressor(writer, level)?;
+        }
+        Ok(StorageWriter(writer))
+    }
+
+    /// Reads what a [`StorageOptions::writer`] wrote, whichever options it
+    /// was written with. Data that was written as is passes through.
+    pub(crate) fn reader<'a, R: Read + 'a>(&self, mut inner: R) -> io::Result<Box<dyn Read + 'a>> {
+        let mut magic = Vec::with_capacity(MAGIC.len());
+        (&mut inner)
+            .take(MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        if magic != MAGIC {
+            self.check_flags(0)?;
+            return Ok(Box::new(io::Cursor::new(magic).chain(inner)));
+        }
+
+        let mut rest = [0; 5];
+        inner.read_exact(&mut rest)?;
+        let mut version = [0; 4];
+        version.copy_from_slice(&rest[..4]);
+        let version = u32::from_le_bytes(version);
+        if version != FORMAT_VERSION {
+            return Err(invalid_data(format!(
+                "unsupported storage format {version}"
+            )));
+        }
+        let flags = rest[4];
+        self.check_flags(flags)?;
+
+        let mut header = magic;
+        header.extend_from_slice(&rest);
+        let mut reader: Box<dyn Read + 'a> = match self.key.as_ref() {
+            Some(key) => {
+                let mut prefix = [0; NONCE_PREFIX_LEN];
+                inner.read_exact(&mut prefix)?;
+                header.extend_from_slice(&prefix);
+                decryptor(inner, key, prefix, header)?
+            }
+            None => Box::new(inner),
+        };
+        if flags & COMPRESSED != 0 {
+            reader = decompressor(reader)?;
+        }
+        Ok(reader)
+    }
+
+    /// Compresses and encrypts a record that is stored on its own. `aad`
+    /// isn't stored but has to be the same when the record is unsealed.
+    pub(crate) fn seal(&self, mut data: Vec<u8>, aad: &[u8]) -> io::Result<Vec<u8>> {
+        if let Some(level) = self.compression_level {
+            data = compress(&data, level)?;
+        }
+        if let Some(key) = self.key.as_ref() {
+            data = encrypt(key, &data, aad)?;
+        }
+        Ok(data)
+    }
+
+    /// Undoes [`StorageOptions::seal`] for a record written with `flags`.
+    pub(crate) fn unseal(&self, flags: u8, mut data: Vec<u8>, aad: &[u8]) -> io::Result<Vec<u8>> {
+        if flags & ENCRYPTED != 0 {
+            let key = self
+                .key
+                .as_ref()
+                .ok_or_else(|| invalid_data("the record is encrypted"))?;
+            data = decrypt(key, &data, aad)?;
+        }
+        if flags & COMPRESSED != 0 {
+            data = decompress(&data)?;
+        }
+        Ok(data)
+    }
+}
+
+/// Writes through the compression and encryption of [`StorageOptions`].
+pub(crate) struct StorageWriter<'a>(Box<dyn FinishWrite + 'a>);
+
+impl StorageWriter<'_> {
+    /// Writes out what is buffered and marks the end of the data.
+    pub(crate) fn finish(self) -> io::Result<()> {
+        self.0.finish_write()
+    }
+}
+
+impl Write for StorageWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// A writer that has to be told when the data ends.
+trait FinishWrite: Write {
+    fn finish_write(self: Box<Self>) -> io::Result<()>;
+}
+
+struct Plain<W>(W);
+
+impl<W: Write> Write for Plain<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<W: Write> FinishWrite for Plain<W> {
+    fn finish_write(mut self: Box<Self>) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn unsupported(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("wasmer was built without {what} support"),
+    )
+}
+
+#[cfg(feature = "compression")]
+impl<'a> FinishWrite for zstd::stream::write::Encoder<'static, Box<dyn FinishWrite + 'a>> {
+    fn finish_write(self: Box<Self>) -> io::Result<()> {
+        let inner = (*self).finish()?;
+        inner.finish_write()
+    }
+}
+
+#[cfg(feature = "compression")]
+fn compressor<'a>(
+    inner: Box<dyn FinishWrite + 'a>,
+    level: i32,
+) -> io::Result<Box<dyn FinishWrite + 'a>> {
+    Ok(Box::new(zstd::stream::write::Encoder::new(inner, level)?))
+}
+
+#[cfg(feature = "compression")]
+fn decompressor<'a>(inner: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+    Ok(Box::new(zstd::stream::read::Decoder::new(inner)?))
+}
+
+#[cfg(feature = "compression")]
+fn compress(data: &[u8], level: i32) -> io::Result<Vec<u8>> {
+    zstd::stream::encode_all(data, level)
+}
+
+#[cfg(feature = "compression")]
+fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::stream::decode_all(data)
+}
+
+#[cfg(not(feature = "compression"))]
+fn compressor<'a>(
+    _inner: Box<dyn FinishWrite + 'a>,
+    _level: i32,
+) -> io::Result<Box<dyn FinishWrite + 'a>> {
+    Err(unsupported("compression"))
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompressor<'a>(_inner: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+    Err(unsupported("compression"))
+}
+
+#[cfg(not(feature = "compression"))]
+fn compress(_data: &[u8], _level: i32) -> io::Result<Vec<u8>> {
+    Err(unsupported("compression"))
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_data: &[u8]) -> io::Result<Vec<u8>> {
+    Err(unsupported("compression"))
+}
+
+#[cfg(feature = "encryption")]
+mod crypto {
+    use std::io::{self, Read, Write};
+
+    use aes_gcm::{
+        aead::{generic_array::GenericArray, Aead, KeyInit, Payload},
+        Aes256Gcm,
+    };
+
+    use super::{
+        invalid_data, EncryptionKey, FinishWrite, CHUNK_LEN, LAST_CHUNK, NONCE_PREFIX_LEN,
+    };
+
+    const NONCE_LEN: usize = 12;
+
+    /// AES-GCM adds a tag of this many bytes
+    const TAG_LEN: usize = 16;
+
+    fn cipher(key: &EncryptionKey) -> Aes256Gcm {
+        Aes256Gcm::new_from_slice(&key.0).expect("AES-256 keys are 32 bytes")
+    }
+
+    fn chunk_nonce(prefix: &[u8; NONCE_PREFIX_LEN], index: u32, last: bool) -> [u8; NONCE_LEN] {
+        let mut nonce = [0; NONCE_LEN];
+        nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+        nonce[NONCE_PREFIX_LEN..NONCE_LEN - 1].copy_from_slice(&index.to_be_bytes());
+        nonce[NONCE_LEN - 1] = last as u8;
+        nonce
+    }
+
+    fn tampered() -> io::Error {
+        invalid_data("unable to decrypt, the key is wrong or the data was tampered with")
+    }
+
+    pub(super) fn encrypt(key: &EncryptionKey, data: &[u8], aad: &[u8]) -> io::Result<Vec<u8>> {
+        let mut nonce = [0; NONCE_LEN];
+        getrandom::getrandom(&mut nonce)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        let ciphertext = cipher(key)
+            .encrypt(GenericArray::from_slice(&nonce), Payload { msg: data, aad })
+            .map_err(|_| invalid_data("unable to encrypt"))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    pub(super) fn decrypt(key: &EncryptionKey, data: &[u8], aad: &[u8]) -> io::Result<Vec<u8>> {
+        if data.len() < NONCE_LEN + TAG_LEN {
+            return Err(tampered());
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        cipher(key)
+            .decrypt(
+                GenericArray::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| tampered())
+    }
+
+    /// Encrypts the data in chunks of [`CHUNK_LEN`] bytes, each of them
+    /// prefixed with its length.
+    pub(super) struct Encryptor<W> {
+        inner: W,
+        cipher: Aes256Gcm,
+        prefix: [u8; NONCE_PREFIX_LEN],
+        /// The header of the file, authenticated with every chunk
+        header: Vec<u8>,
+        buffer: Vec<u8>,
+        index: u32,
+    }
+
+    impl<W: Write> Encryptor<W> {
+        pub(super) fn new(
+            inner: W,
+            key: &EncryptionKey,
+            prefix: [u8; NONCE_PREFIX_LEN],
+            header: Vec<u8>,
+        ) -> Self {
+            Encryptor {
+                inner,
+                cipher: cipher(key),
+                prefix,
+                header,
+                buffer: Vec::with_capacity(CHUNK_LEN),
+                index: 0,
+            }
+        }
+
+        fn write_chunk(&mut self, last: bool) -> io::Result<()> {
+            let nonce = chunk_nonce(&self.prefix, self.index, last);
+            let ciphertext = self
+                .cipher
+                .encrypt(
+                    GenericArray::from_slice(&nonce),
+                    Payload {
+                        msg: &self.buffer,
+                        aad: &self.header,
+                    },
+                )
+                .map_err(|_| invalid_data("unable to encrypt"))?;
+            let mut len = ciphertext.len() as u32;
+            if last {
+                len |= LAST_CHUNK;
+            }
+            self.inner.write_all(&len.to_le_bytes())?;
+            self.inner.write_all(&ciphertext)?;
+
+            self.buffer.clear();
+            self.index = self
+                .index
+                .checked_add(1)
+                .ok_or_else(|| invalid_data("too much data to encrypt"))?;
+            Ok(())
+        }
+    }
+
+    impl<W: Write> Write for Encryptor<W> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let len = buf.len().min(CHUNK_LEN - self.buffer.len());
+            self.buffer.extend_from_slice(&buf[..len]);
+            if self.buffer.len() == CHUNK_LEN {
+                self.write_chunk(false)?;
+            }
+            Ok(len)
+        }
+
+        /// Only whole chunks are written before the end
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    impl<W: Write> FinishWrite for Encryptor<W> {
+        fn finish_write(mut self: Box<Self>) -> io::Result<()> {
+            self.write_chunk(true)?;
+            self.inner.flush()
+        }
+    }
+
+    pub(super) struct Decryptor<R> {
+        inner: R,
+        cipher: Aes256Gcm,
+        prefix: [u8; NONCE_PREFIX_LEN],
+        header: Vec<u8>,
+        buffer: Vec<u8>,
+        position: usize,
+        index: u32,
+        finished: bool,
+    }
+
+    impl<R: Read> Decryptor<R> {
+        pub(super) fn new(
+            inner: R,
+            key: &EncryptionKey,
+            prefix: [u8; NONCE_PREFIX_LEN],
+            header: Vec<u8>,
+        ) -> Self {
+            Decryptor {
+                inner,
+                cipher: cipher(key),
+                prefix,
+                header,
+                buffer: Vec::new(),
+                position: 0,
+                index: 0,
+                finished: false,
+            }
+        }
+
+        fn read_chunk(&mut self) -> io::Result<()> {
+            let mut len = [0; 4];
+            self.inner
+                .read_exact(&mut len)
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::UnexpectedEof => invalid_data("the data was cut short"),
+                    _ => e,
+                })?;
+            let len = u32::from_le_bytes(len);
+            let last = len & LAST_CHUNK != 0;
+            let len = (len & !LAST_CHUNK) as usize;
+            if len > CHUNK_LEN + TAG_LEN {
+                return Err(tampered());
+            }
+
+            let mut ciphertext = vec![0; len];
+            self.inner.read_exact(&mut ciphertext)?;
+            let nonce = chunk_nonce(&self.prefix, self.index, last);
+            self.buffer = self
+                .cipher
+                .decrypt(
+                    GenericArray::from_slice(&nonce),
+                    Payload {
+                        msg: &ciphertext,
+                        aad: &self.header,
+                    },
+                )
+                .map_err(|_| tampered())?;
+            self.position = 0;
+            self.index = self.index.checked_add(1).ok_or_else(tampered)?;
+
+            if last {
+                self.finished = true;
+                if self.inner.read(&mut [0u8])? != 0 {
+                    return Err(invalid_data("there is data after the end"));
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl<R: Read> Read for Decryptor<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while self.position == self.buffer.len() {
+                if self.finished {
+                    return Ok(0);
+                }
+                self.read_chunk()?;
+            }
+            let len = buf.len().min(self.buffer.len() - self.position);
+            buf[..len].copy_from_slice(&self.buffer[self.position..self.position + len]);
+            self.position += len;
+            Ok(len)
+        }
+    }
+}
+
+#[cfg(feature = "encryption")]
+use self::crypto::{decrypt, encrypt};
+
+#[cfg(feature = "encryption")]
+fn encryptor<'a, W: Write + 'a>(
+    inner: W,
+    key: &EncryptionKey,
+    prefix: [u8; NONCE_PREFIX_LEN],
+    header: Vec<u8>,
+) -> io::Result<Box<dyn FinishWrite + 'a>> {
+    Ok(Box::new(crypto::Encryptor::new(inner, key, prefix, header)))
+}
+
+#[cfg(feature = "encryption")]
+fn decryptor<'a, R: Read + 'a>(
+    inner: R,
+    key: &EncryptionKey,
+    prefix: [u8; NONCE_PREFIX_LEN],
+    header: Vec<u8>,
+) -> io::Result<Box<dyn Read + 'a>> {
+    Ok(Box::new(crypto::Decryptor::new(inner, key, prefix, header)))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn encryptor<'a, W: Write + 'a>(
+    _inner: W,
+    _key: &EncryptionKey,
+    _prefix: [u8; NONCE_PREFIX_LEN],
+    _header: Vec<u8>,
+) -> io::Result<Box<dyn FinishWrite + 'a>> {
+    Err(unsupported("encryption"))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn decryptor<'a, R: Read + 'a>(
+    _inner: R,
+    _key: &EncryptionKey,
+    _prefix: [u8; NONCE_PREFIX_LEN],
+    _header: Vec<u8>,
+) -> io::Result<Box<dyn Read + 'a>> {
+    Err(unsupported("encryption"))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn encrypt(_key: &EncryptionKey, _data: &[u8], _aad: &[u8]) -> io::Result<Vec<u8>> {
+    Err(unsupported("encryption"))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn decrypt(_key: &EncryptionKey, _data: &[u8], _aad: &[u8]) -> io::Result<Vec<u8>> {
+    Err(unsupported("encryption"))
+}
+
+#[cfg(all(test, feature = "compression", feature = "encryption"))]
+mod tests {
+    use super::*;
+
+    fn data() -> Vec<u8> {
+        // More than one chunk, and compressible
+        (0..200_000u32)
+            .flat_map(|i| (i % 251).to_le_bytes())
+            .collect()
+    }
+
+    fn write(options: &StorageOptions, data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut writer = options.writer(&mut bytes).unwrap();
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    fn read(options: &StorageOptions, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        options.reader(bytes)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn data_roundtrips_with_every_option() {
+        let key = EncryptionKey::new([7; 32]);
+        let data = data();
+        for options in [
+            StorageOptions::new(),
+            StorageOptions::new().with_compression(3),
+            StorageOptions::new().with_encryption(key.clone()),
+            StorageOptions::new()
+                .with_compression(3)
+                .with_encryption(key.clone()),
+        ] {
+            let bytes = write(&options, &data);
+            assert_eq!(read(&options, &bytes).unwrap(), data, "{options:?}");
+        }
+
+        // Without any options the data is left as is
+        assert_eq!(write(&StorageOptions::new(), b"hello"), b"hello");
+        let compressed = write(&StorageOptions::new().with_compression(3), &data);
+        assert!(compressed.len() < data.len() / 4);
+    }
+
+    #[test]
+    fn encrypted_data_is_authenticated() {
+        let options = StorageOptions::new().with_encryption(EncryptionKey::new([7; 32]));
+        let bytes = write(&options, &data());
+
+        let mut tampered = bytes.clone();
+        tampered[100] ^= 1;
+        assert!(read(&options, &tampered).is_err());
+
+        // The last chunk is missing
+        let cut = &bytes[..bytes.len() - 10];
+        assert!(read(&options, cut).is_err());
+
+        let wrong_key = StorageOptions::new().with_encryption(EncryptionKey::new([8; 32]));
+        assert!(read(&wrong_key, &bytes).is_err());
+        assert_eq!(
+            read(&StorageOptions::new(), &bytes).unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+        // Nor can the file be swapped for one that isn't encrypted
+        assert!(read(&options, b"hello").is_err());
+    }
+
+    #[test]
+    fn records_are_sealed_on_their_own() {
+        let options = StorageOptions::new()
+            .with_compression(3)
+            .with_encryption(EncryptionKey::new([7; 32]));
+        let sealed = options.seal(data(), b"1").unwrap();
+        assert_eq!(
+            options
+                .unseal(options.flags(), sealed.clone(), b"1")
+                .unwrap(),
+            data()
+        );
+        assert!(options.unseal(options.flags(), sealed, b"2").is_err());
+    }
+
+    #[test]
+    fn keys_are_read_as_bytes_or_hex() {
+        let hex = format!("{}\n", "ab".repeat(32));
+        assert_eq!(
+            EncryptionKey::from_bytes(hex.as_bytes()),
+            Some(EncryptionKey::new([0xab; 32]))
+        );
+        assert_eq!(
+            EncryptionKey::from_bytes(&[1; 32]),
+            Some(EncryptionKey::new([1; 32]))
+        );
+        assert_eq!(EncryptionKey::from_bytes(b"too short"), None);
+    }
+}
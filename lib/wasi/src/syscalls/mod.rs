@@ -94,7 +94,9 @@ pub(crate) use crate::os::task::{
 };
 pub(crate) use crate::{
     bin_factory::spawn_exec_module,
-    current_caller_id, import_object_for_all_wasi_versions, mem_error_to_wasi,
+    current_caller_id, import_object_for_all_wasi_versions,
+    journal::{JournalEntry, SocketEvent},
+    mem_error_to_wasi,
     net::{
         read_ip_port,
         socket::{InodeHttpSocketType, InodeSocket, InodeSocketKind},
@@ -293,6 +295,10 @@ where
                 }
                 return Poll::Ready(Ok(Err(Errno::Intr)));
             }
+            // The syscall is entered again once the process is thawed
+            if self.ctx.data().park_requested_or_subscribe(cx.waker()) {
+                return Poll::Ready(Ok(Err(Errno::Intr)));
+            }
             Poll::Pending
         }
     }
@@ -380,6 +386,9 @@ where
             if let Some(signals) = self.env.thread.pop_signals_or_subscribe(cx.waker()) {
                 return Poll::Ready(Ok(Err(Errno::Intr)));
             }
+            if self.env.park_requested_or_subscribe(cx.waker()) {
+                return Poll::Ready(Ok(Err(Errno::Intr)));
+            }
             Poll::Pending
         }
     }
@@ -623,6 +632,7 @@ where
                     let mut guard = inode.write();
                     match guard.deref_mut() {
                         Kind::Socket { socket } => {
+                            new_socket.inherit_permit(socket);
                             std::mem::swap(socket, &mut new_socket);
                         }
                         _ => {
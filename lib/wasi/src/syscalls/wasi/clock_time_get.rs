@@ -21,11 +21,30 @@ pub fn clock_time_get<M: MemorySize>(
     let env = ctx.data();
     let memory = env.memory_view(&ctx);
 
-    let mut t_out = wasi_try!(platform_clock_time_get(clock_id, precision));
-    {
-        let guard = env.state.clock_offset.lock().unwrap();
-        if let Some(offset) = guard.get(&clock_id) {
-            t_out += *offset;
+    let replayed = env.replayed_input(JournalEntry::ClockTime {
+        clock: clock_id as u32,
+        time: 0,
+    });
+    let t_out = match replayed {
+        Some(entry) => match wasi_try!(entry) {
+            JournalEntry::ClockTime { time, .. } => time,
+            _ => return Errno::Notrecoverable,
+        },
+        None => {
+            let mut t_out = wasi_try!(platform_clock_time_get(clock_id, precision));
+            {
+                let guard = env.state.clock_offset.lock().unwrap();
+                if let Some(offset) = guard.get(&clock_id) {
+                    t_out += *offset;
+                }
+            };
+            env.record_input(|| {
+                Some(JournalEntry::ClockTime {
+                    clock: clock_id as u32,
+                    time: t_out,
+                })
+            });
+            t_out
         }
     };
     wasi_try_mem!(time.write(&memory, t_out as Timestamp));
@@ -1,5 +1,8 @@
 use super::*;
-use crate::syscalls::*;
+use crate::{
+    audit::{AuditAction, AuditDecision, AuditEvent},
+    syscalls::*,
+};
 
 /// ### `environ_get()`
 /// Read environment variable data.
@@ -18,5 +21,18 @@ pub fn environ_get<M: MemorySize>(
     let env = ctx.data();
     let (memory, mut state) = env.get_memory_and_wasi_state(&ctx, 0);
 
+    if let Some(log) = env.control_plane.audit_log() {
+        let names = state
+            .envs
+            .iter()
+            .map(|env| {
+                let name = env.split(|b| *b == b'=').next().unwrap_or_default();
+                String::from_utf8_lossy(name).into_owned()
+            })
+            .collect();
+        let action = AuditAction::EnvRead { names };
+        log.record(AuditEvent::new(env.pid(), action, AuditDecision::Allowed));
+    }
+
     write_buffer_array(&memory, &state.envs, environ, environ_buf)
 }
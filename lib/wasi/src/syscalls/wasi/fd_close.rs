@@ -17,6 +17,7 @@ pub fn fd_close(mut ctx: FunctionEnvMut<'_, WasiEnv>, fd: WasiFd) -> Result<Errn
     let env = ctx.data();
     let (_, mut state) = env.get_memory_and_wasi_state(&ctx, 0);
     wasi_try_ok!(state.fs.close_fd(fd));
+    env.record_journal(|| Some(JournalEntry::CloseFd { fd }));
 
     Ok(Errno::Success)
 }
@@ -26,13 +26,19 @@ pub fn fd_filestat_set_size(
     {
         let mut guard = inode.write();
         match guard.deref_mut() {
-            Kind::File { handle, .. } => {
+            Kind::File { handle, path, .. } => {
                 if let Some(handle) = handle {
                     let mut handle = handle.write().unwrap();
                     wasi_try!(handle.set_len(st_size).map_err(fs_error_into_wasi_err));
                 } else {
                     return Errno::Badf;
                 }
+                env.record_journal(|| {
+                    Some(JournalEntry::SetFileLength {
+                        path: path.clone(),
+                        len: st_size,
+                    })
+                });
             }
             Kind::Buffer { buffer } => {
                 buffer.resize(st_size as usize, 0);
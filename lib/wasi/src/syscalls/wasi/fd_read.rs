@@ -26,6 +26,8 @@ pub fn fd_read<M: MemorySize>(
     iovs_len: M::Offset,
     nread: WasmPtr<M::Offset, M>,
 ) -> Result<Errno, WasiError> {
+    maybe_snapshot!(ctx, M);
+
     let pid = ctx.data().pid();
     let tid = ctx.data().tid();
 
@@ -39,6 +41,11 @@ pub fn fd_read<M: MemorySize>(
     };
 
     let res = fd_read_internal::<M>(&mut ctx, fd, iovs, iovs_len, offset, nread, true)?;
+    // Nothing was read yet, the read starts over once the process is thawed
+    if res == Err(Errno::Intr) && ctx.data().park_requested() {
+        return crate::parking::unwind_and_park::<M>(ctx);
+    }
+    record_read::<M>(&ctx, fd, iovs, iovs_len, &res);
 
     let mut ret = Errno::Success;
     let bytes_read = match res {
@@ -91,6 +98,7 @@ pub fn fd_pread<M: MemorySize>(
     let tid = ctx.data().tid();
 
     let res = fd_read_internal::<M>(&mut ctx, fd, iovs, iovs_len, offset as usize, nread, false)?;
+    record_read::<M>(&ctx, fd, iovs, iovs_len, &res);
 
     let mut ret = Errno::Success;
     let bytes_read = match res {
@@ -126,6 +134,22 @@ fn fd_read_internal<M: MemorySize>(
 ) -> Result<Result<usize, Errno>, WasiError> {
     wasi_try_ok_ok!(WasiEnv::process_signals_and_exit(ctx)?);
 
+    let replayed = ctx.data().replayed_input(JournalEntry::Read {
+        fd,
+        result: Ok(Vec::new()),
+    });
+    if let Some(entry) = replayed {
+        let entry = wasi_try_ok_ok!(entry);
+        return Ok(replay_read::<M>(
+            ctx,
+            fd,
+            entry,
+            iovs,
+            iovs_len,
+            should_update_cursor,
+        ));
+    }
+
     let env = ctx.data();
     let memory = env.memory_view(&ctx);
     let state = env.state();
@@ -369,5 +393,88 @@ fn fd_read_internal<M: MemorySize>(
         bytes_read
     };
 
+    if let Some(metrics) = ctx.data().control_plane.metrics() {
+        metrics.bytes_read(bytes_read as u64);
+    }
+
     Ok(Ok(bytes_read))
 }
+
+/// Journals the outcome of a read, if the journal records inputs.
+fn record_read<M: MemorySize>(
+    ctx: &FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    iovs: WasmPtr<__wasi_iovec_t<M>, M>,
+    iovs_len: M::Offset,
+    res: &Result<usize, Errno>,
+) {
+    let env = ctx.data();
+    env.record_input(|| {
+        let result = match res {
+            Ok(bytes_read) => {
+                let memory = env.memory_view(ctx);
+                Ok(gather_iovs::<M>(&memory, iovs, iovs_len, *bytes_read).ok()?)
+            }
+            Err(err) => Err(*err as u16),
+        };
+        Some(JournalEntry::Read { fd, result })
+    });
+}
+
+/// Hands the data of a journaled read to the process instead of reading it.
+fn replay_read<M: MemorySize>(
+    ctx: &FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    entry: JournalEntry,
+    iovs: WasmPtr<__wasi_iovec_t<M>, M>,
+    iovs_len: M::Offset,
+    should_update_cursor: bool,
+) -> Result<usize, Errno> {
+    let data = match entry {
+        JournalEntry::Read {
+            result: Ok(data), ..
+        } => data,
+        JournalEntry::Read {
+            result: Err(code), ..
+        } => return Err(crate::journal::errno(code)),
+        _ => return Err(Errno::Notrecoverable),
+    };
+
+    let env = ctx.data();
+    let memory = env.memory_view(ctx);
+    let iovs_arr = iovs.slice(&memory, iovs_len).map_err(mem_error_to_wasi)?;
+    let bytes_read = read_bytes(&data[..], &memory, iovs_arr)?;
+
+    if should_update_cursor {
+        if let Ok(fd_entry) = env.state.fs.get_fd(fd) {
+            if !fd_entry.is_stdio {
+                fd_entry
+                    .offset
+                    .fetch_add(bytes_read as u64, Ordering::AcqRel);
+            }
+        }
+    }
+    Ok(bytes_read)
+}
+
+/// The first `len` bytes that were read into the buffers of `iovs`.
+fn gather_iovs<M: MemorySize>(
+    memory: &MemoryView,
+    iovs: WasmPtr<__wasi_iovec_t<M>, M>,
+    iovs_len: M::Offset,
+    len: usize,
+) -> Result<Vec<u8>, MemoryAccessError> {
+    let mut data = Vec::with_capacity(len);
+    for iov in iovs.slice(memory, iovs_len)?.iter() {
+        if data.len() >= len {
+            break;
+        }
+        let iov = iov.read()?;
+        let buf = WasmPtr::<u8, M>::new(iov.buf)
+            .slice(memory, iov.buf_len)?
+            .read_to_vec()?;
+        let remaining = len - data.len();
+        data.extend_from_slice(&buf[..buf.len().min(remaining)]);
+    }
+    Ok(data)
+}
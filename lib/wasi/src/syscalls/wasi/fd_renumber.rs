@@ -27,6 +27,8 @@ pub fn fd_renumber(ctx: FunctionEnvMut<'_, WasiEnv>, from: WasiFd, to: WasiFd) -
         ..*fd_entry
     };
     fd_map.insert(to, new_fd_entry);
+    drop(fd_map);
+    env.record_journal(|| Some(JournalEntry::RenumberFd { from, to }));
 
     Errno::Success
 }
@@ -23,6 +23,8 @@ pub fn fd_write<M: MemorySize>(
     iovs_len: M::Offset,
     nwritten: WasmPtr<M::Offset, M>,
 ) -> Result<Errno, WasiError> {
+    maybe_snapshot!(ctx, M);
+
     let offset = {
         let mut env = ctx.data();
         let state = env.state.clone();
@@ -268,10 +270,33 @@ fn fd_write_internal<M: MemorySize>(
             let (mut memory, _, inodes) = env.get_memory_and_wasi_state_and_inodes(&ctx, 0);
             // Cast is valid because we don't support 128 bit systems...
             fd_entry.inode.stat.write().unwrap().st_size += bytes_written as u64;
+
+            if can_update_cursor {
+                env.record_journal(|| {
+                    let path = match fd_entry.inode.read().deref() {
+                        Kind::File { path, .. } => path.clone(),
+                        _ => return None,
+                    };
+                    let data = gather_iovs::<M>(&memory, iovs, iovs_len, bytes_written).ok()?;
+                    Some(JournalEntry::WriteFile {
+                        path,
+                        offset: offset as u64,
+                        data,
+                    })
+                });
+            }
+        } else {
+            env.record_journal(|| {
+                let data = gather_iovs::<M>(&memory, iovs, iovs_len, bytes_written).ok()?;
+                Some(JournalEntry::Output { fd, data })
+            });
         }
         bytes_written
     };
     Span::current().record("nwritten", bytes_written);
+    if let Some(metrics) = env.control_plane.metrics() {
+        metrics.bytes_written(bytes_written as u64);
+    }
 
     let memory = env.memory_view(&ctx);
     let nwritten_ref = nwritten.deref(&memory);
@@ -281,3 +306,25 @@ fn fd_write_internal<M: MemorySize>(
 
     Ok(Errno::Success)
 }
+
+/// The first `len` bytes of the buffers of `iovs`.
+fn gather_iovs<M: MemorySize>(
+    memory: &MemoryView,
+    iovs: WasmPtr<__wasi_ciovec_t<M>, M>,
+    iovs_len: M::Offset,
+    len: usize,
+) -> Result<Vec<u8>, MemoryAccessError> {
+    let mut data = Vec::with_capacity(len);
+    for iov in iovs.slice(memory, iovs_len)?.iter() {
+        if data.len() >= len {
+            break;
+        }
+        let iov = iov.read()?;
+        let buf = WasmPtr::<u8, M>::new(iov.buf)
+            .slice(memory, iov.buf_len)?
+            .read_to_vec()?;
+        let remaining = len - data.len();
+        data.extend_from_slice(&buf[..buf.len().min(remaining)]);
+    }
+    Ok(data)
+}
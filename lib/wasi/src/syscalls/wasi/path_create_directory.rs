@@ -100,6 +100,11 @@ pub fn path_create_directory<M: MemorySize>(
                         }
                     } else {
                         wasi_try!(state.fs_create_dir(&adjusted_path));
+                        env.record_journal(|| {
+                            Some(JournalEntry::CreateDirectory {
+                                path: adjusted_path.clone(),
+                            })
+                        });
                     }
                     let kind = Kind::Dir {
                         parent: cur_dir_inode.downgrade(),
@@ -1,5 +1,8 @@
 use super::*;
-use crate::syscalls::*;
+use crate::{
+    audit::{AuditAction, AuditDecision, AuditEvent},
+    syscalls::*,
+};
 
 /// ### `path_open()`
 /// Open file located at the given path
@@ -37,6 +40,56 @@ pub fn path_open<M: MemorySize>(
     fs_rights_inheriting: Rights,
     fs_flags: Fdflags,
     fd: WasmPtr<WasiFd, M>,
+) -> Errno {
+    let audit = match ctx.data().control_plane.audit_log().cloned() {
+        Some(log) => {
+            let memory = ctx.data().memory_view(&ctx);
+            let path = unsafe { get_input_str!(&memory, path, path_len) };
+            Some((log, ctx.data().pid(), path))
+        }
+        None => None,
+    };
+
+    let ret = path_open_internal(
+        ctx,
+        dirfd,
+        dirflags,
+        path,
+        path_len,
+        o_flags,
+        fs_rights_base,
+        fs_rights_inheriting,
+        fs_flags,
+        fd,
+    );
+
+    // Opens relative to the virtual root reach past the directories that
+    // were preopened for the guest, denied opens are recorded regardless.
+    if let Some((log, pid, path)) = audit {
+        let decision = AuditDecision::from_errno(ret);
+        if dirfd == crate::VIRTUAL_ROOT_FD || decision == AuditDecision::Denied {
+            let action = AuditAction::FileOpen {
+                path,
+                write: fs_rights_base.contains(Rights::FD_WRITE),
+            };
+            log.record(AuditEvent::new(pid, action, decision));
+        }
+    }
+    ret
+}
+
+#[allow(clippy::too_many_arguments)]
+fn path_open_internal<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    dirfd: WasiFd,
+    dirflags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    o_flags: Oflags,
+    fs_rights_base: Rights,
+    fs_rights_inheriting: Rights,
+    fs_flags: Fdflags,
+    fd: WasmPtr<WasiFd, M>,
 ) -> Errno {
     if dirflags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0 {
         Span::current().record("follow_symlinks", true);
@@ -324,6 +377,25 @@ pub fn path_open<M: MemorySize>(
 
     Span::current().record("ret_fd", out_fd);
 
+    if let Some(opened) = env
+        .journal
+        .as_ref()
+        .and_then(|_| crate::journal::fd_entry(env, out_fd))
+    {
+        // Only files that are known to be empty can be journaled as such
+        let emptied = o_flags.contains(Oflags::TRUNC)
+            || (o_flags.contains(Oflags::CREATE) && o_flags.contains(Oflags::EXCL));
+        if emptied && !opened.is_dir {
+            env.record_journal(|| {
+                Some(JournalEntry::SetFileLength {
+                    path: opened.path.clone(),
+                    len: 0,
+                })
+            });
+        }
+        env.record_journal(|| Some(JournalEntry::OpenFd(opened)));
+    }
+
     wasi_try_mem!(fd_ref.write(out_fd));
     Errno::Success
 }
@@ -64,7 +64,7 @@ pub fn path_remove_directory<M: MemorySize>(
         }
     }
 
-    if let Err(err) = state.fs_remove_dir(host_path_to_remove) {
+    if let Err(err) = state.fs_remove_dir(&host_path_to_remove) {
         // reinsert to prevent FS from being in bad state
         let mut guard = parent_inode.write();
         if let Kind::Dir {
@@ -75,6 +75,11 @@ pub fn path_remove_directory<M: MemorySize>(
         }
         return err;
     }
+    env.record_journal(|| {
+        Some(JournalEntry::RemoveDirectory {
+            path: host_path_to_remove,
+        })
+    });
 
     Errno::Success
 }
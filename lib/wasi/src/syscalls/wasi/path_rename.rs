@@ -108,6 +108,13 @@ pub fn path_rename<M: MemorySize>(
         }
     };
 
+    let renamed = match source_entry.read().deref() {
+        Kind::File { path, .. } | Kind::Dir { path, .. } => {
+            Some((path.clone(), host_adjusted_target_path.clone()))
+        }
+        _ => None,
+    };
+
     {
         let mut guard = source_entry.write();
         match guard.deref_mut() {
@@ -178,5 +185,9 @@ pub fn path_rename<M: MemorySize>(
         }
     }
 
+    if let Some((from, to)) = renamed {
+        env.record_journal(|| Some(JournalEntry::Rename { from, to }));
+    }
+
     Errno::Success
 }
@@ -69,6 +69,7 @@ pub fn path_unlink_file<M: MemorySize>(
             let mut guard = removed_inode.read();
             match guard.deref() {
                 Kind::File { handle, path, .. } => {
+                    let removed = path.clone();
                     if let Some(h) = handle {
                         let mut h = h.write().unwrap();
                         wasi_try!(h.unlink().map_err(fs_error_into_wasi_err));
@@ -80,6 +81,7 @@ pub fn path_unlink_file<M: MemorySize>(
                         drop(guard);
                         wasi_try!(state.fs_remove_file(path));
                     }
+                    env.record_journal(|| Some(JournalEntry::RemoveFile { path: removed }));
                 }
                 Kind::Dir { .. } | Kind::Root { .. } => return Errno::Isdir,
                 Kind::Symlink { .. } => {
@@ -30,12 +30,35 @@ pub fn poll_oneoff<M: MemorySize>(
     nsubscriptions: M::Offset,
     nevents: WasmPtr<M::Offset, M>,
 ) -> Result<Errno, WasiError> {
+    maybe_snapshot!(ctx, M);
+
     wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
 
     ctx.data_mut().poll_seed += 1;
     let mut env = ctx.data();
     let mut memory = env.memory_view(&ctx);
 
+    if let Some(entry) = env.replayed_input(JournalEntry::Poll {
+        result: Ok(Vec::new()),
+    }) {
+        let events = match wasi_try_ok!(entry) {
+            JournalEntry::Poll { result: Ok(events) } => events,
+            JournalEntry::Poll { result: Err(code) } => return Ok(crate::journal::errno(code)),
+            _ => return Ok(Errno::Notrecoverable),
+        };
+        // The events are written where they were when the run was journaled
+        let events_seen = events.len() / std::mem::size_of::<Event>();
+        let max_events: u64 = nsubscriptions.into();
+        if events_seen as u64 > max_events {
+            return Ok(Errno::Notrecoverable);
+        }
+        wasi_try_mem_ok!(memory.write(out_.offset().into(), &events));
+        let events_seen: M::Offset =
+            wasi_try_ok!(events_seen.try_into().map_err(|_| Errno::Overflow));
+        wasi_try_mem_ok!(nevents.deref(&memory).write(events_seen));
+        return Ok(Errno::Success);
+    }
+
     let subscription_array = wasi_try_mem_ok!(in_.slice(&memory, nsubscriptions));
     let mut subscriptions = Vec::with_capacity(subscription_array.len() as usize);
     for n in 0..subscription_array.len() {
@@ -49,7 +72,16 @@ pub fn poll_oneoff<M: MemorySize>(
     let triggered_events = poll_oneoff_internal(&mut ctx, subscriptions)?;
     let triggered_events = match triggered_events {
         Ok(a) => a,
+        // The subscriptions are polled again once the process is thawed
+        Err(Errno::Intr) if ctx.data().park_requested() => {
+            return crate::parking::unwind_and_park::<M>(ctx);
+        }
         Err(err) => {
+            ctx.data().record_input(|| {
+                Some(JournalEntry::Poll {
+                    result: Err(err as u16),
+                })
+            });
             return Ok(err);
         }
     };
@@ -63,6 +95,11 @@ pub fn poll_oneoff<M: MemorySize>(
         wasi_try_mem_ok!(event_array.index(events_seen as u64).write(event));
         events_seen += 1;
     }
+    env.record_input(|| {
+        let mut events = vec![0; events_seen as usize * std::mem::size_of::<Event>()];
+        memory.read(out_.offset().into(), &mut events).ok()?;
+        Some(JournalEntry::Poll { result: Ok(events) })
+    });
     let events_seen: M::Offset = wasi_try_ok!(events_seen.try_into().map_err(|_| Errno::Overflow));
     let out_ptr = nevents.deref(&memory);
     wasi_try_mem_ok!(out_ptr.write(events_seen));
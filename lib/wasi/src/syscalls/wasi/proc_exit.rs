@@ -78,5 +78,10 @@ pub fn proc_exit<M: MemorySize>(
     }
 
     // Otherwise just exit
+    ctx.data().record_journal(|| {
+        Some(JournalEntry::Exit {
+            code: code.raw() as u32,
+        })
+    });
     Err(WasiError::Exit(code))
 }
@@ -18,7 +18,29 @@ pub fn random_get<M: MemorySize>(
     let memory = env.memory_view(&ctx);
     let buf_len64: u64 = buf_len.into();
     let mut u8_buffer = vec![0; buf_len64 as usize];
-    let res = getrandom::getrandom(&mut u8_buffer);
+    let replayed = env.replayed_input(JournalEntry::Random {
+        data: u8_buffer.clone(),
+    });
+    let res = match replayed {
+        Some(entry) => match wasi_try!(entry) {
+            JournalEntry::Random { data } => {
+                u8_buffer = data;
+                Ok(())
+            }
+            _ => return Errno::Notrecoverable,
+        },
+        None => {
+            let res = getrandom::getrandom(&mut u8_buffer);
+            if res.is_ok() {
+                env.record_input(|| {
+                    Some(JournalEntry::Random {
+                        data: u8_buffer.clone(),
+                    })
+                });
+            }
+            res
+        }
+    };
     match res {
         Ok(()) => {
             let buf = wasi_try_mem!(buf.slice(&memory, buf_len));
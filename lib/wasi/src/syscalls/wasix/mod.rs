@@ -34,7 +34,9 @@ mod sock_accept;
 mod sock_addr_local;
 mod sock_addr_peer;
 mod sock_bind;
+mod sock_bind_unix;
 mod sock_connect;
+mod sock_connect_unix;
 mod sock_get_opt_flag;
 mod sock_get_opt_size;
 mod sock_get_opt_time;
@@ -44,9 +46,12 @@ mod sock_leave_multicast_v4;
 mod sock_leave_multicast_v6;
 mod sock_listen;
 mod sock_open;
+mod sock_pair;
 mod sock_recv;
+mod sock_recv_fds;
 mod sock_recv_from;
 mod sock_send;
+mod sock_send_fds;
 mod sock_send_file;
 mod sock_send_to;
 mod sock_set_opt_flag;
@@ -106,7 +111,9 @@ pub use sock_accept::*;
 pub use sock_addr_local::*;
 pub use sock_addr_peer::*;
 pub use sock_bind::*;
+pub use sock_bind_unix::*;
 pub use sock_connect::*;
+pub use sock_connect_unix::*;
 pub use sock_get_opt_flag::*;
 pub use sock_get_opt_size::*;
 pub use sock_get_opt_time::*;
@@ -116,9 +123,12 @@ pub use sock_leave_multicast_v4::*;
 pub use sock_leave_multicast_v6::*;
 pub use sock_listen::*;
 pub use sock_open::*;
+pub use sock_pair::*;
 pub use sock_recv::*;
+pub use sock_recv_fds::*;
 pub use sock_recv_from::*;
 pub use sock_send::*;
+pub use sock_send_fds::*;
 pub use sock_send_file::*;
 pub use sock_send_to::*;
 pub use sock_set_opt_flag::*;
@@ -1,7 +1,10 @@
 use virtual_fs::Pipe;
 
 use super::*;
-use crate::syscalls::*;
+use crate::{
+    audit::{AuditAction, AuditDecision, AuditEvent},
+    syscalls::*,
+};
 
 /// Spawns a new process within the context of this machine
 ///
@@ -68,7 +71,15 @@ pub fn proc_spawn<M: MemorySize>(
         .filter(|a| !a.is_empty())
         .collect();
 
-    let (handles, ctx) = match proc_spawn_internal(
+    let audit = ctx.data().control_plane.audit_log().cloned().map(|log| {
+        let action = AuditAction::ProcSpawn {
+            name: name.clone(),
+            args: args.clone(),
+        };
+        (log, ctx.data().pid(), action)
+    });
+
+    let ret = proc_spawn_internal(
         ctx,
         name,
         Some(args),
@@ -77,7 +88,15 @@ pub fn proc_spawn<M: MemorySize>(
         stdin,
         stdout,
         stderr,
-    )? {
+    )?;
+    if let Some((log, pid, action)) = audit {
+        let decision = match &ret {
+            Err(BusErrno::Denied) => AuditDecision::Denied,
+            _ => AuditDecision::Allowed,
+        };
+        log.record(AuditEvent::new(pid, action, decision));
+    }
+    let (handles, ctx) = match ret {
         Ok(a) => a,
         Err(err) => {
             return Ok(err);
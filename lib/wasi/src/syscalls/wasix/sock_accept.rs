@@ -21,8 +21,13 @@ pub fn sock_accept<M: MemorySize>(
     ro_fd: WasmPtr<WasiFd, M>,
     ro_addr: WasmPtr<__wasi_addr_port_t, M>,
 ) -> Result<Errno, WasiError> {
+    maybe_snapshot!(ctx, M);
+
     wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
 
+    let limiter = ctx.data().control_plane.socket_limiter().clone();
+    let permit = wasi_try_ok!(limiter.acquire_socket());
+
     let tasks = ctx.data().tasks().clone();
     let (child, addr, fd_flags) = wasi_try_ok!(__sock_asyncify(
         ctx.data(),
@@ -38,6 +43,7 @@ pub fn sock_accept<M: MemorySize>(
                 .map(|a| (a.0, a.1, fd_flags))
         }
     ));
+    wasi_try_ok!(limiter.register_connection());
 
     let env = ctx.data();
     let (memory, state, inodes) = env.get_memory_and_wasi_state_and_inodes(&ctx, 0);
@@ -47,7 +53,8 @@ pub fn sock_accept<M: MemorySize>(
             socket: child,
             write_timeout: None,
             read_timeout: None,
-        }),
+        })
+        .with_permit(permit),
     };
     let inode = state
         .fs
@@ -66,6 +73,15 @@ pub fn sock_accept<M: MemorySize>(
     let rights = Rights::all_socket();
     let fd = wasi_try_ok!(state.fs.create_fd(rights, rights, new_flags, 0, inode));
     Span::current().record("fd", fd);
+    env.record_journal(|| {
+        Some(JournalEntry::Socket {
+            fd,
+            event: SocketEvent::Accepted {
+                listener: sock,
+                peer: addr,
+            },
+        })
+    });
 
     wasi_try_mem_ok!(ro_fd.write(&memory, fd));
     wasi_try_ok!(crate::net::write_ip_port(
@@ -30,6 +30,12 @@ pub fn sock_bind<M: MemorySize>(
         Rights::SOCK_BIND,
         move |socket| async move { socket.bind(tasks.deref(), net.deref(), addr).await }
     ));
+    ctx.data().record_journal(|| {
+        Some(JournalEntry::Socket {
+            fd: sock,
+            event: SocketEvent::Bound { addr },
+        })
+    });
 
     Errno::Success
 }
@@ -0,0 +1,36 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `sock_bind_unix()`
+/// Bind a Unix domain socket to a name
+/// Note: This is similar to `bind` in POSIX using PF_UNIX
+///
+/// ## Parameters
+///
+/// * `fd` - File descriptor of the socket to be bind
+/// * `path` - Name of the socket, a leading nul byte denotes an abstract name
+/// * `path_len` - Length of the name
+#[instrument(level = "debug", skip_all, fields(%sock, addr = field::Empty), ret)]
+pub fn sock_bind_unix<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state, inodes) = env.get_memory_and_wasi_state_and_inodes(&ctx, 0);
+    let addr = wasi_try!(crate::net::read_unix_addr(
+        &memory, &state.fs, inodes, path, path_len
+    ));
+    Span::current().record("addr", &format!("{:?}", addr));
+
+    let unix_sockets = env.control_plane.unix_sockets().clone();
+    wasi_try!(__sock_upgrade(
+        &mut ctx,
+        sock,
+        Rights::SOCK_BIND,
+        move |socket| async move { socket.bind_unix(&unix_sockets, addr) }
+    ));
+
+    Errno::Success
+}
@@ -1,5 +1,8 @@
 use super::*;
-use crate::syscalls::*;
+use crate::{
+    audit::{AuditAction, AuditDecision, AuditEvent},
+    syscalls::*,
+};
 
 /// ### `sock_connect()`
 /// Initiate a connection on a socket to the specified address
@@ -26,13 +29,45 @@ pub fn sock_connect<M: MemorySize>(
     let addr = SocketAddr::new(addr.0, addr.1);
     Span::current().record("addr", &format!("{:?}", addr));
 
+    let pid = env.pid();
+    let audit_log = env.control_plane.audit_log().cloned();
+    let audit = move |decision| {
+        if let Some(log) = audit_log.as_ref() {
+            let action = AuditAction::NetConnect { addr };
+            log.record(AuditEvent::new(pid, action, decision));
+        }
+    };
+
+    let pending = match env.control_plane.socket_limiter().begin_connect() {
+        Ok(pending) => pending,
+        Err(err) => {
+            audit(AuditDecision::Denied);
+            return err;
+        }
+    };
+
     let tasks = ctx.data().tasks().clone();
-    wasi_try!(__sock_upgrade(
+    let ret = __sock_upgrade(
         &mut ctx,
         sock,
         Rights::SOCK_CONNECT,
-        move |mut socket| async move { socket.connect(tasks.deref(), net.deref(), addr, None).await }
-    ));
+        move |mut socket| async move {
+            let ret = socket.connect(tasks.deref(), net.deref(), addr, None).await;
+            drop(pending);
+            ret
+        },
+    );
+    match ret {
+        Ok(()) => audit(AuditDecision::Allowed),
+        Err(err) => audit(AuditDecision::from_errno(err)),
+    }
+    wasi_try!(ret);
+    ctx.data().record_journal(|| {
+        Some(JournalEntry::Socket {
+            fd: sock,
+            event: SocketEvent::Connected { peer: addr },
+        })
+    });
 
     Errno::Success
 }
@@ -0,0 +1,38 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `sock_connect_unix()`
+/// Initiate a connection on a Unix domain socket to the specified name
+///
+/// Note: This is similar to `connect` in POSIX using PF_UNIX
+///
+/// ## Parameters
+///
+/// * `fd` - Socket descriptor
+/// * `path` - Name of the socket to connect to, a leading nul byte denotes
+///   an abstract name
+/// * `path_len` - Length of the name
+#[instrument(level = "debug", skip_all, fields(%sock, addr = field::Empty), ret)]
+pub fn sock_connect_unix<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state, inodes) = env.get_memory_and_wasi_state_and_inodes(&ctx, 0);
+    let addr = wasi_try!(crate::net::read_unix_addr(
+        &memory, &state.fs, inodes, path, path_len
+    ));
+    Span::current().record("addr", &format!("{:?}", addr));
+
+    let unix_sockets = env.control_plane.unix_sockets().clone();
+    wasi_try!(__sock_upgrade(
+        &mut ctx,
+        sock,
+        Rights::SOCK_CONNECT,
+        move |mut socket| async move { socket.connect_unix(&unix_sockets, addr) }
+    ));
+
+    Errno::Success
+}
@@ -21,6 +21,7 @@ pub fn sock_listen<M: MemorySize>(
 ) -> Errno {
     let env = ctx.data();
     let net = env.net().clone();
+    let unix_sockets = env.control_plane.unix_sockets().clone();
     let backlog: usize = wasi_try!(backlog.try_into().map_err(|_| Errno::Inval));
 
     let tasks = ctx.data().tasks().clone();
@@ -28,8 +29,18 @@ pub fn sock_listen<M: MemorySize>(
         &mut ctx,
         sock,
         Rights::SOCK_LISTEN,
-        |socket| async move { socket.listen(tasks.deref(), net.deref(), backlog).await }
+        |socket| async move {
+            socket
+                .listen(tasks.deref(), net.deref(), &unix_sockets, backlog)
+                .await
+        }
     ));
+    ctx.data().record_journal(|| {
+        Some(JournalEntry::Socket {
+            fd: sock,
+            event: SocketEvent::Listening,
+        })
+    });
 
     Errno::Success
 }
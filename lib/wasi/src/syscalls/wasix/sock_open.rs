@@ -30,6 +30,7 @@ pub fn sock_open<M: MemorySize>(
 ) -> Errno {
     let env = ctx.data();
     let (memory, state, inodes) = env.get_memory_and_wasi_state_and_inodes(&ctx, 0);
+    let permit = wasi_try!(env.control_plane.socket_limiter().acquire_socket());
 
     let kind = match ty {
         Socktype::Stream | Socktype::Dgram => Kind::Socket {
@@ -38,16 +39,19 @@ pub fn sock_open<M: MemorySize>(
                 ty,
                 pt,
                 addr: None,
+                unix_addr: None,
                 only_v6: false,
                 reuse_port: false,
                 reuse_addr: false,
+                broadcast: false,
                 send_buf_size: None,
                 recv_buf_size: None,
                 write_timeout: None,
                 read_timeout: None,
                 accept_timeout: None,
                 connect_timeout: None,
-            }),
+            })
+            .with_permit(permit),
         },
         _ => return Errno::Notsup,
     };
@@ -61,6 +65,15 @@ pub fn sock_open<M: MemorySize>(
         .fs
         .create_fd(rights, rights, Fdflags::empty(), 0, inode));
     Span::current().record("sock", fd);
+    env.record_journal(|| {
+        Some(JournalEntry::Socket {
+            fd,
+            event: SocketEvent::Opened {
+                family: af as u16,
+                ty: ty as u16,
+            },
+        })
+    });
 
     wasi_try_mem!(ro_sock.write(&memory, fd));
 
@@ -0,0 +1,58 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `sock_pair()`
+/// Create a pair of connected Unix domain stream sockets
+///
+/// Note: This is similar to `socketpair` in POSIX using PF_UNIX
+///
+/// ## Return
+///
+/// The file descriptors of both ends of the connection.
+#[instrument(level = "debug", skip_all, fields(fd1 = field::Empty, fd2 = field::Empty), ret)]
+pub fn sock_pair<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    ro_sock1: WasmPtr<WasiFd, M>,
+    ro_sock2: WasmPtr<WasiFd, M>,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state, inodes) = env.get_memory_and_wasi_state_and_inodes(&ctx, 0);
+
+    let limiter = env.control_plane.socket_limiter();
+    let permit1 = wasi_try!(limiter.acquire_socket());
+    let permit2 = wasi_try!(limiter.acquire_socket());
+
+    let (socket1, socket2) = env.control_plane.unix_sockets().pair();
+
+    let mut fds = Vec::with_capacity(2);
+    for (socket, permit) in [(socket1, permit1), (socket2, permit2)] {
+        let kind = Kind::Socket {
+            socket: InodeSocket::new(InodeSocketKind::TcpStream {
+                socket: Box::new(socket),
+                write_timeout: None,
+                read_timeout: None,
+            })
+            .with_permit(permit),
+        };
+        let inode = state.fs.create_inode_with_default_stat(
+            inodes,
+            kind,
+            false,
+            "socket".to_string().into(),
+        );
+        let rights = Rights::all_socket();
+        fds.push(wasi_try!(state.fs.create_fd(
+            rights,
+            rights,
+            Fdflags::empty(),
+            0,
+            inode
+        )));
+    }
+    Span::current().record("fd1", fds[0]).record("fd2", fds[1]);
+
+    wasi_try_mem!(ro_sock1.write(&memory, fds[0]));
+    wasi_try_mem!(ro_sock2.write(&memory, fds[1]));
+
+    Errno::Success
+}
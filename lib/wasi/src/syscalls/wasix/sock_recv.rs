@@ -59,6 +59,9 @@ pub fn sock_recv<M: MemorySize>(
     Span::current().record("nread", bytes_read);
 
     let env = ctx.data();
+    if let Some(metrics) = env.control_plane.metrics() {
+        metrics.net_received(bytes_read as u64);
+    }
     let memory = env.memory_view(&ctx);
 
     let bytes_read: M::Offset = wasi_try_ok!(bytes_read.try_into().map_err(|_| Errno::Overflow));
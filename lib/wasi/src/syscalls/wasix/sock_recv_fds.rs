@@ -0,0 +1,90 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `sock_recv_fds()`
+/// Receives the next batch of file descriptors that were passed by the peer
+/// of a connected Unix domain socket. If the buffer is too small to hold all
+/// of them then the remaining file descriptors are discarded.
+///
+/// Note: This is similar to `recvmsg` in POSIX with `SCM_RIGHTS`
+///
+/// ## Parameters
+///
+/// * `fd` - Socket descriptor
+/// * `fds` - Buffer that will receive the file descriptors
+/// * `fds_len` - Capacity of the buffer
+///
+/// ## Return
+///
+/// Number of file descriptors that were received
+#[instrument(level = "debug", skip_all, fields(%sock, nfds = field::Empty), ret)]
+pub fn sock_recv_fds<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    fds: WasmPtr<WasiFd, M>,
+    fds_len: M::Offset,
+    ro_nfds: WasmPtr<M::Offset, M>,
+) -> Errno {
+    let rights = wasi_try!(__sock_actor(
+        &mut ctx,
+        sock,
+        Rights::SOCK_RECV,
+        move |socket, _| socket.recv_rights()
+    ))
+    .unwrap_or_default();
+
+    let env = ctx.data();
+    let (memory, state) = env.get_memory_and_wasi_state(&ctx, 0);
+    let fds = wasi_try_mem!(fds.slice(&memory, fds_len));
+
+    let passed: Vec<Fd> = rights
+        .iter()
+        .filter_map(|right| right.downcast_ref::<Fd>().cloned())
+        .collect();
+    if passed.len() as u64 > fds.len() {
+        tracing::debug!("discarding passed file descriptors as the buffer is too small");
+    }
+
+    let mut created = Vec::with_capacity(passed.len());
+    let mut ret = Ok(());
+    for passed in passed.iter().take(fds.len() as usize) {
+        match state.fs.create_fd(
+            passed.rights,
+            passed.rights_inheriting,
+            passed.flags,
+            passed.open_flags,
+            passed.inode.clone(),
+        ) {
+            Ok(fd) => created.push(fd),
+            Err(err) => {
+                ret = Err(err);
+                break;
+            }
+        }
+    }
+    let ret = ret.and_then(|()| {
+        for (idx, fd) in created.iter().enumerate() {
+            fds.index(idx as u64)
+                .write(*fd)
+                .map_err(crate::mem_error_to_wasi)?;
+        }
+        let nfds: M::Offset = (created.len() as u64)
+            .try_into()
+            .map_err(|_| Errno::Overflow)?;
+        ro_nfds
+            .write(&memory, nfds)
+            .map_err(crate::mem_error_to_wasi)
+    });
+
+    // The guest never learns about the descriptors if any of them failed,
+    // so none of them may be left open
+    if let Err(err) = ret {
+        for fd in created {
+            state.fs.close_fd(fd).ok();
+        }
+        return err;
+    }
+    Span::current().record("nfds", created.len());
+
+    Errno::Success
+}
@@ -100,6 +100,9 @@ pub fn sock_recv_from<M: MemorySize>(
     Span::current()
         .record("nread", bytes_read)
         .record("peer", &format!("{:?}", peer));
+    if let Some(metrics) = env.control_plane.metrics() {
+        metrics.net_received(bytes_read as u64);
+    }
 
     wasi_try_ok!(write_ip_port(&memory, ro_addr, peer.ip(), peer.port()));
 
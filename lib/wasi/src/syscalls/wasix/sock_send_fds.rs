@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use super::*;
+use crate::syscalls::*;
+
+/// ### `sock_send_fds()`
+/// Passes file descriptors to the peer of a connected Unix domain socket.
+/// The peer receives duplicates of the file descriptors via `sock_recv_fds`
+///
+/// Note: This is similar to `sendmsg` in POSIX with `SCM_RIGHTS`
+///
+/// ## Parameters
+///
+/// * `fd` - Socket descriptor
+/// * `fds` - List of file descriptors to pass to the peer
+/// * `fds_len` - Number of file descriptors in the list
+#[instrument(level = "debug", skip_all, fields(%sock, nfds = field::Empty), ret)]
+pub fn sock_send_fds<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    fds: WasmPtr<WasiFd, M>,
+    fds_len: M::Offset,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state) = env.get_memory_and_wasi_state(&ctx, 0);
+    let fds = wasi_try_mem!(fds.slice(&memory, fds_len));
+    let fds = wasi_try_mem!(fds.read_to_vec());
+    Span::current().record("nfds", fds.len());
+
+    let mut rights = Vec::with_capacity(fds.len());
+    for fd in fds {
+        let fd = wasi_try!(state.fs.get_fd(fd));
+        rights.push(Arc::new(fd) as Arc<dyn std::any::Any + Send + Sync>);
+    }
+
+    wasi_try!(__sock_actor(
+        &mut ctx,
+        sock,
+        Rights::SOCK_SEND,
+        move |socket, _| socket.send_rights(rights)
+    ));
+
+    Errno::Success
+}
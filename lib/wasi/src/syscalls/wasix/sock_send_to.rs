@@ -72,6 +72,9 @@ pub fn sock_send_to<M: MemorySize>(
         ))
     };
     Span::current().record("nsent", bytes_written);
+    if let Some(metrics) = env.control_plane.metrics() {
+        metrics.net_sent(bytes_written as u64);
+    }
 
     let memory = env.memory_view(&ctx);
     let bytes_written: M::Offset =
@@ -148,6 +148,10 @@ pub(crate) fn thread_spawn_internal<M: MemorySize>(
                     debug!("failed as wasi version is unknown",);
                     ret = Errno::Noexec;
                 }
+                Ok(err @ WasiError::UnsupportedMemory64(_)) => {
+                    debug!("failed as {}", err);
+                    ret = Errno::Noexec;
+                }
                 Err(err) => {
                     debug!("failed with runtime error: {}", err);
                     ret = Errno::Noexec;
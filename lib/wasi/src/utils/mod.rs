@@ -10,6 +10,8 @@ use std::collections::BTreeSet;
 use wasmer::Module;
 use wasmer_wasix_types::wasi::Errno;
 
+use crate::WasiError;
+
 pub use self::thread_parker::WasiParkingLot;
 pub(crate) use owned_mutex_guard::{
     read_owned, write_owned, OwnedRwLockReadGuard, OwnedRwLockWriteGuard,
@@ -211,10 +213,61 @@ pub fn get_wasi_versions(module: &Module, strict: bool) -> Option<BTreeSet<WasiV
     }
 }
 
+/// Checks that the WASI versions imported by a module can address its
+/// memory.
+///
+/// Only `wasix_64v1` passes pointers as 64-bit integers, so it is the only
+/// version that modules with a 64-bit memory can use.
+pub(crate) fn check_memory64(module: &Module) -> Result<(), WasiError> {
+    let memory64 = module.exports().memories().any(|m| m.ty().is_memory64())
+        || module.imports().memories().any(|m| m.ty().is_memory64());
+    if !memory64 {
+        return Ok(());
+    }
+    let versions = get_wasi_versions(module, false).unwrap_or_default();
+    match versions
+        .into_iter()
+        .find(|version| *version != WasiVersion::Wasix64v1)
+    {
+        Some(version) => Err(WasiError::UnsupportedMemory64(version)),
+        None => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn memory64_needs_wasix_64v1() {
+        let mut features = wasmer::Features::default();
+        features.memory64(true);
+        let engine = wasmer::EngineBuilder::new(wasmer::Cranelift::default())
+            .set_features(Some(features))
+            .engine();
+        let module = |namespace: &str, index: &str| {
+            let wat = format!(
+                r#"(module
+                    (import "{}" "proc_exit" (func (param i32)))
+                    (memory (export "memory") {} 1))"#,
+                namespace, index
+            );
+            Module::new(&engine, wat).unwrap()
+        };
+
+        assert_eq!(check_memory64(&module(SNAPSHOT1_NAMESPACE, "")), Ok(()));
+        assert_eq!(check_memory64(&module(WASIX_64V1_NAMESPACE, "i64")), Ok(()));
+        assert_eq!(
+            check_memory64(&module(SNAPSHOT1_NAMESPACE, "i64")),
+            Err(WasiError::UnsupportedMemory64(WasiVersion::Snapshot1))
+        );
+        assert_eq!(
+            check_memory64(&module(WASIX_32V1_NAMESPACE, "i64")),
+            Err(WasiError::UnsupportedMemory64(WasiVersion::Wasix32v1))
+        );
+    }
+
     #[test]
     fn wasi_version_equality() {
         assert_eq!(WasiVersion::Snapshot0, WasiVersion::Snapshot0);
@@ -0,0 +1,159 @@
+use std::path::Path;
+
+use virtual_fs::{FileSystem, TmpFileSystem};
+use wasmer::{Module, Store};
+use wasmer_wasix::WasiEnv;
+
+/// Runs a module with `/tmp` in its file system, every failed check exits
+/// with the number of the check
+fn run(wat: &str) {
+    let mut store = Store::default();
+    let module = Module::new(&store, wat).unwrap();
+
+    let fs = TmpFileSystem::new();
+    fs.create_dir(Path::new("/tmp")).unwrap();
+    let mut builder = WasiEnv::builder("unix-sockets").sandbox_fs(fs);
+    builder.preopen_vfs_dirs(["/".to_string()]).unwrap();
+
+    let ret = std::thread::spawn(move || builder.run_with_store(module, &mut store))
+        .join()
+        .unwrap();
+    if let Err(err) = ret {
+        panic!("check {:?} failed - {}", err.as_exit_code(), err);
+    }
+}
+
+/// Imports and helpers shared by the test modules.
+///
+/// Memory layout: 0 listener, 4 client, 8 accepted socket, 12 and 16 the
+/// ends of a pair, 20 number of received fds, 24 received fds, 64 and 80
+/// iovecs, 96 bytes written or read, 100 and 120 socket paths, 160 "ping",
+/// 200 read buffer, 256 peer address.
+const PRELUDE: &str = r#"
+    (import "wasix_32v1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "proc_exit" (func $proc_exit (param i32)))
+    (import "wasix_32v1" "sock_open" (func $sock_open (param i32 i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_bind_unix" (func $sock_bind_unix (param i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_listen" (func $sock_listen (param i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_accept" (func $sock_accept (param i32 i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_connect_unix" (func $sock_connect_unix (param i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_pair" (func $sock_pair (param i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_send_fds" (func $sock_send_fds (param i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_recv_fds" (func $sock_recv_fds (param i32 i32 i32 i32) (result i32)))
+
+    (memory (export "memory") 1)
+    (data (i32.const 64) "\a0\00\00\00\04\00\00\00")
+    (data (i32.const 80) "\c8\00\00\00\10\00\00\00")
+    (data (i32.const 100) "/tmp/app.sock")
+    (data (i32.const 120) "/tmp/../tmp/app.sock")
+    (data (i32.const 160) "ping")
+
+    ;; Exits with `code` unless `errno` is zero
+    (func $check (param $errno i32) (param $code i32)
+        (if (local.get $errno)
+            (then (call $proc_exit (local.get $code)))))
+
+    ;; Opens a Unix stream socket and stores it at `ptr`
+    (func $open (param $ptr i32) (param $code i32)
+        (call $check
+            (call $sock_open (i32.const 3) (i32.const 1) (i32.const 0) (local.get $ptr))
+            (local.get $code)))
+
+    ;; Sends "ping" on `fd` and checks that it comes out of `peer`
+    (func $ping (param $fd i32) (param $peer i32) (param $code i32)
+        (call $check
+            (call $fd_write (local.get $fd) (i32.const 64) (i32.const 1) (i32.const 96))
+            (local.get $code))
+        (call $check
+            (call $fd_read (local.get $peer) (i32.const 80) (i32.const 1) (i32.const 96))
+            (local.get $code))
+        (call $check (i32.ne (i32.load (i32.const 96)) (i32.const 4)) (local.get $code))
+        (call $check
+            (i32.ne (i32.load (i32.const 200)) (i32.load (i32.const 160)))
+            (local.get $code)))
+"#;
+
+#[test]
+fn sockets_are_named_by_their_path() {
+    run(&format!(
+        r#"(module {}
+            (func (export "_start")
+                (call $open (i32.const 0) (i32.const 1))
+                (call $check
+                    (call $sock_bind_unix (i32.load (i32.const 0)) (i32.const 100) (i32.const 13))
+                    (i32.const 2))
+                (call $check
+                    (call $sock_listen (i32.load (i32.const 0)) (i32.const 8))
+                    (i32.const 3))
+
+                ;; `..` leads to the same socket
+                (call $open (i32.const 4) (i32.const 4))
+                (call $check
+                    (call $sock_connect_unix (i32.load (i32.const 4)) (i32.const 120) (i32.const 20))
+                    (i32.const 5))
+                (call $check
+                    (call $sock_accept (i32.load (i32.const 0)) (i32.const 0) (i32.const 8) (i32.const 256))
+                    (i32.const 6))
+                (call $ping (i32.load (i32.const 4)) (i32.load (i32.const 8)) (i32.const 7))
+                (call $ping (i32.load (i32.const 8)) (i32.load (i32.const 4)) (i32.const 8))
+
+                ;; The name is taken, whichever way it is spelled
+                (call $open (i32.const 12) (i32.const 9))
+                (call $check
+                    (i32.ne
+                        (call $sock_bind_unix (i32.load (i32.const 12)) (i32.const 120) (i32.const 20))
+                        (i32.const 3)) ;; addrinuse
+                    (i32.const 10))))"#,
+        PRELUDE
+    ));
+}
+
+#[test]
+fn sockets_are_looked_up_in_existing_directories() {
+    run(&format!(
+        r#"(module {}
+            (data (i32.const 300) "/missing/app.sock")
+            (func (export "_start")
+                (call $open (i32.const 0) (i32.const 1))
+                (call $check
+                    (i32.ne
+                        (call $sock_bind_unix (i32.load (i32.const 0)) (i32.const 300) (i32.const 17))
+                        (i32.const 44)) ;; noent
+                    (i32.const 2))))"#,
+        PRELUDE
+    ));
+}
+
+#[test]
+fn file_descriptors_are_passed_over_socket_pairs() {
+    run(&format!(
+        r#"(module {}
+            (func (export "_start")
+                (call $check (call $sock_pair (i32.const 12) (i32.const 16)) (i32.const 1))
+                (call $ping (i32.load (i32.const 12)) (i32.load (i32.const 16)) (i32.const 2))
+
+                ;; A second pair, one end of which is passed over the first
+                (call $check (call $sock_pair (i32.const 4) (i32.const 8)) (i32.const 3))
+                (call $check
+                    (call $sock_send_fds (i32.load (i32.const 12)) (i32.const 4) (i32.const 1))
+                    (i32.const 4))
+                (call $check
+                    (call $sock_recv_fds (i32.load (i32.const 16)) (i32.const 24) (i32.const 4) (i32.const 20))
+                    (i32.const 5))
+                (call $check (i32.ne (i32.load (i32.const 20)) (i32.const 1)) (i32.const 6))
+                (call $check
+                    (i32.eq (i32.load (i32.const 24)) (i32.load (i32.const 4)))
+                    (i32.const 7))
+
+                ;; The received descriptor is the same socket
+                (call $ping (i32.load (i32.const 24)) (i32.load (i32.const 8)) (i32.const 8))
+
+                ;; Nothing else was passed
+                (call $check
+                    (call $sock_recv_fds (i32.load (i32.const 16)) (i32.const 24) (i32.const 4) (i32.const 20))
+                    (i32.const 9))
+                (call $check (i32.load (i32.const 20)) (i32.const 10))))"#,
+        PRELUDE
+    ));
+}
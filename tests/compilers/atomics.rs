@@ -0,0 +1,71 @@
+use anyhow::Result;
+use wasmer::*;
+
+const ATOMICS_WAT: &[u8] = br#"
+(module
+  (memory (export "memory") 1 1 shared)
+  (func (export "store32") (param i32 i32)
+    (i32.atomic.store (local.get 0) (local.get 1)))
+  (func (export "store64_32") (param i32 i64)
+    (i64.atomic.store32 (local.get 0) (local.get 1)))
+  (func (export "load32") (param i32) (result i32)
+    (i32.atomic.load (local.get 0)))
+  (func (export "load8") (param i32) (result i32)
+    (i32.atomic.load8_u (local.get 0)))
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.atomic.rmw.add (local.get 0) (local.get 1)))
+  (func (export "cmpxchg") (param i32 i32 i32) (result i32)
+    (i32.atomic.rmw.cmpxchg (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "wait") (param i32 i32 i64) (result i32)
+    (memory.atomic.wait32 (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "notify") (param i32 i32) (result i32)
+    (memory.atomic.notify (local.get 0) (local.get 1))))
+"#;
+
+#[compiler_test(atomics)]
+fn atomic_operations(mut config: crate::Config) -> Result<()> {
+    let mut features = Features::default();
+    features.threads(true);
+    config.set_features(features);
+    let mut store = config.store();
+    let module = Module::new(&store, wat2wasm(ATOMICS_WAT)?)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+
+    let store32: TypedFunction<(i32, i32), ()> =
+        instance.exports.get_typed_function(&store, "store32")?;
+    let store64_32: TypedFunction<(i32, i64), ()> =
+        instance.exports.get_typed_function(&store, "store64_32")?;
+    let load32: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "load32")?;
+    let load8: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "load8")?;
+    let add: TypedFunction<(i32, i32), i32> = instance.exports.get_typed_function(&store, "add")?;
+    let cmpxchg: TypedFunction<(i32, i32, i32), i32> =
+        instance.exports.get_typed_function(&store, "cmpxchg")?;
+    let wait: TypedFunction<(i32, i32, i64), i32> =
+        instance.exports.get_typed_function(&store, "wait")?;
+    let notify: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_typed_function(&store, "notify")?;
+
+    store32.call(&mut store, 8, 0x0102_0304)?;
+    assert_eq!(load32.call(&mut store, 8)?, 0x0102_0304);
+    assert_eq!(load8.call(&mut store, 9)?, 0x03);
+
+    assert_eq!(add.call(&mut store, 8, 1)?, 0x0102_0304);
+    assert_eq!(cmpxchg.call(&mut store, 8, 0, 7)?, 0x0102_0305);
+    assert_eq!(cmpxchg.call(&mut store, 8, 0x0102_0305, 7)?, 0x0102_0305);
+    assert_eq!(load32.call(&mut store, 8)?, 7);
+
+    store64_32.call(&mut store, 12, 0x1_0000_0009)?;
+    assert_eq!(load32.call(&mut store, 12)?, 9);
+
+    // Atomic accesses must be naturally aligned.
+    assert!(store32.call(&mut store, 10, 1).is_err());
+    assert!(store64_32.call(&mut store, 14, 1).is_err());
+    assert!(load32.call(&mut store, 9).is_err());
+
+    // "not-equal" and "timed-out" results.
+    assert_eq!(wait.call(&mut store, 8, 1, 0)?, 1);
+    assert_eq!(wait.call(&mut store, 8, 7, 1_000)?, 2);
+    // No thread is waiting, so nobody is woken up.
+    assert_eq!(notify.call(&mut store, 8, 1)?, 0);
+    Ok(())
+}
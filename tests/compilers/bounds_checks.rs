@@ -0,0 +1,64 @@
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &[u8] = br#"
+    (module
+        (memory (export "memory") 1 4)
+        (func (export "load") (param i32) (result i32)
+            (i32.load (local.get 0)))
+        (func (export "grow") (param i32) (result i32)
+            (memory.grow (local.get 0)))
+    )
+"#;
+
+fn check_bounds(store: &mut Store, module: &Module) -> Result<()> {
+    let instance = Instance::new(store, module, &imports! {})?;
+    let load: TypedFunction<i32, i32> = instance.exports.get_typed_function(store, "load")?;
+    let grow: TypedFunction<i32, i32> = instance.exports.get_typed_function(store, "grow")?;
+
+    assert_eq!(load.call(store, 0xfffc)?, 0);
+    assert!(load.call(store, 0xfffd).is_err());
+    assert!(load.call(store, 0x10000).is_err());
+
+    assert_eq!(grow.call(store, 1)?, 1);
+    assert_eq!(load.call(store, 0x1fffc)?, 0);
+    assert!(load.call(store, 0x1fffd).is_err());
+    Ok(())
+}
+
+#[compiler_test(bounds_checks)]
+fn out_of_bounds_accesses_trap(config: crate::Config) -> Result<()> {
+    let strategies = [
+        BoundsCheckStrategy::GuardPages,
+        BoundsCheckStrategy::Explicit,
+        BoundsCheckStrategy::ExplicitNoReservation,
+    ];
+    for strategy in strategies {
+        let mut engine = config.engine(config.compiler_config(config.canonicalize_nans));
+        let tunables = BaseTunables::for_target(engine.target()).with_bounds_checks(strategy);
+        engine.set_tunables(tunables);
+        let mut store = Store::new(engine);
+        let module = Module::new(&store, WAT)?;
+        check_bounds(&mut store, &module)?;
+    }
+    Ok(())
+}
+
+#[compiler_test(bounds_checks)]
+fn bounds_checks_are_kept_in_the_artifact(config: crate::Config) -> Result<()> {
+    let mut engine = config.engine(config.compiler_config(config.canonicalize_nans));
+    let tunables = BaseTunables::for_target(engine.target()).with_memory_bounds_checks(
+        MemoryIndex::from_u32(0),
+        BoundsCheckStrategy::ExplicitNoReservation,
+    );
+    engine.set_tunables(tunables);
+    let store = Store::new(engine);
+    let serialized = Module::new(&store, WAT)?.serialize()?;
+
+    // The memory is checked explicitly even where the engine would use
+    // guard pages.
+    let engine = config.engine(config.compiler_config(config.canonicalize_nans));
+    let mut store = Store::new(engine);
+    let module = unsafe { Module::deserialize(&store, serialized) }?;
+    check_bounds(&mut store, &module)
+}
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use wasmer::*;
+
+// The `if` of `select` is hinted unlikely, and the `br_if` of `skip` is
+// hinted likely. Offsets are relative to the start of each function body.
+const BRANCH_HINTS_WAT: &str = r#"
+(module
+  (func (export "select") (param i32) (result i32)
+    local.get 0
+    if (result i32)
+      i32.const 1
+    else
+      i32.const 2
+    end)
+  (func (export "skip") (param i32) (result i32)
+    block
+      local.get 0
+      br_if 0
+      i32.const 7
+      return
+    end
+    i32.const 9)
+  (@custom "metadata.code.branch_hint" "\02\00\01\03\01\00\01\01\05\01\01"))
+"#;
+
+#[compiler_test(branch_hints)]
+fn hinted_branches(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let module = Module::new(&store, BRANCH_HINTS_WAT)?;
+    assert_eq!(
+        module.custom_sections("metadata.code.branch_hint").count(),
+        1
+    );
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+
+    // Hints must not change the behavior on either side of the branch.
+    let select: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "select")?;
+    assert_eq!(select.call(&mut store, 1)?, 1);
+    assert_eq!(select.call(&mut store, 0)?, 2);
+    let skip: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "skip")?;
+    assert_eq!(skip.call(&mut store, 1)?, 9);
+    assert_eq!(skip.call(&mut store, 0)?, 7);
+    Ok(())
+}
+
+// Blocks that end with a trap are laid out as cold code.
+const TRAP_PATHS_WAT: &str = r#"
+(module
+  (func (export "checked_div") (param i32 i32) (result i32)
+    local.get 1
+    i32.eqz
+    if
+      unreachable
+    end
+    block
+      local.get 0
+      i32.const 0
+      i32.ge_s
+      br_if 0
+      unreachable
+    end
+    local.get 0
+    local.get 1
+    i32.div_u))
+"#;
+
+#[compiler_test(branch_hints)]
+fn cold_trap_paths(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let module = Module::new(&store, TRAP_PATHS_WAT)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+
+    let checked_div: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_typed_function(&store, "checked_div")?;
+    assert_eq!(checked_div.call(&mut store, 9, 3)?, 3);
+    assert!(checked_div.call(&mut store, 9, 0).is_err());
+    assert!(checked_div.call(&mut store, -9, 3).is_err());
+    Ok(())
+}
@@ -15,6 +15,7 @@ pub struct Config {
     pub features: Option<Features>,
     pub middlewares: Vec<Arc<dyn ModuleMiddleware>>,
     pub canonicalize_nans: bool,
+    pub relaxed_simd_deterministic: bool,
 }
 
 impl Config {
@@ -23,6 +24,7 @@ impl Config {
             compiler,
             features: None,
             canonicalize_nans: false,
+            relaxed_simd_deterministic: false,
             middlewares: vec![],
         }
     }
@@ -39,6 +41,10 @@ impl Config {
         self.canonicalize_nans = canonicalize_nans;
     }
 
+    pub fn set_relaxed_simd_deterministic(&mut self, deterministic: bool) {
+        self.relaxed_simd_deterministic = deterministic;
+    }
+
     pub fn store(&self) -> Store {
         let compiler_config = self.compiler_config(self.canonicalize_nans);
         let engine = self.engine(compiler_config);
@@ -71,6 +77,10 @@ impl Config {
             Compiler::Cranelift => {
                 let mut compiler = wasmer_compiler_cranelift::Cranelift::new();
                 compiler.canonicalize_nans(canonicalize_nans);
+                CompilerConfig::relaxed_simd_deterministic(
+                    &mut compiler,
+                    self.relaxed_simd_deterministic,
+                );
                 compiler.enable_verifier();
                 self.add_middlewares(&mut compiler);
                 Box::new(compiler)
@@ -79,6 +89,10 @@ impl Config {
             Compiler::LLVM => {
                 let mut compiler = wasmer_compiler_llvm::LLVM::new();
                 compiler.canonicalize_nans(canonicalize_nans);
+                CompilerConfig::relaxed_simd_deterministic(
+                    &mut compiler,
+                    self.relaxed_simd_deterministic,
+                );
                 compiler.enable_verifier();
                 self.add_middlewares(&mut compiler);
                 Box::new(compiler)
@@ -0,0 +1,70 @@
+use anyhow::Result;
+use wasmer::*;
+
+/// A triple for an architecture other than the host's.
+fn foreign_triple() -> Triple {
+    if cfg!(target_arch = "x86_64") {
+        "aarch64-unknown-linux-gnu".parse().unwrap()
+    } else {
+        "x86_64-unknown-linux-gnu".parse().unwrap()
+    }
+}
+
+#[compiler_test(cross_compile)]
+fn cross_compiled_artifact_roundtrip(config: crate::Config) -> Result<()> {
+    let wat = r#"
+        (module $name
+            (import "host" "log" (func (param i32)))
+            (memory (export "memory") 1)
+            (func (export "run") (param i32) (result i32)
+                local.get 0
+                call 0
+                local.get 0
+                i32.const 1
+                i32.add)
+        )
+    "#;
+    let triple = foreign_triple();
+    let compiler_config = config.compiler_config(config.canonicalize_nans);
+    let engine = EngineBuilder::new(compiler_config)
+        .set_target_triple(triple.clone(), CpuFeature::set())
+        .engine();
+    assert_eq!(engine.target().triple(), &triple);
+    assert!(engine
+        .target()
+        .cpu_features()
+        .is_superset(CpuFeature::baseline(&triple)));
+
+    let mut store = Store::new(engine);
+    let module = Module::new(&store, wat)?;
+    // The module can't run on this host.
+    let log = Function::new_typed(&mut store, |_: i32| {});
+    let imports = imports! { "host" => { "log" => log } };
+    assert!(matches!(
+        Instance::new(&mut store, &module, &imports),
+        Err(InstantiationError::DifferentArchOS)
+    ));
+
+    // A headless engine for the same target loads the artifact.
+    let serialized = module.serialize()?;
+    let target = Target::new(triple, CpuFeature::set());
+    let headless = EngineBuilder::headless().set_target(Some(target)).engine();
+    let deserialized = unsafe { Module::deserialize(&headless, serialized.clone())? };
+    assert_eq!(deserialized.name(), Some("name"));
+    assert_eq!(
+        deserialized.exports().collect::<Vec<_>>(),
+        module.exports().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        deserialized.imports().collect::<Vec<_>>(),
+        module.imports().collect::<Vec<_>>()
+    );
+
+    // The host engine rejects it instead of running foreign code.
+    let host_engine: Engine = config.engine_headless().into();
+    assert!(matches!(
+        unsafe { Module::deserialize(&host_engine, serialized) },
+        Err(DeserializeError::Incompatible(_))
+    ));
+    Ok(())
+}
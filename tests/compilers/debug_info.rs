@@ -0,0 +1,56 @@
+use anyhow::Result;
+use wasmer::*;
+
+/// Appends an empty `.debug_info` section to a module, as if it was
+/// compiled with debug info but without any compilation unit.
+fn with_debug_info(wasm: &[u8]) -> Vec<u8> {
+    let name = b".debug_info";
+    let mut wasm = wasm.to_vec();
+    wasm.push(0);
+    wasm.push(name.len() as u8 + 1);
+    wasm.push(name.len() as u8);
+    wasm.extend_from_slice(name);
+    wasm
+}
+
+#[compiler_test(debug_info)]
+fn debug_info_is_registered(config: crate::Config) -> Result<()> {
+    let wasm = with_debug_info(&wat2wasm(
+        br#"
+        (module
+            (func $double (param i32) (result i32)
+                (i32.add (local.get 0) (local.get 0)))
+            (func (export "quadruple") (param i32) (result i32)
+                (call $double (call $double (local.get 0))))
+        )
+        "#,
+    )?);
+
+    let plain = {
+        let engine = config.engine(config.compiler_config(config.canonicalize_nans));
+        Module::new(&Store::new(engine), &wasm)?.serialize()?
+    };
+
+    let mut engine = config.engine(config.compiler_config(config.canonicalize_nans));
+    engine.set_debug_info(true);
+    let mut store = Store::new(engine);
+    let module = Module::new(&store, &wasm)?;
+    let serialized = module.serialize()?;
+    // The translated sections are kept in the artifact.
+    assert!(serialized.len() > plain.len());
+
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let quadruple: TypedFunction<i32, i32> =
+        instance.exports.get_typed_function(&store, "quadruple")?;
+    assert_eq!(quadruple.call(&mut store, 3)?, 12);
+    drop(instance);
+    drop(module);
+
+    // The debug info is registered again when the artifact is loaded.
+    let module = unsafe { Module::deserialize(&store, serialized) }?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let quadruple: TypedFunction<i32, i32> =
+        instance.exports.get_typed_function(&store, "quadruple")?;
+    assert_eq!(quadruple.call(&mut store, 5)?, 20);
+    Ok(())
+}
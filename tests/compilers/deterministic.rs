@@ -1,35 +1,32 @@
 use anyhow::Result;
-use wasmer::{wat2wasm, Module, Store};
+use wasmer::{wat2wasm, Module};
 
-fn compile_and_compare(wasm: &[u8]) -> Result<()> {
-    let store = Store::default();
+fn compile_and_compare(config: crate::Config, wasm: &[u8]) -> Result<()> {
+    // Each compilation uses its own engine, as a cache would.
+    let store = config.store();
+    let first = Module::new(&store, wasm)?.serialize()?;
 
-    // compile for first time
-    let module = Module::new(&store, wasm)?;
-    let first = module.serialize()?;
-
-    // compile for second time
-    let module = Module::new(&store, wasm)?;
-    let second = module.serialize()?;
+    let store = config.store();
+    let second = Module::new(&store, wasm)?.serialize()?;
 
     assert!(first == second);
 
     Ok(())
 }
 
-#[test]
-fn deterministic_empty() -> Result<()> {
+#[compiler_test(deterministic)]
+fn deterministic_empty(config: crate::Config) -> Result<()> {
     let wasm_bytes = wat2wasm(
         br#"
     (module)
     "#,
     )?;
 
-    compile_and_compare(&wasm_bytes)
+    compile_and_compare(config, &wasm_bytes)
 }
 
-#[test]
-fn deterministic_table() -> Result<()> {
+#[compiler_test(deterministic)]
+fn deterministic_table(config: crate::Config) -> Result<()> {
     let wasm_bytes = wat2wasm(
         br#"
 (module
@@ -40,5 +37,38 @@ fn deterministic_table() -> Result<()> {
 "#,
     )?;
 
-    compile_and_compare(&wasm_bytes)
+    compile_and_compare(config, &wasm_bytes)
+}
+
+#[compiler_test(deterministic)]
+fn deterministic_segments_and_names(config: crate::Config) -> Result<()> {
+    // Passive segments and function names are kept in hash maps while
+    // compiling, which must not leak into the artifact.
+    let wasm_bytes = wat2wasm(
+        br#"
+(module
+  (import "env" "log" (func $log (param i32)))
+  (memory (export "memory") 1)
+  (table 4 funcref)
+  (data $a "first")
+  (data $b "second")
+  (data $c "third")
+  (elem $e1 func $f1 $f2)
+  (elem $e2 func $f2 $f3)
+  (elem $e3 func $f3 $f1)
+  (func $f1 (export "f1") (result i32)
+    (memory.init $a (i32.const 0) (i32.const 0) (i32.const 5))
+    (i32.const 1))
+  (func $f2 (export "f2") (result i32)
+    (table.init $e2 (i32.const 0) (i32.const 0) (i32.const 2))
+    (i32.const 2))
+  (func $f3 (export "f3") (param i32) (result i32)
+    (call $log (local.get 0))
+    (data.drop $b)
+    (elem.drop $e3)
+    (i32.add (local.get 0) (i32.const 3))))
+"#,
+    )?;
+
+    compile_and_compare(config, &wasm_bytes)
 }
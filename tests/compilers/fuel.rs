@@ -0,0 +1,80 @@
+use anyhow::Result;
+use wasmer_middlewares::Fuel;
+
+use std::sync::Arc;
+use wasmer::wasmparser::Operator;
+use wasmer::*;
+use wasmer_types::TrapCode;
+
+fn cost_always_one(_: &Operator) -> u64 {
+    1
+}
+
+fn instantiate(mut config: crate::Config, wat: &str) -> Result<(Store, Instance)> {
+    config
+        .middlewares
+        .push(Arc::new(Fuel::new(cost_always_one)));
+    let mut store = config.store();
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    Ok((store, instance))
+}
+
+#[compiler_test(fuel)]
+fn fuel_is_consumed_per_block(config: crate::Config) -> Result<()> {
+    let (mut store, instance) = instantiate(
+        config,
+        r#"(module
+        (func (export "add") (param i32 i32) (result i32)
+           (i32.add (local.get 0)
+                    (local.get 1))))"#,
+    )?;
+    let add: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_typed_function(&mut store, "add")?;
+
+    // `add` costs 4: two `local.get`, `i32.add` and `end`.
+    store.set_fuel(7);
+    assert_eq!(add.call(&mut store, 4, 6)?, 10);
+    assert_eq!(store.fuel_remaining(), 3);
+    let err = add.call(&mut store, 4, 6).unwrap_err();
+    assert_eq!(err.to_trap(), Some(TrapCode::OutOfFuel));
+    assert_eq!(store.fuel_remaining(), 3);
+
+    store.set_fuel(4);
+    assert_eq!(add.call(&mut store, 4, 6)?, 10);
+    assert_eq!(store.fuel_remaining(), 0);
+    Ok(())
+}
+
+#[compiler_test(fuel)]
+fn fuel_stops_loops(config: crate::Config) -> Result<()> {
+    let (mut store, instance) = instantiate(
+        config,
+        r#"(module
+        (func (export "test") (param i32)
+           (local i32)
+           (local.set 1 (i32.const 0))
+           (loop
+            (local.get 1)
+            (i32.const 1)
+            (i32.add)
+            (local.tee 1)
+            (local.get 0)
+            (i32.ne)
+            (br_if 0))))"#,
+    )?;
+    let test: TypedFunction<i32, ()> = instance.exports.get_typed_function(&mut store, "test")?;
+
+    // The same number of iterations always costs the same.
+    store.set_fuel(1_000);
+    test.call(&mut store, 10)?;
+    let cost = 1_000 - store.fuel_remaining();
+    store.set_fuel(1_000);
+    test.call(&mut store, 10)?;
+    assert_eq!(1_000 - store.fuel_remaining(), cost);
+
+    store.set_fuel(1_000);
+    let err = test.call(&mut store, 1_000).unwrap_err();
+    assert_eq!(err.to_trap(), Some(TrapCode::OutOfFuel));
+    Ok(())
+}
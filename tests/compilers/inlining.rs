@@ -0,0 +1,60 @@
+use anyhow::Result;
+use wasmer::*;
+
+#[compiler_test(inlining)]
+fn inline_small_functions(config: crate::Config) -> Result<()> {
+    let mut compiler_config = config.compiler_config(config.canonicalize_nans);
+    compiler_config.inline_budget(8);
+    let mut store = Store::new(config.engine(compiler_config));
+    let wat = r#"
+        (module
+            (memory 1)
+            (data (i32.const 8) "\2a\00\00\00")
+            (func $field (param i32) (result i32)
+                (i32.load offset=8 (local.get 0)))
+            (func $add (param i32 i32) (result i32)
+                (i32.add (local.get 0) (local.get 1)))
+            ;; Reads its parameters out of order.
+            (func $sub (param i32 i32) (result i32)
+                local.get 1
+                local.get 0
+                i32.sub)
+            ;; Uses a local, which starts at zero on every call.
+            (func $square_plus (param i32) (result i32) (local i32)
+                (local.set 1 (i32.add (local.get 1) (local.get 0)))
+                (i32.mul (local.get 1) (local.get 0)))
+            ;; Has control flow: called.
+            (func $max (param i32 i32) (result i32)
+                (if (result i32) (i32.gt_s (local.get 0) (local.get 1))
+                    (then (local.get 0))
+                    (else (local.get 1))))
+            (func (export "field") (param i32) (result i32)
+                (call $field (local.get 0)))
+            (func (export "compute") (param i32 i32) (result i32)
+                (call $sub
+                    (call $add (local.get 0) (call $field (i32.const 0)))
+                    (local.get 1)))
+            (func (export "locals") (param i32) (result i32) (local i32)
+                (local.set 1 (i32.const 1000))
+                (i32.add
+                    (call $square_plus (call $square_plus (local.get 0)))
+                    (call $max (local.get 1) (local.get 0))))
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+
+    let field: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "field")?;
+    assert_eq!(field.call(&mut store, 0)?, 42);
+    // Traps of an inlined body are still reported.
+    assert!(field.call(&mut store, 65536).is_err());
+
+    let compute: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_typed_function(&store, "compute")?;
+    assert_eq!(compute.call(&mut store, 1, 100)?, 57);
+
+    // The locals of the caller are left alone.
+    let locals: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "locals")?;
+    assert_eq!(locals.call(&mut store, 3)?, 81 + 1000);
+    Ok(())
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "llvm")]
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_compiler_llvm::{CompiledKind, InkwellPassManager, LLVMPassPipeline, LLVM};
+
+/// Replaces the pipeline of the local functions, and extends the one of
+/// the trampolines.
+#[derive(Debug, Default)]
+struct CountingPipeline {
+    local_functions: AtomicUsize,
+    trampolines: AtomicUsize,
+}
+
+impl LLVMPassPipeline for CountingPipeline {
+    fn default_passes(&self, function: &CompiledKind) -> bool {
+        !matches!(function, CompiledKind::Local(_))
+    }
+
+    fn add_passes(&self, function: &CompiledKind, pass_manager: &InkwellPassManager) {
+        match function {
+            CompiledKind::Local(_) => {
+                self.local_functions.fetch_add(1, Ordering::SeqCst);
+                pass_manager.add_instruction_combining_pass();
+            }
+            _ => {
+                self.trampolines.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+#[test]
+fn custom_pass_pipeline() -> Result<()> {
+    let pipeline = Arc::new(CountingPipeline::default());
+    let mut config = LLVM::new();
+    config
+        .pass_pipeline(Some(pipeline.clone()))
+        .target_cpu(Some("generic".to_string()));
+    let mut store = Store::new(config);
+    let wat = r#"
+        (module
+            (func $double (param i32) (result i32)
+                (i32.mul (local.get 0) (i32.const 2)))
+            (func (export "add_double") (param i32 i32) (result i32)
+                (i32.add (local.get 0) (call $double (local.get 1))))
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let add_double: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_typed_function(&store, "add_double")?;
+    assert_eq!(add_double.call(&mut store, 1, 2)?, 5);
+
+    assert_eq!(pipeline.local_functions.load(Ordering::SeqCst), 2);
+    assert!(pipeline.trampolines.load(Ordering::SeqCst) > 0);
+    Ok(())
+}
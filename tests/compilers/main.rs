@@ -5,14 +5,30 @@
 #[macro_use]
 extern crate compiler_test_derive;
 
+mod atomics;
+mod bounds_checks;
+mod branch_hints;
 mod config;
+mod cross_compile;
+mod debug_info;
 mod deterministic;
+mod fuel;
 mod imports;
+mod inlining;
 mod issues;
+mod llvm_pass_pipeline;
+mod memory64;
 mod metering;
 mod middlewares;
+mod multi_memory;
 // mod multi_value_imports;
+mod nan_canonicalization;
+mod optimize_for_size;
+mod profiling;
+mod relaxed_simd;
+mod resource_limiter;
 mod serialize;
+mod tail_calls;
 mod traps;
 mod typed_functions;
 mod wasi;
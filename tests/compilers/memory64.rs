@@ -0,0 +1,80 @@
+use anyhow::Result;
+use wasmer::*;
+
+const MEMORY64_WAT: &[u8] = br#"
+(module
+  (memory (export "memory") i64 1 4)
+  (func (export "store") (param i64 i32)
+    (i32.store (local.get 0) (local.get 1)))
+  (func (export "load") (param i64) (result i32)
+    (i32.load offset=4 (local.get 0)))
+  (func (export "size") (result i64)
+    (memory.size))
+  (func (export "grow") (param i64) (result i64)
+    (memory.grow (local.get 0))))
+"#;
+
+fn memory64_store(mut config: crate::Config) -> Store {
+    let mut features = Features::default();
+    features.memory64(true);
+    config.set_features(features);
+    config.store()
+}
+
+#[compiler_test(memory64)]
+fn load_store_size_grow(config: crate::Config) -> Result<()> {
+    let singlepass = config.compiler == crate::Compiler::Singlepass;
+    let mut store = memory64_store(config);
+    let wasm = wat2wasm(MEMORY64_WAT)?;
+
+    if singlepass {
+        let err = Module::new(&store, wasm).unwrap_err();
+        assert!(matches!(err, CompileError::Wasm(WasmError::Unsupported(_))));
+        return Ok(());
+    }
+
+    let module = Module::new(&store, wasm)?;
+    let memory_type = module.exports().memories().next().unwrap();
+    assert!(memory_type.ty().is_memory64());
+
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let store_fn: TypedFunction<(i64, i32), ()> =
+        instance.exports.get_typed_function(&store, "store")?;
+    let load: TypedFunction<i64, i32> = instance.exports.get_typed_function(&store, "load")?;
+    let size: TypedFunction<(), i64> = instance.exports.get_typed_function(&store, "size")?;
+    let grow: TypedFunction<i64, i64> = instance.exports.get_typed_function(&store, "grow")?;
+
+    store_fn.call(&mut store, 12, 42)?;
+    assert_eq!(load.call(&mut store, 8)?, 42);
+
+    // Addresses above 4GiB are out of bounds rather than wrapped around.
+    assert!(load.call(&mut store, 0x1_0000_0008).is_err());
+    assert!(load.call(&mut store, -1).is_err());
+
+    assert_eq!(size.call(&mut store)?, 1);
+    assert_eq!(grow.call(&mut store, 2)?, 1);
+    assert_eq!(size.call(&mut store)?, 3);
+    assert_eq!(grow.call(&mut store, 2)?, -1);
+    assert_eq!(grow.call(&mut store, 0x1_0000_0000)?, -1);
+    assert_eq!(size.call(&mut store)?, 3);
+    Ok(())
+}
+
+#[compiler_test(memory64)]
+fn bulk_memory_is_rejected(config: crate::Config) -> Result<()> {
+    let store = memory64_store(config);
+    let wasm = wat2wasm(
+        br#"(module
+          (memory i64 1)
+          (func (param i64 i32 i64)
+            (memory.fill (local.get 0) (local.get 1) (local.get 2))))"#,
+    )?;
+
+    let err = Module::new(&store, wasm).unwrap_err();
+    assert!(
+        matches!(err, CompileError::Wasm(WasmError::Unsupported(_))),
+        "{:?}",
+        err
+    );
+    Ok(())
+}
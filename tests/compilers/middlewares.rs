@@ -210,3 +210,62 @@ fn middleware_chain_order_2(mut config: crate::Config) -> Result<()> {
     assert_eq!(result, 48);
     Ok(())
 }
+
+/// Replaces `nop` with a trap raised by the middleware.
+#[derive(Debug)]
+struct NopTrapGen;
+
+impl ModuleMiddleware for NopTrapGen {
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(NopTrapGen)
+    }
+}
+
+impl FunctionMiddleware for NopTrapGen {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        match operator {
+            Operator::Nop => state.push_trap(wasmer_types::TrapCode::StackExhausted),
+            _ => state.push_operator(operator),
+        }
+        Ok(())
+    }
+}
+
+#[compiler_test(middlewares)]
+fn middleware_trap_code(mut config: crate::Config) -> Result<()> {
+    config.set_middlewares(vec![
+        Arc::new(NopTrapGen) as Arc<dyn ModuleMiddleware>,
+        Arc::new(Add2MulGen { value_off: 0 }) as Arc<dyn ModuleMiddleware>,
+    ]);
+    let mut store = config.store();
+    let wat = r#"(module
+        (func (export "raised") (param i32) (result i32)
+           (if (local.get 0) (then (nop)))
+           (i32.add (local.get 0) (i32.const 3)))
+        (func (export "unreachable")
+           (unreachable))
+)"#;
+    let module = Module::new(&store, wat).unwrap();
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+
+    let raised: TypedFunction<i32, i32> =
+        instance.exports.get_typed_function(&mut store, "raised")?;
+    assert_eq!(raised.call(&mut store, 0)?, 0);
+    let err = raised.call(&mut store, 1).unwrap_err();
+    assert_eq!(err.to_trap(), Some(wasmer_types::TrapCode::StackExhausted));
+
+    // Other traps keep their code.
+    let unreachable: TypedFunction<(), ()> = instance
+        .exports
+        .get_typed_function(&mut store, "unreachable")?;
+    let err = unreachable.call(&mut store).unwrap_err();
+    assert_eq!(
+        err.to_trap(),
+        Some(wasmer_types::TrapCode::UnreachableCodeReached)
+    );
+    Ok(())
+}
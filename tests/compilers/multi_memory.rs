@@ -0,0 +1,76 @@
+use anyhow::Result;
+use wasmer::*;
+
+const MULTI_MEMORY_WAT: &[u8] = br#"
+(module
+  (import "env" "memory" (memory 1))
+  (memory (export "secondary") 1 3)
+  (func (export "store1") (param i32 i32)
+    (i32.store 1 (local.get 0) (local.get 1)))
+  (func (export "load0") (param i32) (result i32)
+    (i32.load 0 (local.get 0)))
+  (func (export "load1") (param i32) (result i32)
+    (i32.load 1 (local.get 0)))
+  (func (export "size1") (result i32)
+    (memory.size 1))
+  (func (export "grow1") (param i32) (result i32)
+    (memory.grow 1 (local.get 0)))
+  (func (export "copy_1_to_0") (param i32 i32 i32)
+    (memory.copy 0 1 (local.get 0) (local.get 1) (local.get 2))))
+"#;
+
+#[compiler_test(multi_memory)]
+fn imported_and_exported_memories(mut config: crate::Config) -> Result<()> {
+    let mut features = Features::default();
+    features.multi_memory(true);
+    config.set_features(features);
+    let mut store = config.store();
+    let module = Module::new(&store, wat2wasm(MULTI_MEMORY_WAT)?)?;
+
+    let primary = Memory::new(&mut store, MemoryType::new(1, None, false))?;
+    let imports = imports! {
+        "env" => {
+            "memory" => primary.clone(),
+        },
+    };
+    let instance = Instance::new(&mut store, &module, &imports)?;
+    let secondary = instance.exports.get_memory("secondary")?.clone();
+
+    let store1: TypedFunction<(i32, i32), ()> =
+        instance.exports.get_typed_function(&store, "store1")?;
+    let load0: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "load0")?;
+    let load1: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "load1")?;
+    let size1: TypedFunction<(), i32> = instance.exports.get_typed_function(&store, "size1")?;
+    let grow1: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "grow1")?;
+    let copy: TypedFunction<(i32, i32, i32), ()> =
+        instance.exports.get_typed_function(&store, "copy_1_to_0")?;
+
+    store1.call(&mut store, 16, 0x1234_5678)?;
+    assert_eq!(load1.call(&mut store, 16)?, 0x1234_5678);
+    assert_eq!(load0.call(&mut store, 16)?, 0);
+    assert_eq!(secondary.view(&store).read_u8(16)?, 0x78);
+    assert_eq!(primary.view(&store).read_u8(16)?, 0);
+
+    copy.call(&mut store, 100, 16, 4)?;
+    assert_eq!(load0.call(&mut store, 100)?, 0x1234_5678);
+    assert!(copy.call(&mut store, 0, 0x1_0000 - 2, 4).is_err());
+
+    assert_eq!(size1.call(&mut store)?, 1);
+    assert_eq!(grow1.call(&mut store, 2)?, 1);
+    assert_eq!(size1.call(&mut store)?, 3);
+    assert_eq!(grow1.call(&mut store, 1)?, -1);
+    assert_eq!(secondary.view(&store).size(), Pages(3));
+    assert_eq!(primary.view(&store).size(), Pages(1));
+
+    // Host side copies between the two memories.
+    store1.call(&mut store, 0x2_0000, 0x0bad_cafe)?;
+    secondary
+        .view(&store)
+        .copy_range_to_memory(0x2_0000, &primary.view(&store), 200, 4)?;
+    assert_eq!(load0.call(&mut store, 200)?, 0x0bad_cafe);
+    assert!(secondary
+        .view(&store)
+        .copy_range_to_memory(0, &primary.view(&store), 0x1_0000 - 2, 4)
+        .is_err());
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &[u8] = br#"
+    (module
+        (func (export "f32_add") (param f32 f32) (result i32)
+            (i32.reinterpret_f32 (f32.add (local.get 0) (local.get 1))))
+        (func (export "f32_div") (param f32 f32) (result i32)
+            (i32.reinterpret_f32 (f32.div (local.get 0) (local.get 1))))
+        (func (export "f32_sqrt") (param f32) (result i32)
+            (i32.reinterpret_f32 (f32.sqrt (local.get 0))))
+        (func (export "f64_add") (param f64 f64) (result i64)
+            (i64.reinterpret_f64 (f64.add (local.get 0) (local.get 1))))
+        (func (export "f64_div") (param f64 f64) (result i64)
+            (i64.reinterpret_f64 (f64.div (local.get 0) (local.get 1))))
+        (func (export "f64_sqrt") (param f64) (result i64)
+            (i64.reinterpret_f64 (f64.sqrt (local.get 0))))
+    )
+"#;
+
+const F32_CANONICAL: u32 = 0x7fc0_0000;
+const F64_CANONICAL: u64 = 0x7ff8_0000_0000_0000;
+
+// The sign of a canonical NaN is not specified.
+fn is_canonical_f32(bits: i32) -> bool {
+    bits as u32 & 0x7fff_ffff == F32_CANONICAL
+}
+fn is_canonical_f64(bits: i64) -> bool {
+    bits as u64 & 0x7fff_ffff_ffff_ffff == F64_CANONICAL
+}
+
+#[compiler_test(nan_canonicalization)]
+fn arithmetic_nans_are_canonical(config: crate::Config) -> Result<()> {
+    let engine = EngineBuilder::new(config.compiler_config(false))
+        .canonicalize_nans(true)
+        .engine();
+    let mut store = Store::new(engine);
+    let module = Module::new(&store, WAT)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+
+    let f32_add: TypedFunction<(f32, f32), i32> =
+        instance.exports.get_typed_function(&store, "f32_add")?;
+    let f32_div: TypedFunction<(f32, f32), i32> =
+        instance.exports.get_typed_function(&store, "f32_div")?;
+    let f32_sqrt: TypedFunction<f32, i32> =
+        instance.exports.get_typed_function(&store, "f32_sqrt")?;
+    let f64_add: TypedFunction<(f64, f64), i64> =
+        instance.exports.get_typed_function(&store, "f64_add")?;
+    let f64_div: TypedFunction<(f64, f64), i64> =
+        instance.exports.get_typed_function(&store, "f64_div")?;
+    let f64_sqrt: TypedFunction<f64, i64> =
+        instance.exports.get_typed_function(&store, "f64_sqrt")?;
+
+    // NaNs with a payload are not propagated as is.
+    let f32_nan = f32::from_bits(0x7fa0_0001);
+    let f64_nan = f64::from_bits(0x7ff4_0000_0000_0001);
+
+    assert!(is_canonical_f32(f32_add.call(&mut store, f32_nan, 1.0)?));
+    assert!(is_canonical_f32(f32_div.call(&mut store, 0.0, 0.0)?));
+    assert!(is_canonical_f32(f32_sqrt.call(&mut store, -1.0)?));
+    assert!(is_canonical_f64(f64_add.call(&mut store, f64_nan, 1.0)?));
+    assert!(is_canonical_f64(f64_div.call(&mut store, 0.0, 0.0)?));
+    assert!(is_canonical_f64(f64_sqrt.call(&mut store, -1.0)?));
+
+    // Other values are left untouched.
+    assert_eq!(f32_add.call(&mut store, 1.0, 2.0)?, 3.0f32.to_bits() as i32);
+    assert_eq!(
+        f64_div.call(&mut store, 1.0, 4.0)?,
+        0.25f64.to_bits() as i64
+    );
+    Ok(())
+}
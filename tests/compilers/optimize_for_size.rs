@@ -0,0 +1,48 @@
+use anyhow::Result;
+use wasmer::*;
+
+#[cfg_attr(target_env = "musl", ignore)]
+#[compiler_test(optimize_for_size)]
+fn optimize_for_size(config: crate::Config) -> Result<()> {
+    let mut compiler_config = config.compiler_config(config.canonicalize_nans);
+    compiler_config.optimize_for_size(true);
+    let mut store = Store::new(config.engine(compiler_config));
+    let wat = r#"
+        (module
+            (memory 1)
+            (func $div (param i32 i32) (result i32)
+                (i32.div_u (local.get 0) (local.get 1)))
+            (func (export "sum") (param $n i32) (result i32)
+                (local $i i32)
+                (local $sum i32)
+                (block $done
+                    (loop $loop
+                        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+                        (i32.store (i32.shl (local.get $i) (i32.const 2)) (local.get $i))
+                        (local.set $sum
+                            (i32.add
+                                (local.get $sum)
+                                (i32.load (i32.shl (local.get $i) (i32.const 2)))))
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br $loop)))
+                (local.get $sum))
+            (func (export "div") (param i32 i32) (result i32)
+                (call $div (local.get 0) (local.get 1)))
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+
+    let sum: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "sum")?;
+    assert_eq!(sum.call(&mut store, 100)?, 4950);
+
+    // Traps are still reported, even with compact address maps.
+    let div: TypedFunction<(i32, i32), i32> = instance.exports.get_typed_function(&store, "div")?;
+    assert_eq!(div.call(&mut store, 7, 2)?, 3);
+    let error = div.call(&mut store, 1, 0).unwrap_err();
+    let trace = error.trace();
+    assert_eq!(trace.len(), 2);
+    assert_eq!(trace[0].func_index(), 0);
+    assert_eq!(trace[1].func_index(), 2);
+    Ok(())
+}
@@ -0,0 +1,39 @@
+#![cfg(unix)]
+
+use anyhow::Result;
+use wasmer::*;
+
+#[compiler_test(profiling)]
+fn perf_map(config: crate::Config) -> Result<()> {
+    let compiler_config = config.compiler_config(config.canonicalize_nans);
+    let mut engine = config.engine(compiler_config);
+    engine.set_profiling_strategy(ProfilingStrategy::PerfMap);
+    let store = Store::new(engine);
+    let wat = r#"
+        (module $perf_mod
+            (func $first (export "first"))
+            (func (export "second") (call $first))
+        )
+    "#;
+    let _module = Module::new(&store, wat)?;
+
+    let perf_map = std::fs::read_to_string(format!("/tmp/perf-{}.map", std::process::id()))?;
+    let lines = perf_map
+        .lines()
+        .filter(|line| line.contains("(perf_mod["))
+        .collect::<Vec<_>>();
+    assert!(lines
+        .iter()
+        .any(|line| line.ends_with(" first (perf_mod[0])")));
+    assert!(lines
+        .iter()
+        .any(|line| line.ends_with(" <unnamed> (perf_mod[1])")));
+    for line in lines {
+        let mut fields = line.splitn(3, ' ');
+        let address = u64::from_str_radix(fields.next().unwrap(), 16)?;
+        let size = u64::from_str_radix(fields.next().unwrap(), 16)?;
+        assert_ne!(address, 0);
+        assert_ne!(size, 0);
+    }
+    Ok(())
+}
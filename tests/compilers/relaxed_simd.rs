@@ -0,0 +1,100 @@
+use anyhow::Result;
+use wasmer::*;
+
+const RELAXED_SIMD_WAT: &[u8] = br#"
+(module
+  (func (export "madd") (param f32 f32 f32) (result f32)
+    (f32x4.extract_lane 0
+      (f32x4.relaxed_madd
+        (f32x4.splat (local.get 0))
+        (f32x4.splat (local.get 1))
+        (f32x4.splat (local.get 2)))))
+  (func (export "nmadd") (param f64 f64 f64) (result f64)
+    (f64x2.extract_lane 1
+      (f64x2.relaxed_nmadd
+        (f64x2.splat (local.get 0))
+        (f64x2.splat (local.get 1))
+        (f64x2.splat (local.get 2)))))
+  (func (export "min") (param f32 f32) (result f32)
+    (f32x4.extract_lane 2
+      (f32x4.relaxed_min (f32x4.splat (local.get 0)) (f32x4.splat (local.get 1)))))
+  (func (export "max") (param f64 f64) (result f64)
+    (f64x2.extract_lane 0
+      (f64x2.relaxed_max (f64x2.splat (local.get 0)) (f64x2.splat (local.get 1)))))
+  (func (export "swizzle") (param i32) (result i32)
+    (i8x16.extract_lane_u 0
+      (i8x16.relaxed_swizzle
+        (v128.const i8x16 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25)
+        (i8x16.splat (local.get 0)))))
+  (func (export "laneselect") (result i64)
+    (i64x2.extract_lane 0
+      (i64x2.relaxed_laneselect
+        (v128.const i64x2 1 1)
+        (v128.const i64x2 2 2)
+        (v128.const i64x2 -1 0))))
+  (func (export "trunc") (param f32) (result i32)
+    (i32x4.extract_lane 3 (i32x4.relaxed_trunc_f32x4_s (f32x4.splat (local.get 0)))))
+  (func (export "dot") (param i32 i32) (result i32)
+    (i16x8.extract_lane_s 0
+      (i16x8.relaxed_dot_i8x16_i7x16_s
+        (i8x16.splat (local.get 0))
+        (i8x16.splat (local.get 1)))))
+  (func (export "dot_add") (param i32 i32 i32) (result i32)
+    (i32x4.extract_lane 1
+      (i32x4.relaxed_dot_i8x16_i7x16_add_s
+        (i8x16.splat (local.get 0))
+        (i8x16.splat (local.get 1))
+        (i32x4.splat (local.get 2))))))
+"#;
+
+fn check_relaxed_simd(mut config: crate::Config, deterministic: bool) -> Result<()> {
+    let mut features = Features::default();
+    features.relaxed_simd(true);
+    config.set_features(features);
+    config.set_relaxed_simd_deterministic(deterministic);
+    let mut store = config.store();
+
+    let module = Module::new(&store, wat2wasm(RELAXED_SIMD_WAT)?)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let exports = &instance.exports;
+
+    let madd: TypedFunction<(f32, f32, f32), f32> = exports.get_typed_function(&store, "madd")?;
+    let nmadd: TypedFunction<(f64, f64, f64), f64> = exports.get_typed_function(&store, "nmadd")?;
+    let min: TypedFunction<(f32, f32), f32> = exports.get_typed_function(&store, "min")?;
+    let max: TypedFunction<(f64, f64), f64> = exports.get_typed_function(&store, "max")?;
+    let swizzle: TypedFunction<i32, i32> = exports.get_typed_function(&store, "swizzle")?;
+    let laneselect: TypedFunction<(), i64> = exports.get_typed_function(&store, "laneselect")?;
+    let trunc: TypedFunction<f32, i32> = exports.get_typed_function(&store, "trunc")?;
+    let dot: TypedFunction<(i32, i32), i32> = exports.get_typed_function(&store, "dot")?;
+    let dot_add: TypedFunction<(i32, i32, i32), i32> =
+        exports.get_typed_function(&store, "dot_add")?;
+
+    // Only inputs whose results are the same in every lowering.
+    assert_eq!(madd.call(&mut store, 2.0, 3.0, 4.0)?, 10.0);
+    assert_eq!(nmadd.call(&mut store, 2.0, 3.0, 4.0)?, -2.0);
+    assert_eq!(min.call(&mut store, 1.5, -2.5)?, -2.5);
+    assert_eq!(max.call(&mut store, 1.5, -2.5)?, 1.5);
+    assert_eq!(swizzle.call(&mut store, 3)?, 13);
+    assert_eq!(laneselect.call(&mut store)?, 1);
+    assert_eq!(trunc.call(&mut store, -7.75)?, -7);
+    assert_eq!(dot.call(&mut store, -3, 5)?, -30);
+    assert_eq!(dot_add.call(&mut store, 7, -2, 100)?, 44);
+    Ok(())
+}
+
+#[compiler_test(relaxed_simd)]
+fn relaxed_simd(config: crate::Config) -> Result<()> {
+    if config.compiler == crate::Compiler::Singlepass {
+        // Singlepass doesn't support SIMD.
+        return Ok(());
+    }
+    check_relaxed_simd(config, false)
+}
+
+#[compiler_test(relaxed_simd)]
+fn relaxed_simd_deterministic(config: crate::Config) -> Result<()> {
+    if config.compiler == crate::Compiler::Singlepass {
+        return Ok(());
+    }
+    check_relaxed_simd(config, true)
+}
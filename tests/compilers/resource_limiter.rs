@@ -0,0 +1,63 @@
+use anyhow::Result;
+use wasmer::*;
+
+/// Allows memories up to `max_pages` pages and tables up to
+/// `max_elements` elements.
+#[derive(Debug)]
+struct Limits {
+    max_pages: u32,
+    max_elements: u32,
+}
+
+impl ResourceLimiter for Limits {
+    fn memory_growing(&mut self, _current: Pages, desired: Pages, _maximum: Option<Pages>) -> bool {
+        desired.0 <= self.max_pages
+    }
+
+    fn table_growing(&mut self, _current: u32, desired: u32, _maximum: Option<u32>) -> bool {
+        desired <= self.max_elements
+    }
+}
+
+#[compiler_test(resource_limiter)]
+fn limit_guest_growth(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (memory (export "memory") 1)
+            (table (export "table") 1 funcref)
+            (func (export "grow_memory") (param i32) (result i32)
+                (memory.grow (local.get 0)))
+            (func (export "grow_table") (param i32) (result i32)
+                (table.grow (ref.null func) (local.get 0)))
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    store.set_resource_limiter(Some(Box::new(Limits {
+        max_pages: 3,
+        max_elements: 4,
+    })));
+
+    let grow_memory: TypedFunction<i32, i32> =
+        instance.exports.get_typed_function(&store, "grow_memory")?;
+    assert_eq!(grow_memory.call(&mut store, 2)?, 1);
+    assert_eq!(grow_memory.call(&mut store, 1)?, -1);
+    assert_eq!(grow_memory.call(&mut store, 0)?, 3);
+
+    let grow_table: TypedFunction<i32, i32> =
+        instance.exports.get_typed_function(&store, "grow_table")?;
+    assert_eq!(grow_table.call(&mut store, 3)?, 1);
+    assert_eq!(grow_table.call(&mut store, 1)?, -1);
+
+    // Growth requested by the host is limited too.
+    let memory = instance.exports.get_memory("memory")?;
+    assert!(memory.grow(&mut store, 1).is_err());
+    let table = instance.exports.get_table("table")?;
+    assert!(table.grow(&mut store, 1, Value::FuncRef(None)).is_err());
+
+    store.set_resource_limiter(None);
+    assert_eq!(grow_memory.call(&mut store, 1)?, 3);
+    assert_eq!(grow_table.call(&mut store, 1)?, 4);
+    Ok(())
+}
@@ -85,3 +85,105 @@ fn test_deserialize(config: crate::Config) -> Result<()> {
     assert_eq!(result.to_vec(), vec![Value::I64(1500)]);
     Ok(())
 }
+
+#[compiler_test(serialize)]
+fn test_deserialize_variants(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"(module (func (export "run") (result i32) i32.const 42))"#,
+    )?;
+    let cpu_features = *store.engine().target().cpu_features();
+    let all_cpu_features = [
+        CpuFeature::SSE2,
+        CpuFeature::SSE3,
+        CpuFeature::SSSE3,
+        CpuFeature::SSE41,
+        CpuFeature::SSE42,
+        CpuFeature::POPCNT,
+        CpuFeature::AVX,
+        CpuFeature::BMI1,
+        CpuFeature::BMI2,
+        CpuFeature::AVX2,
+        CpuFeature::AVX512DQ,
+        CpuFeature::AVX512VL,
+        CpuFeature::AVX512F,
+        CpuFeature::LZCNT,
+    ]
+    .iter()
+    .fold(cpu_features, |features, feature| features | *feature);
+
+    let mut variants = ArtifactVariants::new();
+    variants.push(cpu_features, module.serialize()?.to_vec());
+    // A variant for CPU features the engine doesn't have must not be
+    // picked, even though it requires more of them.
+    if cpu_features != all_cpu_features {
+        variants.push(all_cpu_features, b"not an artifact".to_vec());
+    }
+    let serialized = variants.serialize();
+
+    let mut headless_store = config.headless_store();
+    let deserialized = unsafe { Module::deserialize(&headless_store, serialized)? };
+    let instance = Instance::new(&mut headless_store, &deserialized, &imports! {})?;
+    let run: TypedFunction<(), i32> = instance
+        .exports
+        .get_typed_function(&headless_store, "run")?;
+    assert_eq!(run.call(&mut headless_store)?, 42);
+
+    let mut unsupported = ArtifactVariants::new();
+    unsupported.push(all_cpu_features, b"not an artifact".to_vec());
+    if cpu_features != all_cpu_features {
+        assert!(unsafe { Module::deserialize(&headless_store, unsupported.serialize()) }.is_err());
+    }
+    Ok(())
+}
+
+#[cfg_attr(target_env = "musl", ignore)]
+#[compiler_test(serialize)]
+fn test_serialize_stripped(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wat = r#"
+        (module $hello_mod
+            (func (export "run") (call $hello))
+            (func $hello (unreachable))
+            (@custom "producers" "\00")
+        )
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let serialized = module.serialize()?;
+    let stripped = module.serialize_with(&SerializeOptions::strip_all())?;
+    assert!(stripped.len() < serialized.len());
+    assert_eq!(
+        module.serialize_with(&SerializeOptions::default())?,
+        serialized
+    );
+
+    let mut headless_store = config.headless_store();
+    let deserialized = unsafe { Module::deserialize(&headless_store, stripped)? };
+    assert_eq!(deserialized.name(), None);
+    assert_eq!(deserialized.custom_sections("producers").count(), 0);
+    assert_eq!(
+        deserialized.exports().collect::<Vec<_>>(),
+        module.exports().collect::<Vec<_>>()
+    );
+
+    // Traps still have their message and a frame for each function, only
+    // without names.
+    let instance = Instance::new(&mut headless_store, &deserialized, &imports! {})?;
+    let run = instance.exports.get_function("run")?;
+    let e = run
+        .call(&mut headless_store, &[])
+        .expect_err("error calling function");
+    assert!(
+        e.message().contains("unreachable"),
+        "wrong message: {}",
+        e.message()
+    );
+    let trace = e.trace();
+    assert_eq!(trace.len(), 2);
+    assert_eq!(trace[0].func_index(), 1);
+    assert_eq!(trace[0].function_name(), None);
+    assert_eq!(trace[1].func_index(), 0);
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! Tail calls must not grow the stack: compilers that can't guarantee it
+//! must reject `return_call` and `return_call_indirect` rather than
+//! compile them as a call followed by a return. Singlepass supports them
+//! on x86_64.
+use anyhow::Result;
+use wasmer::*;
+
+#[compiler_test(tail_calls)]
+fn return_call_and_return_call_indirect(mut config: crate::Config) -> Result<()> {
+    let mut features = Features::default();
+    features.tail_call(true);
+    config.set_features(features);
+    let mut store = config.store();
+
+    let wasm = wat2wasm(
+        br#"
+        (module
+          (type $count (func (param i32 i64) (result i64)))
+          (table 1 funcref)
+          (elem (i32.const 0) $odd)
+
+          ;; Sums the numbers from 1 to `n`, alternating between a direct
+          ;; and an indirect tail call.
+          (func $even (export "sum") (type $count)
+            (if (result i64) (i32.eqz (local.get 0))
+              (then (local.get 1))
+              (else
+                (return_call_indirect (type $count)
+                  (i32.sub (local.get 0) (i32.const 1))
+                  (i64.add (local.get 1) (i64.extend_i32_u (local.get 0)))
+                  (i32.const 0)))))
+          (func $odd (type $count)
+            (if (result i64) (i32.eqz (local.get 0))
+              (then (local.get 1))
+              (else
+                (return_call $even
+                  (i32.sub (local.get 0) (i32.const 1))
+                  (i64.add (local.get 1) (i64.extend_i32_u (local.get 0))))))))
+        "#,
+    )?;
+    let module = match Module::new(&store, wasm) {
+        Ok(module) => module,
+        Err(err) => {
+            let supported =
+                cfg!(target_arch = "x86_64") && config.compiler == crate::Compiler::Singlepass;
+            assert!(!supported, "tail calls failed to compile: {}", err);
+            assert!(matches!(err, CompileError::Wasm(WasmError::Unsupported(_))));
+            return Ok(());
+        }
+    };
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+
+    let sum: TypedFunction<(i32, i64), i64> =
+        instance.exports.get_function("sum")?.typed(&mut store)?;
+    assert_eq!(sum.call(&mut store, 0, 0)?, 0);
+    // Deep enough to overflow the stack if each call kept its frame
+    assert_eq!(sum.call(&mut store, 1_000_000, 0)?, 500_000_500_000);
+    Ok(())
+}
+
+#[compiler_test(tail_calls)]
+fn return_call_moves_arguments(mut config: crate::Config) -> Result<()> {
+    let mut features = Features::default();
+    features.tail_call(true);
+    config.set_features(features);
+    let mut store = config.store();
+
+    let wasm = wat2wasm(
+        br#"
+        (module
+          (import "env" "sub" (func $sub (param i64 i64) (result i64)))
+          (type $rotate (func (param i32 i64 f32 f64 i64 i64 i64 i64) (result i64)))
+          (table 1 funcref)
+          (elem (i32.const 0) $rotate)
+
+          ;; Rotates the last seven arguments `n` times, so that arguments
+          ;; passed in registers and on the stack trade places.
+          (func $rotate (export "rotate") (type $rotate)
+            (if (result i64) (i32.eqz (local.get 0))
+              (then
+                (i64.add
+                  (i64.add
+                    (i64.add (local.get 1)
+                             (i64.mul (i64.trunc_f32_s (local.get 2)) (i64.const 10)))
+                    (i64.add (i64.mul (i64.trunc_f64_s (local.get 3)) (i64.const 100))
+                             (i64.mul (local.get 4) (i64.const 1000))))
+                  (i64.add
+                    (i64.add (i64.mul (local.get 5) (i64.const 10000))
+                             (i64.mul (local.get 6) (i64.const 100000)))
+                    (i64.mul (local.get 7) (i64.const 1000000)))))
+              (else
+                (return_call_indirect (type $rotate)
+                  (i32.sub (local.get 0) (i32.const 1))
+                  (local.get 7)
+                  (f32.convert_i64_s (local.get 1))
+                  (f64.promote_f32 (local.get 2))
+                  (i64.trunc_f64_s (local.get 3))
+                  (local.get 4)
+                  (local.get 5)
+                  (local.get 6)
+                  (i32.const 0)))))
+
+          (func (export "sub") (param i64 i64) (result i64)
+            (return_call $sub (local.get 1) (local.get 0))))
+        "#,
+    )?;
+    let module = match Module::new(&store, wasm) {
+        Ok(module) => module,
+        Err(err) => {
+            let supported =
+                cfg!(target_arch = "x86_64") && config.compiler == crate::Compiler::Singlepass;
+            assert!(!supported, "tail calls failed to compile: {}", err);
+            assert!(matches!(err, CompileError::Wasm(WasmError::Unsupported(_))));
+            return Ok(());
+        }
+    };
+    let imports = imports! {
+        "env" => {
+            "sub" => Function::new_typed(&mut store, |a: i64, b: i64| a - b),
+        },
+    };
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let rotate: TypedFunction<(i32, i64, f32, f64, i64, i64, i64, i64), i64> =
+        instance.exports.get_function("rotate")?.typed(&mut store)?;
+    assert_eq!(
+        rotate.call(&mut store, 0, 1, 2., 3., 4, 5, 6, 7)?,
+        7_654_321
+    );
+    assert_eq!(
+        rotate.call(&mut store, 1, 1, 2., 3., 4, 5, 6, 7)?,
+        6_543_217
+    );
+    assert_eq!(
+        rotate.call(&mut store, 7, 1, 2., 3., 4, 5, 6, 7)?,
+        7_654_321
+    );
+    assert_eq!(
+        rotate.call(&mut store, 7_000, 1, 2., 3., 4, 5, 6, 7)?,
+        7_654_321
+    );
+
+    let sub: TypedFunction<(i64, i64), i64> =
+        instance.exports.get_function("sub")?.typed(&mut store)?;
+    assert_eq!(sub.call(&mut store, 2, 10)?, 8);
+    Ok(())
+}
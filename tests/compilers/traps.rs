@@ -155,6 +155,47 @@ fn test_trap_stack_overflow(config: crate::Config) -> Result<()> {
     Ok(())
 }
 
+#[cfg_attr(target_env = "musl", ignore)]
+#[compiler_test(traps)]
+fn test_trap_stack_size_per_store(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (func $depth (export "depth") (param i32) (result i32)
+                local.get 0
+                i32.eqz
+                if (result i32)
+                    i32.const 0
+                else
+                    local.get 0
+                    i32.const 1
+                    i32.sub
+                    call $depth
+                    i32.const 1
+                    i32.add
+                end)
+        )
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let depth: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "depth")?;
+
+    assert_eq!(depth.call(&mut store, 5000)?, 5000);
+
+    store.set_stack_size(Some(64 * 1024));
+    let e = depth
+        .call(&mut store, 5000)
+        .expect_err("the stack should overflow");
+    assert_eq!(e.to_trap(), Some(wasmer_types::TrapCode::StackOverflow));
+    assert_eq!(depth.call(&mut store, 100)?, 100);
+
+    store.set_stack_size(None);
+    assert_eq!(depth.call(&mut store, 5000)?, 5000);
+
+    Ok(())
+}
+
 #[cfg_attr(target_env = "musl", ignore)]
 #[compiler_test(traps)]
 fn trap_display_pretty(config: crate::Config) -> Result<()> {
@@ -490,3 +531,98 @@ fn present_after_module_drop(config: crate::Config) -> Result<()> {
         // assert_eq!(t.trace()[0].func_index(), 0);
     }
 }
+
+#[compiler_test(traps)]
+fn coredump_on_trap(config: crate::Config) -> Result<()> {
+    use std::sync::{Arc, Mutex};
+
+    let mut store = config.store();
+    let dumps = Arc::new(Mutex::new(Vec::new()));
+    let sink = dumps.clone();
+    store.set_coredump_sink(Some(Box::new(move |dump: &[u8]| {
+        sink.lock().unwrap().push(dump.to_vec())
+    })));
+    let wat = r#"
+        (module $guest
+            (import "" "fail" (func $fail))
+            (memory 1)
+            (data (i32.const 16) "guest memory")
+            (global $g (mut i32) (i32.const 7))
+            (func (export "crash") unreachable)
+            (func (export "host") (call $fail))
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let fail = Function::new_typed(&mut store, || -> Result<(), RuntimeError> {
+        Err(RuntimeError::new("host error"))
+    });
+    let instance = Instance::new(&mut store, &module, &imports! { "" => { "fail" => fail } })?;
+
+    // Errors of host functions aren't dumped.
+    let host: TypedFunction<(), ()> = instance.exports.get_typed_function(&store, "host")?;
+    host.call(&mut store).unwrap_err();
+    assert!(dumps.lock().unwrap().is_empty());
+
+    let crash = instance.exports.get_function("crash")?;
+    crash.call(&mut store, &[]).unwrap_err();
+    let crash: TypedFunction<(), ()> = instance.exports.get_typed_function(&store, "crash")?;
+    crash.call(&mut store).unwrap_err();
+
+    let dumps = dumps.lock().unwrap();
+    assert_eq!(dumps.len(), 2);
+    for dump in dumps.iter() {
+        let contains = |bytes: &[u8]| dump.windows(bytes.len()).any(|w| w == bytes);
+        assert!(dump.starts_with(b"\0asm"));
+        assert!(contains(b"\x05guest"));
+        assert!(contains(b"corestack"));
+        assert!(contains(b"guest memory"));
+    }
+    Ok(())
+}
+
+#[compiler_test(traps)]
+fn host_error_with_code(config: crate::Config) -> Result<()> {
+    #[derive(Debug)]
+    enum LookupError {
+        NotFound(i32),
+    }
+
+    impl From<LookupError> for HostError {
+        fn from(error: LookupError) -> Self {
+            match error {
+                LookupError::NotFound(key) => HostError::new(404, format!("no key {}", key)),
+            }
+        }
+    }
+
+    fn lookup(key: i32) -> Result<i32, LookupError> {
+        match key {
+            0 => Ok(42),
+            _ => Err(LookupError::NotFound(key)),
+        }
+    }
+
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (import "env" "lookup" (func $lookup (param i32) (result i32)))
+            (func (export "run") (param i32) (result i32)
+                (call $lookup (local.get 0))))
+    "#;
+    let module = Module::new(&store, wat)?;
+    let host_lookup = Function::new_typed(&mut store, |key: i32| -> Result<i32, HostError> {
+        Ok(lookup(key)?)
+    });
+    let instance = Instance::new(
+        &mut store,
+        &module,
+        &imports! { "env" => { "lookup" => host_lookup } },
+    )?;
+    let run: TypedFunction<i32, i32> = instance.exports.get_typed_function(&mut store, "run")?;
+
+    assert_eq!(run.call(&mut store, 0)?, 42);
+    let e = run.call(&mut store, 7).unwrap_err();
+    assert_eq!(e.host_error(), Some(&HostError::new(404, "no key 7")));
+    assert_eq!(e.message(), "no key 7 (code 404)");
+    Ok(())
+}
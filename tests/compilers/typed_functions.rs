@@ -474,3 +474,33 @@ fn dynamic_host_function_with_env(config: crate::Config) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[compiler_test(typed_functions)]
+fn typed_functions_wide_integers(config: crate::Config) -> anyhow::Result<()> {
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (import "env" "mul" (func $mul (param i64 i64) (result i64 i64)))
+            (func (export "mul") (param i64 i64) (result i64 i64)
+                (call $mul (local.get 0) (local.get 1)))
+            (func (export "max") (result i64 i64)
+                (i64.const -1) (i64.const 0x7fffffffffffffff)))
+    "#;
+    let module = Module::new(&store, wat)?;
+
+    // 128-bit results are returned as their low then high halves.
+    let mul = Function::new_typed(&mut store, |a: i64, b: i64| a as i128 * b as i128);
+    let instance = Instance::new(&mut store, &module, &imports! { "env" => { "mul" => mul } })?;
+
+    let mul: TypedFunction<(i64, i64), i128> =
+        instance.exports.get_typed_function(&mut store, "mul")?;
+    assert_eq!(mul.call(&mut store, i64::MAX, 4)?, i64::MAX as i128 * 4);
+    assert_eq!(mul.call(&mut store, i64::MIN, 3)?, i64::MIN as i128 * 3);
+
+    let max: TypedFunction<(), i128> = instance.exports.get_typed_function(&mut store, "max")?;
+    assert_eq!(max.call(&mut store)?, i128::MAX);
+    let max: TypedFunction<(), u128> = instance.exports.get_typed_function(&mut store, "max")?;
+    assert_eq!(max.call(&mut store)?, i128::MAX as u128);
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use std::process::Command;
+use wasmer_integration_tests_cli::{get_wasmer_path, C_ASSET_PATH};
+
+#[test]
+fn bench_an_exported_function() -> anyhow::Result<()> {
+    let temp = tempfile::tempdir()?;
+    let wat = temp.path().join("add.wat");
+    std::fs::write(
+        &wat,
+        r#"(module
+            (func (export "add") (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                i32.add))"#,
+    )?;
+
+    let output = Command::new(get_wasmer_path())
+        .arg("bench")
+        .arg("--json")
+        .arg("--iterations=3")
+        .arg("--invoke=add")
+        .arg(&wat)
+        .arg("1")
+        .arg("2")
+        .output()?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stdout}\n{stderr}");
+
+    assert!(stdout.contains("\"entrypoint\": \"add\""), "{stdout}");
+    assert!(stdout.contains("\"iterations\": 3"), "{stdout}");
+    assert!(stdout.contains("\"mean_ms\""), "{stdout}");
+    assert!(!stdout.contains("\"error\""), "{stdout}");
+
+    Ok(())
+}
+
+#[test]
+fn bench_a_wasi_program() -> anyhow::Result<()> {
+    let output = Command::new(get_wasmer_path())
+        .arg("bench")
+        .arg("--iterations=2")
+        .arg("--warmup=0")
+        .arg(format!("{}/qjs.wasm", C_ASSET_PATH))
+        .arg("--")
+        .arg("-e")
+        .arg("print(1 + 1)")
+        .output()?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stdout}\n{stderr}");
+    assert!(stdout.contains("instantiate"), "{stdout}");
+    // The program's own output is discarded
+    assert!(!stdout.contains("\n2\n"), "{stdout}");
+
+    Ok(())
+}
@@ -213,6 +213,43 @@ fn test_create_exe_with_pirita_works_1() {
     assert!(cmd.status.success());
 }
 
+#[test]
+fn test_create_obj_with_all_atoms() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path();
+    let wasm_out = path.join("out.obj");
+    let cmd = Command::new(get_wasmer_path())
+        .arg("create-obj")
+        .arg(create_exe_wabt_path())
+        .arg("--all-atoms")
+        .arg("-o")
+        .arg(&wasm_out)
+        .output()
+        .unwrap();
+
+    assert!(
+        cmd.status.success(),
+        "{}",
+        String::from_utf8_lossy(&cmd.stderr)
+    );
+    assert!(wasm_out.exists());
+
+    // A single header exposes every module of the object.
+    let header = fs::read_to_string(path.join("out.h")).unwrap();
+    for atom in [
+        "wasm_interp",
+        "wasm_strip",
+        "wasm_validate",
+        "wasmwat",
+        "watwasm",
+    ] {
+        assert!(
+            header.contains(&format!("wasmer_object_module_new_{atom}(")),
+            "missing module {atom} in header"
+        );
+    }
+}
+
 #[test]
 fn test_create_exe_with_precompiled_works_1() {
     use object::{Object, ObjectSymbol};
@@ -0,0 +1,82 @@
+use std::process::Command;
+use wasmer_integration_tests_cli::get_wasmer_path;
+
+const MANIFEST: &str = r#"[package]
+name = "wasmer/hello"
+version = "0.1.0"
+description = "hello"
+
+# The interpreter the package's scripts run with
+[dependencies]
+"python/python" = "0.1.0"
+"sharrattj/coreutils" = "1.0.16"
+"#;
+
+const LOCKFILE: &str = r#"version = 1
+
+[[package]]
+name = "python/python"
+version = "0.1.0"
+registry = "https://registry.wapm.io/graphql"
+download-url = "https://registry-cdn.wapm.io/packages/python/python/python-0.1.0.tar.gz"
+
+[[package]]
+name = "sharrattj/coreutils"
+version = "1.0.16"
+registry = "https://registry.wapm.io/graphql"
+download-url = "https://registry-cdn.wapm.io/packages/sharrattj/coreutils/coreutils-1.0.16.tar.gz"
+"#;
+
+#[test]
+fn remove_updates_manifest_and_lockfile() -> anyhow::Result<()> {
+    let temp = tempfile::tempdir()?;
+    std::fs::write(temp.path().join("wasmer.toml"), MANIFEST)?;
+    std::fs::write(temp.path().join("wasmer.lock"), LOCKFILE)?;
+
+    let output = Command::new(get_wasmer_path())
+        .arg("remove")
+        .arg("python/python")
+        .current_dir(temp.path())
+        .output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+
+    let manifest = std::fs::read_to_string(temp.path().join("wasmer.toml"))?;
+    assert!(!manifest.contains("python/python"), "{manifest}");
+    assert!(manifest.contains("# The interpreter"), "{manifest}");
+    assert!(manifest.contains("sharrattj/coreutils"), "{manifest}");
+
+    let lockfile = std::fs::read_to_string(temp.path().join("wasmer.lock"))?;
+    assert!(!lockfile.contains("python/python"), "{lockfile}");
+    assert!(
+        lockfile.contains("name = \"sharrattj/coreutils\""),
+        "{lockfile}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn remove_rejects_unknown_dependencies() -> anyhow::Result<()> {
+    let temp = tempfile::tempdir()?;
+    std::fs::write(temp.path().join("wasmer.toml"), MANIFEST)?;
+
+    let output = Command::new(get_wasmer_path())
+        .arg("remove")
+        .arg("--package-path")
+        .arg(temp.path())
+        .arg("wasmer/unknown")
+        .output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("wasmer/unknown isn't a dependency of this package"),
+        "{stderr}"
+    );
+    assert_eq!(
+        std::fs::read_to_string(temp.path().join("wasmer.toml"))?,
+        MANIFEST
+    );
+
+    Ok(())
+}
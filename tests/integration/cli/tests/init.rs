@@ -133,3 +133,39 @@ fn wasmer_init_works_2() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn wasmer_init_with_language_template() -> anyhow::Result<()> {
+    let tempdir = tempfile::tempdir()?;
+    let path = tempdir.path().join("hello");
+
+    let output = Command::new(get_wasmer_path())
+        .arg("init")
+        .arg("--template=rust")
+        .arg("--namespace=ns")
+        .arg("--quiet")
+        .arg(&path)
+        .output()?;
+    check_output!(output);
+
+    let cargo_toml = std::fs::read_to_string(path.join("Cargo.toml"))?;
+    assert!(cargo_toml.contains("name = \"hello\""));
+    assert!(path.join("src").join("main.rs").exists());
+
+    let manifest = std::fs::read_to_string(path.join("wasmer.toml"))?;
+    assert!(manifest.contains("name = \"ns/hello\""));
+    assert!(manifest.contains("source = \"target/wasm32-wasi/release/hello.wasm\""));
+    assert!(manifest.contains("[[command]]"));
+
+    // An existing package is never clobbered without --overwrite
+    let output = Command::new(get_wasmer_path())
+        .arg("init")
+        .arg("--template=c")
+        .arg("--namespace=ns")
+        .arg("--quiet")
+        .arg(&path)
+        .output()?;
+    assert!(!output.status.success());
+
+    Ok(())
+}
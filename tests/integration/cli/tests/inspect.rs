@@ -0,0 +1,39 @@
+use std::process::Command;
+use wasmer_integration_tests_cli::{get_wasmer_path, C_ASSET_PATH};
+
+#[test]
+fn inspect_reports_abi_and_limits() -> anyhow::Result<()> {
+    let output = Command::new(get_wasmer_path())
+        .arg("inspect")
+        .arg(format!("{}/qjs.wasm", C_ASSET_PATH))
+        .output()?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stdout}\n{stderr}");
+    assert!(stdout.contains("ABI: wasi_snapshot_preview1"), "{stdout}");
+    assert!(stdout.contains("Required features:"), "{stdout}");
+    assert!(stdout.contains("Compiled size:"), "{stdout}");
+
+    Ok(())
+}
+
+#[test]
+fn inspect_json() -> anyhow::Result<()> {
+    let output = Command::new(get_wasmer_path())
+        .arg("inspect")
+        .arg("--json")
+        .arg("--skip-compiled-size")
+        .arg(format!("{}/qjs.wasm", C_ASSET_PATH))
+        .output()?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stdout}\n{stderr}");
+    assert!(stdout.trim_start().starts_with('{'), "{stdout}");
+    assert!(stdout.contains("\"abi\": [\n    \"wasi_snapshot_preview1\""), "{stdout}");
+    assert!(stdout.contains("\"kind\": \"memory\""), "{stdout}");
+    assert!(stdout.contains("\"compiled_sizes\": []"), "{stdout}");
+
+    Ok(())
+}
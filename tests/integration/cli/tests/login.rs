@@ -50,3 +50,36 @@ fn login_works() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn unknown_profiles_are_rejected() -> anyhow::Result<()> {
+    let wasmer_dir = tempfile::tempdir()?;
+
+    let output = Command::new(get_wasmer_path())
+        .arg("config")
+        .arg("set")
+        .arg("profile")
+        .arg("staging")
+        .env("WASMER_DIR", wasmer_dir.path())
+        .output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("there is no profile named \"staging\""),
+        "{stderr}"
+    );
+
+    let output = Command::new(get_wasmer_path())
+        .arg("whoami")
+        .arg("--profile=staging")
+        .env("WASMER_DIR", wasmer_dir.path())
+        .output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("wasmer login --profile staging"),
+        "{stderr}"
+    );
+
+    Ok(())
+}
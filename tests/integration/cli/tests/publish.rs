@@ -152,3 +152,54 @@ fn wasmer_init_publish() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn wasmer_package_build() -> anyhow::Result<()> {
+    let tempdir = tempfile::tempdir()?;
+    let path = tempdir.path();
+
+    std::fs::copy(create_exe_test_wasm_path(), path.join("qjs.wasm"))?;
+    std::fs::create_dir_all(path.join("data"))?;
+    std::fs::write(path.join("data").join("hello.txt"), "Hello, World!")?;
+    std::fs::write(
+        path.join("wasmer.toml"),
+        r#"
+[package]
+name = "ciuser/qjs"
+version = "0.1.0"
+description = "QuickJS"
+
+[[module]]
+name = "qjs"
+source = "qjs.wasm"
+abi = "wasi"
+
+[[command]]
+name = "qjs"
+module = "qjs"
+
+[fs]
+"/data" = "data"
+"#,
+    )?;
+
+    let output = std::process::Command::new(get_wasmer_path())
+        .arg("package")
+        .arg("build")
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stdout}\n{stderr}");
+    assert!(
+        stdout.contains("Built package `ciuser/qjs@0.1.0`"),
+        "{stdout}"
+    );
+
+    let webc = std::fs::read(path.join("ciuser-qjs-0.1.0.webc"))?;
+    assert!(webc.starts_with(b"\0webc"));
+
+    Ok(())
+}
@@ -301,6 +301,27 @@ fn run_wasi_works() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn run_snapshot_to_requires_asyncify() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(wasi_test_wasm_path())
+        .arg("--snapshot-to")
+        .arg(temp_dir.path().join("qjs.snapshot"))
+        .arg("--")
+        .arg("-e")
+        .arg("print(3 * (4 + 5))")
+        .output()?;
+
+    assert!(!output.status.success());
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.contains("asyncify"), "{stderr}");
+    assert!(!temp_dir.path().join("qjs.snapshot").exists());
+
+    Ok(())
+}
+
 /// TODO: on linux-musl, the packaging of libwasmer.a doesn't work properly
 /// Tracked in https://github.com/wasmerio/wasmer/issues/3271
 #[cfg(not(any(target_env = "musl", target_os = "windows")))]
@@ -871,3 +892,65 @@ fn test_wasmer_run_complex_url() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// A WASI script that exits with the total size of its arguments (including
+/// `argv[0]`), as reported by `args_sizes_get`.
+const ARGS_SIZE_SCRIPT: &str = r#"#!/usr/bin/env wasmer
+(module
+  (import "wasi_snapshot_preview1" "args_sizes_get"
+    (func $args_sizes_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  (func (export "_start")
+    (drop (call $args_sizes_get (i32.const 0) (i32.const 4)))
+    (call $proc_exit (i32.load (i32.const 4)))))
+"#;
+
+#[test]
+fn run_script_passes_arguments_through() -> anyhow::Result<()> {
+    let temp = tempfile::tempdir()?;
+    let script = temp.path().join("args-size");
+    std::fs::write(&script, ARGS_SIZE_SCRIPT)?;
+
+    // What the kernel runs for `./args-size a --flag -x`
+    let output = Command::new(get_wasmer_path())
+        .arg("--argv0=s")
+        .arg(&script)
+        .arg("a")
+        .arg("--flag")
+        .arg("-x")
+        .output()?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // "s\0a\0--flag\0-x\0"
+    assert_eq!(output.status.code(), Some(14), "{stderr}");
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn run_script_with_shebang() -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = tempfile::tempdir()?;
+    let script = temp.path().join("args-size");
+    let contents = ARGS_SIZE_SCRIPT.replace(
+        "/usr/bin/env wasmer",
+        &get_wasmer_path().display().to_string(),
+    );
+    std::fs::write(&script, contents)?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+
+    let output = Command::new(&script)
+        .arg("abc")
+        .current_dir(temp.path())
+        .output()?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // argv[0] is the path to the script, like for native scripts
+    let expected = script.display().to_string().len() + 1 + "abc".len() + 1;
+    assert_eq!(output.status.code(), Some(expected as i32), "{stderr}");
+
+    Ok(())
+}
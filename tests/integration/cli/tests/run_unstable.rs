@@ -29,6 +29,18 @@ fn wasmer_run_unstable() -> std::process::Command {
     cmd
 }
 
+fn wasmer_serve() -> std::process::Command {
+    let mut cmd = std::process::Command::new("cargo");
+    cmd.arg("run")
+        .arg("--quiet")
+        .arg("--package=wasmer-cli")
+        .arg("--features=singlepass,cranelift")
+        .arg("--")
+        .arg("serve");
+    cmd.env("RUST_LOG", RUST_LOG);
+    cmd
+}
+
 mod webc_on_disk {
     use super::*;
     use rand::Rng;
@@ -183,6 +195,45 @@ mod webc_on_disk {
                 "response generated method=GET uri=/path/to/file.txt status_code=200 OK",
             ));
     }
+
+    #[test]
+    #[cfg_attr(
+        all(target_env = "musl", target_os = "linux"),
+        ignore = "wasmer run-unstable segfaults on musl"
+    )]
+    fn serve_several_packages() {
+        let port = rand::thread_rng().gen_range(10_000_u16..u16::MAX);
+        let mut cmd = wasmer_serve();
+        cmd.arg(format!("--addr=127.0.0.1:{port}"))
+            .arg(format!("/first={}", fixtures::static_server().display()))
+            .arg(format!("/second={}", fixtures::static_server().display()));
+
+        let mut child = JoinableChild::spawn(cmd);
+        child.wait_for_stdout("Health checks at");
+
+        for route in ["first", "second"] {
+            let body = http_get(format!("http://127.0.0.1:{port}/{route}/")).unwrap();
+            assert!(body.contains("<title>Index of /</title>"), "{body}");
+        }
+
+        let err = http_get(format!("http://127.0.0.1:{port}/third/")).unwrap_err();
+        assert_eq!(err.status().unwrap(), reqwest::StatusCode::NOT_FOUND);
+
+        let health = http_get(format!("http://127.0.0.1:{port}/_wasmer/health")).unwrap();
+        assert!(health.contains("\"status\":\"ok\""), "{health}");
+
+        let metrics = http_get(format!("http://127.0.0.1:{port}/_wasmer/metrics")).unwrap();
+        assert!(
+            metrics.contains("wasmer_serve_requests_total{route=\"/first\"} 1"),
+            "{metrics}"
+        );
+        assert!(
+            metrics.contains("wasmer_serve_unrouted_requests_total 1"),
+            "{metrics}"
+        );
+
+        child.join();
+    }
 }
 
 mod wasm_on_disk {
@@ -0,0 +1,71 @@
+use std::path::Path;
+use std::process::{Command, Output};
+use wasmer_integration_tests_cli::get_wasmer_path;
+
+/// `(module (func (result v128) v128.const i32x4 0 0 0 0))`
+const SIMD_MODULE: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+    0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7b, // type section
+    0x03, 0x02, 0x01, 0x00, // function section
+    0x0a, 0x16, 0x01, 0x14, 0x00, 0xfd, 0x0c, // code section
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+    0x0b,
+];
+
+fn validate(module: &Path, args: &[&str]) -> anyhow::Result<Output> {
+    Ok(Command::new(get_wasmer_path())
+        .arg("validate")
+        .args(args)
+        .arg(module)
+        .output()?)
+}
+
+#[test]
+fn validate_reports_required_features() -> anyhow::Result<()> {
+    let temp = tempfile::tempdir()?;
+    let module = temp.path().join("simd.wasm");
+    std::fs::write(&module, SIMD_MODULE)?;
+
+    let output = validate(&module, &["--json"])?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stdout}\n{stderr}");
+
+    assert!(
+        stdout.contains("\"required_features\": [\n    \"simd\"\n  ]"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("\"feature\": \"simd\""), "{stdout}");
+    assert!(stdout.contains("\"function\": 0"), "{stdout}");
+
+    Ok(())
+}
+
+#[test]
+fn validate_fails_on_denied_features() -> anyhow::Result<()> {
+    let temp = tempfile::tempdir()?;
+    let module = temp.path().join("simd.wasm");
+    std::fs::write(&module, SIMD_MODULE)?;
+
+    let output = validate(&module, &["--deny-feature=simd"])?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(3), "{stderr}");
+    assert!(stderr.contains("simd (denied)"), "{stderr}");
+
+    Ok(())
+}
+
+#[test]
+fn validate_fails_on_invalid_modules() -> anyhow::Result<()> {
+    let temp = tempfile::tempdir()?;
+    let module = temp.path().join("truncated.wasm");
+    std::fs::write(&module, &SIMD_MODULE[..SIMD_MODULE.len() - 1])?;
+
+    let output = validate(&module, &[])?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(stderr.contains("Validation failed"), "{stderr}");
+
+    Ok(())
+}